@@ -0,0 +1,101 @@
+use consensus::crypto::bls::{
+    batch_verify_partials, par_partial_verify, par_threshold_verify, partial_verify, threshold_combine,
+    threshold_sign, threshold_verify, threshold_verify_batch, BLSPartialSignature, BLSPublicKey, BLSSecretKey,
+    BLSSignature, PublicKeyCache,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// Validator set sizes
+const VALIDATORS: [u64; 3] = [16, 64, 256];
+
+/// QCs verified together when catching up on a chain segment
+const QCS: usize = 32;
+
+fn validators(n: u64) -> (Vec<BLSSecretKey>, Vec<BLSPublicKey>) {
+    let secret_keys: Vec<_> = (0..n).map(BLSSecretKey::generate).collect();
+    let public_keys = secret_keys.iter().map(|sk| sk.public_key()).collect();
+    (secret_keys, public_keys)
+}
+
+/// Quorum size for `n` validators (2f+1)
+fn quorum(n: u64) -> usize {
+    (n - (n - 1) / 3) as usize
+}
+
+fn bench_votes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_votes");
+    let message = b"block hash and view";
+    for n in VALIDATORS {
+        let (secret_keys, public_keys) = validators(n);
+        let partials: Vec<BLSPartialSignature> = secret_keys.iter().map(|sk| threshold_sign(sk, message)).collect();
+
+        group.bench_with_input(BenchmarkId::new("sequential", n), &n, |b, _| {
+            b.iter(|| partials.iter().zip(&public_keys).all(|(p, pk)| partial_verify(message, p, pk)))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", n), &n, |b, _| {
+            b.iter(|| par_partial_verify(message, black_box(&partials), &public_keys))
+        });
+        group.bench_with_input(BenchmarkId::new("batch", n), &n, |b, _| {
+            b.iter(|| batch_verify_partials(message, black_box(&partials), &public_keys))
+        });
+    }
+    group.finish();
+}
+
+fn bench_qcs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_qcs");
+    for n in VALIDATORS {
+        let (secret_keys, public_keys) = validators(n);
+        let k = quorum(n);
+        let messages: Vec<Vec<u8>> = (0..QCS).map(|i| (i as u64).to_be_bytes().to_vec()).collect();
+        let signatures: Vec<BLSSignature> = messages
+            .iter()
+            .map(|m| {
+                let partials: Vec<_> = secret_keys[..k].iter().map(|sk| threshold_sign(sk, m)).collect();
+                threshold_combine(m, &partials, k).unwrap()
+            })
+            .collect();
+        let batch: Vec<(&[u8], &BLSSignature, &[BLSPublicKey])> = messages
+            .iter()
+            .zip(&signatures)
+            .map(|(m, s)| (m.as_slice(), s, &public_keys[..k]))
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("sequential", n), &n, |b, _| {
+            b.iter(|| batch.iter().all(|(m, s, pks)| threshold_verify(m, s, pks).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", n), &n, |b, _| {
+            b.iter(|| par_threshold_verify(black_box(&batch)))
+        });
+        group.bench_with_input(BenchmarkId::new("batch", n), &n, |b, _| {
+            b.iter(|| threshold_verify_batch(black_box(&batch)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_key_decoding(c: &mut Criterion) {
+    let (_, public_keys) = validators(64);
+    let encoded: Vec<(Vec<u8>, u64)> = public_keys.iter().map(|pk| (pk.to_bytes(), pk.validator_id())).collect();
+    let cache = PublicKeyCache::new(1024);
+
+    let mut group = c.benchmark_group("decode_public_keys_64");
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            for (bytes, id) in &encoded {
+                black_box(BLSPublicKey::from_bytes(bytes, *id).unwrap());
+            }
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            for (bytes, id) in &encoded {
+                black_box(cache.get_or_insert(bytes, *id).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_votes, bench_qcs, bench_key_decoding);
+criterion_main!(benches);
@@ -0,0 +1,236 @@
+// Portable checkpoint bundles
+//
+// A bundle packages a checkpoint for out-of-band distribution: a manifest
+// with the checkpoint metadata, the QC certifying its block, the validator
+// set that QC is checked against and the hash of every state chunk, signed
+// by the exporting operator; plus the state itself split into chunks. The
+// manifest is verified before any chunk is accepted, and each chunk against
+// its hash as it arrives, so bundles can be streamed chunk by chunk and a
+// corrupted or forged one is rejected before it is installed. Trust comes
+// from the QC: the importer supplies the validator set it already trusts.
+
+use super::{CheckpointError, Result};
+use crate::checkpoint::types::Checkpoint;
+use crate::crypto::{hash, partial_verify, threshold_sign, BLSPartialSignature, BLSPublicKey, BLSSecretKey, Hash};
+use crate::hotstuff::types::QuorumCertificate;
+use crate::light_client::ValidatorSet;
+use crate::storage::State;
+use serde::{Deserialize, Serialize};
+
+/// Domain separator for manifest signatures
+pub const BUNDLE_DOMAIN: &[u8] = b"openliquid/checkpoint-bundle/1";
+
+/// Default number of state entries per chunk
+pub const DEFAULT_CHUNK_ENTRIES: usize = 1024;
+
+/// Contiguous run of state entries, in key order
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StateChunk {
+    pub index: u32,
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl StateChunk {
+    /// Hash the manifest commits to
+    pub fn hash(&self) -> Hash {
+        hash(&bincode::serialize(self).expect("chunk serializes"))
+    }
+}
+
+/// Everything needed to check a bundle before its chunks arrive
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub height: u64,
+    pub view: u64,
+    pub block_hash: Hash,
+    /// Root of the checkpointed state's Merkle tree
+    pub state_root: Hash,
+    /// Checkpoint state hash (state root committed with the height)
+    pub state_hash: Hash,
+    /// QC certifying `block_hash`
+    pub qc: QuorumCertificate,
+    pub validator_epoch: u64,
+    /// Validator keys and weights the QC is checked against, by validator ID
+    pub validators: Vec<(BLSPublicKey, u64)>,
+    /// Hash of every chunk, by index
+    pub chunk_hashes: Vec<Hash>,
+    /// Exporting operator
+    pub signer: BLSPublicKey,
+    pub signature: BLSPartialSignature,
+}
+
+impl BundleManifest {
+    /// Bytes the operator signs: every field but the signature
+    fn signing_bytes(&self) -> Vec<u8> {
+        let fields = (
+            self.height,
+            self.view,
+            &self.block_hash,
+            &self.state_root,
+            &self.state_hash,
+            &self.qc,
+            self.validator_epoch,
+            &self.validators,
+            &self.chunk_hashes,
+            &self.signer,
+        );
+        let mut bytes = Vec::from(BUNDLE_DOMAIN);
+        bytes.extend(bincode::serialize(&fields).expect("manifest serializes"));
+        bytes
+    }
+
+    /// Validator set carried by the manifest
+    pub fn validator_set(&self) -> ValidatorSet {
+        ValidatorSet::weighted(self.validator_epoch, self.validators.iter().cloned())
+    }
+
+    /// Check the operator signature, that the validator set is the trusted
+    /// one and that a quorum of it certified the checkpoint block
+    pub fn verify(&self, trusted: &ValidatorSet) -> Result<()> {
+        let invalid = |reason: String| CheckpointError::InvalidBundle(reason);
+        if !partial_verify(&self.signing_bytes(), &self.signature, &self.signer) {
+            return Err(invalid("bad operator signature".to_string()));
+        }
+        if self.validator_set() != *trusted {
+            return Err(invalid(format!(
+                "validator set of epoch {} is not the trusted set of epoch {}",
+                self.validator_epoch, trusted.epoch
+            )));
+        }
+        if self.qc.block_hash != self.block_hash {
+            return Err(invalid("QC certifies another block".to_string()));
+        }
+        trusted.verify_qc(&self.qc).map_err(invalid)?;
+        if State::commitment(&self.state_root, self.height) != self.state_hash {
+            return Err(invalid("state hash does not commit to the state root".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Signed checkpoint with its state, for distribution as a single file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckpointBundle {
+    pub manifest: BundleManifest,
+    pub chunks: Vec<StateChunk>,
+}
+
+impl CheckpointBundle {
+    /// Package and sign `checkpoint`, certified by `qc` from `validators`
+    pub fn export(
+        checkpoint: &Checkpoint,
+        qc: QuorumCertificate,
+        validators: &ValidatorSet,
+        signer: &BLSSecretKey,
+        chunk_entries: usize,
+    ) -> Self {
+        let mut entries: Vec<(Vec<u8>, Vec<u8>)> = checkpoint
+            .state
+            .data
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        entries.sort_unstable();
+        let chunks: Vec<StateChunk> = entries
+            .chunks(chunk_entries.max(1))
+            .enumerate()
+            .map(|(index, entries)| StateChunk {
+                index: index as u32,
+                entries: entries.to_vec(),
+            })
+            .collect();
+
+        let mut manifest = BundleManifest {
+            height: checkpoint.height,
+            view: checkpoint.view,
+            block_hash: checkpoint.block_hash,
+            state_root: checkpoint.state.state_root(),
+            state_hash: checkpoint.state.root_hash,
+            qc,
+            validator_epoch: validators.epoch,
+            validators: validators.members(),
+            chunk_hashes: chunks.iter().map(StateChunk::hash).collect(),
+            signer: signer.public_key(),
+            // Replaced below; the signature is not part of the signed bytes
+            signature: threshold_sign(signer, &[]),
+        };
+        manifest.signature = threshold_sign(signer, &manifest.signing_bytes());
+        Self { manifest, chunks }
+    }
+
+    /// Encode for distribution
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        bincode::serialize(self).map_err(|e| CheckpointError::SerializationError(e.to_string()))
+    }
+
+    /// Decode a distributed bundle; verify it with `BundleImport`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize(bytes).map_err(|e| CheckpointError::SerializationError(e.to_string()))
+    }
+}
+
+/// Import of a bundle streamed chunk by chunk
+#[derive(Debug)]
+pub struct BundleImport {
+    manifest: BundleManifest,
+    state: State,
+    next_chunk: u32,
+}
+
+impl BundleImport {
+    /// Start importing after verifying the manifest against the trusted
+    /// validator set
+    pub fn new(manifest: BundleManifest, trusted: &ValidatorSet) -> Result<Self> {
+        manifest.verify(trusted)?;
+        let mut state = State::genesis();
+        state.height = manifest.height;
+        Ok(Self {
+            manifest,
+            state,
+            next_chunk: 0,
+        })
+    }
+
+    /// Accept the next chunk, which must match its manifest hash
+    pub fn add_chunk(&mut self, chunk: StateChunk) -> Result<()> {
+        let expected = self.manifest.chunk_hashes.get(chunk.index as usize);
+        if chunk.index != self.next_chunk || expected != Some(&chunk.hash()) {
+            return Err(CheckpointError::InvalidBundle(format!(
+                "chunk {} does not match the manifest (expected chunk {})",
+                chunk.index, self.next_chunk
+            )));
+        }
+        for (key, value) in chunk.entries {
+            self.state.set(key, value);
+        }
+        self.next_chunk += 1;
+        Ok(())
+    }
+
+    /// Whether every chunk has been accepted
+    pub fn is_complete(&self) -> bool {
+        self.next_chunk as usize == self.manifest.chunk_hashes.len()
+    }
+
+    /// Assemble the checkpoint once every chunk is in, checking the state
+    /// against the manifest's root
+    pub fn finish(mut self) -> Result<Checkpoint> {
+        if !self.is_complete() {
+            return Err(CheckpointError::InvalidBundle(format!(
+                "{} of {} chunks received",
+                self.next_chunk,
+                self.manifest.chunk_hashes.len()
+            )));
+        }
+        self.state.root_hash = self.state.compute_hash();
+        if self.state.state_root() != self.manifest.state_root || self.state.root_hash != self.manifest.state_hash {
+            return Err(CheckpointError::InvalidBundle("state root mismatch".to_string()));
+        }
+        Ok(Checkpoint::new(
+            self.manifest.height,
+            self.manifest.view,
+            self.state,
+            self.manifest.block_hash,
+        ))
+    }
+}
@@ -4,17 +4,23 @@
 /// - Fast bootstrap/recovery
 /// - State pruning
 /// - Crash recovery
+/// - Signed bundles for out-of-band bootstrap
 /// - Network sync
 
+pub mod bundle;
 pub mod types;
 
-use crate::crypto::Hash;
+use crate::crypto::{BLSSecretKey, Hash};
+use crate::hotstuff::types::QuorumCertificate;
+use crate::light_client::ValidatorSet;
 use crate::storage::{Storage, State, StorageError};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
+use tracing::warn;
 
+pub use bundle::{BundleImport, BundleManifest, CheckpointBundle, StateChunk};
 pub use types::{Checkpoint, CheckpointMetadata};
 
 /// Checkpoint errors
@@ -34,6 +40,9 @@ pub enum CheckpointError {
     
     #[error("Serialization error: {0}")]
     SerializationError(String),
+    
+    #[error("Invalid checkpoint bundle: {0}")]
+    InvalidBundle(String),
 }
 
 pub type Result<T> = std::result::Result<T, CheckpointError>;
@@ -77,13 +86,22 @@ pub struct CheckpointManager {
 }
 
 impl CheckpointManager {
-    /// Create a new checkpoint manager
+    /// Create a new checkpoint manager, rebuilding the index from the
+    /// checkpoints persisted in storage
     pub fn new(storage: Arc<Storage>, config: CheckpointConfig) -> Self {
+        let checkpoints: BTreeMap<u64, CheckpointMetadata> = match storage.load_checkpoints() {
+            Ok(checkpoints) => checkpoints.into_iter().map(|m| (m.height, m)).collect(),
+            Err(e) => {
+                warn!("Failed to load checkpoint index, starting empty: {}", e);
+                BTreeMap::new()
+            }
+        };
+        let last_checkpoint_height = checkpoints.keys().next_back().copied().unwrap_or(0);
         Self {
             storage,
             config,
-            checkpoints: Arc::new(RwLock::new(BTreeMap::new())),
-            last_checkpoint_height: Arc::new(RwLock::new(0)),
+            checkpoints: Arc::new(RwLock::new(checkpoints)),
+            last_checkpoint_height: Arc::new(RwLock::new(last_checkpoint_height)),
         }
     }
     
@@ -110,25 +128,8 @@ impl CheckpointManager {
             ));
         }
         
-        // Serialize and store
-        let _checkpoint_bytes = bincode::serialize(&checkpoint)
-            .map_err(|e| CheckpointError::SerializationError(e.to_string()))?;
-        
-        // Store in a special checkpoint column family (we'll use state storage for now)
-        // In production, you'd want a dedicated CF for checkpoints
-        self.storage.store_state(height, &checkpoint.state)?;
-        
-        // Update index
-        let metadata = CheckpointMetadata::from(&checkpoint);
-        self.checkpoints.write().await.insert(height, metadata);
-        
-        // Update last checkpoint height
-        let mut last_height = self.last_checkpoint_height.write().await;
-        *last_height = height;
-        drop(last_height);
-        
-        // Prune old checkpoints
-        self.prune_checkpoints().await?;
+        // Persist and index it, pruning old checkpoints
+        self.store_checkpoint(&checkpoint).await?;
         
         Ok(checkpoint)
     }
@@ -155,27 +156,52 @@ impl CheckpointManager {
             ));
         }
         
-        // Store state at checkpoint height
-        self.storage.store_state(checkpoint.height, &checkpoint.state)?;
-        
-        Ok(())
+        // Store and index it so it can be served to other nodes
+        self.store_checkpoint(checkpoint).await
+    }
+    
+    /// Export the checkpoint at `height` as a signed bundle, with `qc`
+    /// from `validators` certifying its block
+    pub async fn export_bundle(
+        &self,
+        height: u64,
+        qc: QuorumCertificate,
+        validators: &ValidatorSet,
+        signer: &BLSSecretKey,
+        chunk_entries: usize,
+    ) -> Result<CheckpointBundle> {
+        let checkpoint = self
+            .get_checkpoint(height)
+            .await?
+            .ok_or(CheckpointError::CheckpointNotFound(height))?;
+        Ok(CheckpointBundle::export(&checkpoint, qc, validators, signer, chunk_entries))
+    }
+    
+    /// Verify a bundle against the trusted validator set and restore its
+    /// checkpoint
+    pub async fn import_bundle(&self, bundle: CheckpointBundle, trusted: &ValidatorSet) -> Result<Checkpoint> {
+        let mut import = BundleImport::new(bundle.manifest, trusted)?;
+        for chunk in bundle.chunks {
+            import.add_chunk(chunk)?;
+        }
+        let checkpoint = import.finish()?;
+        self.restore_from_checkpoint(&checkpoint).await?;
+        Ok(checkpoint)
     }
     
     /// Get checkpoint at specific height
     pub async fn get_checkpoint(&self, height: u64) -> Result<Option<Checkpoint>> {
         // Try to load state
         if let Some(state) = self.storage.get_state(height)? {
-            // Try to get block
-            if let Some(block) = self.storage.get_latest_block()? {
-                if block.height >= height {
-                    let checkpoint = Checkpoint::new(
-                        height,
-                        block.view,
-                        state,
-                        block.hash(),
-                    );
-                    return Ok(Some(checkpoint));
-                }
+            // Pair it with the block at the same height
+            if let Some(block) = self.storage.get_block_by_height(height)? {
+                let checkpoint = Checkpoint::new(
+                    height,
+                    block.view,
+                    state,
+                    block.hash(),
+                );
+                return Ok(Some(checkpoint));
             }
         }
         
@@ -204,31 +230,37 @@ impl CheckpointManager {
         self.checkpoints.read().await.get(&height).cloned()
     }
     
-    /// Prune old checkpoints beyond max_checkpoints
-    async fn prune_checkpoints(&self) -> Result<()> {
+    /// Persist a checkpoint and add it to the index, pruning the oldest
+    /// beyond max_checkpoints in the same write
+    async fn store_checkpoint(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let metadata = CheckpointMetadata::from(checkpoint);
         let mut checkpoints = self.checkpoints.write().await;
         
-        while checkpoints.len() > self.config.max_checkpoints {
-            // Remove oldest checkpoint
-            if let Some((&height, _)) = checkpoints.iter().next() {
-                checkpoints.remove(&height);
-                // Note: We keep the state in storage, only remove from index
-            } else {
-                break;
-            }
+        // Oldest checkpoints to drop once this one is indexed
+        let mut heights: BTreeSet<u64> = checkpoints.keys().copied().collect();
+        heights.insert(checkpoint.height);
+        let excess = heights.len().saturating_sub(self.config.max_checkpoints);
+        let pruned: Vec<u64> = heights.into_iter().take(excess).collect();
+        
+        // Note: We keep pruned checkpoints' state in storage, only drop their metadata
+        self.storage.store_checkpoint(&metadata, &checkpoint.state, &pruned)?;
+        
+        checkpoints.insert(checkpoint.height, metadata);
+        for height in &pruned {
+            checkpoints.remove(height);
         }
+        let mut last_height = self.last_checkpoint_height.write().await;
+        *last_height = (*last_height).max(checkpoint.height);
         
         Ok(())
     }
     
     /// Delete checkpoint at height
     pub async fn delete_checkpoint(&self, height: u64) -> Result<()> {
-        // Remove from index
+        // Remove metadata and state in one write, then from the index
+        self.storage.delete_checkpoint(height)?;
         self.checkpoints.write().await.remove(&height);
         
-        // Remove state from storage
-        self.storage.delete_state(height)?;
-        
         Ok(())
     }
     
@@ -363,6 +395,29 @@ mod tests {
         assert_eq!(stats.newest_height, Some(50));
     }
     
+    #[tokio::test]
+    async fn test_index_rebuilt_from_storage() {
+        let storage = Arc::new(Storage::new_temp().unwrap());
+        let config = CheckpointConfig { max_checkpoints: 2, ..Default::default() };
+        let manager = CheckpointManager::new(storage.clone(), config.clone());
+        
+        for i in 1..=4 {
+            let mut state = State::genesis();
+            state.height = i * 10;
+            state.root_hash = state.compute_hash();
+            manager.create_checkpoint(i * 10, i * 10, state, Hash::genesis()).await.unwrap();
+        }
+        manager.delete_checkpoint(40).await.unwrap();
+        drop(manager);
+        
+        // Pruned and deleted checkpoints stay gone after a restart
+        let restarted = CheckpointManager::new(storage.clone(), config);
+        let heights: Vec<u64> = restarted.list_checkpoints().await.iter().map(|m| m.height).collect();
+        assert_eq!(heights, vec![30]);
+        assert_eq!(restarted.stats().await.last_checkpoint_height, 30);
+        assert!(storage.get_state(40).unwrap().is_none());
+    }
+    
     #[tokio::test]
     async fn test_list_checkpoints() {
         let storage = Arc::new(Storage::new_temp().unwrap());
@@ -421,5 +476,56 @@ mod tests {
         
         assert!(result.is_err());
     }
+    
+    #[tokio::test]
+    async fn test_bundle_export_and_streamed_import() {
+        use crate::crypto::{threshold_combine, threshold_sign, BLSKeyPair};
+        use crate::hotstuff::types::{vote_message, Block, MessageType};
+        
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let validators = ValidatorSet::new(0, keys.iter().map(|k| k.public_key.clone()));
+        let block = Block::new(Hash::genesis(), 7, 9, None, vec![], keys[0].public_key.clone());
+        let data = vote_message(&MessageType::Commit, &block.hash(), block.view);
+        let partials: Vec<_> = keys[..3].iter().map(|k| threshold_sign(&k.secret_key, &data)).collect();
+        let qc = QuorumCertificate::new(MessageType::Commit, block.hash(), block.view, threshold_combine(&data, &partials, 3).unwrap())
+            .with_signers(vec![0, 1, 2]);
+        
+        let storage = Arc::new(Storage::new_temp().unwrap());
+        storage.store_block(&block).unwrap();
+        let manager = CheckpointManager::new_default(storage);
+        let mut state = State::genesis();
+        state.height = 7;
+        for i in 0..5u8 {
+            state.set(vec![i], vec![i; 4]);
+        }
+        state.root_hash = state.compute_hash();
+        manager.create_checkpoint(7, 9, state, block.hash()).await.unwrap();
+        
+        let operator = BLSKeyPair::with_id(99);
+        let bundle = manager.export_bundle(7, qc, &validators, &operator.secret_key, 2).await.unwrap();
+        assert_eq!(bundle.chunks.len(), 3);
+        let bundle = CheckpointBundle::from_bytes(&bundle.to_bytes().unwrap()).unwrap();
+        
+        // Manifests from another validator set or with edited fields are refused
+        let other = ValidatorSet::new(0, (4..8).map(|id| BLSKeyPair::with_id(id).public_key));
+        assert!(BundleImport::new(bundle.manifest.clone(), &other).is_err());
+        let mut edited = bundle.manifest.clone();
+        edited.height = 8;
+        assert!(BundleImport::new(edited, &validators).is_err());
+        
+        // Chunks are checked as they stream in
+        let mut import = BundleImport::new(bundle.manifest.clone(), &validators).unwrap();
+        let mut tampered = bundle.chunks[0].clone();
+        tampered.entries[0].1 = b"forged".to_vec();
+        assert!(import.add_chunk(tampered).is_err());
+        assert!(import.add_chunk(bundle.chunks[1].clone()).is_err());
+        import.add_chunk(bundle.chunks[0].clone()).unwrap();
+        assert!(!import.is_complete());
+        
+        let target = CheckpointManager::new_default(Arc::new(Storage::new_temp().unwrap()));
+        let checkpoint = target.import_bundle(bundle, &validators).await.unwrap();
+        assert_eq!((checkpoint.height, checkpoint.view, checkpoint.block_hash), (7, 9, block.hash()));
+        assert_eq!(checkpoint.state.get(&[4]), Some(&vec![4; 4]));
+        assert_eq!(target.stats().await.last_checkpoint_height, 7);
+    }
 }
-
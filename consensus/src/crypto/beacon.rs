@@ -0,0 +1,52 @@
+//! Per-block randomness beacon
+//!
+//! Each block's randomness is derived from the BLS threshold signature in its
+//! justify QC: `H(domain || block_hash || signature)`. BLS signatures are
+//! deterministic, so the output is fixed once a quorum has signed and no
+//! single validator can predict it before the QC forms or grind it afterwards.
+//!
+//! Note: QCs aggregate the first k partial signatures received rather than a
+//! true threshold-shared key, so the leader forming the QC can choose among
+//! signer subsets. Consumers needing stronger guarantees should mix in
+//! randomness from several consecutive blocks.
+
+use super::bls::BLSSignature;
+use super::hash::{hash_data, Hash};
+
+/// Domain separation tag for beacon derivation
+pub const BEACON_DOMAIN: &[u8] = b"openliquid/beacon/v1";
+
+/// Derive beacon randomness from a threshold signature over `block_hash`
+pub fn derive_randomness(block_hash: &Hash, signature: &BLSSignature) -> Hash {
+    let mut data = Vec::with_capacity(BEACON_DOMAIN.len() + 32 + 48);
+    data.extend_from_slice(BEACON_DOMAIN);
+    data.extend_from_slice(block_hash.as_bytes());
+    data.extend_from_slice(&signature.to_bytes());
+    hash_data(&data)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::bls::{threshold_combine, threshold_sign, BLSSecretKey};
+
+    #[test]
+    fn test_randomness_depends_on_block_and_signature() {
+        let validators: Vec<_> = (0..4).map(BLSSecretKey::generate).collect();
+        let sign = |msg: &[u8]| {
+            let partials: Vec<_> = validators.iter().map(|v| threshold_sign(v, msg)).collect();
+            threshold_combine(msg, &partials, 3).unwrap()
+        };
+
+        let hash_a = Hash::new([1u8; 32]);
+        let hash_b = Hash::new([2u8; 32]);
+        let sig_a = sign(hash_a.as_bytes());
+        let sig_b = sign(hash_b.as_bytes());
+
+        // Deterministic for the same inputs
+        assert_eq!(derive_randomness(&hash_a, &sig_a), derive_randomness(&hash_a, &sig_a));
+        assert_ne!(derive_randomness(&hash_a, &sig_a), derive_randomness(&hash_b, &sig_b));
+        assert_ne!(derive_randomness(&hash_a, &sig_a), derive_randomness(&hash_a, &sig_b));
+    }
+}
@@ -10,6 +10,9 @@ use blst::min_pk::{
     PublicKey as BlstPublicKey, SecretKey as BlstSecretKey, 
     Signature as BlstSignature, AggregateSignature, AggregatePublicKey
 };
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
 use thiserror::Error;
 
 // Note: BLS12-381 signatures in blst are 96 bytes (uncompressed)
@@ -227,11 +230,7 @@ pub fn threshold_verify(
         return Err(BLSError::InvalidThreshold);
     }
 
-    // Aggregate public keys
-    let pks: Vec<&BlstPublicKey> = public_keys.iter().map(|pk| &pk.inner).collect();
-    let aggregated_pk = AggregatePublicKey::aggregate(&pks, false)
-        .map_err(|_| BLSError::InvalidKey)?
-        .to_public_key();
+    let aggregated_pk = aggregate_public_keys(public_keys)?;
 
     // Verify aggregated signature
     let result = signature.inner.verify(true, message, &[], &[], &aggregated_pk, true);
@@ -239,6 +238,208 @@ pub fn threshold_verify(
     Ok(result == blst::BLST_ERROR::BLST_SUCCESS)
 }
 
+/// Verify one validator's partial signature
+/// 
+/// # Returns
+/// true if `partial` was produced by `public_key`'s validator over `message`
+pub fn partial_verify(
+    message: &[u8],
+    partial: &BLSPartialSignature,
+    public_key: &BLSPublicKey,
+) -> bool {
+    partial.validator_id == public_key.validator_id
+        && partial.signature.inner.verify(true, message, &[], &[], &public_key.inner, true)
+            == blst::BLST_ERROR::BLST_SUCCESS
+}
+
+/// Verify many partial signatures over the same message at once
+/// 
+/// Each signature is weighted by a random 64-bit scalar, so invalid
+/// signatures cannot cancel each other out in the check.
+/// 
+/// # Returns
+/// true only if every partial signature is valid; on false, fall back to
+/// `partial_verify` to find the invalid ones
+/// 
+/// # Complexity
+/// One multi-pairing instead of one pairing check per signature
+pub fn batch_verify_partials(
+    message: &[u8],
+    partials: &[BLSPartialSignature],
+    public_keys: &[BLSPublicKey],
+) -> bool {
+    if partials.is_empty() || partials.len() != public_keys.len() {
+        return false;
+    }
+    if partials.iter().zip(public_keys).any(|(p, pk)| p.validator_id != pk.validator_id) {
+        return false;
+    }
+
+    let rands = random_scalars(partials.len());
+    let msgs = vec![message; partials.len()];
+    let pks: Vec<&BlstPublicKey> = public_keys.iter().map(|pk| &pk.inner).collect();
+    let sigs: Vec<&BlstSignature> = partials.iter().map(|p| &p.signature.inner).collect();
+
+    BlstSignature::verify_multiple_aggregate_signatures(&msgs, &[], &pks, true, &sigs, true, &rands, 64)
+        == blst::BLST_ERROR::BLST_SUCCESS
+}
+
+/// Verify an aggregate of signatures over different messages
+/// 
+/// # Arguments
+/// * `messages` - One message per signer
+/// * `signature` - Aggregate of the signers' partial signatures
+/// * `public_keys` - Signer public keys, in the same order as `messages`
+/// 
+/// # Returns
+/// true if each key signed its message and the signatures aggregate to
+/// `signature`
+pub fn aggregate_verify(
+    messages: &[&[u8]],
+    signature: &BLSSignature,
+    public_keys: &[BLSPublicKey],
+) -> bool {
+    if messages.is_empty() || messages.len() != public_keys.len() {
+        return false;
+    }
+    let pks: Vec<&BlstPublicKey> = public_keys.iter().map(|pk| &pk.inner).collect();
+    signature.inner.aggregate_verify(true, messages, &[], &pks, true) == blst::BLST_ERROR::BLST_SUCCESS
+}
+
+/// Verify many threshold signatures (e.g. the QCs of a chain segment) at once
+/// 
+/// # Arguments
+/// * `batch` - Message, combined signature and signer public keys of
+///   each threshold signature
+/// 
+/// # Returns
+/// true only if every signature is valid; on false, fall back to
+/// `par_threshold_verify` to find the invalid ones
+/// 
+/// # Complexity
+/// Public keys are aggregated in parallel, then all signatures are
+/// checked with one randomized multi-pairing (as in
+/// `batch_verify_partials`)
+pub fn threshold_verify_batch(batch: &[(&[u8], &BLSSignature, &[BLSPublicKey])]) -> Result<bool, BLSError> {
+    if batch.is_empty() {
+        return Err(BLSError::InvalidThreshold);
+    }
+
+    let aggregated_pks = batch
+        .par_iter()
+        .map(|(_, _, public_keys)| aggregate_public_keys(public_keys))
+        .collect::<Result<Vec<_>, _>>()?;
+    let rands = random_scalars(batch.len());
+    let msgs: Vec<&[u8]> = batch.iter().map(|(message, _, _)| *message).collect();
+    let pks: Vec<&BlstPublicKey> = aggregated_pks.iter().collect();
+    let sigs: Vec<&BlstSignature> = batch.iter().map(|(_, signature, _)| &signature.inner).collect();
+
+    Ok(BlstSignature::verify_multiple_aggregate_signatures(&msgs, &[], &pks, true, &sigs, true, &rands, 64)
+        == blst::BLST_ERROR::BLST_SUCCESS)
+}
+
+/// Verify each threshold signature on its own, across the rayon thread pool
+/// 
+/// # Returns
+/// `threshold_verify`'s result for each entry of `batch`, in order
+pub fn par_threshold_verify(batch: &[(&[u8], &BLSSignature, &[BLSPublicKey])]) -> Vec<Result<bool, BLSError>> {
+    batch
+        .par_iter()
+        .map(|(message, signature, public_keys)| threshold_verify(message, signature, public_keys))
+        .collect()
+}
+
+/// Verify each partial signature on its own, across the rayon thread pool
+/// 
+/// # Returns
+/// `partial_verify`'s result for each partial, paired with the public key
+/// at the same position
+pub fn par_partial_verify(
+    message: &[u8],
+    partials: &[BLSPartialSignature],
+    public_keys: &[BLSPublicKey],
+) -> Vec<bool> {
+    partials
+        .par_iter()
+        .zip(public_keys.par_iter())
+        .map(|(partial, public_key)| partial_verify(message, partial, public_key))
+        .collect()
+}
+
+fn aggregate_public_keys(public_keys: &[BLSPublicKey]) -> Result<BlstPublicKey, BLSError> {
+    if public_keys.is_empty() {
+        return Err(BLSError::InvalidThreshold);
+    }
+    let pks: Vec<&BlstPublicKey> = public_keys.iter().map(|pk| &pk.inner).collect();
+    Ok(AggregatePublicKey::aggregate(&pks, false)
+        .map_err(|_| BLSError::InvalidKey)?
+        .to_public_key())
+}
+
+/// Random 64-bit scalars weighting each signature in a batch check
+fn random_scalars(n: usize) -> Vec<blst::blst_scalar> {
+    (0..n)
+        .map(|_| {
+            let mut b = [0u8; 32];
+            // Non-zero so every signature contributes
+            b[..8].copy_from_slice(&rand::Rng::gen_range(&mut rand::thread_rng(), 1..=u64::MAX).to_le_bytes());
+            blst::blst_scalar { b }
+        })
+        .collect()
+}
+
+/// Default capacity of the process-wide public key cache
+pub const PUBLIC_KEY_CACHE_CAPACITY: usize = 4096;
+
+/// Cache of deserialized public keys, keyed by encoding and validator ID
+/// 
+/// The same validator keys arrive with every block and vote; a lookup is
+/// much cheaper than decoding and checking the curve point again. When
+/// full, the cache is cleared rather than tracking recency.
+pub struct PublicKeyCache {
+    keys: RwLock<HashMap<(Vec<u8>, u64), BLSPublicKey>>,
+    capacity: usize,
+}
+
+impl PublicKeyCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            keys: RwLock::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// Deserialize a public key, reusing an earlier decoding of `bytes`
+    pub fn get_or_insert(&self, bytes: &[u8], validator_id: u64) -> Result<BLSPublicKey, BLSError> {
+        let key = (bytes.to_vec(), validator_id);
+        if let Some(public_key) = self.keys.read().unwrap().get(&key) {
+            return Ok(public_key.clone());
+        }
+
+        let public_key = BLSPublicKey::from_bytes(bytes, validator_id)?;
+        let mut keys = self.keys.write().unwrap();
+        if keys.len() >= self.capacity {
+            keys.clear();
+        }
+        keys.insert(key, public_key.clone());
+        Ok(public_key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Process-wide cache used when deserializing `BLSPublicKey`s
+pub fn public_key_cache() -> &'static PublicKeyCache {
+    static CACHE: OnceLock<PublicKeyCache> = OnceLock::new();
+    CACHE.get_or_init(|| PublicKeyCache::new(PUBLIC_KEY_CACHE_CAPACITY))
+}
+
 // Note: Serde implementations removed for simplicity.
 // Use to_bytes() / from_bytes() for serialization if needed.
 
@@ -246,6 +447,98 @@ pub fn threshold_verify(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_bls_partial_verification() {
+        let validators = (0..4)
+            .map(|i| BLSSecretKey::generate(i))
+            .collect::<Vec<_>>();
+        let public_keys: Vec<_> = validators.iter().map(|v| v.public_key()).collect();
+        let message = b"block hash and view";
+
+        let mut partials: Vec<_> = validators.iter().map(|v| threshold_sign(v, message)).collect();
+        assert!(partial_verify(message, &partials[0], &public_keys[0]));
+        assert!(!partial_verify(message, &partials[0], &public_keys[1]));
+        assert!(batch_verify_partials(message, &partials, &public_keys));
+
+        // One validator signs a different message
+        partials[2] = threshold_sign(&validators[2], b"other block");
+        assert!(!batch_verify_partials(message, &partials, &public_keys));
+        assert!(!partial_verify(message, &partials[2], &public_keys[2]));
+        assert!(partial_verify(message, &partials[3], &public_keys[3]));
+    }
+
+    fn qc_batch<'a>(
+        messages: &'a [Vec<u8>],
+        signatures: &'a [BLSSignature],
+        public_keys: &'a [BLSPublicKey],
+    ) -> Vec<(&'a [u8], &'a BLSSignature, &'a [BLSPublicKey])> {
+        messages.iter().zip(signatures).map(|(m, s)| (m.as_slice(), s, public_keys)).collect()
+    }
+
+    #[test]
+    fn test_threshold_verify_batch() {
+        let validators: Vec<_> = (0..7).map(BLSSecretKey::generate).collect();
+        let public_keys: Vec<_> = validators.iter().map(|v| v.public_key()).collect();
+        let messages: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 32]).collect();
+        let mut signatures: Vec<_> = messages
+            .iter()
+            .map(|m| {
+                let partials: Vec<_> = validators[..5].iter().map(|v| threshold_sign(v, m)).collect();
+                threshold_combine(m, &partials, 5).unwrap()
+            })
+            .collect();
+        let signers = &public_keys[..5];
+
+        assert!(threshold_verify_batch(&qc_batch(&messages, &signatures, signers)).unwrap());
+        assert!(par_threshold_verify(&qc_batch(&messages, &signatures, signers)).into_iter().all(|r| r.unwrap()));
+
+        // Swap two QCs: each is valid, but not for its message
+        signatures.swap(1, 2);
+        assert!(!threshold_verify_batch(&qc_batch(&messages, &signatures, signers)).unwrap());
+        let results: Vec<bool> = par_threshold_verify(&qc_batch(&messages, &signatures, signers))
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(results, vec![true, false, false, true]);
+
+        assert!(matches!(threshold_verify_batch(&[]), Err(BLSError::InvalidThreshold)));
+    }
+
+    #[test]
+    fn test_par_partial_verify() {
+        let validators: Vec<_> = (0..8).map(BLSSecretKey::generate).collect();
+        let public_keys: Vec<_> = validators.iter().map(|v| v.public_key()).collect();
+        let message = b"block hash and view";
+        let mut partials: Vec<_> = validators.iter().map(|v| threshold_sign(v, message)).collect();
+        partials[5] = threshold_sign(&validators[5], b"other block");
+
+        let results = par_partial_verify(message, &partials, &public_keys);
+        assert_eq!(results.iter().filter(|ok| !**ok).count(), 1);
+        assert!(!results[5]);
+    }
+
+    #[test]
+    fn test_public_key_cache() {
+        let cache = PublicKeyCache::new(2);
+        let keys: Vec<_> = (0..3).map(|i| BLSSecretKey::generate(i).public_key()).collect();
+
+        assert_eq!(cache.get_or_insert(&keys[0].to_bytes(), 0).unwrap(), keys[0]);
+        assert_eq!(cache.get_or_insert(&keys[0].to_bytes(), 0).unwrap(), keys[0]);
+        assert_eq!(cache.len(), 1);
+        // Same encoding under another validator ID is a separate entry
+        assert_eq!(cache.get_or_insert(&keys[0].to_bytes(), 9).unwrap().validator_id(), 9);
+        assert_eq!(cache.len(), 2);
+
+        // Full: cleared before inserting
+        cache.get_or_insert(&keys[2].to_bytes(), 2).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(matches!(cache.get_or_insert(&[1, 2, 3], 0), Err(BLSError::InvalidKey)));
+
+        // Deserialization goes through the process-wide cache
+        let decoded: BLSPublicKey = bincode::deserialize(&bincode::serialize(&keys[1]).unwrap()).unwrap();
+        assert_eq!(decoded, keys[1]);
+    }
+
     /// TEST_SPEC 1.1.1: BLS threshold signature generation
     #[test]
     fn test_bls_threshold_signature_generation() {
@@ -437,7 +730,7 @@ impl<'de> serde::Deserialize<'de> for BLSPublicKey {
                     .ok_or_else(|| de::Error::invalid_length(0, &self))?;
                 let validator_id: u64 = seq.next_element()?
                     .ok_or_else(|| de::Error::invalid_length(1, &self))?;
-                BLSPublicKey::from_bytes(&bytes, validator_id).map_err(de::Error::custom)
+                public_key_cache().get_or_insert(&bytes, validator_id).map_err(de::Error::custom)
             }
         }
 
@@ -21,7 +21,7 @@ pub enum HashError {
 pub struct Hash([u8; HASH_SIZE]);
 
 impl Hash {
-    pub fn new(bytes: [u8; HASH_SIZE]) -> Self {
+    pub const fn new(bytes: [u8; HASH_SIZE]) -> Self {
         Self(bytes)
     }
 
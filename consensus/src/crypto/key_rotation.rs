@@ -0,0 +1,103 @@
+//! Validator BLS key rotation
+//!
+//! A validator replaces its key with a rotation transaction carrying the new
+//! public key, signed by the current key (authorizing the change) and by the
+//! new key (proving possession, so nobody can register a key they cannot
+//! sign with). Once committed, the rotation takes effect at the start of
+//! `effective_epoch`; validator sets of earlier epochs keep the old key, so
+//! QCs signed under it still verify at their heights but new votes under it
+//! are rejected.
+
+use super::bls::{partial_verify, threshold_sign, BLSPartialSignature, BLSPublicKey, BLSSecretKey};
+use serde::{Deserialize, Serialize};
+
+/// Prefix marking a transaction as a validator key rotation
+pub const KEY_ROTATION_PREFIX: &[u8] = b"openliquid/key-rotation/1";
+
+/// Signed replacement of a validator's BLS key
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub validator_id: u64,
+    /// First epoch signed with the new key
+    pub effective_epoch: u64,
+    pub new_key: BLSPublicKey,
+    /// Signature by the current key
+    pub authorization: BLSPartialSignature,
+    /// Signature by the new key
+    pub proof_of_possession: BLSPartialSignature,
+}
+
+impl KeyRotation {
+    /// Rotate `current`'s validator to `new` from `effective_epoch` on.
+    /// Returns None if the keys belong to different validators.
+    pub fn new(current: &BLSSecretKey, new: &BLSSecretKey, effective_epoch: u64) -> Option<Self> {
+        if current.validator_id() != new.validator_id() {
+            return None;
+        }
+        let new_key = new.public_key();
+        let message = Self::signing_message(current.validator_id(), effective_epoch, &new_key);
+        Some(Self {
+            validator_id: current.validator_id(),
+            effective_epoch,
+            authorization: threshold_sign(current, &message),
+            proof_of_possession: threshold_sign(new, &message),
+            new_key,
+        })
+    }
+
+    /// Check both signatures, with `current_key` the key being replaced
+    pub fn verify(&self, current_key: &BLSPublicKey) -> bool {
+        if current_key.validator_id() != self.validator_id || self.new_key.validator_id() != self.validator_id {
+            return false;
+        }
+        let message = Self::signing_message(self.validator_id, self.effective_epoch, &self.new_key);
+        partial_verify(&message, &self.authorization, current_key)
+            && partial_verify(&message, &self.proof_of_possession, &self.new_key)
+    }
+
+    /// Encode as a key rotation transaction
+    pub fn to_transaction(&self) -> Vec<u8> {
+        let mut tx = Vec::from(KEY_ROTATION_PREFIX);
+        tx.extend(bincode::serialize(self).expect("key rotation serializes"));
+        tx
+    }
+
+    /// Decode a key rotation transaction
+    pub fn from_transaction(tx: &[u8]) -> Option<Self> {
+        bincode::deserialize(tx.strip_prefix(KEY_ROTATION_PREFIX)?).ok()
+    }
+
+    fn signing_message(validator_id: u64, effective_epoch: u64, new_key: &BLSPublicKey) -> Vec<u8> {
+        let mut message = Vec::from(KEY_ROTATION_PREFIX);
+        message.extend_from_slice(&validator_id.to_be_bytes());
+        message.extend_from_slice(&effective_epoch.to_be_bytes());
+        message.extend_from_slice(&new_key.to_bytes());
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_needs_both_keys() {
+        let current = BLSSecretKey::generate(3);
+        let new = BLSSecretKey::generate(3);
+        let rotation = KeyRotation::new(&current, &new, 5).unwrap();
+        assert!(rotation.verify(&current.public_key()));
+        assert_eq!(KeyRotation::from_transaction(&rotation.to_transaction()), Some(rotation.clone()));
+
+        // Only the current key can authorize, and the epoch is signed over
+        assert!(!rotation.verify(&new.public_key()));
+        let mut replayed = rotation.clone();
+        replayed.effective_epoch = 6;
+        assert!(!replayed.verify(&current.public_key()));
+
+        // A key the sender cannot sign with is refused
+        let mut stolen = rotation;
+        stolen.new_key = BLSSecretKey::generate(3).public_key();
+        assert!(!stolen.verify(&current.public_key()));
+        assert!(KeyRotation::new(&current, &BLSSecretKey::generate(4), 5).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+//! Binary Merkle trees over ordered leaves
+//!
+//! Leaves and internal nodes are hashed under distinct prefixes, so a leaf
+//! can never be passed off as a node. Levels are built pairwise and an odd
+//! node out is promoted to the next level unchanged rather than duplicated,
+//! so no two leaf lists share a root. Proofs are checked against the leaf
+//! count, which fixes the tree's shape and each leaf's position.
+
+use super::hash::{hash_data, Hash};
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+fn leaf_hash(leaf: &[u8]) -> Hash {
+    let mut data = Vec::with_capacity(1 + leaf.len());
+    data.push(LEAF_PREFIX);
+    data.extend_from_slice(leaf);
+    hash_data(&data)
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut data = Vec::with_capacity(1 + 2 * 32);
+    data.push(NODE_PREFIX);
+    data.extend_from_slice(left.as_bytes());
+    data.extend_from_slice(right.as_bytes());
+    hash_data(&data)
+}
+
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Root of the tree over `leaves`; all zeros when there are none
+pub fn merkle_root<T: AsRef<[u8]>>(leaves: &[T]) -> Hash {
+    let mut level: Vec<Hash> = leaves.iter().map(|leaf| leaf_hash(leaf.as_ref())).collect();
+    if level.is_empty() {
+        return Hash::genesis();
+    }
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Proof that a leaf sits at `index` in a tree
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub index: u64,
+    /// Sibling hashes from the leaf up, skipping levels where the node
+    /// was promoted
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Build the proof for `leaves[index]`
+    pub fn new<T: AsRef<[u8]>>(leaves: &[T], index: usize) -> Option<Self> {
+        if index >= leaves.len() {
+            return None;
+        }
+        let mut level: Vec<Hash> = leaves.iter().map(|leaf| leaf_hash(leaf.as_ref())).collect();
+        let mut position = index;
+        let mut siblings = Vec::new();
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            level = next_level(&level);
+            position /= 2;
+        }
+        Some(Self {
+            index: index as u64,
+            siblings,
+        })
+    }
+
+    /// Check that `leaf` is leaf `index` of the `leaf_count`-leaf tree
+    /// with `root`
+    pub fn verify(&self, root: &Hash, leaf: &[u8], leaf_count: u64) -> bool {
+        if self.index >= leaf_count {
+            return false;
+        }
+        let mut current = leaf_hash(leaf);
+        let mut siblings = self.siblings.iter();
+        let (mut position, mut width) = (self.index, leaf_count);
+        while width > 1 {
+            if position % 2 == 1 {
+                let Some(sibling) = siblings.next() else { return false };
+                current = node_hash(sibling, &current);
+            } else if position + 1 < width {
+                let Some(sibling) = siblings.next() else { return false };
+                current = node_hash(&current, sibling);
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+        siblings.next().is_none() && current == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proofs_for_every_leaf_and_tree_size() {
+        for count in 1..=9u8 {
+            let leaves: Vec<Vec<u8>> = (0..count).map(|i| vec![i]).collect();
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = MerkleProof::new(&leaves, index).unwrap();
+                assert!(proof.verify(&root, leaf, count as u64));
+                assert!(!proof.verify(&root, b"other", count as u64));
+                // The index fixes the leaf's position
+                if count > 1 {
+                    let moved = MerkleProof { index: (index as u64 + 1) % count as u64, ..proof.clone() };
+                    assert!(!moved.verify(&root, leaf, count as u64));
+                }
+            }
+            assert!(MerkleProof::new(&leaves, count as usize).is_none());
+        }
+
+        // Promoting rather than duplicating the odd node keeps roots distinct
+        assert_ne!(merkle_root(&[b"a", b"b", b"c"]), merkle_root(&[b"a", b"b", b"c", b"c"]));
+        assert_eq!(merkle_root::<Vec<u8>>(&[]), Hash::genesis());
+    }
+}
@@ -3,17 +3,28 @@
 /// Implements:
 /// - BLS threshold signatures (k-of-n, constant-size QCs)
 /// - ECDSA signatures for transactions
+/// - Per-block randomness beacon from QC signatures
+/// - Signed validator key rotations
+/// - Merkle trees and inclusion proofs
 /// - Hash functions (SHA-256 / BLAKE3)
 
 pub mod bls;
 pub mod hash;
 pub mod ecdsa;
+pub mod beacon;
+pub mod merkle;
+pub mod key_rotation;
 
 pub use bls::{
     BLSSecretKey, BLSPublicKey, BLSSignature, BLSPartialSignature, BLSKeyPair,
-    threshold_sign, threshold_combine, threshold_verify,
+    threshold_sign, threshold_combine, threshold_verify, partial_verify, batch_verify_partials,
+    aggregate_verify, threshold_verify_batch, par_threshold_verify, par_partial_verify,
+    PublicKeyCache, public_key_cache,
 };
 pub use hash::{Hash, hash_data, HashFunction};
+pub use beacon::{derive_randomness, BEACON_DOMAIN};
+pub use merkle::{merkle_root, MerkleProof};
+pub use key_rotation::{KeyRotation, KEY_ROTATION_PREFIX};
 pub use ecdsa::{
     ECDSASecretKey, ECDSAPublicKey, ECDSASignature,
     sign as ecdsa_sign, verify as ecdsa_verify
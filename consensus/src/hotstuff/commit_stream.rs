@@ -0,0 +1,63 @@
+//! Stream of committed blocks
+//!
+//! The engine records the QC committing each block in storage and then
+//! publishes it on a bounded broadcast channel. Subscribers receive commits
+//! in height order. The engine never waits for them: a subscriber that
+//! falls more than the channel's capacity behind reads the commits it
+//! missed from storage instead, which also lets a new subscriber start from
+//! any earlier height.
+
+use crate::hotstuff::types::{Block, QuorumCertificate};
+use crate::storage::{Result, Storage};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Commits buffered for live subscribers before they fall back to storage
+pub const DEFAULT_COMMIT_BUFFER: usize = 1024;
+
+/// A committed block with the QC certifying it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommittedBlock {
+    pub block: Block,
+    pub qc: QuorumCertificate,
+}
+
+/// Committed blocks in height order, starting from a given height
+pub struct CommittedBlockStream {
+    storage: Arc<Storage>,
+    live: broadcast::Receiver<CommittedBlock>,
+    next_height: u64,
+}
+
+impl CommittedBlockStream {
+    pub(crate) fn new(storage: Arc<Storage>, live: broadcast::Receiver<CommittedBlock>, from_height: u64) -> Self {
+        Self { storage, live, next_height: from_height }
+    }
+
+    /// Lowest height the next commit can have
+    pub fn next_height(&self) -> u64 {
+        self.next_height
+    }
+
+    /// Wait for the next committed block. Returns None once the engine is
+    /// gone and every stored commit has been delivered.
+    pub async fn recv(&mut self) -> Result<Option<CommittedBlock>> {
+        loop {
+            // Commits are stored before they are published, so anything
+            // missed (before subscribing, or while lagging) is in storage
+            if let Some(commit) = self.storage.next_commit(self.next_height)? {
+                self.next_height = commit.block.height + 1;
+                return Ok(Some(commit));
+            }
+            match self.live.recv().await {
+                Ok(commit) if commit.block.height >= self.next_height => {
+                    self.next_height = commit.block.height + 1;
+                    return Ok(Some(commit));
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(None),
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+// State divergence detection
+//
+// Validators periodically attest to their core and EVM state hashes after
+// executing a height. Any attestation that disagrees with the local state
+// at the same height is flagged immediately, with a diagnostic dump of
+// which validators hold which state.
+
+use crate::crypto::{partial_verify, threshold_sign, BLSKeyPair, BLSPartialSignature, BLSPublicKey, Hash};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+/// Heights of attestations kept for comparison
+pub const RETAINED_HEIGHTS: u64 = 256;
+
+/// Signed state hashes of one validator at one height
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StateAttestation {
+    pub height: u64,
+    /// Core (order book, margin) state hash
+    pub core_state: Hash,
+    /// EVM state root
+    pub evm_state: Hash,
+    /// Attester's public key
+    pub attester: BLSPublicKey,
+    /// Signature over (height, core_state, evm_state)
+    pub signature: BLSPartialSignature,
+}
+
+impl StateAttestation {
+    /// Sign the state hashes at `height`
+    pub fn new(height: u64, core_state: Hash, evm_state: Hash, keypair: &BLSKeyPair) -> Self {
+        Self {
+            height,
+            core_state,
+            evm_state,
+            attester: keypair.public_key.clone(),
+            signature: threshold_sign(&keypair.secret_key, &attestation_message(height, &core_state, &evm_state)),
+        }
+    }
+
+    /// Validator ID of the attester
+    pub fn validator_id(&self) -> u64 {
+        self.attester.validator_id()
+    }
+
+    /// Check the signature against the attester's key
+    pub fn verify(&self) -> bool {
+        let message = attestation_message(self.height, &self.core_state, &self.evm_state);
+        partial_verify(&message, &self.signature, &self.attester)
+    }
+
+    /// Whether both state hashes match `other`
+    pub fn agrees_with(&self, other: &StateAttestation) -> bool {
+        self.core_state == other.core_state && self.evm_state == other.evm_state
+    }
+}
+
+/// Message signed by an attestation
+fn attestation_message(height: u64, core_state: &Hash, evm_state: &Hash) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&height.to_le_bytes());
+    data.extend_from_slice(core_state.as_bytes());
+    data.extend_from_slice(evm_state.as_bytes());
+    data
+}
+
+/// A remote attestation that disagrees with the local state
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DivergenceReport {
+    pub height: u64,
+    /// Local attestation at the height
+    pub local: StateAttestation,
+    /// Disagreeing attestation
+    pub remote: StateAttestation,
+    /// Every attestation received at the height, including the local one
+    pub attestations: Vec<StateAttestation>,
+}
+
+impl DivergenceReport {
+    /// Validator that disagrees with the local state
+    pub fn validator_id(&self) -> u64 {
+        self.remote.validator_id()
+    }
+
+    /// Whether the core state hashes differ
+    pub fn core_diverged(&self) -> bool {
+        self.local.core_state != self.remote.core_state
+    }
+
+    /// Whether the EVM state roots differ
+    pub fn evm_diverged(&self) -> bool {
+        self.local.evm_state != self.remote.evm_state
+    }
+
+    /// Human-readable dump grouping validators by the state they attested
+    pub fn diagnostics(&self) -> String {
+        let mut groups: Vec<(&StateAttestation, Vec<u64>)> = Vec::new();
+        for attestation in &self.attestations {
+            match groups.iter_mut().find(|(a, _)| a.agrees_with(attestation)) {
+                Some((_, ids)) => ids.push(attestation.validator_id()),
+                None => groups.push((attestation, vec![attestation.validator_id()])),
+            }
+        }
+
+        let mut out = format!(
+            "state divergence at height {}: validator {} disagrees with local validator {} (core: {}, evm: {})\n",
+            self.height,
+            self.validator_id(),
+            self.local.validator_id(),
+            if self.core_diverged() { "differs" } else { "matches" },
+            if self.evm_diverged() { "differs" } else { "matches" },
+        );
+        for (attestation, mut ids) in groups {
+            ids.sort_unstable();
+            let _ = writeln!(
+                out,
+                "  core={} evm={} validators={:?}",
+                attestation.core_state, attestation.evm_state, ids
+            );
+        }
+        out
+    }
+}
+
+/// Compares attestations received from other validators against the local
+/// attestation at the same height
+#[derive(Debug, Default)]
+pub struct DivergenceMonitor {
+    /// Height -> attestations received, first per validator
+    attestations: BTreeMap<u64, Vec<StateAttestation>>,
+    /// Height -> validator ID of the local node
+    local: BTreeMap<u64, u64>,
+    /// (height, validator) already reported
+    reported: HashSet<(u64, u64)>,
+}
+
+impl DivergenceMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the local attestation; returns reports for attestations
+    /// already received at the height that disagree with it
+    pub fn record_local(&mut self, attestation: StateAttestation) -> Vec<DivergenceReport> {
+        let height = attestation.height;
+        self.local.insert(height, attestation.validator_id());
+        self.insert(attestation);
+        self.prune_below(height.saturating_sub(RETAINED_HEIGHTS - 1));
+
+        let ids: Vec<u64> = self.attestations[&height].iter().map(|a| a.validator_id()).collect();
+        ids.into_iter().filter_map(|id| self.check(height, id)).collect()
+    }
+
+    /// Record a remote attestation; returns a report if it disagrees with
+    /// the local attestation at its height
+    pub fn observe(&mut self, attestation: StateAttestation) -> Option<DivergenceReport> {
+        let (height, id) = (attestation.height, attestation.validator_id());
+        if !self.insert(attestation) {
+            return None;
+        }
+        self.check(height, id)
+    }
+
+    /// Local attestation at `height`, if any
+    pub fn local_attestation(&self, height: u64) -> Option<&StateAttestation> {
+        let id = self.local.get(&height)?;
+        self.attestations.get(&height)?.iter().find(|a| a.validator_id() == *id)
+    }
+
+    /// Forget heights below `height`
+    pub fn prune_below(&mut self, height: u64) {
+        self.attestations = self.attestations.split_off(&height);
+        self.local = self.local.split_off(&height);
+        self.reported.retain(|(h, _)| *h >= height);
+    }
+
+    /// Keep the first attestation per validator and height
+    fn insert(&mut self, attestation: StateAttestation) -> bool {
+        let seen = self.attestations.entry(attestation.height).or_default();
+        if seen.iter().any(|a| a.validator_id() == attestation.validator_id()) {
+            return false;
+        }
+        seen.push(attestation);
+        true
+    }
+
+    /// Report `id` at `height` once if it disagrees with the local state
+    fn check(&mut self, height: u64, id: u64) -> Option<DivergenceReport> {
+        let local = self.local_attestation(height)?.clone();
+        let attestations = self.attestations.get(&height)?;
+        let remote = attestations.iter().find(|a| a.validator_id() == id)?;
+        if remote.agrees_with(&local) || !self.reported.insert((height, id)) {
+            return None;
+        }
+
+        Some(DivergenceReport {
+            height,
+            local,
+            remote: remote.clone(),
+            attestations: attestations.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergence_detected_at_same_height() {
+        let keys: Vec<_> = (0..3).map(BLSKeyPair::with_id).collect();
+        let good = Hash::new([1; 32]);
+        let bad = Hash::new([2; 32]);
+        let evm = Hash::new([9; 32]);
+
+        let mut monitor = DivergenceMonitor::new();
+        // A divergent attestation arriving before the local one is held
+        assert!(monitor.observe(StateAttestation::new(5, bad, evm, &keys[2])).is_none());
+        assert!(monitor.observe(StateAttestation::new(5, good, evm, &keys[1])).is_none());
+
+        let local = StateAttestation::new(5, good, evm, &keys[0]);
+        assert!(local.verify());
+        let reports = monitor.record_local(local);
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!((report.height, report.validator_id()), (5, 2));
+        assert!(report.core_diverged() && !report.evm_diverged());
+        assert!(report.diagnostics().contains("validators=[0, 1]"));
+        assert!(report.diagnostics().contains("validators=[2]"));
+
+        // Reported once, and other heights are unaffected
+        assert!(monitor.observe(StateAttestation::new(5, bad, evm, &keys[2])).is_none());
+        assert!(monitor.observe(StateAttestation::new(6, bad, evm, &keys[2])).is_none());
+    }
+}
@@ -12,12 +12,20 @@
 use crate::crypto::{Hash, BLSKeyPair};
 use crate::hotstuff::types::{Block, Vote, QuorumCertificate, MessageType};
 use crate::hotstuff::Validator;
-use crate::pacemaker::Pacemaker;
-use crate::storage::{Storage, StateMachine};
+use crate::hotstuff::commit_stream::{CommittedBlock, CommittedBlockStream, DEFAULT_COMMIT_BUFFER};
+use crate::hotstuff::divergence::{DivergenceMonitor, DivergenceReport, StateAttestation};
+use crate::hotstuff::evidence::{EquivocationDetector, Evidence, Misbehavior};
+use crate::hotstuff::participation::{ParticipationTracker, DEFAULT_EPOCH_LENGTH};
+use crate::hotstuff::replay::{ReplayConfig, ReplayStats, ReplayWindow};
+use crate::hotstuff::slashing::{Slasher, SlashingConfig, SlashingEvent};
+use crate::hotstuff::timestamp::{median_time_past, now_millis, validate_timestamp, TimestampConfig};
+use crate::hotstuff::tx_validation::{validate_payload, TxValidator};
+use crate::pacemaker::{Pacemaker, TimeoutCertificate, TimeoutCollector, TimeoutMessage, TIMEOUT_VIEW_WINDOW};
+use crate::storage::{Storage, StateMachine, WalEntry, WalState};
 use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 
 /// Consensus engine errors
 #[derive(Error, Debug)]
@@ -40,6 +48,12 @@ pub enum EngineError {
     #[error("Insufficient votes: {0}")]
     InsufficientVotes(String),
     
+    #[error("Invalid timeout: {0}")]
+    InvalidTimeout(String),
+    
+    #[error("Invalid state attestation: {0}")]
+    InvalidAttestation(String),
+    
     #[error("Consensus stalled")]
     Stalled,
 }
@@ -84,6 +98,13 @@ impl VoteCollector {
     fn clear(&mut self, block_hash: &Hash) {
         self.votes.remove(block_hash);
     }
+    
+    /// Drop the votes of some validators for a block
+    fn remove_voters(&mut self, block_hash: &Hash, voters: &[u64]) {
+        if let Some(votes) = self.votes.get_mut(block_hash) {
+            votes.retain(|v| !voters.contains(&v.voter.validator_id()));
+        }
+    }
 }
 
 /// Main consensus engine
@@ -105,12 +126,67 @@ pub struct ConsensusEngine {
     precommit_votes: VoteCollector,
     commit_votes: VoteCollector,
     
+    /// Participation stats from committed blocks
+    participation: ParticipationTracker,
+    
+    /// Votes that failed signature verification, per validator
+    invalid_votes: HashMap<u64, u64>,
+    
+    /// Double-proposal and double-vote detection
+    equivocation: EquivocationDetector,
+    
+    /// Recently handled proposals and votes, to drop replays
+    replay: ReplayWindow,
+    
+    /// Bounds on proposal timestamps
+    timestamp_config: TimestampConfig,
+    
+    /// Evidence detected since the last `take_evidence`
+    new_evidence: Vec<Evidence>,
+    
+    /// Stake penalties for evidence committed in blocks
+    slasher: Slasher,
+    
+    /// Slashing events since the last `take_slashing_events`
+    new_slashing_events: Vec<SlashingEvent>,
+    
+    /// State attestations compared across replicas
+    divergence: DivergenceMonitor,
+    
+    /// Divergences detected since the last `take_divergences`
+    new_divergences: Vec<DivergenceReport>,
+    
+    /// Committed blocks published to `subscribe_commits` streams
+    commits: broadcast::Sender<CommittedBlock>,
+    
+    /// Stateless check of proposed transactions, if the application has one
+    tx_validator: Option<Arc<dyn TxValidator>>,
+    
+    /// Account this node's proposals pay their priority fees to
+    fee_recipient: [u8; 20],
+    
+    /// Epoch in which the block tree was last pruned
+    pruned_epoch: u64,
+    
+    /// Timeout collectors for the current and future views
+    timeouts: HashMap<u64, TimeoutCollector>,
+    
+    /// Consensus state recorded in the write-ahead log
+    wal: WalState,
+    
+    /// Receive and vote times behind the latency metrics
+    #[cfg(feature = "metrics")]
+    timings: crate::metrics::BlockTimings,
+    
     /// Whether this engine is started
     started: bool,
 }
 
 impl ConsensusEngine {
     /// Create a new consensus engine
+    /// 
+    /// Votes only count once their validators are registered, through
+    /// `validator_mut().set_validator_keys` or an epoch's validator set.
     pub fn new(
         storage: Arc<Storage>,
         state_machine: Box<dyn StateMachine>,
@@ -130,6 +206,24 @@ impl ConsensusEngine {
             prepare_votes: VoteCollector::new(quorum_size),
             precommit_votes: VoteCollector::new(quorum_size),
             commit_votes: VoteCollector::new(quorum_size),
+            participation: ParticipationTracker::new(total_validators, DEFAULT_EPOCH_LENGTH),
+            invalid_votes: HashMap::new(),
+            equivocation: EquivocationDetector::new(),
+            replay: ReplayWindow::default(),
+            timestamp_config: TimestampConfig::default(),
+            new_evidence: Vec::new(),
+            slasher: Slasher::new(SlashingConfig::default(), []),
+            new_slashing_events: Vec::new(),
+            divergence: DivergenceMonitor::new(),
+            new_divergences: Vec::new(),
+            commits: broadcast::channel(DEFAULT_COMMIT_BUFFER).0,
+            tx_validator: None,
+            fee_recipient: [0; 20],
+            pruned_epoch: 0,
+            timeouts: HashMap::new(),
+            wal: WalState::default(),
+            #[cfg(feature = "metrics")]
+            timings: Default::default(),
             started: false,
         })
     }
@@ -180,6 +274,31 @@ impl ConsensusEngine {
             self.validator.add_block(genesis);
         }
         
+        // Replay the write-ahead log so a restart never rolls back the
+        // view, QCs or last vote
+        let entries = self.storage.load_wal()
+            .map_err(|e| EngineError::StorageError(e.to_string()))?;
+        self.wal = WalState::replay(&entries);
+        self.wal.restore_into(&mut self.validator.state);
+        if self.validator.state.view_number > self.pacemaker.current_view() {
+            self.pacemaker.update_view(self.validator.state.view_number)
+                .map_err(EngineError::StorageError)?;
+        }
+        
+        // Resume slashing from the stored slasher, catching up on blocks
+        // committed after it was stored
+        let mut height = 0;
+        if let Some((stored_at, slasher)) = self.storage.load_slasher()
+            .map_err(|e| EngineError::StorageError(e.to_string()))? {
+            self.slasher = slasher;
+            height = stored_at + 1;
+        }
+        while let Some(commit) = self.storage.next_commit(height)
+            .map_err(|e| EngineError::StorageError(e.to_string()))? {
+            height = commit.block.height + 1;
+            self.slash_committed(&commit.block)?;
+        }
+        
         Ok(())
     }
     
@@ -220,8 +339,19 @@ impl ConsensusEngine {
                 .ok_or_else(|| EngineError::BlockNotFound("Genesis block not found".into()))?
         };
         
-        // Create new block
-        let block = self.validator.create_leaf(parent, transactions);
+        // Create new block, justifying its view with the TC that ended the
+        // previous one
+        // Stamped with our clock, but never before the median of the
+        // ancestors or validators would refuse it
+        let median = median_time_past(&self.ancestor_timestamps(&parent.hash()));
+        let mut block = self.validator.create_leaf(parent, transactions).with_timestamp(now_millis().max(median));
+        // Blocks are applied as they are received, so the state machine's
+        // next base fee follows the parent; validators check it on apply
+        let base_fee = self.state_machine.read().await.next_base_fee();
+        block = block.with_fees(base_fee, self.fee_recipient);
+        if let Some(tc) = self.pacemaker.high_tc().filter(|tc| tc.view + 1 == block.view) {
+            block = block.with_timeout_cert(tc.clone());
+        }
         
         Ok(block)
     }
@@ -251,10 +381,42 @@ impl ConsensusEngine {
             return Err(EngineError::InvalidBlock("Parent not found".into()));
         }
         
+        // Accepted already; orphans above and rejected blocks are retried
+        if self.replay.is_block_replay(&block) {
+            return Ok(());
+        }
+        
+        if let Some(misbehavior) = self.equivocation.observe_proposal(&block) {
+            self.record_evidence(misbehavior)?;
+        }
+        
+        // A TC for the previous view lets lagging nodes enter the block's view
+        if let Some(ref tc) = block.timeout_cert {
+            if tc.view + 1 != block.view {
+                return Err(EngineError::InvalidBlock("TC is not for the previous view".into()));
+            }
+            tc.verify(self.validator.validator_keys(), self.validator.quorum_size)
+                .map_err(EngineError::InvalidBlock)?;
+            self.enter_view_with_tc(tc.as_ref().clone())?;
+        }
+        
+        // Never vote for a payload the application cannot execute
+        if let Some(tx_validator) = &self.tx_validator {
+            validate_payload(tx_validator.as_ref(), &block.transactions)
+                .map_err(|e| EngineError::InvalidBlock(format!("Invalid payload: {}", e)))?;
+        }
+        
+        // Refuse timestamps that run backwards or too far ahead of our clock
+        let ancestors = self.ancestor_timestamps(&block.parent);
+        validate_timestamp(&self.timestamp_config, block.timestamp, &ancestors, now_millis())
+            .map_err(|e| EngineError::InvalidBlock(format!("Invalid timestamp: {}", e)))?;
+        
         // Check safety (SafeNode predicate)
         if !self.validator.safe_node(&block) {
             return Err(EngineError::InvalidBlock("SafeNode check failed".into()));
         }
+        #[cfg(feature = "metrics")]
+        self.timings.block_received(block_hash, block.view);
         
         // Store block in database
         self.storage.store_block(&block)
@@ -274,15 +436,47 @@ impl ConsensusEngine {
         self.storage.store_state(block.height, &transition.new_state)
             .map_err(|e| EngineError::StorageError(e.to_string()))?;
         
+        // Only now is the block remembered, so a refusal is not final
+        self.replay.admit_block(&block);
+        
         // Add to block tree
         self.validator.add_block(block.clone());
         
         // Check for three-chain commit
-        if let Some(_committed) = self.validator.check_commit(&block) {
+        if let Some(committed) = self.validator.check_commit(&block) {
             // Block committed! Reset timeout
+            // The QC committing b1 is the one b2 carries, and `block` justifies b2
+            let commit_qc = block.justify.as_ref()
+                .and_then(|qc| self.validator.blocks.get(&qc.block_hash))
+                .and_then(|b2| b2.justify.clone());
+            if let Some(qc) = commit_qc {
+                self.publish_commit(&committed, qc)?;
+            }
+            self.participation.record_block(&committed);
+            self.slash_committed(&committed)?;
+            #[cfg(feature = "metrics")]
+            self.timings.block_committed(&committed.hash(), committed.view);
+            self.equivocation.prune_below(committed.view);
             self.pacemaker.reset_timeout();
+            self.storage.compact_wal(&self.wal)
+                .map_err(|e| EngineError::StorageError(e.to_string()))?;
+            
+            // Once per epoch, drop the tree below the latest checkpoint
+            let epoch = self.participation.epoch_of(committed.height);
+            if epoch > self.pruned_epoch {
+                self.pruned_epoch = epoch;
+                self.enter_epoch(epoch);
+                self.prune_to_checkpoint()?;
+            }
         }
         
+        // Never vote twice in a view, including across restarts
+        let view = self.validator.state.view_number;
+        if self.wal.conflicts(view, &block_hash) {
+            return Ok(());
+        }
+        self.log_wal(WalEntry::Vote { view, block_hash })?;
+        
         // Vote on this block (Prepare phase)
         let vote = self.validator.vote(MessageType::Prepare, &block);
         
@@ -294,6 +488,15 @@ impl ConsensusEngine {
     
     /// Handle incoming vote
     pub async fn on_receive_vote(&mut self, vote: Vote) -> Result<()> {
+        if !self.replay.admit_vote(&vote) {
+            return Ok(());
+        }
+        
+        // A conflicting second vote is recorded as evidence, not counted
+        if let Some(misbehavior) = self.equivocation.observe_vote(&vote) {
+            return self.record_evidence(misbehavior);
+        }
+        
         // Add vote to appropriate collector
         let collector = match vote.msg_type {
             MessageType::Prepare => &mut self.prepare_votes,
@@ -302,46 +505,137 @@ impl ConsensusEngine {
             _ => return Ok(()),
         };
         
+        #[cfg(feature = "metrics")]
+        self.timings.vote_received(&vote.msg_type, vote.block_hash, vote.view);
+        
         // Try to form QC
         if let Some(votes) = collector.add_vote(vote.clone()) {
             // We have a quorum! Form QC
-            let qc = self.validator.form_qc(
+            let formation = self.validator.form_qc(
                 vote.msg_type.clone(),
                 vote.block_hash,
                 vote.view,
                 votes,
             ).map_err(|e| EngineError::InsufficientVotes(e))?;
             
+            // Drop invalid votes so later valid ones can still reach a quorum
+            collector.remove_voters(&vote.block_hash, &formation.invalid_voters);
+            for voter in &formation.invalid_voters {
+                *self.invalid_votes.entry(*voter).or_default() += 1;
+            }
+            let Some(qc) = formation.qc else {
+                return Ok(());
+            };
+            
+            // Clear votes for this block
+            collector.clear(&vote.block_hash);
+            #[cfg(feature = "metrics")]
+            self.timings.qc_formed(&vote.msg_type, vote.block_hash);
+            
             // Update validator state based on QC type
             match vote.msg_type {
                 MessageType::Prepare => {
-                    self.validator.state.update_prepare_qc(qc.clone());
+                    self.log_wal(WalEntry::PrepareQc(qc.clone()))?;
+                    self.validator.state.update_prepare_qc(qc);
                 }
                 MessageType::PreCommit => {
-                    self.validator.state.update_locked_qc(qc.clone());
+                    self.log_wal(WalEntry::LockedQc(qc.clone()))?;
+                    self.validator.state.update_locked_qc(qc);
                 }
                 MessageType::Commit => {
                     // Commit phase complete
                 }
                 _ => {}
             }
-            
-            // Clear votes for this block
-            collector.clear(&vote.block_hash);
         }
         
         Ok(())
     }
     
     /// Handle timeout event
-    pub async fn on_timeout(&mut self) -> Result<()> {
-        // Advance view
-        self.pacemaker.advance_view();
-        self.validator.state.advance_view();
+    /// 
+    /// Signs a timeout of the current view for broadcast. The view only
+    /// advances once n-f timeouts form a timeout certificate.
+    pub async fn on_timeout(&mut self) -> Result<TimeoutMessage> {
+        let view = self.validator.state.view_number;
+        self.pacemaker.record_timeout();
+        
+        let msg = TimeoutMessage::new(view, self.validator.get_highest_qc(), &self.validator.keypair);
+        self.on_receive_timeout(msg.clone()).await?;
+        
+        Ok(msg)
+    }
+    
+    /// Handle incoming timeout message
+    /// 
+    /// Returns the timeout certificate when this message completes one;
+    /// the engine has then entered the following view.
+    pub async fn on_receive_timeout(&mut self, msg: TimeoutMessage) -> Result<Option<TimeoutCertificate>> {
+        // Timeouts for views already left are no longer needed
+        if msg.view < self.validator.state.view_number {
+            return Ok(None);
+        }
+        if msg.view > self.validator.state.view_number + TIMEOUT_VIEW_WINDOW {
+            return Err(EngineError::InvalidTimeout(format!(
+                "Timeout for view {} is too far ahead of view {}",
+                msg.view, self.validator.state.view_number
+            )));
+        }
+        
+        let sender = msg.sender.validator_id();
+        if self.validator.validator_keys().get(&sender) != Some(&msg.sender) {
+            return Err(EngineError::InvalidTimeout(format!("Unregistered key for validator {}", sender)));
+        }
         
+        let quorum_size = self.validator.quorum_size;
+        let collector = self.timeouts
+            .entry(msg.view)
+            .or_insert_with(|| TimeoutCollector::new(msg.view, quorum_size));
+        let tc = collector
+            .add_message(msg, self.validator.validator_keys())
+            .map_err(EngineError::InvalidTimeout)?;
+        
+        if let Some(ref tc) = tc {
+            self.enter_view_with_tc(tc.clone())?;
+        }
+        Ok(tc)
+    }
+    
+    /// Enter the view after a timeout certificate, adopting its high QC if
+    /// it certifies a known block newer than ours
+    fn enter_view_with_tc(&mut self, tc: TimeoutCertificate) -> Result<()> {
+        if let Some(ref qc) = tc.high_qc {
+            let newer = self.validator.state.prepare_qc.as_ref().is_none_or(|ours| qc.view > ours.view);
+            if newer && self.validator.blocks.contains_key(&qc.block_hash) {
+                self.log_wal(WalEntry::PrepareQc(qc.clone()))?;
+                self.validator.state.update_prepare_qc(qc.clone());
+            }
+        }
+        
+        if self.pacemaker.advance_with_tc(tc) {
+            let view = self.pacemaker.current_view().max(self.validator.state.view_number);
+            self.log_wal(WalEntry::View(view))?;
+            self.validator.state.view_number = view;
+            self.timeouts.retain(|v, _| *v >= view);
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().view_changed(view);
+        }
+        Ok(())
+    }
+    
+    /// Durably log a consensus state change before acting on it
+    fn log_wal(&mut self, entry: WalEntry) -> Result<()> {
+        self.storage.append_wal(&entry)
+            .map_err(|e| EngineError::StorageError(e.to_string()))?;
+        self.wal.apply(&entry);
         Ok(())
     }
     
+    /// Highest timeout certificate seen
+    pub fn high_tc(&self) -> Option<&TimeoutCertificate> {
+        self.pacemaker.high_tc()
+    }
+    
     /// Get current view
     pub fn current_view(&self) -> u64 {
         self.validator.state.view_number
@@ -354,11 +648,190 @@ impl ConsensusEngine {
             .unwrap_or(0)
     }
     
+    /// Drop blocks below the latest checkpoint from the in-memory tree,
+    /// keeping the committed block it was taken at and its descendants.
+    /// Pruned blocks stay readable from storage (see `get_block`).
+    pub fn prune_to_checkpoint(&mut self) -> Result<usize> {
+        let checkpoint = self.storage.load_checkpoints()
+            .map_err(|e| EngineError::StorageError(e.to_string()))?
+            .into_iter()
+            .map(|m| m.height)
+            .max();
+        Ok(checkpoint.map_or(0, |height| self.validator.prune_below(height)))
+    }
+    
+    /// Look a block up in the tree, falling back to storage for blocks
+    /// pruned from it
+    pub fn get_block(&self, hash: &Hash) -> Result<Option<Block>> {
+        if let Some(block) = self.validator.blocks.get(hash) {
+            return Ok(Some(block.clone()));
+        }
+        self.storage.get_block(hash)
+            .map_err(|e| EngineError::StorageError(e.to_string()))
+    }
+    
     /// Get committed blocks
     pub fn committed_blocks(&self) -> &[Block] {
         &self.validator.committed
     }
     
+    /// Get validator participation stats
+    pub fn participation(&self) -> &ParticipationTracker {
+        &self.participation
+    }
+    
+    /// Sign, persist and queue evidence of a misbehavior
+    fn record_evidence(&mut self, misbehavior: Misbehavior) -> Result<()> {
+        let evidence = Evidence::new(misbehavior, &self.validator.keypair);
+        self.storage.store_evidence(&evidence)
+            .map_err(|e| EngineError::StorageError(e.to_string()))?;
+        self.new_evidence.push(evidence);
+        Ok(())
+    }
+    
+    /// Take evidence detected since the last call (e.g. for slashing)
+    pub fn take_evidence(&mut self) -> Vec<Evidence> {
+        std::mem::take(&mut self.new_evidence)
+    }
+    
+    /// Stream committed blocks with their QCs in height order, starting
+    /// at `from_height` (earlier commits are replayed from storage)
+    pub fn subscribe_commits(&self, from_height: u64) -> CommittedBlockStream {
+        CommittedBlockStream::new(self.storage.clone(), self.commits.subscribe(), from_height)
+    }
+    
+    /// Store a commit, then publish it to live subscribers
+    fn publish_commit(&mut self, block: &Block, qc: QuorumCertificate) -> Result<()> {
+        self.storage.store_commit(block.height, &qc)
+            .map_err(|e| EngineError::StorageError(e.to_string()))?;
+        // Fails only without subscribers
+        let _ = self.commits.send(CommittedBlock { block: block.clone(), qc });
+        Ok(())
+    }
+    
+    /// Execute the evidence and appeals committed in `block`, and persist
+    /// the slasher as of it
+    fn slash_committed(&mut self, block: &Block) -> Result<()> {
+        let epoch = self.participation.epoch_of(block.height);
+        let events = self.slasher.process_block(block, epoch, self.validator.validator_keys());
+        self.new_slashing_events.extend(events);
+        self.storage.store_slasher(block.height, &self.slasher)
+            .map_err(|e| EngineError::StorageError(e.to_string()))
+    }
+    
+    /// Count votes from the slasher's validator set of `epoch` only:
+    /// jailed and fully slashed validators drop out until released
+    fn enter_epoch(&mut self, epoch: u64) {
+        if let Some(set) = self.slasher.validator_set(epoch, self.validator.validator_keys()) {
+            self.validator.enter_epoch(&set);
+        }
+    }
+    
+    /// Set the bounds proposals' timestamps are validated against
+    pub fn set_timestamp_config(&mut self, config: TimestampConfig) {
+        self.timestamp_config = config;
+    }
+    
+    /// Timestamps of the block `hash` and its ancestors in the tree, nearest
+    /// first, up to the median window
+    fn ancestor_timestamps(&self, hash: &Hash) -> Vec<u64> {
+        self.validator.ancestors(hash)
+            .take(self.timestamp_config.median_window)
+            .map(|block| block.timestamp)
+            .collect()
+    }
+    
+    /// Resize the replay protection window, forgetting what it has seen
+    pub fn set_replay_config(&mut self, config: ReplayConfig) {
+        self.replay = ReplayWindow::new(config);
+    }
+    
+    /// Proposals and votes dropped as replays
+    pub fn replay_stats(&self) -> ReplayStats {
+        self.replay.stats()
+    }
+    
+    /// Set the account this node's proposals pay their priority fees to
+    pub fn set_fee_recipient(&mut self, fee_recipient: [u8; 20]) {
+        self.fee_recipient = fee_recipient;
+    }
+    
+    /// Base fee the next block must carry
+    pub async fn next_base_fee(&self) -> u128 {
+        self.state_machine.read().await.next_base_fee()
+    }
+    
+    /// Reject proposals carrying transactions that fail `tx_validator`
+    pub fn set_tx_validator(&mut self, tx_validator: Arc<dyn TxValidator>) {
+        self.tx_validator = Some(tx_validator);
+    }
+    
+    /// Set the genesis slasher (bonded stake, parameters and governance);
+    /// a slasher stored by an earlier run replaces it on recovery. Appeals
+    /// are filed and decided through committed transactions (see
+    /// `slashing::Appeal`).
+    pub fn set_slasher(&mut self, slasher: Slasher) {
+        self.slasher = slasher;
+    }
+    
+    /// Bonded stake, jails and slashes
+    pub fn slasher(&self) -> &Slasher {
+        &self.slasher
+    }
+    
+    /// Take slashing events since the last call
+    pub fn take_slashing_events(&mut self) -> Vec<SlashingEvent> {
+        std::mem::take(&mut self.new_slashing_events)
+    }
+    
+    /// All evidence persisted by this node
+    pub fn stored_evidence(&self) -> Result<Vec<Evidence>> {
+        self.storage.load_evidence()
+            .map_err(|e| EngineError::StorageError(e.to_string()))
+    }
+    
+    /// Sign the local core and EVM state hashes after executing `height`.
+    /// The returned attestation should be broadcast to the other validators.
+    pub fn attest_state(&mut self, height: u64, core_state: Hash, evm_state: Hash) -> StateAttestation {
+        let attestation = StateAttestation::new(height, core_state, evm_state, &self.validator.keypair);
+        for report in self.divergence.record_local(attestation.clone()) {
+            self.record_divergence(report);
+        }
+        attestation
+    }
+    
+    /// Compare another validator's attestation with the local state
+    pub fn on_receive_attestation(&mut self, attestation: StateAttestation) -> Result<Option<DivergenceReport>> {
+        let id = attestation.validator_id();
+        if self.validator.validator_keys().get(&id).is_some_and(|pk| *pk != attestation.attester) {
+            return Err(EngineError::InvalidAttestation(format!("Unregistered key for validator {}", id)));
+        }
+        if !attestation.verify() {
+            return Err(EngineError::InvalidAttestation(format!("Bad signature from validator {}", id)));
+        }
+        
+        let report = self.divergence.observe(attestation);
+        if let Some(ref report) = report {
+            self.record_divergence(report.clone());
+        }
+        Ok(report)
+    }
+    
+    /// Take divergences detected since the last call
+    pub fn take_divergences(&mut self) -> Vec<DivergenceReport> {
+        std::mem::take(&mut self.new_divergences)
+    }
+    
+    fn record_divergence(&mut self, report: DivergenceReport) {
+        tracing::error!("{}", report.diagnostics());
+        self.new_divergences.push(report);
+    }
+    
+    /// Votes rejected for invalid signatures, per validator
+    pub fn invalid_votes(&self) -> &HashMap<u64, u64> {
+        &self.invalid_votes
+    }
+    
     /// Get storage reference
     pub fn storage(&self) -> &Arc<Storage> {
         &self.storage
@@ -384,6 +857,8 @@ impl ConsensusEngine {
 mod tests {
     use super::*;
     use crate::crypto::bls::BLSKeyPair;
+    use crate::hotstuff::evidence::EVIDENCE_PREFIX;
+    use crate::hotstuff::types::vote_message;
     use crate::storage::state_machine::SimpleStateMachine;
     
     fn create_test_engine(validator_index: usize) -> ConsensusEngine {
@@ -421,14 +896,30 @@ mod tests {
     
     #[tokio::test]
     async fn test_leader_check() {
-        let mut engine = create_test_engine(1);
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let mut engine = ConsensusEngine::new(
+            Arc::new(Storage::new_temp().unwrap()),
+            Box::new(SimpleStateMachine::new()),
+            keypairs[1].clone(),
+            1,
+            4,
+        ).unwrap();
+        engine.validator_mut().set_validator_keys(keypairs.iter().map(|k| k.public_key.clone()));
         engine.start().await.unwrap();
         
         // View 1, validator 1 should be leader (1 % 4 = 1)
         assert!(engine.is_leader());
         
-        // Advance view
+        // A timeout alone does not leave the view
         engine.on_timeout().await.unwrap();
+        assert!(engine.is_leader());
+        
+        // Timeouts from two more validators form a TC for view 1
+        for id in [0, 2] {
+            let msg = TimeoutMessage::new(1, None, &keypairs[id]);
+            engine.on_receive_timeout(msg).await.unwrap();
+        }
+        assert_eq!(engine.high_tc().unwrap().view, 1);
         
         // View 2, validator 2 should be leader (2 % 4 = 2)
         assert!(!engine.is_leader());
@@ -462,6 +953,28 @@ mod tests {
         assert!(result.is_err());
     }
     
+    #[tokio::test]
+    async fn test_block_timestamps_validated() {
+        let mut engine = create_test_engine(0);
+        engine.start().await.unwrap();
+        let pk = BLSKeyPair::generate().public_key;
+        let now = now_millis();
+        
+        // Too far ahead of our clock
+        let future = Block::new(Hash::genesis(), 1, 1, None, vec![], pk.clone()).with_timestamp(now + 60_000);
+        let err = engine.process_block(future).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid timestamp"));
+        
+        let b1 = Block::new(Hash::genesis(), 1, 1, None, vec![], pk.clone()).with_timestamp(now);
+        engine.process_block(b1.clone()).await.unwrap();
+        
+        // Before the median of its ancestors
+        let backwards = Block::new(b1.hash(), 2, 2, None, vec![], pk.clone()).with_timestamp(now - 1);
+        assert!(engine.process_block(backwards).await.is_err());
+        let b2 = Block::new(b1.hash(), 2, 2, None, vec![], pk).with_timestamp(now);
+        engine.process_block(b2).await.unwrap();
+    }
+    
     #[tokio::test]
     async fn test_process_block() {
         let mut engine = create_test_engine(0);
@@ -490,22 +1003,92 @@ mod tests {
         assert!(stored.is_some());
     }
     
+    struct MaxLen(usize);
+    
+    impl TxValidator for MaxLen {
+        fn validate(&self, tx: &[u8]) -> std::result::Result<(), String> {
+            if tx.len() > self.0 {
+                return Err(format!("{} bytes", tx.len()));
+            }
+            Ok(())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_invalid_payload_not_voted_for() {
+        let mut engine = create_test_engine(0);
+        engine.start().await.unwrap();
+        engine.set_tx_validator(Arc::new(MaxLen(3)));
+        
+        let leader_keypair = BLSKeyPair::generate();
+        let junk = Block::new(
+            Hash::genesis(),
+            1,
+            1,
+            None,
+            vec![vec![1, 2, 3], vec![0; 64]],
+            leader_keypair.public_key,
+        );
+        let err = engine.process_block(junk.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("transaction 1"));
+        assert!(!engine.validator.blocks.contains_key(&junk.hash()));
+        assert!(engine.storage.get_block(&junk.hash()).unwrap().is_none());
+        
+        // Evidence is checked by the engine, not the application
+        let mut payload = EVIDENCE_PREFIX.to_vec();
+        payload.extend_from_slice(&[0; 64]);
+        assert!(validate_payload(&MaxLen(3), &[payload]).is_ok());
+        
+        let valid = Block::new(Hash::genesis(), 1, 1, None, vec![vec![1, 2, 3]], BLSKeyPair::generate().public_key);
+        engine.process_block(valid).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_pruned_blocks_served_from_storage() {
+        use crate::checkpoint::{Checkpoint, CheckpointMetadata};
+        use crate::storage::State;
+        
+        let mut engine = create_test_engine(0);
+        engine.start().await.unwrap();
+        let pk = BLSKeyPair::generate().public_key;
+        let b1 = Block::new(Hash::genesis(), 1, 1, None, vec![], pk.clone());
+        let b2 = Block::new(b1.hash(), 2, 2, None, vec![], pk);
+        engine.process_block(b1.clone()).await.unwrap();
+        engine.process_block(b2.clone()).await.unwrap();
+        engine.validator.committed.extend([b1.clone(), b2.clone()]);
+        
+        // No checkpoint yet
+        assert_eq!(engine.prune_to_checkpoint().unwrap(), 0);
+        
+        let mut state = State::genesis();
+        state.height = 2;
+        let metadata = CheckpointMetadata::from(&Checkpoint::new(2, 2, state.clone(), b2.hash()));
+        engine.storage.store_checkpoint(&metadata, &state, &[]).unwrap();
+        assert_eq!(engine.prune_to_checkpoint().unwrap(), 2);
+        assert_eq!(engine.validator.blocks.len(), 1);
+        
+        assert!(!engine.validator.blocks.contains_key(&b1.hash()));
+        assert_eq!(engine.get_block(&b1.hash()).unwrap().unwrap().height, 1);
+    }
+    
     #[tokio::test]
     async fn test_vote_collection() {
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
         let mut engine = create_test_engine(0);
+        engine.validator_mut().set_validator_keys(keypairs.iter().map(|k| k.public_key.clone()));
         engine.start().await.unwrap();
         
         let block_hash = Hash::new([1u8; 32]);
+        let data = vote_message(&MessageType::Prepare, &block_hash, 1);
         
         // Create 3 votes (quorum)
-        for i in 0..3 {
-            let keypair = BLSKeyPair::generate();
-            let partial_sig = crate::crypto::threshold_sign(&keypair.secret_key, b"vote");
+        for (i, keypair) in keypairs[..3].iter().enumerate() {
+            let partial_sig = crate::crypto::threshold_sign(&keypair.secret_key, &data);
             let vote = Vote::new(
                 MessageType::Prepare,
                 block_hash,
                 1,
-                keypair.public_key,
+                keypair.public_key.clone(),
                 partial_sig,
             );
             
@@ -521,6 +1104,318 @@ mod tests {
         assert!(engine.validator.state.prepare_qc.is_some());
     }
     
+    #[tokio::test]
+    async fn test_replayed_votes_and_blocks_dropped() {
+        let mut engine = create_test_engine(0);
+        engine.start().await.unwrap();
+        
+        let block_hash = Hash::new([1u8; 32]);
+        let data = vote_message(&MessageType::Prepare, &block_hash, 1);
+        let keypair = BLSKeyPair::with_id(1);
+        let vote = Vote::new(
+            MessageType::Prepare,
+            block_hash,
+            1,
+            keypair.public_key.clone(),
+            crate::crypto::threshold_sign(&keypair.secret_key, &data),
+        );
+        
+        // A forged copy does not shadow the genuine vote
+        let forged = Vote::new(
+            MessageType::Prepare,
+            block_hash,
+            1,
+            keypair.public_key,
+            crate::crypto::threshold_sign(&BLSKeyPair::with_id(2).secret_key, &data),
+        );
+        engine.on_receive_vote(forged).await.unwrap();
+        engine.on_receive_vote(vote.clone()).await.unwrap();
+        engine.on_receive_vote(vote).await.unwrap();
+        assert_eq!(engine.replay_stats().votes_dropped, 1);
+        
+        // Rejected proposals are verified again when redelivered
+        engine.set_tx_validator(Arc::new(MaxLen(0)));
+        let block = Block::new(Hash::genesis(), 1, 1, None, vec![vec![1]], BLSKeyPair::generate().public_key);
+        assert!(engine.process_block(block.clone()).await.is_err());
+        assert!(engine.process_block(block.clone()).await.is_err());
+        assert_eq!(engine.replay_stats().blocks_dropped, 0);
+        
+        // Once valid, it is accepted and remembered
+        engine.set_tx_validator(Arc::new(MaxLen(1)));
+        engine.process_block(block.clone()).await.unwrap();
+        assert!(engine.replay.is_block_replay(&block));
+        assert_eq!(engine.replay_stats().blocks_dropped, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_proposal_carries_timeout_certificate() {
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let engine = |index: usize| {
+            let mut engine = ConsensusEngine::new(
+                Arc::new(Storage::new_temp().unwrap()),
+                Box::new(SimpleStateMachine::new()),
+                keypairs[index].clone(),
+                index,
+                4,
+            ).unwrap();
+            engine.validator_mut().set_validator_keys(keypairs.iter().map(|k| k.public_key.clone()));
+            engine
+        };
+        
+        // Validators 0, 1 and 3 time out of view 1; the view 2 leader forms the TC
+        let mut leader = engine(2);
+        leader.start().await.unwrap();
+        for id in [0, 1, 3] {
+            let msg = TimeoutMessage::new(1, None, &keypairs[id]);
+            let tc = leader.on_receive_timeout(msg).await.unwrap();
+            assert_eq!(tc.is_some(), id == 3);
+        }
+        assert_eq!(leader.current_view(), 2);
+        
+        // Timeouts too far past the current view are refused
+        let far = TimeoutMessage::new(3 + TIMEOUT_VIEW_WINDOW, None, &keypairs[0]);
+        assert!(matches!(leader.on_receive_timeout(far).await, Err(EngineError::InvalidTimeout(_))));
+        
+        let block = leader.propose_block(vec![]).await.unwrap();
+        assert_eq!(block.timeout_cert.as_ref().unwrap().view, 1);
+        
+        // A validator that missed the timeouts enters view 2 from the proposal
+        let mut follower = engine(0);
+        follower.start().await.unwrap();
+        assert_eq!(follower.current_view(), 1);
+        follower.process_block(block.clone()).await.unwrap();
+        assert_eq!(follower.current_view(), 2);
+        
+        // A forged TC is rejected
+        let mut forged = block;
+        forged.timeout_cert.as_mut().unwrap().signers[0].1 = 5;
+        let mut other = engine(3);
+        other.start().await.unwrap();
+        assert!(matches!(other.process_block(forged).await, Err(EngineError::InvalidBlock(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_timeouts_from_unregistered_senders_are_refused() {
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let mut engine = ConsensusEngine::new(
+            Arc::new(Storage::new_temp().unwrap()),
+            Box::new(SimpleStateMachine::new()),
+            keypairs[2].clone(),
+            2,
+            4,
+        ).unwrap();
+        engine.validator_mut().set_validator_keys(keypairs.iter().map(|k| k.public_key.clone()));
+        engine.start().await.unwrap();
+        
+        // An ID outside the set, and a fresh key claiming a registered ID
+        for keypair in [BLSKeyPair::with_id(7), BLSKeyPair::with_id(1)] {
+            let msg = TimeoutMessage::new(1, None, &keypair);
+            assert!(matches!(engine.on_receive_timeout(msg).await, Err(EngineError::InvalidTimeout(_))));
+        }
+        
+        // Neither counted toward the TC
+        for id in [0, 1] {
+            let msg = TimeoutMessage::new(1, None, &keypairs[id]);
+            assert!(engine.on_receive_timeout(msg).await.unwrap().is_none());
+        }
+        assert_eq!(engine.current_view(), 1);
+        let msg = TimeoutMessage::new(1, None, &keypairs[3]);
+        assert!(engine.on_receive_timeout(msg).await.unwrap().is_some());
+        assert_eq!(engine.current_view(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_double_vote_recorded_as_evidence() {
+        let mut engine = create_test_engine(0);
+        engine.start().await.unwrap();
+        
+        let voter = BLSKeyPair::with_id(2);
+        for block in [[1u8; 32], [2u8; 32]] {
+            let block_hash = Hash::new(block);
+            let data = vote_message(&MessageType::Prepare, &block_hash, 1);
+            let partial_sig = crate::crypto::threshold_sign(&voter.secret_key, &data);
+            let vote = Vote::new(MessageType::Prepare, block_hash, 1, voter.public_key.clone(), partial_sig);
+            engine.on_receive_vote(vote).await.unwrap();
+        }
+        
+        // The second vote is not counted
+        assert_eq!(engine.prepare_votes.count(&Hash::new([2u8; 32])), 0);
+        let evidence = engine.take_evidence();
+        assert_eq!(evidence.len(), 1);
+        assert_eq!((evidence[0].offender(), evidence[0].view()), (2, 1));
+        assert!(evidence[0].verify(&engine.validator.keypair.public_key));
+        assert!(engine.take_evidence().is_empty());
+        assert_eq!(engine.stored_evidence().unwrap(), evidence);
+    }
+    
+    #[tokio::test]
+    async fn test_committed_evidence_slashes_offender() {
+        use crate::light_client::ValidatorSet;
+        
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let mut engine = create_test_engine(0);
+        engine.validator_mut().set_validator_keys(keypairs.iter().map(|k| k.public_key.clone()));
+        let set = ValidatorSet::weighted(0, keypairs.iter().map(|k| (k.public_key.clone(), 100)));
+        engine.set_slasher(Slasher::from_validator_set(SlashingConfig::default(), &set));
+        
+        // Validator 2 double votes; validator 1 reports it
+        let vote = |block: [u8; 32]| {
+            let block_hash = Hash::new(block);
+            let data = vote_message(&MessageType::Prepare, &block_hash, 1);
+            let partial_sig = crate::crypto::threshold_sign(&keypairs[2].secret_key, &data);
+            Vote::new(MessageType::Prepare, block_hash, 1, keypairs[2].public_key.clone(), partial_sig)
+        };
+        let evidence = Evidence::new(Misbehavior::DoubleVote { first: Box::new(vote([1; 32])), second: Box::new(vote([2; 32])) }, &keypairs[1]);
+        let block = Block::new(Hash::genesis(), 2_500, 9, None, vec![evidence.to_transaction()], keypairs[0].public_key.clone());
+        engine.slash_committed(&block).unwrap();
+        
+        // 5% of 100, jailed for two epochs after epoch 2
+        let events = engine.take_slashing_events();
+        assert!(events.contains(&SlashingEvent::Slashed { slash_id: 0, offender: 2, amount: 5, burned: 5 }));
+        assert!(events.contains(&SlashingEvent::Jailed { validator: 2, until_epoch: 4 }));
+        assert_eq!(engine.slasher().stake(2), 95);
+        assert!(engine.take_slashing_events().is_empty());
+        
+        // Its votes stop counting while jailed, and count again once released
+        engine.enter_epoch(4);
+        assert!(!engine.validator.validator_keys().contains_key(&2));
+        engine.enter_epoch(5);
+        assert_eq!(engine.validator.validator_keys().get(&2), Some(&keypairs[2].public_key));
+        
+        // A restarted node resumes from the stored slasher, not the genesis one
+        let mut restarted = ConsensusEngine::new(
+            engine.storage.clone(),
+            Box::new(SimpleStateMachine::new()),
+            BLSKeyPair::generate(),
+            0,
+            4,
+        ).unwrap();
+        restarted.set_slasher(Slasher::from_validator_set(SlashingConfig::default(), &set));
+        restarted.recover().await.unwrap();
+        assert_eq!(restarted.slasher().stake(2), 95);
+        assert!(restarted.slasher().is_jailed(2, 4));
+    }
+    
+    #[tokio::test]
+    async fn test_commit_stream_replays_and_survives_lag() {
+        let mut engine = create_test_engine(0);
+        let pk = engine.validator.keypair.public_key.clone();
+        let mut live = engine.subscribe_commits(0);
+        
+        // More commits than the live buffer holds, so `live` lags
+        let count = DEFAULT_COMMIT_BUFFER as u64 + 10;
+        let mut parent = Hash::genesis();
+        for height in 1..=count {
+            let block = Block::new(parent, height, height, None, vec![], pk.clone());
+            parent = block.hash();
+            engine.storage.store_block(&block).unwrap();
+            let qc = QuorumCertificate::new(
+                MessageType::Prepare,
+                parent,
+                height,
+                crate::crypto::threshold_sign(&engine.validator.keypair.secret_key, b"qc").signature,
+            );
+            engine.publish_commit(&block, qc).unwrap();
+        }
+        
+        // Missed commits come from storage, still in order
+        for height in 1..=count {
+            let commit = live.recv().await.unwrap().unwrap();
+            assert_eq!(commit.block.height, height);
+            assert_eq!(commit.qc.block_hash, commit.block.hash());
+        }
+        
+        // A late subscriber resumes from any height
+        let mut resumed = engine.subscribe_commits(count - 1);
+        assert_eq!(resumed.recv().await.unwrap().unwrap().block.height, count - 1);
+        assert_eq!(resumed.recv().await.unwrap().unwrap().block.height, count);
+        
+        drop(engine);
+        assert!(live.recv().await.unwrap().is_none());
+        assert_eq!(live.next_height(), count + 1);
+    }
+    
+    #[tokio::test]
+    async fn test_state_divergence_flagged() {
+        let mut engine = create_test_engine(0);
+        let remote = BLSKeyPair::with_id(1);
+        let evm = Hash::new([9u8; 32]);
+        
+        engine.attest_state(4, Hash::new([1u8; 32]), evm);
+        let agreeing = StateAttestation::new(4, Hash::new([1u8; 32]), evm, &remote);
+        assert!(engine.on_receive_attestation(agreeing).unwrap().is_none());
+        
+        let diverging = StateAttestation::new(5, Hash::new([2u8; 32]), evm, &remote);
+        assert!(engine.on_receive_attestation(diverging).unwrap().is_none());
+        engine.attest_state(5, Hash::new([1u8; 32]), evm);
+        let reports = engine.take_divergences();
+        assert_eq!(reports.len(), 1);
+        assert_eq!((reports[0].height, reports[0].validator_id()), (5, 1));
+        
+        // Tampered attestations are rejected
+        let mut forged = StateAttestation::new(6, Hash::new([1u8; 32]), evm, &remote);
+        forged.core_state = Hash::new([3u8; 32]);
+        assert!(matches!(engine.on_receive_attestation(forged), Err(EngineError::InvalidAttestation(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_wal_prevents_double_vote_after_restart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let block = |tx: u8| Block::new(Hash::genesis(), 1, 1, None, vec![vec![tx]], BLSKeyPair::generate().public_key);
+        let voted = block(1).hash();
+        
+        // A vote logged just before a crash, and a later view change
+        {
+            let storage = Storage::new(temp_dir.path()).unwrap();
+            storage.append_wal(&WalEntry::Vote { view: 1, block_hash: voted }).unwrap();
+        }
+        
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let mut engine = ConsensusEngine::new(storage, Box::new(SimpleStateMachine::new()), BLSKeyPair::generate(), 0, 4).unwrap();
+        engine.start().await.unwrap();
+        assert_eq!(engine.wal.last_vote, Some((1, voted)));
+        
+        // A conflicting block in the same view is accepted but not voted for
+        let other = block(2);
+        engine.process_block(other.clone()).await.unwrap();
+        assert!(engine.validator.blocks.contains_key(&other.hash()));
+        assert_eq!(engine.prepare_votes.count(&other.hash()), 0);
+        assert_eq!(engine.wal.last_vote, Some((1, voted)));
+        
+        // The vote that was logged can still be re-sent
+        engine.process_block(block(1)).await.unwrap();
+        assert_eq!(engine.prepare_votes.count(&voted), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_invalid_votes_do_not_form_qc() {
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let mut engine = create_test_engine(0);
+        engine.validator_mut().set_validator_keys(keypairs.iter().map(|k| k.public_key.clone()));
+        engine.start().await.unwrap();
+        
+        let block_hash = Hash::new([1u8; 32]);
+        let data = vote_message(&MessageType::Prepare, &block_hash, 1);
+        
+        // Validator 2 signs its vote as a commit vote
+        let commit = vote_message(&MessageType::Commit, &block_hash, 1);
+        for (i, message) in [(0u64, &data), (1, &data), (2, &commit), (3, &data)] {
+            let keypair = &keypairs[i as usize];
+            let partial_sig = crate::crypto::threshold_sign(&keypair.secret_key, message);
+            let vote = Vote::new(MessageType::Prepare, block_hash, 1, keypair.public_key.clone(), partial_sig);
+            engine.on_receive_vote(vote).await.unwrap();
+            
+            if i == 2 {
+                // The invalid vote is dropped, leaving the quorum incomplete
+                assert!(engine.validator.state.prepare_qc.is_none());
+                assert_eq!(engine.prepare_votes.count(&block_hash), 2);
+            }
+        }
+        
+        assert_eq!(engine.invalid_votes().get(&2), Some(&1));
+        assert_eq!(engine.validator.state.prepare_qc.as_ref().unwrap().signers, vec![0, 1, 3]);
+    }
+    
     #[tokio::test]
     async fn test_recovery_with_existing_blocks() {
         let storage = Arc::new(Storage::new_temp().unwrap());
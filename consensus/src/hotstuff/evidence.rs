@@ -0,0 +1,250 @@
+// Equivocation evidence
+//
+// Detects validators that propose two blocks, or vote for two blocks, in
+// the same view, and records signed evidence the application layer can
+// use for slashing. Evidence is committed as a transaction for the
+// slashing pipeline (see `slashing`).
+
+use crate::crypto::{hash, partial_verify, threshold_sign, BLSKeyPair, BLSPartialSignature, BLSPublicKey};
+use crate::hotstuff::types::{vote_message, Block, Vote};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Prefix marking a transaction as equivocation evidence
+pub const EVIDENCE_PREFIX: &[u8] = b"openliquid/evidence/1";
+
+/// Conflicting messages from one validator in one view
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Misbehavior {
+    /// Two different blocks proposed in the same view
+    DoubleProposal { first: Box<Block>, second: Box<Block> },
+    /// Two votes of the same phase for different blocks in the same view
+    DoubleVote { first: Box<Vote>, second: Box<Vote> },
+}
+
+impl Misbehavior {
+    /// Validator that misbehaved
+    pub fn offender(&self) -> u64 {
+        match self {
+            Misbehavior::DoubleProposal { first, .. } => first.proposer.validator_id(),
+            Misbehavior::DoubleVote { first, .. } => first.voter.validator_id(),
+        }
+    }
+
+    pub fn view(&self) -> u64 {
+        match self {
+            Misbehavior::DoubleProposal { first, .. } => first.view,
+            Misbehavior::DoubleVote { first, .. } => first.view,
+        }
+    }
+
+    /// Storage discriminant (one evidence per offender, view and kind)
+    pub fn kind(&self) -> u8 {
+        match self {
+            Misbehavior::DoubleProposal { .. } => 0,
+            Misbehavior::DoubleVote { .. } => 1,
+        }
+    }
+
+    /// Whether the offender signed the conflicting messages, so the
+    /// misbehavior cannot be pinned on it by someone else
+    /// 
+    /// Blocks are not signed: anyone can build two blocks naming an honest
+    /// proposer, so double proposals are only fit for monitoring.
+    pub fn is_attributable(&self) -> bool {
+        matches!(self, Misbehavior::DoubleVote { .. })
+    }
+
+    /// Check the messages really conflict. Votes carry the offender's
+    /// partial signatures, which must both verify; blocks are not signed,
+    /// so proposals are attributed by their proposer key (see
+    /// `is_attributable`).
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Misbehavior::DoubleProposal { first, second } => {
+                first.view == second.view
+                    && first.proposer == second.proposer
+                    && first.hash() != second.hash()
+            }
+            Misbehavior::DoubleVote { first, second } => {
+                first.view == second.view
+                    && first.msg_type == second.msg_type
+                    && first.voter == second.voter
+                    && first.block_hash != second.block_hash
+                    && [first, second]
+                        .iter()
+                        .all(|v| partial_verify(&vote_message(&v.msg_type, &v.block_hash, v.view), &v.partial_sig, &v.voter))
+            }
+        }
+    }
+}
+
+/// Signed record of a misbehavior
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub misbehavior: Misbehavior,
+    /// Signature of the reporting validator over the misbehavior
+    pub signature: BLSPartialSignature,
+}
+
+impl Evidence {
+    /// Sign a misbehavior as `reporter`
+    pub fn new(misbehavior: Misbehavior, reporter: &BLSKeyPair) -> Self {
+        let signature = threshold_sign(&reporter.secret_key, &Self::digest(&misbehavior));
+        Self { misbehavior, signature }
+    }
+
+    pub fn offender(&self) -> u64 {
+        self.misbehavior.offender()
+    }
+
+    pub fn view(&self) -> u64 {
+        self.misbehavior.view()
+    }
+
+    /// Validator that reported the evidence
+    pub fn reporter(&self) -> u64 {
+        self.signature.validator_id
+    }
+
+    /// Check the misbehavior and the reporter's signature
+    pub fn verify(&self, reporter_key: &BLSPublicKey) -> bool {
+        self.misbehavior.is_valid()
+            && partial_verify(&Self::digest(&self.misbehavior), &self.signature, reporter_key)
+    }
+
+    /// (offender, view, kind): evidence with the same key is a duplicate
+    pub fn key(&self) -> (u64, u64, u8) {
+        (self.offender(), self.view(), self.misbehavior.kind())
+    }
+
+    /// Encode as an evidence transaction
+    pub fn to_transaction(&self) -> Vec<u8> {
+        let mut tx = Vec::from(EVIDENCE_PREFIX);
+        tx.extend(bincode::serialize(self).expect("evidence serializes"));
+        tx
+    }
+
+    /// Decode an evidence transaction
+    pub fn from_transaction(tx: &[u8]) -> Option<Self> {
+        bincode::deserialize(tx.strip_prefix(EVIDENCE_PREFIX)?).ok()
+    }
+
+    fn digest(misbehavior: &Misbehavior) -> Vec<u8> {
+        let bytes = bincode::serialize(misbehavior).expect("misbehavior serializes");
+        hash(&bytes).to_vec()
+    }
+}
+
+/// Remembers the first proposal and vote of each validator per view
+#[derive(Debug, Default)]
+pub struct EquivocationDetector {
+    /// View -> first block seen per proposer
+    proposals: BTreeMap<u64, Vec<Block>>,
+    /// View -> first vote seen per (voter, phase)
+    votes: BTreeMap<u64, Vec<Vote>>,
+    /// (offender, view, kind) already reported
+    reported: HashSet<(u64, u64, u8)>,
+}
+
+impl EquivocationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a proposal; returns the misbehavior if its proposer already
+    /// proposed a different block in the same view
+    pub fn observe_proposal(&mut self, block: &Block) -> Option<Misbehavior> {
+        let seen = self.proposals.entry(block.view).or_default();
+        let first = match seen.iter().find(|b| b.proposer == block.proposer) {
+            Some(first) if first.hash() != block.hash() => first.clone(),
+            Some(_) => return None,
+            None => {
+                seen.push(block.clone());
+                return None;
+            }
+        };
+        self.report(Misbehavior::DoubleProposal { first: Box::new(first), second: Box::new(block.clone()) })
+    }
+
+    /// Record a vote; returns the misbehavior if its voter already voted
+    /// for a different block in the same view and phase
+    pub fn observe_vote(&mut self, vote: &Vote) -> Option<Misbehavior> {
+        let seen = self.votes.entry(vote.view).or_default();
+        let same_slot = |v: &&Vote| v.voter == vote.voter && v.msg_type == vote.msg_type;
+        let first = match seen.iter().find(same_slot) {
+            Some(first) if first.block_hash != vote.block_hash => first.clone(),
+            Some(_) => return None,
+            None => {
+                seen.push(vote.clone());
+                return None;
+            }
+        };
+        self.report(Misbehavior::DoubleVote { first: Box::new(first), second: Box::new(vote.clone()) })
+    }
+
+    /// Forget views below `view`
+    pub fn prune_below(&mut self, view: u64) {
+        self.proposals = self.proposals.split_off(&view);
+        self.votes = self.votes.split_off(&view);
+        self.reported.retain(|(_, v, _)| *v >= view);
+    }
+
+    /// Report each (offender, view, kind) once
+    fn report(&mut self, misbehavior: Misbehavior) -> Option<Misbehavior> {
+        let key = (misbehavior.offender(), misbehavior.view(), misbehavior.kind());
+        self.reported.insert(key).then_some(misbehavior)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Hash;
+    use crate::hotstuff::types::MessageType;
+
+    #[test]
+    fn test_double_vote_evidence() {
+        let offender = BLSKeyPair::with_id(2);
+        let reporter = BLSKeyPair::with_id(0);
+        let vote = |block: [u8; 32]| {
+            let block_hash = Hash::new(block);
+            let data = vote_message(&MessageType::Prepare, &block_hash, 7);
+            let sig = threshold_sign(&offender.secret_key, &data);
+            Vote::new(MessageType::Prepare, block_hash, 7, offender.public_key.clone(), sig)
+        };
+
+        let mut detector = EquivocationDetector::new();
+        assert!(detector.observe_vote(&vote([1; 32])).is_none());
+        assert!(detector.observe_vote(&vote([1; 32])).is_none());
+        let misbehavior = detector.observe_vote(&vote([2; 32])).unwrap();
+        assert_eq!((misbehavior.offender(), misbehavior.view()), (2, 7));
+        // Reported once
+        assert!(detector.observe_vote(&vote([3; 32])).is_none());
+
+        let evidence = Evidence::new(misbehavior, &reporter);
+        assert_eq!(evidence.reporter(), 0);
+        assert!(evidence.verify(&reporter.public_key));
+        assert!(!evidence.verify(&offender.public_key));
+
+        let decoded = Evidence::from_transaction(&evidence.to_transaction()).unwrap();
+        assert!(decoded.verify(&reporter.public_key));
+        assert_eq!(decoded.key(), (2, 7, 1));
+
+        detector.prune_below(8);
+        assert!(detector.observe_vote(&vote([4; 32])).is_none());
+    }
+
+    #[test]
+    fn test_double_proposal_detection() {
+        let proposer = BLSKeyPair::with_id(1);
+        let block = |txs: Vec<Vec<u8>>| Block::new(Hash::genesis(), 1, 3, None, txs, proposer.public_key.clone());
+
+        let mut detector = EquivocationDetector::new();
+        assert!(detector.observe_proposal(&block(vec![])).is_none());
+        assert!(detector.observe_proposal(&block(vec![])).is_none());
+        let misbehavior = detector.observe_proposal(&block(vec![vec![1]])).unwrap();
+        assert!(misbehavior.is_valid());
+        assert_eq!(misbehavior.offender(), 1);
+    }
+}
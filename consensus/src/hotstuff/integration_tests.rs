@@ -145,6 +145,7 @@ mod tests {
         let storage = Arc::new(Storage::new_temp().unwrap());
         let state_machine = Box::new(SimpleStateMachine::new());
         let keypair = BLSKeyPair::generate();
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
         
         let mut engine = ConsensusEngine::new(
             storage,
@@ -153,20 +154,21 @@ mod tests {
             0,
             4,
         ).unwrap();
+        engine.validator_mut().set_validator_keys(keypairs.iter().map(|k| k.public_key.clone()));
         
         engine.start().await.unwrap();
         
         let block_hash = Hash::new([1u8; 32]);
+        let data = crate::hotstuff::types::vote_message(&MessageType::Prepare, &block_hash, 1);
         
         // Collect 3 votes (quorum for n=4)
-        for _ in 0..3 {
-            let kp = BLSKeyPair::generate();
-            let partial_sig = crate::crypto::threshold_sign(&kp.secret_key, b"vote");
+        for kp in &keypairs[..3] {
+            let partial_sig = crate::crypto::threshold_sign(&kp.secret_key, &data);
             let vote = crate::hotstuff::types::Vote::new(
                 MessageType::Prepare,
                 block_hash,
                 1,
-                kp.public_key,
+                kp.public_key.clone(),
                 partial_sig,
             );
             
@@ -3,14 +3,49 @@
 // Implements the three-phase BFT consensus protocol
 
 pub mod types;
+pub mod commit_stream;
 pub mod engine;
+pub mod divergence;
+pub mod evidence;
+pub mod participation;
+pub mod replay;
+pub mod slashing;
+pub mod timestamp;
+pub mod tx_validation;
 
 #[cfg(test)]
 mod integration_tests;
+#[cfg(test)]
+mod simulation_tests;
+
+use types::{vote_message, Block, Vote, QuorumCertificate, ValidatorState, MessageType};
+use crate::crypto::{Hash, BLSKeyPair, BLSPartialSignature, BLSPublicKey, KeyRotation};
+use crate::light_client::ValidatorSet;
+use std::collections::{HashMap, HashSet};
 
-use types::{Block, Vote, QuorumCertificate, ValidatorState, MessageType};
-use crate::crypto::{Hash, BLSKeyPair, BLSPartialSignature};
-use std::collections::HashMap;
+/// Outcome of combining votes into a Quorum Certificate
+#[derive(Debug, Clone)]
+pub struct QcFormation {
+    /// The QC, or None if too few votes remained valid
+    pub qc: Option<QuorumCertificate>,
+    /// Validators whose votes failed verification, in vote order
+    pub invalid_voters: Vec<u64>,
+}
+
+/// One branch of the block tree, from where it leaves the canonical chain
+/// to its tip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    /// Leaf block of the branch
+    pub tip: Hash,
+    pub tip_height: u64,
+    /// Last block the branch shares with the canonical chain
+    pub fork_point: Hash,
+    /// Blocks between the fork point and the tip
+    pub length: u64,
+    /// Whether the tip is the canonical head or extends it
+    pub canonical: bool,
+}
 
 /// Validator implementing HotStuff-BFT consensus
 pub struct Validator {
@@ -34,6 +69,13 @@ pub struct Validator {
     
     /// Quorum size (n - f = 2f + 1)
     pub quorum_size: usize,
+    
+    /// Known validator public keys (validator ID -> key); only votes
+    /// carrying a voter's registered key count
+    validator_keys: HashMap<u64, BLSPublicKey>,
+    
+    /// Key this validator switches to once an epoch's set carries it
+    pending_keypair: Option<BLSKeyPair>,
 }
 
 impl Validator {
@@ -57,6 +99,44 @@ impl Validator {
             n,
             f,
             quorum_size,
+            validator_keys: HashMap::new(),
+            pending_keypair: None,
+        }
+    }
+
+    /// Register the public keys of the validator set
+    pub fn set_validator_keys(&mut self, keys: impl IntoIterator<Item = BLSPublicKey>) {
+        self.validator_keys = keys.into_iter().map(|pk| (pk.validator_id(), pk)).collect();
+    }
+
+    /// Registered validator public keys by validator ID
+    pub fn validator_keys(&self) -> &HashMap<u64, BLSPublicKey> {
+        &self.validator_keys
+    }
+    
+    /// Sign a rotation to `new_keypair` from `effective_epoch` on, to be
+    /// committed as a transaction. The current key keeps signing until an
+    /// epoch's validator set carries the new one.
+    pub fn rotate_key(&mut self, new_keypair: BLSKeyPair, effective_epoch: u64) -> Result<KeyRotation, String> {
+        let rotation = KeyRotation::new(&self.keypair.secret_key, &new_keypair.secret_key, effective_epoch)
+            .ok_or_else(|| "New key belongs to another validator".to_string())?;
+        self.pending_keypair = Some(new_keypair);
+        Ok(rotation)
+    }
+    
+    /// Start an epoch: register its validator keys, and sign with the
+    /// pending rotated key once the set carries it. Votes under replaced
+    /// keys are rejected from then on.
+    pub fn enter_epoch(&mut self, validators: &ValidatorSet) {
+        self.set_validator_keys(validators.keys.values().cloned());
+        let own = self.keypair.public_key.validator_id();
+        if let Some(pending) = self.pending_keypair.take() {
+            if validators.keys.get(&own) == Some(&pending.public_key) {
+                self.state.public_key = pending.public_key.clone();
+                self.keypair = pending;
+            } else {
+                self.pending_keypair = Some(pending);
+            }
         }
     }
 
@@ -134,10 +214,7 @@ impl Validator {
         use crate::crypto::threshold_sign;
         
         let block_hash = block.hash();
-        let mut data = Vec::new();
-        data.extend_from_slice(block_hash.as_bytes());
-        data.extend_from_slice(&self.state.view_number.to_le_bytes());
-        
+        let data = vote_message(&msg_type, &block_hash, self.state.view_number);
         let partial_sig = threshold_sign(&self.keypair.secret_key, &data);
         
         Vote::new(
@@ -150,13 +227,21 @@ impl Validator {
     }
 
     /// Combine votes into a Quorum Certificate
+    /// 
+    /// Only votes from registered validators under their registered key
+    /// count. Every vote's partial signature is checked against that key
+    /// and the voted (phase, block hash, view) before aggregation: all votes are
+    /// batch-verified first, and checked one by one only if the batch fails.
+    /// Invalid votes are left out and their voters reported; the QC is
+    /// formed from the first `quorum_size` valid votes. Repeated votes from
+    /// the same validator are ignored.
     pub fn form_qc(
         &self,
         msg_type: MessageType,
         block_hash: Hash,
         view: u64,
         votes: Vec<Vote>,
-    ) -> Result<QuorumCertificate, String> {
+    ) -> Result<QcFormation, String> {
         if votes.len() < self.quorum_size {
             return Err(format!(
                 "Insufficient votes: {} < {}",
@@ -165,27 +250,68 @@ impl Validator {
             ));
         }
 
-        use crate::crypto::threshold_combine;
+        use crate::crypto::{batch_verify_partials, partial_verify, threshold_combine};
+        
+        let data = vote_message(&msg_type, &block_hash, view);
+        
+        // Votes for another block, view or phase, from unregistered
+        // validators or signed under a key other than the voter's
+        // registered one, are invalid outright
+        let mut seen = HashSet::new();
+        let mut invalid_voters = Vec::new();
+        let mut candidates = Vec::new();
+        for vote in &votes {
+            let voter = vote.voter.validator_id();
+            if !seen.insert(voter) {
+                continue;
+            }
+            let registered = self.validator_keys.get(&voter) == Some(&vote.voter);
+            if vote.msg_type == msg_type
+                && vote.block_hash == block_hash
+                && vote.view == view
+                && registered
+            {
+                candidates.push(vote);
+            } else {
+                invalid_voters.push(voter);
+            }
+        }
         
-        // Collect partial signatures
-        let partial_sigs: Vec<BLSPartialSignature> = votes.iter()
-            .map(|v| v.partial_sig.clone())
-            .collect();
+        let partial_sigs: Vec<BLSPartialSignature> =
+            candidates.iter().map(|v| v.partial_sig.clone()).collect();
+        let public_keys: Vec<BLSPublicKey> = candidates.iter().map(|v| v.voter.clone()).collect();
+        let valid: Vec<BLSPartialSignature> = if batch_verify_partials(&data, &partial_sigs, &public_keys) {
+            partial_sigs
+        } else {
+            let mut valid = Vec::new();
+            for (sig, pk) in partial_sigs.into_iter().zip(&public_keys) {
+                if partial_verify(&data, &sig, pk) {
+                    valid.push(sig);
+                } else {
+                    invalid_voters.push(pk.validator_id());
+                }
+            }
+            valid
+        };
         
-        // Combine into threshold signature
-        let mut data = Vec::new();
-        data.extend_from_slice(block_hash.as_bytes());
-        data.extend_from_slice(&view.to_le_bytes());
+        if valid.len() < self.quorum_size {
+            return Ok(QcFormation { qc: None, invalid_voters });
+        }
         
-        let combined_sig = threshold_combine(&data, &partial_sigs, self.quorum_size)
+        // Combine into threshold signature
+        let partial_sigs = &valid[..self.quorum_size];
+        let combined_sig = threshold_combine(&data, partial_sigs, self.quorum_size)
             .map_err(|e| format!("Failed to combine signatures: {:?}", e))?;
         
-        Ok(QuorumCertificate::new(
+        let signers = partial_sigs.iter().map(|sig| sig.validator_id).collect();
+        let qc = QuorumCertificate::new(
             msg_type,
             block_hash,
             view,
             combined_sig,
-        ))
+        )
+        .with_signers(signers);
+        Ok(QcFormation { qc: Some(qc), invalid_voters })
     }
 
     /// Three-chain commit rule
@@ -227,6 +353,145 @@ impl Validator {
         self.blocks.insert(hash, block);
     }
 
+    /// Garbage-collect the block tree below `height`
+    ///
+    /// The latest committed block at or below `height` becomes the root of
+    /// the tree: it and its descendants are kept, and everything else (older
+    /// committed blocks and forks that can never commit) is dropped. Returns
+    /// the number of blocks removed.
+    pub fn prune_below(&mut self, height: u64) -> usize {
+        let Some(root) = self.committed.iter()
+            .filter(|b| b.height <= height)
+            .max_by_key(|b| b.height)
+            .map(|b| b.hash())
+        else {
+            return 0;
+        };
+        if !self.blocks.contains_key(&root) {
+            return 0;
+        }
+
+        let mut children: HashMap<Hash, Vec<Hash>> = HashMap::new();
+        for (hash, block) in &self.blocks {
+            children.entry(block.parent).or_default().push(*hash);
+        }
+        let mut keep = HashSet::from([root]);
+        let mut frontier = vec![root];
+        while let Some(hash) = frontier.pop() {
+            for child in children.get(&hash).into_iter().flatten() {
+                if keep.insert(*child) {
+                    frontier.push(*child);
+                }
+            }
+        }
+
+        let before = self.blocks.len();
+        self.blocks.retain(|hash, _| keep.contains(hash));
+        before - self.blocks.len()
+    }
+
+    /// Parent of a block in the tree; blocks on genesis may name it by
+    /// `Hash::genesis()`
+    pub fn parent_of(&self, block: &Block) -> Option<&Block> {
+        if block.height == 0 {
+            return None;
+        }
+        self.blocks.get(&block.parent).or_else(|| {
+            (block.parent == Hash::genesis())
+                .then(|| self.blocks.values().find(|b| b.height == 0))
+                .flatten()
+        })
+    }
+    
+    /// The block `hash` followed by its ancestors in the tree, nearest first
+    pub fn ancestors(&self, hash: &Hash) -> impl Iterator<Item = &Block> + '_ {
+        std::iter::successors(self.blocks.get(hash), move |block| self.parent_of(block))
+    }
+    
+    /// Blocks in the tree whose parent is `hash`
+    pub fn children(&self, hash: &Hash) -> Vec<Hash> {
+        let Some(parent) = self.blocks.get(hash) else {
+            return vec![];
+        };
+        self.blocks
+            .iter()
+            .filter(|(_, block)| self.parent_of(block).is_some_and(|p| p.hash() == parent.hash()))
+            .map(|(hash, _)| *hash)
+            .collect()
+    }
+    
+    /// Blocks without children: the tips of every branch
+    pub fn leaves(&self) -> Vec<Hash> {
+        let parents: HashSet<Hash> = self.blocks.values()
+            .filter_map(|block| self.parent_of(block))
+            .map(|parent| parent.hash())
+            .collect();
+        self.blocks.keys().filter(|hash| !parents.contains(hash)).copied().collect()
+    }
+    
+    /// Whether `ancestor` is `descendant` or one of its ancestors in the tree
+    pub fn is_ancestor(&self, ancestor: &Hash, descendant: &Hash) -> bool {
+        let Some(target) = self.blocks.get(ancestor) else {
+            return false;
+        };
+        self.ancestors(descendant)
+            .take_while(|block| block.height >= target.height)
+            .any(|block| block.hash() == *ancestor)
+    }
+    
+    /// Most recent block that both `a` and `b` descend from (or are)
+    pub fn common_ancestor(&self, a: &Hash, b: &Hash) -> Option<Hash> {
+        let on_a: HashSet<Hash> = self.ancestors(a).map(|block| block.hash()).collect();
+        self.ancestors(b).map(|block| block.hash()).find(|hash| on_a.contains(hash))
+    }
+    
+    /// Head of the canonical chain: the block certified by the highest QC,
+    /// else the latest committed block, else the tree's root
+    pub fn canonical_head(&self) -> Option<Hash> {
+        self.get_highest_qc()
+            .map(|qc| qc.block_hash)
+            .filter(|hash| self.blocks.contains_key(hash))
+            .or_else(|| self.committed.last().map(|b| b.hash()).filter(|hash| self.blocks.contains_key(hash)))
+            .or_else(|| self.blocks.values().min_by_key(|b| b.height).map(|b| b.hash()))
+    }
+    
+    /// Every branch of the tree, longest first; the branch extending the
+    /// canonical head (if any) is marked canonical
+    pub fn forks(&self) -> Vec<Branch> {
+        let Some(head) = self.canonical_head() else {
+            return vec![];
+        };
+        let mut branches: Vec<Branch> = self.leaves()
+            .into_iter()
+            .filter_map(|tip| {
+                let fork_point = self.common_ancestor(&tip, &head)?;
+                let tip_height = self.blocks[&tip].height;
+                Some(Branch {
+                    tip,
+                    tip_height,
+                    fork_point,
+                    length: tip_height - self.blocks[&fork_point].height,
+                    canonical: fork_point == head,
+                })
+            })
+            .collect();
+        branches.sort_by(|a, b| b.length.cmp(&a.length).then(a.tip_height.cmp(&b.tip_height)));
+        branches
+    }
+    
+    /// Branches that can never be committed: they leave the canonical chain
+    /// below the latest committed block
+    pub fn orphaned_branches(&self) -> Vec<Branch> {
+        let Some(committed) = self.committed.last().filter(|b| self.blocks.contains_key(&b.hash())) else {
+            return vec![];
+        };
+        let committed_height = committed.height;
+        self.forks()
+            .into_iter()
+            .filter(|branch| !branch.canonical && self.blocks[&branch.fork_point].height < committed_height)
+            .collect()
+    }
+    
     /// Get highest QC (prepare QC or locked QC, whichever is higher)
     pub fn get_highest_qc(&self) -> Option<QuorumCertificate> {
         match (&self.state.prepare_qc, &self.state.locked_qc) {
@@ -404,6 +669,78 @@ mod tests {
         assert_eq!(leaf.transactions.len(), 1);
     }
 
+    #[test]
+    fn test_fork_inspection() {
+        let mut validator = setup_validator(4, 0);
+        let pk = validator.keypair.public_key.clone();
+        let genesis = validator.blocks.values().next().unwrap().hash();
+        
+        // genesis <- b1 <- b2 <- b3, with forks f1 (off genesis, named by
+        // Hash::genesis()) and f2 (off b1)
+        let b1 = Block::new(genesis, 1, 1, None, vec![], pk.clone());
+        let b2 = Block::new(b1.hash(), 2, 2, None, vec![], pk.clone());
+        let b3 = Block::new(b2.hash(), 3, 3, None, vec![], pk.clone());
+        let f1 = Block::new(Hash::genesis(), 1, 1, None, vec![vec![1]], pk.clone());
+        let f2 = Block::new(b1.hash(), 2, 2, None, vec![vec![2]], pk);
+        for block in [&b1, &b2, &b3, &f1, &f2] {
+            validator.add_block(block.clone());
+        }
+        validator.committed.push(b1.clone());
+        validator.state.prepare_qc = Some(QuorumCertificate {
+            msg_type: MessageType::Prepare,
+            block_hash: b2.hash(),
+            view: 2,
+            signature: create_test_signature(),
+            signers: vec![],
+        });
+        
+        assert!(validator.is_ancestor(&genesis, &b3.hash()));
+        assert!(validator.is_ancestor(&genesis, &f1.hash()));
+        assert!(!validator.is_ancestor(&b3.hash(), &b1.hash()));
+        assert_eq!(validator.common_ancestor(&b3.hash(), &f2.hash()), Some(b1.hash()));
+        let children: HashSet<Hash> = validator.children(&b1.hash()).into_iter().collect();
+        assert_eq!(children, HashSet::from([b2.hash(), f2.hash()]));
+        
+        assert_eq!(validator.canonical_head(), Some(b2.hash()));
+        let forks = validator.forks();
+        assert_eq!(forks.len(), 3);
+        let canonical: Vec<_> = forks.iter().filter(|b| b.canonical).collect();
+        assert_eq!((canonical.len(), canonical[0].tip, canonical[0].length), (1, b3.hash(), 1));
+        
+        // Only f1 leaves the chain below the committed b1
+        let orphaned = validator.orphaned_branches();
+        assert_eq!(orphaned.len(), 1);
+        assert_eq!((orphaned[0].tip, orphaned[0].fork_point), (f1.hash(), genesis));
+    }
+    
+    #[test]
+    fn test_prune_below_keeps_committed_root_and_descendants() {
+        let mut validator = setup_validator(4, 0);
+        let pk = validator.keypair.public_key.clone();
+        let genesis = validator.blocks.values().next().unwrap().clone();
+        
+        // genesis <- b1 <- b2 <- b3, with forks f1 (off genesis) and f2 (off b1)
+        let b1 = Block::new(genesis.hash(), 1, 1, None, vec![], pk.clone());
+        let b2 = Block::new(b1.hash(), 2, 2, None, vec![], pk.clone());
+        let b3 = Block::new(b2.hash(), 3, 3, None, vec![], pk.clone());
+        let f1 = Block::new(genesis.hash(), 1, 1, None, vec![vec![1]], pk.clone());
+        let f2 = Block::new(b1.hash(), 2, 2, None, vec![vec![2]], pk);
+        for block in [&b1, &b2, &b3, &f1, &f2] {
+            validator.add_block(block.clone());
+        }
+        
+        // Nothing committed at or below the checkpoint yet
+        assert_eq!(validator.prune_below(2), 0);
+        
+        validator.committed.extend([b1.clone(), b2.clone()]);
+        assert_eq!(validator.prune_below(2), 4);
+        let mut kept: Vec<u64> = validator.blocks.values().map(|b| b.height).collect();
+        kept.sort();
+        assert_eq!(kept, vec![2, 3]);
+        assert!(validator.blocks.contains_key(&b2.hash()));
+        assert!(validator.blocks.contains_key(&b3.hash()));
+    }
+
     #[test]
     fn test_vote_creation() {
         let validator = setup_validator(4, 0);
@@ -417,6 +754,82 @@ mod tests {
         assert_eq!(vote.voter, pk);
     }
 
+    #[test]
+    fn test_form_qc_rejects_invalid_votes() {
+        let validators: Vec<_> = (0..4)
+            .map(|i| Validator::new(BLSKeyPair::with_id(i as u64), i, 4))
+            .collect();
+        let genesis = Block::genesis(validators[0].keypair.public_key.clone());
+        let mut leader = setup_validator(4, 0);
+        leader.set_validator_keys(validators.iter().map(|v| v.keypair.public_key.clone()));
+        let votes: Vec<_> = validators.iter().map(|v| v.vote(MessageType::Prepare, &genesis)).collect();
+        
+        // Validator 1 signs something other than the block
+        let mut bad = votes.clone();
+        bad[1].partial_sig = threshold_sign(&validators[1].keypair.secret_key, b"garbage");
+        let formation = leader.form_qc(MessageType::Prepare, genesis.hash(), 1, bad.clone()).unwrap();
+        assert_eq!(formation.invalid_voters, vec![1]);
+        let qc = formation.qc.unwrap();
+        assert_eq!(qc.signers, vec![0, 2, 3]);
+        let keys: Vec<_> = [0, 2, 3].iter().map(|&i| validators[i].keypair.public_key.clone()).collect();
+        let data = vote_message(&MessageType::Prepare, &genesis.hash(), 1);
+        assert!(crate::crypto::threshold_verify(&data, &qc.signature, &keys).unwrap());
+        
+        // An impostor voting as validator 3 leaves no quorum
+        let impostor = Validator::new(BLSKeyPair::with_id(3), 3, 4);
+        bad[3] = impostor.vote(MessageType::Prepare, &genesis);
+        let formation = leader.form_qc(MessageType::Prepare, genesis.hash(), 1, bad).unwrap();
+        assert!(formation.qc.is_none());
+        assert_eq!(formation.invalid_voters, vec![3, 1]);
+        
+        // Votes from validators outside the registered set do not count
+        let mut unregistered = votes.clone();
+        unregistered[3] = Validator::new(BLSKeyPair::with_id(7), 3, 4).vote(MessageType::Prepare, &genesis);
+        let formation = leader.form_qc(MessageType::Prepare, genesis.hash(), 1, unregistered).unwrap();
+        assert_eq!(formation.invalid_voters, vec![7]);
+        assert_eq!(formation.qc.unwrap().signers, vec![0, 1, 2]);
+        
+        // Prepare votes cannot be replayed as another phase's
+        let mut replayed = votes;
+        for vote in &mut replayed {
+            vote.msg_type = MessageType::PreCommit;
+        }
+        let formation = leader.form_qc(MessageType::PreCommit, genesis.hash(), 1, replayed).unwrap();
+        assert!(formation.qc.is_none());
+        assert_eq!(formation.invalid_voters, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_votes_switch_to_rotated_key_at_epoch() {
+        let mut validators: Vec<_> = (0..4)
+            .map(|i| Validator::new(BLSKeyPair::with_id(i as u64), i, 4))
+            .collect();
+        let set = ValidatorSet::new(0, validators.iter().map(|v| v.keypair.public_key.clone()));
+        for v in &mut validators {
+            v.enter_epoch(&set);
+        }
+        let old = Validator::new(validators[1].keypair.clone(), 1, 4);
+        assert!(validators[1].rotate_key(BLSKeyPair::with_id(2), 1).is_err());
+        let rotation = validators[1].rotate_key(BLSKeyPair::with_id(1), 1).unwrap();
+        let genesis = Block::genesis(validators[0].keypair.public_key.clone());
+        
+        // Epoch 0 still votes with the old key
+        assert_eq!(validators[1].vote(MessageType::Prepare, &genesis).voter, old.keypair.public_key);
+        
+        let next = set.apply_rotations([&rotation]).unwrap();
+        for v in &mut validators {
+            v.enter_epoch(&next);
+        }
+        assert_eq!(validators[1].vote(MessageType::Prepare, &genesis).voter, rotation.new_key);
+        
+        // A vote under the replaced key is no longer accepted
+        let mut votes: Vec<_> = validators.iter().map(|v| v.vote(MessageType::Prepare, &genesis)).collect();
+        votes[1] = old.vote(MessageType::Prepare, &genesis);
+        let formation = validators[0].form_qc(MessageType::Prepare, genesis.hash(), 1, votes).unwrap();
+        assert_eq!(formation.invalid_voters, vec![1]);
+        assert_eq!(formation.qc.unwrap().signers, vec![0, 2, 3]);
+    }
+
     #[test]
     fn test_three_chain_commit() {
         let mut validator = setup_validator(4, 0);
@@ -0,0 +1,217 @@
+// Validator participation statistics
+//
+// Aggregates proposals, QC votes and timeouts from committed blocks, per epoch
+
+use super::types::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Committed blocks per epoch
+pub const DEFAULT_EPOCH_LENGTH: u64 = 1000;
+
+/// Participation of one validator
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorParticipation {
+    pub validator_id: u64,
+    /// Committed blocks proposed
+    pub proposals: u64,
+    /// Votes included in QCs of committed blocks
+    pub votes_included: u64,
+    /// Views led without producing a committed block
+    pub timeouts_caused: u64,
+    /// Highest view in which the validator proposed or voted
+    pub last_active_view: Option<u64>,
+}
+
+impl ValidatorParticipation {
+    fn merge(&mut self, other: &ValidatorParticipation) {
+        self.proposals += other.proposals;
+        self.votes_included += other.votes_included;
+        self.timeouts_caused += other.timeouts_caused;
+        self.last_active_view = self.last_active_view.max(other.last_active_view);
+    }
+
+    fn mark_active(&mut self, view: u64) {
+        self.last_active_view = Some(self.last_active_view.map_or(view, |last| last.max(view)));
+    }
+}
+
+/// Participation of every validator in an epoch
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochParticipation {
+    pub epoch: u64,
+    /// Committed blocks in the epoch
+    pub blocks: u64,
+    /// One entry per validator, by validator ID
+    pub validators: Vec<ValidatorParticipation>,
+}
+
+/// Tracks validator participation from committed blocks
+///
+/// Blocks must be recorded in commit order. Views skipped between two
+/// consecutive committed blocks are charged as timeouts to their leaders
+/// (round-robin, as in the pacemaker).
+#[derive(Clone, Debug)]
+pub struct ParticipationTracker {
+    validator_count: usize,
+    epoch_length: u64,
+    /// Epoch -> validator ID -> stats
+    epochs: BTreeMap<u64, BTreeMap<u64, ValidatorParticipation>>,
+    blocks_per_epoch: BTreeMap<u64, u64>,
+    last_view: Option<u64>,
+}
+
+impl ParticipationTracker {
+    pub fn new(validator_count: usize, epoch_length: u64) -> Self {
+        Self {
+            validator_count,
+            epoch_length: epoch_length.max(1),
+            epochs: BTreeMap::new(),
+            blocks_per_epoch: BTreeMap::new(),
+            last_view: None,
+        }
+    }
+
+    /// Epoch of a block height
+    pub fn epoch_of(&self, height: u64) -> u64 {
+        height / self.epoch_length
+    }
+
+    /// Record a committed block
+    pub fn record_block(&mut self, block: &Block) {
+        let epoch = self.epoch_of(block.height);
+        let leader_count = self.validator_count.max(1) as u64;
+        *self.blocks_per_epoch.entry(epoch).or_default() += 1;
+        let stats = self.epochs.entry(epoch).or_default();
+
+        let proposer = entry(stats, block.proposer.validator_id());
+        proposer.proposals += 1;
+        proposer.mark_active(block.view);
+
+        if let Some(qc) = &block.justify {
+            for &signer in &qc.signers {
+                let voter = entry(stats, signer);
+                voter.votes_included += 1;
+                voter.mark_active(qc.view);
+            }
+        }
+
+        if let Some(last) = self.last_view {
+            for view in last + 1..block.view {
+                entry(stats, view % leader_count).timeouts_caused += 1;
+            }
+        }
+        self.last_view = Some(self.last_view.map_or(block.view, |last| last.max(block.view)));
+    }
+
+    /// Participation in one epoch (None if no block was committed in it)
+    pub fn epoch(&self, epoch: u64) -> Option<EpochParticipation> {
+        let stats = self.epochs.get(&epoch)?;
+        Some(EpochParticipation {
+            epoch,
+            blocks: self.blocks_per_epoch.get(&epoch).copied().unwrap_or(0),
+            validators: self.with_all_validators(stats.values()),
+        })
+    }
+
+    /// Epochs with committed blocks, oldest first
+    pub fn epochs(&self) -> impl Iterator<Item = u64> + '_ {
+        self.epochs.keys().copied()
+    }
+
+    /// A validator's participation across all epochs
+    pub fn validator(&self, validator_id: u64) -> ValidatorParticipation {
+        let mut total = ValidatorParticipation {
+            validator_id,
+            ..Default::default()
+        };
+        for stats in self.epochs.values() {
+            if let Some(epoch_stats) = stats.get(&validator_id) {
+                total.merge(epoch_stats);
+            }
+        }
+        total
+    }
+
+    /// Participation of every validator across all epochs
+    pub fn totals(&self) -> Vec<ValidatorParticipation> {
+        let mut totals: BTreeMap<u64, ValidatorParticipation> = BTreeMap::new();
+        for stats in self.epochs.values() {
+            for (id, epoch_stats) in stats {
+                entry(&mut totals, *id).merge(epoch_stats);
+            }
+        }
+        self.with_all_validators(totals.values())
+    }
+
+    /// Fill in validators that did not participate
+    fn with_all_validators<'a>(
+        &self,
+        stats: impl Iterator<Item = &'a ValidatorParticipation>,
+    ) -> Vec<ValidatorParticipation> {
+        let mut all: BTreeMap<u64, ValidatorParticipation> = (0..self.validator_count as u64)
+            .map(|id| {
+                (id, ValidatorParticipation {
+                    validator_id: id,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        for s in stats {
+            all.insert(s.validator_id, s.clone());
+        }
+        all.into_values().collect()
+    }
+}
+
+fn entry(stats: &mut BTreeMap<u64, ValidatorParticipation>, id: u64) -> &mut ValidatorParticipation {
+    stats.entry(id).or_insert_with(|| ValidatorParticipation {
+        validator_id: id,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{hash, BLSKeyPair};
+    use crate::hotstuff::types::{MessageType, QuorumCertificate};
+
+    fn block(height: u64, view: u64, proposer: u64, signers: &[u64]) -> Block {
+        let keypair = BLSKeyPair::with_id(proposer);
+        let justify = (!signers.is_empty()).then(|| {
+            let sig = crate::crypto::threshold_sign(&keypair.secret_key, b"qc").signature;
+            QuorumCertificate::new(MessageType::Prepare, hash(b"parent"), view - 1, sig)
+                .with_signers(signers.to_vec())
+        });
+        Block::new(hash(b"parent"), height, view, justify, vec![], keypair.public_key)
+    }
+
+    #[test]
+    fn test_participation_by_epoch() {
+        let mut tracker = ParticipationTracker::new(4, 2);
+        tracker.record_block(&block(1, 1, 1, &[]));
+        tracker.record_block(&block(2, 2, 2, &[0, 1, 2]));
+        // Views 3 and 4 timed out (leaders 3 and 0)
+        tracker.record_block(&block(3, 5, 1, &[1, 2, 3]));
+
+        let epoch0 = tracker.epoch(0).unwrap();
+        assert_eq!(epoch0.blocks, 1);
+        assert_eq!(epoch0.validators.len(), 4);
+        assert_eq!(epoch0.validators[1].proposals, 1);
+        assert_eq!(epoch0.validators[0].proposals, 0);
+
+        let epoch1 = tracker.epoch(1).unwrap();
+        assert_eq!(epoch1.blocks, 2);
+        assert_eq!(epoch1.validators[0].timeouts_caused, 1);
+        assert_eq!(epoch1.validators[3].timeouts_caused, 1);
+        assert_eq!(epoch1.validators[2].votes_included, 2);
+        assert_eq!(tracker.epoch(2), None);
+
+        let v1 = tracker.validator(1);
+        assert_eq!((v1.proposals, v1.votes_included, v1.last_active_view), (2, 2, Some(5)));
+        let totals = tracker.totals();
+        assert_eq!(totals[0].last_active_view, Some(1));
+        assert_eq!(totals[3].votes_included, 1);
+    }
+}
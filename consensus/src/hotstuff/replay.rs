@@ -0,0 +1,130 @@
+// Replay protection window
+//
+// Gossip redelivers proposals and votes, and a peer can replay old ones on
+// purpose to make validators redo signature verification. The engine keeps
+// bounded windows of the proposals and votes it has recently handled and
+// drops exact repeats before any verification. Proposals are remembered only
+// once they pass every check, so one refused for a passing reason (a
+// timestamp ahead of our clock, a state machine error) is checked again when
+// redelivered. Entries are keyed by the full encoding, not just the block
+// hash or voter, so a forged copy with bad signatures cannot shadow the
+// genuine message.
+
+use super::types::{Block, Vote};
+use crate::network::seen_cache::SeenCache;
+use std::time::Duration;
+
+/// Window sizes and lifetime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayConfig {
+    /// Recently handled proposals remembered
+    pub max_blocks: usize,
+    /// Recently handled votes remembered
+    pub max_votes: usize,
+    /// How long an entry is remembered after it was last seen
+    pub ttl: Duration,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            max_blocks: 1024,
+            max_votes: 16 * 1024,
+            ttl: Duration::from_secs(120),
+        }
+    }
+}
+
+/// Replay counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    /// Repeated proposals dropped
+    pub blocks_dropped: u64,
+    /// Repeated votes dropped
+    pub votes_dropped: u64,
+    /// Entries forgotten to stay within the window sizes
+    pub evicted: u64,
+    /// Entries forgotten after the TTL
+    pub expired: u64,
+}
+
+/// Recently handled proposals and votes
+#[derive(Debug)]
+pub struct ReplayWindow {
+    blocks: SeenCache,
+    votes: SeenCache,
+    blocks_dropped: u64,
+    votes_dropped: u64,
+}
+
+impl ReplayWindow {
+    pub fn new(config: ReplayConfig) -> Self {
+        Self {
+            blocks: SeenCache::new(config.max_blocks, config.ttl),
+            votes: SeenCache::new(config.max_votes, config.ttl),
+            blocks_dropped: 0,
+            votes_dropped: 0,
+        }
+    }
+
+    /// Whether a proposal was admitted already, counting it as dropped if so
+    pub fn is_block_replay(&mut self, block: &Block) -> bool {
+        let replay = seen(&self.blocks, block);
+        if replay {
+            self.blocks_dropped += 1;
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().replay_dropped(crate::metrics::ReplayKind::Block);
+        }
+        replay
+    }
+
+    /// Record a proposal that passed every check; false if it is a replay
+    pub fn admit_block(&mut self, block: &Block) -> bool {
+        let fresh = admit(&mut self.blocks, block);
+        if !fresh {
+            self.blocks_dropped += 1;
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().replay_dropped(crate::metrics::ReplayKind::Block);
+        }
+        fresh
+    }
+
+    /// Record a vote; false if it is a replay to drop
+    pub fn admit_vote(&mut self, vote: &Vote) -> bool {
+        let fresh = admit(&mut self.votes, vote);
+        if !fresh {
+            self.votes_dropped += 1;
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().replay_dropped(crate::metrics::ReplayKind::Vote);
+        }
+        fresh
+    }
+
+    pub fn stats(&self) -> ReplayStats {
+        let (blocks, votes) = (self.blocks.stats(), self.votes.stats());
+        ReplayStats {
+            blocks_dropped: self.blocks_dropped,
+            votes_dropped: self.votes_dropped,
+            evicted: blocks.evicted + votes.evicted,
+            expired: blocks.expired + votes.expired,
+        }
+    }
+}
+
+impl Default for ReplayWindow {
+    fn default() -> Self {
+        Self::new(ReplayConfig::default())
+    }
+}
+
+/// Messages that cannot be encoded are never treated as replays
+fn admit<T: serde::Serialize>(cache: &mut SeenCache, message: &T) -> bool {
+    match bincode::serialize(message) {
+        Ok(bytes) => cache.insert(&bytes),
+        Err(_) => true,
+    }
+}
+
+fn seen<T: serde::Serialize>(cache: &SeenCache, message: &T) -> bool {
+    bincode::serialize(message).is_ok_and(|bytes| cache.contains(&bytes))
+}
@@ -0,0 +1,338 @@
+// End-to-end consensus tests over the deterministic network simulator
+//
+// Each simulated node runs chained HotStuff on a `Validator` and a
+// `Pacemaker`: the leader of view v proposes on its highest QC, replicas
+// vote to the leader of v + 1, and a timed-out view moves on once the next
+// leader collects n - f NewView messages. A node that missed blocks fetches
+// them from the peer whose message referenced them.
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::bls::BLSKeyPair;
+    use crate::crypto::{threshold_sign, BLSPublicKey, Hash};
+    use crate::hotstuff::types::{Block, MessageType, QuorumCertificate, Vote};
+    use crate::hotstuff::Validator;
+    use crate::pacemaker::{NewViewCollector, NewViewMessage, Pacemaker};
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use testutil::simulation::{Context, Node, NodeId, SimConfig, Simulation};
+
+    const VALIDATORS: usize = 4;
+
+    #[derive(Clone, Debug)]
+    enum Message {
+        Proposal(Block),
+        Vote(Vote),
+        NewView(NewViewMessage),
+        Fetch(Hash),
+        Block(Block),
+    }
+
+    struct SimValidator {
+        validator: Validator,
+        pacemaker: Pacemaker,
+        /// Highest view voted in
+        voted_view: u64,
+        /// Highest view proposed in
+        proposed_view: u64,
+        /// Prepare votes for blocks this node leads the next view of
+        votes: HashMap<Hash, Vec<Vote>>,
+        new_views: HashMap<u64, NewViewCollector>,
+        /// Timer tag of the current view; older timers are stale
+        timer_epoch: u64,
+        /// Blocks waiting for a missing parent, with their sender and
+        /// whether they were proposals
+        orphans: HashMap<Hash, (NodeId, Block, bool)>,
+    }
+
+    impl SimValidator {
+        fn new(keypair: BLSKeyPair, index: usize, keys: &[BLSPublicKey]) -> Self {
+            let mut validator = Validator::new(keypair, index, VALIDATORS);
+            validator.set_validator_keys(keys.iter().cloned());
+            validator.state.view_number = 1;
+            Self {
+                validator,
+                pacemaker: Pacemaker::new(VALIDATORS, Some(Duration::from_millis(500))),
+                voted_view: 0,
+                proposed_view: 0,
+                votes: HashMap::new(),
+                new_views: HashMap::new(),
+                timer_epoch: 0,
+                orphans: HashMap::new(),
+            }
+        }
+
+        fn index(&self) -> usize {
+            self.validator.state.validator_index
+        }
+
+        fn view(&self) -> u64 {
+            self.pacemaker.current_view()
+        }
+
+        fn committed(&self) -> &[Block] {
+            &self.validator.committed
+        }
+
+        fn arm_timer(&mut self, ctx: &mut Context<Message>) {
+            self.timer_epoch += 1;
+            ctx.set_timer(self.pacemaker.next_view_timeout().as_millis() as u64, self.timer_epoch);
+        }
+
+        fn enter_view(&mut self, view: u64, ctx: &mut Context<Message>) {
+            if view <= self.view() {
+                return;
+            }
+            self.pacemaker.update_view(view).unwrap();
+            self.validator.state.view_number = view;
+            self.new_views.retain(|v, _| *v >= view);
+            self.arm_timer(ctx);
+        }
+
+        fn adopt_qc(&mut self, qc: &QuorumCertificate) {
+            let state = &mut self.validator.state;
+            if state.prepare_qc.as_ref().is_none_or(|ours| qc.view > ours.view) {
+                state.update_prepare_qc(qc.clone());
+            }
+            // Two-chain lock: lock on the QC the certified block carries
+            let locked = self.validator.blocks.get(&qc.block_hash).and_then(|b| b.justify.clone());
+            if let Some(locked) = locked {
+                let state = &mut self.validator.state;
+                if state.locked_qc.as_ref().is_none_or(|ours| locked.view > ours.view) {
+                    state.update_locked_qc(locked);
+                }
+            }
+        }
+
+        /// Propose on the highest QC if this node leads the current view
+        fn propose(&mut self, ctx: &mut Context<Message>) {
+            let view = self.view();
+            if self.pacemaker.leader(view) != self.index() || self.proposed_view >= view {
+                return;
+            }
+            let parent = match &self.validator.state.prepare_qc {
+                Some(qc) => self.validator.blocks.get(&qc.block_hash).cloned(),
+                None => self.validator.blocks.values().find(|b| b.height == 0).cloned(),
+            };
+            // A leader that missed the certified block cannot extend it
+            let Some(parent) = parent else {
+                return;
+            };
+            self.proposed_view = view;
+            let block = self.validator.create_leaf(&parent, vec![view.to_le_bytes().to_vec()]);
+            ctx.broadcast(Message::Proposal(block));
+        }
+
+        fn on_proposal(&mut self, from: NodeId, block: Block, ctx: &mut Context<Message>) {
+            if block.view <= self.voted_view || block.view < self.view() || from != self.pacemaker.leader(block.view) {
+                return;
+            }
+            if !self.validator.blocks.contains_key(&block.parent) {
+                self.fetch_parent(from, block, true, ctx);
+                return;
+            }
+            if !self.validator.safe_node(&block) {
+                return;
+            }
+            self.validator.add_block(block.clone());
+            if let Some(ref qc) = block.justify {
+                self.adopt_qc(qc);
+            }
+            if self.validator.check_commit(&block).is_some() {
+                self.pacemaker.reset_timeout();
+            }
+
+            // Votes are signed for the block's view
+            self.validator.state.view_number = block.view;
+            self.voted_view = block.view;
+            let vote = self.validator.vote(MessageType::Prepare, &block);
+            ctx.send(self.pacemaker.leader(block.view + 1), Message::Vote(vote));
+            self.validator.state.view_number = self.view();
+            self.enter_view(block.view + 1, ctx);
+        }
+
+        fn fetch_parent(&mut self, from: NodeId, block: Block, proposal: bool, ctx: &mut Context<Message>) {
+            ctx.send(from, Message::Fetch(block.parent));
+            self.orphans.insert(block.parent, (from, block, proposal));
+        }
+
+        /// Add a fetched block, then any orphans it completes
+        fn on_block(&mut self, from: NodeId, block: Block, ctx: &mut Context<Message>) {
+            let hash = block.hash();
+            if self.validator.blocks.contains_key(&hash) {
+                return;
+            }
+            if !self.validator.blocks.contains_key(&block.parent) {
+                self.fetch_parent(from, block, false, ctx);
+                return;
+            }
+            self.validator.add_block(block);
+            if let Some((from, child, proposal)) = self.orphans.remove(&hash) {
+                if proposal {
+                    self.on_proposal(from, child, ctx);
+                } else {
+                    self.on_block(from, child, ctx);
+                }
+            }
+        }
+
+        fn on_vote(&mut self, vote: Vote, ctx: &mut Context<Message>) {
+            if self.pacemaker.leader(vote.view + 1) != self.index() {
+                return;
+            }
+            let votes = self.votes.entry(vote.block_hash).or_default();
+            votes.push(vote.clone());
+            if votes.len() != self.validator.quorum_size {
+                return;
+            }
+            let votes = self.votes.remove(&vote.block_hash).unwrap_or_default();
+            let formation = self
+                .validator
+                .form_qc(MessageType::Prepare, vote.block_hash, vote.view, votes)
+                .unwrap();
+            if let Some(qc) = formation.qc {
+                self.adopt_qc(&qc);
+                self.enter_view(vote.view + 1, ctx);
+                self.propose(ctx);
+            }
+        }
+
+        fn on_new_view(&mut self, msg: NewViewMessage, ctx: &mut Context<Message>) {
+            let view = msg.view;
+            if self.pacemaker.leader(view) != self.index() || view < self.view() {
+                return;
+            }
+            if let Some(ref qc) = msg.high_qc {
+                if self.validator.blocks.contains_key(&qc.block_hash) {
+                    self.adopt_qc(qc);
+                }
+            }
+            let quorum_size = self.validator.quorum_size;
+            let collector = self
+                .new_views
+                .entry(view)
+                .or_insert_with(|| NewViewCollector::new(view, quorum_size));
+            if collector.add_message(msg).is_ok() && collector.has_quorum() {
+                self.enter_view(view, ctx);
+                self.propose(ctx);
+            }
+        }
+    }
+
+    impl Node for SimValidator {
+        type Message = Message;
+
+        fn start(&mut self, ctx: &mut Context<Message>) {
+            self.arm_timer(ctx);
+            self.propose(ctx);
+        }
+
+        fn on_message(&mut self, from: NodeId, message: Message, ctx: &mut Context<Message>) {
+            match message {
+                Message::Proposal(block) => self.on_proposal(from, block, ctx),
+                Message::Vote(vote) => self.on_vote(vote, ctx),
+                Message::NewView(msg) => self.on_new_view(msg, ctx),
+                Message::Fetch(hash) => {
+                    if let Some(block) = self.validator.blocks.get(&hash) {
+                        ctx.send(from, Message::Block(block.clone()));
+                    }
+                }
+                Message::Block(block) => self.on_block(from, block, ctx),
+            }
+        }
+
+        fn on_timer(&mut self, tag: u64, ctx: &mut Context<Message>) {
+            if tag != self.timer_epoch {
+                return;
+            }
+            self.pacemaker.record_timeout();
+            let view = self.view() + 1;
+            self.enter_view(view, ctx);
+
+            let signature = threshold_sign(&self.validator.keypair.secret_key, &view.to_le_bytes());
+            let msg = NewViewMessage::new(
+                view,
+                self.validator.get_highest_qc(),
+                self.validator.keypair.public_key.clone(),
+                signature,
+            );
+            ctx.send(self.pacemaker.leader(view), Message::NewView(msg));
+        }
+    }
+
+    fn simulation(config: SimConfig) -> Simulation<SimValidator> {
+        let keypairs: Vec<_> = (0..VALIDATORS as u64).map(BLSKeyPair::with_id).collect();
+        let keys: Vec<_> = keypairs.iter().map(|k| k.public_key.clone()).collect();
+        let nodes = keypairs
+            .into_iter()
+            .enumerate()
+            .map(|(index, keypair)| SimValidator::new(keypair, index, &keys))
+            .collect();
+        Simulation::new(config, nodes)
+    }
+
+    /// Safety: no two nodes commit different blocks at the same height
+    fn assert_no_conflicting_commits(nodes: &[SimValidator]) {
+        let mut by_height: HashMap<u64, Hash> = HashMap::new();
+        for node in nodes {
+            for block in node.committed() {
+                let hash = *by_height.entry(block.height).or_insert(block.hash());
+                assert_eq!(hash, block.hash(), "conflicting commits at height {}", block.height);
+            }
+        }
+    }
+
+    fn min_commits(nodes: &[SimValidator]) -> usize {
+        nodes.iter().map(|n| n.committed().len()).min().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_commits_with_latency_drops_and_clock_skew() {
+        for seed in 0..5 {
+            let config = SimConfig {
+                seed,
+                min_latency: 5,
+                max_latency: 80,
+                drop_rate: 0.05,
+                max_clock_skew: 250,
+            };
+            let mut sim = simulation(config);
+            let live = sim.run_until_condition(120_000, |nodes| min_commits(nodes) >= 10);
+            assert!(live, "seed {} stalled at view {}", seed, sim.node(0).view());
+            assert_no_conflicting_commits(sim.nodes());
+        }
+    }
+
+    #[test]
+    fn test_partition_halts_progress_until_healed() {
+        let mut sim = simulation(SimConfig { seed: 42, ..Default::default() });
+        assert!(sim.run_until_condition(30_000, |nodes| min_commits(nodes) >= 3));
+
+        // An isolated validator stalls while the other three keep committing
+        sim.partition(&[&[0, 1, 2]]);
+        let start = sim.now();
+        sim.run_until(start + 1_000);
+        let isolated = sim.node(3).committed().len();
+        let majority = sim.node(0).committed().len();
+        sim.run_until(start + 20_000);
+        assert_eq!(sim.node(3).committed().len(), isolated);
+        assert!(sim.node(0).committed().len() >= majority + 5);
+
+        // Without a quorum on either side nothing commits
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        let start = sim.now();
+        sim.run_until(start + 1_000);
+        let halted: Vec<_> = sim.nodes().iter().map(|n| n.committed().len()).collect();
+        sim.run_until(start + 30_000);
+        let after: Vec<_> = sim.nodes().iter().map(|n| n.committed().len()).collect();
+        assert_eq!(after, halted);
+        assert!(sim.stats().partitioned > 0);
+
+        // The majority side resumes once reconnected
+        sim.heal();
+        let target = halted[0] + 3;
+        let resumed = sim.run_until_condition(sim.now() + 300_000, |nodes| nodes[0].committed().len() >= target);
+        assert!(resumed, "no progress after healing, view {}", sim.node(0).view());
+        assert_no_conflicting_commits(sim.nodes());
+    }
+}
@@ -0,0 +1,555 @@
+// Slashing pipeline
+//
+// Turns equivocation evidence committed in blocks into stake penalties:
+// each verified evidence transaction slashes a configured fraction of the
+// offender's bonded stake (burned, or redistributed pro rata to the other
+// unjailed validators) and jails the offender for a number of epochs. The
+// offender may appeal within a window; governance then upholds the slash
+// or overturns it, restoring the stake and lifting the jail. Appeals and
+// decisions are signed transactions too, executed in block order, so
+// every node's slasher stays identical. Stake and jails feed the next
+// epoch's validator set.
+
+use super::evidence::Evidence;
+use super::types::Block;
+use crate::crypto::{hash, partial_verify, threshold_sign, BLSKeyPair, BLSPartialSignature, BLSPublicKey};
+use crate::light_client::ValidatorSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use thiserror::Error;
+
+/// Slashing errors
+#[derive(Error, Debug, PartialEq)]
+pub enum SlashingError {
+    #[error("Unknown slash {0}")]
+    UnknownSlash(u64),
+
+    #[error("Validator {0} is not the offender")]
+    NotOffender(u64),
+
+    #[error("Appeal window for slash {slash_id} closed at epoch {deadline}")]
+    AppealWindowClosed { slash_id: u64, deadline: u64 },
+
+    #[error("Slash {0} is not under appeal")]
+    NotAppealed(u64),
+
+    #[error("Slash {0} was already appealed")]
+    AlreadyAppealed(u64),
+
+    #[error("Validator {0} is not a governance authority")]
+    Unauthorized(u64),
+}
+
+pub type Result<T> = std::result::Result<T, SlashingError>;
+
+/// Prefix marking a transaction as an appeal action
+pub const APPEAL_PREFIX: &[u8] = b"openliquid/appeal/1";
+
+/// What happens to slashed stake
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlashPenalty {
+    /// Destroyed
+    Burn,
+    /// Shared pro rata among the other unjailed validators (rounding dust
+    /// is burned)
+    Redistribute,
+}
+
+/// Slashing parameters
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlashingConfig {
+    /// Fraction of the offender's stake slashed per evidence (bps)
+    pub slash_bps: u64,
+    /// Epochs after the offense the offender stays out of the validator set
+    pub jail_epochs: u64,
+    /// Epochs after the offense in which it can be appealed
+    pub appeal_epochs: u64,
+    pub penalty: SlashPenalty,
+}
+
+impl Default for SlashingConfig {
+    fn default() -> Self {
+        Self {
+            slash_bps: 500,
+            jail_epochs: 2,
+            appeal_epochs: 1,
+            penalty: SlashPenalty::Burn,
+        }
+    }
+}
+
+/// Lifecycle of a slash
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlashStatus {
+    /// Applied; appealable until the deadline
+    Applied,
+    /// Awaiting a governance decision
+    Appealed,
+    /// Confirmed on appeal
+    Upheld,
+    /// Reverted on appeal
+    Overturned,
+}
+
+/// A stake penalty applied for one piece of evidence
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlashRecord {
+    pub id: u64,
+    pub offender: u64,
+    /// Epoch of the block that committed the evidence
+    pub epoch: u64,
+    /// (offender, view, kind) of the evidence
+    pub evidence: (u64, u64, u8),
+    /// Stake taken from the offender
+    pub amount: u64,
+    pub burned: u64,
+    /// Stake credited to each other validator
+    pub redistributed: BTreeMap<u64, u64>,
+    /// Last epoch the offender is jailed for
+    pub jailed_until: u64,
+    /// Last epoch an appeal may be filed in
+    pub appeal_deadline: u64,
+    pub status: SlashStatus,
+}
+
+/// Emitted as evidence is processed and appeals are decided
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlashingEvent {
+    Slashed { slash_id: u64, offender: u64, amount: u64, burned: u64 },
+    Jailed { validator: u64, until_epoch: u64 },
+    AppealFiled { slash_id: u64 },
+    AppealResolved { slash_id: u64, overturned: bool },
+}
+
+/// Step of an appeal, committed as a transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AppealAction {
+    /// Appeal a slash (its offender only)
+    File { slash_id: u64 },
+    /// Decide an appeal (governance only)
+    Resolve { slash_id: u64, overturn: bool },
+}
+
+/// Appeal action signed by the validator taking it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Appeal {
+    pub action: AppealAction,
+    pub signature: BLSPartialSignature,
+}
+
+impl Appeal {
+    /// Sign `action` as `signer`
+    pub fn new(action: AppealAction, signer: &BLSKeyPair) -> Self {
+        let signature = threshold_sign(&signer.secret_key, &Self::digest(&action));
+        Self { action, signature }
+    }
+
+    /// Validator taking the action
+    pub fn signer(&self) -> u64 {
+        self.signature.validator_id
+    }
+
+    pub fn verify(&self, signer_key: &BLSPublicKey) -> bool {
+        partial_verify(&Self::digest(&self.action), &self.signature, signer_key)
+    }
+
+    /// Encode as an appeal transaction
+    pub fn to_transaction(&self) -> Vec<u8> {
+        let mut tx = Vec::from(APPEAL_PREFIX);
+        tx.extend(bincode::serialize(self).expect("appeal serializes"));
+        tx
+    }
+
+    /// Decode an appeal transaction
+    pub fn from_transaction(tx: &[u8]) -> Option<Self> {
+        bincode::deserialize(tx.strip_prefix(APPEAL_PREFIX)?).ok()
+    }
+
+    fn digest(action: &AppealAction) -> Vec<u8> {
+        let mut bytes = Vec::from(APPEAL_PREFIX);
+        bytes.extend(bincode::serialize(action).expect("appeal action serializes"));
+        hash(&bytes).to_vec()
+    }
+}
+
+/// Applies slashes to bonded stake and tracks jails and appeals
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Slasher {
+    config: SlashingConfig,
+    /// Bonded stake by validator ID
+    stakes: BTreeMap<u64, u64>,
+    /// Bonded validators' keys, from which epoch sets are drawn
+    keys: BTreeMap<u64, BLSPublicKey>,
+    /// Last jailed epoch by validator ID
+    jailed: BTreeMap<u64, u64>,
+    /// Validator IDs that decide appeals
+    governance: BTreeSet<u64>,
+    slashes: Vec<SlashRecord>,
+    /// Evidence already slashed for
+    processed: HashSet<(u64, u64, u8)>,
+    /// Total stake burned
+    burned: u64,
+}
+
+impl Slasher {
+    /// Slasher over `stakes` (validator ID, bonded stake)
+    pub fn new(config: SlashingConfig, stakes: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        Self {
+            config,
+            stakes: stakes.into_iter().collect(),
+            keys: BTreeMap::new(),
+            jailed: BTreeMap::new(),
+            governance: BTreeSet::new(),
+            slashes: Vec::new(),
+            processed: HashSet::new(),
+            burned: 0,
+        }
+    }
+
+    /// Slasher bonding each member of `validators` with its weight
+    pub fn from_validator_set(config: SlashingConfig, validators: &ValidatorSet) -> Self {
+        let mut slasher = Self::new(config, validators.members().into_iter().map(|(pk, weight)| (pk.validator_id(), weight)));
+        slasher.keys = validators.keys.iter().map(|(id, pk)| (*id, pk.clone())).collect();
+        slasher
+    }
+
+    /// Let `authorities` decide appeals
+    pub fn with_governance(mut self, authorities: impl IntoIterator<Item = u64>) -> Self {
+        self.governance = authorities.into_iter().collect();
+        self
+    }
+
+    pub fn config(&self) -> &SlashingConfig {
+        &self.config
+    }
+
+    /// Bonded stake of a validator
+    pub fn stake(&self, validator_id: u64) -> u64 {
+        self.stakes.get(&validator_id).copied().unwrap_or(0)
+    }
+
+    /// Total stake burned by slashing
+    pub fn burned(&self) -> u64 {
+        self.burned
+    }
+
+    /// Whether a validator is jailed in `epoch`
+    pub fn is_jailed(&self, validator_id: u64, epoch: u64) -> bool {
+        self.jailed.get(&validator_id).is_some_and(|until| epoch <= *until)
+    }
+
+    pub fn slash(&self, slash_id: u64) -> Option<&SlashRecord> {
+        self.slashes.get(slash_id as usize)
+    }
+
+    /// Slashes in the order they were applied
+    pub fn slashes(&self) -> &[SlashRecord] {
+        &self.slashes
+    }
+
+    /// Execute the evidence and appeal transactions of a committed block,
+    /// in order. `keys` are the block epoch's validator keys: evidence must
+    /// be reported by one of them, and each piece is slashed for once. Only
+    /// attributable misbehavior is slashed (see `Misbehavior::is_attributable`).
+    /// Appeals are signed by a bonded validator (jailed ones included);
+    /// unverified or rejected ones are skipped.
+    pub fn process_block(
+        &mut self,
+        block: &Block,
+        epoch: u64,
+        keys: &HashMap<u64, BLSPublicKey>,
+    ) -> Vec<SlashingEvent> {
+        let mut events = Vec::new();
+        for tx in &block.transactions {
+            if let Some(evidence) = Evidence::from_transaction(tx) {
+                let verified = keys.get(&evidence.reporter()).is_some_and(|pk| evidence.verify(pk));
+                if !verified || !evidence.misbehavior.is_attributable() || self.stake(evidence.offender()) == 0 || !self.processed.insert(evidence.key()) {
+                    continue;
+                }
+                events.extend(self.apply(&evidence, epoch));
+            } else if let Some(appeal) = Appeal::from_transaction(tx) {
+                let signer = appeal.signer();
+                let verified = self.keys.get(&signer).or_else(|| keys.get(&signer)).is_some_and(|pk| appeal.verify(pk));
+                if !verified {
+                    continue;
+                }
+                let result = match appeal.action {
+                    AppealAction::File { slash_id } => self.file_appeal(signer, slash_id, epoch),
+                    AppealAction::Resolve { slash_id, overturn } => self.resolve_appeal(signer, slash_id, overturn),
+                };
+                events.extend(result.ok());
+            }
+        }
+        events
+    }
+
+    fn apply(&mut self, evidence: &Evidence, epoch: u64) -> [SlashingEvent; 2] {
+        let offender = evidence.offender();
+        let stake = self.stake(offender);
+        let amount = (stake as u128 * self.config.slash_bps.min(10_000) as u128 / 10_000) as u64;
+        self.stakes.insert(offender, stake - amount);
+
+        let mut redistributed = BTreeMap::new();
+        if self.config.penalty == SlashPenalty::Redistribute {
+            let recipients: Vec<(u64, u64)> = self
+                .stakes
+                .iter()
+                .filter(|(id, stake)| **id != offender && **stake > 0 && !self.is_jailed(**id, epoch))
+                .map(|(id, stake)| (*id, *stake))
+                .collect();
+            let total: u128 = recipients.iter().map(|(_, stake)| *stake as u128).sum();
+            for (id, stake) in recipients {
+                let share = (amount as u128 * stake as u128 / total) as u64;
+                if share > 0 {
+                    *self.stakes.entry(id).or_default() += share;
+                    redistributed.insert(id, share);
+                }
+            }
+        }
+        let burned = amount - redistributed.values().sum::<u64>();
+        self.burned += burned;
+
+        let jailed_until = (epoch + self.config.jail_epochs).max(self.jailed.get(&offender).copied().unwrap_or(0));
+        self.jailed.insert(offender, jailed_until);
+
+        let slash_id = self.slashes.len() as u64;
+        self.slashes.push(SlashRecord {
+            id: slash_id,
+            offender,
+            epoch,
+            evidence: evidence.key(),
+            amount,
+            burned,
+            redistributed,
+            jailed_until,
+            appeal_deadline: epoch + self.config.appeal_epochs,
+            status: SlashStatus::Applied,
+        });
+        [
+            SlashingEvent::Slashed { slash_id, offender, amount, burned },
+            SlashingEvent::Jailed { validator: offender, until_epoch: jailed_until },
+        ]
+    }
+
+    /// Appeal a slash as its offender, before the window closes
+    fn file_appeal(&mut self, caller: u64, slash_id: u64, epoch: u64) -> Result<SlashingEvent> {
+        let slash = self.slashes.get_mut(slash_id as usize).ok_or(SlashingError::UnknownSlash(slash_id))?;
+        if slash.offender != caller {
+            return Err(SlashingError::NotOffender(caller));
+        }
+        if slash.status != SlashStatus::Applied {
+            return Err(SlashingError::AlreadyAppealed(slash_id));
+        }
+        if epoch > slash.appeal_deadline {
+            return Err(SlashingError::AppealWindowClosed { slash_id, deadline: slash.appeal_deadline });
+        }
+        slash.status = SlashStatus::Appealed;
+        Ok(SlashingEvent::AppealFiled { slash_id })
+    }
+
+    /// Decide an appeal (governance only). Overturning returns the slashed
+    /// stake to the offender, from the burn and from the validators it was
+    /// redistributed to, and lifts the jail.
+    fn resolve_appeal(&mut self, caller: u64, slash_id: u64, overturn: bool) -> Result<SlashingEvent> {
+        if !self.governance.contains(&caller) {
+            return Err(SlashingError::Unauthorized(caller));
+        }
+        let slash = self.slashes.get_mut(slash_id as usize).ok_or(SlashingError::UnknownSlash(slash_id))?;
+        if slash.status != SlashStatus::Appealed {
+            return Err(SlashingError::NotAppealed(slash_id));
+        }
+        if !overturn {
+            slash.status = SlashStatus::Upheld;
+            return Ok(SlashingEvent::AppealResolved { slash_id, overturned: false });
+        }
+
+        slash.status = SlashStatus::Overturned;
+        let slash = slash.clone();
+        let mut restored = slash.burned;
+        self.burned -= slash.burned;
+        for (id, share) in &slash.redistributed {
+            let stake = self.stakes.entry(*id).or_default();
+            let clawback = (*share).min(*stake);
+            *stake -= clawback;
+            restored += clawback;
+        }
+        *self.stakes.entry(slash.offender).or_default() += restored;
+
+        // Jails from the offender's other standing slashes still apply
+        let other_jail = self
+            .slashes
+            .iter()
+            .filter(|s| s.offender == slash.offender && s.status != SlashStatus::Overturned)
+            .map(|s| s.jailed_until)
+            .max();
+        match other_jail {
+            Some(until) => self.jailed.insert(slash.offender, until),
+            None => self.jailed.remove(&slash.offender),
+        };
+        Ok(SlashingEvent::AppealResolved { slash_id, overturned: true })
+    }
+
+    /// Validator set of `epoch`: the bonded validators with stake left and
+    /// not jailed in it, weighted by stake. A member's key in `current`
+    /// (e.g. after a rotation) wins over its bonded one. None when the
+    /// slasher was built without keys (see `from_validator_set`).
+    pub fn validator_set(&self, epoch: u64, current: &HashMap<u64, BLSPublicKey>) -> Option<ValidatorSet> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let members = self.keys.iter().filter_map(|(id, bonded)| {
+            let stake = self.stake(*id);
+            let pk = current.get(id).unwrap_or(bonded);
+            (stake > 0 && !self.is_jailed(*id, epoch)).then(|| (pk.clone(), stake))
+        });
+        Some(ValidatorSet::weighted(epoch, members))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{threshold_sign, BLSKeyPair, Hash};
+    use crate::hotstuff::evidence::Misbehavior;
+    use crate::hotstuff::types::{vote_message, MessageType, Vote};
+
+    fn double_vote(offender: &BLSKeyPair, reporter: &BLSKeyPair, view: u64) -> Evidence {
+        let vote = |block: [u8; 32]| {
+            let block_hash = Hash::new(block);
+            let data = vote_message(&MessageType::Prepare, &block_hash, view);
+            let sig = threshold_sign(&offender.secret_key, &data);
+            Vote::new(MessageType::Prepare, block_hash, view, offender.public_key.clone(), sig)
+        };
+        Evidence::new(Misbehavior::DoubleVote { first: Box::new(vote([1; 32])), second: Box::new(vote([2; 32])) }, reporter)
+    }
+
+    fn setup(penalty: SlashPenalty) -> (Vec<BLSKeyPair>, ValidatorSet, Slasher) {
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let set = ValidatorSet::weighted(0, keys.iter().map(|k| (k.public_key.clone(), 1_000)));
+        let config = SlashingConfig { slash_bps: 1_000, jail_epochs: 2, appeal_epochs: 1, penalty };
+        let slasher = Slasher::from_validator_set(config, &set).with_governance([0]);
+        (keys, set, slasher)
+    }
+
+    fn block_with(txs: Vec<Vec<u8>>, proposer: &BLSKeyPair) -> Block {
+        Block::new(Hash::genesis(), 1, 1, None, txs, proposer.public_key.clone())
+    }
+
+    #[test]
+    fn test_evidence_in_block_slashes_and_jails() {
+        let (keys, set, mut slasher) = setup(SlashPenalty::Burn);
+        let evidence = double_vote(&keys[2], &keys[0], 7);
+        // Reported by an outsider: not verifiable against the set
+        let outsider = double_vote(&keys[2], &BLSKeyPair::with_id(9), 8);
+        let block = block_with(
+            vec![b"transfer".to_vec(), evidence.to_transaction(), evidence.to_transaction(), outsider.to_transaction()],
+            &keys[0],
+        );
+
+        let events = slasher.process_block(&block, 3, &set.keys);
+        assert_eq!(
+            events,
+            vec![
+                SlashingEvent::Slashed { slash_id: 0, offender: 2, amount: 100, burned: 100 },
+                SlashingEvent::Jailed { validator: 2, until_epoch: 5 },
+            ]
+        );
+        assert_eq!(slasher.stake(2), 900);
+        assert_eq!(slasher.burned(), 100);
+        // Already slashed for, even in a later block
+        assert!(slasher.process_block(&block, 4, &set.keys).is_empty());
+
+        // Jailed validators leave the set until released
+        assert!(!slasher.validator_set(5, &set.keys).unwrap().keys.contains_key(&2));
+        let released = slasher.validator_set(6, &set.keys).unwrap();
+        assert_eq!(released.weight(2), 900);
+        assert_eq!(released.total_weight(), 3_900);
+    }
+
+    #[test]
+    fn test_double_proposal_not_slashed() {
+        let (keys, set, mut slasher) = setup(SlashPenalty::Burn);
+        // Blocks are unsigned, so validator 0 can frame validator 2
+        let framed = Misbehavior::DoubleProposal {
+            first: Box::new(block_with(vec![b"a".to_vec()], &keys[2])),
+            second: Box::new(block_with(vec![b"b".to_vec()], &keys[2])),
+        };
+        let evidence = Evidence::new(framed, &keys[0]);
+        assert!(evidence.verify(&keys[0].public_key));
+        let block = block_with(vec![evidence.to_transaction()], &keys[0]);
+        assert!(slasher.process_block(&block, 0, &set.keys).is_empty());
+        assert_eq!(slasher.stake(2), 1_000);
+    }
+
+    #[test]
+    fn test_redistribution_and_appeals() {
+        let (keys, set, mut slasher) = setup(SlashPenalty::Redistribute);
+        let block = block_with(vec![double_vote(&keys[1], &keys[0], 2).to_transaction()], &keys[0]);
+        slasher.process_block(&block, 0, &set.keys);
+        // 100 split over three validators of equal stake; 1 of dust burned
+        assert_eq!(slasher.stake(0), 1_033);
+        assert_eq!(slasher.burned(), 1);
+
+        assert_eq!(slasher.file_appeal(2, 0, 1), Err(SlashingError::NotOffender(2)));
+        assert_eq!(
+            slasher.file_appeal(1, 0, 2),
+            Err(SlashingError::AppealWindowClosed { slash_id: 0, deadline: 1 })
+        );
+        assert_eq!(slasher.resolve_appeal(0, 0, true), Err(SlashingError::NotAppealed(0)));
+        assert_eq!(slasher.file_appeal(1, 0, 1), Ok(SlashingEvent::AppealFiled { slash_id: 0 }));
+        assert_eq!(slasher.file_appeal(1, 0, 1), Err(SlashingError::AlreadyAppealed(0)));
+        assert_eq!(slasher.resolve_appeal(1, 0, true), Err(SlashingError::Unauthorized(1)));
+
+        // Overturned: stake and set membership restored
+        assert_eq!(
+            slasher.resolve_appeal(0, 0, true),
+            Ok(SlashingEvent::AppealResolved { slash_id: 0, overturned: true })
+        );
+        assert_eq!(slasher.slash(0).unwrap().status, SlashStatus::Overturned);
+        assert_eq!((0..4).map(|id| slasher.stake(id)).collect::<Vec<_>>(), vec![1_000; 4]);
+        assert_eq!(slasher.burned(), 0);
+        assert!(!slasher.is_jailed(1, 1));
+        assert_eq!(slasher.validator_set(1, &set.keys).unwrap().total_weight(), 4_000);
+    }
+
+    #[test]
+    fn test_appeals_execute_as_committed_transactions() {
+        let (keys, set, mut slasher) = setup(SlashPenalty::Burn);
+        let block = block_with(vec![double_vote(&keys[1], &keys[0], 2).to_transaction()], &keys[0]);
+        slasher.process_block(&block, 0, &set.keys);
+        // The jailed offender is out of the epoch's keys but may still appeal
+        let mut epoch_keys = set.keys.clone();
+        epoch_keys.remove(&1);
+
+        let file = Appeal::new(AppealAction::File { slash_id: 0 }, &keys[1]);
+        let overturn = |signer: &BLSKeyPair| Appeal::new(AppealAction::Resolve { slash_id: 0, overturn: true }, signer);
+        // Forged: claims validator 0's approval under validator 2's key
+        let mut forged = overturn(&keys[2]);
+        forged.signature.validator_id = 0;
+        let txs = vec![
+            overturn(&keys[0]).to_transaction(),
+            file.to_transaction(),
+            overturn(&keys[2]).to_transaction(),
+            forged.to_transaction(),
+            overturn(&keys[0]).to_transaction(),
+        ];
+        // Executed in order: the first decision predates the appeal
+        let events = slasher.process_block(&block_with(txs, &keys[2]), 1, &epoch_keys);
+        assert_eq!(
+            events,
+            vec![
+                SlashingEvent::AppealFiled { slash_id: 0 },
+                SlashingEvent::AppealResolved { slash_id: 0, overturned: true },
+            ]
+        );
+        assert_eq!(slasher.stake(1), 1_000);
+        assert_eq!(slasher.burned(), 0);
+
+        // The whole state, governance included, survives a round trip
+        let mut restored: Slasher = bincode::deserialize(&bincode::serialize(&slasher).unwrap()).unwrap();
+        assert_eq!(restored.slashes(), slasher.slashes());
+        assert_eq!(restored.validator_set(1, &set.keys), slasher.validator_set(1, &set.keys));
+        assert_eq!(restored.resolve_appeal(0, 0, true), Err(SlashingError::NotAppealed(0)));
+        assert_eq!(restored.resolve_appeal(2, 0, true), Err(SlashingError::Unauthorized(2)));
+    }
+}
@@ -0,0 +1,92 @@
+// Block timestamp rules
+//
+// Leaders stamp proposals with their wall clock, and applications use the
+// timestamp as block time (funding intervals, order expiry), so a Byzantine
+// leader must not be able to move it freely. Validators accept a proposal
+// only if its timestamp is
+//
+// - not earlier than the median timestamp of its most recent ancestors, so
+//   block time never runs backwards and one skewed leader cannot drag it
+//   back, and
+// - not further ahead of the local clock than the configured skew, so it
+//   cannot be pushed into the future.
+//
+// Honest leaders never propose below the median, even when their own clock
+// lags behind the chain.
+
+use std::time::Duration;
+
+/// Timestamp validation parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampConfig {
+    /// How far ahead of the local clock a proposal may be
+    pub max_future_skew: Duration,
+    /// Ancestors whose median timestamp bounds a proposal from below
+    pub median_window: usize,
+}
+
+impl Default for TimestampConfig {
+    fn default() -> Self {
+        Self {
+            max_future_skew: Duration::from_secs(10),
+            median_window: 11,
+        }
+    }
+}
+
+/// Median of the ancestors' timestamps, nearest ancestor first; 0 without
+/// ancestors
+pub fn median_time_past(ancestors: &[u64]) -> u64 {
+    if ancestors.is_empty() {
+        return 0;
+    }
+    let mut sorted = ancestors.to_vec();
+    sorted.sort_unstable();
+    sorted[sorted.len() / 2]
+}
+
+/// Check a proposal's timestamp against its ancestors' and the local clock
+/// (milliseconds since the Unix epoch)
+pub fn validate_timestamp(config: &TimestampConfig, timestamp: u64, ancestors: &[u64], now: u64) -> Result<(), String> {
+    let max_skew = config.max_future_skew.as_millis() as u64;
+    if timestamp > now.saturating_add(max_skew) {
+        return Err(format!(
+            "timestamp {} is {} ms ahead of the local clock (max {} ms)",
+            timestamp,
+            timestamp - now,
+            max_skew
+        ));
+    }
+    let median = median_time_past(ancestors);
+    if timestamp < median {
+        return Err(format!("timestamp {} is before the median of its ancestors {}", timestamp, median));
+    }
+    Ok(())
+}
+
+/// Wall-clock time in milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_bounds() {
+        let config = TimestampConfig::default();
+        let ancestors = [1_000, 5_000, 2_000, 9_000, 3_000];
+        assert_eq!(median_time_past(&ancestors), 3_000);
+
+        assert!(validate_timestamp(&config, 3_000, &ancestors, 20_000).is_ok());
+        assert!(validate_timestamp(&config, 2_999, &ancestors, 20_000).is_err());
+        // Up to the skew ahead of our clock is tolerated
+        assert!(validate_timestamp(&config, 30_000, &ancestors, 20_000).is_ok());
+        assert!(validate_timestamp(&config, 30_001, &ancestors, 20_000).is_err());
+        assert!(validate_timestamp(&config, 0, &[], 0).is_ok());
+    }
+}
@@ -0,0 +1,32 @@
+// Stateless transaction validation
+//
+// Transactions are opaque bytes to consensus, so a Byzantine leader could
+// otherwise fill blocks with payloads the application can never execute and
+// have honest validators commit them. A TxValidator supplied by the
+// application checks each transaction on its own (encoding, size, chain ID,
+// signature); the leader's proposal builder drops transactions that fail it,
+// and validators refuse to vote for proposals containing any.
+//
+// Evidence transactions are produced and checked by the engine itself and
+// never reach the validator.
+
+use super::evidence::EVIDENCE_PREFIX;
+
+/// Application-defined check of a single transaction, independent of state
+pub trait TxValidator: Send + Sync {
+    /// Reject a malformed transaction with a reason
+    fn validate(&self, tx: &[u8]) -> Result<(), String>;
+}
+
+/// Check every application transaction of a block payload
+pub fn validate_payload(validator: &dyn TxValidator, transactions: &[Vec<u8>]) -> Result<(), String> {
+    transactions
+        .iter()
+        .enumerate()
+        .filter(|(_, tx)| !tx.starts_with(EVIDENCE_PREFIX))
+        .try_for_each(|(index, tx)| {
+            validator
+                .validate(tx)
+                .map_err(|reason| format!("transaction {}: {}", index, reason))
+        })
+}
@@ -4,6 +4,7 @@
 /// Based on HotStuff: BFT Consensus in the Lens of Blockchain (Algorithm 2)
 
 use crate::crypto::{BLSSignature, BLSPublicKey};
+use crate::pacemaker::TimeoutCertificate;
 use std::collections::HashMap;
 
 // Re-export Hash for convenience
@@ -11,6 +12,7 @@ pub use crate::crypto::Hash;
 
 /// Message types in HotStuff protocol
 #[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "metrics", derive(prometheus_client::encoding::EncodeLabelValue))]
 pub enum MessageType {
     NewView,
     Prepare,
@@ -19,6 +21,16 @@ pub enum MessageType {
     Decide,
 }
 
+/// Message signed by a vote: the phase, block hash and view
+/// 
+/// Binding the phase keeps a vote from being replayed as another phase's.
+pub fn vote_message(msg_type: &MessageType, block_hash: &Hash, view: u64) -> Vec<u8> {
+    let mut data = vec![msg_type.clone() as u8];
+    data.extend_from_slice(block_hash.as_bytes());
+    data.extend_from_slice(&view.to_le_bytes());
+    data
+}
+
 /// Block structure
 /// Contains parent hash, height, view number, justify QC, and transactions
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -29,6 +41,21 @@ pub struct Block {
     pub justify: Option<QuorumCertificate>,
     pub transactions: Vec<Vec<u8>>,
     pub proposer: BLSPublicKey,
+    /// Timeout certificate for the previous view, when the proposer
+    /// entered this view after a timeout
+    #[serde(default)]
+    pub timeout_cert: Option<Box<TimeoutCertificate>>,
+    /// Proposal time (ms since the Unix epoch), checked against the
+    /// ancestors and the local clock before voting
+    #[serde(default)]
+    pub timestamp: u64,
+    /// Base fee per unit of gas the block charges, checked by the state
+    /// machine when the block is applied
+    #[serde(default)]
+    pub base_fee: u128,
+    /// Account the proposer's priority fees are paid to
+    #[serde(default)]
+    pub fee_recipient: [u8; 20],
 }
 
 impl Block {
@@ -48,9 +75,33 @@ impl Block {
             justify,
             transactions,
             proposer,
+            timeout_cert: None,
+            timestamp: 0,
+            base_fee: 0,
+            fee_recipient: [0; 20],
         }
     }
 
+    /// Stamp the block with its proposal time
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Set the base fee the block charges and the account its priority
+    /// fees go to
+    pub fn with_fees(mut self, base_fee: u128, fee_recipient: [u8; 20]) -> Self {
+        self.base_fee = base_fee;
+        self.fee_recipient = fee_recipient;
+        self
+    }
+
+    /// Attach the timeout certificate justifying this block's view
+    pub fn with_timeout_cert(mut self, tc: TimeoutCertificate) -> Self {
+        self.timeout_cert = Some(Box::new(tc));
+        self
+    }
+
     /// Create genesis block
     pub fn genesis(proposer: BLSPublicKey) -> Self {
         Self {
@@ -60,28 +111,46 @@ impl Block {
             justify: None,
             transactions: vec![],
             proposer,
+            timeout_cert: None,
+            timestamp: 0,
+            base_fee: 0,
+            fee_recipient: [0; 20],
         }
     }
 
-    /// Compute hash of this block
+    /// Compute hash of this block, which is the hash of its header
     pub fn hash(&self) -> Hash {
-        use crate::crypto::hash;
-        let mut data = Vec::new();
-        data.extend_from_slice(self.parent.as_bytes());
-        data.extend_from_slice(&self.height.to_le_bytes());
-        data.extend_from_slice(&self.view.to_le_bytes());
-        
-        // Include justify QC if present
-        if let Some(ref qc) = self.justify {
-            data.extend_from_slice(qc.block_hash.as_bytes());
-        }
-        
-        // Include transactions
-        for tx in &self.transactions {
-            data.extend_from_slice(tx);
+        self.header().hash()
+    }
+
+    /// Header committing to this block's contents
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            parent: self.parent,
+            height: self.height,
+            view: self.view,
+            justify: self.justify.as_ref().map(|qc| qc.block_hash),
+            transactions_root: crate::crypto::merkle_root(&self.transactions),
+            transaction_count: self.transactions.len() as u64,
+            timeout_view: self.timeout_cert.as_ref().map(|tc| tc.view),
+            timestamp: self.timestamp,
+            base_fee: self.base_fee,
+            fee_recipient: self.fee_recipient,
         }
-        
-        hash(&data)
+    }
+
+    /// Proof that transaction `index` is part of this block, against the
+    /// header's transactions root
+    pub fn prove_transaction(&self, index: usize) -> Option<crate::crypto::MerkleProof> {
+        crate::crypto::MerkleProof::new(&self.transactions, index)
+    }
+
+    /// Randomness beacon for this block, from the QC it carries
+    ///
+    /// The justify QC is formed before the block is proposed, so the proposer
+    /// cannot influence the value. Blocks without a QC (genesis) have none.
+    pub fn randomness(&self) -> Option<Hash> {
+        self.justify.as_ref().map(|qc| qc.randomness())
     }
 
     /// Check if this block extends from another block
@@ -111,6 +180,67 @@ impl Block {
     }
 }
 
+/// Block header
+/// Commits to a block's transactions by their Merkle root, so light clients
+/// can verify blocks and transaction inclusion without the transactions
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BlockHeader {
+    pub parent: Hash,
+    pub height: u64,
+    pub view: u64,
+    /// Hash of the block certified by the justify QC
+    pub justify: Option<Hash>,
+    pub transactions_root: Hash,
+    pub transaction_count: u64,
+    /// View of the timeout certificate the block carries
+    pub timeout_view: Option<u64>,
+    /// Proposal time (ms since the Unix epoch)
+    pub timestamp: u64,
+    /// Base fee per unit of gas
+    #[serde(default)]
+    pub base_fee: u128,
+    /// Account priority fees are paid to
+    #[serde(default)]
+    pub fee_recipient: [u8; 20],
+}
+
+impl BlockHeader {
+    /// Compute the block hash
+    pub fn hash(&self) -> Hash {
+        use crate::crypto::hash;
+        let mut data = Vec::new();
+        data.extend_from_slice(self.parent.as_bytes());
+        data.extend_from_slice(&self.height.to_le_bytes());
+        data.extend_from_slice(&self.view.to_le_bytes());
+        
+        // Optional fields are tagged so their presence is unambiguous
+        match self.justify {
+            Some(ref justify) => {
+                data.push(1);
+                data.extend_from_slice(justify.as_bytes());
+            }
+            None => data.push(0),
+        }
+        
+        data.extend_from_slice(self.transactions_root.as_bytes());
+        data.extend_from_slice(&self.transaction_count.to_le_bytes());
+        
+        match self.timeout_view {
+            Some(view) => {
+                data.push(1);
+                data.extend_from_slice(&view.to_le_bytes());
+            }
+            None => data.push(0),
+        }
+        
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data.extend_from_slice(&self.base_fee.to_le_bytes());
+        data.extend_from_slice(&self.fee_recipient);
+        
+        hash(&data)
+    }
+}
+
 /// Quorum Certificate (QC)
 /// Represents a collection of n-f votes combined into a threshold signature
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -119,6 +249,9 @@ pub struct QuorumCertificate {
     pub block_hash: Hash,
     pub view: u64,
     pub signature: BLSSignature,
+    /// Validator IDs whose votes were combined into the signature
+    #[serde(default)]
+    pub signers: Vec<u64>,
 }
 
 impl QuorumCertificate {
@@ -134,18 +267,54 @@ impl QuorumCertificate {
             block_hash,
             view,
             signature,
+            signers: Vec::new(),
         }
     }
 
+    /// Record the validators whose votes formed this QC
+    pub fn with_signers(mut self, mut signers: Vec<u64>) -> Self {
+        signers.sort_unstable();
+        signers.dedup();
+        self.signers = signers;
+        self
+    }
+
     /// Verify QC signature
     pub fn verify(&self, public_keys: &[BLSPublicKey]) -> Result<bool, String> {
         use crate::crypto::bls::threshold_verify;
-        let mut data = Vec::new();
-        data.extend_from_slice(self.block_hash.as_bytes());
-        data.extend_from_slice(&self.view.to_le_bytes());
+        let data = vote_message(&self.msg_type, &self.block_hash, self.view);
         threshold_verify(&data, &self.signature, public_keys)
             .map_err(|e| format!("QC verification failed: {:?}", e))
     }
+
+    /// Verify the QC against the validator set's keys
+    ///
+    /// Checks for a quorum of distinct registered signers and the
+    /// aggregate signature over their keys.
+    pub fn verify_quorum(&self, validator_keys: &HashMap<u64, BLSPublicKey>, quorum_size: usize) -> Result<(), String> {
+        if self.signers.len() < quorum_size {
+            return Err(format!("QC has {} signers, need {}", self.signers.len(), quorum_size));
+        }
+        if self.signers.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("QC signers not sorted or duplicated".to_string());
+        }
+        let keys = self
+            .signers
+            .iter()
+            .map(|id| validator_keys.get(id).cloned().ok_or_else(|| format!("Unknown QC signer {}", id)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !self.verify(&keys)? {
+            return Err("QC signature verification failed".to_string());
+        }
+        Ok(())
+    }
+
+    /// Beacon randomness derived from this QC's threshold signature
+    ///
+    /// Check `verify` first when the QC comes from an untrusted source.
+    pub fn randomness(&self) -> Hash {
+        crate::crypto::derive_randomness(&self.block_hash, &self.signature)
+    }
 }
 
 /// Vote structure
@@ -232,6 +401,53 @@ mod tests {
         assert!(genesis.justify.is_none());
     }
 
+    #[test]
+    fn test_block_randomness_from_justify_qc() {
+        use crate::crypto::{threshold_combine, threshold_sign, BLSSecretKey};
+
+        let validators: Vec<_> = (0..4).map(BLSSecretKey::generate).collect();
+        let parent = Hash::new([7u8; 32]);
+        let data = vote_message(&MessageType::Prepare, &parent, 1);
+        let partials: Vec<_> = validators.iter().map(|v| threshold_sign(v, &data)).collect();
+        let qc = QuorumCertificate::new(
+            MessageType::Prepare,
+            parent,
+            1,
+            threshold_combine(&data, &partials, 3).unwrap(),
+        );
+
+        let keys: Vec<_> = validators[..3].iter().map(|v| v.public_key()).collect();
+        assert!(qc.verify(&keys).unwrap());
+
+        let proposer = BLSKeyPair::generate().public_key;
+        let block = Block::new(parent, 2, 2, Some(qc.clone()), vec![], proposer.clone());
+        assert_eq!(block.randomness(), Some(qc.randomness()));
+        assert!(Block::genesis(proposer).randomness().is_none());
+    }
+
+    #[test]
+    fn test_verify_quorum_rejects_duplicate_signers() {
+        use crate::crypto::{threshold_combine, threshold_sign};
+
+        let validators: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let keys: HashMap<_, _> = validators.iter().map(|v| (v.public_key.validator_id(), v.public_key.clone())).collect();
+        let block_hash = Hash::new([7u8; 32]);
+        let data = vote_message(&MessageType::Prepare, &block_hash, 1);
+
+        let partials: Vec<_> = validators[..3].iter().map(|v| threshold_sign(&v.secret_key, &data)).collect();
+        let qc = QuorumCertificate::new(MessageType::Prepare, block_hash, 1, threshold_combine(&data, &partials, 3).unwrap())
+            .with_signers(vec![0, 1, 2]);
+        assert!(qc.verify_quorum(&keys, 3).is_ok());
+
+        // One validator's vote counted three times
+        let repeated = vec![partials[1].clone(); 3];
+        let mut forged = QuorumCertificate::new(MessageType::Prepare, block_hash, 1, threshold_combine(&data, &repeated, 3).unwrap());
+        forged.signers = vec![1, 1, 1];
+        let key = validators[1].public_key.clone();
+        assert!(forged.verify(&[key.clone(), key.clone(), key]).unwrap());
+        assert!(forged.verify_quorum(&keys, 3).is_err());
+    }
+
     #[test]
     fn test_block_hash_consistency() {
         let keypair = BLSKeyPair::generate();
@@ -247,6 +463,10 @@ mod tests {
         let hash1 = block.hash();
         let hash2 = block.hash();
         assert_eq!(hash1, hash2);
+        
+        // The fee fields are committed to by the header
+        assert_ne!(block.clone().with_fees(1, [0; 20]).hash(), hash1);
+        assert_ne!(block.with_fees(0, [1; 20]).hash(), hash1);
     }
 
     #[test]
@@ -13,5 +13,9 @@ pub mod network;
 pub mod storage;
 pub mod sync;
 pub mod checkpoint;
+pub mod light_client;
+pub mod watchdog;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 
 pub use crypto::{BLSSignature, BLSPublicKey, BLSSecretKey, Hash};
@@ -0,0 +1,541 @@
+// Light client following the chain by block headers and commit QCs
+//
+// Starting from a trusted header and validator set, each next header is
+// accepted only with a Commit QC over its hash signed by a quorum of the
+// current validator set, so the client learns committed headers without
+// executing or even downloading blocks. Validator set changes are
+// transactions carrying the encoded next set: once one is proven included
+// in the latest accepted header, headers after it are checked against the
+// new set. Every set is kept with the height it took effect at, so QCs on
+// old headers are still checked against the set that signed them. Wallets
+// and bridges check transactions against the accepted headers' transaction
+// roots with `verify_transaction_inclusion`. Committed key rotations are
+// held until their epoch's set change, which must carry the rotated keys.
+
+use crate::crypto::{BLSPublicKey, Hash, KeyRotation, MerkleProof};
+use crate::hotstuff::types::{Block, BlockHeader, MessageType, QuorumCertificate};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// Prefix marking a transaction as a validator set change
+pub const VALIDATOR_SET_PREFIX: &[u8] = b"openliquid/validator-set/1";
+
+/// Default number of headers kept before the oldest are pruned
+pub const DEFAULT_MAX_HEADERS: usize = 10_000;
+
+/// Light client errors
+#[derive(Error, Debug, PartialEq)]
+pub enum LightClientError {
+    #[error("Expected header at height {expected}, got {got}")]
+    UnexpectedHeight { expected: u64, got: u64 },
+
+    #[error("Header at height {0} does not extend the latest header")]
+    ParentMismatch(u64),
+
+    #[error("Invalid commit QC: {0}")]
+    InvalidCommitQc(String),
+
+    #[error("No header at height {0}")]
+    UnknownHeader(u64),
+
+    #[error("Invalid inclusion proof: {0}")]
+    InvalidProof(String),
+
+    #[error("Invalid validator set change: {0}")]
+    InvalidValidatorSet(String),
+
+    #[error("Invalid key rotation: {0}")]
+    InvalidKeyRotation(String),
+}
+
+pub type Result<T> = std::result::Result<T, LightClientError>;
+
+/// Validator set of an epoch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorSet {
+    pub epoch: u64,
+    /// Public keys by validator ID
+    pub keys: HashMap<u64, BLSPublicKey>,
+    /// Voting weights by validator ID; validators not listed weigh 1
+    pub weights: HashMap<u64, u64>,
+}
+
+impl ValidatorSet {
+    /// Create the set of equally weighted `keys` for `epoch`
+    pub fn new(epoch: u64, keys: impl IntoIterator<Item = BLSPublicKey>) -> Self {
+        Self::weighted(epoch, keys.into_iter().map(|pk| (pk, 1)))
+    }
+
+    /// Create the set of `(key, weight)` members for `epoch`
+    pub fn weighted(epoch: u64, members: impl IntoIterator<Item = (BLSPublicKey, u64)>) -> Self {
+        let mut set = Self {
+            epoch,
+            keys: HashMap::new(),
+            weights: HashMap::new(),
+        };
+        for (pk, weight) in members {
+            set.weights.insert(pk.validator_id(), weight);
+            set.keys.insert(pk.validator_id(), pk);
+        }
+        set
+    }
+
+    /// Members with their weights, by validator ID
+    pub fn members(&self) -> Vec<(BLSPublicKey, u64)> {
+        let mut members: Vec<(BLSPublicKey, u64)> =
+            self.keys.iter().map(|(id, pk)| (pk.clone(), self.weight(*id))).collect();
+        members.sort_by_key(|(pk, _)| pk.validator_id());
+        members
+    }
+
+    /// Voting weight of a validator (0 outside the set)
+    pub fn weight(&self, validator_id: u64) -> u64 {
+        if !self.keys.contains_key(&validator_id) {
+            return 0;
+        }
+        self.weights.get(&validator_id).copied().unwrap_or(1)
+    }
+
+    /// Combined weight of the set
+    pub fn total_weight(&self) -> u64 {
+        self.keys.keys().map(|id| self.weight(*id)).sum()
+    }
+
+    /// Weight a QC's signers need (n - f, by weight)
+    pub fn quorum_weight(&self) -> u64 {
+        let total = self.total_weight();
+        total - total.saturating_sub(1) / 3
+    }
+
+    /// Check that a quorum of the set by weight signed `qc`
+    pub fn verify_qc(&self, qc: &QuorumCertificate) -> std::result::Result<(), String> {
+        // Each signer's weight counts once
+        if qc.signers.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("QC signers not sorted or duplicated".to_string());
+        }
+        let signed: u64 = qc.signers.iter().map(|id| self.weight(*id)).sum();
+        if signed < self.quorum_weight() {
+            return Err(format!("QC signers weigh {}, need {}", signed, self.quorum_weight()));
+        }
+        // Weight is checked above; this checks the signers' aggregate signature
+        qc.verify_quorum(&self.keys, 0)
+    }
+
+    /// Check a key rotation against the set: it must replace a member's
+    /// current key, from a later epoch
+    pub fn verify_rotation(&self, rotation: &KeyRotation) -> std::result::Result<(), String> {
+        let current = self
+            .keys
+            .get(&rotation.validator_id)
+            .ok_or_else(|| format!("validator {} is not in epoch {}", rotation.validator_id, self.epoch))?;
+        if rotation.effective_epoch <= self.epoch {
+            return Err(format!(
+                "effective epoch {} is not after epoch {}",
+                rotation.effective_epoch, self.epoch
+            ));
+        }
+        if !rotation.verify(current) {
+            return Err(format!("bad signature from validator {}", rotation.validator_id));
+        }
+        Ok(())
+    }
+
+    /// The next epoch's set: same members and weights, with the rotations
+    /// effective from it applied. Rotations for later epochs are ignored.
+    pub fn apply_rotations<'a>(
+        &self,
+        rotations: impl IntoIterator<Item = &'a KeyRotation>,
+    ) -> std::result::Result<ValidatorSet, String> {
+        let mut next = self.clone();
+        next.epoch = self.epoch + 1;
+        for rotation in rotations {
+            if rotation.effective_epoch != next.epoch {
+                continue;
+            }
+            self.verify_rotation(rotation)?;
+            if next.keys[&rotation.validator_id] != self.keys[&rotation.validator_id] {
+                return Err(format!("validator {} rotates twice", rotation.validator_id));
+            }
+            next.keys.insert(rotation.validator_id, rotation.new_key.clone());
+        }
+        Ok(next)
+    }
+
+    /// Encode the set as a validator set change transaction
+    pub fn to_transaction(&self) -> Vec<u8> {
+        let mut tx = Vec::from(VALIDATOR_SET_PREFIX);
+        tx.extend(bincode::serialize(&(self.epoch, self.members())).expect("validator set serializes"));
+        tx
+    }
+
+    /// Decode a validator set change transaction
+    pub fn from_transaction(tx: &[u8]) -> Option<Self> {
+        let encoded = tx.strip_prefix(VALIDATOR_SET_PREFIX)?;
+        let (epoch, members): (u64, Vec<(BLSPublicKey, u64)>) = bincode::deserialize(encoded).ok()?;
+        Some(Self::weighted(epoch, members))
+    }
+}
+
+/// Validator sets by the height they took effect at
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidatorHistory {
+    sets: BTreeMap<u64, ValidatorSet>,
+}
+
+impl ValidatorHistory {
+    /// History starting with `validators` at `height`
+    pub fn new(height: u64, validators: ValidatorSet) -> Self {
+        Self {
+            sets: BTreeMap::from([(height, validators)]),
+        }
+    }
+
+    /// Record `validators` as active from `height` on
+    pub fn insert(&mut self, height: u64, validators: ValidatorSet) {
+        self.sets.insert(height, validators);
+    }
+
+    /// Set active at `height`
+    pub fn at(&self, height: u64) -> Option<&ValidatorSet> {
+        self.sets.range(..=height).next_back().map(|(_, set)| set)
+    }
+
+    /// Most recent set
+    pub fn latest(&self) -> Option<&ValidatorSet> {
+        self.sets.values().next_back()
+    }
+
+    /// Check `qc` against the set active at `height`
+    pub fn verify_qc_at(&self, height: u64, qc: &QuorumCertificate) -> std::result::Result<(), String> {
+        self.at(height)
+            .ok_or_else(|| format!("No validator set for height {}", height))?
+            .verify_qc(qc)
+    }
+}
+
+impl FromIterator<(u64, ValidatorSet)> for ValidatorHistory {
+    fn from_iter<I: IntoIterator<Item = (u64, ValidatorSet)>>(iter: I) -> Self {
+        Self {
+            sets: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Proof that a transaction is part of the block at `height`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionInclusionProof {
+    pub height: u64,
+    pub transaction: Vec<u8>,
+    pub proof: MerkleProof,
+}
+
+impl TransactionInclusionProof {
+    /// Prove that `block`'s transaction `index` is included in it
+    pub fn new(block: &Block, index: usize) -> Option<Self> {
+        Some(Self {
+            height: block.height,
+            transaction: block.transactions.get(index)?.clone(),
+            proof: block.prove_transaction(index)?,
+        })
+    }
+}
+
+/// Header-only client of the committed chain
+#[derive(Debug)]
+pub struct LightClient {
+    validators: ValidatorHistory,
+    /// Committed key rotations by validator ID, until their epoch starts
+    pending_rotations: BTreeMap<u64, KeyRotation>,
+    /// Accepted headers by height
+    headers: BTreeMap<u64, BlockHeader>,
+    max_headers: usize,
+}
+
+impl LightClient {
+    /// Start from a trusted header and the validator set that certifies
+    /// the headers after it
+    pub fn new(validators: ValidatorSet, trusted: BlockHeader) -> Self {
+        Self {
+            validators: ValidatorHistory::new(trusted.height + 1, validators),
+            pending_rotations: BTreeMap::new(),
+            headers: BTreeMap::from([(trusted.height, trusted)]),
+            max_headers: DEFAULT_MAX_HEADERS,
+        }
+    }
+
+    /// Keep at most `max_headers` headers (at least one)
+    pub fn with_max_headers(mut self, max_headers: usize) -> Self {
+        self.max_headers = max_headers.max(1);
+        self
+    }
+
+    /// Current validator set
+    pub fn validators(&self) -> &ValidatorSet {
+        self.validators.latest().expect("light client always holds a validator set")
+    }
+
+    /// Validator sets since the trusted header
+    pub fn validator_history(&self) -> &ValidatorHistory {
+        &self.validators
+    }
+
+    /// Latest accepted header
+    pub fn latest(&self) -> &BlockHeader {
+        self.headers.values().next_back().expect("light client always holds a header")
+    }
+
+    /// Accepted header at `height`, unless pruned
+    pub fn header(&self, height: u64) -> Option<&BlockHeader> {
+        self.headers.get(&height)
+    }
+
+    /// Accept the next header, certified by `qc`
+    pub fn submit_header(&mut self, header: BlockHeader, qc: &QuorumCertificate) -> Result<Hash> {
+        let latest = self.latest();
+        if header.height != latest.height + 1 {
+            return Err(LightClientError::UnexpectedHeight {
+                expected: latest.height + 1,
+                got: header.height,
+            });
+        }
+        if header.parent != latest.hash() {
+            return Err(LightClientError::ParentMismatch(header.height));
+        }
+
+        let block_hash = header.hash();
+        if qc.msg_type != MessageType::Commit {
+            return Err(LightClientError::InvalidCommitQc(format!("{:?} QC", qc.msg_type)));
+        }
+        if qc.block_hash != block_hash {
+            return Err(LightClientError::InvalidCommitQc("QC certifies another block".to_string()));
+        }
+        self.validators()
+            .verify_qc(qc)
+            .map_err(LightClientError::InvalidCommitQc)?;
+
+        self.headers.insert(header.height, header);
+        while self.headers.len() > self.max_headers {
+            self.headers.pop_first();
+        }
+        Ok(block_hash)
+    }
+
+    /// Check a commit QC for an accepted header against the validator set
+    /// active at its height
+    pub fn verify_commit_qc(&self, height: u64, qc: &QuorumCertificate) -> Result<()> {
+        let header = self.headers.get(&height).ok_or(LightClientError::UnknownHeader(height))?;
+        if qc.msg_type != MessageType::Commit || qc.block_hash != header.hash() {
+            return Err(LightClientError::InvalidCommitQc(format!(
+                "not a commit QC for the header at height {}",
+                height
+            )));
+        }
+        self.validators
+            .verify_qc_at(height, qc)
+            .map_err(LightClientError::InvalidCommitQc)
+    }
+
+    /// Check that a transaction is included in an accepted header
+    pub fn verify_transaction_inclusion(&self, proof: &TransactionInclusionProof) -> Result<()> {
+        let header = self
+            .headers
+            .get(&proof.height)
+            .ok_or(LightClientError::UnknownHeader(proof.height))?;
+        if !proof.proof.verify(&header.transactions_root, &proof.transaction, header.transaction_count) {
+            return Err(LightClientError::InvalidProof(format!(
+                "transaction not in block {}",
+                proof.height
+            )));
+        }
+        Ok(())
+    }
+
+    /// Key rotations committed but not yet in effect
+    pub fn pending_rotations(&self) -> impl Iterator<Item = &KeyRotation> {
+        self.pending_rotations.values()
+    }
+
+    /// Record a key rotation proven included in an accepted header. It
+    /// takes effect with the set change of its epoch; until then QCs are
+    /// checked against the current key.
+    pub fn apply_key_rotation(&mut self, rotation: &TransactionInclusionProof) -> Result<()> {
+        self.verify_transaction_inclusion(rotation)?;
+        let rotation = KeyRotation::from_transaction(&rotation.transaction)
+            .ok_or_else(|| LightClientError::InvalidKeyRotation("not a key rotation transaction".to_string()))?;
+        self.validators()
+            .verify_rotation(&rotation)
+            .map_err(LightClientError::InvalidKeyRotation)?;
+        self.pending_rotations.insert(rotation.validator_id, rotation);
+        Ok(())
+    }
+
+    /// Switch to the next validator set, proven included in the latest
+    /// header; headers after it are verified against the new set. Members
+    /// with a rotation effective from the new epoch must carry its key.
+    pub fn apply_validator_set_change(&mut self, change: &TransactionInclusionProof) -> Result<()> {
+        let latest = self.latest().height;
+        if change.height != latest {
+            return Err(LightClientError::InvalidValidatorSet(format!(
+                "change at height {} is not in the latest header {}",
+                change.height, latest
+            )));
+        }
+        self.verify_transaction_inclusion(change)?;
+        let next = ValidatorSet::from_transaction(&change.transaction)
+            .ok_or_else(|| LightClientError::InvalidValidatorSet("not a validator set transaction".to_string()))?;
+        let current = self.validators().epoch;
+        if next.epoch != current + 1 {
+            return Err(LightClientError::InvalidValidatorSet(format!(
+                "epoch {} does not follow {}",
+                next.epoch, current
+            )));
+        }
+        if next.total_weight() == 0 {
+            return Err(LightClientError::InvalidValidatorSet("empty validator set".to_string()));
+        }
+        for rotation in self.pending_rotations.values().filter(|r| r.effective_epoch == next.epoch) {
+            if next.keys.get(&rotation.validator_id).is_some_and(|pk| *pk != rotation.new_key) {
+                return Err(LightClientError::InvalidValidatorSet(format!(
+                    "validator {} is missing its rotated key",
+                    rotation.validator_id
+                )));
+            }
+        }
+        self.pending_rotations.retain(|_, r| r.effective_epoch > next.epoch);
+        self.validators.insert(latest + 1, next);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{threshold_combine, threshold_sign, BLSKeyPair};
+    use crate::hotstuff::types::vote_message;
+
+    fn commit_qc(keys: &[BLSKeyPair], block: &Block) -> QuorumCertificate {
+        let data = vote_message(&MessageType::Commit, &block.hash(), block.view);
+        let partials: Vec<_> = keys.iter().map(|k| threshold_sign(&k.secret_key, &data)).collect();
+        let signature = threshold_combine(&data, &partials, partials.len()).unwrap();
+        QuorumCertificate::new(MessageType::Commit, block.hash(), block.view, signature)
+            .with_signers(keys.iter().map(|k| k.public_key.validator_id()).collect())
+    }
+
+    #[test]
+    fn test_follows_headers_and_validator_set_changes() {
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let genesis = Block::genesis(keys[0].public_key.clone());
+        let mut client = LightClient::new(
+            ValidatorSet::new(0, keys.iter().map(|k| k.public_key.clone())),
+            genesis.header(),
+        );
+
+        let transfer = b"transfer".to_vec();
+        let block1 = Block::new(genesis.hash(), 1, 1, None, vec![b"a".to_vec(), transfer.clone()], keys[0].public_key.clone());
+        // A QC below quorum or for another phase does not commit the header
+        assert!(client.submit_header(block1.header(), &commit_qc(&keys[..2], &block1)).is_err());
+        let mut prepare = commit_qc(&keys, &block1);
+        prepare.msg_type = MessageType::Prepare;
+        assert!(client.submit_header(block1.header(), &prepare).is_err());
+        client.submit_header(block1.header(), &commit_qc(&keys[..3], &block1)).unwrap();
+
+        let proof = TransactionInclusionProof::new(&block1, 1).unwrap();
+        client.verify_transaction_inclusion(&proof).unwrap();
+        let mut forged = proof.clone();
+        forged.transaction = b"forged".to_vec();
+        assert!(client.verify_transaction_inclusion(&forged).is_err());
+
+        // Rotate to a new validator set in block 2
+        let new_keys: Vec<_> = (10..14).map(BLSKeyPair::with_id).collect();
+        let next = ValidatorSet::new(1, new_keys.iter().map(|k| k.public_key.clone()));
+        let block2 = Block::new(block1.hash(), 2, 2, None, vec![next.to_transaction()], keys[0].public_key.clone());
+        client.submit_header(block2.header(), &commit_qc(&keys, &block2)).unwrap();
+        client
+            .apply_validator_set_change(&TransactionInclusionProof::new(&block2, 0).unwrap())
+            .unwrap();
+        assert_eq!(client.validators(), &next);
+
+        // Block 3 must be certified by the new set
+        let block3 = Block::new(block2.hash(), 3, 3, None, vec![], new_keys[0].public_key.clone());
+        assert!(client.submit_header(block3.header(), &commit_qc(&keys, &block3)).is_err());
+        client.submit_header(block3.header(), &commit_qc(&new_keys, &block3)).unwrap();
+        assert_eq!(client.latest().height, 3);
+
+        // Old headers' QCs are still checked against the set that signed them
+        client.verify_commit_qc(2, &commit_qc(&keys, &block2)).unwrap();
+        assert!(client.verify_commit_qc(2, &commit_qc(&new_keys, &block2)).is_err());
+        client.verify_commit_qc(3, &commit_qc(&new_keys, &block3)).unwrap();
+
+        // Headers must extend the latest one
+        let orphan = Block::new(genesis.hash(), 4, 4, None, vec![], new_keys[0].public_key.clone());
+        assert_eq!(
+            client.submit_header(orphan.header(), &commit_qc(&new_keys, &orphan)),
+            Err(LightClientError::ParentMismatch(4))
+        );
+    }
+    #[test]
+    fn test_key_rotation_takes_effect_at_epoch_boundary() {
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let genesis = Block::genesis(keys[0].public_key.clone());
+        let current = ValidatorSet::new(0, keys.iter().map(|k| k.public_key.clone()));
+        let mut client = LightClient::new(current.clone(), genesis.header());
+
+        let rotated = BLSKeyPair::with_id(1);
+        let rotation = KeyRotation::new(&keys[1].secret_key, &rotated.secret_key, 1).unwrap();
+        let block1 = Block::new(genesis.hash(), 1, 1, None, vec![rotation.to_transaction()], keys[0].public_key.clone());
+        client.submit_header(block1.header(), &commit_qc(&keys, &block1)).unwrap();
+        client.apply_key_rotation(&TransactionInclusionProof::new(&block1, 0).unwrap()).unwrap();
+        assert_eq!(client.pending_rotations().count(), 1);
+
+        // Still epoch 0: the new key cannot sign yet
+        let new_keys = vec![keys[0].clone(), rotated.clone(), keys[2].clone(), keys[3].clone()];
+        let block2 = Block::new(block1.hash(), 2, 2, None, vec![], keys[0].public_key.clone());
+        assert!(client.submit_header(block2.header(), &commit_qc(&new_keys, &block2)).is_err());
+
+        // The epoch 1 set must carry the rotated key
+        let next = current.apply_rotations([&rotation]).unwrap();
+        assert_eq!(next.keys[&1], rotated.public_key);
+        assert_eq!(next.weight(1), 1);
+        let unrotated = current.apply_rotations([]).unwrap();
+        let changes = vec![unrotated.to_transaction(), next.to_transaction()];
+        let block2 = Block::new(block1.hash(), 2, 2, None, changes, keys[0].public_key.clone());
+        client.submit_header(block2.header(), &commit_qc(&keys, &block2)).unwrap();
+        assert!(client.apply_validator_set_change(&TransactionInclusionProof::new(&block2, 0).unwrap()).is_err());
+        client.apply_validator_set_change(&TransactionInclusionProof::new(&block2, 1).unwrap()).unwrap();
+        assert_eq!(client.pending_rotations().count(), 0);
+
+        // From epoch 1 the old key is only good for historical QCs
+        let block3 = Block::new(block2.hash(), 3, 3, None, vec![], keys[0].public_key.clone());
+        assert!(client.submit_header(block3.header(), &commit_qc(&keys, &block3)).is_err());
+        client.submit_header(block3.header(), &commit_qc(&new_keys, &block3)).unwrap();
+        client.verify_commit_qc(2, &commit_qc(&keys, &block2)).unwrap();
+
+        // Rotations need the current key and a member
+        let stale = KeyRotation::new(&keys[1].secret_key, &BLSKeyPair::with_id(1).secret_key, 2).unwrap();
+        assert!(client.validators().verify_rotation(&stale).is_err());
+        let outsider = BLSKeyPair::with_id(9);
+        let foreign = KeyRotation::new(&outsider.secret_key, &BLSKeyPair::with_id(9).secret_key, 2).unwrap();
+        assert!(client.validators().verify_rotation(&foreign).is_err());
+    }
+
+    #[test]
+    fn test_weighted_quorum() {
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let weights = [5, 1, 1, 1];
+        let set = ValidatorSet::weighted(0, keys.iter().zip(weights).map(|(k, w)| (k.public_key.clone(), w)));
+        assert_eq!(set.quorum_weight(), 6);
+        assert_eq!(ValidatorSet::from_transaction(&set.to_transaction()), Some(set.clone()));
+
+        let block = Block::genesis(keys[0].public_key.clone());
+        // The heavy validator and one other carry a quorum; the other three do not
+        set.verify_qc(&commit_qc(&keys[..2], &block)).unwrap();
+        assert!(set.verify_qc(&commit_qc(&keys[1..], &block)).is_err());
+
+        // Listing a light validator repeatedly does not add up to a quorum
+        let repeated = vec![keys[1].clone(); 6];
+        let mut forged = commit_qc(&repeated, &block);
+        forged.signers = vec![1; 6];
+        assert!(set.verify_qc(&forged).is_err());
+    }
+}
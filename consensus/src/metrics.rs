@@ -0,0 +1,430 @@
+// Prometheus metrics for consensus, network and storage
+//
+// Components record into one process-wide registry (`global()`), so hot
+// paths never thread a handle through. `MetricsServer` serves the registry
+// in the Prometheus text format for scraping.
+
+use crate::crypto::Hash;
+use crate::hotstuff::types::MessageType;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+
+/// Metrics endpoint configuration
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    /// Address the HTTP endpoint listens on
+    pub listen_addr: SocketAddr,
+    /// Path metrics are served under
+    pub path: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: ([127, 0, 0, 1], 9100).into(),
+            path: "/metrics".to_string(),
+        }
+    }
+}
+
+/// Storage operation kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+pub enum StorageOp {
+    Read,
+    Write,
+}
+
+/// EVM state cache kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+pub enum StateCacheKind {
+    Account,
+    Storage,
+}
+
+/// Consensus message dropped as a replay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+pub enum ReplayKind {
+    Block,
+    Vote,
+}
+
+/// Outcome of a cache lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+enum CacheResult {
+    Hit,
+    Miss,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct PhaseLabels {
+    phase: MessageType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct ReplayLabels {
+    kind: ReplayKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct StorageLabels {
+    op: StorageOp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct StateCacheLabels {
+    cache: StateCacheKind,
+    result: CacheResult,
+}
+
+/// Consensus, network and storage metrics
+pub struct Metrics {
+    registry: Registry,
+    view_changes: Counter,
+    current_view: Gauge,
+    commit_latency: Histogram,
+    qc_formation: Family<PhaseLabels, Histogram>,
+    gossip_propagation: Histogram,
+    connected_peers: Gauge,
+    validator_peers: Gauge,
+    storage_latency: Family<StorageLabels, Histogram>,
+    mempool_depth: Gauge,
+    state_cache_lookups: Family<StateCacheLabels, Counter>,
+    replays_dropped: Family<ReplayLabels, Counter>,
+}
+
+impl Metrics {
+    /// Create a registry with every metric, prefixed `openliquid_`
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("openliquid");
+
+        let view_changes = Counter::default();
+        registry.register("view_changes", "Views entered after a timeout", view_changes.clone());
+        let current_view = Gauge::default();
+        registry.register("current_view", "Current consensus view", current_view.clone());
+
+        // 1ms to ~16s
+        let commit_latency = Histogram::new(exponential_buckets(0.001, 2.0, 15));
+        registry.register(
+            "commit_latency_seconds",
+            "Time from receiving a block to committing it",
+            commit_latency.clone(),
+        );
+        let qc_formation = Family::new_with_constructor(latency_histogram as fn() -> Histogram);
+        registry.register(
+            "qc_formation_seconds",
+            "Time from the first vote for a block to its quorum certificate",
+            qc_formation.clone(),
+        );
+        let gossip_propagation = latency_histogram();
+        registry.register(
+            "gossip_propagation_seconds",
+            "Time for a broadcast gossip message to propagate",
+            gossip_propagation.clone(),
+        );
+
+        let connected_peers = Gauge::default();
+        registry.register("connected_peers", "Connected peers", connected_peers.clone());
+        let validator_peers = Gauge::default();
+        registry.register("validator_peers", "Connected validator peers", validator_peers.clone());
+
+        // 10us to ~160ms
+        let storage_latency = Family::new_with_constructor(storage_histogram as fn() -> Histogram);
+        registry.register(
+            "storage_latency_seconds",
+            "RocksDB read and write latency",
+            storage_latency.clone(),
+        );
+
+        let mempool_depth = Gauge::default();
+        registry.register("mempool_depth", "Transactions waiting in the mempool", mempool_depth.clone());
+        let state_cache_lookups = Family::default();
+        registry.register(
+            "evm_state_cache_lookups",
+            "EVM account and storage-slot cache lookups",
+            state_cache_lookups.clone(),
+        );
+        let replays_dropped = Family::default();
+        registry.register(
+            "replays_dropped",
+            "Repeated proposals and votes dropped before verification",
+            replays_dropped.clone(),
+        );
+
+        Self {
+            registry,
+            view_changes,
+            current_view,
+            commit_latency,
+            qc_formation,
+            gossip_propagation,
+            connected_peers,
+            validator_peers,
+            storage_latency,
+            mempool_depth,
+            state_cache_lookups,
+            replays_dropped,
+        }
+    }
+
+    /// Record entering `view` after a timeout
+    pub fn view_changed(&self, view: u64) {
+        self.view_changes.inc();
+        self.current_view.set(view as i64);
+    }
+
+    /// Record a committed block
+    pub fn block_committed(&self, latency: Duration) {
+        self.commit_latency.observe(latency.as_secs_f64());
+    }
+
+    /// Record a quorum certificate formed in `phase`
+    pub fn qc_formed(&self, phase: &MessageType, elapsed: Duration) {
+        self.qc_formation
+            .get_or_create(&PhaseLabels { phase: phase.clone() })
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Record a gossip message's propagation time
+    pub fn gossip_propagated(&self, elapsed: Duration) {
+        self.gossip_propagation.observe(elapsed.as_secs_f64());
+    }
+
+    /// Set the connected and validator peer counts
+    pub fn set_peers(&self, connected: usize, validators: usize) {
+        self.connected_peers.set(connected as i64);
+        self.validator_peers.set(validators as i64);
+    }
+
+    /// Time a storage operation until the returned guard drops
+    pub fn time_storage(&self, op: StorageOp) -> StorageTimer<'_> {
+        StorageTimer {
+            metrics: self,
+            op,
+            start: Instant::now(),
+        }
+    }
+
+    /// Set the number of transactions in the mempool
+    pub fn set_mempool_depth(&self, depth: usize) {
+        self.mempool_depth.set(depth as i64);
+    }
+
+    /// Record an EVM state cache lookup
+    pub fn state_cache_lookup(&self, cache: StateCacheKind, hit: bool) {
+        let result = if hit { CacheResult::Hit } else { CacheResult::Miss };
+        self.state_cache_lookups
+            .get_or_create(&StateCacheLabels { cache, result })
+            .inc();
+    }
+
+    /// Record a proposal or vote dropped as a replay
+    pub fn replay_dropped(&self, kind: ReplayKind) {
+        self.replays_dropped.get_or_create(&ReplayLabels { kind }).inc();
+    }
+
+    /// Encode every metric in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut text = String::new();
+        // Writing to a String cannot fail
+        let _ = encode(&mut text, &self.registry);
+        text
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 100us to ~1.6s
+fn latency_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.0001, 2.0, 15))
+}
+
+fn storage_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.00001, 2.0, 15))
+}
+
+/// Process-wide metrics
+pub fn global() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Guard recording a storage operation's latency on drop
+pub struct StorageTimer<'a> {
+    metrics: &'a Metrics,
+    op: StorageOp,
+    start: Instant,
+}
+
+impl Drop for StorageTimer<'_> {
+    fn drop(&mut self) {
+        self.metrics
+            .storage_latency
+            .get_or_create(&StorageLabels { op: self.op })
+            .observe(self.start.elapsed().as_secs_f64());
+    }
+}
+
+/// Per-block timestamps behind the commit latency and QC formation metrics
+///
+/// Entries for views at or below the last commit are dropped on commit, so
+/// blocks that never commit do not accumulate.
+#[derive(Debug, Default)]
+pub struct BlockTimings {
+    /// First time each block was received, with its view
+    received: HashMap<Hash, (u64, Instant)>,
+    /// First vote per phase and block, with its view
+    first_vote: HashMap<(MessageType, Hash), (u64, Instant)>,
+}
+
+impl BlockTimings {
+    /// Note a received block
+    pub fn block_received(&mut self, hash: Hash, view: u64) {
+        self.received.entry(hash).or_insert((view, Instant::now()));
+    }
+
+    /// Note a vote; only the first per phase and block starts the clock
+    pub fn vote_received(&mut self, phase: &MessageType, hash: Hash, view: u64) {
+        self.first_vote
+            .entry((phase.clone(), hash))
+            .or_insert((view, Instant::now()));
+    }
+
+    /// Record the QC formation time of `hash` in `phase`
+    pub fn qc_formed(&mut self, phase: &MessageType, hash: Hash) {
+        if let Some((_, start)) = self.first_vote.remove(&(phase.clone(), hash)) {
+            global().qc_formed(phase, start.elapsed());
+        }
+    }
+
+    /// Record the commit latency of `hash` and forget older blocks
+    pub fn block_committed(&mut self, hash: &Hash, view: u64) {
+        if let Some((_, received)) = self.received.remove(hash) {
+            global().block_committed(received.elapsed());
+        }
+        self.received.retain(|_, (v, _)| *v > view);
+        self.first_vote.retain(|_, (v, _)| *v > view);
+    }
+}
+
+/// HTTP endpoint serving the global metrics
+pub struct MetricsServer {
+    listener: TcpListener,
+    path: String,
+}
+
+impl MetricsServer {
+    /// Bind the endpoint
+    pub async fn bind(config: MetricsConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(config.listen_addr).await?;
+        info!("Serving metrics on http://{}{}", listener.local_addr()?, config.path);
+        Ok(Self {
+            listener,
+            path: config.path,
+        })
+    }
+
+    /// Bound address
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve scrapes until the task is dropped
+    pub async fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let path = self.path.clone();
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream, &path).await {
+                    debug!("Metrics request from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+/// Answer one HTTP request; anything but `GET <path>` is a 404
+async fn respond(mut stream: TcpStream, path: &str) -> io::Result<()> {
+    let mut request = [0u8; 1024];
+    let len = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..len]);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) if target == path => {
+            let body = global().encode();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_endpoint_serves_recorded_metrics() {
+        let server = MetricsServer::bind(MetricsConfig {
+            listen_addr: ([127, 0, 0, 1], 0).into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        // Values are checked on a private registry: the global one is
+        // shared with every other test in the process
+        let metrics = Metrics::new();
+        metrics.view_changed(7);
+        metrics.qc_formed(&MessageType::PreCommit, Duration::from_millis(3));
+        drop(metrics.time_storage(StorageOp::Write));
+        metrics.state_cache_lookup(StateCacheKind::Storage, false);
+        let text = metrics.encode();
+        assert!(text.contains("openliquid_view_changes_total 1"));
+        assert!(text.contains("openliquid_current_view 7"));
+        assert!(text.contains("openliquid_qc_formation_seconds_count{phase=\"PreCommit\"} 1"));
+        assert!(text.contains("openliquid_storage_latency_seconds_count{op=\"Write\"} 1"));
+        assert!(text.contains("openliquid_evm_state_cache_lookups_total{cache=\"Storage\",result=\"Miss\"} 1"));
+
+        let get = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        global().set_mempool_depth(3);
+        let response = get("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("openliquid_mempool_depth"));
+        assert!(get("/other").await.starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,233 @@
+// Signed validator announcements and the address book built from them
+//
+// Validators periodically gossip their PeerId and listen addresses, signed
+// with their consensus key for the current epoch. Verified announcements
+// tell every node which peers are validators and where to dial them, so
+// nodes keep direct connections to the whole validator set. Announcements
+// from other epochs, from keys outside the set, or older than the one
+// already known are refused, so stale addresses cannot be replayed.
+
+use super::{NetworkError, NetworkResult};
+use crate::crypto::{partial_verify, threshold_sign, BLSPartialSignature, BLSPublicKey, BLSSecretKey};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::info;
+
+/// Domain separator for announcement signatures
+pub const ANNOUNCEMENT_DOMAIN: &[u8] = b"openliquid/validator-announcement/1";
+
+/// How often validators re-announce themselves
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A validator's signed claim to a PeerId and its addresses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorAnnouncement {
+    /// PeerId bytes
+    pub peer_id: Vec<u8>,
+    /// Multiaddr bytes the validator listens on
+    pub addrs: Vec<Vec<u8>>,
+    /// Consensus public key
+    pub public_key: BLSPublicKey,
+    /// Validator set epoch the announcement is valid for
+    pub epoch: u64,
+    /// Issue time (ms since the Unix epoch); later announcements replace
+    /// earlier ones
+    pub timestamp: u64,
+    /// Signature over all of the above
+    pub signature: BLSPartialSignature,
+}
+
+impl ValidatorAnnouncement {
+    /// Sign an announcement of `peer_id` at `addrs`
+    pub fn new(
+        secret_key: &BLSSecretKey,
+        peer_id: PeerId,
+        addrs: &[Multiaddr],
+        epoch: u64,
+        timestamp: u64,
+    ) -> Self {
+        let peer_id = peer_id.to_bytes();
+        let addrs: Vec<Vec<u8>> = addrs.iter().map(|addr| addr.to_vec()).collect();
+        let public_key = secret_key.public_key();
+        let payload = announcement_payload(&peer_id, &addrs, &public_key, epoch, timestamp);
+        Self {
+            peer_id,
+            addrs,
+            public_key,
+            epoch,
+            timestamp,
+            signature: threshold_sign(secret_key, &payload),
+        }
+    }
+
+    /// Validator ID of the signer
+    pub fn validator_id(&self) -> u64 {
+        self.signature.validator_id
+    }
+
+    /// Announced PeerId
+    pub fn peer(&self) -> NetworkResult<PeerId> {
+        PeerId::from_bytes(&self.peer_id).map_err(|_| NetworkError::InvalidAnnouncement("malformed PeerId".to_string()))
+    }
+
+    /// Announced addresses; malformed ones are skipped
+    pub fn addresses(&self) -> Vec<Multiaddr> {
+        self.addrs
+            .iter()
+            .filter_map(|bytes| Multiaddr::try_from(bytes.clone()).ok())
+            .collect()
+    }
+
+    /// Check the signature against `public_key`
+    pub fn verify(&self, public_key: &BLSPublicKey) -> bool {
+        let payload = announcement_payload(&self.peer_id, &self.addrs, &self.public_key, self.epoch, self.timestamp);
+        self.public_key == *public_key && partial_verify(&payload, &self.signature, public_key)
+    }
+}
+
+/// Bytes a validator signs to announce itself
+pub fn announcement_payload(
+    peer_id: &[u8],
+    addrs: &[Vec<u8>],
+    public_key: &BLSPublicKey,
+    epoch: u64,
+    timestamp: u64,
+) -> Vec<u8> {
+    let mut payload = Vec::from(ANNOUNCEMENT_DOMAIN);
+    payload.extend_from_slice(&epoch.to_le_bytes());
+    payload.extend_from_slice(&timestamp.to_le_bytes());
+    // Length prefixes keep the variable-length fields unambiguous
+    for field in std::iter::once(peer_id).chain(addrs.iter().map(Vec::as_slice)) {
+        payload.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        payload.extend_from_slice(field);
+    }
+    payload.extend_from_slice(&public_key.to_bytes());
+    payload
+}
+
+/// Where a validator was last announced
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidatorAddress {
+    pub peer_id: PeerId,
+    pub addrs: Vec<Multiaddr>,
+    pub timestamp: u64,
+}
+
+/// Latest verified announcement of each validator in the current epoch
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    epoch: u64,
+    validator_keys: HashMap<u64, BLSPublicKey>,
+    entries: HashMap<u64, ValidatorAddress>,
+}
+
+impl AddressBook {
+    /// Create an empty address book for the validator set of `epoch`
+    pub fn new(epoch: u64, validator_keys: HashMap<u64, BLSPublicKey>) -> Self {
+        Self {
+            epoch,
+            validator_keys,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Current epoch
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Verify and record an announcement
+    ///
+    /// Returns false, without error, for an announcement no newer than the
+    /// one already recorded for its validator.
+    pub fn apply(&mut self, announcement: &ValidatorAnnouncement) -> NetworkResult<bool> {
+        let invalid = |reason: String| NetworkError::InvalidAnnouncement(reason);
+        let validator_id = announcement.validator_id();
+        if announcement.epoch != self.epoch {
+            return Err(invalid(format!(
+                "validator {} announced for epoch {}, current epoch is {}",
+                validator_id, announcement.epoch, self.epoch
+            )));
+        }
+        let public_key = self
+            .validator_keys
+            .get(&validator_id)
+            .ok_or_else(|| invalid(format!("unknown validator {}", validator_id)))?;
+        if !announcement.verify(public_key) {
+            return Err(invalid(format!("invalid signature for validator {}", validator_id)));
+        }
+        if self
+            .entries
+            .get(&validator_id)
+            .is_some_and(|known| known.timestamp >= announcement.timestamp)
+        {
+            return Ok(false);
+        }
+
+        let address = ValidatorAddress {
+            peer_id: announcement.peer()?,
+            addrs: announcement.addresses(),
+            timestamp: announcement.timestamp,
+        };
+        info!("Validator {} announced as {} at {:?}", validator_id, address.peer_id, address.addrs);
+        self.entries.insert(validator_id, address);
+        Ok(true)
+    }
+
+    /// Validator ID announced for `peer`
+    pub fn validator_id(&self, peer: &PeerId) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|(_, address)| address.peer_id == *peer)
+            .map(|(id, _)| *id)
+    }
+
+    /// Latest address of a validator
+    pub fn get(&self, validator_id: u64) -> Option<&ValidatorAddress> {
+        self.entries.get(&validator_id)
+    }
+
+    /// Every announced validator
+    pub fn entries(&self) -> impl Iterator<Item = (u64, &ValidatorAddress)> {
+        self.entries.iter().map(|(id, address)| (*id, address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::BLSKeyPair;
+
+    #[test]
+    fn test_address_book_verifies_and_orders_announcements() {
+        let keys: Vec<_> = (0..2).map(BLSKeyPair::with_id).collect();
+        let validator_keys: HashMap<u64, BLSPublicKey> =
+            keys.iter().map(|k| (k.public_key.validator_id(), k.public_key.clone())).collect();
+        let mut book = AddressBook::new(3, validator_keys);
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/10.0.0.1/tcp/9000".parse().unwrap();
+        let announce = |key: &BLSSecretKey, epoch, timestamp| {
+            ValidatorAnnouncement::new(key, peer, std::slice::from_ref(&addr), epoch, timestamp)
+        };
+
+        let first = announce(&keys[1].secret_key, 3, 100);
+        assert!(book.apply(&first).unwrap());
+        assert_eq!(book.validator_id(&peer), Some(1));
+        assert_eq!(book.get(1).unwrap().addrs, vec![addr.clone()]);
+
+        // Replays and older announcements do not replace the newer entry
+        assert!(!book.apply(&first).unwrap());
+        assert!(!book.apply(&announce(&keys[1].secret_key, 3, 50)).unwrap());
+        assert!(book.apply(&announce(&keys[1].secret_key, 3, 200)).unwrap());
+
+        // Other epochs, outsiders and tampered fields are refused
+        assert!(book.apply(&announce(&keys[1].secret_key, 2, 300)).is_err());
+        assert!(book.apply(&announce(&BLSKeyPair::with_id(7).secret_key, 3, 300)).is_err());
+        let mut tampered = announce(&keys[0].secret_key, 3, 300);
+        tampered.addrs = vec!["/ip4/6.6.6.6/tcp/1".parse::<Multiaddr>().unwrap().to_vec()];
+        assert!(book.apply(&tampered).is_err());
+        assert_eq!(book.get(0), None);
+    }
+}
@@ -0,0 +1,308 @@
+// Bandwidth accounting and per-peer rate limiting
+//
+// Every message in or out is counted against its peer and topic. Peers can
+// be held to byte rate limits (token buckets refilled at the configured
+// rate): a message over the limit is dropped, and a peer that keeps
+// exceeding it is disconnected. A peer's bucket and violations outlive its
+// connection for a cooldown, so reconnecting does not reset them.
+
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a disconnected peer's buckets and violations are kept
+pub const PEER_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Byte rate a peer may sustain, with room for bursts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub bytes_per_sec: u64,
+    /// Bytes that may be spent at once after an idle period
+    pub burst: u64,
+}
+
+/// Rate limits applied to every peer; no limits by default
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthConfig {
+    /// Limit on bytes received from a peer
+    pub ingress: Option<RateLimit>,
+    /// Limit on bytes sent to a peer directly
+    pub egress: Option<RateLimit>,
+    /// Disconnect a peer after this many consecutive ingress messages over
+    /// its limit; 0 only throttles
+    pub disconnect_after: u32,
+}
+
+/// Bytes and messages in each direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub messages_in: u64,
+    pub messages_out: u64,
+}
+
+impl Traffic {
+    fn record_in(&mut self, bytes: usize) {
+        self.bytes_in += bytes as u64;
+        self.messages_in += 1;
+    }
+
+    fn record_out(&mut self, bytes: usize) {
+        self.bytes_out += bytes as u64;
+        self.messages_out += 1;
+    }
+}
+
+/// One peer's traffic and the messages refused for its limits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeerBandwidth {
+    pub traffic: Traffic,
+    /// Inbound messages dropped for exceeding the ingress limit
+    pub throttled_in: u64,
+    /// Direct sends refused for exceeding the egress limit
+    pub throttled_out: u64,
+}
+
+/// Traffic of the whole node, by peer and by topic
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthStats {
+    pub total: Traffic,
+    pub peers: HashMap<PeerId, PeerBandwidth>,
+    /// Gossip traffic by topic; direct messages are under "direct"
+    pub topics: HashMap<String, Traffic>,
+    /// Peers disconnected for exceeding their ingress limit
+    pub rate_limit_disconnects: u64,
+}
+
+/// Topic that direct (non-gossip) messages are accounted under
+pub const DIRECT_TOPIC: &str = "direct";
+
+/// Verdict on an inbound message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Within the limit; process it
+    Accept,
+    /// Over the limit; drop it
+    Throttle,
+    /// Over the limit too often; drop it and disconnect the peer
+    Disconnect,
+}
+
+/// Token bucket holding the bytes a peer may still spend
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            refilled_at: now,
+        }
+    }
+
+    fn try_take(&mut self, limit: RateLimit, bytes: usize, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.bytes_per_sec as f64).min(limit.burst as f64);
+        self.refilled_at = now;
+        if self.tokens < bytes as f64 {
+            return false;
+        }
+        self.tokens -= bytes as f64;
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerState {
+    ingress: Option<Bucket>,
+    egress: Option<Bucket>,
+    /// Consecutive inbound messages over the limit
+    violations: u32,
+    /// When the peer disconnected, if it has not reconnected since
+    disconnected_at: Option<Instant>,
+}
+
+/// Counts traffic and enforces the per-peer rate limits
+#[derive(Debug, Default)]
+pub struct BandwidthTracker {
+    config: BandwidthConfig,
+    stats: BandwidthStats,
+    peers: HashMap<PeerId, PeerState>,
+}
+
+impl BandwidthTracker {
+    pub fn new(config: BandwidthConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &BandwidthConfig {
+        &self.config
+    }
+
+    /// Replace the limits; buckets restart full
+    pub fn set_config(&mut self, config: BandwidthConfig) {
+        self.config = config;
+        self.peers.clear();
+    }
+
+    pub fn stats(&self) -> &BandwidthStats {
+        &self.stats
+    }
+
+    /// Account a message received from `peer_id` on `topic`, deciding
+    /// whether it is processed
+    pub fn record_in(&mut self, peer_id: PeerId, topic: &str, bytes: usize, now: Instant) -> Admission {
+        self.account_in(peer_id, topic, bytes);
+
+        let Some(limit) = self.config.ingress else {
+            return Admission::Accept;
+        };
+        let state = self.peers.entry(peer_id).or_default();
+        state.disconnected_at = None;
+        let bucket = state.ingress.get_or_insert_with(|| Bucket::new(limit, now));
+        if bucket.try_take(limit, bytes, now) {
+            state.violations = 0;
+            return Admission::Accept;
+        }
+
+        state.violations += 1;
+        if let Some(peer) = self.stats.peers.get_mut(&peer_id) {
+            peer.throttled_in += 1;
+        }
+        if self.config.disconnect_after > 0 && state.violations >= self.config.disconnect_after {
+            self.stats.rate_limit_disconnects += 1;
+            return Admission::Disconnect;
+        }
+        Admission::Throttle
+    }
+
+    /// Account a message received from `peer_id` on `topic` without
+    /// holding it to the ingress limit
+    pub fn account_in(&mut self, peer_id: PeerId, topic: &str, bytes: usize) {
+        self.stats.total.record_in(bytes);
+        self.stats.topics.entry(topic.to_string()).or_default().record_in(bytes);
+        self.stats.peers.entry(peer_id).or_default().traffic.record_in(bytes);
+    }
+
+    /// Take room for a direct message to `peer_id` from its egress limit
+    pub fn reserve_out(&mut self, peer_id: PeerId, bytes: usize, now: Instant) -> bool {
+        let Some(limit) = self.config.egress else {
+            return true;
+        };
+        let state = self.peers.entry(peer_id).or_default();
+        state.disconnected_at = None;
+        let bucket = state.egress.get_or_insert_with(|| Bucket::new(limit, now));
+        if bucket.try_take(limit, bytes, now) {
+            return true;
+        }
+        self.stats.peers.entry(peer_id).or_default().throttled_out += 1;
+        false
+    }
+
+    /// Account a message sent on `topic`, to `peer_id` when sent directly
+    pub fn record_out(&mut self, peer_id: Option<PeerId>, topic: &str, bytes: usize) {
+        self.stats.total.record_out(bytes);
+        self.stats.topics.entry(topic.to_string()).or_default().record_out(bytes);
+        if let Some(peer_id) = peer_id {
+            self.stats.peers.entry(peer_id).or_default().traffic.record_out(bytes);
+        }
+    }
+
+    /// Forget a disconnected peer's traffic. Its buckets and violations
+    /// are kept for `PEER_COOLDOWN`, then dropped; those of peers that
+    /// left longer ago are dropped now.
+    pub fn remove_peer(&mut self, peer_id: &PeerId, now: Instant) {
+        self.stats.peers.remove(peer_id);
+        if let Some(state) = self.peers.get_mut(peer_id) {
+            state.disconnected_at = Some(now);
+        }
+        self.peers.retain(|_, state| {
+            !state.disconnected_at.is_some_and(|at| now.saturating_duration_since(at) >= PEER_COOLDOWN)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ingress_limit_throttles_then_disconnects() {
+        let mut tracker = BandwidthTracker::new(BandwidthConfig {
+            ingress: Some(RateLimit { bytes_per_sec: 1_000, burst: 2_000 }),
+            egress: None,
+            disconnect_after: 3,
+        });
+        let peer = PeerId::random();
+        let start = Instant::now();
+
+        assert_eq!(tracker.record_in(peer, "blocks", 1_500, start), Admission::Accept);
+        assert_eq!(tracker.record_in(peer, "blocks", 1_000, start), Admission::Throttle);
+        // Half a second refills 500 bytes
+        assert_eq!(tracker.record_in(peer, "txs", 1_000, start + Duration::from_millis(500)), Admission::Accept);
+        assert_eq!(tracker.record_in(peer, "txs", 10, start + Duration::from_millis(500)), Admission::Throttle);
+        assert_eq!(tracker.record_in(peer, "txs", 10, start + Duration::from_millis(500)), Admission::Throttle);
+        assert_eq!(tracker.record_in(peer, "txs", 10, start + Duration::from_millis(500)), Admission::Disconnect);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.total.bytes_in, 3_530);
+        assert_eq!(stats.topics["blocks"].bytes_in, 2_500);
+        assert_eq!(stats.peers[&peer].traffic.messages_in, 6);
+        assert_eq!(stats.peers[&peer].throttled_in, 4);
+        assert_eq!(stats.rate_limit_disconnects, 1);
+    }
+
+    #[test]
+    fn test_egress_limit_and_accounting() {
+        let mut tracker = BandwidthTracker::new(BandwidthConfig {
+            egress: Some(RateLimit { bytes_per_sec: 100, burst: 100 }),
+            ..Default::default()
+        });
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(tracker.reserve_out(peer, 80, now));
+        tracker.record_out(Some(peer), DIRECT_TOPIC, 80);
+        assert!(!tracker.reserve_out(peer, 80, now));
+        tracker.record_out(None, "blocks", 500);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.total.bytes_out, 580);
+        assert_eq!(stats.peers[&peer].traffic.bytes_out, 80);
+        assert_eq!(stats.peers[&peer].throttled_out, 1);
+
+        tracker.remove_peer(&peer, now);
+        assert!(tracker.stats().peers.is_empty());
+    }
+
+    #[test]
+    fn test_limits_survive_reconnects_until_cooldown() {
+        let mut tracker = BandwidthTracker::new(BandwidthConfig {
+            ingress: Some(RateLimit { bytes_per_sec: 1, burst: 100 }),
+            egress: None,
+            disconnect_after: 2,
+        });
+        let peer = PeerId::random();
+        let start = Instant::now();
+
+        assert_eq!(tracker.record_in(peer, "txs", 100, start), Admission::Accept);
+        assert_eq!(tracker.record_in(peer, "txs", 100, start), Admission::Throttle);
+        // Reconnecting keeps the spent bucket and the violation
+        tracker.remove_peer(&peer, start);
+        assert_eq!(tracker.record_in(peer, "txs", 100, start), Admission::Disconnect);
+
+        // After the cooldown the peer starts over
+        tracker.remove_peer(&peer, start);
+        let later = start + PEER_COOLDOWN;
+        tracker.remove_peer(&PeerId::random(), later);
+        assert_eq!(tracker.record_in(peer, "txs", 100, later), Admission::Accept);
+    }
+}
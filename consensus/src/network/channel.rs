@@ -0,0 +1,325 @@
+// Bounded channels with explicit overflow policies
+//
+// Every queue between the network layer and its consumers is bounded so a
+// slow consumer or a flood of peer traffic cannot grow memory without limit.
+// When a queue is full the configured policy decides what happens:
+// - DropOldest: discard the oldest queued item (gossip, where newer data
+//   supersedes older data and losing a message is recoverable)
+// - Block: wait for the consumer to make room (consensus-critical traffic
+//   that must never be lost)
+
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use tokio::sync::Notify;
+
+/// Default capacity for network events (peer connections, consensus traffic)
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// Default capacity for gossip events
+pub const DEFAULT_GOSSIP_CAPACITY: usize = 4096;
+
+/// Default capacity for incoming validator messages
+pub const DEFAULT_VALIDATOR_CAPACITY: usize = 1024;
+
+/// Default capacity for commands queued to the network event loop
+pub const DEFAULT_COMMAND_CAPACITY: usize = 1024;
+
+/// What to do when sending into a full channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued item to make room
+    DropOldest,
+    /// Wait until the receiver makes room
+    Block,
+}
+
+/// Channel capacities used by the network layer
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+    /// Capacity of the network event channel (Block)
+    pub event_capacity: usize,
+    /// Capacity of the gossip event channel (DropOldest)
+    pub gossip_capacity: usize,
+    /// Capacity of the incoming validator message channel (Block)
+    pub validator_capacity: usize,
+    /// Capacity of the command channel from handles to the event loop
+    /// (senders wait for room)
+    pub command_capacity: usize,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            event_capacity: DEFAULT_EVENT_CAPACITY,
+            gossip_capacity: DEFAULT_GOSSIP_CAPACITY,
+            validator_capacity: DEFAULT_VALIDATOR_CAPACITY,
+            command_capacity: DEFAULT_COMMAND_CAPACITY,
+        }
+    }
+}
+
+/// Error returned when the receiver has been dropped
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("channel closed")]
+pub struct ChannelClosed<T>(pub T);
+
+/// Saturation metrics for a bounded channel
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelMetrics {
+    /// Maximum number of queued items
+    pub capacity: usize,
+    /// Items currently queued
+    pub len: usize,
+    /// Highest queue length observed
+    pub high_water: usize,
+    /// Items accepted by the channel
+    pub sent: u64,
+    /// Items discarded by DropOldest
+    pub dropped: u64,
+    /// Sends that had to wait for room (Block)
+    pub blocked: u64,
+}
+
+impl ChannelMetrics {
+    /// Whether the queue is full
+    pub fn is_saturated(&self) -> bool {
+        self.len >= self.capacity
+    }
+
+    /// Fill ratio in [0, 1]
+    pub fn utilization(&self) -> f64 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+        self.len as f64 / self.capacity as f64
+    }
+}
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    /// Signalled when an item is queued or the last sender is dropped
+    item_ready: Notify,
+    /// Signalled when room is made or the receiver is dropped
+    space_ready: Notify,
+    senders: AtomicUsize,
+    receiver_closed: AtomicBool,
+    high_water: AtomicUsize,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    blocked: AtomicU64,
+}
+
+impl<T> Shared<T> {
+    fn metrics(&self) -> ChannelMetrics {
+        ChannelMetrics {
+            capacity: self.capacity,
+            len: self.queue.lock().unwrap().len(),
+            high_water: self.high_water.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        let item = self.queue.lock().unwrap().pop_front();
+        if item.is_some() {
+            self.space_ready.notify_one();
+        }
+        item
+    }
+
+    fn push(&self, queue: &mut VecDeque<T>, item: T) {
+        queue.push_back(item);
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        self.high_water.fetch_max(queue.len(), Ordering::Relaxed);
+        self.item_ready.notify_one();
+    }
+}
+
+/// Create a bounded channel with the given overflow policy
+pub fn bounded<T>(capacity: usize, policy: OverflowPolicy) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    assert!(capacity > 0, "channel capacity must be positive");
+
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity.min(64))),
+        capacity,
+        policy,
+        item_ready: Notify::new(),
+        space_ready: Notify::new(),
+        senders: AtomicUsize::new(1),
+        receiver_closed: AtomicBool::new(false),
+        high_water: AtomicUsize::new(0),
+        sent: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+        blocked: AtomicU64::new(0),
+    });
+
+    (
+        BoundedSender { shared: shared.clone() },
+        BoundedReceiver { shared },
+    )
+}
+
+/// Sending half of a bounded channel
+pub struct BoundedSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> BoundedSender<T> {
+    /// Send an item, applying the overflow policy if the channel is full
+    pub async fn send(&self, item: T) -> Result<(), ChannelClosed<T>> {
+        let mut waited = false;
+
+        loop {
+            if self.shared.receiver_closed.load(Ordering::Acquire) {
+                return Err(ChannelClosed(item));
+            }
+
+            // Register for wakeups before checking for room
+            let space_ready = self.shared.space_ready.notified();
+            {
+                let mut queue = self.shared.queue.lock().unwrap();
+                if queue.len() < self.shared.capacity {
+                    self.shared.push(&mut queue, item);
+                    return Ok(());
+                }
+
+                if self.shared.policy == OverflowPolicy::DropOldest {
+                    queue.pop_front();
+                    self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    self.shared.push(&mut queue, item);
+                    return Ok(());
+                }
+            }
+
+            if !waited {
+                self.shared.blocked.fetch_add(1, Ordering::Relaxed);
+                waited = true;
+            }
+            space_ready.await;
+        }
+    }
+
+    /// Current saturation metrics
+    pub fn metrics(&self) -> ChannelMetrics {
+        self.shared.metrics()
+    }
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::AcqRel);
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Wake the receiver so it can observe the close
+            self.shared.item_ready.notify_one();
+        }
+    }
+}
+
+/// Receiving half of a bounded channel
+pub struct BoundedReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> BoundedReceiver<T> {
+    /// Receive the next item; None once empty and all senders are dropped
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            let item_ready = self.shared.item_ready.notified();
+            if let Some(item) = self.shared.pop() {
+                return Some(item);
+            }
+            if self.shared.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+            item_ready.await;
+        }
+    }
+
+    /// Receive an item if one is queued
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.shared.pop()
+    }
+
+    /// Current saturation metrics
+    pub fn metrics(&self) -> ChannelMetrics {
+        self.shared.metrics()
+    }
+}
+
+impl<T> Drop for BoundedReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_closed.store(true, Ordering::Release);
+        self.shared.space_ready.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_newest() {
+        let (tx, mut rx) = bounded(2, OverflowPolicy::DropOldest);
+        for i in 0..5 {
+            tx.send(i).await.unwrap();
+        }
+
+        let metrics = tx.metrics();
+        assert_eq!(metrics.sent, 5);
+        assert_eq!(metrics.dropped, 3);
+        assert_eq!(metrics.high_water, 2);
+        assert!(metrics.is_saturated());
+
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, Some(4));
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let (tx, mut rx) = bounded(1, OverflowPolicy::Block);
+        tx.send(1).await.unwrap();
+
+        let sender = tx.clone();
+        let handle = tokio::spawn(async move { sender.send(2).await });
+
+        // Sender is parked until the receiver makes room
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!handle.is_finished());
+        assert_eq!(tx.metrics().blocked, 1);
+
+        assert_eq!(rx.recv().await, Some(1));
+        handle.await.unwrap().unwrap();
+        assert_eq!(rx.recv().await, Some(2));
+        assert_eq!(tx.metrics().dropped, 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_semantics() {
+        let (tx, mut rx) = bounded(4, OverflowPolicy::Block);
+        tx.send(1).await.unwrap();
+        drop(tx);
+
+        // Queued items are still delivered after the senders are gone
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, None);
+
+        let (tx, rx) = bounded::<u32>(1, OverflowPolicy::Block);
+        drop(rx);
+        assert_eq!(tx.send(7).await, Err(ChannelClosed(7)));
+    }
+}
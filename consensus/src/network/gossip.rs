@@ -3,16 +3,17 @@
 // This module provides efficient message propagation across the network
 // with a target propagation time of <500ms.
 
+use super::seen_cache::SeenCache;
 use super::{NetworkConfig, NetworkError, NetworkResult};
 use libp2p::{
     gossipsub::{
-        Behaviour as GossipsubBehaviour, Config as GossipsubConfig,
+        Behaviour as GossipsubBehaviour, ConfigBuilder as GossipsubConfigBuilder,
         IdentTopic, Message, MessageAuthenticity, MessageId,
     },
     identify::{Behaviour as IdentifyBehaviour, Config as IdentifyConfig},
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     time::{Duration, Instant},
 };
 use tracing::{debug, info};
@@ -21,6 +22,21 @@ use tracing::{debug, info};
 pub const TOPIC_BLOCKS: &str = "openliquid/blocks/1.0.0";
 pub const TOPIC_TRANSACTIONS: &str = "openliquid/transactions/1.0.0";
 pub const TOPIC_QCS: &str = "openliquid/qcs/1.0.0";
+pub const TOPIC_VALIDATORS: &str = "openliquid/validators/1.0.0";
+
+/// Prefix of the per-asset (or per-shard) DEX market data topics
+pub const TOPIC_MARKET_PREFIX: &str = "openliquid/market";
+
+/// How DEX order flow and fills are split across market topics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarketSharding {
+    /// One topic per asset
+    #[default]
+    PerAsset,
+    /// Assets hashed onto a fixed number of topics; subscribers drop the
+    /// other assets sharing their shard
+    Hashed { shards: u32 },
+}
 
 /// Network behavior combining gossipsub and identify protocols
 #[derive(libp2p::swarm::NetworkBehaviour)]
@@ -33,9 +49,12 @@ pub struct Behaviour {
 }
 
 /// Create the network behavior with configured gossipsub
-pub fn create_behaviour(_config: &NetworkConfig) -> NetworkResult<Behaviour> {
-    // Configure gossipsub with proper validation
-    let gossipsub_config = GossipsubConfig::default();
+pub fn create_behaviour(_config: &NetworkConfig, gossip_config: &GossipConfig) -> NetworkResult<Behaviour> {
+    // Configure gossipsub to carry the largest message any topic accepts
+    let gossipsub_config = GossipsubConfigBuilder::default()
+        .max_transmit_size(gossip_config.max_transmit_size())
+        .build()
+        .map_err(|e| NetworkError::GossipsubError(format!("Invalid gossipsub config: {}", e)))?;
     
     // Create message ID function (hash-based for deduplication)
     let _message_id_fn = |message: &Message| {
@@ -59,6 +78,7 @@ pub fn create_behaviour(_config: &NetworkConfig) -> NetworkResult<Behaviour> {
     let block_topic = IdentTopic::new(TOPIC_BLOCKS);
     let tx_topic = IdentTopic::new(TOPIC_TRANSACTIONS);
     let qc_topic = IdentTopic::new(TOPIC_QCS);
+    let validator_topic = IdentTopic::new(TOPIC_VALIDATORS);
     
     gossipsub.subscribe(&block_topic)
         .map_err(|e| NetworkError::GossipsubError(format!("Failed to subscribe: {}", e)))?;
@@ -66,8 +86,10 @@ pub fn create_behaviour(_config: &NetworkConfig) -> NetworkResult<Behaviour> {
         .map_err(|e| NetworkError::GossipsubError(format!("Failed to subscribe: {}", e)))?;
     gossipsub.subscribe(&qc_topic)
         .map_err(|e| NetworkError::GossipsubError(format!("Failed to subscribe: {}", e)))?;
+    gossipsub.subscribe(&validator_topic)
+        .map_err(|e| NetworkError::GossipsubError(format!("Failed to subscribe: {}", e)))?;
     
-    info!("Subscribed to gossipsub topics: blocks, transactions, qcs");
+    info!("Subscribed to gossipsub topics: blocks, transactions, qcs, validators");
     
     // Create identify behavior (using a placeholder public key for now)
     // In production, this should use the actual keypair
@@ -85,7 +107,7 @@ pub fn create_behaviour(_config: &NetworkConfig) -> NetworkResult<Behaviour> {
 /// Gossip manager for tracking message propagation and statistics
 pub struct GossipManager {
     /// Messages we've seen (for deduplication)
-    seen_messages: HashSet<MessageId>,
+    seen: SeenCache,
     
     /// Message propagation tracking
     propagation_times: HashMap<MessageId, Instant>,
@@ -100,26 +122,98 @@ pub struct GossipManager {
 /// Gossip configuration
 #[derive(Debug, Clone)]
 pub struct GossipConfig {
-    /// Maximum messages to track
+    /// Maximum messages to track; the least recently seen are forgotten
+    /// beyond this
     pub max_tracked_messages: usize,
     
-    /// Message deduplication window
+    /// Message deduplication window: messages not seen again within it
+    /// are forgotten
     pub dedup_window: Duration,
     
     /// Target propagation time (for monitoring)
     pub target_propagation_ms: u64,
+    
+    /// Maximum uncompressed message size per topic
+    pub max_message_sizes: HashMap<String, usize>,
+    
+    /// Maximum uncompressed message size for topics without their own limit
+    pub default_max_message_size: usize,
+    
+    /// Topic layout of market data
+    pub market_sharding: MarketSharding,
 }
 
 impl Default for GossipConfig {
     fn default() -> Self {
+        let max_message_sizes = [
+            (TOPIC_BLOCKS, 4 * 1024 * 1024),
+            (TOPIC_TRANSACTIONS, 128 * 1024),
+            (TOPIC_QCS, 16 * 1024),
+            (TOPIC_VALIDATORS, 4 * 1024),
+        ]
+        .into_iter()
+        .map(|(topic, size)| (topic.to_string(), size))
+        .collect();
+        
         Self {
             max_tracked_messages: 10000,
             dedup_window: Duration::from_secs(60),
             target_propagation_ms: 500,
+            max_message_sizes,
+            default_max_message_size: 64 * 1024,
+            market_sharding: MarketSharding::default(),
         }
     }
 }
 
+impl GossipConfig {
+    /// Maximum uncompressed message size accepted on `topic`
+    pub fn max_message_size(&self, topic: &str) -> usize {
+        self.max_message_sizes
+            .get(topic)
+            .copied()
+            .unwrap_or(self.default_max_message_size)
+    }
+    
+    /// Topic carrying the market data of `asset`
+    pub fn market_topic(&self, asset: u32) -> String {
+        match self.market_sharding {
+            MarketSharding::PerAsset => format!("{}/{}/1.0.0", TOPIC_MARKET_PREFIX, asset),
+            MarketSharding::Hashed { shards } => {
+                // Every node must agree on the shard, so the hash is unkeyed
+                let digest = blake3::hash(&asset.to_le_bytes());
+                let bucket = u32::from_le_bytes(digest.as_bytes()[..4].try_into().unwrap()) % shards.max(1);
+                format!("{}/shard-{}/1.0.0", TOPIC_MARKET_PREFIX, bucket)
+            }
+        }
+    }
+    
+    /// Largest message gossipsub must carry, with room for the codec tag
+    /// and incompressible payloads
+    pub fn max_transmit_size(&self) -> usize {
+        self.max_message_sizes
+            .values()
+            .copied()
+            .chain(std::iter::once(self.default_max_message_size))
+            .max()
+            .unwrap_or(0)
+            + 1024
+    }
+}
+
+/// Traffic on one gossip topic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TopicStats {
+    pub messages_broadcast: u64,
+    pub messages_received: u64,
+    pub duplicates_filtered: u64,
+    pub oversized_dropped: u64,
+    /// Wire bytes of broadcast messages
+    pub bytes_broadcast: u64,
+    /// Wire bytes of received (non-duplicate) messages
+    pub bytes_received: u64,
+}
+
 /// Gossip statistics
 #[derive(Debug, Clone, Default)]
 pub struct GossipStats {
@@ -140,13 +234,43 @@ pub struct GossipStats {
     
     /// Total messages tracked for propagation
     pub total_tracked: u64,
+    
+    /// Bytes broadcast before compression
+    pub bytes_uncompressed: u64,
+    
+    /// Bytes broadcast on the wire
+    pub bytes_compressed: u64,
+    
+    /// Messages refused for exceeding their topic's size limit
+    pub oversized_dropped: u64,
+    
+    /// Seen-cache entries evicted to stay within `max_tracked_messages`
+    pub seen_evicted: u64,
+    
+    /// Seen-cache entries dropped after the dedup window
+    pub seen_expired: u64,
+    
+    /// Traffic by topic
+    pub topics: HashMap<String, TopicStats>,
+}
+
+impl GossipStats {
+    /// Uncompressed bytes per wire byte of broadcast messages (1.0 before
+    /// anything was sent)
+    pub fn compression_ratio(&self) -> f64 {
+        if self.bytes_compressed == 0 {
+            1.0
+        } else {
+            self.bytes_uncompressed as f64 / self.bytes_compressed as f64
+        }
+    }
 }
 
 impl GossipManager {
     /// Create a new gossip manager
     pub fn new(config: GossipConfig) -> Self {
         Self {
-            seen_messages: HashSet::new(),
+            seen: SeenCache::new(config.max_tracked_messages, config.dedup_window),
             propagation_times: HashMap::new(),
             config,
             stats: GossipStats::default(),
@@ -155,24 +279,70 @@ impl GossipManager {
     
     /// Check if we've seen a message before
     pub fn is_duplicate(&self, message_id: &MessageId) -> bool {
-        self.seen_messages.contains(message_id)
+        self.seen.contains(&message_id.0)
     }
     
     /// Mark a message as seen
     pub fn mark_seen(&mut self, message_id: MessageId) {
-        self.seen_messages.insert(message_id);
+        self.seen.insert(&message_id.0);
         self.stats.messages_received += 1;
-        
-        // Cleanup old entries if we exceed the limit
-        if self.seen_messages.len() > self.config.max_tracked_messages {
-            self.cleanup_old_messages();
+    }
+    
+    /// Record a message received on `topic` by its contents; returns false
+    /// for a duplicate, which is counted as filtered
+    pub fn observe(&mut self, topic: &str, data: &[u8]) -> bool {
+        let fresh = self.seen.insert(data);
+        let topic_stats = self.topic_mut(topic);
+        if !fresh {
+            topic_stats.duplicates_filtered += 1;
+            self.stats.duplicates_filtered += 1;
+            return false;
         }
+        topic_stats.messages_received += 1;
+        topic_stats.bytes_received += data.len() as u64;
+        self.stats.messages_received += 1;
+        true
     }
     
     /// Track message broadcast for propagation measurement
     pub fn track_broadcast(&mut self, message_id: MessageId) {
         self.propagation_times.insert(message_id, Instant::now());
         self.stats.messages_broadcast += 1;
+        
+        // Broadcasts that never come back are dropped after the window
+        if self.propagation_times.len() > self.config.max_tracked_messages {
+            self.cleanup_old_messages();
+        }
+    }
+    
+    /// Record the uncompressed and on-the-wire size of a message broadcast
+    /// on `topic`
+    pub fn record_encoded(&mut self, topic: &str, uncompressed: usize, encoded: usize) {
+        self.stats.bytes_uncompressed += uncompressed as u64;
+        self.stats.bytes_compressed += encoded as u64;
+        let topic_stats = self.topic_mut(topic);
+        topic_stats.messages_broadcast += 1;
+        topic_stats.bytes_broadcast += encoded as u64;
+    }
+    
+    /// Record a message refused for exceeding its topic's size limit
+    pub fn record_oversized(&mut self, topic: &str, size: usize) {
+        self.stats.oversized_dropped += 1;
+        self.topic_mut(topic).oversized_dropped += 1;
+        debug!(
+            "Dropped {} byte message on {} (limit {})",
+            size, topic, self.config.max_message_size(topic)
+        );
+    }
+    
+    /// Get the configuration
+    pub fn config(&self) -> &GossipConfig {
+        &self.config
+    }
+    
+    /// Change the topic layout of market data
+    pub fn set_market_sharding(&mut self, sharding: MarketSharding) {
+        self.config.market_sharding = sharding;
     }
     
     /// Record message receipt and calculate propagation time
@@ -180,6 +350,8 @@ impl GossipManager {
         if let Some(start_time) = self.propagation_times.remove(message_id) {
             let elapsed = start_time.elapsed();
             let elapsed_ms = elapsed.as_millis() as u64;
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().gossip_propagated(elapsed);
             
             // Update statistics
             self.stats.total_tracked += 1;
@@ -202,9 +374,26 @@ impl GossipManager {
         }
     }
     
+    /// Traffic on one topic
+    pub fn topic_stats(&self, topic: &str) -> TopicStats {
+        self.stats.topics.get(topic).copied().unwrap_or_default()
+    }
+    
+    fn topic_mut(&mut self, topic: &str) -> &mut TopicStats {
+        if !self.stats.topics.contains_key(topic) {
+            self.stats.topics.insert(topic.to_string(), TopicStats::default());
+        }
+        self.stats.topics.get_mut(topic).unwrap()
+    }
+    
     /// Get current statistics
     pub fn stats(&self) -> GossipStats {
-        self.stats.clone()
+        let seen = self.seen.stats();
+        GossipStats {
+            seen_evicted: seen.evicted,
+            seen_expired: seen.expired,
+            ..self.stats.clone()
+        }
     }
     
     /// Cleanup old message tracking data
@@ -214,20 +403,6 @@ impl GossipManager {
         // Remove old propagation times
         self.propagation_times.retain(|_, &mut time| time > cutoff);
         
-        // Keep only recent seen messages (simple approach: keep half)
-        if self.seen_messages.len() > self.config.max_tracked_messages / 2 {
-            let to_remove = self.seen_messages.len() - self.config.max_tracked_messages / 2;
-            let mut removed = 0;
-            self.seen_messages.retain(|_| {
-                if removed < to_remove {
-                    removed += 1;
-                    false
-                } else {
-                    true
-                }
-            });
-        }
-        
         debug!("Cleaned up old message tracking data");
     }
 }
@@ -316,7 +491,33 @@ mod tests {
         }
         
         // Should have cleaned up
-        assert!(manager.seen_messages.len() <= 10);
+        assert!(manager.seen.len() <= 10);
+        assert_eq!(manager.stats().seen_evicted, 10);
+    }
+    
+    #[test]
+    fn test_market_topics_and_topic_stats() {
+        let mut config = GossipConfig::default();
+        assert_eq!(config.market_topic(7), "openliquid/market/7/1.0.0");
+        
+        config.market_sharding = MarketSharding::Hashed { shards: 4 };
+        let topics: std::collections::HashSet<String> = (0..64).map(|asset| config.market_topic(asset)).collect();
+        assert_eq!(topics.len(), 4);
+        assert_eq!(config.market_topic(9), config.market_topic(9));
+        
+        let mut manager = GossipManager::new(config);
+        let topic = manager.config().market_topic(9);
+        assert!(manager.observe(&topic, b"fill"));
+        assert!(!manager.observe(&topic, b"fill"));
+        manager.record_encoded(&topic, 100, 40);
+        manager.record_encoded(TOPIC_BLOCKS, 10, 10);
+        
+        let stats = manager.topic_stats(&topic);
+        assert_eq!(stats.messages_received, 1);
+        assert_eq!(stats.duplicates_filtered, 1);
+        assert_eq!(stats.bytes_broadcast, 40);
+        assert_eq!(manager.stats().topics.len(), 2);
+        assert_eq!(manager.topic_stats(TOPIC_QCS), TopicStats::default());
     }
     
     #[test]
@@ -0,0 +1,263 @@
+// Command channel to the network event loop
+//
+// The swarm, peer table and gossip/validator state are owned by the event
+// loop alone. Other tasks talk to it through a cloneable NetworkHandle that
+// queues commands; the loop applies them between swarm events, so senders
+// never contend with the loop (or each other) for a lock.
+
+use super::{
+    announcement, gossip, NetworkError, NetworkHealth, NetworkManager, NetworkMessage, NetworkResult,
+    PeerInfo,
+};
+use crate::crypto::{BLSPublicKey, BLSSecretKey};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::HashMap;
+use tokio::sync::{mpsc, oneshot};
+
+/// Request applied by the network event loop
+pub(crate) enum NetworkCommand {
+    Listen {
+        addr: Multiaddr,
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+    Connect {
+        peer_id: PeerId,
+        addr: Multiaddr,
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+    Broadcast {
+        message: NetworkMessage,
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+    SendToPeer {
+        peer_id: PeerId,
+        message: NetworkMessage,
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+    AddValidator {
+        peer_id: PeerId,
+        reply: oneshot::Sender<()>,
+    },
+    SetValidatorSet {
+        epoch: u64,
+        validator_keys: HashMap<u64, BLSPublicKey>,
+        identity: Option<BLSSecretKey>,
+        reply: oneshot::Sender<()>,
+    },
+    Announce {
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+    ValidatorId {
+        peer_id: PeerId,
+        reply: oneshot::Sender<Option<u64>>,
+    },
+    ValidatorAddresses {
+        reply: oneshot::Sender<HashMap<u64, announcement::ValidatorAddress>>,
+    },
+    Peers {
+        reply: oneshot::Sender<Vec<PeerInfo>>,
+    },
+    Health {
+        reply: oneshot::Sender<NetworkHealth>,
+    },
+    GossipStats {
+        reply: oneshot::Sender<gossip::GossipStats>,
+    },
+    SubscribeMarket {
+        asset: u32,
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+    UnsubscribeMarket {
+        asset: u32,
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+    MarketSubscriptions {
+        reply: oneshot::Sender<Vec<u32>>,
+    },
+    Close {
+        reply: oneshot::Sender<NetworkResult<()>>,
+    },
+}
+
+/// Cloneable handle to a running network event loop
+///
+/// Every call is a command answered by the loop; calls fail with
+/// `SendError` once the loop has stopped.
+#[derive(Clone)]
+pub struct NetworkHandle {
+    peer_id: PeerId,
+    commands: mpsc::Sender<NetworkCommand>,
+}
+
+impl NetworkHandle {
+    pub(crate) fn new(peer_id: PeerId, commands: mpsc::Sender<NetworkCommand>) -> Self {
+        Self { peer_id, commands }
+    }
+
+    /// Local peer ID
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    /// Start listening on an address
+    pub async fn listen(&self, addr: Multiaddr) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::Listen { addr, reply }).await?
+    }
+
+    /// Dial a peer
+    pub async fn connect(&self, peer_id: PeerId, addr: Multiaddr) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::Connect { peer_id, addr, reply }).await?
+    }
+
+    /// Broadcast a gossip message
+    pub async fn broadcast(&self, message: NetworkMessage) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::Broadcast { message, reply }).await?
+    }
+
+    /// Send a consensus message to a validator peer
+    pub async fn send_to_peer(&self, peer_id: PeerId, message: NetworkMessage) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::SendToPeer { peer_id, message, reply }).await?
+    }
+
+    /// Add a validator to the network
+    pub async fn add_validator(&self, peer_id: PeerId) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::AddValidator { peer_id, reply }).await
+    }
+
+    /// Authenticate peers against the `epoch` validator set (see
+    /// `NetworkManager::set_validator_set`)
+    pub async fn set_validator_set(
+        &self,
+        epoch: u64,
+        validator_keys: HashMap<u64, BLSPublicKey>,
+        identity: Option<BLSSecretKey>,
+    ) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::SetValidatorSet { epoch, validator_keys, identity, reply }).await
+    }
+
+    /// Gossip our validator announcement, if we are a validator
+    pub async fn announce(&self) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::Announce { reply }).await?
+    }
+
+    /// Validator ID a peer authenticated or was announced as
+    pub async fn validator_id(&self, peer_id: PeerId) -> NetworkResult<Option<u64>> {
+        self.request(|reply| NetworkCommand::ValidatorId { peer_id, reply }).await
+    }
+
+    /// Latest announced addresses of every validator
+    pub async fn validator_addresses(&self) -> NetworkResult<HashMap<u64, announcement::ValidatorAddress>> {
+        self.request(|reply| NetworkCommand::ValidatorAddresses { reply }).await
+    }
+
+    /// Connected peers
+    pub async fn peers(&self) -> NetworkResult<Vec<PeerInfo>> {
+        self.request(|reply| NetworkCommand::Peers { reply }).await
+    }
+
+    /// Network health metrics
+    pub async fn health(&self) -> NetworkResult<NetworkHealth> {
+        self.request(|reply| NetworkCommand::Health { reply }).await
+    }
+
+    /// Gossip statistics
+    pub async fn gossip_stats(&self) -> NetworkResult<gossip::GossipStats> {
+        self.request(|reply| NetworkCommand::GossipStats { reply }).await
+    }
+
+    /// Follow the market data of an asset
+    pub async fn subscribe_market(&self, asset: u32) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::SubscribeMarket { asset, reply }).await?
+    }
+    
+    /// Stop following the market data of an asset
+    pub async fn unsubscribe_market(&self, asset: u32) -> NetworkResult<()> {
+        self.request(|reply| NetworkCommand::UnsubscribeMarket { asset, reply }).await?
+    }
+    
+    /// Assets whose market data is followed
+    pub async fn market_subscriptions(&self) -> NetworkResult<Vec<u32>> {
+        self.request(|reply| NetworkCommand::MarketSubscriptions { reply }).await
+    }
+    
+    /// Stop the event loop, waiting until it has drained (see
+    /// `NetworkManager::close`). Closing a stopped loop succeeds.
+    pub async fn close(&self) -> NetworkResult<()> {
+        match self.request(|reply| NetworkCommand::Close { reply }).await {
+            Ok(result) => result,
+            Err(_) => Ok(()),
+        }
+    }
+    
+    /// Queue a command and wait for the loop's answer
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> NetworkCommand) -> NetworkResult<T> {
+        let (reply, answer) = oneshot::channel();
+        self.commands.send(command(reply)).await.map_err(|_| stopped())?;
+        answer.await.map_err(|_| stopped())
+    }
+}
+
+fn stopped() -> NetworkError {
+    NetworkError::SendError("Network event loop stopped".to_string())
+}
+
+impl NetworkManager {
+    /// Apply a command from a handle; the requester may have given up on
+    /// the answer, so failed replies are ignored
+    pub(super) async fn handle_command(&mut self, command: NetworkCommand) {
+        match command {
+            NetworkCommand::Listen { addr, reply } => {
+                let _ = reply.send(self.listen(addr).await);
+            }
+            NetworkCommand::Connect { peer_id, addr, reply } => {
+                let _ = reply.send(self.connect(peer_id, addr).await);
+            }
+            NetworkCommand::Broadcast { message, reply } => {
+                let _ = reply.send(self.broadcast(message).await);
+            }
+            NetworkCommand::SendToPeer { peer_id, message, reply } => {
+                let _ = reply.send(self.send_to_peer(peer_id, message).await);
+            }
+            NetworkCommand::AddValidator { peer_id, reply } => {
+                self.add_validator(peer_id).await;
+                let _ = reply.send(());
+            }
+            NetworkCommand::SetValidatorSet { epoch, validator_keys, identity, reply } => {
+                self.set_validator_set(epoch, validator_keys, identity).await;
+                let _ = reply.send(());
+            }
+            NetworkCommand::Announce { reply } => {
+                let _ = reply.send(self.announce().await);
+            }
+            NetworkCommand::ValidatorId { peer_id, reply } => {
+                let _ = reply.send(self.validator_id(&peer_id));
+            }
+            NetworkCommand::ValidatorAddresses { reply } => {
+                let _ = reply.send(self.validator_addresses());
+            }
+            NetworkCommand::Peers { reply } => {
+                let _ = reply.send(self.peers());
+            }
+            NetworkCommand::Health { reply } => {
+                let _ = reply.send(self.health());
+            }
+            NetworkCommand::GossipStats { reply } => {
+                let _ = reply.send(self.gossip_stats());
+            }
+            NetworkCommand::SubscribeMarket { asset, reply } => {
+                let _ = reply.send(self.subscribe_market(asset));
+            }
+            NetworkCommand::UnsubscribeMarket { asset, reply } => {
+                let _ = reply.send(self.unsubscribe_market(asset));
+            }
+            NetworkCommand::MarketSubscriptions { reply } => {
+                let _ = reply.send(self.market_subscriptions());
+            }
+            NetworkCommand::Close { reply } => {
+                // Answered once the loop has drained
+                self.shutdown.cancel();
+                self.close_waiters.push(reply);
+            }
+        }
+    }
+}
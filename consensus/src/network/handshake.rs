@@ -0,0 +1,159 @@
+// Validator handshake tying libp2p peer identities to BLS validator keys
+//
+// Each side challenges the other with a fresh nonce. A validator answers
+// with a BLS signature over its own PeerId and the nonce, proving that the
+// key behind the connection belongs to a member of the validator set.
+
+use super::types::ControlMessage;
+use super::{NetworkError, NetworkResult};
+use crate::crypto::{partial_verify, threshold_sign, BLSPartialSignature, BLSPublicKey, BLSSecretKey};
+use libp2p::PeerId;
+use std::collections::HashMap;
+use tracing::info;
+
+/// Domain separator for handshake signatures
+pub const HANDSHAKE_DOMAIN: &[u8] = b"openliquid/validator-handshake/1";
+
+/// Bytes a validator signs to prove it controls `peer_id` in the session
+/// opened by `nonce`
+pub fn handshake_payload(peer_id: &PeerId, nonce: &[u8; 32]) -> Vec<u8> {
+    let peer_bytes = peer_id.to_bytes();
+    let mut payload = Vec::with_capacity(HANDSHAKE_DOMAIN.len() + peer_bytes.len() + nonce.len());
+    payload.extend_from_slice(HANDSHAKE_DOMAIN);
+    payload.extend_from_slice(&peer_bytes);
+    payload.extend_from_slice(nonce);
+    payload
+}
+
+/// Handshake state of the local node
+pub struct ValidatorHandshake {
+    /// Our PeerId, bound into the proofs we sign
+    local_peer: PeerId,
+    /// Our validator key, if we are a validator
+    identity: Option<BLSSecretKey>,
+    /// Public keys of the validator set by validator ID
+    validator_keys: HashMap<u64, BLSPublicKey>,
+    /// Outstanding nonces we sent, by peer
+    challenges: HashMap<PeerId, [u8; 32]>,
+    /// Authenticated peers and their validator IDs
+    authenticated: HashMap<PeerId, u64>,
+}
+
+impl ValidatorHandshake {
+    /// Create handshake state for the validator set `validator_keys`
+    ///
+    /// With an empty validator set authentication is disabled.
+    pub fn new(
+        local_peer: PeerId,
+        validator_keys: HashMap<u64, BLSPublicKey>,
+        identity: Option<BLSSecretKey>,
+    ) -> Self {
+        Self {
+            local_peer,
+            identity,
+            validator_keys,
+            challenges: HashMap::new(),
+            authenticated: HashMap::new(),
+        }
+    }
+
+    /// Whether peers must authenticate before acting as validators
+    pub fn is_enabled(&self) -> bool {
+        !self.validator_keys.is_empty()
+    }
+
+    /// Our validator key, if we are a validator
+    pub fn identity(&self) -> Option<&BLSSecretKey> {
+        self.identity.as_ref()
+    }
+
+    /// Open a session with `peer` by sending it a fresh nonce
+    pub fn challenge(&mut self, peer: PeerId) -> ControlMessage {
+        let nonce: [u8; 32] = rand::random();
+        self.challenges.insert(peer, nonce);
+        ControlMessage::HandshakeChallenge { nonce }
+    }
+
+    /// Answer a peer's challenge; non-validators have nothing to prove
+    pub fn respond(&self, nonce: &[u8; 32]) -> Option<ControlMessage> {
+        let secret_key = self.identity.as_ref()?;
+        let proof = threshold_sign(secret_key, &handshake_payload(&self.local_peer, nonce));
+        Some(ControlMessage::HandshakeProof { proof })
+    }
+
+    /// Check a peer's proof against the challenge we sent it
+    ///
+    /// The nonce is single-use, so a proof cannot be replayed. A validator
+    /// authenticating from a new PeerId replaces its previous connection.
+    /// Returns the peer's validator ID.
+    pub fn verify(&mut self, peer: PeerId, proof: &BLSPartialSignature) -> NetworkResult<u64> {
+        let failed = |reason: String| NetworkError::HandshakeFailed(peer, reason);
+        let nonce = self
+            .challenges
+            .remove(&peer)
+            .ok_or_else(|| failed("no outstanding challenge".to_string()))?;
+        let public_key = self
+            .validator_keys
+            .get(&proof.validator_id)
+            .ok_or_else(|| failed(format!("unknown validator {}", proof.validator_id)))?;
+        if !partial_verify(&handshake_payload(&peer, &nonce), proof, public_key) {
+            return Err(failed(format!("invalid signature for validator {}", proof.validator_id)));
+        }
+
+        self.authenticated.retain(|_, id| *id != proof.validator_id);
+        self.authenticated.insert(peer, proof.validator_id);
+        info!("Peer {} authenticated as validator {}", peer, proof.validator_id);
+        Ok(proof.validator_id)
+    }
+
+    /// Validator ID of an authenticated peer
+    pub fn validator_id(&self, peer: &PeerId) -> Option<u64> {
+        self.authenticated.get(peer).copied()
+    }
+
+    /// Whether `peer` has authenticated as a validator
+    pub fn is_authenticated(&self, peer: &PeerId) -> bool {
+        self.authenticated.contains_key(peer)
+    }
+
+    /// Forget a disconnected peer
+    pub fn remove_peer(&mut self, peer: &PeerId) {
+        self.challenges.remove(peer);
+        self.authenticated.remove(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::BLSKeyPair;
+
+    #[test]
+    fn test_handshake_binds_peer_id_and_nonce() {
+        let keys: Vec<_> = (0..2).map(BLSKeyPair::with_id).collect();
+        let validator_keys: HashMap<u64, BLSPublicKey> =
+            keys.iter().map(|k| (k.public_key.validator_id(), k.public_key.clone())).collect();
+        let (local, remote, imposter) = (PeerId::random(), PeerId::random(), PeerId::random());
+
+        let mut ours = ValidatorHandshake::new(local, validator_keys.clone(), None);
+        let theirs = ValidatorHandshake::new(remote, validator_keys.clone(), Some(keys[1].secret_key.clone()));
+        assert!(ours.respond(&[0; 32]).is_none());
+
+        let ControlMessage::HandshakeChallenge { nonce } = ours.challenge(remote) else { unreachable!() };
+        let Some(ControlMessage::HandshakeProof { proof }) = theirs.respond(&nonce) else { unreachable!() };
+
+        // The proof only holds for the PeerId it was signed for, and only once
+        ours.challenge(imposter);
+        assert!(ours.verify(imposter, &proof).is_err());
+        assert_eq!(ours.verify(remote, &proof).unwrap(), 1);
+        assert!(ours.verify(remote, &proof).is_err());
+        assert_eq!(ours.validator_id(&remote), Some(1));
+        assert!(!ours.is_authenticated(&imposter));
+
+        // Keys outside the validator set are refused
+        let outsider = ValidatorHandshake::new(remote, validator_keys, Some(BLSKeyPair::with_id(7).secret_key));
+        let ControlMessage::HandshakeChallenge { nonce } = ours.challenge(remote) else { unreachable!() };
+        let Some(ControlMessage::HandshakeProof { proof }) = outsider.respond(&nonce) else { unreachable!() };
+        assert!(ours.verify(remote, &proof).is_err());
+    }
+}
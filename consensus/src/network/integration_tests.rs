@@ -29,6 +29,7 @@ fn test_network_config(port: u16) -> NetworkConfig {
         gossip_interval: Duration::from_millis(100),
         heartbeat_interval: Duration::from_secs(10),
         connection_timeout: Duration::from_secs(30),
+        enable_quic: false,
     }
 }
 
@@ -85,7 +86,7 @@ impl NetworkCluster {
     /// Check if all nodes have quorum connectivity
     async fn all_have_quorum(&self) -> bool {
         for (peer_id, network) in &self.nodes {
-            let health = network.health().await;
+            let health = network.health();
             let config = self.configs.get(peer_id).unwrap();
             
             if health.validator_peers < config.min_validators() {
@@ -107,7 +108,7 @@ async fn test_two_node_network() {
     // Verify both nodes know about each other
     for peer_id in &peer_ids {
         let network = cluster.get_network(peer_id).unwrap();
-        let health = network.health().await;
+        let health = network.health();
         assert_eq!(health.validator_peers, 1);
     }
 }
@@ -123,7 +124,7 @@ async fn test_four_node_mesh_network() {
     // Each node should know about 3 other validators
     for peer_id in &peer_ids {
         let network = cluster.get_network(peer_id).unwrap();
-        let health = network.health().await;
+        let health = network.health();
         assert_eq!(health.validator_peers, 3);
     }
 }
@@ -150,7 +151,7 @@ async fn test_broadcast_to_multiple_nodes() {
     let _ = sender.broadcast(message).await;
     
     // Verify the cluster has correct validator connectivity
-    let health = sender.health().await;
+    let health = sender.health();
     assert_eq!(health.validator_peers, 2); // Should be connected to 2 other validators
 }
 
@@ -186,7 +187,7 @@ async fn test_network_partition_detection() {
     let network = NetworkManager::new(config).unwrap();
     
     // With no connected validators, should detect partition
-    assert!(network.check_partition().await);
+    assert!(network.check_partition());
 }
 
 #[tokio::test]
@@ -197,7 +198,7 @@ async fn test_network_quorum_restoration() {
     let mut network = NetworkManager::new(config).unwrap();
     
     // Initially in partition
-    assert!(network.check_partition().await);
+    assert!(network.check_partition());
     
     // Add quorum of validators
     for _ in 0..5 {
@@ -205,7 +206,7 @@ async fn test_network_quorum_restoration() {
     }
     
     // Should no longer be in partition
-    assert!(!network.check_partition().await);
+    assert!(!network.check_partition());
 }
 
 #[tokio::test]
@@ -218,7 +219,7 @@ async fn test_cluster_health_metrics() {
     // Check health metrics for all nodes
     for peer_id in &peer_ids {
         let network = cluster.get_network(peer_id).unwrap();
-        let health = network.health().await;
+        let health = network.health();
         
         // Each node should be connected to 4 other validators
         assert_eq!(health.validator_peers, 4);
@@ -249,8 +250,7 @@ async fn test_validator_channel_message_tracking() {
     }
     
     // Check that messages were tracked
-    let validator_channel = sender.validator_channel.read().await;
-    let stats = validator_channel.stats();
+    let stats = sender.validator_channel.stats();
     assert_eq!(stats.total_sent, 5);
 }
 
@@ -275,7 +275,7 @@ async fn test_gossip_deduplication() {
     );
     
     // Mark message as seen
-    let mut gossip_manager = node.gossip_manager.write().await;
+    let gossip_manager = &mut node.gossip_manager;
     gossip_manager.mark_seen(message_id.clone());
     
     // Check if it's detected as duplicate
@@ -314,7 +314,7 @@ async fn test_large_cluster() {
     // Each node should know about 9 other validators
     for peer_id in &peer_ids {
         let network = cluster.get_network(peer_id).unwrap();
-        let health = network.health().await;
+        let health = network.health();
         assert_eq!(health.validator_peers, 9);
     }
 }
@@ -334,11 +334,11 @@ async fn test_partial_connectivity() {
     
     // Nodes 0 and 2 should each only know about 1 validator
     let node0 = cluster.get_network(&peer_ids[0]).unwrap();
-    let health0 = node0.health().await;
+    let health0 = node0.health();
     assert_eq!(health0.validator_peers, 1);
     
     let node2 = cluster.get_network(&peer_ids[2]).unwrap();
-    let health2 = node2.health().await;
+    let health2 = node2.health();
     assert_eq!(health2.validator_peers, 1);
 }
 
@@ -382,7 +382,7 @@ async fn test_network_metrics_aggregation() {
     // Check that all nodes have proper validator connectivity
     for peer_id in &peer_ids {
         let node = cluster.get_network(peer_id).unwrap();
-        let health = node.health().await;
+        let health = node.health();
         
         // Each node should be connected to 2 other validators
         assert_eq!(health.validator_peers, 2);
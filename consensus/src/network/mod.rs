@@ -5,23 +5,37 @@
 // - libp2p integration for peer discovery and connection management
 // - Gossip protocol for block/transaction broadcasting
 // - Direct validator channels for votes and proposals
+// - Signed validator announcements keeping connections to the whole set
+// - QUIC transport with per-peer fallback to TCP/noise/yamux
 // - Network partition detection and recovery
 
 use libp2p::{
+    core::muxing::StreamMuxerBox,
+    futures::future::Either,
     identity::Keypair,
-    noise, yamux,
+    noise, quic, yamux,
+    swarm::dial_opts::DialOpts,
     tcp, Multiaddr, PeerId, Swarm, Transport,
     futures::StreamExt,
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
+    num::NonZeroU8,
     sync::Arc,
     time::{Duration, Instant},
 };
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
+pub mod announcement;
+pub mod bandwidth;
+pub mod channel;
 pub mod gossip;
+pub mod handle;
+pub mod handshake;
+pub mod peer_store;
+pub mod seen_cache;
 pub mod types;
 pub mod validator;
 
@@ -31,7 +45,9 @@ mod integration_tests;
 #[cfg(test)]
 mod performance_tests;
 
-pub use types::{NetworkConfig, NetworkEvent, NetworkMessage};
+pub use channel::{ChannelConfig, ChannelMetrics, OverflowPolicy};
+pub use handle::NetworkHandle;
+pub use types::{quic_address, NetworkConfig, NetworkEvent, NetworkMessage};
 
 /// Network error types
 #[derive(Debug, thiserror::Error)]
@@ -48,8 +64,22 @@ pub enum NetworkError {
     NetworkPartition,
     #[error("Invalid message format")]
     InvalidMessage,
+    #[error("Message of {size} bytes exceeds the {limit} byte limit")]
+    MessageTooLarge { size: usize, limit: usize },
     #[error("Gossipsub error: {0}")]
     GossipsubError(String),
+    #[error("Handshake with {0} failed: {1}")]
+    HandshakeFailed(PeerId, String),
+    #[error("Peer {0} has not authenticated as a validator")]
+    NotAuthenticated(PeerId),
+    #[error("Invalid validator announcement: {0}")]
+    InvalidAnnouncement(String),
+    #[error("Peer store error: {0}")]
+    PeerStore(String),
+    #[error("Egress rate limit to {0} exceeded")]
+    RateLimited(PeerId),
+    #[error("Rate limit burst of {burst} bytes is below the largest message of {largest} bytes")]
+    BurstTooSmall { burst: u64, largest: usize },
 }
 
 /// Result type for network operations
@@ -62,33 +92,136 @@ pub type NetworkResult<T> = Result<T, NetworkError>;
 /// - Gossip protocol for broadcasting
 /// - Direct validator channels for consensus messages
 /// - Network health monitoring
+///
+/// All of this state has a single owner: the manager is driven directly
+/// before it runs, and once `run` (or `spawn`) starts the event loop, other
+/// tasks reach it through `NetworkHandle` commands instead of locks.
 pub struct NetworkManager {
     /// Local peer ID
     peer_id: PeerId,
     
     /// libp2p swarm
-    swarm: Arc<RwLock<Swarm<gossip::Behaviour>>>,
+    swarm: Swarm<gossip::Behaviour>,
     
-    /// Event receiver channel
-    event_rx: mpsc::UnboundedReceiver<NetworkEvent>,
+    /// Peer, consensus and gossip events for the consumer
+    events: NetworkEvents,
     
     /// Event sender channel (for internal use)
-    event_tx: mpsc::UnboundedSender<NetworkEvent>,
+    event_tx: channel::BoundedSender<NetworkEvent>,
+    
+    /// Gossip event sender channel (for internal use)
+    gossip_tx: channel::BoundedSender<NetworkEvent>,
+    
+    /// Command sender, cloned into handles
+    command_tx: mpsc::Sender<handle::NetworkCommand>,
+    
+    /// Commands from handles, applied by the event loop
+    command_rx: mpsc::Receiver<handle::NetworkCommand>,
     
     /// Connected peers
-    peers: Arc<RwLock<HashMap<PeerId, PeerInfo>>>,
+    peers: HashMap<PeerId, PeerInfo>,
     
     /// Network configuration
     config: NetworkConfig,
     
     /// Network health metrics
-    health: Arc<RwLock<NetworkHealth>>,
+    health: NetworkHealth,
     
     /// Gossip manager for message tracking
-    gossip_manager: Arc<RwLock<gossip::GossipManager>>,
+    gossip_manager: gossip::GossipManager,
     
     /// Validator channel for direct communication
-    validator_channel: Arc<RwLock<validator::ValidatorChannel>>,
+    validator_channel: validator::ValidatorChannel,
+    
+    /// Codecs announced by peers
+    peer_codecs: HashMap<PeerId, Vec<types::Codec>>,
+    
+    /// Validator authentication of peers
+    handshake: handshake::ValidatorHandshake,
+    
+    /// Announced PeerIds and addresses of the validator set
+    address_book: announcement::AddressBook,
+    
+    /// Progress of the event loop, for the watchdog
+    heartbeat: Option<crate::watchdog::Heartbeat>,
+    
+    /// Assets whose market data topics we follow
+    market_subscriptions: BTreeSet<u32>,
+    
+    /// Stops the event loop
+    shutdown: CancellationToken,
+    
+    /// Handles waiting for the loop to finish closing
+    close_waiters: Vec<oneshot::Sender<NetworkResult<()>>>,
+    
+    /// Known peer addresses and dial backoff, kept across restarts once
+    /// storage is set
+    peer_book: peer_store::PeerBook,
+    
+    /// Traffic accounting and per-peer rate limits
+    bandwidth: bandwidth::BandwidthTracker,
+}
+
+/// How often disconnected validators from the peer book are redialed
+const REDIAL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wall-clock time in milliseconds since the Unix epoch
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Longest time spent flushing outbound traffic on shutdown
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Shutdown drain ends early once the swarm is quiet for this long
+const DRAIN_QUIET: Duration = Duration::from_millis(100);
+
+/// Receiving end of the network's events
+///
+/// Peer/consensus events block the event loop when full; gossip events
+/// drop the oldest.
+pub struct NetworkEvents {
+    event_rx: channel::BoundedReceiver<NetworkEvent>,
+    gossip_rx: channel::BoundedReceiver<NetworkEvent>,
+}
+
+impl NetworkEvents {
+    /// Receive the next network event, preferring peer/consensus events over gossip
+    pub async fn next_event(&mut self) -> Option<NetworkEvent> {
+        if let Some(event) = self.event_rx.try_recv() {
+            return Some(event);
+        }
+        if let Some(event) = self.gossip_rx.try_recv() {
+            return Some(event);
+        }
+        
+        tokio::select! {
+            biased;
+            event = self.event_rx.recv() => event,
+            event = self.gossip_rx.recv() => event,
+        }
+    }
+    
+    /// Receivers whose senders are gone, left behind by `spawn`
+    fn detached() -> Self {
+        let (_, event_rx) = channel::bounded(1, OverflowPolicy::Block);
+        let (_, gossip_rx) = channel::bounded(1, OverflowPolicy::DropOldest);
+        Self { event_rx, gossip_rx }
+    }
+}
+
+/// Saturation metrics of the network layer's channels
+#[derive(Debug, Clone)]
+pub struct NetworkChannelMetrics {
+    /// Peer and consensus events
+    pub events: ChannelMetrics,
+    /// Gossip events
+    pub gossip: ChannelMetrics,
+    /// Incoming validator messages
+    pub validator: ChannelMetrics,
 }
 
 /// Information about a connected peer
@@ -137,6 +270,9 @@ pub struct NetworkHealth {
     
     /// Total messages received
     pub total_messages_received: u64,
+    
+    /// Bytes in and out by peer and topic, with rate limit activity
+    pub bandwidth: bandwidth::BandwidthStats,
 }
 
 impl Default for NetworkHealth {
@@ -149,6 +285,7 @@ impl Default for NetworkHealth {
             last_partition_check: Instant::now(),
             total_messages_sent: 0,
             total_messages_received: 0,
+            bandwidth: bandwidth::BandwidthStats::default(),
         }
     }
 }
@@ -156,45 +293,75 @@ impl Default for NetworkHealth {
 impl NetworkManager {
     /// Create a new network manager
     pub fn new(config: NetworkConfig) -> NetworkResult<Self> {
+        Self::new_with_channels(config, ChannelConfig::default())
+    }
+    
+    /// Create a new network manager with custom channel capacities
+    pub fn new_with_channels(config: NetworkConfig, channels: ChannelConfig) -> NetworkResult<Self> {
         let keypair = Keypair::generate_ed25519();
         let peer_id = PeerId::from(keypair.public());
         
         info!("Creating network manager with peer ID: {}", peer_id);
         
-        // Create event channel
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        // Create event channels: consensus-critical events block, gossip drops oldest
+        let (event_tx, event_rx) = channel::bounded(channels.event_capacity, OverflowPolicy::Block);
+        let (gossip_tx, gossip_rx) = channel::bounded(channels.gossip_capacity, OverflowPolicy::DropOldest);
+        let (command_tx, command_rx) = mpsc::channel(channels.command_capacity);
         
         // Build the transport
-        let transport = libp2p::tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
+        let tcp_transport = libp2p::tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
             .upgrade(libp2p::core::upgrade::Version::V1)
             .authenticate(noise::Config::new(&keypair).unwrap())
-            .multiplex(yamux::Config::default())
-            .boxed();
+            .multiplex(yamux::Config::default());
+        let transport = if config.enable_quic {
+            // QUIC handles /udp/../quic-v1 addresses, TCP everything else
+            quic::tokio::Transport::new(quic::Config::new(&keypair))
+                .or_transport(tcp_transport)
+                .map(|output, _| match output {
+                    Either::Left((peer_id, conn)) => (peer_id, StreamMuxerBox::new(conn)),
+                    Either::Right((peer_id, conn)) => (peer_id, StreamMuxerBox::new(conn)),
+                })
+                .boxed()
+        } else {
+            tcp_transport.boxed()
+        };
         
         // Create network behavior (will be implemented in separate modules)
-        let behaviour = gossip::create_behaviour(&config)?;
+        let gossip_config = gossip::GossipConfig::default();
+        let behaviour = gossip::create_behaviour(&config, &gossip_config)?;
         
         // Create swarm
         let swarm_config = libp2p::swarm::Config::with_tokio_executor();
         let swarm = Swarm::new(transport, behaviour, peer_id, swarm_config);
         
         // Create gossip manager
-        let gossip_config = gossip::GossipConfig::default();
         let gossip_manager = gossip::GossipManager::new(gossip_config);
         
         // Create validator channel
-        let validator_channel = validator::ValidatorChannel::new(peer_id);
+        let validator_channel = validator::ValidatorChannel::with_capacity(peer_id, channels.validator_capacity);
         
         Ok(Self {
             peer_id,
-            swarm: Arc::new(RwLock::new(swarm)),
-            event_rx,
+            swarm,
+            events: NetworkEvents { event_rx, gossip_rx },
             event_tx,
-            peers: Arc::new(RwLock::new(HashMap::new())),
+            gossip_tx,
+            command_tx,
+            command_rx,
+            peers: HashMap::new(),
             config,
-            health: Arc::new(RwLock::new(NetworkHealth::default())),
-            gossip_manager: Arc::new(RwLock::new(gossip_manager)),
-            validator_channel: Arc::new(RwLock::new(validator_channel)),
+            health: NetworkHealth::default(),
+            gossip_manager,
+            validator_channel,
+            peer_codecs: HashMap::new(),
+            handshake: handshake::ValidatorHandshake::new(peer_id, HashMap::new(), None),
+            address_book: announcement::AddressBook::default(),
+            heartbeat: None,
+            market_subscriptions: BTreeSet::new(),
+            shutdown: CancellationToken::new(),
+            close_waiters: Vec::new(),
+            peer_book: peer_store::PeerBook::new(peer_store::DialBackoff::default()),
+            bandwidth: bandwidth::BandwidthTracker::default(),
         })
     }
     
@@ -203,33 +370,103 @@ impl NetworkManager {
         self.peer_id
     }
     
+    /// Report event loop progress and queue depths to a watchdog: the loop
+    /// is busy while handling a wakeup and idle while waiting for one
+    pub fn set_heartbeat(&mut self, heartbeat: crate::watchdog::Heartbeat) {
+        self.heartbeat = Some(heartbeat);
+    }
+    
+    /// Handle for sending commands to the event loop from other tasks
+    pub fn handle(&self) -> NetworkHandle {
+        NetworkHandle::new(self.peer_id, self.command_tx.clone())
+    }
+    
+    /// Token that stops the event loop when cancelled; the loop drains
+    /// before `run` returns
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+    
+    /// Keep the peer book in `storage` across restarts: stored peers are
+    /// dialed when the event loop starts, and validators are redialed with
+    /// backoff whenever they are disconnected
+    pub fn set_peer_storage(&mut self, storage: Arc<crate::storage::Storage>, backoff: peer_store::DialBackoff) -> NetworkResult<()> {
+        self.peer_book = peer_store::PeerBook::with_storage(backoff, storage)?;
+        info!("Loaded {} peers from storage", self.peer_book.len());
+        Ok(())
+    }
+    
+    /// Known peers with their connection history
+    pub fn peer_book(&self) -> &peer_store::PeerBook {
+        &self.peer_book
+    }
+    
+    /// Stop the network: flush outbound traffic and disconnect every peer. For a manager driven directly; a spawned
+    /// event loop is closed through its handle.
+    pub async fn close(&mut self) -> NetworkResult<()> {
+        self.shutdown.cancel();
+        self.drain().await
+    }
+    
+    /// Run the event loop on its own task, returning a handle to it and
+    /// the receiving end of its events
+    pub fn spawn(mut self) -> (NetworkHandle, NetworkEvents) {
+        let handle = self.handle();
+        let events = std::mem::replace(&mut self.events, NetworkEvents::detached());
+        tokio::spawn(async move {
+            if let Err(e) = self.run().await {
+                error!("Network event loop failed: {}", e);
+            }
+        });
+        (handle, events)
+    }
+    
     /// Add a validator to the network
     pub async fn add_validator(&mut self, peer_id: PeerId) {
-        let mut validator_channel = self.validator_channel.write().await;
-        validator_channel.add_validator(peer_id);
+        self.validator_channel.add_validator(peer_id);
         
         // Update health metrics
-        let mut health = self.health.write().await;
-        health.validator_peers = validator_channel.stats().active_connections;
+        self.health.validator_peers = self.validator_channel.stats().active_connections;
+        #[cfg(feature = "metrics")]
+        crate::metrics::global().set_peers(self.health.connected_peers, self.health.validator_peers);
         
         info!("Added validator: {}", peer_id);
     }
     
     /// Start listening on the configured address
+    /// 
+    /// With QUIC enabled, a TCP address is also listened on over QUIC at
+    /// the same host and port.
     pub async fn listen(&mut self, addr: Multiaddr) -> NetworkResult<()> {
-        let mut swarm = self.swarm.write().await;
-        swarm.listen_on(addr.clone())
+        self.swarm.listen_on(addr.clone())
             .map_err(|e| NetworkError::SendError(format!("Failed to listen: {}", e)))?;
-        
         info!("Network listening on: {}", addr);
+        
+        if let Some(quic_addr) = self.config.enable_quic.then(|| quic_address(&addr)).flatten() {
+            self.swarm.listen_on(quic_addr.clone())
+                .map_err(|e| NetworkError::SendError(format!("Failed to listen: {}", e)))?;
+            info!("Network listening on: {}", quic_addr);
+        }
         Ok(())
     }
     
     /// Connect to a peer
+    /// 
+    /// With QUIC enabled, a TCP address is dialed over QUIC first and over
+    /// TCP only if the QUIC attempt fails.
     pub async fn connect(&mut self, peer_id: PeerId, addr: Multiaddr) -> NetworkResult<()> {
-        let mut swarm = self.swarm.write().await;
-        swarm.dial(addr.clone())
-            .map_err(|e| NetworkError::SendError(format!("Failed to dial: {}", e)))?;
+        match self.config.enable_quic.then(|| quic_address(&addr)).flatten() {
+            Some(quic_addr) => {
+                let opts = DialOpts::peer_id(peer_id)
+                    .addresses(vec![quic_addr, addr.clone()])
+                    // Try the addresses one at a time, in order
+                    .override_dial_concurrency_factor(NonZeroU8::MIN)
+                    .build();
+                self.swarm.dial(opts)
+            }
+            None => self.swarm.dial(addr.clone()),
+        }
+        .map_err(|e| NetworkError::SendError(format!("Failed to dial: {}", e)))?;
         
         info!("Connecting to peer {} at {}", peer_id, addr);
         Ok(())
@@ -239,44 +476,65 @@ impl NetworkManager {
     pub async fn broadcast(&mut self, message: NetworkMessage) -> NetworkResult<()> {
         debug!("Broadcasting message: {:?}", message.message_type());
         
-        // Serialize the message
-        let msg_bytes = message.to_bytes()
-            .map_err(|e| NetworkError::SendError(format!("Serialization failed: {}", e)))?;
-        
         // Determine the topic based on message type
+        let market_topic;
         let topic = match &message {
             NetworkMessage::Gossip(gossip_msg) => match gossip_msg {
                 types::GossipMessage::Block { .. } => gossip::TOPIC_BLOCKS,
                 types::GossipMessage::Transaction { .. } => gossip::TOPIC_TRANSACTIONS,
                 types::GossipMessage::QuorumCert { .. } => gossip::TOPIC_QCS,
+                types::GossipMessage::ValidatorAnnouncement { .. } => gossip::TOPIC_VALIDATORS,
+                types::GossipMessage::MarketData { asset, .. } => {
+                    market_topic = self.gossip_manager.config().market_topic(*asset);
+                    market_topic.as_str()
+                }
             },
             _ => return Err(NetworkError::InvalidMessage),
         };
         
+        // Refuse messages over the topic's limit before compressing
+        let raw_size = message.raw_size()
+            .map_err(|e| NetworkError::SendError(format!("Serialization failed: {}", e)))? as usize;
+        let max_size = self.gossip_manager.config().max_message_size(topic);
+        if raw_size > max_size {
+            self.gossip_manager.record_oversized(topic, raw_size);
+            return Err(NetworkError::MessageTooLarge { size: raw_size, limit: max_size });
+        }
+        
+        // Serialize the message with a codec every peer can decode
+        let msg_bytes = message.encode(self.gossip_codec())
+            .map_err(|e| NetworkError::SendError(format!("Serialization failed: {}", e)))?;
+        let message_id = libp2p::gossipsub::MessageId::from(
+            blake3::hash(&msg_bytes).as_bytes().to_vec()
+        );
+        let encoded_size = msg_bytes.len();
+        
         // Publish to gossipsub
-        let topic = libp2p::gossipsub::IdentTopic::new(topic);
-        let mut swarm = self.swarm.write().await;
-        swarm.behaviour_mut().gossipsub.publish(topic, msg_bytes)
+        self.swarm.behaviour_mut().gossipsub.publish(libp2p::gossipsub::IdentTopic::new(topic), msg_bytes)
             .map_err(|e| NetworkError::GossipsubError(format!("Publish failed: {}", e)))?;
         
         // Track the broadcast
-        let mut gossip_manager = self.gossip_manager.write().await;
-        let message_id = libp2p::gossipsub::MessageId::from(
-            blake3::hash(&message.to_bytes().unwrap()).as_bytes().to_vec()
-        );
-        gossip_manager.track_broadcast(message_id);
+        self.gossip_manager.record_encoded(topic, raw_size, encoded_size);
+        self.bandwidth.record_out(None, topic, encoded_size);
+        self.gossip_manager.track_broadcast(message_id);
         
         // Update health metrics
-        let mut health = self.health.write().await;
-        health.total_messages_sent += 1;
+        self.health.total_messages_sent += 1;
         
         Ok(())
     }
     
     /// Send a direct message to a specific peer (for validator communication)
+    ///
+    /// Once a validator set is configured, only peers that authenticated
+    /// or were announced as validators can be sent consensus messages.
     pub async fn send_to_peer(&mut self, peer_id: PeerId, message: NetworkMessage) -> NetworkResult<()> {
         debug!("Sending message to peer {}: {:?}", peer_id, message.message_type());
         
+        if self.handshake.is_enabled() && self.validator_id(&peer_id).is_none() {
+            return Err(NetworkError::NotAuthenticated(peer_id));
+        }
+        
         // Convert NetworkMessage to ValidatorMessage if it's a consensus message
         let validator_msg = match message {
             NetworkMessage::Consensus(consensus_msg) => {
@@ -338,13 +596,19 @@ impl NetworkManager {
             _ => return Err(NetworkError::InvalidMessage),
         };
         
+        // Hold the peer to its egress limit
+        let size = bincode::serialized_size(&validator_msg)
+            .map_err(|e| NetworkError::SendError(format!("Serialization failed: {}", e)))? as usize;
+        if !self.bandwidth.reserve_out(peer_id, size, Instant::now()) {
+            return Err(NetworkError::RateLimited(peer_id));
+        }
+        
         // Send through validator channel
-        let mut validator_channel = self.validator_channel.write().await;
-        validator_channel.send_to_validator(&peer_id, validator_msg).await?;
+        self.validator_channel.send_to_validator(&peer_id, validator_msg).await?;
+        self.bandwidth.record_out(Some(peer_id), bandwidth::DIRECT_TOPIC, size);
         
         // Update peer info
-        let mut peers = self.peers.write().await;
-        if let Some(peer) = peers.get_mut(&peer_id) {
+        if let Some(peer) = self.peers.get_mut(&peer_id) {
             peer.messages_sent += 1;
             peer.last_seen = Instant::now();
         }
@@ -352,30 +616,254 @@ impl NetworkManager {
         Ok(())
     }
     
-    /// Receive the next network event
+    /// Receive the next network event, preferring peer/consensus events over gossip
     pub async fn next_event(&mut self) -> Option<NetworkEvent> {
-        self.event_rx.recv().await
+        self.events.next_event().await
+    }
+    
+    /// Get saturation metrics of the network channels
+    pub fn channel_metrics(&self) -> NetworkChannelMetrics {
+        NetworkChannelMetrics {
+            events: self.events.event_rx.metrics(),
+            gossip: self.events.gossip_rx.metrics(),
+            validator: self.validator_channel.incoming_metrics(),
+        }
     }
     
     /// Get connected peers
-    pub async fn peers(&self) -> Vec<PeerInfo> {
-        let peers = self.peers.read().await;
-        peers.values().cloned().collect()
+    pub fn peers(&self) -> Vec<PeerInfo> {
+        self.peers.values().cloned().collect()
     }
     
     /// Get network health metrics
-    pub async fn health(&self) -> NetworkHealth {
-        self.health.read().await.clone()
+    pub fn health(&self) -> NetworkHealth {
+        let mut health = self.health.clone();
+        health.bandwidth = self.bandwidth.stats().clone();
+        health
     }
     
-    /// Check for network partition
-    pub async fn check_partition(&self) -> bool {
-        let health = self.health.read().await;
+    /// Hold every peer to `config`'s rate limits. Each burst must fit the
+    /// largest message a topic accepts, or such messages could never pass.
+    pub fn set_bandwidth_limits(&mut self, config: bandwidth::BandwidthConfig) -> NetworkResult<()> {
+        let largest = self.gossip_manager.config().max_transmit_size();
+        for limit in [config.ingress, config.egress].into_iter().flatten() {
+            if limit.burst < largest as u64 {
+                return Err(NetworkError::BurstTooSmall { burst: limit.burst, largest });
+            }
+        }
+        self.bandwidth.set_config(config);
+        Ok(())
+    }
+    
+    /// Get gossip statistics, including compression ratio and messages
+    /// dropped for size
+    pub fn gossip_stats(&self) -> gossip::GossipStats {
+        self.gossip_manager.stats()
+    }
+    
+    /// Follow the market data (order flow and fills) of `asset`
+    pub fn subscribe_market(&mut self, asset: u32) -> NetworkResult<()> {
+        let topic = libp2p::gossipsub::IdentTopic::new(self.gossip_manager.config().market_topic(asset));
+        self.swarm.behaviour_mut().gossipsub.subscribe(&topic)
+            .map_err(|e| NetworkError::GossipsubError(format!("Failed to subscribe: {}", e)))?;
+        self.market_subscriptions.insert(asset);
+        Ok(())
+    }
+    
+    /// Stop following the market data of `asset`; a hashed shard is left
+    /// once no subscribed asset maps to it
+    pub fn unsubscribe_market(&mut self, asset: u32) -> NetworkResult<()> {
+        if !self.market_subscriptions.remove(&asset) {
+            return Ok(());
+        }
+        let config = self.gossip_manager.config();
+        let topic = config.market_topic(asset);
+        if self.market_subscriptions.iter().any(|other| config.market_topic(*other) == topic) {
+            return Ok(());
+        }
+        self.swarm.behaviour_mut().gossipsub.unsubscribe(&libp2p::gossipsub::IdentTopic::new(topic))
+            .map_err(|e| NetworkError::GossipsubError(format!("Failed to unsubscribe: {}", e)))?;
+        Ok(())
+    }
+    
+    /// Assets whose market data we follow
+    pub fn market_subscriptions(&self) -> Vec<u32> {
+        self.market_subscriptions.iter().copied().collect()
+    }
+    
+    /// Change how market data is split across topics, moving current
+    /// subscriptions to their new topics. Every node must use the same layout.
+    pub fn set_market_sharding(&mut self, sharding: gossip::MarketSharding) -> NetworkResult<()> {
+        let assets = self.market_subscriptions();
+        for asset in &assets {
+            self.unsubscribe_market(*asset)?;
+        }
+        self.gossip_manager.set_market_sharding(sharding);
+        for asset in assets {
+            self.subscribe_market(asset)?;
+        }
+        Ok(())
+    }
+    
+    /// Require peers to authenticate against the `epoch` validator set
+    /// `validator_keys` before they are treated as validators, signing our
+    /// own proofs and announcements with `identity` when we are one
+    ///
+    /// Announcements from the previous validator set are forgotten.
+    pub async fn set_validator_set(
+        &mut self,
+        epoch: u64,
+        validator_keys: HashMap<u64, crate::crypto::BLSPublicKey>,
+        identity: Option<crate::crypto::BLSSecretKey>,
+    ) {
+        self.address_book = announcement::AddressBook::new(epoch, validator_keys.clone());
+        self.handshake = handshake::ValidatorHandshake::new(self.peer_id, validator_keys, identity);
+        self.sync_validator_peers().await;
+    }
+    
+    /// Validator ID a peer authenticated or was announced as
+    pub fn validator_id(&self, peer_id: &PeerId) -> Option<u64> {
+        self.handshake.validator_id(peer_id).or_else(|| self.address_book.validator_id(peer_id))
+    }
+    
+    /// Latest announced addresses of every validator
+    pub fn validator_addresses(&self) -> HashMap<u64, announcement::ValidatorAddress> {
+        self.address_book.entries().map(|(id, address)| (id, address.clone())).collect()
+    }
+    
+    /// Signed announcement of our PeerId and listen addresses, when we are
+    /// a validator
+    pub fn announcement(&self) -> Option<NetworkMessage> {
+        let secret_key = self.handshake.identity()?;
+        let addrs: Vec<Multiaddr> = self.swarm.external_addresses().chain(self.swarm.listeners()).cloned().collect();
+        let timestamp = unix_millis();
+        let epoch = self.address_book.epoch();
+        let announcement = announcement::ValidatorAnnouncement::new(secret_key, self.peer_id, &addrs, epoch, timestamp);
+        Some(NetworkMessage::Gossip(types::GossipMessage::ValidatorAnnouncement { announcement }))
+    }
+    
+    /// Gossip our validator announcement, if we are a validator
+    pub async fn announce(&mut self) -> NetworkResult<()> {
+        match self.announcement() {
+            Some(message) => self.broadcast(message).await,
+            None => Ok(()),
+        }
+    }
+    
+    /// Verify and record a validator announcement, marking the announced
+    /// peer as a validator and dialing it if we are not connected
+    ///
+    /// Returns whether the announcement was newer than the one known.
+    pub async fn handle_announcement(&mut self, announcement: &announcement::ValidatorAnnouncement) -> NetworkResult<bool> {
+        if !self.address_book.apply(announcement)? {
+            return Ok(false);
+        }
+        self.peer_book.record_validator(announcement.peer()?, announcement.validator_id(), &announcement.addresses());
+        self.sync_validator_peers().await;
+        self.connect_to_validators().await;
+        Ok(true)
+    }
+    
+    /// Dial every announced validator we are not connected to, skipping
+    /// those backing off after failed dials
+    pub async fn connect_to_validators(&mut self) {
+        let now = unix_millis();
+        let unconnected: Vec<_> = self
+            .address_book
+            .entries()
+            .filter(|(_, address)| address.peer_id != self.peer_id && !self.peers.contains_key(&address.peer_id))
+            .filter(|(_, address)| self.peer_book.is_due(&address.peer_id, now))
+            .filter_map(|(_, address)| Some((address.peer_id, address.addrs.first()?.clone())))
+            .collect();
+        for (peer_id, addr) in unconnected {
+            if let Err(e) = self.connect(peer_id, addr).await {
+                debug!("Failed to dial validator {}: {}", peer_id, e);
+            }
+        }
+    }
+    
+    /// Challenge for a newly connected peer, when a validator set is
+    /// configured
+    pub async fn handshake_challenge(&mut self, peer_id: PeerId) -> Option<NetworkMessage> {
+        if !self.handshake.is_enabled() {
+            return None;
+        }
+        Some(NetworkMessage::Control(self.handshake.challenge(peer_id)))
+    }
+    
+    /// Handle a handshake message from a peer, returning the reply to send
+    ///
+    /// A valid proof marks the peer as a validator and opens its direct
+    /// consensus channel.
+    pub async fn handle_handshake(
+        &mut self,
+        peer_id: PeerId,
+        message: types::ControlMessage,
+    ) -> NetworkResult<Option<NetworkMessage>> {
+        match message {
+            types::ControlMessage::HandshakeChallenge { nonce } => {
+                Ok(self.handshake.respond(&nonce).map(NetworkMessage::Control))
+            }
+            types::ControlMessage::HandshakeProof { proof } => {
+                self.handshake.verify(peer_id, &proof)?;
+                self.sync_validator_peers().await;
+                let _ = self.event_tx.send(NetworkEvent::PeerConnected {
+                    peer_id,
+                    is_validator: true,
+                }).await;
+                Ok(None)
+            }
+            _ => Err(NetworkError::InvalidMessage),
+        }
+    }
+    
+    /// Match validator flags and channels to the authenticated and
+    /// announced peers
+    async fn sync_validator_peers(&mut self) {
+        for (peer_id, peer) in self.peers.iter_mut() {
+            let authenticated = self.handshake.is_authenticated(peer_id) || self.address_book.validator_id(peer_id).is_some();
+            if authenticated && !peer.is_validator {
+                self.validator_channel.add_validator(*peer_id);
+            } else if !authenticated && peer.is_validator {
+                self.validator_channel.remove_validator(peer_id);
+            }
+            peer.is_validator = authenticated;
+        }
         
+        self.health.validator_peers = self.validator_channel.stats().active_connections;
+        #[cfg(feature = "metrics")]
+        crate::metrics::global().set_peers(self.health.connected_peers, self.health.validator_peers);
+    }
+    
+    /// Announcement of the codecs this node decodes, for new peers
+    pub fn codec_announcement(&self) -> NetworkMessage {
+        NetworkMessage::Control(types::ControlMessage::Codecs {
+            supported: types::Codec::SUPPORTED.to_vec(),
+        })
+    }
+    
+    /// Record the codecs a peer announced
+    pub async fn set_peer_codecs(&mut self, peer_id: PeerId, supported: Vec<types::Codec>) {
+        self.peer_codecs.insert(peer_id, supported);
+    }
+    
+    /// Codec used for gossip: the most preferred one every peer decodes
+    ///
+    /// Peers that have not announced their codecs are assumed to decode
+    /// all of them.
+    pub fn gossip_codec(&self) -> types::Codec {
+        types::Codec::SUPPORTED
+            .into_iter()
+            .find(|codec| self.peer_codecs.values().all(|supported| supported.contains(codec)))
+            .unwrap_or(types::Codec::Raw)
+    }
+    
+    /// Check for network partition
+    pub fn check_partition(&self) -> bool {
         // Simple heuristic: if we have fewer than n-f validators connected,
         // we might be in a partition
         let min_validators = self.config.min_validators();
-        health.validator_peers < min_validators
+        self.health.validator_peers < min_validators
     }
     
     /// Handle partition recovery
@@ -387,34 +875,131 @@ impl NetworkManager {
             // Attempt to reconnect
             debug!("Reconnecting to validator: {}", validator_addr);
         }
+        self.connect_to_validators().await;
         
         Ok(())
     }
     
     /// Run the network event loop
-    /// This should be spawned as a separate task
+    /// This should be spawned as a separate task (see `spawn`); commands
+    /// from handles are applied between swarm events
     pub async fn run(&mut self) -> NetworkResult<()> {
         info!("Starting network event loop");
         
         let mut partition_check_interval = tokio::time::interval(Duration::from_secs(30));
+        let mut announce_interval = tokio::time::interval(announcement::ANNOUNCE_INTERVAL);
+        let mut redial_interval = tokio::time::interval(REDIAL_INTERVAL);
+        let shutdown = self.shutdown.clone();
+        self.dial_known_peers(false);
+        
+        enum Wakeup<E> {
+            Swarm(E),
+            Command(Box<handle::NetworkCommand>),
+            PartitionCheck,
+            Announce,
+            Redial,
+        }
         
-        loop {
-            // Get the swarm and poll for the next event
-            let event_opt = {
-                let mut swarm = self.swarm.write().await;
-                tokio::select! {
-                    event = swarm.select_next_some() => Some(event),
-                    _ = partition_check_interval.tick() => None,
-                }
+        while !shutdown.is_cancelled() {
+            // Poll the swarm, commands and timers for the next wakeup
+            let wakeup = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                event = self.swarm.select_next_some() => Wakeup::Swarm(event),
+                // The manager holds a sender, so the channel never closes
+                Some(command) = self.command_rx.recv() => Wakeup::Command(Box::new(command)),
+                _ = partition_check_interval.tick() => Wakeup::PartitionCheck,
+                _ = announce_interval.tick() => Wakeup::Announce,
+                _ = redial_interval.tick() => Wakeup::Redial,
             };
             
-            if let Some(event) = event_opt {
-                self.handle_swarm_event(event).await;
-            } else {
-                // Partition check tick
-                if self.check_partition().await {
-                    self.recover_from_partition().await?;
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.busy(match wakeup {
+                    Wakeup::Swarm(_) => "swarm event",
+                    Wakeup::Command(_) => "command",
+                    Wakeup::PartitionCheck => "partition check",
+                    Wakeup::Announce => "announce",
+                    Wakeup::Redial => "redial",
+                });
+            }
+            
+            match wakeup {
+                Wakeup::Swarm(event) => self.handle_swarm_event(event).await,
+                Wakeup::Command(command) => self.handle_command(*command).await,
+                Wakeup::PartitionCheck => {
+                    if self.check_partition() {
+                        self.recover_from_partition().await?;
+                    }
+                }
+                Wakeup::Announce => {
+                    // Without peers subscribed yet the announcement cannot be
+                    // published; the next tick retries
+                    if let Err(e) = self.announce().await {
+                        debug!("Validator announcement not sent: {}", e);
+                    }
+                    self.connect_to_validators().await;
                 }
+                Wakeup::Redial => self.dial_known_peers(true),
+            }
+            
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.set_queue_depth("events", self.event_tx.metrics().len);
+                heartbeat.set_queue_depth("gossip", self.gossip_tx.metrics().len);
+                heartbeat.set_queue_depth("commands", self.command_tx.max_capacity() - self.command_tx.capacity());
+                heartbeat.idle();
+            }
+        }
+        
+        self.drain().await
+    }
+    
+    /// Shut down after the event loop stops: apply commands already
+    /// queued, let the swarm flush until it goes quiet, then save the peer
+    /// store and disconnect. Waiting `close` calls are answered last.
+    async fn drain(&mut self) -> NetworkResult<()> {
+        info!("Draining network before shutdown");
+        while let Ok(command) = self.command_rx.try_recv() {
+            self.handle_command(command).await;
+        }
+        
+        let deadline = tokio::time::Instant::now() + DRAIN_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            match tokio::time::timeout(DRAIN_QUIET, self.swarm.select_next_some()).await {
+                Ok(event) => self.handle_swarm_event(event).await,
+                Err(_) => break,
+            }
+        }
+        
+        let connected: Vec<PeerId> = self.swarm.connected_peers().copied().collect();
+        for peer_id in connected {
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+        
+        for waiter in self.close_waiters.drain(..) {
+            let _ = waiter.send(Ok(()));
+        }
+        info!("Network stopped");
+        Ok(())
+    }
+    
+    /// Forget stale peers, then dial the peer book's disconnected peers
+    /// that are not backing off, only validators when `validators_only` is
+    /// set
+    fn dial_known_peers(&mut self, validators_only: bool) {
+        let now = unix_millis();
+        let pruned = self.peer_book.prune(now);
+        if pruned > 0 {
+            debug!("Forgot {} stale peers", pruned);
+        }
+        for (peer_id, addrs) in self.peer_book.due(now, validators_only) {
+            if peer_id == self.peer_id || self.swarm.is_connected(&peer_id) {
+                continue;
+            }
+            let opts = DialOpts::peer_id(peer_id)
+                .condition(libp2p::swarm::dial_opts::PeerCondition::DisconnectedAndNotDialing)
+                .addresses(addrs)
+                .build();
+            if let Err(e) = self.swarm.dial(opts) {
+                debug!("Known peer {} not dialed: {}", peer_id, e);
             }
         }
     }
@@ -427,9 +1012,15 @@ impl NetworkManager {
             SwarmEvent::Behaviour(behaviour_event) => {
                 self.handle_behaviour_event(behaviour_event).await;
             }
-            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+            SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
                 info!("Connection established with peer: {}", peer_id);
                 self.on_peer_connected(peer_id).await;
+                // Remember where dialed peers can be reached again
+                let dialed = endpoint.is_dialer().then(|| endpoint.get_remote_address().clone());
+                if let (Some(addr), Some(peer)) = (&dialed, self.peers.get_mut(&peer_id)) {
+                    peer.addresses.push(addr.clone());
+                }
+                self.peer_book.record_connected(peer_id, dialed.as_ref(), unix_millis());
             }
             SwarmEvent::ConnectionClosed { peer_id, .. } => {
                 info!("Connection closed with peer: {}", peer_id);
@@ -441,6 +1032,10 @@ impl NetworkManager {
             SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
                 if let Some(peer_id) = peer_id {
                     warn!("Outgoing connection error with {}: {:?}", peer_id, error);
+                    let backoff = self.peer_book.record_dial_failure(peer_id, unix_millis());
+                    if !backoff.is_zero() {
+                        debug!("Backing off dials to {} for {:?}", peer_id, backoff);
+                    }
                 }
             }
             SwarmEvent::IncomingConnectionError { .. } => {
@@ -459,8 +1054,8 @@ impl NetworkManager {
         match event {
             gossip::BehaviourEvent::Gossipsub(gossip_event) => {
                 match gossip_event {
-                    GossipsubEvent::Message { message, .. } => {
-                        self.on_gossip_message(message).await;
+                    GossipsubEvent::Message { propagation_source, message, .. } => {
+                        self.on_gossip_message(propagation_source, message).await;
                     }
                     GossipsubEvent::Subscribed { peer_id, topic } => {
                         debug!("Peer {} subscribed to topic: {}", peer_id, topic);
@@ -480,26 +1075,60 @@ impl NetworkManager {
     }
     
     /// Handle a gossip message
-    async fn on_gossip_message(&mut self, message: libp2p::gossipsub::Message) {
+    async fn on_gossip_message(&mut self, propagation_source: PeerId, message: libp2p::gossipsub::Message) {
         debug!("Received gossip message from peer: {:?}", message.source);
         
+        // Account the bytes against the forwarding peer, dropping the
+        // message if the peer is over its limit. Consensus topics and
+        // validators are never throttled: a flood elsewhere must not
+        // starve blocks and QCs.
+        let topic = message.topic.as_str();
+        let exempt = [gossip::TOPIC_BLOCKS, gossip::TOPIC_QCS].contains(&topic)
+            || self.validator_channel.is_validator(&propagation_source);
+        let admission = if exempt {
+            self.bandwidth.account_in(propagation_source, topic, message.data.len());
+            bandwidth::Admission::Accept
+        } else {
+            self.bandwidth.record_in(propagation_source, topic, message.data.len(), Instant::now())
+        };
+        match admission {
+            bandwidth::Admission::Accept => {}
+            bandwidth::Admission::Throttle => return,
+            bandwidth::Admission::Disconnect => {
+                warn!("Disconnecting {} for exceeding its ingress rate limit", propagation_source);
+                let _ = self.swarm.disconnect_peer_id(propagation_source);
+                return;
+            }
+        }
+        
         // Generate message ID from the message data
         let message_id = libp2p::gossipsub::MessageId::from(
             blake3::hash(&message.data).as_bytes().to_vec()
         );
         
-        // Check for duplicates
-        let mut gossip_manager = self.gossip_manager.write().await;
-        if gossip_manager.is_duplicate(&message_id) {
-            // Can't access private field, but we can just skip
+        // Drop duplicates, marking new messages as seen
+        if !self.gossip_manager.observe(topic, &message.data) {
             return;
         }
         
-        // Mark as seen
-        gossip_manager.mark_seen(message_id.clone());
-        
-        // Deserialize the message
-        match NetworkMessage::from_bytes(&message.data) {
+        // Deserialize the message, dropping it if it exceeds the topic's limit
+        let max_size = self.gossip_manager.config().max_message_size(topic);
+        if message.data.len() > max_size + 1 {
+            self.gossip_manager.record_oversized(topic, message.data.len());
+            return;
+        }
+        match NetworkMessage::from_bytes_limited(&message.data, max_size) {
+            Ok(NetworkMessage::Gossip(types::GossipMessage::ValidatorAnnouncement { announcement })) => {
+                // Consumed by the network layer itself
+                if let Err(e) = self.handle_announcement(&announcement).await {
+                    warn!("Dropping validator announcement from {:?}: {}", message.source, e);
+                }
+            }
+            Ok(NetworkMessage::Gossip(types::GossipMessage::MarketData { asset, .. }))
+                if !self.market_subscriptions.contains(&asset) =>
+            {
+                // Another asset sharing a hashed shard
+            }
             Ok(network_msg) => {
                 // Emit network event
                 let event = NetworkEvent::GossipReceived {
@@ -510,11 +1139,11 @@ impl NetworkManager {
                     message_id: message_id.0.clone(),
                 };
                 
-                let _ = self.event_tx.send(event);
+                // Gossip never blocks: the oldest queued event is dropped when full
+                let _ = self.gossip_tx.send(event).await;
                 
                 // Update health metrics
-                let mut health = self.health.write().await;
-                health.total_messages_received += 1;
+                self.health.total_messages_received += 1;
             }
             Err(e) => {
                 warn!("Failed to deserialize gossip message: {}", e);
@@ -524,46 +1153,71 @@ impl NetworkManager {
     
     /// Handle peer connection
     async fn on_peer_connected(&mut self, peer_id: PeerId) {
-        let mut peers = self.peers.write().await;
-        
         let peer_info = PeerInfo {
             peer_id,
             addresses: vec![],
             connected_at: Instant::now(),
             last_seen: Instant::now(),
-            is_validator: false, // TODO: determine if peer is a validator
+            is_validator: false, // Set once the peer authenticates or is announced
             messages_sent: 0,
             messages_received: 0,
         };
         
-        peers.insert(peer_id, peer_info);
+        self.peers.insert(peer_id, peer_info);
+        
+        // Over the limit, make room by dropping the oldest non-validator;
+        // validators are never evicted
+        let evicted = if self.peers.len() > self.config.max_peers {
+            self.peers
+                .values()
+                .filter(|peer| !peer.is_validator && self.address_book.validator_id(&peer.peer_id).is_none())
+                .filter(|peer| peer.peer_id != peer_id)
+                .min_by_key(|peer| peer.connected_at)
+                .map(|peer| peer.peer_id)
+        } else {
+            None
+        };
         
         // Update health
-        let mut health = self.health.write().await;
-        health.connected_peers = peers.len();
+        self.health.connected_peers = self.peers.len();
+        #[cfg(feature = "metrics")]
+        crate::metrics::global().set_peers(self.health.connected_peers, self.health.validator_peers);
+        
+        if let Some(evicted) = evicted {
+            info!("Peer limit reached, disconnecting {}", evicted);
+            let _ = self.swarm.disconnect_peer_id(evicted);
+        }
+        
+        // Announced validators are recognized before any handshake
+        let announced = self.address_book.validator_id(&peer_id).is_some();
+        if announced {
+            self.sync_validator_peers().await;
+        }
         
-        // Emit event
+        // Emit event (this may wait for the consumer)
         let _ = self.event_tx.send(NetworkEvent::PeerConnected {
             peer_id,
-            is_validator: false,
-        });
+            is_validator: announced,
+        }).await;
     }
     
     /// Handle peer disconnection
     async fn on_peer_disconnected(&mut self, peer_id: PeerId) {
-        let mut peers = self.peers.write().await;
-        peers.remove(&peer_id);
+        self.handshake.remove_peer(&peer_id);
+        self.peers.remove(&peer_id);
+        self.peer_codecs.remove(&peer_id);
+        self.bandwidth.remove_peer(&peer_id, Instant::now());
         
         // Update health
-        let mut health = self.health.write().await;
-        health.connected_peers = peers.len();
+        self.health.connected_peers = self.peers.len();
         
         // Remove from validator channel
-        let mut validator_channel = self.validator_channel.write().await;
-        validator_channel.remove_validator(&peer_id);
+        self.validator_channel.remove_validator(&peer_id);
+        #[cfg(feature = "metrics")]
+        crate::metrics::global().set_peers(self.health.connected_peers, self.validator_channel.stats().active_connections);
         
-        // Emit event
-        let _ = self.event_tx.send(NetworkEvent::PeerDisconnected { peer_id });
+        // Emit event (this may wait for the consumer)
+        let _ = self.event_tx.send(NetworkEvent::PeerDisconnected { peer_id }).await;
     }
 }
 
@@ -582,6 +1236,7 @@ mod tests {
             gossip_interval: Duration::from_millis(100),
             heartbeat_interval: Duration::from_secs(10),
             connection_timeout: Duration::from_secs(30),
+            enable_quic: false,
         }
     }
     
@@ -591,6 +1246,44 @@ mod tests {
         secret_key.public_key()
     }
     
+    #[tokio::test]
+    async fn test_gossip_overflow_drops_oldest_and_yields_to_events() {
+        let channels = ChannelConfig {
+            event_capacity: 4,
+            gossip_capacity: 2,
+            validator_capacity: 4,
+            command_capacity: 4,
+        };
+        let mut network = NetworkManager::new_with_channels(test_config(), channels).unwrap();
+        
+        for i in 0..3u8 {
+            let event = NetworkEvent::GossipReceived {
+                message: types::GossipMessage::Transaction {
+                    tx_hash: crate::crypto::Hash::genesis(),
+                    tx_data: vec![i],
+                    timestamp: 0,
+                },
+                message_id: vec![i],
+            };
+            network.gossip_tx.send(event).await.unwrap();
+        }
+        let peer_id = network.peer_id();
+        network.event_tx.send(NetworkEvent::PeerDisconnected { peer_id }).await.unwrap();
+        
+        let metrics = network.channel_metrics();
+        assert_eq!(metrics.gossip.dropped, 1);
+        assert!(metrics.gossip.is_saturated());
+        assert_eq!(metrics.events.len, 1);
+        assert_eq!(metrics.validator.capacity, 4);
+        
+        // Peer/consensus events are delivered before queued gossip
+        assert!(matches!(network.next_event().await, Some(NetworkEvent::PeerDisconnected { .. })));
+        match network.next_event().await {
+            Some(NetworkEvent::GossipReceived { message_id, .. }) => assert_eq!(message_id, vec![1]),
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+    
     #[tokio::test]
     async fn test_network_creation() {
         let config = test_config();
@@ -598,7 +1291,7 @@ mod tests {
         assert!(network.is_ok());
         
         let network = network.unwrap();
-        assert_eq!(network.peers().await.len(), 0);
+        assert_eq!(network.peers().len(), 0);
     }
     
     #[tokio::test]
@@ -615,7 +1308,7 @@ mod tests {
     async fn test_network_health_initialization() {
         let config = test_config();
         let network = NetworkManager::new(config).unwrap();
-        let health = network.health().await;
+        let health = network.health();
         
         assert_eq!(health.connected_peers, 0);
         assert_eq!(health.validator_peers, 0);
@@ -630,7 +1323,7 @@ mod tests {
         let validator_peer = PeerId::random();
         network.add_validator(validator_peer).await;
         
-        let health = network.health().await;
+        let health = network.health();
         assert_eq!(health.validator_peers, 1);
     }
     
@@ -671,12 +1364,87 @@ mod tests {
         assert!(result.is_ok());
     }
     
+    #[tokio::test]
+    async fn test_egress_rate_limit_and_bandwidth_in_health() {
+        let mut network = NetworkManager::new(test_config()).unwrap();
+        let burst = network.gossip_manager.config().max_transmit_size() as u64;
+        assert!(matches!(
+            network.set_bandwidth_limits(bandwidth::BandwidthConfig {
+                egress: Some(bandwidth::RateLimit { bytes_per_sec: 1, burst: burst - 1 }),
+                ..Default::default()
+            }),
+            Err(NetworkError::BurstTooSmall { .. })
+        ));
+        network.set_bandwidth_limits(bandwidth::BandwidthConfig {
+            egress: Some(bandwidth::RateLimit { bytes_per_sec: 1, burst }),
+            ..Default::default()
+        }).unwrap();
+        let validator_peer = PeerId::random();
+        network.add_validator(validator_peer).await;
+        
+        let message = NetworkMessage::Consensus(types::ConsensusMessage::Proposal {
+            block: Block::genesis(create_test_bls_key()),
+            sender: vec![1, 2, 3, 4],
+        });
+        network.send_to_peer(validator_peer, message.clone()).await.unwrap();
+        let sent = network.health().bandwidth.peers[&validator_peer].traffic.bytes_out;
+        assert!(sent > 0);
+        
+        // The burst is spent well before it refills
+        let mut refused = false;
+        for _ in 0..=burst / sent {
+            if let Err(NetworkError::RateLimited(peer)) = network.send_to_peer(validator_peer, message.clone()).await {
+                assert_eq!(peer, validator_peer);
+                refused = true;
+                break;
+            }
+        }
+        assert!(refused);
+        
+        let health = network.health();
+        assert_eq!(health.bandwidth.peers[&validator_peer].throttled_out, 1);
+        assert_eq!(health.bandwidth.topics[bandwidth::DIRECT_TOPIC].bytes_out, health.bandwidth.total.bytes_out);
+    }
+    
+    #[tokio::test]
+    async fn test_ingress_limit_spares_consensus_topics_and_validators() {
+        let mut network = NetworkManager::new(test_config()).unwrap();
+        let burst = network.gossip_manager.config().max_transmit_size() as u64;
+        network.set_bandwidth_limits(bandwidth::BandwidthConfig {
+            ingress: Some(bandwidth::RateLimit { bytes_per_sec: 1, burst }),
+            ..Default::default()
+        }).unwrap();
+        let (peer, validator_peer) = (PeerId::random(), PeerId::random());
+        network.add_validator(validator_peer).await;
+        
+        let message = |topic: &str, fill: u8| libp2p::gossipsub::Message {
+            source: None,
+            data: vec![fill; 64 * 1024],
+            sequence_number: None,
+            topic: libp2p::gossipsub::TopicHash::from_raw(topic),
+        };
+        // Enough transaction gossip to spend either peer's burst
+        let floods = burst as usize / (64 * 1024) + 2;
+        for i in 0..floods {
+            network.on_gossip_message(peer, message(gossip::TOPIC_TRANSACTIONS, i as u8)).await;
+            network.on_gossip_message(validator_peer, message(gossip::TOPIC_TRANSACTIONS, i as u8)).await;
+        }
+        network.on_gossip_message(peer, message(gossip::TOPIC_BLOCKS, 0)).await;
+        network.on_gossip_message(peer, message(gossip::TOPIC_QCS, 0)).await;
+        
+        let stats = network.health().bandwidth;
+        assert_eq!(stats.peers[&peer].throttled_in, 2);
+        assert_eq!(stats.peers[&peer].traffic.messages_in, floods as u64 + 2);
+        assert_eq!(stats.peers[&validator_peer].throttled_in, 0);
+        assert_eq!(stats.topics[gossip::TOPIC_BLOCKS].messages_in, 1);
+    }
+    
     #[tokio::test]
     async fn test_network_health_metrics() {
         let config = test_config();
         let network = NetworkManager::new(config).unwrap();
         
-        let health = network.health().await;
+        let health = network.health();
         assert_eq!(health.total_messages_sent, 0);
         assert_eq!(health.total_messages_received, 0);
     }
@@ -688,7 +1456,7 @@ mod tests {
         let network = NetworkManager::new(config).unwrap();
         
         // With no validator peers, we should detect a partition
-        assert!(network.check_partition().await);
+        assert!(network.check_partition());
     }
     
     #[tokio::test]
@@ -703,7 +1471,7 @@ mod tests {
         }
         
         // Should not detect partition with quorum
-        assert!(!network.check_partition().await);
+        assert!(!network.check_partition());
     }
     
     #[tokio::test]
@@ -714,11 +1482,38 @@ mod tests {
         let peer_id = PeerId::random();
         network.on_peer_connected(peer_id).await;
         
-        let peers = network.peers().await;
+        let peers = network.peers();
         assert_eq!(peers.len(), 1);
         assert_eq!(peers[0].peer_id, peer_id);
     }
     
+    #[tokio::test]
+    async fn test_close_stops_loop_and_peer_book_survives_restart() {
+        let storage = Arc::new(crate::storage::Storage::new_temp().unwrap());
+        let mut network = NetworkManager::new(test_config()).unwrap();
+        network.set_peer_storage(storage.clone(), peer_store::DialBackoff::default()).unwrap();
+        
+        // A dialed peer is remembered with its address
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/10.0.0.7/tcp/9000".parse().unwrap();
+        network.on_peer_connected(peer_id).await;
+        network.peer_book.record_connected(peer_id, Some(&addr), unix_millis());
+        
+        let (handle, _events) = network.spawn();
+        handle.close().await.unwrap();
+        
+        // The loop is gone; closing again is a no-op
+        assert!(handle.peers().await.is_err());
+        handle.close().await.unwrap();
+        
+        // A restarted node finds the peer in storage
+        let mut restarted = NetworkManager::new(test_config()).unwrap();
+        restarted.set_peer_storage(storage, peer_store::DialBackoff::default()).unwrap();
+        let record = restarted.peer_book().get(&peer_id).unwrap();
+        assert_eq!(record.addresses(), vec![addr]);
+        assert_eq!(record.connections, 1);
+    }
+    
     #[tokio::test]
     async fn test_peer_disconnection() {
         let config = test_config();
@@ -726,10 +1521,10 @@ mod tests {
         
         let peer_id = PeerId::random();
         network.on_peer_connected(peer_id).await;
-        assert_eq!(network.peers().await.len(), 1);
+        assert_eq!(network.peers().len(), 1);
         
         network.on_peer_disconnected(peer_id).await;
-        assert_eq!(network.peers().await.len(), 0);
+        assert_eq!(network.peers().len(), 0);
     }
     
     #[tokio::test]
@@ -743,10 +1538,164 @@ mod tests {
             network.add_validator(*peer_id).await;
         }
         
-        let health = network.health().await;
+        let health = network.health();
         assert_eq!(health.validator_peers, 5);
     }
     
+    #[tokio::test]
+    async fn test_quic_listens_alongside_tcp() {
+        let config = NetworkConfig { enable_quic: true, ..test_config() };
+        let mut network = NetworkManager::new(config.clone()).unwrap();
+        network.listen(config.listen_addr).await.unwrap();
+        
+        // Both listeners report an address
+        let swarm = &mut network.swarm;
+        let (mut tcp, mut quic) = (false, false);
+        while !(tcp && quic) {
+            let event = tokio::time::timeout(Duration::from_secs(5), swarm.select_next_some()).await.unwrap();
+            if let libp2p::swarm::SwarmEvent::NewListenAddr { address, .. } = event {
+                let address = address.to_string();
+                tcp |= address.contains("/tcp/");
+                quic |= address.contains("/quic-v1");
+            }
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_gossip_codec_and_size_limit() {
+        let mut network = NetworkManager::new(test_config()).unwrap();
+        assert_eq!(network.gossip_codec(), types::Codec::Zstd);
+        
+        // A single peer without zstd forces raw gossip until it leaves
+        let peer_id = PeerId::random();
+        network.on_peer_connected(peer_id).await;
+        network.set_peer_codecs(peer_id, vec![types::Codec::Raw]).await;
+        assert_eq!(network.gossip_codec(), types::Codec::Raw);
+        network.on_peer_disconnected(peer_id).await;
+        assert_eq!(network.gossip_codec(), types::Codec::Zstd);
+        
+        // Transactions over the topic limit are refused before publishing
+        let message = NetworkMessage::Gossip(types::GossipMessage::Transaction {
+            tx_hash: crate::crypto::Hash::new([0; 32]),
+            tx_data: vec![0u8; 256 * 1024],
+            timestamp: 0,
+        });
+        let result = network.broadcast(message).await;
+        assert!(matches!(result, Err(NetworkError::MessageTooLarge { .. })));
+        assert_eq!(network.gossip_stats().oversized_dropped, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_market_subscriptions_follow_sharding() {
+        let mut network = NetworkManager::new(test_config()).unwrap();
+        network.subscribe_market(1).unwrap();
+        network.subscribe_market(2).unwrap();
+        let subscribed = |network: &NetworkManager| -> Vec<String> {
+            let mut topics: Vec<String> = network.swarm.behaviour().gossipsub.topics()
+                .map(|t| t.to_string())
+                .filter(|t| t.starts_with(gossip::TOPIC_MARKET_PREFIX))
+                .collect();
+            topics.sort();
+            topics
+        };
+        assert_eq!(subscribed(&network), vec!["openliquid/market/1/1.0.0", "openliquid/market/2/1.0.0"]);
+        
+        // With a single shard both assets share one topic, left with the last of them
+        network.set_market_sharding(gossip::MarketSharding::Hashed { shards: 1 }).unwrap();
+        assert_eq!(subscribed(&network), vec!["openliquid/market/shard-0/1.0.0"]);
+        network.unsubscribe_market(1).unwrap();
+        assert_eq!(subscribed(&network).len(), 1);
+        network.unsubscribe_market(2).unwrap();
+        assert!(subscribed(&network).is_empty());
+        assert!(network.market_subscriptions().is_empty());
+        
+        // Market data is published (and counted) on the asset's topic
+        let message = NetworkMessage::Gossip(types::GossipMessage::MarketData {
+            asset: 5,
+            data: vec![0u8; 1024 * 1024],
+            timestamp: 0,
+        });
+        assert!(network.broadcast(message).await.is_err());
+        assert_eq!(network.gossip_stats().topics["openliquid/market/shard-0/1.0.0"].oversized_dropped, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_validator_handshake_opens_consensus_channel() {
+        use crate::crypto::BLSKeyPair;
+        
+        let keys: Vec<_> = (0..2).map(BLSKeyPair::with_id).collect();
+        let validator_keys: HashMap<_, _> = keys.iter().map(|k| (k.public_key.validator_id(), k.public_key.clone())).collect();
+        let mut ours = NetworkManager::new(test_config()).unwrap();
+        let mut theirs = NetworkManager::new(test_config()).unwrap();
+        ours.set_validator_set(0, validator_keys.clone(), Some(keys[0].secret_key.clone())).await;
+        theirs.set_validator_set(0, validator_keys, Some(keys[1].secret_key.clone())).await;
+        let remote = theirs.peer_id();
+        ours.on_peer_connected(remote).await;
+        
+        let proposal = || NetworkMessage::Consensus(types::ConsensusMessage::Proposal {
+            block: Block::genesis(create_test_bls_key()),
+            sender: vec![1],
+        });
+        assert!(matches!(ours.send_to_peer(remote, proposal()).await, Err(NetworkError::NotAuthenticated(_))));
+        
+        let Some(NetworkMessage::Control(challenge)) = ours.handshake_challenge(remote).await else { unreachable!() };
+        let Some(NetworkMessage::Control(proof)) = theirs.handle_handshake(ours.peer_id(), challenge).await.unwrap() else {
+            unreachable!()
+        };
+        assert!(ours.handle_handshake(remote, proof).await.unwrap().is_none());
+        
+        assert_eq!(ours.validator_id(&remote), Some(1));
+        assert!(ours.peers()[0].is_validator);
+        assert_eq!(ours.health().validator_peers, 1);
+        ours.send_to_peer(remote, proposal()).await.unwrap();
+        
+        ours.on_peer_disconnected(remote).await;
+        assert_eq!(ours.validator_id(&remote), None);
+    }
+    
+    #[tokio::test]
+    async fn test_validator_announcement_marks_peer_as_validator() {
+        use crate::crypto::BLSKeyPair;
+        
+        let keys: Vec<_> = (0..2).map(BLSKeyPair::with_id).collect();
+        let validator_keys: HashMap<_, _> = keys.iter().map(|k| (k.public_key.validator_id(), k.public_key.clone())).collect();
+        let mut ours = NetworkManager::new(test_config()).unwrap();
+        let mut theirs = NetworkManager::new(test_config()).unwrap();
+        ours.set_validator_set(5, validator_keys.clone(), Some(keys[0].secret_key.clone())).await;
+        let remote = theirs.peer_id();
+        ours.on_peer_connected(remote).await;
+        assert!(!ours.peers()[0].is_validator);
+        
+        // Non-validators have nothing to announce
+        assert!(theirs.announcement().is_none());
+        theirs.set_validator_set(5, validator_keys, Some(keys[1].secret_key.clone())).await;
+        let Some(NetworkMessage::Gossip(types::GossipMessage::ValidatorAnnouncement { announcement })) =
+            theirs.announcement()
+        else {
+            unreachable!()
+        };
+        assert_eq!(announcement.epoch, 5);
+        
+        assert!(ours.handle_announcement(&announcement).await.unwrap());
+        assert!(!ours.handle_announcement(&announcement).await.unwrap());
+        assert_eq!(ours.validator_id(&remote), Some(1));
+        assert!(ours.peers()[0].is_validator);
+        assert_eq!(ours.health().validator_peers, 1);
+        assert_eq!(ours.validator_addresses()[&1].peer_id, remote);
+        ours.send_to_peer(remote, NetworkMessage::Consensus(types::ConsensusMessage::Proposal {
+            block: Block::genesis(create_test_bls_key()),
+            sender: vec![1],
+        })).await.unwrap();
+        
+        // A new epoch's validator set forgets the old announcements
+        ours.set_validator_set(6, HashMap::new(), None).await;
+        assert!(!ours.peers()[0].is_validator);
+        assert!(matches!(
+            ours.handle_announcement(&announcement).await,
+            Err(NetworkError::InvalidAnnouncement(_))
+        ));
+    }
+    
     #[tokio::test]
     async fn test_network_config_quorum_calculation() {
         let config = NetworkConfig {
@@ -757,6 +1706,7 @@ mod tests {
             gossip_interval: Duration::from_millis(100),
             heartbeat_interval: Duration::from_secs(10),
             connection_timeout: Duration::from_secs(30),
+            enable_quic: false,
         };
         
         // n=10, f=3, quorum=7
@@ -769,8 +1719,7 @@ mod tests {
         let config = test_config();
         let network = NetworkManager::new(config).unwrap();
         
-        let gossip_manager = network.gossip_manager.read().await;
-        let stats = gossip_manager.stats();
+        let stats = network.gossip_manager.stats();
         
         assert_eq!(stats.messages_broadcast, 0);
         assert_eq!(stats.messages_received, 0);
@@ -781,8 +1730,7 @@ mod tests {
         let config = test_config();
         let network = NetworkManager::new(config).unwrap();
         
-        let validator_channel = network.validator_channel.read().await;
-        let stats = validator_channel.stats();
+        let stats = network.validator_channel.stats();
         
         assert_eq!(stats.total_sent, 0);
         assert_eq!(stats.total_received, 0);
@@ -0,0 +1,352 @@
+// Peer address book persisted across restarts
+//
+// Every peer we dialed successfully or learned from a validator
+// announcement is kept with its addresses and connection history. Records
+// are written through to `Storage`, so after a restart the network dials
+// them straight away instead of waiting for announcements to be gossiped
+// again. Failed dials back off exponentially per peer, and the backoff
+// survives restarts too.
+//
+// The book is bounded: peers that are not validators are forgotten after
+// repeated failed dials or a long time without a connection, and the
+// least recently connected of them make room once the book is full. Each
+// validator keeps only the peer it last announced.
+
+use super::{NetworkError, NetworkResult};
+use crate::storage::Storage;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Most addresses remembered per peer; the oldest are forgotten first
+const MAX_ADDRS: usize = 8;
+
+/// Most peers remembered
+const MAX_PEERS: usize = 1024;
+
+/// Consecutive failed dials after which a non-validator peer is forgotten
+const MAX_DIAL_FAILURES: u32 = 8;
+
+/// Non-validator peers not connected for this long are forgotten
+const PEER_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Delay before redialing a peer after consecutive failed dials
+#[derive(Debug, Clone, Copy)]
+pub struct DialBackoff {
+    /// Delay after the first failure; doubles with every further failure
+    pub initial: Duration,
+    /// Upper bound on the delay
+    pub max: Duration,
+}
+
+impl Default for DialBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(300),
+        }
+    }
+}
+
+impl DialBackoff {
+    /// Delay after `failures` consecutive failed dials
+    pub fn delay(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32.checked_shl(failures - 1).unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// What is known about one peer (times are unix milliseconds)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PeerRecord {
+    /// Encoded multiaddrs, most recently used last
+    pub addrs: Vec<Vec<u8>>,
+    /// Validator the peer was last announced as
+    pub validator_id: Option<u64>,
+    /// Successful connections so far
+    pub connections: u64,
+    pub last_connected: Option<u64>,
+    /// Failed dials since the last successful connection
+    pub failures: u32,
+    /// Not dialed again before this time
+    pub next_dial: u64,
+}
+
+impl PeerRecord {
+    /// Decoded addresses, skipping any that do not parse
+    pub fn addresses(&self) -> Vec<Multiaddr> {
+        self.addrs
+            .iter()
+            .filter_map(|bytes| Multiaddr::try_from(bytes.clone()).ok())
+            .collect()
+    }
+
+    fn add_address(&mut self, addr: &Multiaddr) {
+        let bytes = addr.to_vec();
+        self.addrs.retain(|known| *known != bytes);
+        self.addrs.push(bytes);
+        if self.addrs.len() > MAX_ADDRS {
+            self.addrs.remove(0);
+        }
+    }
+}
+
+/// Known peers with their dial backoff, written through to storage
+pub struct PeerBook {
+    records: HashMap<PeerId, PeerRecord>,
+    backoff: DialBackoff,
+    storage: Option<Arc<Storage>>,
+}
+
+impl PeerBook {
+    /// In-memory book, forgotten on restart
+    pub fn new(backoff: DialBackoff) -> Self {
+        Self {
+            records: HashMap::new(),
+            backoff,
+            storage: None,
+        }
+    }
+
+    /// Book persisted in `storage`, starting from the records kept there
+    pub fn with_storage(backoff: DialBackoff, storage: Arc<Storage>) -> NetworkResult<Self> {
+        let records = storage
+            .load_peers()
+            .map_err(|e| NetworkError::PeerStore(e.to_string()))?
+            .into_iter()
+            .collect();
+        Ok(Self {
+            records,
+            backoff,
+            storage: Some(storage),
+        })
+    }
+
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerRecord> {
+        self.records.get(peer_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Whether the peer may be dialed at `now`
+    pub fn is_due(&self, peer_id: &PeerId, now: u64) -> bool {
+        self.records.get(peer_id).is_none_or(|record| record.next_dial <= now)
+    }
+
+    /// Peers with addresses that may be dialed at `now`, validators only
+    /// when `validators_only` is set
+    pub fn due(&self, now: u64, validators_only: bool) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        self.records
+            .iter()
+            .filter(|(_, record)| record.next_dial <= now && (!validators_only || record.validator_id.is_some()))
+            .map(|(peer_id, record)| (*peer_id, record.addresses()))
+            .filter(|(_, addrs)| !addrs.is_empty())
+            .collect()
+    }
+
+    /// Record a validator announcement's addresses. A peer the validator
+    /// announced before is no longer treated as a validator.
+    pub fn record_validator(&mut self, peer_id: PeerId, validator_id: u64, addrs: &[Multiaddr]) {
+        let replaced: Vec<PeerId> = self
+            .records
+            .iter()
+            .filter(|(id, record)| **id != peer_id && record.validator_id == Some(validator_id))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in replaced {
+            if let Some(record) = self.records.get_mut(&id) {
+                record.validator_id = None;
+            }
+            self.persist(&id);
+        }
+
+        self.make_room(&peer_id);
+        let record = self.records.entry(peer_id).or_default();
+        record.validator_id = Some(validator_id);
+        for addr in addrs {
+            record.add_address(addr);
+        }
+        self.persist(&peer_id);
+    }
+
+    /// Record a successful connection, resetting the backoff. `addr` is
+    /// the address we dialed; inbound peers are only remembered once their
+    /// addresses are known.
+    pub fn record_connected(&mut self, peer_id: PeerId, addr: Option<&Multiaddr>, now: u64) {
+        if addr.is_none() && !self.records.contains_key(&peer_id) {
+            return;
+        }
+        self.make_room(&peer_id);
+        let record = self.records.entry(peer_id).or_default();
+        if let Some(addr) = addr {
+            record.add_address(addr);
+        }
+        record.connections += 1;
+        record.last_connected = Some(now);
+        record.failures = 0;
+        record.next_dial = 0;
+        self.persist(&peer_id);
+    }
+
+    /// Record a failed dial, returning how long the peer is backed off.
+    /// A peer that is not a validator is forgotten after
+    /// `MAX_DIAL_FAILURES` failures in a row.
+    pub fn record_dial_failure(&mut self, peer_id: PeerId, now: u64) -> Duration {
+        let Some(record) = self.records.get_mut(&peer_id) else {
+            return Duration::ZERO;
+        };
+        record.failures = record.failures.saturating_add(1);
+        if record.validator_id.is_none() && record.failures >= MAX_DIAL_FAILURES {
+            self.remove(&peer_id);
+            return Duration::ZERO;
+        }
+        let delay = self.backoff.delay(record.failures);
+        record.next_dial = now.saturating_add(delay.as_millis() as u64);
+        self.persist(&peer_id);
+        delay
+    }
+
+    /// Forget non-validator peers not connected within `PEER_TTL` of `now`,
+    /// returning how many were removed
+    pub fn prune(&mut self, now: u64) -> usize {
+        let cutoff = now.saturating_sub(PEER_TTL.as_millis() as u64);
+        let stale: Vec<PeerId> = self
+            .records
+            .iter()
+            .filter(|(_, record)| record.validator_id.is_none() && record.last_connected.unwrap_or(0) < cutoff)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        for peer_id in &stale {
+            self.remove(peer_id);
+        }
+        stale.len()
+    }
+
+    /// Before `peer_id` is added to a full book, forget the least recently
+    /// connected peer that is not a validator
+    fn make_room(&mut self, peer_id: &PeerId) {
+        if self.records.len() < MAX_PEERS || self.records.contains_key(peer_id) {
+            return;
+        }
+        let oldest = self
+            .records
+            .iter()
+            .filter(|(_, record)| record.validator_id.is_none())
+            .min_by_key(|(_, record)| record.last_connected)
+            .map(|(peer_id, _)| *peer_id);
+        if let Some(oldest) = oldest {
+            self.remove(&oldest);
+        }
+    }
+
+    fn remove(&mut self, peer_id: &PeerId) {
+        self.records.remove(peer_id);
+        if let Some(storage) = &self.storage {
+            if let Err(e) = storage.delete_peer(peer_id) {
+                warn!("Peer {} not removed from storage: {}", peer_id, e);
+            }
+        }
+    }
+
+    fn persist(&self, peer_id: &PeerId) {
+        let (Some(storage), Some(record)) = (&self.storage, self.records.get(peer_id)) else {
+            return;
+        };
+        if let Err(e) = storage.store_peer(peer_id, record) {
+            warn!("Peer {} not persisted: {}", peer_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_resets_and_persists() {
+        let backoff = DialBackoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+        };
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(3), Duration::from_secs(4));
+        assert_eq!(backoff.delay(40), Duration::from_secs(5));
+
+        let storage = Arc::new(Storage::new_temp().unwrap());
+        let mut book = PeerBook::with_storage(backoff, storage.clone()).unwrap();
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/10.0.0.7/tcp/9000".parse().unwrap();
+
+        // Unknown inbound peers are not remembered
+        book.record_connected(peer_id, None, 0);
+        assert!(book.is_empty());
+
+        book.record_validator(peer_id, 3, std::slice::from_ref(&addr));
+        assert_eq!(book.record_dial_failure(peer_id, 1_000), Duration::from_secs(1));
+        assert_eq!(book.record_dial_failure(peer_id, 2_000), Duration::from_secs(2));
+        assert!(!book.is_due(&peer_id, 3_999));
+        assert!(book.due(3_999, true).is_empty());
+
+        // A restarted node keeps the backoff and redials once it expires
+        let reloaded = PeerBook::with_storage(backoff, storage.clone()).unwrap();
+        assert_eq!(reloaded.get(&peer_id).unwrap().failures, 2);
+        assert_eq!(reloaded.due(4_000, true), vec![(peer_id, vec![addr.clone()])]);
+
+        book.record_connected(peer_id, Some(&addr), 5_000);
+        let reloaded = PeerBook::with_storage(backoff, storage).unwrap();
+        let record = reloaded.get(&peer_id).unwrap();
+        assert_eq!((record.failures, record.connections, record.last_connected), (0, 1, Some(5_000)));
+        assert_eq!(record.addresses(), vec![addr]);
+    }
+
+    #[test]
+    fn test_book_forgets_failing_stale_and_excess_peers() {
+        let storage = Arc::new(Storage::new_temp().unwrap());
+        let mut book = PeerBook::with_storage(DialBackoff::default(), storage.clone()).unwrap();
+        let addr: Multiaddr = "/ip4/10.0.0.7/tcp/9000".parse().unwrap();
+
+        // Peers that keep failing are forgotten unless they are validators
+        let (peer, validator) = (PeerId::random(), PeerId::random());
+        book.record_connected(peer, Some(&addr), 0);
+        book.record_validator(validator, 1, std::slice::from_ref(&addr));
+        for _ in 0..MAX_DIAL_FAILURES {
+            book.record_dial_failure(peer, 0);
+            book.record_dial_failure(validator, 0);
+        }
+        assert!(book.get(&peer).is_none());
+        assert_eq!(book.get(&validator).unwrap().failures, MAX_DIAL_FAILURES);
+
+        // A validator announcing a new peer leaves the old one to go stale
+        let moved = PeerId::random();
+        book.record_validator(moved, 1, std::slice::from_ref(&addr));
+        assert_eq!(book.get(&validator).unwrap().validator_id, None);
+        let ttl = PEER_TTL.as_millis() as u64;
+        assert_eq!(book.prune(ttl + 1), 1);
+        assert!(book.get(&validator).is_none());
+
+        // A full book drops the least recently connected peer
+        let peers: Vec<PeerId> = (0..MAX_PEERS).map(|_| PeerId::random()).collect();
+        for (i, peer_id) in peers.iter().enumerate() {
+            book.record_connected(*peer_id, Some(&addr), ttl + i as u64);
+        }
+        assert_eq!(book.len(), MAX_PEERS);
+        assert!(book.get(&peers[0]).is_none());
+        assert!(book.get(&moved).is_some());
+
+        let reloaded = PeerBook::with_storage(DialBackoff::default(), storage).unwrap();
+        assert_eq!(reloaded.len(), MAX_PEERS);
+        assert!(reloaded.get(&peer).is_none() && reloaded.get(&peers[0]).is_none());
+    }
+}
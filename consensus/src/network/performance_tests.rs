@@ -26,6 +26,7 @@ fn test_network_config(port: u16) -> NetworkConfig {
         gossip_interval: Duration::from_millis(100),
         heartbeat_interval: Duration::from_secs(10),
         connection_timeout: Duration::from_secs(30),
+        enable_quic: false,
     }
 }
 
@@ -131,7 +132,7 @@ async fn test_network_health_metrics_performance() {
     
     // Read health metrics 1000 times
     for _ in 0..1000 {
-        let _ = network.health().await;
+        let _ = network.health();
     }
     
     let elapsed = start.elapsed();
@@ -163,7 +164,7 @@ async fn test_peer_tracking_performance() {
     // Should handle 150 operations quickly (< 100ms)
     assert!(elapsed < Duration::from_millis(100), "Took {:?}", elapsed);
     
-    let peers = network.peers().await;
+    let peers = network.peers();
     assert_eq!(peers.len(), 50); // 50 peers still connected
 }
 
@@ -204,7 +205,7 @@ async fn test_partition_check_performance() {
     
     // Check partition status 1000 times
     for _ in 0..1000 {
-        let _ = network.check_partition().await;
+        let _ = network.check_partition();
     }
     
     let elapsed = start.elapsed();
@@ -296,6 +297,43 @@ async fn test_concurrent_message_handling() {
     assert!(elapsed < Duration::from_millis(50), "Took {:?}", elapsed);
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_handle_command_throughput() {
+    let network = NetworkManager::new(test_network_config(45000)).unwrap();
+    let (handle, _events) = network.spawn();
+    let validator = PeerId::random();
+    handle.add_validator(validator).await.unwrap();
+    
+    let message = NetworkMessage::Consensus(types::ConsensusMessage::Proposal {
+        block: Block::genesis(create_test_bls_key(0)),
+        sender: vec![1],
+    });
+    
+    let start = Instant::now();
+    
+    // 8 concurrent senders, 20k messages through the single-owner event loop
+    let senders: Vec<_> = (0..8)
+        .map(|_| {
+            let handle = handle.clone();
+            let message = message.clone();
+            tokio::spawn(async move {
+                for _ in 0..2_500 {
+                    handle.send_to_peer(validator, message.clone()).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    for sender in senders {
+        sender.await.unwrap();
+    }
+    
+    let elapsed = start.elapsed();
+    
+    // Well over 10k messages per second (< 2s)
+    assert!(elapsed < Duration::from_secs(2), "Took {:?}", elapsed);
+    assert_eq!(handle.health().await.unwrap().validator_peers, 1);
+}
+
 #[tokio::test]
 async fn test_network_scaling() {
     // Test network performance with increasing numbers of validators
@@ -318,7 +356,7 @@ async fn test_network_scaling() {
         let expected_max = Duration::from_millis(((count / 10) * 10) as u64);
         assert!(elapsed < expected_max, "Took {:?} for {} validators", elapsed, count);
         
-        let health = network.health().await;
+        let health = network.health();
         assert_eq!(health.validator_peers, count);
     }
 }
@@ -329,6 +367,7 @@ async fn test_memory_efficiency() {
         max_tracked_messages: 1000,
         dedup_window: Duration::from_secs(60),
         target_propagation_ms: 500,
+        ..Default::default()
     };
     let mut manager = gossip::GossipManager::new(config);
     
@@ -0,0 +1,172 @@
+// Bounded duplicate-suppression cache for gossip
+//
+// Entries expire once they have not been seen for `ttl`, and the least
+// recently seen entry is evicted when the cache is full, so memory stays
+// bounded however fast messages arrive. Keys are SipHash digests of the
+// message contents under a per-node random key: peers cannot predict them,
+// so they cannot grind contents to collide with or target specific entries.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+use std::time::{Duration, Instant};
+
+type Key = u64;
+
+/// Hasher for keys that are already keyed digests
+#[derive(Default)]
+struct PassThrough(u64);
+
+impl Hasher for PassThrough {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(*byte);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+}
+
+/// Eviction counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeenCacheStats {
+    /// Entries evicted to stay within capacity
+    pub evicted: u64,
+    /// Entries dropped after their TTL
+    pub expired: u64,
+}
+
+/// TTL-bounded LRU set of seen messages
+#[derive(Debug)]
+pub struct SeenCache {
+    /// Randomly keyed hasher deriving cache keys
+    salt: RandomState,
+    capacity: usize,
+    ttl: Duration,
+    /// Recency sequence number and last sighting of each key
+    entries: HashMap<Key, (u64, Instant), BuildHasherDefault<PassThrough>>,
+    /// Keys by recency sequence number, least recent first
+    order: BTreeMap<u64, Key>,
+    next_seq: u64,
+    stats: SeenCacheStats,
+}
+
+impl SeenCache {
+    /// Create a cache holding up to `capacity` entries for `ttl` each
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            salt: RandomState::new(),
+            capacity: capacity.max(1),
+            ttl,
+            entries: HashMap::default(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+            stats: SeenCacheStats::default(),
+        }
+    }
+
+    fn key(&self, data: &[u8]) -> Key {
+        self.salt.hash_one(data)
+    }
+
+    /// Whether `data` is cached; entries past the TTL are collected on the
+    /// next insert
+    pub fn contains(&self, data: &[u8]) -> bool {
+        self.entries.contains_key(&self.key(data))
+    }
+
+    /// Record a sighting of `data`; returns true if it was not already
+    /// cached. Repeat sightings refresh the entry's TTL and recency.
+    pub fn insert(&mut self, data: &[u8]) -> bool {
+        let now = Instant::now();
+        self.expire(now);
+
+        let key = self.key(data);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        if let Some((old_seq, _)) = self.entries.insert(key, (seq, now)) {
+            self.order.remove(&old_seq);
+            self.order.insert(seq, key);
+            return false;
+        }
+        self.order.insert(seq, key);
+
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.stats.evicted += 1;
+        }
+        true
+    }
+
+    /// Drop entries not seen for the TTL
+    fn expire(&mut self, now: Instant) {
+        while let Some((&seq, key)) = self.order.first_key_value() {
+            let expired = self
+                .entries
+                .get(key)
+                .is_none_or(|(_, seen)| now.duration_since(*seen) >= self.ttl);
+            if !expired {
+                break;
+            }
+            let key = *key;
+            self.order.remove(&seq);
+            self.entries.remove(&key);
+            self.stats.expired += 1;
+        }
+    }
+
+    /// Number of cached entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Eviction counters
+    pub fn stats(&self) -> SeenCacheStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction_and_ttl() {
+        let mut cache = SeenCache::new(3, Duration::from_secs(60));
+        for id in [1u8, 2, 3] {
+            assert!(cache.insert(&[id]));
+        }
+        // A repeat sighting is not new and makes 1 the most recent entry
+        assert!(!cache.insert(&[1]));
+        assert!(cache.insert(&[4]));
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.contains(&[2]));
+        assert!(cache.contains(&[1]));
+        assert_eq!(cache.stats(), SeenCacheStats { evicted: 1, expired: 0 });
+
+        let mut cache = SeenCache::new(10, Duration::from_millis(20));
+        cache.insert(b"block");
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(cache.insert(b"block"));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats().expired, 1);
+
+        // Keys depend on the node's salt, not just the contents
+        let other = SeenCache::new(10, Duration::from_secs(60));
+        assert_ne!(cache.key(b"block"), other.key(b"block"));
+    }
+}
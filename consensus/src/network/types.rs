@@ -28,6 +28,9 @@ pub struct NetworkConfig {
     
     /// Connection timeout
     pub connection_timeout: Duration,
+    
+    /// Also listen and dial over QUIC, falling back to TCP per peer
+    pub enable_quic: bool,
 }
 
 impl NetworkConfig {
@@ -43,6 +46,29 @@ impl NetworkConfig {
     }
 }
 
+/// QUIC address on the same host and port as a TCP address
+/// 
+/// `/ip4/1.2.3.4/tcp/9000[/p2p/..]` maps to
+/// `/ip4/1.2.3.4/udp/9000/quic-v1[/p2p/..]`; returns `None` for addresses
+/// without a TCP component.
+pub fn quic_address(addr: &Multiaddr) -> Option<Multiaddr> {
+    use libp2p::multiaddr::Protocol;
+    
+    let mut quic = Multiaddr::empty();
+    let mut converted = false;
+    for protocol in addr.iter() {
+        match protocol {
+            Protocol::Tcp(port) if !converted => {
+                quic.push(Protocol::Udp(port));
+                quic.push(Protocol::QuicV1);
+                converted = true;
+            }
+            other => quic.push(other),
+        }
+    }
+    converted.then_some(quic)
+}
+
 /// Network messages exchanged between nodes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NetworkMessage {
@@ -113,6 +139,18 @@ pub enum GossipMessage {
         qc: QuorumCertificate,
         timestamp: u64,
     },
+    
+    /// Validator's signed PeerId and addresses
+    ValidatorAnnouncement {
+        announcement: super::announcement::ValidatorAnnouncement,
+    },
+    
+    /// DEX order flow or fills of one asset, sent on the asset's market topic
+    MarketData {
+        asset: u32,
+        data: Vec<u8>,
+        timestamp: u64,
+    },
 }
 
 /// Control messages for peer management
@@ -145,6 +183,21 @@ pub enum ControlMessage {
     SyncResponse {
         blocks: Vec<Block>,
     },
+    
+    /// Codecs the sender can decode, most preferred first
+    Codecs {
+        supported: Vec<Codec>,
+    },
+    
+    /// Fresh nonce a validator must sign to authenticate
+    HandshakeChallenge {
+        nonce: [u8; 32],
+    },
+    
+    /// Validator's signature over its PeerId and the challenge nonce
+    HandshakeProof {
+        proof: crate::crypto::BLSPartialSignature,
+    },
 }
 
 /// Network events emitted by the network layer
@@ -189,15 +242,101 @@ pub enum NetworkEvent {
     },
 }
 
+/// Encodings of the serialized message on the wire
+/// 
+/// Every encoded message starts with the codec's tag byte, so a receiver
+/// can decode any supported codec without knowing the sender's choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Codec {
+    /// Uncompressed bincode
+    Raw = 0,
+    /// Zstandard-compressed bincode
+    Zstd = 1,
+}
+
+impl Codec {
+    /// Codecs this node can decode, most preferred first
+    pub const SUPPORTED: [Codec; 2] = [Codec::Zstd, Codec::Raw];
+    
+    /// Most preferred codec that a peer supporting `theirs` can decode
+    pub fn negotiate(theirs: &[Codec]) -> Codec {
+        Self::SUPPORTED
+            .into_iter()
+            .find(|codec| theirs.contains(codec))
+            .unwrap_or(Codec::Raw)
+    }
+    
+    fn from_tag(tag: u8) -> Option<Codec> {
+        match tag {
+            0 => Some(Codec::Raw),
+            1 => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Messages smaller than this are sent raw; compression would not pay off
+pub const COMPRESSION_THRESHOLD: usize = 512;
+
+/// Largest message accepted after decompression
+pub const MAX_DECODED_SIZE: usize = 16 * 1024 * 1024;
+
+/// Zstandard level used for compressed messages
+const ZSTD_LEVEL: i32 = 3;
+
 impl NetworkMessage {
-    /// Serialize message to bytes
+    /// Serialize message to bytes, compressed when large enough
     pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
-        bincode::serialize(self)
+        self.encode(Codec::Zstd)
     }
     
-    /// Deserialize message from bytes
+    /// Serialize message to bytes with `codec`
+    /// 
+    /// Messages under `COMPRESSION_THRESHOLD` are always sent raw.
+    pub fn encode(&self, codec: Codec) -> Result<Vec<u8>, bincode::Error> {
+        let raw = bincode::serialize(self)?;
+        if codec == Codec::Zstd && raw.len() >= COMPRESSION_THRESHOLD {
+            let compressed = zstd::bulk::compress(&raw, ZSTD_LEVEL).map_err(bincode::ErrorKind::Io)?;
+            // Incompressible payloads are cheaper to send as they are
+            if compressed.len() < raw.len() {
+                let mut bytes = Vec::with_capacity(compressed.len() + 1);
+                bytes.push(Codec::Zstd as u8);
+                bytes.extend_from_slice(&compressed);
+                return Ok(bytes);
+            }
+        }
+        
+        let mut bytes = Vec::with_capacity(raw.len() + 1);
+        bytes.push(Codec::Raw as u8);
+        bytes.extend_from_slice(&raw);
+        Ok(bytes)
+    }
+    
+    /// Deserialize message from bytes of any supported codec
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
-        bincode::deserialize(bytes)
+        Self::from_bytes_limited(bytes, MAX_DECODED_SIZE)
+    }
+    
+    /// Deserialize message from bytes, refusing payloads that exceed
+    /// `max_size` once decompressed
+    pub fn from_bytes_limited(bytes: &[u8], max_size: usize) -> Result<Self, bincode::Error> {
+        let invalid = |reason: String| Box::new(bincode::ErrorKind::Custom(reason));
+        let (&tag, payload) = bytes.split_first().ok_or_else(|| invalid("Empty message".to_string()))?;
+        match Codec::from_tag(tag).ok_or_else(|| invalid(format!("Unknown codec {}", tag)))? {
+            Codec::Raw if payload.len() > max_size => {
+                Err(invalid(format!("Message of {} bytes exceeds limit {}", payload.len(), max_size)))
+            }
+            Codec::Raw => bincode::deserialize(payload),
+            Codec::Zstd => {
+                let raw = zstd::bulk::decompress(payload, max_size).map_err(bincode::ErrorKind::Io)?;
+                bincode::deserialize(&raw)
+            }
+        }
+    }
+    
+    /// Size of the message serialized without compression
+    pub fn raw_size(&self) -> Result<u64, bincode::Error> {
+        bincode::serialized_size(self)
     }
     
     /// Get message type as string (for logging)
@@ -214,6 +353,8 @@ impl NetworkMessage {
                 GossipMessage::Block { .. } => "GossipBlock",
                 GossipMessage::Transaction { .. } => "GossipTransaction",
                 GossipMessage::QuorumCert { .. } => "GossipQC",
+                GossipMessage::ValidatorAnnouncement { .. } => "GossipValidatorAnnouncement",
+                GossipMessage::MarketData { .. } => "GossipMarketData",
             },
             NetworkMessage::Control(msg) => match msg {
                 ControlMessage::Ping { .. } => "Ping",
@@ -221,6 +362,9 @@ impl NetworkMessage {
                 ControlMessage::PeerInfo { .. } => "PeerInfo",
                 ControlMessage::SyncRequest { .. } => "SyncRequest",
                 ControlMessage::SyncResponse { .. } => "SyncResponse",
+                ControlMessage::Codecs { .. } => "Codecs",
+                ControlMessage::HandshakeChallenge { .. } => "HandshakeChallenge",
+                ControlMessage::HandshakeProof { .. } => "HandshakeProof",
             },
         }
     }
@@ -237,6 +381,17 @@ mod tests {
         secret_key.public_key()
     }
     
+    #[test]
+    fn test_quic_address_from_tcp() {
+        let tcp: Multiaddr = "/ip4/10.0.0.1/tcp/9000/p2p/12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA".parse().unwrap();
+        let quic = quic_address(&tcp).unwrap();
+        assert_eq!(
+            quic.to_string(),
+            "/ip4/10.0.0.1/udp/9000/quic-v1/p2p/12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA"
+        );
+        assert!(quic_address(&quic).is_none());
+    }
+    
     #[test]
     fn test_network_config_quorum() {
         let config = NetworkConfig {
@@ -247,6 +402,7 @@ mod tests {
             gossip_interval: Duration::from_millis(100),
             heartbeat_interval: Duration::from_secs(10),
             connection_timeout: Duration::from_secs(30),
+            enable_quic: false,
         };
         
         // n=7, f=2, quorum=5
@@ -267,6 +423,41 @@ mod tests {
         assert_eq!(msg.message_type(), deserialized.message_type());
     }
     
+    #[test]
+    fn test_compressed_round_trip_and_limits() {
+        let block = Block::new(
+            Block::genesis(create_test_bls_key()).hash(),
+            1,
+            1,
+            None,
+            vec![vec![7u8; 4096]; 4],
+            create_test_bls_key(),
+        );
+        let msg = NetworkMessage::Gossip(GossipMessage::Block { block, timestamp: 123 });
+        let raw_size = msg.raw_size().unwrap() as usize;
+        
+        let compressed = msg.encode(Codec::Zstd).unwrap();
+        let raw = msg.encode(Codec::Raw).unwrap();
+        assert_eq!(compressed[0], Codec::Zstd as u8);
+        assert_eq!(raw[0], Codec::Raw as u8);
+        assert!(compressed.len() * 10 < raw.len());
+        assert_eq!(NetworkMessage::from_bytes(&compressed).unwrap().encode(Codec::Raw).unwrap(), raw);
+        assert_eq!(NetworkMessage::from_bytes(&raw).unwrap().encode(Codec::Raw).unwrap(), raw);
+        
+        // The limit applies to the decompressed size under either codec
+        assert!(NetworkMessage::from_bytes_limited(&compressed, raw_size - 1).is_err());
+        assert!(NetworkMessage::from_bytes_limited(&raw, raw_size - 1).is_err());
+        assert!(NetworkMessage::from_bytes_limited(&compressed, raw_size).is_ok());
+        
+        // Small messages are not worth compressing
+        let ping = NetworkMessage::Control(ControlMessage::Ping { nonce: 1, timestamp: 2 });
+        assert_eq!(ping.to_bytes().unwrap()[0], Codec::Raw as u8);
+        
+        assert_eq!(Codec::negotiate(&[Codec::Raw, Codec::Zstd]), Codec::Zstd);
+        assert_eq!(Codec::negotiate(&[Codec::Raw]), Codec::Raw);
+        assert!(NetworkMessage::from_bytes(&[9, 0]).is_err());
+    }
+    
     #[test]
     fn test_message_types() {
         let block = Block::genesis(create_test_bls_key());
@@ -4,6 +4,7 @@
 // for consensus messages (proposals, votes, QCs). Unlike gossip, these are
 // direct peer-to-peer connections optimized for validator-to-validator traffic.
 
+use super::channel::{self, BoundedReceiver, BoundedSender, ChannelMetrics, OverflowPolicy};
 use super::{NetworkError, NetworkResult};
 use crate::hotstuff::types::{Block, QuorumCertificate, Vote};
 use libp2p::PeerId;
@@ -12,7 +13,6 @@ use std::{
     collections::HashMap,
     time::{Duration, Instant},
 };
-use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 
 /// Validator channel for direct communication
@@ -24,10 +24,9 @@ pub struct ValidatorChannel {
     /// Map of validator peer IDs to their channels
     channels: HashMap<PeerId, ValidatorConnection>,
     
-    /// Incoming message queue
-    #[allow(dead_code)]
-    incoming_tx: mpsc::UnboundedSender<ValidatorMessage>,
-    incoming_rx: mpsc::UnboundedReceiver<ValidatorMessage>,
+    /// Incoming message queue (bounded, blocks when full)
+    incoming_tx: BoundedSender<ValidatorMessage>,
+    incoming_rx: BoundedReceiver<ValidatorMessage>,
     
     /// Channel statistics
     stats: ChannelStats,
@@ -136,7 +135,13 @@ pub struct ChannelStats {
 impl ValidatorChannel {
     /// Create a new validator channel
     pub fn new(local_peer: PeerId) -> Self {
-        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        Self::with_capacity(local_peer, channel::DEFAULT_VALIDATOR_CAPACITY)
+    }
+    
+    /// Create a validator channel with a custom incoming queue capacity
+    pub fn with_capacity(local_peer: PeerId, capacity: usize) -> Self {
+        // Consensus messages must not be dropped: senders wait for room
+        let (incoming_tx, incoming_rx) = channel::bounded(capacity, OverflowPolicy::Block);
         
         Self {
             local_peer,
@@ -173,6 +178,11 @@ impl ValidatorChannel {
         }
     }
     
+    /// Whether `peer_id` has a validator connection
+    pub fn is_validator(&self, peer_id: &PeerId) -> bool {
+        self.channels.contains_key(peer_id)
+    }
+    
     /// Send a message to a specific validator
     pub async fn send_to_validator(
         &mut self,
@@ -208,6 +218,24 @@ impl ValidatorChannel {
         Ok(())
     }
     
+    /// Queue a message received from a validator, waiting while the queue is full
+    pub async fn deliver(&self, message: ValidatorMessage) -> NetworkResult<()> {
+        self.incoming_tx
+            .send(message)
+            .await
+            .map_err(|_| NetworkError::SendError("Validator channel closed".to_string()))
+    }
+    
+    /// Get a sender for delivering incoming validator messages
+    pub fn incoming_sender(&self) -> BoundedSender<ValidatorMessage> {
+        self.incoming_tx.clone()
+    }
+    
+    /// Get saturation metrics of the incoming queue
+    pub fn incoming_metrics(&self) -> ChannelMetrics {
+        self.incoming_rx.metrics()
+    }
+    
     /// Receive the next message from validators
     pub async fn recv(&mut self) -> Option<ValidatorMessage> {
         if let Some(message) = self.incoming_rx.recv().await {
@@ -363,6 +391,34 @@ mod tests {
         assert_eq!(channel.stats().total_sent, 1);
     }
     
+    #[tokio::test]
+    async fn test_incoming_queue_is_bounded() {
+        let local_peer = create_test_peer();
+        let mut channel = ValidatorChannel::with_capacity(local_peer, 1);
+        
+        let message = ValidatorMessage::Proposal {
+            block: Block::genesis(create_test_bls_key()),
+            from_validator: peer_id_to_bytes(&local_peer),
+            timestamp: 123,
+        };
+        channel.deliver(message.clone()).await.unwrap();
+        
+        // A second message waits for room rather than being dropped
+        let sender = channel.incoming_sender();
+        let pending = tokio::spawn(async move { sender.send(message).await });
+        tokio::task::yield_now().await;
+        assert!(channel.incoming_metrics().is_saturated());
+        
+        assert!(channel.recv().await.is_some());
+        pending.await.unwrap().unwrap();
+        assert!(channel.recv().await.is_some());
+        
+        let metrics = channel.incoming_metrics();
+        assert_eq!(metrics.sent, 2);
+        assert_eq!(metrics.dropped, 0);
+        assert_eq!(channel.stats().total_received, 2);
+    }
+    
     #[tokio::test]
     async fn test_send_to_unknown_validator() {
         let local_peer = create_test_peer();
@@ -3,9 +3,13 @@
 // Implements leader election, timeout mechanism, view changes, and new-view handling
 // Based on HotStuff paper Algorithm 2 and hyperbft_implementation_plan.md
 
+use std::collections::HashMap;
 use std::time::Duration;
 use crate::hotstuff::types::QuorumCertificate;
-use crate::crypto::{BLSPublicKey, BLSPartialSignature};
+use crate::crypto::{
+    aggregate_verify, partial_verify, threshold_combine, threshold_sign, BLSKeyPair, BLSPartialSignature,
+    BLSPublicKey, BLSSignature,
+};
 
 /// Pacemaker ensures liveness by managing view progression and leader election
 pub struct Pacemaker {
@@ -23,6 +27,9 @@ pub struct Pacemaker {
     
     /// Total number of validators in the network
     validator_count: usize,
+    
+    /// Highest timeout certificate seen (justifies entering `view + 1`)
+    high_tc: Option<TimeoutCertificate>,
 }
 
 impl Pacemaker {
@@ -40,6 +47,7 @@ impl Pacemaker {
             max_timeout: Duration::from_secs(60),
             timeout_count: 0,
             validator_count,
+            high_tc: None,
         }
     }
 
@@ -103,6 +111,38 @@ impl Pacemaker {
         self.timeout_count += 1;
     }
 
+    /// Record a local timeout without leaving the view
+    /// 
+    /// The view only advances once a timeout certificate forms, but the
+    /// next timeout still backs off.
+    pub fn record_timeout(&mut self) {
+        self.timeout_count += 1;
+    }
+
+    /// Enter the view after a timeout certificate's view
+    /// 
+    /// Certificates for views already left are kept only if newer than the
+    /// current highest one.
+    /// 
+    /// # Returns
+    /// true if the current view advanced
+    pub fn advance_with_tc(&mut self, tc: TimeoutCertificate) -> bool {
+        let next_view = tc.view + 1;
+        if self.high_tc.as_ref().is_none_or(|high| tc.view > high.view) {
+            self.high_tc = Some(tc);
+        }
+        if next_view <= self.current_view {
+            return false;
+        }
+        self.current_view = next_view;
+        true
+    }
+
+    /// Highest timeout certificate seen
+    pub fn high_tc(&self) -> Option<&TimeoutCertificate> {
+        self.high_tc.as_ref()
+    }
+
     /// Reset timeout counter (called on successful commit)
     /// 
     /// When progress is made, we reset the exponential backoff
@@ -163,6 +203,211 @@ impl NewViewMessage {
     }
 }
 
+/// Message signed by a validator timing out of `view`
+/// 
+/// Binds the view to the view of the sender's highest QC (0 if none), so a
+/// timeout certificate proves which QC the new leader must extend.
+pub fn timeout_message(view: u64, high_qc_view: u64) -> Vec<u8> {
+    let mut data = b"timeout".to_vec();
+    data.extend_from_slice(&view.to_le_bytes());
+    data.extend_from_slice(&high_qc_view.to_le_bytes());
+    data
+}
+
+/// Timeout message broadcast when a validator's view timer expires
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TimeoutMessage {
+    /// View that timed out
+    pub view: u64,
+    
+    /// Highest QC known by the sender
+    pub high_qc: Option<QuorumCertificate>,
+    
+    /// Sender's public key
+    pub sender: BLSPublicKey,
+    
+    /// Signature over (view, high_qc view)
+    pub signature: BLSPartialSignature,
+}
+
+impl TimeoutMessage {
+    /// Sign a timeout of `view`
+    pub fn new(view: u64, high_qc: Option<QuorumCertificate>, keypair: &BLSKeyPair) -> Self {
+        let high_qc_view = high_qc.as_ref().map_or(0, |qc| qc.view);
+        Self {
+            view,
+            high_qc,
+            sender: keypair.public_key.clone(),
+            signature: threshold_sign(&keypair.secret_key, &timeout_message(view, high_qc_view)),
+        }
+    }
+
+    /// View of the sender's highest QC (0 if none)
+    pub fn high_qc_view(&self) -> u64 {
+        self.high_qc.as_ref().map_or(0, |qc| qc.view)
+    }
+
+    /// Check the signature against the sender's key
+    pub fn verify(&self) -> bool {
+        partial_verify(&timeout_message(self.view, self.high_qc_view()), &self.signature, &self.sender)
+    }
+}
+
+/// Timeout Certificate (TC)
+/// 
+/// Aggregates n-f timeout signatures for one view. A TC for view v lets
+/// every honest node enter view v + 1, and carries the highest QC among
+/// the signers for the next leader to extend.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TimeoutCertificate {
+    /// View that timed out
+    pub view: u64,
+    
+    /// Highest QC among the signers' timeout messages
+    pub high_qc: Option<QuorumCertificate>,
+    
+    /// (validator ID, high QC view) signed by each signer, by ID
+    pub signers: Vec<(u64, u64)>,
+    
+    /// Aggregate of the signers' timeout signatures
+    pub signature: BLSSignature,
+}
+
+impl TimeoutCertificate {
+    /// Verify the certificate against the validator set's keys
+    /// 
+    /// Checks for a quorum of distinct registered signers, that the
+    /// carried QC is the highest one signed for and is itself valid, and
+    /// the aggregate signature.
+    pub fn verify(&self, validator_keys: &HashMap<u64, BLSPublicKey>, quorum_size: usize) -> Result<(), String> {
+        if self.signers.len() < quorum_size {
+            return Err(format!("TC has {} signers, need {}", self.signers.len(), quorum_size));
+        }
+        if self.signers.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err("TC signers not sorted or duplicated".to_string());
+        }
+        
+        let highest = self.signers.iter().map(|(_, v)| *v).max().unwrap_or(0);
+        let carried = self.high_qc.as_ref().map_or(0, |qc| qc.view);
+        if carried != highest {
+            return Err(format!("TC carries QC of view {}, signers reported {}", carried, highest));
+        }
+        // Timeout signatures only cover the QC's view, not the QC itself
+        if let Some(ref qc) = self.high_qc {
+            qc.verify_quorum(validator_keys, quorum_size)
+                .map_err(|e| format!("TC carries an invalid QC: {}", e))?;
+        }
+        
+        let mut messages = Vec::with_capacity(self.signers.len());
+        let mut keys = Vec::with_capacity(self.signers.len());
+        for (id, high_qc_view) in &self.signers {
+            let key = validator_keys.get(id).ok_or_else(|| format!("Unknown TC signer {}", id))?;
+            messages.push(timeout_message(self.view, *high_qc_view));
+            keys.push(key.clone());
+        }
+        let messages: Vec<&[u8]> = messages.iter().map(|m| m.as_slice()).collect();
+        if !aggregate_verify(&messages, &self.signature, &keys) {
+            return Err("TC signature verification failed".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How many views past the current one timeouts are collected for; later
+/// ones are refused so a peer cannot open collectors without bound
+pub const TIMEOUT_VIEW_WINDOW: u64 = 64;
+
+/// Timeout collector for one view
+/// 
+/// Verifies timeout messages as they arrive and aggregates the first n-f
+/// into a timeout certificate.
+pub struct TimeoutCollector {
+    /// View being collected
+    view: u64,
+    
+    /// Verified timeout messages by sender ID
+    messages: HashMap<u64, TimeoutMessage>,
+    
+    /// Required number of messages (n - f)
+    quorum_size: usize,
+}
+
+impl TimeoutCollector {
+    /// Create a new collector
+    pub fn new(view: u64, quorum_size: usize) -> Self {
+        Self {
+            view,
+            messages: HashMap::new(),
+            quorum_size,
+        }
+    }
+
+    /// Add a timeout message
+    /// 
+    /// The message's high QC is verified against `validator_keys`, since
+    /// its signature only covers the QC's view.
+    /// 
+    /// # Returns
+    /// `Ok(Some(tc))` once the message completes a quorum,
+    /// `Ok(None)` while collecting,
+    /// `Err(String)` if the message is invalid or a duplicate
+    pub fn add_message(
+        &mut self,
+        msg: TimeoutMessage,
+        validator_keys: &HashMap<u64, BLSPublicKey>,
+    ) -> Result<Option<TimeoutCertificate>, String> {
+        if msg.view != self.view {
+            return Err(format!(
+                "Timeout message view mismatch: expected {}, got {}",
+                self.view, msg.view
+            ));
+        }
+        if !msg.verify() {
+            return Err("Invalid timeout signature".to_string());
+        }
+        if let Some(ref qc) = msg.high_qc {
+            qc.verify_quorum(validator_keys, self.quorum_size)
+                .map_err(|e| format!("Invalid timeout high QC: {}", e))?;
+        }
+        
+        let sender = msg.sender.validator_id();
+        if self.messages.contains_key(&sender) {
+            return Err("Duplicate timeout message from sender".to_string());
+        }
+        self.messages.insert(sender, msg);
+        
+        if self.messages.len() != self.quorum_size {
+            return Ok(None);
+        }
+        self.form_tc().map(Some)
+    }
+
+    /// Number of collected messages
+    pub fn message_count(&self) -> usize {
+        self.messages.len()
+    }
+
+    fn form_tc(&self) -> Result<TimeoutCertificate, String> {
+        let mut messages: Vec<_> = self.messages.values().collect();
+        messages.sort_by_key(|m| m.sender.validator_id());
+        
+        let partials: Vec<_> = messages.iter().map(|m| m.signature.clone()).collect();
+        let signature = threshold_combine(&[], &partials, partials.len())
+            .map_err(|e| format!("TC aggregation failed: {}", e))?;
+        let high_qc = messages
+            .iter()
+            .filter_map(|m| m.high_qc.clone())
+            .max_by_key(|qc| qc.view);
+        
+        Ok(TimeoutCertificate {
+            view: self.view,
+            high_qc,
+            signers: messages.iter().map(|m| (m.sender.validator_id(), m.high_qc_view())).collect(),
+            signature,
+        })
+    }
+}
+
 /// New-View collector for the leader
 /// 
 /// The new leader collects n-f new-view messages before proposing.
@@ -238,8 +483,8 @@ impl NewViewCollector {
 mod tests {
     use super::*;
     use crate::crypto::bls::BLSKeyPair;
-    use crate::hotstuff::types::{QuorumCertificate, MessageType};
-    use crate::crypto::{Hash, threshold_sign};
+    use crate::hotstuff::types::{vote_message, QuorumCertificate, MessageType};
+    use crate::crypto::{Hash, threshold_combine, threshold_sign};
 
     #[test]
     fn test_pacemaker_creation() {
@@ -523,6 +768,65 @@ mod tests {
         assert_eq!(high_qc, qc2);
     }
 
+    /// QC for `view` signed by validators 0-2
+    fn qc(keypairs: &[BLSKeyPair], view: u64) -> QuorumCertificate {
+        let block_hash = Hash::new([view as u8; 32]);
+        let data = vote_message(&MessageType::Prepare, &block_hash, view);
+        let partials: Vec<_> = keypairs[..3].iter().map(|k| threshold_sign(&k.secret_key, &data)).collect();
+        QuorumCertificate::new(MessageType::Prepare, block_hash, view, threshold_combine(&data, &partials, 3).unwrap())
+            .with_signers(vec![0, 1, 2])
+    }
+
+    #[test]
+    fn test_timeout_certificate_formation() {
+        let keypairs: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let keys: HashMap<_, _> = keypairs.iter().map(|k| (k.public_key.validator_id(), k.public_key.clone())).collect();
+        let qc = |view| qc(&keypairs, view);
+        let mut collector = TimeoutCollector::new(3, 3);
+        
+        // Wrong view, bad signature and duplicate are rejected
+        assert!(collector.add_message(TimeoutMessage::new(4, None, &keypairs[0]), &keys).is_err());
+        let mut forged = TimeoutMessage::new(3, Some(qc(2)), &keypairs[0]);
+        forged.high_qc = Some(qc(5));
+        assert!(collector.add_message(forged, &keys).is_err());
+        
+        // So is a validly signed timeout carrying a forged QC
+        let mut fake = qc(2);
+        fake.signature = threshold_sign(&keypairs[0].secret_key, b"qc").signature;
+        assert!(collector.add_message(TimeoutMessage::new(3, Some(fake.clone()), &keypairs[0]), &keys).is_err());
+        
+        assert!(collector.add_message(TimeoutMessage::new(3, Some(qc(1)), &keypairs[0]), &keys).unwrap().is_none());
+        assert!(collector.add_message(TimeoutMessage::new(3, Some(qc(1)), &keypairs[0]), &keys).is_err());
+        assert!(collector.add_message(TimeoutMessage::new(3, None, &keypairs[2]), &keys).unwrap().is_none());
+        let tc = collector.add_message(TimeoutMessage::new(3, Some(qc(2)), &keypairs[3]), &keys).unwrap().unwrap();
+        
+        assert_eq!(tc.signers, vec![(0, 1), (2, 0), (3, 2)]);
+        assert_eq!(tc.high_qc.as_ref().unwrap().view, 2);
+        assert!(tc.verify(&keys, 3).is_ok());
+        assert!(tc.verify(&keys, 4).is_err());
+        
+        // Swapping in a lower QC than the signers reported is detected
+        let mut tampered = tc.clone();
+        tampered.high_qc = Some(qc(1));
+        assert!(tampered.verify(&keys, 3).is_err());
+        let mut tampered = tc.clone();
+        tampered.signers[1].1 = 2;
+        assert!(tampered.verify(&keys, 3).is_err());
+        // as is a forged QC of the reported view
+        let mut tampered = tc.clone();
+        tampered.high_qc = Some(fake);
+        assert!(tampered.verify(&keys, 3).is_err());
+        
+        // The TC moves the pacemaker past the timed-out view
+        let mut pm = Pacemaker::new(4, None);
+        pm.record_timeout();
+        assert_eq!(pm.current_view(), 1);
+        assert!(pm.advance_with_tc(tc.clone()));
+        assert_eq!(pm.current_view(), 4);
+        assert!(!pm.advance_with_tc(tc));
+        assert_eq!(pm.high_tc().unwrap().view, 3);
+    }
+
     #[test]
     fn test_new_view_no_qcs() {
         let mut collector = NewViewCollector::new(2, 3);
@@ -1,27 +1,38 @@
 /// Storage layer implementation using RocksDB
 /// 
+/// Production nodes use RocksDB; any `kvstore::StorageBackend` can be
+/// swapped in (tests use the in-memory backend).
 /// Provides persistent storage for blocks, state, and metadata
 /// with efficient querying and pruning capabilities.
 
-use crate::crypto::Hash;
-use crate::hotstuff::types::Block;
-use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, Options, DB};
+use crate::checkpoint::CheckpointMetadata;
+use crate::crypto::{Hash, KeyRotation};
+use crate::hotstuff::commit_stream::CommittedBlock;
+use crate::hotstuff::evidence::Evidence;
+use crate::hotstuff::slashing::Slasher;
+use crate::hotstuff::types::{Block, QuorumCertificate};
+use crate::light_client::{ValidatorHistory, ValidatorSet};
+use crate::network::peer_store::PeerRecord;
+use libp2p::PeerId;
+use kvstore::{Direction, MemoryBackend, RocksDbBackend, StorageBackend, WriteBatch};
 use std::path::Path;
 use std::sync::Arc;
 use thiserror::Error;
 
 pub mod pruning;
 pub mod state_machine;
+pub mod wal;
 
 // Re-export for convenience
-pub use state_machine::{Query, QueryResponse, State, StateMachine, StateTransition};
-pub use pruning::{Pruner, PruningConfig};
+pub use state_machine::{Query, QueryResponse, SparseMerkleProof, SparseMerkleTree, State, StateMachine, StateTransition};
+pub use pruning::{PruneProgress, PruneStats, Pruner, PruningConfig, RetentionPolicy};
+pub use wal::{WalEntry, WalState};
 
 /// Storage errors
 #[derive(Error, Debug)]
 pub enum StorageError {
     #[error("Database error: {0}")]
-    DatabaseError(#[from] rocksdb::Error),
+    DatabaseError(#[from] kvstore::BackendError),
     
     #[error("Serialization error: {0}")]
     SerializationError(String),
@@ -38,53 +49,76 @@ pub enum StorageError {
 
 pub type Result<T> = std::result::Result<T, StorageError>;
 
+/// Key rotations are keyed by effective epoch, then validator ID
+fn key_rotation_key(epoch: u64, validator_id: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(16);
+    key.extend_from_slice(&epoch.to_be_bytes());
+    key.extend_from_slice(&validator_id.to_be_bytes());
+    key
+}
+
 /// Column family names
 const CF_BLOCKS: &str = "blocks";
+const CF_HEIGHTS: &str = "heights";
 const CF_STATES: &str = "states";
 const CF_TRANSACTIONS: &str = "transactions";
 const CF_METADATA: &str = "metadata";
+const CF_EVIDENCE: &str = "evidence";
+const CF_WAL: &str = "wal";
+const CF_CHECKPOINTS: &str = "checkpoints";
+const CF_VALIDATOR_SETS: &str = "validator_sets";
+const CF_KEY_ROTATIONS: &str = "key_rotations";
+const CF_COMMITS: &str = "commits";
+const CF_PEERS: &str = "peers";
+
+const COLUMN_FAMILIES: &[&str] = &[
+    CF_BLOCKS,
+    CF_HEIGHTS,
+    CF_STATES,
+    CF_TRANSACTIONS,
+    CF_METADATA,
+    CF_EVIDENCE,
+    CF_WAL,
+    CF_CHECKPOINTS,
+    CF_VALIDATOR_SETS,
+    CF_KEY_ROTATIONS,
+    CF_COMMITS,
+    CF_PEERS,
+];
 
 /// Metadata keys
 const KEY_LATEST_BLOCK_HASH: &[u8] = b"latest_block_hash";
 const KEY_LATEST_BLOCK_HEIGHT: &[u8] = b"latest_block_height";
+const KEY_HEALTH_PROBE: &[u8] = b"health_probe";
+const KEY_SLASHER: &[u8] = b"slasher";
 
 /// Main storage implementation
 pub struct Storage {
-    db: Arc<DB>,
+    backend: Arc<dyn StorageBackend>,
 }
 
 impl Storage {
     /// Create a new storage instance
     /// Opens RocksDB with predefined column families
     pub fn new(path: &Path) -> Result<Self> {
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
-        opts.create_missing_column_families(true);
-        
-        // Define column families
-        let cfs = vec![
-            ColumnFamilyDescriptor::new(CF_BLOCKS, Options::default()),
-            ColumnFamilyDescriptor::new(CF_STATES, Options::default()),
-            ColumnFamilyDescriptor::new(CF_TRANSACTIONS, Options::default()),
-            ColumnFamilyDescriptor::new(CF_METADATA, Options::default()),
-        ];
-        
-        let db = DB::open_cf_descriptors(&opts, path, cfs)?;
-        
-        Ok(Self {
-            db: Arc::new(db),
-        })
+        let backend = RocksDbBackend::open(path, COLUMN_FAMILIES)?;
+        Ok(Self::with_backend(Arc::new(backend)))
     }
     
     /// Create an in-memory storage for testing
     pub fn new_temp() -> Result<Self> {
-        let temp_dir = tempfile::tempdir()
-            .map_err(|e| StorageError::InvalidData(e.to_string()))?;
-        Self::new(temp_dir.path())
+        Ok(Self::with_backend(Arc::new(MemoryBackend::new())))
+    }
+    
+    /// Create a storage instance over any key-value backend
+    pub fn with_backend(backend: Arc<dyn StorageBackend>) -> Self {
+        Self { backend }
     }
     
     /// Store a block
     pub fn store_block(&self, block: &Block) -> Result<()> {
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::global().time_storage(crate::metrics::StorageOp::Write);
         let hash = block.hash();
         let height = block.height;
         
@@ -93,17 +127,19 @@ impl Storage {
             .map_err(|e| StorageError::SerializationError(e.to_string()))?;
         
         // Get column families
-        let cf_blocks = self.get_cf(CF_BLOCKS)?;
-        let cf_metadata = self.get_cf(CF_METADATA)?;
         
-        // Store block by hash
-        self.db.put_cf(cf_blocks, hash.as_bytes(), &block_bytes)?;
+        // Store block by hash, indexed by height (the latest block stored
+        // at a height wins)
+        let mut batch = WriteBatch::new();
+        batch.put(CF_BLOCKS, hash.as_bytes(), &block_bytes);
+        batch.put(CF_HEIGHTS, height.to_be_bytes(), hash.as_bytes());
+        self.backend.write(batch, false)?;
         
         // Update latest block metadata
         let current_latest = self.get_latest_block_height()?;
         if current_latest.is_none() || height > current_latest.unwrap() {
-            self.db.put_cf(cf_metadata, KEY_LATEST_BLOCK_HASH, hash.as_bytes())?;
-            self.db.put_cf(cf_metadata, KEY_LATEST_BLOCK_HEIGHT, &height.to_le_bytes())?;
+            self.backend.put(CF_METADATA, KEY_LATEST_BLOCK_HASH, hash.as_bytes())?;
+            self.backend.put(CF_METADATA, KEY_LATEST_BLOCK_HEIGHT, &height.to_le_bytes())?;
         }
         
         Ok(())
@@ -111,9 +147,10 @@ impl Storage {
     
     /// Retrieve a block by hash
     pub fn get_block(&self, hash: &Hash) -> Result<Option<Block>> {
-        let cf_blocks = self.get_cf(CF_BLOCKS)?;
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::global().time_storage(crate::metrics::StorageOp::Read);
         
-        match self.db.get_cf(cf_blocks, hash.as_bytes())? {
+        match self.backend.get(CF_BLOCKS, hash.as_bytes())? {
             Some(bytes) => {
                 let block = bincode::deserialize(&bytes)
                     .map_err(|e| StorageError::SerializationError(e.to_string()))?;
@@ -123,11 +160,65 @@ impl Storage {
         }
     }
     
+    /// Retrieve the block indexed at a height
+    pub fn get_block_by_height(&self, height: u64) -> Result<Option<Block>> {
+        
+        match self.backend.get(CF_HEIGHTS, &height.to_be_bytes())? {
+            Some(hash_bytes) => self.get_block(&Self::decode_hash(&hash_bytes)?),
+            None => Ok(None),
+        }
+    }
+    
+    /// Retrieve indexed blocks with heights in `from..=to`, by height.
+    /// Heights without a stored block are skipped.
+    pub fn get_block_range(&self, from: u64, to: u64) -> Result<Vec<Block>> {
+        let start = from.to_be_bytes();
+        
+        let mut blocks = Vec::new();
+        for item in self.backend.iter(CF_HEIGHTS, Some(&start), Direction::Forward)? {
+            let (key, hash_bytes) = item?;
+            let height_bytes: [u8; 8] = key.as_slice().try_into()
+                .map_err(|_| StorageError::InvalidData("Invalid height key".into()))?;
+            if u64::from_be_bytes(height_bytes) > to {
+                break;
+            }
+            if let Some(block) = self.get_block(&Self::decode_hash(&hash_bytes)?)? {
+                blocks.push(block);
+            }
+        }
+        
+        Ok(blocks)
+    }
+    
+    /// Record the QC committing the block at `height`
+    pub fn store_commit(&self, height: u64, qc: &QuorumCertificate) -> Result<()> {
+        let bytes = bincode::serialize(qc)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        self.backend.put(CF_COMMITS, &height.to_be_bytes(), &bytes)?;
+        
+        Ok(())
+    }
+    
+    /// First committed block at or above `height`, with the QC committing
+    /// it. Commits whose block has been pruned are skipped.
+    pub fn next_commit(&self, height: u64) -> Result<Option<CommittedBlock>> {
+        let start = height.to_be_bytes();
+        for item in self.backend.iter(CF_COMMITS, Some(&start), Direction::Forward)? {
+            let (_, bytes) = item?;
+            let qc: QuorumCertificate = bincode::deserialize(&bytes)
+                .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+            if let Some(block) = self.get_block(&qc.block_hash)? {
+                return Ok(Some(CommittedBlock { block, qc }));
+            }
+        }
+        
+        Ok(None)
+    }
+    
     /// Get the latest block
     pub fn get_latest_block(&self) -> Result<Option<Block>> {
-        let cf_metadata = self.get_cf(CF_METADATA)?;
         
-        match self.db.get_cf(cf_metadata, KEY_LATEST_BLOCK_HASH)? {
+        match self.backend.get(CF_METADATA, KEY_LATEST_BLOCK_HASH)? {
             Some(hash_bytes) => {
                 if hash_bytes.len() != 32 {
                     return Err(StorageError::InvalidData("Invalid hash length".into()));
@@ -143,9 +234,8 @@ impl Storage {
     
     /// Get the latest block height
     pub fn get_latest_block_height(&self) -> Result<Option<u64>> {
-        let cf_metadata = self.get_cf(CF_METADATA)?;
         
-        match self.db.get_cf(cf_metadata, KEY_LATEST_BLOCK_HEIGHT)? {
+        match self.backend.get(CF_METADATA, KEY_LATEST_BLOCK_HEIGHT)? {
             Some(bytes) => {
                 if bytes.len() != 8 {
                     return Err(StorageError::InvalidData("Invalid height bytes".into()));
@@ -160,21 +250,23 @@ impl Storage {
     
     /// Store state at a specific height
     pub fn store_state(&self, height: u64, state: &State) -> Result<()> {
-        let cf_states = self.get_cf(CF_STATES)?;
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::global().time_storage(crate::metrics::StorageOp::Write);
         
         let state_bytes = bincode::serialize(state)
             .map_err(|e| StorageError::SerializationError(e.to_string()))?;
         
-        self.db.put_cf(cf_states, &height.to_le_bytes(), &state_bytes)?;
+        self.backend.put(CF_STATES, &height.to_le_bytes(), &state_bytes)?;
         
         Ok(())
     }
     
     /// Retrieve state at a specific height
     pub fn get_state(&self, height: u64) -> Result<Option<State>> {
-        let cf_states = self.get_cf(CF_STATES)?;
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::global().time_storage(crate::metrics::StorageOp::Read);
         
-        match self.db.get_cf(cf_states, &height.to_le_bytes())? {
+        match self.backend.get(CF_STATES, &height.to_le_bytes())? {
             Some(bytes) => {
                 let state = bincode::deserialize(&bytes)
                     .map_err(|e| StorageError::SerializationError(e.to_string()))?;
@@ -184,36 +276,331 @@ impl Storage {
         }
     }
     
+    /// Atomically store a checkpoint's state and metadata, dropping the
+    /// metadata of the `pruned` checkpoints in the same write
+    pub fn store_checkpoint(&self, metadata: &CheckpointMetadata, state: &State, pruned: &[u64]) -> Result<()> {
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::global().time_storage(crate::metrics::StorageOp::Write);
+        
+        let state_bytes = bincode::serialize(state)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        let metadata_bytes = bincode::serialize(metadata)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        
+        let mut batch = WriteBatch::new();
+        batch.put(CF_STATES, metadata.height.to_le_bytes(), &state_bytes);
+        batch.put(CF_CHECKPOINTS, metadata.height.to_be_bytes(), &metadata_bytes);
+        for height in pruned {
+            batch.delete(CF_CHECKPOINTS, height.to_be_bytes());
+        }
+        self.backend.write(batch, true)?;
+        
+        Ok(())
+    }
+    
+    /// Load all checkpoint metadata, by height
+    pub fn load_checkpoints(&self) -> Result<Vec<CheckpointMetadata>> {
+        
+        let mut checkpoints = Vec::new();
+        for item in self.backend.iter(CF_CHECKPOINTS, None, Direction::Forward)? {
+            let (_, bytes) = item?;
+            checkpoints.push(
+                bincode::deserialize(&bytes)
+                    .map_err(|e| StorageError::SerializationError(e.to_string()))?,
+            );
+        }
+        
+        Ok(checkpoints)
+    }
+    
+    /// Atomically delete a checkpoint's metadata and state
+    pub fn delete_checkpoint(&self, height: u64) -> Result<()> {
+        
+        let mut batch = WriteBatch::new();
+        batch.delete(CF_CHECKPOINTS, height.to_be_bytes());
+        batch.delete(CF_STATES, height.to_le_bytes());
+        self.backend.write(batch, true)?;
+        
+        Ok(())
+    }
+    
+    /// Store the validator set of an epoch, active from `height` on
+    pub fn store_validator_set(&self, height: u64, validators: &ValidatorSet) -> Result<()> {
+        
+        let bytes = bincode::serialize(&(height, validators))
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        self.backend.put(CF_VALIDATOR_SETS, &validators.epoch.to_be_bytes(), &bytes)?;
+        
+        Ok(())
+    }
+    
+    /// Load the validator set of `epoch` with the height it took effect at
+    pub fn get_validator_set(&self, epoch: u64) -> Result<Option<(u64, ValidatorSet)>> {
+        
+        match self.backend.get(CF_VALIDATOR_SETS, &epoch.to_be_bytes())? {
+            Some(bytes) => {
+                let entry = bincode::deserialize(&bytes)
+                    .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+                Ok(Some(entry))
+            }
+            None => Ok(None),
+        }
+    }
+    
+    /// Load every stored validator set, by the height it took effect at
+    pub fn load_validator_history(&self) -> Result<ValidatorHistory> {
+        
+        let mut sets = Vec::new();
+        for item in self.backend.iter(CF_VALIDATOR_SETS, None, Direction::Forward)? {
+            let (_, bytes) = item?;
+            let entry: (u64, ValidatorSet) = bincode::deserialize(&bytes)
+                .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+            sets.push(entry);
+        }
+        
+        Ok(sets.into_iter().collect())
+    }
+    
+    /// Store a committed key rotation until its epoch starts (a later
+    /// rotation by the same validator for the same epoch replaces it)
+    pub fn store_key_rotation(&self, rotation: &KeyRotation) -> Result<()> {
+        
+        let bytes = bincode::serialize(rotation)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        let key = key_rotation_key(rotation.effective_epoch, rotation.validator_id);
+        self.backend.put(CF_KEY_ROTATIONS, &key, &bytes)?;
+        
+        Ok(())
+    }
+    
+    /// Load the key rotations taking effect at `epoch`, by validator ID
+    pub fn get_key_rotations(&self, epoch: u64) -> Result<Vec<KeyRotation>> {
+        
+        let mut rotations = Vec::new();
+        let start = epoch.to_be_bytes();
+        for item in self.backend.iter(CF_KEY_ROTATIONS, Some(&start), Direction::Forward)? {
+            let (key, bytes) = item?;
+            if !key.starts_with(&start) {
+                break;
+            }
+            rotations.push(
+                bincode::deserialize(&bytes)
+                    .map_err(|e| StorageError::SerializationError(e.to_string()))?,
+            );
+        }
+        
+        Ok(rotations)
+    }
+    
+    /// Start the epoch after `current` at `height`: apply its pending key
+    /// rotations, store the resulting set and drop the applied rotations
+    /// in one batch. Earlier sets keep the old keys for historical QCs.
+    pub fn advance_epoch(&self, height: u64, current: &ValidatorSet) -> Result<ValidatorSet> {
+        let epoch = current.epoch + 1;
+        let rotations = self.get_key_rotations(epoch)?;
+        let next = current
+            .apply_rotations(&rotations)
+            .map_err(StorageError::InvalidData)?;
+        
+        let bytes = bincode::serialize(&(height, &next))
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        let mut batch = WriteBatch::new();
+        batch.put(CF_VALIDATOR_SETS, epoch.to_be_bytes(), &bytes);
+        for rotation in &rotations {
+            batch.delete(CF_KEY_ROTATIONS, key_rotation_key(epoch, rotation.validator_id));
+        }
+        self.backend.write(batch, true)?;
+        
+        Ok(next)
+    }
+    
+    /// Store equivocation evidence (once per offender, view and kind)
+    pub fn store_evidence(&self, evidence: &Evidence) -> Result<()> {
+        
+        let mut key = Vec::with_capacity(17);
+        key.extend_from_slice(&evidence.view().to_be_bytes());
+        key.extend_from_slice(&evidence.offender().to_be_bytes());
+        key.push(evidence.misbehavior.kind());
+        
+        let bytes = bincode::serialize(evidence)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        self.backend.put(CF_EVIDENCE, &key, &bytes)?;
+        
+        Ok(())
+    }
+    
+    /// Load all stored evidence, by view then offender
+    pub fn load_evidence(&self) -> Result<Vec<Evidence>> {
+        
+        let mut evidence = Vec::new();
+        for item in self.backend.iter(CF_EVIDENCE, None, Direction::Forward)? {
+            let (_, bytes) = item?;
+            evidence.push(
+                bincode::deserialize(&bytes)
+                    .map_err(|e| StorageError::SerializationError(e.to_string()))?,
+            );
+        }
+        
+        Ok(evidence)
+    }
+    
+    /// Store the slasher as of the committed block at `height`
+    pub fn store_slasher(&self, height: u64, slasher: &Slasher) -> Result<()> {
+        
+        let bytes = bincode::serialize(&(height, slasher))
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        self.backend.put(CF_METADATA, KEY_SLASHER, &bytes)?;
+        
+        Ok(())
+    }
+    
+    /// Load the last stored slasher with the height it was stored at
+    pub fn load_slasher(&self) -> Result<Option<(u64, Slasher)>> {
+        
+        match self.backend.get(CF_METADATA, KEY_SLASHER)? {
+            Some(bytes) => {
+                let entry = bincode::deserialize(&bytes)
+                    .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+                Ok(Some(entry))
+            }
+            None => Ok(None),
+        }
+    }
+    
+    /// Store what is known about a peer, replacing the previous record
+    pub fn store_peer(&self, peer_id: &PeerId, record: &PeerRecord) -> Result<()> {
+        
+        let bytes = bincode::serialize(record)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        self.backend.put(CF_PEERS, &peer_id.to_bytes(), &bytes)?;
+        
+        Ok(())
+    }
+    
+    /// Forget a peer's record
+    pub fn delete_peer(&self, peer_id: &PeerId) -> Result<()> {
+        self.backend.delete(CF_PEERS, &peer_id.to_bytes())?;
+        Ok(())
+    }
+    
+    /// Load every stored peer record
+    pub fn load_peers(&self) -> Result<Vec<(PeerId, PeerRecord)>> {
+        
+        let mut peers = Vec::new();
+        for item in self.backend.iter(CF_PEERS, None, Direction::Forward)? {
+            let (key, bytes) = item?;
+            let peer_id = PeerId::from_bytes(&key)
+                .map_err(|e| StorageError::InvalidData(format!("peer ID: {}", e)))?;
+            let record = bincode::deserialize(&bytes)
+                .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+            peers.push((peer_id, record));
+        }
+        
+        Ok(peers)
+    }
+    
+    /// Durably append a consensus write-ahead log entry (synced to disk
+    /// before returning)
+    pub fn append_wal(&self, entry: &WalEntry) -> Result<()> {
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::global().time_storage(crate::metrics::StorageOp::Write);
+        let seq = self.next_wal_seq()?;
+        
+        let bytes = bincode::serialize(entry)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        let mut batch = WriteBatch::new();
+        batch.put(CF_WAL, seq.to_be_bytes(), &bytes);
+        self.backend.write(batch, true)?;
+        
+        Ok(())
+    }
+    
+    /// Load write-ahead log entries in append order
+    pub fn load_wal(&self) -> Result<Vec<WalEntry>> {
+        
+        let mut entries = Vec::new();
+        for item in self.backend.iter(CF_WAL, None, Direction::Forward)? {
+            let (_, bytes) = item?;
+            entries.push(
+                bincode::deserialize(&bytes)
+                    .map_err(|e| StorageError::SerializationError(e.to_string()))?,
+            );
+        }
+        
+        Ok(entries)
+    }
+    
+    /// Replace the write-ahead log with a single snapshot of `state`
+    pub fn compact_wal(&self, state: &WalState) -> Result<()> {
+        let seq = self.next_wal_seq()?;
+        
+        let bytes = bincode::serialize(&WalEntry::Snapshot(Box::new(state.clone())))
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        let mut batch = WriteBatch::new();
+        for item in self.backend.iter(CF_WAL, None, Direction::Forward)? {
+            let (key, _) = item?;
+            batch.delete(CF_WAL, key);
+        }
+        batch.put(CF_WAL, seq.to_be_bytes(), &bytes);
+        self.backend.write(batch, true)?;
+        
+        Ok(())
+    }
+    
+    /// Sequence number after the last write-ahead log entry
+    fn next_wal_seq(&self) -> Result<u64> {
+        match self.backend.iter(CF_WAL, None, Direction::Reverse)?.next() {
+            Some(item) => {
+                let (key, _) = item?;
+                let bytes: [u8; 8] = key.as_slice().try_into()
+                    .map_err(|_| StorageError::InvalidData("Invalid WAL key".into()))?;
+                Ok(u64::from_be_bytes(bytes) + 1)
+            }
+            None => Ok(0),
+        }
+    }
+    
     /// Perform atomic batch writes
     pub fn batch_write<F>(&self, f: F) -> Result<()>
     where
-        F: FnOnce(&mut rocksdb::WriteBatch) -> Result<()>,
+        F: FnOnce(&mut WriteBatch) -> Result<()>,
     {
-        let mut batch = rocksdb::WriteBatch::default();
+        let mut batch = WriteBatch::new();
         f(&mut batch)?;
-        self.db.write(batch)?;
+        self.backend.write(batch, false)?;
         Ok(())
     }
     
-    /// Delete a block by hash
+    /// Check that the database accepts writes (for health probes)
+    pub fn check_writable(&self) -> Result<()> {
+        self.backend.put(CF_METADATA, KEY_HEALTH_PROBE, &[1u8])?;
+        self.backend.delete(CF_METADATA, KEY_HEALTH_PROBE)?;
+        Ok(())
+    }
+    
+    /// Delete a block by hash, and its height index entry if it points
+    /// to this block
     pub fn delete_block(&self, hash: &Hash) -> Result<()> {
-        let cf_blocks = self.get_cf(CF_BLOCKS)?;
-        self.db.delete_cf(cf_blocks, hash.as_bytes())?;
+        
+        if let Some(block) = self.get_block(hash)? {
+            let key = block.height.to_be_bytes();
+            if self.backend.get(CF_HEIGHTS, &key)?.as_deref() == Some(hash.as_bytes().as_slice()) {
+                self.backend.delete(CF_HEIGHTS, &key)?;
+            }
+        }
+        self.backend.delete(CF_BLOCKS, hash.as_bytes())?;
         Ok(())
     }
     
     /// Delete state at a specific height
     pub fn delete_state(&self, height: u64) -> Result<()> {
-        let cf_states = self.get_cf(CF_STATES)?;
-        self.db.delete_cf(cf_states, &height.to_le_bytes())?;
+        self.backend.delete(CF_STATES, &height.to_le_bytes())?;
         Ok(())
     }
     
-    /// Get column family handle
-    fn get_cf(&self, name: &str) -> Result<&ColumnFamily> {
-        self.db
-            .cf_handle(name)
-            .ok_or_else(|| StorageError::InvalidData(format!("Column family not found: {}", name)))
+    fn decode_hash(bytes: &[u8]) -> Result<Hash> {
+        Hash::from_slice(bytes)
+            .map_err(|_| StorageError::InvalidData("Invalid hash length".into()))
     }
 }
 
@@ -286,6 +673,26 @@ mod tests {
         assert_eq!(latest.unwrap().height, 3);
     }
     
+    #[test]
+    fn test_block_height_index() {
+        let storage = Storage::new_temp().unwrap();
+        for height in [1, 2, 4, 5] {
+            storage.store_block(&create_test_block(height, height)).unwrap();
+        }
+        
+        assert_eq!(storage.get_block_by_height(2).unwrap().unwrap().height, 2);
+        assert!(storage.get_block_by_height(3).unwrap().is_none());
+        
+        let heights: Vec<u64> = storage.get_block_range(2, 4).unwrap().iter().map(|b| b.height).collect();
+        assert_eq!(heights, vec![2, 4]);
+        
+        // Deleting a block removes it from the index
+        let block = storage.get_block_by_height(4).unwrap().unwrap();
+        storage.delete_block(&block.hash()).unwrap();
+        assert!(storage.get_block_by_height(4).unwrap().is_none());
+        assert_eq!(storage.get_block_range(0, 10).unwrap().len(), 3);
+    }
+    
     #[test]
     fn test_store_state() {
         let storage = Storage::new_temp().unwrap();
@@ -308,14 +715,13 @@ mod tests {
         
         // Batch write both blocks
         storage.batch_write(|batch| {
-            let cf_blocks = storage.get_cf(CF_BLOCKS)?;
             let block1_bytes = bincode::serialize(&block1)
                 .map_err(|e| StorageError::SerializationError(e.to_string()))?;
             let block2_bytes = bincode::serialize(&block2)
                 .map_err(|e| StorageError::SerializationError(e.to_string()))?;
             
-            batch.put_cf(cf_blocks, hash1.as_bytes(), &block1_bytes);
-            batch.put_cf(cf_blocks, hash2.as_bytes(), &block2_bytes);
+            batch.put(CF_BLOCKS, hash1.as_bytes(), &block1_bytes);
+            batch.put(CF_BLOCKS, hash2.as_bytes(), &block2_bytes);
             
             Ok(())
         }).unwrap();
@@ -377,4 +783,33 @@ mod tests {
             assert!(storage.get_block(&hash).unwrap().is_some());
         }
     }
+    
+    #[test]
+    fn test_key_rotations_applied_at_epoch_boundary() {
+        use crate::crypto::BLSKeyPair;
+        
+        let storage = Storage::new_temp().unwrap();
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let genesis = ValidatorSet::new(0, keys.iter().map(|k| k.public_key.clone()));
+        storage.store_validator_set(0, &genesis).unwrap();
+        
+        let new_key = BLSKeyPair::with_id(2);
+        let early = KeyRotation::new(&keys[2].secret_key, &new_key.secret_key, 1).unwrap();
+        let later = KeyRotation::new(&keys[3].secret_key, &BLSKeyPair::with_id(3).secret_key, 2).unwrap();
+        storage.store_key_rotation(&early).unwrap();
+        storage.store_key_rotation(&later).unwrap();
+        assert_eq!(storage.get_key_rotations(1).unwrap(), vec![early]);
+        
+        // Epoch 1 takes the first rotation only; the second waits for epoch 2
+        let epoch1 = storage.advance_epoch(100, &genesis).unwrap();
+        assert_eq!(epoch1.keys[&2], new_key.public_key);
+        assert_eq!(epoch1.keys[&3], keys[3].public_key);
+        assert!(storage.get_key_rotations(1).unwrap().is_empty());
+        assert_eq!(storage.get_key_rotations(2).unwrap().len(), 1);
+        
+        // History keeps the old key for heights before the boundary
+        let history = storage.load_validator_history().unwrap();
+        assert_eq!(history.at(99).unwrap().keys[&2], keys[2].public_key);
+        assert_eq!(history.at(100).unwrap().keys[&2], new_key.public_key);
+    }
 }
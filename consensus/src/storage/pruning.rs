@@ -1,9 +1,18 @@
 /// Pruning logic for storage management
 /// 
+/// Whatever the policy, pruning never removes blocks at or above the latest
+/// checkpoint, blocks at checkpoint heights, or blocks from the lowest one
+/// referenced by a protected (locked/prepare) QC up.
+/// 
 /// Implements configurable retention policies for validators and non-validators
 
 use crate::crypto::Hash;
 use crate::storage::{Storage, Result};
+use std::collections::{BTreeSet, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
 
 /// Pruning configuration
 #[derive(Clone, Debug)]
@@ -13,6 +22,12 @@ pub struct PruningConfig {
     
     /// Whether this node is a validator
     pub is_validator: bool,
+    
+    /// Heights examined per storage scan
+    pub batch_size: u64,
+    
+    /// Interval between background pruning runs
+    pub interval: Duration,
 }
 
 impl Default for PruningConfig {
@@ -20,6 +35,18 @@ impl Default for PruningConfig {
         Self {
             policy: RetentionPolicy::KeepRecent(100),
             is_validator: false,
+            batch_size: 1000,
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl PruningConfig {
+    /// Archive node configuration: nothing is ever pruned
+    pub fn archive() -> Self {
+        Self {
+            policy: RetentionPolicy::KeepAll,
+            ..Default::default()
         }
     }
 }
@@ -27,7 +54,7 @@ impl Default for PruningConfig {
 /// Retention policy determines how many blocks/states to keep
 #[derive(Clone, Debug)]
 pub enum RetentionPolicy {
-    /// Keep all blocks (never prune)
+    /// Keep all blocks (never prune; archive mode)
     KeepAll,
     
     /// Keep the last N blocks
@@ -35,37 +62,71 @@ pub enum RetentionPolicy {
     
     /// Keep blocks after a specific height
     KeepAfterHeight(u64),
+    
+    /// Keep only checkpointed blocks below the latest checkpoint
+    KeepCheckpoints,
+}
+
+/// Cumulative progress of a pruner
+#[derive(Default, Debug, Clone)]
+pub struct PruneProgress {
+    /// Completed pruning runs
+    pub runs: u64,
+    pub blocks_pruned: usize,
+    pub states_pruned: usize,
+    /// Height pruning stopped below in the last run
+    pub floor: u64,
+    /// Error of the last failed background run
+    pub last_error: Option<String>,
 }
 
 /// Pruner manages storage pruning based on configuration
 pub struct Pruner {
     config: PruningConfig,
+    
+    /// Blocks referenced by the locked and prepare QCs
+    protected: RwLock<HashSet<Hash>>,
+    
+    progress: Mutex<PruneProgress>,
 }
 
 impl Pruner {
     /// Create a new pruner with the given configuration
     pub fn new(config: PruningConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            protected: RwLock::new(HashSet::new()),
+            progress: Mutex::new(PruneProgress::default()),
+        }
     }
     
     /// Create a pruner for validators (keep more history)
     pub fn for_validator() -> Self {
-        Self {
-            config: PruningConfig {
-                policy: RetentionPolicy::KeepRecent(1000),
-                is_validator: true,
-            },
-        }
+        Self::new(PruningConfig {
+            policy: RetentionPolicy::KeepRecent(1000),
+            is_validator: true,
+            ..Default::default()
+        })
     }
     
     /// Create a pruner for non-validators (keep less history)
     pub fn for_non_validator() -> Self {
-        Self {
-            config: PruningConfig {
-                policy: RetentionPolicy::KeepRecent(100),
-                is_validator: false,
-            },
-        }
+        Self::new(PruningConfig {
+            policy: RetentionPolicy::KeepRecent(100),
+            is_validator: false,
+            ..Default::default()
+        })
+    }
+    
+    /// Replace the blocks referenced by the locked and prepare QCs; they
+    /// and everything above them are kept
+    pub fn protect(&self, block_hashes: impl IntoIterator<Item = Hash>) {
+        *self.protected.write().unwrap() = block_hashes.into_iter().collect();
+    }
+    
+    /// Progress so far
+    pub fn progress(&self) -> PruneProgress {
+        self.progress.lock().unwrap().clone()
     }
     
     /// Determine if a block at given height should be pruned
@@ -84,6 +145,8 @@ impl Pruner {
                 }
             }
             RetentionPolicy::KeepAfterHeight(min_height) => block_height < min_height,
+            // Checkpointed heights are kept by the pruning guards
+            RetentionPolicy::KeepCheckpoints => true,
         }
     }
     
@@ -97,48 +160,72 @@ impl Pruner {
             return Ok(stats);
         }
         
-        // Determine what to prune
-        let blocks_to_prune = self.find_blocks_to_prune(storage, current_height)?;
+        let checkpoints: BTreeSet<u64> = storage.load_checkpoints()?.iter().map(|m| m.height).collect();
+        let floor = self.prune_floor(storage, &checkpoints)?;
+        let batch_size = self.config.batch_size.max(1);
+        let mut from = 0;
         
-        // Prune blocks and states
-        for (height, hash) in blocks_to_prune {
-            // Delete block
-            storage.delete_block(&hash)?;
-            stats.blocks_pruned += 1;
-            
-            // Delete state at this height (if not validator or very old)
-            if !self.config.is_validator || self.should_prune(height, current_height) {
+        // Prune blocks and states below the floor, a batch at a time; pruned
+        // heights leave the height index, so later runs skip them
+        while from < floor {
+            let to = (from + batch_size - 1).min(floor - 1);
+            for block in storage.get_block_range(from, to)? {
+                let height = block.height;
+                if checkpoints.contains(&height) || !self.should_prune(height, current_height) {
+                    continue;
+                }
+                
+                // Delete block
+                storage.delete_block(&block.hash())?;
+                stats.blocks_pruned += 1;
+                
+                // Delete state at this height
                 if storage.delete_state(height).is_ok() {
                     stats.states_pruned += 1;
                 }
             }
+            from = to + 1;
         }
         
+        let mut progress = self.progress.lock().unwrap();
+        progress.runs += 1;
+        progress.blocks_pruned += stats.blocks_pruned;
+        progress.states_pruned += stats.states_pruned;
+        progress.floor = floor;
+        
         Ok(stats)
     }
     
-    /// Find blocks that should be pruned
-    fn find_blocks_to_prune(
-        &self,
-        _storage: &Storage,
-        current_height: u64,
-    ) -> Result<Vec<(u64, Hash)>> {
-        let to_prune = Vec::new();
-        
-        // Walk backwards from current height to find candidates
-        for _height in 0..current_height {
-            // Uncomment when height index is implemented
-            /* if self.should_prune(height, current_height) {
-                // We need to get the block at this height
-                // For now, we'll skip blocks we can't find
-                // In a real implementation, we'd maintain a height->hash index
-                
-                // This is a simplified version - in production you'd want
-                // to maintain an index of height -> hash mappings
-            } */
+    /// Lowest height pruning must not reach: the latest checkpoint, or the
+    /// lowest block referenced by a protected QC if below it
+    fn prune_floor(&self, storage: &Storage, checkpoints: &BTreeSet<u64>) -> Result<u64> {
+        // Without a checkpoint every block is newer than the latest one
+        let mut floor = checkpoints.last().copied().unwrap_or(0);
+        let protected: Vec<Hash> = self.protected.read().unwrap().iter().copied().collect();
+        for hash in protected {
+            if let Some(block) = storage.get_block(&hash)? {
+                floor = floor.min(block.height);
+            }
         }
-        
-        Ok(to_prune)
+        Ok(floor)
+    }
+    
+    /// Prune in the background every `interval`, up to the latest stored
+    /// block; progress is reported by `progress`
+    pub fn spawn(self: Arc<Self>, storage: Arc<Storage>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.interval);
+            loop {
+                interval.tick().await;
+                let result = storage
+                    .get_latest_block_height()
+                    .and_then(|height| self.prune(&storage, height.unwrap_or(0)));
+                if let Err(e) = result {
+                    warn!("Storage pruning failed: {}", e);
+                    self.progress.lock().unwrap().last_error = Some(e.to_string());
+                }
+            }
+        })
     }
 }
 
@@ -179,6 +266,7 @@ mod tests {
         let pruner = Pruner::new(PruningConfig {
             policy: RetentionPolicy::KeepAll,
             is_validator: true,
+            ..Default::default()
         });
         
         assert!(!pruner.should_prune(0, 1000));
@@ -190,6 +278,7 @@ mod tests {
         let pruner = Pruner::new(PruningConfig {
             policy: RetentionPolicy::KeepRecent(100),
             is_validator: false,
+            ..Default::default()
         });
         
         // At height 150, keep last 100 blocks (51-150), prune 0-50
@@ -204,6 +293,7 @@ mod tests {
         let pruner = Pruner::new(PruningConfig {
             policy: RetentionPolicy::KeepAfterHeight(100),
             is_validator: false,
+            ..Default::default()
         });
         
         assert!(pruner.should_prune(50, 200));
@@ -214,25 +304,51 @@ mod tests {
     
     #[test]
     fn test_prune_old_blocks() {
+        use crate::checkpoint::{Checkpoint, CheckpointMetadata};
+        use crate::storage::State;
+        
         let storage = Storage::new_temp().unwrap();
         let pruner = Pruner::new(PruningConfig {
             policy: RetentionPolicy::KeepRecent(10),
             is_validator: false,
+            batch_size: 4,
+            ..Default::default()
         });
         
         // Store 20 blocks
-        for i in 0..20 {
-            let block = create_test_block(i);
-            storage.store_block(&block).unwrap();
+        let blocks: Vec<Block> = (0..20).map(create_test_block).collect();
+        for block in &blocks {
+            storage.store_block(block).unwrap();
         }
         
-        // Prune should identify old blocks
-        // (actual deletion depends on having a height index)
+        // Nothing is pruned before the first checkpoint
+        assert_eq!(pruner.prune(&storage, 20).unwrap().blocks_pruned, 0);
+        
+        let checkpoint = |height: u64| {
+            let mut state = State::genesis();
+            state.height = height;
+            state.root_hash = state.compute_hash();
+            let metadata = CheckpointMetadata::from(&Checkpoint::new(height, height, state.clone(), blocks[height as usize].hash()));
+            storage.store_checkpoint(&metadata, &state, &[]).unwrap();
+        };
+        checkpoint(4);
+        checkpoint(8);
+        
+        // Blocks referenced by QCs and everything above them are kept
+        pruner.protect([blocks[6].hash()]);
         let stats = pruner.prune(&storage, 20).unwrap();
+        assert_eq!(stats.blocks_pruned, 5); // 0-5 but the checkpointed 4
+        assert!(storage.get_block_by_height(4).unwrap().is_some());
+        assert!(storage.get_state(4).unwrap().is_some());
+        assert!(storage.get_block_by_height(6).unwrap().is_some());
+        
+        // With the QC released, pruning stops at the latest checkpoint
+        pruner.protect([]);
+        assert_eq!(pruner.prune(&storage, 20).unwrap().blocks_pruned, 2);
+        assert!(storage.get_block_by_height(8).unwrap().is_some());
         
-        // Stats should be 0 because we don't have height->hash index yet
-        // In a full implementation with height index, this would be > 0
-        assert_eq!(stats.blocks_pruned, 0);
+        let progress = pruner.progress();
+        assert_eq!((progress.runs, progress.blocks_pruned, progress.floor), (3, 7, 8));
     }
     
     #[test]
@@ -4,9 +4,12 @@
 
 use crate::crypto::Hash;
 use crate::hotstuff::types::Block;
-use std::collections::HashMap;
 use thiserror::Error;
 
+pub mod sparse_merkle;
+
+pub use sparse_merkle::{SparseMerkleLeaf, SparseMerkleProof, SparseMerkleTree};
+
 /// State machine errors
 #[derive(Error, Debug)]
 pub enum StateError {
@@ -26,11 +29,14 @@ pub enum StateError {
 pub type Result<T> = std::result::Result<T, StateError>;
 
 /// State represents the application state at a specific height
+///
+/// The data is authenticated by a sparse Merkle tree, so any key's value or
+/// absence can be proven against the state root.
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct State {
     pub root_hash: Hash,
     pub height: u64,
-    pub data: HashMap<Vec<u8>, Vec<u8>>,
+    pub data: SparseMerkleTree,
 }
 
 impl State {
@@ -39,7 +45,7 @@ impl State {
         Self {
             root_hash,
             height: 0,
-            data: HashMap::new(),
+            data: SparseMerkleTree::new(),
         }
     }
     
@@ -58,24 +64,43 @@ impl State {
         self.data.get(key)
     }
     
-    /// Compute the state hash
-    pub fn compute_hash(&self) -> Hash {
-        use crate::crypto::hash;
-        
-        // Sort keys for deterministic hashing
-        let mut keys: Vec<_> = self.data.keys().collect();
-        keys.sort();
-        
-        let mut data = Vec::new();
-        for key in keys {
-            data.extend_from_slice(key);
-            if let Some(value) = self.data.get(key) {
-                data.extend_from_slice(value);
-            }
+    /// Remove a key, returning its value
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.data.remove(key)
+    }
+    
+    /// Root of the sparse Merkle tree over the data
+    pub fn state_root(&self) -> Hash {
+        self.data.root()
+    }
+    
+    /// Proof of a key's value, or of its absence, against `state_root()`
+    pub fn prove(&self, key: &[u8]) -> SparseMerkleProof {
+        self.data.prove(key)
+    }
+    
+    /// Answer a `Query::GetProof` for `key`
+    pub fn proof_response(&self, key: &[u8]) -> QueryResponse {
+        QueryResponse::Proof {
+            value: self.get(key).cloned(),
+            state_root: self.state_root(),
+            proof: self.prove(key),
         }
-        data.extend_from_slice(&self.height.to_le_bytes());
-        
-        hash(&data)
+    }
+    
+    /// Compute the state hash: the state root bound to the height
+    pub fn compute_hash(&self) -> Hash {
+        Self::commitment(&self.state_root(), self.height)
+    }
+    
+    /// State hash of a state with `state_root` at `height`
+    ///
+    /// Light clients check a state root against a state hash with this
+    /// before verifying proofs against the root.
+    pub fn commitment(state_root: &Hash, height: u64) -> Hash {
+        let mut data = state_root.to_vec();
+        data.extend_from_slice(&height.to_le_bytes());
+        crate::crypto::hash(&data)
     }
 }
 
@@ -99,6 +124,9 @@ pub enum Query {
     
     /// Check if key exists
     Exists { key: Vec<u8> },
+    
+    /// Get a key's value with a proof against the state root
+    GetProof { key: Vec<u8> },
 }
 
 /// Query response
@@ -107,6 +135,11 @@ pub enum QueryResponse {
     Value(Option<Vec<u8>>),
     Hash(Hash),
     Exists(bool),
+    Proof {
+        value: Option<Vec<u8>>,
+        state_root: Hash,
+        proof: SparseMerkleProof,
+    },
 }
 
 /// State machine trait
@@ -123,6 +156,13 @@ pub trait StateMachine: Send + Sync {
     
     /// Rollback to the previous state
     fn rollback(&mut self) -> Result<()>;
+    
+    /// Base fee the next block must carry, stamped into proposals
+    ///
+    /// Applications without a fee market keep the default of zero.
+    fn next_base_fee(&self) -> u128 {
+        0
+    }
 }
 
 /// Simple in-memory state machine implementation
@@ -214,6 +254,7 @@ impl StateMachine for SimpleStateMachine {
                 let exists = self.current_state.get(key).is_some();
                 Ok(QueryResponse::Exists(exists))
             }
+            Query::GetProof { key } => Ok(self.current_state.proof_response(key)),
         }
     }
     
@@ -285,6 +326,27 @@ mod tests {
         assert_eq!(state1.compute_hash(), state2.compute_hash());
     }
     
+    #[test]
+    fn test_query_proof_verifies_against_state_hash() {
+        let mut sm = SimpleStateMachine::new();
+        let tx = vec![3, b'k', b'e', b'y', b'v', b'a', b'l'];
+        sm.apply_block(&create_test_block(1, vec![tx])).unwrap();
+        let state_hash = sm.commit().unwrap();
+        
+        let prove = |key: &[u8]| match sm.query(&Query::GetProof { key: key.to_vec() }).unwrap() {
+            QueryResponse::Proof { value, state_root, proof } => (value, state_root, proof),
+            _ => panic!("Expected proof"),
+        };
+        let (value, state_root, proof) = prove(b"key");
+        assert_eq!(State::commitment(&state_root, 1), state_hash);
+        assert!(proof.verify(&state_root, b"key", value.as_deref()));
+        assert_eq!(value.as_deref(), Some(&b"val"[..]));
+        
+        let (value, state_root, proof) = prove(b"other");
+        assert_eq!(value, None);
+        assert!(proof.verify_exclusion(&state_root, b"other"));
+    }
+    
     #[test]
     fn test_apply_block() {
         let mut sm = SimpleStateMachine::new();
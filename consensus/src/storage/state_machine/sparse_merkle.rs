@@ -0,0 +1,393 @@
+// Sparse Merkle tree authenticating the application state
+//
+// Keys are placed at the path given by the bits of their hash, in a tree of
+// depth 256. Subtrees holding a single leaf are collapsed into that leaf and
+// empty subtrees hash to a placeholder, so the tree only stores nodes on the
+// paths of present keys. Updates rehash just the path of the changed key.
+// Proofs carry the sibling hashes along a key's path: ending at the key's
+// leaf they prove inclusion, ending at an empty subtree or at another key's
+// leaf they prove exclusion.
+
+use crate::crypto::{hash, Hash};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// Hash of an empty subtree
+pub const PLACEHOLDER_HASH: Hash = Hash::new([0u8; 32]);
+
+/// Depth of the tree: one level per bit of a key hash
+const KEY_BITS: usize = 256;
+
+/// Domain separators keeping leaf and internal node hashes distinct
+const LEAF_PREFIX: u8 = 0x00;
+const INTERNAL_PREFIX: u8 = 0x01;
+
+/// Bit of `key_hash` choosing the child at `depth`; set means right
+fn bit(key_hash: &Hash, depth: usize) -> bool {
+    key_hash.as_bytes()[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// Number of leading bits two key hashes share
+fn common_prefix_bits(a: &Hash, b: &Hash) -> usize {
+    (0..KEY_BITS).take_while(|&depth| bit(a, depth) == bit(b, depth)).count()
+}
+
+fn internal_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut data = Vec::with_capacity(1 + 2 * 32);
+    data.push(INTERNAL_PREFIX);
+    data.extend_from_slice(left.as_bytes());
+    data.extend_from_slice(right.as_bytes());
+    hash(&data)
+}
+
+/// Leaf committing to a key and its value by their hashes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMerkleLeaf {
+    pub key_hash: Hash,
+    pub value_hash: Hash,
+}
+
+impl SparseMerkleLeaf {
+    /// Leaf for `key` holding `value`
+    pub fn new(key: &[u8], value: &[u8]) -> Self {
+        Self {
+            key_hash: hash(key),
+            value_hash: hash(value),
+        }
+    }
+
+    /// Hash of the leaf node
+    pub fn hash(&self) -> Hash {
+        let mut data = Vec::with_capacity(1 + 2 * 32);
+        data.push(LEAF_PREFIX);
+        data.extend_from_slice(self.key_hash.as_bytes());
+        data.extend_from_slice(self.value_hash.as_bytes());
+        hash(&data)
+    }
+}
+
+/// Proof that a key holds a value, or holds none, under a root
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMerkleProof {
+    /// Leaf where the key's path ends, if not at an empty subtree
+    pub leaf: Option<SparseMerkleLeaf>,
+    /// Sibling hashes along the key's path, from the root down
+    pub siblings: Vec<Hash>,
+}
+
+impl SparseMerkleProof {
+    /// Check that under `root`, `key` holds `value`, or is absent for
+    /// `None`
+    pub fn verify(&self, root: &Hash, key: &[u8], value: Option<&[u8]>) -> bool {
+        if self.siblings.len() > KEY_BITS {
+            return false;
+        }
+        let key_hash = hash(key);
+        match (value, &self.leaf) {
+            (Some(value), Some(leaf)) => {
+                if leaf.key_hash != key_hash || leaf.value_hash != hash(value) {
+                    return false;
+                }
+            }
+            (Some(_), None) => return false,
+            // Another key's leaf where our path ends proves ours is absent,
+            // provided it really sits on our path
+            (None, Some(leaf)) => {
+                if leaf.key_hash == key_hash || common_prefix_bits(&leaf.key_hash, &key_hash) < self.siblings.len() {
+                    return false;
+                }
+            }
+            (None, None) => {}
+        }
+
+        let mut current = self.leaf.as_ref().map_or(PLACEHOLDER_HASH, SparseMerkleLeaf::hash);
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            current = if bit(&key_hash, depth) {
+                internal_hash(sibling, &current)
+            } else {
+                internal_hash(&current, sibling)
+            };
+        }
+        current == *root
+    }
+
+    /// Check that `key` holds `value` under `root`
+    pub fn verify_inclusion(&self, root: &Hash, key: &[u8], value: &[u8]) -> bool {
+        self.verify(root, key, Some(value))
+    }
+
+    /// Check that `key` is absent under `root`
+    pub fn verify_exclusion(&self, root: &Hash, key: &[u8]) -> bool {
+        self.verify(root, key, None)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf(SparseMerkleLeaf),
+    /// Subtree with at least two leaves
+    Internal {
+        left: Box<Node>,
+        right: Box<Node>,
+        hash: Hash,
+    },
+}
+
+impl Node {
+    fn hash(&self) -> Hash {
+        match self {
+            Node::Empty => PLACEHOLDER_HASH,
+            Node::Leaf(leaf) => leaf.hash(),
+            Node::Internal { hash, .. } => *hash,
+        }
+    }
+
+    fn internal(left: Node, right: Node) -> Node {
+        let hash = internal_hash(&left.hash(), &right.hash());
+        Node::Internal {
+            left: Box::new(left),
+            right: Box::new(right),
+            hash,
+        }
+    }
+
+    /// Subtree at `depth` with `leaf` inserted or replaced
+    fn insert(self, depth: usize, leaf: SparseMerkleLeaf) -> Node {
+        match self {
+            Node::Empty => Node::Leaf(leaf),
+            Node::Leaf(existing) if existing.key_hash == leaf.key_hash => Node::Leaf(leaf),
+            Node::Leaf(existing) => Node::split(existing, leaf, depth),
+            Node::Internal { left, right, .. } => {
+                if bit(&leaf.key_hash, depth) {
+                    Node::internal(*left, right.insert(depth + 1, leaf))
+                } else {
+                    Node::internal(left.insert(depth + 1, leaf), *right)
+                }
+            }
+        }
+    }
+
+    /// Subtree at `depth` holding two leaves with different keys
+    fn split(a: SparseMerkleLeaf, b: SparseMerkleLeaf, depth: usize) -> Node {
+        match (bit(&a.key_hash, depth), bit(&b.key_hash, depth)) {
+            (false, true) => Node::internal(Node::Leaf(a), Node::Leaf(b)),
+            (true, false) => Node::internal(Node::Leaf(b), Node::Leaf(a)),
+            (false, false) => Node::internal(Node::split(a, b, depth + 1), Node::Empty),
+            (true, true) => Node::internal(Node::Empty, Node::split(a, b, depth + 1)),
+        }
+    }
+
+    /// Subtree at `depth` without the leaf for `key_hash`, which must be
+    /// present; subtrees left with a single leaf collapse into it
+    fn remove(self, depth: usize, key_hash: &Hash) -> Node {
+        match self {
+            Node::Leaf(leaf) if leaf.key_hash == *key_hash => Node::Empty,
+            Node::Internal { left, right, .. } => {
+                let (left, right) = if bit(key_hash, depth) {
+                    (*left, right.remove(depth + 1, key_hash))
+                } else {
+                    (left.remove(depth + 1, key_hash), *right)
+                };
+                match (left, right) {
+                    (Node::Empty, Node::Empty) => Node::Empty,
+                    (Node::Empty, leaf @ Node::Leaf(_)) | (leaf @ Node::Leaf(_), Node::Empty) => leaf,
+                    (left, right) => Node::internal(left, right),
+                }
+            }
+            node => node,
+        }
+    }
+}
+
+/// Key-value map authenticated by a sparse Merkle tree
+#[derive(Clone, Debug, Default)]
+pub struct SparseMerkleTree {
+    root: Node,
+    values: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl SparseMerkleTree {
+    /// Create an empty tree
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Root hash committing to every key and value
+    pub fn root(&self) -> Hash {
+        self.root.hash()
+    }
+
+    /// Get a value by key
+    pub fn get(&self, key: &[u8]) -> Option<&Vec<u8>> {
+        self.values.get(key)
+    }
+
+    /// Whether `key` is present
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.values.contains_key(key)
+    }
+
+    /// Set `key` to `value`, returning the previous value
+    pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) -> Option<Vec<u8>> {
+        let leaf = SparseMerkleLeaf::new(&key, &value);
+        self.root = std::mem::take(&mut self.root).insert(0, leaf);
+        self.values.insert(key, value)
+    }
+
+    /// Remove `key`, returning its value
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.values.remove(key)?;
+        self.root = std::mem::take(&mut self.root).remove(0, &hash(key));
+        Some(value)
+    }
+
+    /// Number of keys
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether the tree holds no keys
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Every key and value, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Vec<u8>)> {
+        self.values.iter()
+    }
+
+    /// Proof of `key`'s value, or of its absence, under the current root
+    pub fn prove(&self, key: &[u8]) -> SparseMerkleProof {
+        let key_hash = hash(key);
+        let mut siblings = Vec::new();
+        let mut node = &self.root;
+        loop {
+            match node {
+                Node::Internal { left, right, .. } => {
+                    if bit(&key_hash, siblings.len()) {
+                        siblings.push(left.hash());
+                        node = right;
+                    } else {
+                        siblings.push(right.hash());
+                        node = left;
+                    }
+                }
+                Node::Leaf(leaf) => {
+                    return SparseMerkleProof {
+                        leaf: Some(leaf.clone()),
+                        siblings,
+                    }
+                }
+                Node::Empty => return SparseMerkleProof { leaf: None, siblings },
+            }
+        }
+    }
+}
+
+impl PartialEq for SparseMerkleTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.root() == other.root()
+    }
+}
+
+impl FromIterator<(Vec<u8>, Vec<u8>)> for SparseMerkleTree {
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        for (key, value) in iter {
+            tree.insert(key, value);
+        }
+        tree
+    }
+}
+
+/// Serialized as the plain key-value map; the tree is rebuilt on load
+impl Serialize for SparseMerkleTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.values.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SparseMerkleTree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(HashMap::<Vec<u8>, Vec<u8>>::deserialize(deserializer)?.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(n: u8) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..n).map(|i| (vec![b'k', i], vec![b'v', i])).collect()
+    }
+
+    #[test]
+    fn test_root_is_incremental_and_order_independent() {
+        let forward: SparseMerkleTree = entries(50).into_iter().collect();
+        let backward: SparseMerkleTree = entries(50).into_iter().rev().collect();
+        assert_eq!(forward.root(), backward.root());
+        assert_ne!(forward.root(), PLACEHOLDER_HASH);
+
+        // Removing keys restores the root of the tree that never held them
+        let mut tree = forward.clone();
+        for (key, _) in entries(50).into_iter().skip(20) {
+            assert!(tree.remove(&key).is_some());
+        }
+        let prefix: SparseMerkleTree = entries(20).into_iter().collect();
+        assert_eq!(tree.root(), prefix.root());
+        assert_eq!(tree.remove(b"missing"), None);
+
+        // Updating a value changes the root; restoring it restores the root
+        let root = tree.root();
+        tree.insert(vec![b'k', 0], b"other".to_vec());
+        assert_ne!(tree.root(), root);
+        tree.insert(vec![b'k', 0], vec![b'v', 0]);
+        assert_eq!(tree.root(), root);
+
+        let bytes = bincode::serialize(&forward).unwrap();
+        let restored: SparseMerkleTree = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.root(), forward.root());
+
+        let mut emptied = prefix;
+        for (key, _) in entries(20) {
+            emptied.remove(&key);
+        }
+        assert_eq!(emptied.root(), PLACEHOLDER_HASH);
+    }
+
+    #[test]
+    fn test_inclusion_and_exclusion_proofs() {
+        let tree: SparseMerkleTree = entries(30).into_iter().collect();
+        let root = tree.root();
+
+        for (key, value) in entries(30) {
+            let proof = tree.prove(&key);
+            assert!(proof.verify_inclusion(&root, &key, &value));
+            assert!(!proof.verify_inclusion(&root, &key, b"wrong"));
+            assert!(!proof.verify_exclusion(&root, &key));
+        }
+
+        // Absent keys end at an empty subtree or at another key's leaf
+        let mut via_leaf = false;
+        for i in 0..30u8 {
+            let key = [b'x', i];
+            let proof = tree.prove(&key);
+            via_leaf |= proof.leaf.is_some();
+            assert!(proof.verify_exclusion(&root, &key));
+            assert!(!proof.verify_inclusion(&root, &key, b"v"));
+        }
+        assert!(via_leaf);
+
+        // Proofs are bound to the root and to their siblings
+        let (key, value) = &entries(1)[0];
+        let mut proof = tree.prove(key);
+        assert!(!proof.verify_inclusion(&PLACEHOLDER_HASH, key, value));
+        proof.siblings[0] = PLACEHOLDER_HASH;
+        assert!(!proof.verify_inclusion(&root, key, value));
+
+        let empty = SparseMerkleTree::new();
+        assert!(empty.prove(b"any").verify_exclusion(&PLACEHOLDER_HASH, b"any"));
+    }
+}
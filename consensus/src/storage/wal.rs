@@ -0,0 +1,131 @@
+// Write-ahead log for validator consensus state
+//
+// Votes, QC updates and view changes are durably appended before the
+// corresponding message leaves the node, so a restarted validator resumes
+// from its last view and never votes twice in the same view.
+
+use crate::crypto::Hash;
+use crate::hotstuff::types::{QuorumCertificate, ValidatorState};
+use serde::{Deserialize, Serialize};
+
+/// Entry in the consensus write-ahead log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WalEntry {
+    /// Entered a view
+    View(u64),
+    /// Voted for a block in a view
+    Vote { view: u64, block_hash: Hash },
+    /// Locked on a QC
+    LockedQc(QuorumCertificate),
+    /// New prepare QC
+    PrepareQc(QuorumCertificate),
+    /// Replaces every earlier entry after compaction
+    Snapshot(Box<WalState>),
+}
+
+/// Validator state recovered by replaying the log
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WalState {
+    /// Highest view entered
+    pub view: u64,
+    pub locked_qc: Option<QuorumCertificate>,
+    pub prepare_qc: Option<QuorumCertificate>,
+    /// Last vote cast as (view, block hash)
+    pub last_vote: Option<(u64, Hash)>,
+}
+
+impl WalState {
+    /// Replay log entries in order
+    pub fn replay<'a>(entries: impl IntoIterator<Item = &'a WalEntry>) -> Self {
+        let mut state = Self::default();
+        for entry in entries {
+            state.apply(entry);
+        }
+        state
+    }
+
+    /// Apply one entry, never moving back to an older view, vote or QC
+    pub fn apply(&mut self, entry: &WalEntry) {
+        match entry {
+            WalEntry::View(view) => self.view = self.view.max(*view),
+            WalEntry::Vote { view, block_hash } => {
+                if self.last_vote.is_none_or(|(v, _)| *view >= v) {
+                    self.last_vote = Some((*view, *block_hash));
+                }
+                self.view = self.view.max(*view);
+            }
+            WalEntry::LockedQc(qc) => {
+                if self.locked_qc.as_ref().is_none_or(|ours| qc.view >= ours.view) {
+                    self.locked_qc = Some(qc.clone());
+                }
+            }
+            WalEntry::PrepareQc(qc) => {
+                if self.prepare_qc.as_ref().is_none_or(|ours| qc.view >= ours.view) {
+                    self.prepare_qc = Some(qc.clone());
+                }
+            }
+            WalEntry::Snapshot(snapshot) => *self = snapshot.as_ref().clone(),
+        }
+    }
+
+    /// Whether voting for `block_hash` in `view` would conflict with a
+    /// vote already cast
+    pub fn conflicts(&self, view: u64, block_hash: &Hash) -> bool {
+        self.last_vote
+            .is_some_and(|(v, hash)| view < v || (view == v && hash != *block_hash))
+    }
+
+    /// Restore the recovered view and QCs into `state`, keeping whichever
+    /// is newer
+    pub fn restore_into(&self, state: &mut ValidatorState) {
+        state.view_number = state.view_number.max(self.view);
+        if let Some(qc) = &self.locked_qc {
+            if state.locked_qc.as_ref().is_none_or(|ours| qc.view > ours.view) {
+                state.update_locked_qc(qc.clone());
+            }
+        }
+        if let Some(qc) = &self.prepare_qc {
+            if state.prepare_qc.as_ref().is_none_or(|ours| qc.view > ours.view) {
+                state.update_prepare_qc(qc.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{threshold_sign, BLSKeyPair};
+    use crate::hotstuff::types::MessageType;
+
+    fn qc(view: u64) -> QuorumCertificate {
+        let signature = threshold_sign(&BLSKeyPair::generate().secret_key, b"test").signature;
+        QuorumCertificate::new(MessageType::PreCommit, Hash::new([view as u8; 32]), view, signature)
+    }
+
+    #[test]
+    fn test_replay_keeps_latest_state() {
+        let entries = vec![
+            WalEntry::View(3),
+            WalEntry::Vote { view: 3, block_hash: Hash::new([1; 32]) },
+            WalEntry::LockedQc(qc(2)),
+            WalEntry::LockedQc(qc(1)),
+            WalEntry::View(2),
+        ];
+        let state = WalState::replay(&entries);
+        assert_eq!(state.view, 3);
+        assert_eq!(state.locked_qc.as_ref().map(|qc| qc.view), Some(2));
+        assert!(state.prepare_qc.is_none());
+
+        // Re-voting the same block is fine; another block in the view is not
+        assert!(!state.conflicts(3, &Hash::new([1; 32])));
+        assert!(state.conflicts(3, &Hash::new([2; 32])));
+        assert!(state.conflicts(2, &Hash::new([1; 32])));
+        assert!(!state.conflicts(4, &Hash::new([2; 32])));
+
+        // A snapshot replaces earlier entries
+        let mut entries = entries;
+        entries.push(WalEntry::Snapshot(Box::new(WalState { view: 7, ..Default::default() })));
+        assert_eq!(WalState::replay(&entries), WalState { view: 7, ..Default::default() });
+    }
+}
@@ -4,10 +4,12 @@
 /// - Requesting missing blocks from peers
 /// - Serving blocks to peers
 /// - Detecting when we're behind
+/// - Snapshot sync from a verified checkpoint for new validators
 /// - Fast catch-up synchronization
 
 pub mod types;
 
+use crate::checkpoint::{CheckpointError, CheckpointManager};
 use crate::crypto::Hash;
 use crate::hotstuff::types::Block;
 use crate::storage::{Storage, StorageError};
@@ -17,7 +19,9 @@ use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::RwLock;
 
-pub use types::{SyncRequest, SyncResponse, BlockAnnouncement, HeightStatus};
+pub use types::{
+    SyncRequest, SyncResponse, BlockAnnouncement, HeightStatus, CheckpointProof, COMMIT_CHAIN_LENGTH,
+};
 
 /// Sync errors
 #[derive(Error, Debug)]
@@ -39,6 +43,12 @@ pub enum SyncError {
     
     #[error("Sync already in progress")]
     SyncInProgress,
+    
+    #[error("Checkpoint error: {0}")]
+    CheckpointError(#[from] CheckpointError),
+    
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
 }
 
 pub type Result<T> = std::result::Result<T, SyncError>;
@@ -57,6 +67,10 @@ pub struct SyncConfig {
     
     /// Maximum concurrent sync requests
     pub max_concurrent_requests: usize,
+    
+    /// Blocks behind a peer beyond which snapshot sync is used instead of
+    /// replaying every block
+    pub snapshot_threshold: u64,
 }
 
 impl Default for SyncConfig {
@@ -66,6 +80,7 @@ impl Default for SyncConfig {
             request_timeout: Duration::from_secs(10),
             sync_check_interval: Duration::from_secs(5),
             max_concurrent_requests: 3,
+            snapshot_threshold: 1000,
         }
     }
 }
@@ -203,35 +218,77 @@ impl SyncManager {
     }
     
     /// Serve blocks to a peer
+    /// 
+    /// Returns the stored blocks in the requested range, capped at
+    /// `max_blocks_per_request`; `has_more` tells the peer to ask for the rest.
     pub async fn serve_blocks(&self, request: &SyncRequest) -> Result<SyncResponse> {
-        let mut blocks = Vec::new();
-        
         // Cap the number of blocks we serve
         let max_height = std::cmp::min(
             request.to_height,
             request.from_height + self.config.max_blocks_per_request - 1
         );
         
-        // Fetch blocks from storage
-        for height in request.from_height..=max_height {
-            if let Some(_state) = self.storage.get_state(height)? {
-                // Get block hash from state and fetch block
-                // For now, we'll need to iterate through blocks
-                // This is a simplified implementation
-                if let Some(latest) = self.storage.get_latest_block()? {
-                    if latest.height >= height {
-                        // Try to get this specific block
-                        // In a real implementation, we'd have height->hash index
-                        blocks.push(latest); // Placeholder
-                        break;
-                    }
-                }
+        let blocks = self.storage.get_block_range(request.from_height, max_height)?;
+        let has_more = max_height < request.to_height;
+        
+        Ok(SyncResponse::new(request.request_id, blocks, has_more))
+    }
+    
+    /// Check if we are far enough behind a peer to sync from a snapshot
+    pub async fn needs_snapshot_sync(&self, peer_height: u64) -> Result<bool> {
+        let local_height = self.local_height().await?;
+        Ok(peer_height.saturating_sub(local_height) > self.config.snapshot_threshold)
+    }
+    
+    /// Serve the latest checkpoint whose block is provably committed
+    /// 
+    /// A checkpoint is served once `COMMIT_CHAIN_LENGTH` descendants of its
+    /// block are stored, so the receiver can check the QCs committing it.
+    pub async fn serve_snapshot(&self, checkpoints: &CheckpointManager) -> Result<Option<CheckpointProof>> {
+        for metadata in checkpoints.list_checkpoints().await.iter().rev() {
+            let height = metadata.height;
+            let Some(checkpoint) = checkpoints.get_checkpoint(height).await? else {
+                continue;
+            };
+            let Some(block) = self.storage.get_block_by_height(height)? else {
+                continue;
+            };
+            let chain = self.storage.get_block_range(height + 1, height + COMMIT_CHAIN_LENGTH as u64)?;
+            if chain.len() == COMMIT_CHAIN_LENGTH {
+                return Ok(Some(CheckpointProof { checkpoint, block, chain }));
             }
         }
+        Ok(None)
+    }
+    
+    /// Verify and install a snapshot received from a peer
+    /// 
+    /// The proof's QCs are checked against the validator sets stored for
+    /// the heights they certify, the checkpoint state is restored through
+    /// the checkpoint manager and the proof's blocks are stored. Returns the
+    /// height to resume normal block sync from.
+    pub async fn install_snapshot(
+        &self,
+        proof: &CheckpointProof,
+        checkpoints: &CheckpointManager,
+    ) -> Result<u64> {
+        let local_height = self.local_height().await?;
+        if proof.checkpoint.height <= local_height {
+            return Err(SyncError::InvalidSnapshot(format!(
+                "Checkpoint at height {} is not ahead of local height {}",
+                proof.checkpoint.height, local_height
+            )));
+        }
+        let validators = self.storage.load_validator_history()?;
+        proof.verify(&validators).map_err(SyncError::InvalidSnapshot)?;
         
-        let has_more = max_height < request.to_height;
+        checkpoints.restore_from_checkpoint(&proof.checkpoint).await?;
+        self.storage.store_block(&proof.block)?;
+        for block in &proof.chain {
+            self.storage.store_block(block)?;
+        }
         
-        Ok(SyncResponse::new(request.request_id, blocks, has_more))
+        Ok(proof.tip_height() + 1)
     }
     
     /// Sync to target height
@@ -311,6 +368,7 @@ pub struct SyncStats {
 mod tests {
     use super::*;
     use crate::crypto::bls::BLSKeyPair;
+    use crate::light_client::ValidatorSet;
     
     fn create_test_block(height: u64) -> Block {
         let keypair = BLSKeyPair::generate();
@@ -392,6 +450,108 @@ mod tests {
         assert_eq!(request.to_height, 10);
     }
     
+    #[tokio::test]
+    async fn test_serve_blocks_range() {
+        let storage = Arc::new(Storage::new_temp().unwrap());
+        for height in 1..=5 {
+            storage.store_block(&create_test_block(height)).unwrap();
+        }
+        let config = SyncConfig { max_blocks_per_request: 3, ..Default::default() };
+        let sync = SyncManager::new(storage, config);
+        
+        let request = SyncRequest::new(libp2p::PeerId::random(), 2, 5, 1);
+        let response = sync.serve_blocks(&request).await.unwrap();
+        let heights: Vec<u64> = response.blocks.iter().map(|b| b.height).collect();
+        assert_eq!(heights, vec![2, 3, 4]);
+        assert!(response.has_more);
+    }
+    
+    /// Blocks at heights 1..=tip, each justified by a commit QC from validators 0-2
+    fn certified_chain(keys: &[BLSKeyPair], tip: u64) -> Vec<Block> {
+        use crate::crypto::{threshold_combine, threshold_sign};
+        use crate::hotstuff::types::{vote_message, MessageType, QuorumCertificate};
+        
+        let mut blocks: Vec<Block> = Vec::new();
+        for height in 1..=tip {
+            let justify = blocks.last().map(|parent| {
+                let data = vote_message(&MessageType::Commit, &parent.hash(), parent.view);
+                let partials: Vec<_> = keys[..3].iter().map(|k| threshold_sign(&k.secret_key, &data)).collect();
+                QuorumCertificate::new(MessageType::Commit, parent.hash(), parent.view, threshold_combine(&data, &partials, 3).unwrap())
+                    .with_signers(vec![0, 1, 2])
+            });
+            let parent = blocks.last().map_or(Hash::genesis(), |b| b.hash());
+            blocks.push(Block::new(parent, height, height, justify, vec![], keys[0].public_key.clone()));
+        }
+        blocks
+    }
+    
+    #[tokio::test]
+    async fn test_snapshot_sync_installs_verified_checkpoint() {
+        use crate::storage::State;
+        
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let validators = ValidatorSet::new(0, keys.iter().map(|k| k.public_key.clone()));
+        let rotated = ValidatorSet::new(1, (4..8).map(|id| BLSKeyPair::with_id(id).public_key));
+        let blocks = certified_chain(&keys, 8);
+        
+        // Serving node checkpointed height 5 and has blocks up to 8
+        let server_storage = Arc::new(Storage::new_temp().unwrap());
+        for block in &blocks {
+            server_storage.store_block(block).unwrap();
+        }
+        let mut state = State::new(Hash::genesis());
+        state.height = 5;
+        state.set(b"balance".to_vec(), b"42".to_vec());
+        state.root_hash = state.compute_hash();
+        let server_checkpoints = CheckpointManager::new_default(server_storage.clone());
+        server_checkpoints.create_checkpoint(5, 5, state, blocks[4].hash()).await.unwrap();
+        let server = SyncManager::new_default(server_storage);
+        let proof = server.serve_snapshot(&server_checkpoints).await.unwrap().unwrap();
+        assert_eq!(proof.tip_height(), 8);
+        
+        // QCs are checked against the set active at the height they certify,
+        // so a rotation before the checkpoint's commit chain rejects it
+        let stale_storage = Arc::new(Storage::new_temp().unwrap());
+        stale_storage.store_validator_set(0, &validators).unwrap();
+        stale_storage.store_validator_set(7, &rotated).unwrap();
+        let stale = SyncManager::new_default(stale_storage.clone());
+        assert!(matches!(
+            stale.install_snapshot(&proof, &CheckpointManager::new_default(stale_storage)).await,
+            Err(SyncError::InvalidSnapshot(_))
+        ));
+        
+        // A tampered QC is rejected before anything is installed
+        let client_storage = Arc::new(Storage::new_temp().unwrap());
+        client_storage.store_validator_set(0, &validators).unwrap();
+        client_storage.store_validator_set(9, &rotated).unwrap();
+        let client_checkpoints = CheckpointManager::new_default(client_storage.clone());
+        let client = SyncManager::new_default(client_storage.clone());
+        let mut forged = proof.clone();
+        forged.chain[1].justify.as_mut().unwrap().signers = vec![0, 1, 3];
+        assert!(matches!(
+            client.install_snapshot(&forged, &client_checkpoints).await,
+            Err(SyncError::InvalidSnapshot(_))
+        ));
+        let mut prepare = proof.clone();
+        prepare.chain[1].justify.as_mut().unwrap().msg_type = crate::hotstuff::types::MessageType::Prepare;
+        assert!(matches!(
+            client.install_snapshot(&prepare, &client_checkpoints).await,
+            Err(SyncError::InvalidSnapshot(_))
+        ));
+        assert_eq!(client.local_height().await.unwrap(), 0);
+        
+        // The verified snapshot is installed and block sync resumes after it
+        let next = client.install_snapshot(&proof, &client_checkpoints).await.unwrap();
+        assert_eq!(next, 9);
+        assert_eq!(client.local_height().await.unwrap(), 8);
+        let restored = client_storage.get_state(5).unwrap().unwrap();
+        assert_eq!(restored.get(b"balance"), Some(&b"42".to_vec()));
+        assert_eq!(client_checkpoints.get_latest_checkpoint().await.unwrap().unwrap().block_hash, blocks[4].hash());
+        
+        let request = client.request_blocks(next, 20).await.unwrap();
+        assert_eq!(request.from_height, 9);
+    }
+    
     #[tokio::test]
     async fn test_sync_in_progress_error() {
         let storage = Arc::new(Storage::new_temp().unwrap());
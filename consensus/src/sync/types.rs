@@ -2,10 +2,15 @@
 /// 
 /// Defines messages and data structures for block synchronization
 
+use crate::checkpoint::Checkpoint;
 use crate::crypto::Hash;
-use crate::hotstuff::types::Block;
+use crate::hotstuff::types::{Block, MessageType};
+use crate::light_client::ValidatorHistory;
 use libp2p::PeerId;
 
+/// Blocks certifying a checkpoint block under the three-chain commit rule
+pub const COMMIT_CHAIN_LENGTH: usize = 3;
+
 /// Sync request message
 #[derive(Clone, Debug)]
 pub struct SyncRequest {
@@ -106,6 +111,64 @@ impl HeightStatus {
     }
 }
 
+/// Checkpoint with the chain of QCs proving its block was committed
+#[derive(Clone, Debug)]
+pub struct CheckpointProof {
+    /// Checkpointed state
+    pub checkpoint: Checkpoint,
+    
+    /// Block at the checkpoint height
+    pub block: Block,
+    
+    /// Descendants of `block` in height order, each justified by a QC on
+    /// its predecessor
+    pub chain: Vec<Block>,
+}
+
+impl CheckpointProof {
+    /// Verify the proof against the validator history
+    /// 
+    /// Checks that the checkpoint state matches its hash and the block,
+    /// and that the checkpoint block is followed by a chain of
+    /// `COMMIT_CHAIN_LENGTH` blocks whose commit QCs each carry a quorum
+    /// of the validator set active at the height they certify.
+    pub fn verify(&self, validators: &ValidatorHistory) -> Result<(), String> {
+        if !self.checkpoint.verify() {
+            return Err("Checkpoint state hash mismatch".to_string());
+        }
+        if self.checkpoint.block_hash != self.block.hash() || self.checkpoint.height != self.block.height {
+            return Err(format!("Checkpoint does not match block at height {}", self.block.height));
+        }
+        if self.chain.len() < COMMIT_CHAIN_LENGTH {
+            return Err(format!(
+                "Proof chain has {} blocks, need {}",
+                self.chain.len(),
+                COMMIT_CHAIN_LENGTH
+            ));
+        }
+        
+        let mut parent = &self.block;
+        for block in &self.chain {
+            let qc = block.justify.as_ref()
+                .ok_or_else(|| format!("Block at height {} has no QC", block.height))?;
+            if qc.msg_type != MessageType::Commit {
+                return Err(format!("Block at height {} carries a {:?} QC, not a commit QC", block.height, qc.msg_type));
+            }
+            if block.parent != parent.hash() || block.height != parent.height + 1 || qc.block_hash != parent.hash() {
+                return Err(format!("Block at height {} does not extend the proof chain", block.height));
+            }
+            validators.verify_qc_at(parent.height, qc)?;
+            parent = block;
+        }
+        Ok(())
+    }
+    
+    /// Height of the last block in the proof
+    pub fn tip_height(&self) -> u64 {
+        self.chain.last().map_or(self.block.height, |b| b.height)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,393 @@
+// Watchdog for stuck subsystems
+//
+// Long-running subsystems (the consensus loop, the executor, the network
+// event loop, a matching dispatcher, ...) hold a Heartbeat they beat as
+// they make progress, along with their current phase and queue depths. A
+// subsystem is stalled when it has been busy for longer than the stall
+// timeout without beating; one waiting for work is marked idle and never
+// stalls. The watchdog then captures diagnostics (the phase, queue depths
+// and beat counts of every subsystem, plus the runtime's task counts) and,
+// if the subsystem registered a restart hook, restarts it. Restarts are
+// capped: a subsystem that keeps stalling is reported, not restarted in a
+// loop.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Watchdog thresholds
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    /// How often subsystems are checked
+    pub check_interval: Duration,
+    /// Time busy without a beat before a subsystem is stalled
+    pub stall_timeout: Duration,
+    /// Time a restart hook may take before the attempt counts as failed
+    pub restart_timeout: Duration,
+    /// Restarts attempted per subsystem before it is left stalled
+    pub max_restarts: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(1),
+            stall_timeout: Duration::from_secs(30),
+            restart_timeout: Duration::from_secs(10),
+            max_restarts: 3,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pulse {
+    last_beat: Instant,
+    beats: u64,
+    phase: String,
+    idle: bool,
+    queue_depths: BTreeMap<String, usize>,
+}
+
+/// Progress signal of one subsystem (cheap to clone and beat)
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    pulse: Arc<Mutex<Pulse>>,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self {
+            pulse: Arc::new(Mutex::new(Pulse {
+                last_beat: Instant::now(),
+                beats: 0,
+                phase: "starting".to_string(),
+                idle: false,
+                queue_depths: BTreeMap::new(),
+            })),
+        }
+    }
+
+    /// Record progress
+    pub fn beat(&self) {
+        let mut pulse = self.pulse.lock().unwrap();
+        pulse.last_beat = Instant::now();
+        pulse.beats += 1;
+    }
+
+    /// Start work expected to finish (or beat) within the stall timeout
+    pub fn busy(&self, phase: impl Into<String>) {
+        let mut pulse = self.pulse.lock().unwrap();
+        pulse.phase = phase.into();
+        pulse.idle = false;
+        pulse.last_beat = Instant::now();
+        pulse.beats += 1;
+    }
+
+    /// Wait for work: an idle subsystem is never stalled
+    pub fn idle(&self) {
+        let mut pulse = self.pulse.lock().unwrap();
+        pulse.phase = "idle".to_string();
+        pulse.idle = true;
+        pulse.last_beat = Instant::now();
+        pulse.beats += 1;
+    }
+
+    /// Report the depth of one of the subsystem's queues
+    pub fn set_queue_depth(&self, queue: &str, depth: usize) {
+        self.pulse.lock().unwrap().queue_depths.insert(queue.to_string(), depth);
+    }
+
+    /// Restart the stall clock without counting progress
+    fn reset(&self, now: Instant) {
+        self.pulse.lock().unwrap().last_beat = now;
+    }
+}
+
+/// State of one subsystem when diagnostics were captured
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubsystemSnapshot {
+    pub name: String,
+    pub phase: String,
+    pub idle: bool,
+    pub beats: u64,
+    pub since_last_beat: Duration,
+    pub queue_depths: BTreeMap<String, usize>,
+    pub restarts: u32,
+}
+
+/// Diagnostics captured when a subsystem stalls
+#[derive(Debug, Clone)]
+pub struct Diagnostics {
+    /// The stalled subsystem
+    pub subsystem: String,
+    /// Every registered subsystem, the stalled one included
+    pub subsystems: Vec<SubsystemSnapshot>,
+    /// Tasks alive on the tokio runtime, when captured on one
+    pub runtime_tasks: Option<usize>,
+    /// Tasks queued on the runtime's global queue, when captured on one
+    pub runtime_queue_depth: Option<usize>,
+}
+
+/// What a watchdog check found and did
+#[derive(Debug, Clone)]
+pub enum WatchdogEvent {
+    Stalled(Diagnostics),
+    Restarted { subsystem: String, attempt: u32 },
+    RestartFailed { subsystem: String, attempt: u32, error: String },
+    /// The restart budget is used up; the subsystem stays stalled
+    GaveUp { subsystem: String, restarts: u32 },
+}
+
+type RestartFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+type RestartHook = Arc<dyn Fn() -> RestartFuture + Send + Sync>;
+
+struct Subsystem {
+    name: String,
+    heartbeat: Heartbeat,
+    restart: Option<RestartHook>,
+    restarts: u32,
+    gave_up: bool,
+}
+
+impl Subsystem {
+    fn snapshot(&self, now: Instant) -> SubsystemSnapshot {
+        let pulse = self.heartbeat.pulse.lock().unwrap();
+        SubsystemSnapshot {
+            name: self.name.clone(),
+            phase: pulse.phase.clone(),
+            idle: pulse.idle,
+            beats: pulse.beats,
+            since_last_beat: now.saturating_duration_since(pulse.last_beat),
+            queue_depths: pulse.queue_depths.clone(),
+            restarts: self.restarts,
+        }
+    }
+}
+
+/// Monitors subsystem heartbeats and restarts stalled subsystems
+///
+/// Clones share the registered subsystems.
+#[derive(Clone)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    subsystems: Arc<Mutex<Vec<Subsystem>>>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self { config, subsystems: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    /// Monitor a subsystem that is reported but never restarted
+    pub fn register(&self, name: impl Into<String>) -> Heartbeat {
+        self.add(name.into(), None)
+    }
+
+    /// Monitor a subsystem, calling `restart` when it stalls
+    pub fn register_restartable<F, Fut>(&self, name: impl Into<String>, restart: F) -> Heartbeat
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let hook: RestartHook = Arc::new(move || Box::pin(restart()) as RestartFuture);
+        self.add(name.into(), Some(hook))
+    }
+
+    fn add(&self, name: String, restart: Option<RestartHook>) -> Heartbeat {
+        let heartbeat = Heartbeat::new();
+        self.subsystems.lock().unwrap().push(Subsystem {
+            name,
+            heartbeat: heartbeat.clone(),
+            restart,
+            restarts: 0,
+            gave_up: false,
+        });
+        heartbeat
+    }
+
+    /// Current state of every subsystem
+    pub fn snapshot(&self) -> Vec<SubsystemSnapshot> {
+        let now = Instant::now();
+        self.subsystems.lock().unwrap().iter().map(|s| s.snapshot(now)).collect()
+    }
+
+    /// Check every subsystem once, restarting stalled ones
+    pub async fn check(&self) -> Vec<WatchdogEvent> {
+        let now = Instant::now();
+        let stalled: Vec<usize> = {
+            let subsystems = self.subsystems.lock().unwrap();
+            subsystems
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| {
+                    let pulse = s.heartbeat.pulse.lock().unwrap();
+                    !pulse.idle && now.saturating_duration_since(pulse.last_beat) >= self.config.stall_timeout
+                })
+                .map(|(index, _)| index)
+                .collect()
+        };
+
+        let mut events = Vec::new();
+        for index in stalled {
+            let (name, heartbeat, attempt) = {
+                let mut subsystems = self.subsystems.lock().unwrap();
+                events.push(WatchdogEvent::Stalled(Self::diagnostics(&subsystems, index, now)));
+                let subsystem = &mut subsystems[index];
+                // Give the subsystem a full timeout before the next report
+                subsystem.heartbeat.reset(now);
+                let attempt = match subsystem.restart.clone() {
+                    Some(_) if subsystem.restarts >= self.config.max_restarts => {
+                        if !subsystem.gave_up {
+                            subsystem.gave_up = true;
+                            events.push(WatchdogEvent::GaveUp {
+                                subsystem: subsystem.name.clone(),
+                                restarts: subsystem.restarts,
+                            });
+                        }
+                        None
+                    }
+                    Some(hook) => {
+                        subsystem.restarts += 1;
+                        Some((hook, subsystem.restarts))
+                    }
+                    None => None,
+                };
+                (subsystem.name.clone(), subsystem.heartbeat.clone(), attempt)
+            };
+
+            // The hook runs without the registry locked, and is bounded in
+            // time in case it waits on whatever the subsystem is stuck on
+            let Some((hook, attempt)) = attempt else { continue };
+            match tokio::time::timeout(self.config.restart_timeout, hook()).await {
+                Ok(Ok(())) => {
+                    heartbeat.busy("restarted");
+                    events.push(WatchdogEvent::Restarted { subsystem: name, attempt });
+                }
+                Ok(Err(error)) => events.push(WatchdogEvent::RestartFailed { subsystem: name, attempt, error }),
+                Err(_) => events.push(WatchdogEvent::RestartFailed {
+                    subsystem: name,
+                    attempt,
+                    error: "restart timed out".to_string(),
+                }),
+            }
+        }
+        events
+    }
+
+    fn diagnostics(subsystems: &[Subsystem], stalled: usize, now: Instant) -> Diagnostics {
+        let metrics = tokio::runtime::Handle::try_current().ok().map(|handle| handle.metrics());
+        Diagnostics {
+            subsystem: subsystems[stalled].name.clone(),
+            subsystems: subsystems.iter().map(|s| s.snapshot(now)).collect(),
+            runtime_tasks: metrics.as_ref().map(|m| m.num_alive_tasks()),
+            runtime_queue_depth: metrics.as_ref().map(|m| m.global_queue_depth()),
+        }
+    }
+
+    /// Check subsystems every `check_interval` on a background task,
+    /// logging what is found
+    pub fn spawn(&self) -> tokio::task::JoinHandle<()> {
+        let watchdog = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(watchdog.config.check_interval);
+            loop {
+                interval.tick().await;
+                for event in watchdog.check().await {
+                    match event {
+                        WatchdogEvent::Stalled(diagnostics) => {
+                            error!("Subsystem {} stalled: {:?}", diagnostics.subsystem, diagnostics)
+                        }
+                        WatchdogEvent::Restarted { subsystem, attempt } => {
+                            info!("Restarted subsystem {} (attempt {})", subsystem, attempt)
+                        }
+                        WatchdogEvent::RestartFailed { subsystem, attempt, error } => {
+                            warn!("Restart {} of subsystem {} failed: {}", attempt, subsystem, error)
+                        }
+                        WatchdogEvent::GaveUp { subsystem, restarts } => {
+                            error!("Subsystem {} still stalled after {} restarts", subsystem, restarts)
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config() -> WatchdogConfig {
+        WatchdogConfig {
+            stall_timeout: Duration::from_millis(20),
+            max_restarts: 1,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_subsystem_is_diagnosed_and_restarted() {
+        let watchdog = Watchdog::new(config());
+        let restarts = Arc::new(AtomicU32::new(0));
+        let counter = restarts.clone();
+        let consensus = watchdog.register_restartable("consensus", move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+        let executor = watchdog.register("executor");
+        executor.idle();
+        consensus.busy("processing block 7");
+        consensus.set_queue_depth("votes", 12);
+
+        // Beating keeps a busy subsystem healthy; idle ones never stall
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        consensus.beat();
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        assert!(watchdog.check().await.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        let events = watchdog.check().await;
+        let WatchdogEvent::Stalled(diagnostics) = &events[0] else { panic!("{:?}", events) };
+        assert_eq!(diagnostics.subsystem, "consensus");
+        let stalled = &diagnostics.subsystems[0];
+        assert_eq!(stalled.phase, "processing block 7");
+        assert_eq!(stalled.queue_depths["votes"], 12);
+        assert!(diagnostics.subsystems[1].idle);
+        assert!(diagnostics.runtime_tasks.is_some());
+        assert!(matches!(&events[1], WatchdogEvent::Restarted { attempt: 1, .. }));
+        assert_eq!(restarts.load(Ordering::SeqCst), 1);
+        assert_eq!(watchdog.snapshot()[0].phase, "restarted");
+
+        // Out of restarts: reported once, then only diagnosed
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        let events = watchdog.check().await;
+        assert!(matches!(&events[1], WatchdogEvent::GaveUp { restarts: 1, .. }));
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert_eq!(watchdog.check().await.len(), 1);
+        assert_eq!(restarts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hung_restart_times_out() {
+        let watchdog = Watchdog::new(WatchdogConfig {
+            restart_timeout: Duration::from_millis(10),
+            ..config()
+        });
+        let _network = watchdog.register_restartable("network", std::future::pending::<Result<(), String>>);
+
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        let events = watchdog.check().await;
+        assert!(matches!(&events[1], WatchdogEvent::RestartFailed { error, .. } if error == "restart timed out"));
+    }
+}
@@ -0,0 +1,253 @@
+// Order book matching benchmarks
+//
+// To measure a change, save a criterion baseline on the tree it is based
+// on and compare the change against it:
+//
+//   cargo bench -p core --bench matching -- --save-baseline before
+//   cargo bench -p core --bench matching -- --baseline before
+//
+// The arena order book was compared this way with this file copied onto
+// the previous tree, which predates it. Runs were interleaved, three each.
+
+use alloy_primitives::{Address, U256};
+use core::matching::MatchingEngine;
+use core::orderbook::OrderBook;
+use core::types::{AssetId, Price, Side, Size};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+const LEVELS: u64 = 100;
+const ORDERS_PER_LEVEL: u64 = 50;
+
+fn trader(i: u64) -> Address {
+    Address::from_word(U256::from(i % 64 + 1).into())
+}
+
+/// Book with `LEVELS` ask levels of `ORDERS_PER_LEVEL` orders each
+fn deep_book() -> OrderBook {
+    let mut book = OrderBook::new(AssetId(1));
+    for level in 0..LEVELS {
+        for i in 0..ORDERS_PER_LEVEL {
+            book.add_limit_order(
+                trader(i),
+                Side::Ask,
+                Price(1_000_000 + level * 1_000),
+                Size(U256::from(10)),
+                level * ORDERS_PER_LEVEL + i,
+            );
+        }
+    }
+    book
+}
+
+fn bench_add_cancel(c: &mut Criterion) {
+    // Long-lived book: steady-state add/cancel churn
+    let mut book = OrderBook::new(AssetId(1));
+    c.bench_function("add_cancel_5000", |b| {
+        b.iter(|| {
+            let mut ids = Vec::with_capacity((LEVELS * ORDERS_PER_LEVEL) as usize);
+            for level in 0..LEVELS {
+                for i in 0..ORDERS_PER_LEVEL {
+                    ids.push(book.add_limit_order(
+                        trader(i),
+                        Side::Bid,
+                        Price(1_000_000 - level * 1_000),
+                        Size(U256::from(10)),
+                        i,
+                    ));
+                }
+            }
+            // Cancel from the back of each queue (worst case for FIFO scans)
+            for id in ids.into_iter().rev() {
+                book.cancel_order(id).unwrap();
+            }
+            black_box(book.best_bid())
+        })
+    });
+}
+
+fn bench_market_sweep(c: &mut Criterion) {
+    c.bench_function("market_sweep_5000", |b| {
+        b.iter_batched(
+            deep_book,
+            |mut book| {
+                // Each order consumes one full level
+                for i in 0..LEVELS {
+                    let fills = MatchingEngine::execute_market_order(
+                        &mut book,
+                        trader(i + 100),
+                        Side::Bid,
+                        Size(U256::from(10 * ORDERS_PER_LEVEL)),
+                        i,
+                    )
+                    .unwrap();
+                    black_box(fills);
+                }
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_crossing_limits(c: &mut Criterion) {
+    c.bench_function("crossing_limit_small_fills", |b| {
+        b.iter_batched(
+            deep_book,
+            |mut book| {
+                // Small takers nibbling the top of book, plus resting bids
+                for i in 0..5_000u64 {
+                    let price = if i % 2 == 0 { Price(1_200_000) } else { Price(900_000) };
+                    let result = MatchingEngine::execute_limit_order(
+                        &mut book,
+                        trader(i + 100),
+                        Side::Bid,
+                        price,
+                        Size(U256::from(5)),
+                        i,
+                    )
+                    .unwrap();
+                    black_box(result);
+                }
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+/// Two-sided book around a mid of 1_000_000, `orders_per_level` orders of
+/// size 10 on each of `levels` price levels per side
+#[derive(Clone, Copy)]
+struct BookShape {
+    name: &'static str,
+    levels: u64,
+    orders_per_level: u64,
+}
+
+/// Representative shapes, all holding 10_000 resting orders
+const SHAPES: [BookShape; 3] = [
+    // Liquid major: few levels with long queues
+    BookShape { name: "tight", levels: 10, orders_per_level: 500 },
+    BookShape { name: "balanced", levels: 100, orders_per_level: 50 },
+    // Illiquid long tail: one or two orders per tick
+    BookShape { name: "sparse", levels: 2_500, orders_per_level: 2 },
+];
+
+const MID: u64 = 1_000_000;
+const TICK: u64 = 100;
+
+impl BookShape {
+    fn build(self) -> OrderBook {
+        let mut book = OrderBook::new(AssetId(1));
+        for level in 1..=self.levels {
+            for i in 0..self.orders_per_level {
+                let timestamp = level * self.orders_per_level + i;
+                book.add_limit_order(trader(i), Side::Bid, Price(MID - level * TICK), Size(U256::from(10)), timestamp);
+                book.add_limit_order(trader(i), Side::Ask, Price(MID + level * TICK), Size(U256::from(10)), timestamp);
+            }
+        }
+        book
+    }
+}
+
+fn bench_limit_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("limit_insert");
+    for shape in SHAPES {
+        let book = shape.build();
+        // Join an existing level at the back of its queue
+        group.bench_with_input(BenchmarkId::new("existing_level", shape.name), &book, |b, book| {
+            b.iter_batched_ref(
+                || book.clone(),
+                |book| book.add_limit_order(trader(1), Side::Bid, Price(MID - TICK), Size(U256::from(10)), 0),
+                BatchSize::LargeInput,
+            )
+        });
+        // Open a new level inside the spread, moving the top of book
+        group.bench_with_input(BenchmarkId::new("improve_top", shape.name), &book, |b, book| {
+            b.iter_batched_ref(
+                || book.clone(),
+                |book| book.add_limit_order(trader(1), Side::Bid, Price(MID - TICK / 2), Size(U256::from(10)), 0),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_cross_and_match(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cross_and_match");
+    let shape = SHAPES[1];
+    let book = shape.build();
+    // A marketable limit order sweeping `depth` full ask levels
+    for depth in [1u64, 10, 50, 100] {
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, &depth| {
+            b.iter_batched_ref(
+                || book.clone(),
+                |book| {
+                    MatchingEngine::execute_limit_order(
+                        book,
+                        trader(100),
+                        Side::Bid,
+                        Price(MID + depth * TICK),
+                        Size(U256::from(10 * shape.orders_per_level * depth)),
+                        0,
+                    )
+                    .unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_cancel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel");
+    for shape in SHAPES {
+        let book = shape.build();
+        // IDs start at 1 and alternate bid/ask: the first order heads the best
+        // bid queue, `middle` sits halfway down the bid queue of the middle level
+        let top = 1;
+        let middle = 1 + 2 * ((shape.levels / 2 - 1) * shape.orders_per_level + shape.orders_per_level / 2);
+        for (position, order_id) in [("top", top), ("middle", middle)] {
+            group.bench_with_input(BenchmarkId::new(position, shape.name), &book, |b, book| {
+                b.iter_batched_ref(|| book.clone(), |book| book.cancel_order(order_id).unwrap(), BatchSize::LargeInput)
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+    for shape in SHAPES {
+        let book = shape.build();
+        for depth in [10usize, 50, 500] {
+            group.bench_with_input(BenchmarkId::new(shape.name, depth), &depth, |b, &depth| {
+                b.iter(|| black_box(book.snapshot(depth)))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Criterion settings; with the `profiling` feature each benchmark run with
+/// `--profile-time <secs>` also writes
+/// `target/criterion/<group>/<bench>/profile/flamegraph.svg`
+fn config() -> Criterion {
+    #[cfg(feature = "profiling")]
+    {
+        use pprof::criterion::{Output, PProfProfiler};
+        Criterion::default().with_profiler(PProfProfiler::new(1_000, Output::Flamegraph(None)))
+    }
+    #[cfg(not(feature = "profiling"))]
+    Criterion::default()
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = bench_add_cancel, bench_market_sweep, bench_crossing_limits,
+        bench_limit_insert, bench_cross_and_match, bench_cancel, bench_snapshot
+}
+criterion_main!(benches);
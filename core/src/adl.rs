@@ -1,5 +1,5 @@
 use crate::types::*;
-use alloy_primitives::Address;
+use alloy_primitives::{keccak256, Address, B256};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
@@ -14,6 +14,10 @@ pub struct ADLCandidate {
     pub unrealized_pnl: i64,
     pub leverage: u32,
     pub priority: u64,  // Higher = deleveraged first
+    /// Orders candidates with equal priority; derived from the block
+    /// randomness seed when queued (see `ADLEngine::set_randomness`)
+    #[serde(default)]
+    pub tie_breaker: u64,
 }
 
 impl ADLCandidate {
@@ -35,6 +39,7 @@ impl ADLCandidate {
             unrealized_pnl,
             leverage,
             priority,
+            tie_breaker: 0,
         }
     }
     
@@ -57,13 +62,15 @@ impl Eq for ADLCandidate {}
 
 impl PartialEq for ADLCandidate {
     fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority
+        self.priority == other.priority && self.tie_breaker == other.tie_breaker
     }
 }
 
 impl Ord for ADLCandidate {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.priority.cmp(&other.priority)
+        self.priority
+            .cmp(&other.priority)
+            .then(self.tie_breaker.cmp(&other.tie_breaker))
     }
 }
 
@@ -79,6 +86,8 @@ pub struct ADLEngine {
     candidates: BinaryHeap<ADLCandidate>,
     /// Total positions queued per asset
     queued_per_asset: std::collections::HashMap<AssetId, usize>,
+    /// Randomness seed used to break priority ties
+    seed: B256,
 }
 
 impl ADLEngine {
@@ -86,11 +95,36 @@ impl ADLEngine {
         Self {
             candidates: BinaryHeap::new(),
             queued_per_asset: std::collections::HashMap::new(),
+            seed: B256::ZERO,
         }
     }
+
+    /// Seed tie-breaking with the block's randomness beacon.
+    ///
+    /// Candidates with equal priority are then ordered by a hash of the seed
+    /// and their (user, asset), which every node computes identically but no
+    /// user can position themselves ahead of in advance.
+    pub fn set_randomness(&mut self, seed: B256) {
+        self.seed = seed;
+        let candidates: Vec<_> = self.candidates.drain().collect();
+        for mut candidate in candidates {
+            candidate.tie_breaker = self.tie_breaker(&candidate);
+            self.candidates.push(candidate);
+        }
+    }
+
+    fn tie_breaker(&self, candidate: &ADLCandidate) -> u64 {
+        let mut data = Vec::with_capacity(56);
+        data.extend_from_slice(self.seed.as_slice());
+        data.extend_from_slice(candidate.user.as_slice());
+        data.extend_from_slice(&candidate.asset.0.to_be_bytes());
+        let digest = keccak256(&data);
+        u64::from_be_bytes(digest[..8].try_into().unwrap())
+    }
     
     /// Add candidate to ADL queue
-    pub fn add_candidate(&mut self, candidate: ADLCandidate) {
+    pub fn add_candidate(&mut self, mut candidate: ADLCandidate) {
+        candidate.tie_breaker = self.tie_breaker(&candidate);
         let asset = candidate.asset;
         self.candidates.push(candidate);
         *self.queued_per_asset.entry(asset).or_insert(0) += 1;
@@ -116,12 +150,13 @@ impl ADLEngine {
         let mut all_candidates: Vec<_> = self.candidates.drain().collect();
         
         // Find highest priority candidate for this asset
-        let mut best_idx = None;
-        let mut best_priority = 0;
+        let mut best_idx: Option<usize> = None;
         
         for (idx, candidate) in all_candidates.iter().enumerate() {
-            if candidate.asset == asset && candidate.priority > best_priority {
-                best_priority = candidate.priority;
+            if candidate.asset == asset
+                && candidate.priority > 0
+                && best_idx.is_none_or(|best| *candidate > all_candidates[best])
+            {
                 best_idx = Some(idx);
             }
         }
@@ -330,6 +365,39 @@ mod tests {
         assert_eq!(next3.priority, 500);
     }
 
+    #[test]
+    fn test_randomness_breaks_priority_ties() {
+        let candidates: Vec<_> = (1..=8u8)
+            .map(|i| ADLCandidate::new(Address::repeat_byte(i), AssetId(1), 10, Price(1_000_000), 100, 2))
+            .collect();
+        let drain_order = |seed: B256, reversed: bool| {
+            let mut engine = ADLEngine::new();
+            engine.set_randomness(seed);
+            let mut queued = candidates.clone();
+            if reversed {
+                queued.reverse();
+            }
+            for c in queued {
+                engine.add_candidate(c);
+            }
+            std::iter::from_fn(|| engine.get_next_candidate().map(|c| c.user)).collect::<Vec<_>>()
+        };
+
+        // Independent of insertion order, dependent on the seed
+        let order = drain_order(B256::repeat_byte(1), false);
+        assert_eq!(order, drain_order(B256::repeat_byte(1), true));
+        assert_ne!(order, drain_order(B256::repeat_byte(2), false));
+
+        // Re-seeding re-keys queued candidates
+        let mut engine = ADLEngine::new();
+        for c in candidates.clone() {
+            engine.add_candidate(c);
+        }
+        engine.set_randomness(B256::repeat_byte(1));
+        assert_eq!(engine.peek_next().unwrap().user, order[0]);
+        assert_eq!(engine.get_next_candidate_for_asset(AssetId(1)).unwrap().user, order[0]);
+    }
+
     #[test]
     fn test_peek_next() {
         let mut engine = ADLEngine::new();
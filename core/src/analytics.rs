@@ -3,6 +3,10 @@ use alloy_primitives::{Address, U256};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod liquidity;
+pub mod pools;
+pub mod surveillance;
+
 /// Trading volume entry with timestamp
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct VolumeEntry {
@@ -0,0 +1,284 @@
+use crate::orderbook::OrderBook;
+use crate::types::*;
+use alloy_primitives::U256;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// How a market accepts orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MarketMode {
+    /// Normal continuous matching
+    #[default]
+    Continuous,
+    /// Only orders that rest without crossing are accepted
+    PostOnly,
+    /// Orders rest without matching until the book is uncrossed at a single
+    /// price when the market returns to continuous trading
+    Auction,
+}
+
+/// Governance-set liquidity service levels of a market
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiquiditySla {
+    /// Widest acceptable quoted spread (bps of mid)
+    pub max_spread_bps: u64,
+    /// Depth is measured within this distance of mid (bps)
+    pub depth_range_bps: u64,
+    /// Minimum size on each side within `depth_range_bps`
+    pub min_depth: U256,
+    /// Rolling window for uptime in seconds
+    pub window: u64,
+    /// Uptime (bps of samples in the window) below which a market is thin
+    pub min_uptime_bps: u64,
+    /// Samples needed in the window before the mode may change
+    pub min_samples: usize,
+    /// Mode thin markets are switched to (None = monitor only)
+    pub thin_market_mode: Option<MarketMode>,
+}
+
+impl Default for LiquiditySla {
+    fn default() -> Self {
+        Self {
+            max_spread_bps: 50,
+            depth_range_bps: 100,
+            min_depth: U256::from(1),
+            window: 60 * 60,  // 1 hour
+            min_uptime_bps: 9000,
+            min_samples: 10,
+            thin_market_mode: None,
+        }
+    }
+}
+
+/// Liquidity of a market at one sample
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiquiditySample {
+    pub timestamp: u64,
+    /// Quoted spread in bps of mid (None with an empty side, 0 when crossed)
+    pub spread_bps: Option<u64>,
+    /// Bid size within the SLA depth range of mid
+    pub bid_depth: U256,
+    /// Ask size within the SLA depth range of mid
+    pub ask_depth: U256,
+    /// Whether the sample met the spread and depth requirements
+    pub compliant: bool,
+}
+
+/// Published liquidity metrics of a market
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiquidityStats {
+    pub latest: LiquiditySample,
+    /// Compliant share of samples in the window (bps)
+    pub uptime_bps: u64,
+    /// Samples in the window
+    pub samples: usize,
+    pub mode: MarketMode,
+}
+
+/// Rolling spread, depth and uptime per market, with automatic mode changes
+#[derive(Debug, Clone, Default)]
+pub struct LiquidityMonitor {
+    /// SLA for markets without their own
+    default_sla: LiquiditySla,
+    /// Per-market SLA overrides
+    slas: HashMap<AssetId, LiquiditySla>,
+    /// Windowed (timestamp, compliant) samples per market
+    history: HashMap<AssetId, VecDeque<(u64, bool)>>,
+    latest: HashMap<AssetId, LiquiditySample>,
+    modes: HashMap<AssetId, MarketMode>,
+}
+
+impl LiquidityMonitor {
+    pub fn new(default_sla: LiquiditySla) -> Self {
+        Self {
+            default_sla,
+            ..Self::default()
+        }
+    }
+
+    /// Get the SLA of a market
+    pub fn sla(&self, asset: AssetId) -> &LiquiditySla {
+        self.slas.get(&asset).unwrap_or(&self.default_sla)
+    }
+
+    /// Set the SLA for markets without their own
+    pub fn set_default_sla(&mut self, sla: LiquiditySla) {
+        self.default_sla = sla;
+    }
+
+    /// Set a market's SLA
+    pub fn set_sla(&mut self, asset: AssetId, sla: LiquiditySla) {
+        self.slas.insert(asset, sla);
+    }
+
+    /// Get a market's trading mode
+    pub fn mode(&self, asset: AssetId) -> MarketMode {
+        self.modes.get(&asset).copied().unwrap_or_default()
+    }
+
+    /// Force a market's trading mode
+    pub fn set_mode(&mut self, asset: AssetId, mode: MarketMode) {
+        if mode == MarketMode::Continuous {
+            self.modes.remove(&asset);
+        } else {
+            self.modes.insert(asset, mode);
+        }
+    }
+
+    /// Measure a book against its market's SLA and record the sample
+    pub fn sample(&mut self, book: &OrderBook, timestamp: u64) -> LiquiditySample {
+        let sla = self.sla(book.asset).clone();
+        let sample = measure(book, &sla, timestamp);
+
+        let history = self.history.entry(book.asset).or_default();
+        history.push_back((timestamp, sample.compliant));
+        let cutoff = timestamp.saturating_sub(sla.window);
+        while history.front().is_some_and(|(ts, _)| *ts < cutoff) {
+            history.pop_front();
+        }
+
+        self.latest.insert(book.asset, sample.clone());
+        sample
+    }
+
+    /// Get a market's published liquidity metrics
+    pub fn stats(&self, asset: AssetId) -> Option<LiquidityStats> {
+        let latest = self.latest.get(&asset)?.clone();
+        let (samples, uptime_bps) = self.uptime(asset);
+        Some(LiquidityStats {
+            latest,
+            uptime_bps,
+            samples,
+            mode: self.mode(asset),
+        })
+    }
+
+    /// Switch thin markets to their SLA's thin-market mode and restore
+    /// markets that recovered. Returns the new mode if it changed.
+    pub fn evaluate(&mut self, asset: AssetId) -> Option<MarketMode> {
+        let sla = self.sla(asset);
+        let thin_mode = sla.thin_market_mode?;
+        let (samples, uptime_bps) = self.uptime(asset);
+        if samples < sla.min_samples {
+            return None;
+        }
+
+        let current = self.mode(asset);
+        let target = if uptime_bps < sla.min_uptime_bps {
+            thin_mode
+        } else {
+            MarketMode::Continuous
+        };
+        if target == current {
+            return None;
+        }
+
+        self.set_mode(asset, target);
+        Some(target)
+    }
+
+    /// (samples, compliant bps) in a market's window
+    fn uptime(&self, asset: AssetId) -> (usize, u64) {
+        let history = match self.history.get(&asset) {
+            Some(history) if !history.is_empty() => history,
+            _ => return (0, 0),
+        };
+        let compliant = history.iter().filter(|(_, ok)| *ok).count();
+        (history.len(), (compliant * 10000 / history.len()) as u64)
+    }
+}
+
+/// Spread and depth of a book against an SLA
+fn measure(book: &OrderBook, sla: &LiquiditySla, timestamp: u64) -> LiquiditySample {
+    let (bid, ask) = match (book.best_bid(), book.best_ask()) {
+        (Some(bid), Some(ask)) => (bid, ask),
+        _ => {
+            return LiquiditySample {
+                timestamp,
+                ..LiquiditySample::default()
+            }
+        }
+    };
+
+    let mid = (bid.0 as u128 + ask.0 as u128) / 2;
+    let spread_bps = (ask.0.saturating_sub(bid.0) as u128 * 10000 / mid.max(1)) as u64;
+    let range = mid * sla.depth_range_bps as u128 / 10000;
+    let depth = |side: Side| {
+        book.levels(side)
+            .take_while(|level| (level.price.0 as u128).abs_diff(mid) <= range)
+            .fold(U256::ZERO, |acc, level| acc.saturating_add(level.total_size))
+    };
+    let (bid_depth, ask_depth) = (depth(Side::Bid), depth(Side::Ask));
+
+    LiquiditySample {
+        timestamp,
+        spread_bps: Some(spread_bps),
+        compliant: spread_bps <= sla.max_spread_bps
+            && bid_depth >= sla.min_depth
+            && ask_depth >= sla.min_depth,
+        bid_depth,
+        ask_depth,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::Address;
+
+    fn book(bid: f64, ask: f64) -> OrderBook {
+        let mut book = OrderBook::new(AssetId(1));
+        let maker = Address::from([1u8; 20]);
+        book.add_limit_order(maker, Side::Bid, Price::from_float(bid), Size(U256::from(5)), 0);
+        book.add_limit_order(maker, Side::Ask, Price::from_float(ask), Size(U256::from(5)), 0);
+        book
+    }
+
+    #[test]
+    fn test_spread_depth_and_uptime() {
+        let mut monitor = LiquidityMonitor::new(LiquiditySla::default());
+
+        let tight = monitor.sample(&book(99.9, 100.1), 0);
+        assert_eq!(tight.spread_bps, Some(20));
+        assert_eq!((tight.bid_depth, tight.ask_depth), (U256::from(5), U256::from(5)));
+        assert!(tight.compliant);
+
+        // 3% wide: spread breached and both quotes outside the depth range
+        let wide = monitor.sample(&book(98.5, 101.5), 10);
+        assert_eq!(wide.spread_bps, Some(300));
+        assert_eq!(wide.bid_depth, U256::ZERO);
+        assert!(!wide.compliant);
+
+        let stats = monitor.stats(AssetId(1)).unwrap();
+        assert_eq!((stats.samples, stats.uptime_bps), (2, 5000));
+
+        // Old samples leave the window
+        monitor.sample(&book(99.9, 100.1), 3610);
+        let stats = monitor.stats(AssetId(1)).unwrap();
+        assert_eq!((stats.samples, stats.uptime_bps), (2, 5000));
+    }
+
+    #[test]
+    fn test_thin_market_switches_mode_and_recovers() {
+        let asset = AssetId(1);
+        let mut monitor = LiquidityMonitor::default();
+        monitor.set_sla(asset, LiquiditySla {
+            min_samples: 2,
+            thin_market_mode: Some(MarketMode::PostOnly),
+            window: 20,
+            ..LiquiditySla::default()
+        });
+
+        monitor.sample(&book(99.0, 101.0), 0);
+        assert_eq!(monitor.evaluate(asset), None);
+        monitor.sample(&book(99.0, 101.0), 10);
+        assert_eq!(monitor.evaluate(asset), Some(MarketMode::PostOnly));
+        assert_eq!(monitor.evaluate(asset), None);
+
+        for ts in [30, 40] {
+            monitor.sample(&book(99.9, 100.1), ts);
+        }
+        assert_eq!(monitor.evaluate(asset), Some(MarketMode::Continuous));
+        assert_eq!(monitor.mode(asset), MarketMode::Continuous);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::liquidity_pool::{LiquidityPool, PoolId};
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+
+/// Seconds in a 365-day year, for annualizing returns
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Performance of a liquidity pool over one epoch, captured at its end
+///
+/// Pools take quote-denominated deposits, so holding (HODL) is worth net
+/// deposits; the pool is worth its liquidity plus the base inventory it
+/// took on from takers, marked at `mark_price`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolEpochStats {
+    pub pool_id: PoolId,
+    pub epoch: u64,
+    /// Start of the epoch (previous capture, or pool creation)
+    pub period_start: u64,
+    pub timestamp: u64,
+    /// Mark the inventory is valued at (None = inventory valued at zero)
+    pub mark_price: Option<Price>,
+    /// Fees credited to the pool over its life
+    pub cumulative_fees: U256,
+    /// Fees credited during the epoch
+    pub epoch_fees: U256,
+    /// Deposits less withdrawals
+    pub hodl_value: i128,
+    /// Liquidity plus marked inventory
+    pub pool_value: i128,
+    /// Pool value less fees less HODL value (negative = loss vs HODL)
+    pub impermanent_loss: i128,
+    /// Epoch fees annualized over the pool value (bps)
+    pub fee_apr_bps: u64,
+    pub lp_supply: U256,
+}
+
+impl PoolEpochStats {
+    /// Capture a pool's performance since the `previous` capture
+    pub fn capture(
+        pool: &LiquidityPool,
+        epoch: u64,
+        timestamp: u64,
+        mark_price: Option<Price>,
+        previous: Option<&PoolEpochStats>,
+    ) -> Self {
+        let period_start = previous.map_or(pool.created_at, |p| p.timestamp);
+        let epoch_fees = pool
+            .accumulated_fees
+            .saturating_sub(previous.map_or(U256::ZERO, |p| p.cumulative_fees));
+        let pool_value = pool.value(mark_price);
+        let average_value = previous.map_or(pool_value, |p| (p.pool_value + pool_value) / 2);
+
+        Self {
+            pool_id: pool.id,
+            epoch,
+            period_start,
+            timestamp,
+            mark_price,
+            cumulative_fees: pool.accumulated_fees,
+            epoch_fees,
+            hodl_value: pool.net_deposits(),
+            pool_value,
+            impermanent_loss: pool.trading_pnl(mark_price),
+            fee_apr_bps: fee_apr_bps(epoch_fees, average_value, timestamp.saturating_sub(period_start)),
+            lp_supply: pool.total_supply,
+        }
+    }
+
+    /// Impermanent loss in bps of the HODL value (0 without deposits)
+    pub fn impermanent_loss_bps(&self) -> i64 {
+        if self.hodl_value <= 0 {
+            return 0;
+        }
+        (self.impermanent_loss * 10_000 / self.hodl_value) as i64
+    }
+}
+
+/// `fees` earned on `value` over `period` seconds, annualized (bps)
+pub fn fee_apr_bps(fees: U256, value: i128, period: u64) -> u64 {
+    if value <= 0 || period == 0 {
+        return 0;
+    }
+    let fees = fees.saturating_to::<u128>();
+    (fees.saturating_mul(10_000 * SECONDS_PER_YEAR as u128) / (value as u128 * period as u128)) as u64
+}
+
+/// Fee APR over the last `epochs` captures (bps), against their average
+/// pool value
+pub fn rolling_apr_bps(history: &[PoolEpochStats], epochs: usize) -> u64 {
+    let window = &history[history.len().saturating_sub(epochs)..];
+    let (Some(first), Some(last)) = (window.first(), window.last()) else {
+        return 0;
+    };
+    let fees = window.iter().fold(U256::ZERO, |acc, s| acc.saturating_add(s.epoch_fees));
+    let average_value = window.iter().map(|s| s.pool_value).sum::<i128>() / window.len() as i128;
+    fee_apr_bps(fees, average_value, last.timestamp.saturating_sub(first.period_start))
+}
+
+/// An LP token holder's share of a pool's performance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LpPoolReport {
+    pub pool_id: PoolId,
+    pub holder: Address,
+    pub lp_tokens: U256,
+    /// Share of the LP supply (bps)
+    pub share_bps: u64,
+    /// Redeemable liquidity
+    pub liquidity: U256,
+    /// Holder's share of the pool's lifetime fees
+    pub fees_earned: U256,
+    /// Holder's share of the pool's impermanent loss at `mark_price`
+    pub impermanent_loss: i128,
+    /// Fee APR over the rolling window (bps)
+    pub rolling_apr_bps: u64,
+}
+
+impl LpPoolReport {
+    /// Report for `holder`, with APR over the last `epochs` of `history`
+    pub fn new(
+        pool: &LiquidityPool,
+        holder: Address,
+        mark_price: Option<Price>,
+        history: &[PoolEpochStats],
+        epochs: usize,
+    ) -> Self {
+        let lp_tokens = pool.lp_tokens.get(&holder).copied().unwrap_or(U256::ZERO);
+        let share = |amount: U256| {
+            amount
+                .saturating_mul(lp_tokens)
+                .checked_div(pool.total_supply)
+                .unwrap_or(U256::ZERO)
+        };
+        let impermanent_loss = if pool.total_supply.is_zero() {
+            0
+        } else {
+            pool.trading_pnl(mark_price) * lp_tokens.saturating_to::<u128>() as i128
+                / pool.total_supply.saturating_to::<u128>() as i128
+        };
+
+        Self {
+            pool_id: pool.id,
+            holder,
+            lp_tokens,
+            share_bps: share(U256::from(10_000)).saturating_to(),
+            liquidity: pool.get_user_liquidity(&holder),
+            fees_earned: share(pool.accumulated_fees),
+            impermanent_loss,
+            rolling_apr_bps: rolling_apr_bps(history, epochs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> LiquidityPool {
+        let mut pool = LiquidityPool::new(1, AssetId(1), vec![Price::from_float(100.0)], Size(U256::from(10)), 0);
+        pool.add_liquidity(Address::repeat_byte(1), U256::from(10_000)).unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_epoch_stats_fees_and_impermanent_loss() {
+        let mut pool = pool();
+        // Pool sells 10 at 100 to a taker, then the price rises to 110
+        pool.record_fill(Side::Bid, Price::from_float(100.0), Size(U256::from(10)));
+        pool.distribute_fees(U256::from(10));
+
+        let mark = Some(Price::from_float(110.0));
+        let first = PoolEpochStats::capture(&pool, 0, SECONDS_PER_YEAR / 2, mark, None);
+        assert_eq!(first.epoch_fees, U256::from(10));
+        assert_eq!(first.hodl_value, 10_000);
+        // Short 10 from 100 marked at 110
+        assert_eq!(first.impermanent_loss, -100);
+        assert_eq!(first.pool_value, 10_000 + 10 - 100);
+        assert_eq!(first.impermanent_loss_bps(), -100);
+        // 10 over half a year on 9_910: ~20 bps annualized
+        assert_eq!(first.fee_apr_bps, 20);
+
+        pool.distribute_fees(U256::from(30));
+        let second = PoolEpochStats::capture(&pool, 1, SECONDS_PER_YEAR, mark, Some(&first));
+        assert_eq!(second.period_start, first.timestamp);
+        assert_eq!(second.epoch_fees, U256::from(30));
+        assert_eq!(rolling_apr_bps(&[first.clone(), second.clone()], 1), second.fee_apr_bps);
+        // 40 over a year on ~9_925
+        assert_eq!(rolling_apr_bps(&[first, second], 2), 40);
+    }
+
+    #[test]
+    fn test_lp_report_splits_by_share() {
+        let mut pool = pool();
+        let other = Address::repeat_byte(2);
+        pool.add_liquidity(other, U256::from(30_000)).unwrap();
+        pool.record_fill(Side::Ask, Price::from_float(100.0), Size(U256::from(40)));
+        pool.distribute_fees(U256::from(400));
+
+        let report = LpPoolReport::new(&pool, other, Some(Price::from_float(90.0)), &[], 4);
+        assert_eq!(report.share_bps, 7_500);
+        assert_eq!(report.fees_earned, U256::from(300));
+        // Long 40 from 100 marked at 90: -400, three quarters of it
+        assert_eq!(report.impermanent_loss, -300);
+        assert_eq!(report.rolling_apr_bps, 0);
+    }
+}
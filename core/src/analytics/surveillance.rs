@@ -0,0 +1,414 @@
+use crate::emissions::EmissionsEngine;
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Thresholds for the surveillance heuristics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SurveillanceConfig {
+    /// Look-back window in seconds
+    pub window: u64,
+    /// Minimum trades between two accounts before round-trips are flagged
+    pub min_pair_trades: usize,
+    /// A pair round-trips if its net flow is at most this share of gross (bps)
+    pub max_pair_net_bps: u64,
+    /// Minimum flow on every leg of a circular flow
+    pub min_cycle_volume: U256,
+    /// Legs of a cycle must be within this size tolerance of the largest (bps)
+    pub cycle_tolerance_bps: u64,
+    /// Trades at or below this size count towards painting the tape
+    pub tape_max_size: U256,
+    /// Price-moving small trades by one taker needed to flag painting the tape
+    pub tape_min_trades: usize,
+    /// Emissions penalty applied to flagged accounts (bps, 10000 = excluded)
+    pub penalty_bps: u64,
+}
+
+impl Default for SurveillanceConfig {
+    fn default() -> Self {
+        Self {
+            window: 24 * 60 * 60,  // 1 day
+            min_pair_trades: 4,
+            max_pair_net_bps: 1000,
+            min_cycle_volume: U256::from(1),
+            cycle_tolerance_bps: 1000,
+            tape_max_size: U256::from(1),
+            tape_min_trades: 10,
+            penalty_bps: 10000,
+        }
+    }
+}
+
+/// Suspicious pattern found in the fill stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlagKind {
+    /// An account traded with itself
+    SelfMatch,
+    /// Accounts registered as linked traded with each other
+    LinkedAccounts,
+    /// Two accounts traded back and forth with little net change
+    RoundTrip,
+    /// Inventory cycled through three accounts back to the start
+    CircularFlow,
+    /// Repeated tiny trades moving the last traded price
+    PaintingTape { asset: AssetId },
+}
+
+/// A flagged pattern and the accounts involved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SurveillanceFlag {
+    pub kind: FlagKind,
+    /// Accounts involved, sorted
+    pub accounts: Vec<Address>,
+    /// Volume traded in the pattern
+    pub volume: U256,
+    /// Fills in the pattern
+    pub trades: usize,
+}
+
+/// Flags raised over one look-back window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SurveillanceReport {
+    pub window_start: u64,
+    pub window_end: u64,
+    /// Fills analyzed
+    pub fills: usize,
+    pub flags: Vec<SurveillanceFlag>,
+}
+
+impl SurveillanceReport {
+    /// Every account named in at least one flag
+    pub fn flagged_accounts(&self) -> BTreeSet<Address> {
+        self.flags.iter().flat_map(|f| f.accounts.iter().copied()).collect()
+    }
+}
+
+/// Fill with the direction needed to follow inventory
+#[derive(Debug, Clone)]
+struct ObservedFill {
+    asset: AssetId,
+    seller: Address,
+    buyer: Address,
+    taker: Address,
+    price: Price,
+    size: U256,
+    timestamp: u64,
+}
+
+/// Trades between an unordered pair of accounts
+#[derive(Default)]
+struct PairFlow {
+    /// Base bought by the lower address from the higher one
+    to_low: U256,
+    /// Base bought by the higher address from the lower one
+    to_high: U256,
+    trades: usize,
+}
+
+/// Wash-trading and manipulation surveillance over a rolling window of fills
+#[derive(Debug, Clone, Default)]
+pub struct Surveillance {
+    config: SurveillanceConfig,
+    fills: VecDeque<ObservedFill>,
+    /// Account -> link group (accounts known to share an owner)
+    links: BTreeMap<Address, u64>,
+    next_group: u64,
+}
+
+impl Surveillance {
+    pub fn new(config: SurveillanceConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &SurveillanceConfig {
+        &self.config
+    }
+
+    /// Register accounts as controlled by the same owner
+    pub fn link_accounts(&mut self, a: Address, b: Address) {
+        let group = match (self.links.get(&a).copied(), self.links.get(&b).copied()) {
+            (Some(ga), Some(gb)) => {
+                // Merge b's group into a's
+                for group in self.links.values_mut() {
+                    if *group == gb {
+                        *group = ga;
+                    }
+                }
+                ga
+            }
+            (Some(g), None) | (None, Some(g)) => g,
+            (None, None) => {
+                self.next_group += 1;
+                self.next_group
+            }
+        };
+        self.links.insert(a, group);
+        self.links.insert(b, group);
+    }
+
+    /// Whether two distinct accounts are linked
+    pub fn are_linked(&self, a: &Address, b: &Address) -> bool {
+        a != b && self.links.get(a).is_some_and(|ga| self.links.get(b) == Some(ga))
+    }
+
+    /// Record a fill; `taker_side` gives the direction of the trade
+    pub fn record_fill(&mut self, asset: AssetId, taker_side: Side, fill: &Fill) {
+        let (buyer, seller) = match taker_side {
+            Side::Bid => (fill.taker, fill.maker),
+            Side::Ask => (fill.maker, fill.taker),
+        };
+        self.fills.push_back(ObservedFill {
+            asset,
+            seller,
+            buyer,
+            taker: fill.taker,
+            price: fill.price,
+            size: fill.size.0,
+            timestamp: fill.timestamp,
+        });
+
+        let cutoff = fill.timestamp.saturating_sub(self.config.window);
+        while self.fills.front().is_some_and(|f| f.timestamp < cutoff) {
+            self.fills.pop_front();
+        }
+    }
+
+    /// Run every heuristic over fills in `[now - window, now]`
+    pub fn report(&self, now: u64) -> SurveillanceReport {
+        let window_start = now.saturating_sub(self.config.window);
+        let fills: Vec<_> = self
+            .fills
+            .iter()
+            .filter(|f| f.timestamp >= window_start && f.timestamp <= now)
+            .collect();
+
+        let mut flags = self.self_matches(&fills);
+        flags.extend(self.round_trips(&fills));
+        flags.extend(self.circular_flows(&fills));
+        flags.extend(self.painted_tape(&fills));
+
+        SurveillanceReport {
+            window_start,
+            window_end: now,
+            fills: fills.len(),
+            flags,
+        }
+    }
+
+    /// Penalize every flagged account's emissions score
+    pub fn apply_penalties(&self, report: &SurveillanceReport, emissions: &mut EmissionsEngine) {
+        for account in report.flagged_accounts() {
+            emissions.set_penalty(account, self.config.penalty_bps);
+        }
+    }
+
+    fn self_matches(&self, fills: &[&ObservedFill]) -> Vec<SurveillanceFlag> {
+        let mut groups: BTreeMap<(Vec<Address>, bool), (U256, usize)> = BTreeMap::new();
+        for fill in fills {
+            let linked = self.are_linked(&fill.buyer, &fill.seller);
+            if fill.buyer != fill.seller && !linked {
+                continue;
+            }
+            let mut accounts = vec![fill.buyer, fill.seller];
+            accounts.sort();
+            accounts.dedup();
+            let entry = groups.entry((accounts, linked)).or_insert((U256::ZERO, 0));
+            entry.0 += fill.size;
+            entry.1 += 1;
+        }
+
+        groups
+            .into_iter()
+            .map(|((accounts, linked), (volume, trades))| SurveillanceFlag {
+                kind: if linked { FlagKind::LinkedAccounts } else { FlagKind::SelfMatch },
+                accounts,
+                volume,
+                trades,
+            })
+            .collect()
+    }
+
+    fn round_trips(&self, fills: &[&ObservedFill]) -> Vec<SurveillanceFlag> {
+        let mut pairs: BTreeMap<(Address, Address), PairFlow> = BTreeMap::new();
+        for fill in fills.iter().filter(|f| f.buyer != f.seller) {
+            let key = (fill.buyer.min(fill.seller), fill.buyer.max(fill.seller));
+            let flow = pairs.entry(key).or_default();
+            if fill.buyer == key.0 {
+                flow.to_low += fill.size;
+            } else {
+                flow.to_high += fill.size;
+            }
+            flow.trades += 1;
+        }
+
+        pairs
+            .into_iter()
+            .filter_map(|((low, high), flow)| {
+                let gross = flow.to_low + flow.to_high;
+                let net = flow.to_low.abs_diff(flow.to_high);
+                let round_trip = flow.trades >= self.config.min_pair_trades
+                    && flow.to_low > U256::ZERO
+                    && flow.to_high > U256::ZERO
+                    && net.saturating_mul(U256::from(10000))
+                        <= gross.saturating_mul(U256::from(self.config.max_pair_net_bps));
+                round_trip.then(|| SurveillanceFlag {
+                    kind: FlagKind::RoundTrip,
+                    accounts: vec![low, high],
+                    volume: gross,
+                    trades: flow.trades,
+                })
+            })
+            .collect()
+    }
+
+    fn circular_flows(&self, fills: &[&ObservedFill]) -> Vec<SurveillanceFlag> {
+        // Directed inventory flow: seller -> buyer
+        let mut edges: BTreeMap<(Address, Address), (U256, usize)> = BTreeMap::new();
+        for fill in fills.iter().filter(|f| f.buyer != f.seller) {
+            let edge = edges.entry((fill.seller, fill.buyer)).or_insert((U256::ZERO, 0));
+            edge.0 += fill.size;
+            edge.1 += 1;
+        }
+        let min_volume = self.config.min_cycle_volume;
+        let out: BTreeMap<Address, Vec<Address>> =
+            edges.iter().filter(|(_, (v, _))| *v >= min_volume).fold(
+                BTreeMap::new(),
+                |mut out, ((from, to), _)| {
+                    out.entry(*from).or_insert_with(Vec::new).push(*to);
+                    out
+                },
+            );
+
+        let mut flags = Vec::new();
+        // Each 3-cycle is found once, starting from its smallest account
+        for (&a, nexts) in &out {
+            for &b in nexts.iter().filter(|b| **b > a) {
+                for &c in out.get(&b).into_iter().flatten().filter(|c| **c > a && **c != b) {
+                    let Some(&closing) = edges.get(&(c, a)) else { continue };
+                    if closing.0 < min_volume {
+                        continue;
+                    }
+                    let legs = [edges[&(a, b)], edges[&(b, c)], closing];
+                    let max = legs.iter().map(|l| l.0).max().unwrap();
+                    let min = legs.iter().map(|l| l.0).min().unwrap();
+                    let tolerance = U256::from(10000 - self.config.cycle_tolerance_bps.min(10000));
+                    if min.saturating_mul(U256::from(10000)) < max.saturating_mul(tolerance) {
+                        continue;
+                    }
+                    let mut accounts = vec![a, b, c];
+                    accounts.sort();
+                    flags.push(SurveillanceFlag {
+                        kind: FlagKind::CircularFlow,
+                        accounts,
+                        volume: legs.iter().fold(U256::ZERO, |acc, l| acc + l.0),
+                        trades: legs.iter().map(|l| l.1).sum(),
+                    });
+                }
+            }
+        }
+        flags
+    }
+
+    fn painted_tape(&self, fills: &[&ObservedFill]) -> Vec<SurveillanceFlag> {
+        let mut last_price: BTreeMap<u32, Price> = BTreeMap::new();
+        let mut moves: BTreeMap<(u32, Address), (U256, usize)> = BTreeMap::new();
+        for fill in fills {
+            let moved = last_price.insert(fill.asset.0, fill.price).is_some_and(|p| p != fill.price);
+            if moved && fill.size <= self.config.tape_max_size {
+                let entry = moves.entry((fill.asset.0, fill.taker)).or_insert((U256::ZERO, 0));
+                entry.0 += fill.size;
+                entry.1 += 1;
+            }
+        }
+
+        moves
+            .into_iter()
+            .filter(|(_, (_, trades))| *trades >= self.config.tape_min_trades)
+            .map(|((asset, taker), (volume, trades))| SurveillanceFlag {
+                kind: FlagKind::PaintingTape { asset: AssetId(asset) },
+                accounts: vec![taker],
+                volume,
+                trades,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(s: &mut Surveillance, maker: u8, taker: u8, side: Side, price: u64, size: u64, ts: u64) {
+        let fill = Fill {
+            order_id: ts,
+            price: Price(price),
+            size: Size(U256::from(size)),
+            maker: Address::repeat_byte(maker),
+            taker: Address::repeat_byte(taker),
+            timestamp: ts,
+        };
+        s.record_fill(AssetId(1), side, &fill);
+    }
+
+    fn kinds(report: &SurveillanceReport) -> Vec<FlagKind> {
+        report.flags.iter().map(|f| f.kind.clone()).collect()
+    }
+
+    #[test]
+    fn test_self_match_and_round_trip() {
+        let mut s = Surveillance::new(SurveillanceConfig::default());
+        s.link_accounts(Address::repeat_byte(5), Address::repeat_byte(6));
+
+        trade(&mut s, 1, 1, Side::Bid, 100, 10, 1);
+        trade(&mut s, 5, 6, Side::Ask, 100, 10, 2);
+        // 2 and 3 pass 20 back and forth
+        for (i, side) in [Side::Bid, Side::Ask, Side::Bid, Side::Ask].into_iter().enumerate() {
+            trade(&mut s, 2, 3, side, 100, 20, 3 + i as u64);
+        }
+        // Ordinary one-way flow is not flagged
+        trade(&mut s, 4, 7, Side::Bid, 100, 50, 8);
+
+        let report = s.report(10);
+        assert_eq!(report.fills, 7);
+        assert_eq!(kinds(&report), vec![FlagKind::SelfMatch, FlagKind::LinkedAccounts, FlagKind::RoundTrip]);
+        assert_eq!(report.flags[2].volume, U256::from(80));
+        assert!(!report.flagged_accounts().contains(&Address::repeat_byte(4)));
+
+        // Fills age out of the window
+        assert!(s.report(10 + 24 * 60 * 60).flags.is_empty());
+    }
+
+    #[test]
+    fn test_circular_flow_and_painting_tape() {
+        let mut s = Surveillance::new(SurveillanceConfig {
+            tape_min_trades: 3,
+            ..SurveillanceConfig::default()
+        });
+
+        // 1 -> 2 -> 3 -> 1 (takers buy from makers)
+        trade(&mut s, 1, 2, Side::Bid, 100, 100, 1);
+        trade(&mut s, 2, 3, Side::Bid, 100, 100, 2);
+        trade(&mut s, 3, 1, Side::Bid, 100, 95, 3);
+        // 9 prints size-1 trades that each move the last price
+        for (i, price) in [101, 102, 103].into_iter().enumerate() {
+            trade(&mut s, 4, 9, Side::Bid, price, 1, 4 + i as u64);
+        }
+
+        let report = s.report(10);
+        assert_eq!(
+            kinds(&report),
+            vec![FlagKind::CircularFlow, FlagKind::PaintingTape { asset: AssetId(1) }]
+        );
+        assert_eq!(report.flags[0].accounts.len(), 3);
+        assert_eq!(report.flags[1].accounts, vec![Address::repeat_byte(9)]);
+
+        let mut emissions = EmissionsEngine::default();
+        s.apply_penalties(&report, &mut emissions);
+        assert_eq!(emissions.penalty(&Address::repeat_byte(9)), 10000);
+        assert_eq!(emissions.penalty(&Address::repeat_byte(4)), 0);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::types::{Order, OrderId};
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Index of a slot in an `OrderArena`
+pub type SlotId = u32;
+
+/// Arena node: an order plus intrusive links to its neighbours in a price level
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub(crate) order: Order,
+    pub(crate) prev: Option<SlotId>,
+    pub(crate) next: Option<SlotId>,
+}
+
+/// Pooled storage for resting orders.
+///
+/// Freed slots are recycled through a free list, so a book in steady state
+/// (orders added and removed at a similar rate) does not allocate per order.
+#[derive(Debug, Clone, Default)]
+pub struct OrderArena {
+    slots: Vec<Option<Node>>,
+    /// Vacant slots, most recently freed last
+    free: Vec<SlotId>,
+}
+
+impl OrderArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an arena with room for `capacity` orders
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+        }
+    }
+
+    /// Store an (unlinked) order, reusing a free slot if available
+    pub fn insert(&mut self, order: Order) -> SlotId {
+        let node = Some(Node { order, prev: None, next: None });
+
+        match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot as usize] = node;
+                slot
+            }
+            None => {
+                self.slots.push(node);
+                (self.slots.len() - 1) as SlotId
+            }
+        }
+    }
+
+    /// Remove an order and return its slot to the free list
+    pub fn remove(&mut self, slot: SlotId) -> Option<Order> {
+        self.take(slot).map(|node| node.order)
+    }
+
+    /// Get order in slot
+    pub fn get(&self, slot: SlotId) -> Option<&Order> {
+        self.node(slot).map(|n| &n.order)
+    }
+
+    /// Get mutable order in slot
+    pub fn get_mut(&mut self, slot: SlotId) -> Option<&mut Order> {
+        self.node_mut(slot).map(|n| &mut n.order)
+    }
+
+    /// Number of live orders
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of slots allocated (live + free)
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn take(&mut self, slot: SlotId) -> Option<Node> {
+        let node = self.slots.get_mut(slot as usize)?.take()?;
+        self.free.push(slot);
+        Some(node)
+    }
+
+    pub(crate) fn node(&self, slot: SlotId) -> Option<&Node> {
+        self.slots.get(slot as usize)?.as_ref()
+    }
+
+    pub(crate) fn node_mut(&mut self, slot: SlotId) -> Option<&mut Node> {
+        self.slots.get_mut(slot as usize)?.as_mut()
+    }
+}
+
+/// Hasher for order IDs.
+///
+/// IDs are assigned sequentially by the book (never chosen by users), so a
+/// single multiply is enough to spread them and much cheaper than SipHash.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrderIdHasher(u64);
+
+impl Hasher for OrderIdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(b as u64);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+/// Map keyed by order ID
+pub type OrderIdMap<V> = HashMap<OrderId, V, BuildHasherDefault<OrderIdHasher>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+    use alloy_primitives::{Address, U256};
+
+    fn order(id: OrderId) -> Order {
+        Order::new(id, AssetId(1), Address::ZERO, Side::Bid, Price(1_000_000), Size(U256::from(1)), 0)
+    }
+
+    #[test]
+    fn test_insert_remove_reuses_slots() {
+        let mut arena = OrderArena::new();
+        let a = arena.insert(order(1));
+        let b = arena.insert(order(2));
+        assert_eq!(arena.len(), 2);
+
+        assert_eq!(arena.remove(a).unwrap().id, 1);
+        assert!(arena.get(a).is_none());
+        assert!(arena.remove(a).is_none());
+
+        // Freed slot is recycled instead of growing the arena
+        let c = arena.insert(order(3));
+        assert_eq!(c, a);
+        assert_eq!(arena.capacity(), 2);
+        assert_eq!(arena.get(b).unwrap().id, 2);
+        assert_eq!(arena.get(c).unwrap().id, 3);
+    }
+
+    #[test]
+    fn test_free_list_is_lifo() {
+        let mut arena = OrderArena::with_capacity(4);
+        let slots: Vec<_> = (1..=3).map(|i| arena.insert(order(i))).collect();
+        arena.remove(slots[0]);
+        arena.remove(slots[2]);
+
+        assert_eq!(arena.insert(order(4)), slots[2]);
+        assert_eq!(arena.insert(order(5)), slots[0]);
+        assert_eq!(arena.insert(order(6)), 3);
+        assert_eq!(arena.len(), 4);
+    }
+}
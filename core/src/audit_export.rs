@@ -0,0 +1,315 @@
+// Audit Log Export
+//
+// Writes the engine's audit trail (emergency control invocations, position
+// transfer ledger entries and net funding settlements) to files for
+// compliance, accounting and analytics pipelines. Records are partitioned
+// by day or by fixed-length epoch of their timestamp and written as
+// newline-delimited JSON, appended to the partition's file, or with the
+// `parquet` feature as one Parquet file per partition and export run. Every
+// record carries `EXPORT_SCHEMA_VERSION`, bumped whenever a record's fields
+// change so consumers can tell layouts apart.
+
+use crate::emergency::AuditEntry;
+use crate::funding::NetFundingSettlement;
+use crate::transfer::LedgerEntry;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Version of the exported record layout
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Exported audit record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "record", rename_all = "snake_case")]
+pub enum ExportRecord {
+    /// Emergency control invocation
+    Audit(AuditEntry),
+    /// Position transfer ledger entry
+    Ledger(LedgerEntry),
+    /// Net funding collateral settlement
+    FundingSettlement(NetFundingSettlement),
+}
+
+impl ExportRecord {
+    /// Record type, as written to the `kind` field
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Audit(_) => "audit",
+            Self::Ledger(_) => "ledger",
+            Self::FundingSettlement(_) => "funding_settlement",
+        }
+    }
+
+    /// Block timestamp of the record
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Self::Audit(entry) => entry.timestamp,
+            Self::Ledger(entry) => entry.timestamp,
+            Self::FundingSettlement(settlement) => settlement.timestamp,
+        }
+    }
+}
+
+/// One exported line: the record with its schema version
+#[derive(Serialize)]
+struct ExportLine<'a> {
+    schema_version: u32,
+    timestamp: u64,
+    #[serde(flatten)]
+    record: &'a ExportRecord,
+}
+
+/// Output file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Newline-delimited JSON
+    NdJson,
+    /// Parquet with columns `schema_version`, `kind`, `timestamp` and the
+    /// JSON-encoded `record`
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// How records are split into files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportPartition {
+    /// One partition per UTC day, named `YYYY-MM-DD`
+    Day,
+    /// One partition per `seconds`-long epoch, named `epoch-N`
+    Epoch(u64),
+}
+
+impl ExportPartition {
+    /// Name of the partition holding `timestamp`
+    pub fn label(&self, timestamp: u64) -> String {
+        match self {
+            Self::Day => {
+                let (year, month, day) = civil_date(timestamp / SECONDS_PER_DAY);
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            }
+            Self::Epoch(seconds) => format!("epoch-{}", timestamp / (*seconds).max(1)),
+        }
+    }
+}
+
+/// Calendar date of a day count since 1970-01-01
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Writes audit records into partitioned files under a directory
+#[derive(Debug, Clone)]
+pub struct AuditExporter {
+    dir: PathBuf,
+    format: ExportFormat,
+    partition: ExportPartition,
+}
+
+impl AuditExporter {
+    pub fn new(dir: impl Into<PathBuf>, format: ExportFormat, partition: ExportPartition) -> Self {
+        Self {
+            dir: dir.into(),
+            format,
+            partition,
+        }
+    }
+
+    /// Export `records`, returning the files written to
+    pub fn export(&self, records: impl IntoIterator<Item = ExportRecord>) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(&self.dir)?;
+        match self.format {
+            ExportFormat::NdJson => self.export_ndjson(records),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => self.export_parquet(records),
+        }
+    }
+
+    /// Stream records into each partition's file, appending to earlier
+    /// exports of the same partition
+    fn export_ndjson(&self, records: impl IntoIterator<Item = ExportRecord>) -> Result<Vec<PathBuf>> {
+        let mut writers: BTreeMap<PathBuf, BufWriter<File>> = BTreeMap::new();
+        for record in records {
+            let path = self.dir.join(format!("{}.ndjson", self.partition.label(record.timestamp())));
+            let writer = match writers.entry(path) {
+                std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    let file = OpenOptions::new().create(true).append(true).open(entry.key())?;
+                    entry.insert(BufWriter::new(file))
+                }
+            };
+            let line = ExportLine {
+                schema_version: EXPORT_SCHEMA_VERSION,
+                timestamp: record.timestamp(),
+                record: &record,
+            };
+            serde_json::to_writer(&mut *writer, &line)?;
+            writer.write_all(b"\n")?;
+        }
+
+        let mut paths = Vec::with_capacity(writers.len());
+        for (path, mut writer) in writers {
+            writer.flush()?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Write each partition's records to a new Parquet file
+    #[cfg(feature = "parquet")]
+    fn export_parquet(&self, records: impl IntoIterator<Item = ExportRecord>) -> Result<Vec<PathBuf>> {
+        let mut partitions: BTreeMap<String, Vec<ExportRecord>> = BTreeMap::new();
+        for record in records {
+            partitions.entry(self.partition.label(record.timestamp())).or_default().push(record);
+        }
+
+        let mut paths = Vec::with_capacity(partitions.len());
+        for (label, records) in partitions {
+            // Parquet files cannot be appended to; later runs add parts
+            let path = (0..)
+                .map(|part| self.dir.join(format!("{}-part-{}.parquet", label, part)))
+                .find(|path| !path.exists())
+                .expect("unbounded part numbers");
+            parquet_file::write(&path, &records)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_file {
+    use super::{ExportRecord, EXPORT_SCHEMA_VERSION};
+    use anyhow::{anyhow, Result};
+    use parquet::data_type::{ByteArray, ByteArrayType, DataType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+    use parquet::schema::parser::parse_message_type;
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    const SCHEMA: &str = "
+        message audit_record {
+            required int32 schema_version;
+            required binary kind (UTF8);
+            required int64 timestamp;
+            required binary record (UTF8);
+        }
+    ";
+
+    fn write_column<T: DataType>(row_group: &mut SerializedRowGroupWriter<'_, File>, values: &[T::T]) -> Result<()> {
+        let mut column = row_group.next_column()?.ok_or_else(|| anyhow!("Parquet schema has too few columns"))?;
+        column.typed::<T>().write_batch(values, None, None)?;
+        column.close()?;
+        Ok(())
+    }
+
+    pub(super) fn write(path: &Path, records: &[ExportRecord]) -> Result<()> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(File::create(path)?, schema, properties)?;
+
+        let mut encoded = Vec::with_capacity(records.len());
+        for record in records {
+            // The `record` column holds the record's own fields, without the kind tag
+            let value = serde_json::to_value(record)?;
+            encoded.push(ByteArray::from(serde_json::to_vec(&value["record"])?));
+        }
+        let mut row_group = writer.next_row_group()?;
+        write_column::<Int32Type>(&mut row_group, &vec![EXPORT_SCHEMA_VERSION as i32; records.len()])?;
+        write_column::<ByteArrayType>(&mut row_group, &records.iter().map(|r| ByteArray::from(r.kind())).collect::<Vec<_>>())?;
+        write_column::<Int64Type>(&mut row_group, &records.iter().map(|r| r.timestamp() as i64).collect::<Vec<_>>())?;
+        write_column::<ByteArrayType>(&mut row_group, &encoded)?;
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// Read back an NDJSON export, skipping lines of other schema versions
+pub fn read_ndjson(path: &Path) -> Result<Vec<ExportRecord>> {
+    let mut records = Vec::new();
+    for line in std::fs::read_to_string(path)?.lines() {
+        let value: serde_json::Value = serde_json::from_str(line)?;
+        if value["schema_version"] != EXPORT_SCHEMA_VERSION {
+            continue;
+        }
+        records.push(serde_json::from_value(value)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AssetId;
+    use alloy_primitives::Address;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir() -> PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        std::env::temp_dir().join(format!("openliquid_test_audit_export_{}", nanos))
+    }
+
+    fn settlement(timestamp: u64, amount: i64) -> ExportRecord {
+        ExportRecord::FundingSettlement(NetFundingSettlement {
+            user: Address::from([1u8; 20]),
+            amount,
+            assets: vec![AssetId(1)],
+            timestamp,
+        })
+    }
+
+    #[test]
+    fn test_ndjson_export_partitions_by_day_and_appends() {
+        let dir = temp_dir();
+        let exporter = AuditExporter::new(&dir, ExportFormat::NdJson, ExportPartition::Day);
+        // 2024-01-01T00:00:00Z and an hour before it
+        let day = 1_704_067_200;
+
+        let paths = exporter.export([settlement(day - 3600, -5), settlement(day + 60, 7)]).unwrap();
+        assert_eq!(paths, vec![dir.join("2023-12-31.ndjson"), dir.join("2024-01-01.ndjson")]);
+        exporter.export([settlement(day + 120, 9)]).unwrap();
+
+        let records = read_ndjson(&dir.join("2024-01-01.ndjson")).unwrap();
+        assert_eq!(records, vec![settlement(day + 60, 7), settlement(day + 120, 9)]);
+        let line = std::fs::read_to_string(&paths[0]).unwrap();
+        assert!(line.starts_with(r#"{"schema_version":1,"timestamp":1704063600,"kind":"funding_settlement""#));
+        assert_eq!(ExportPartition::Epoch(3600).label(day + 60), "epoch-473352");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_export_writes_new_part_per_run() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let dir = temp_dir();
+        let exporter = AuditExporter::new(&dir, ExportFormat::Parquet, ExportPartition::Epoch(100));
+        let first = exporter.export([settlement(10, 1), settlement(20, 2)]).unwrap();
+        let second = exporter.export([settlement(30, 3)]).unwrap();
+        assert_eq!(first, vec![dir.join("epoch-0-part-0.parquet")]);
+        assert_eq!(second, vec![dir.join("epoch-0-part-1.parquet")]);
+
+        let reader = SerializedFileReader::new(File::open(&first[0]).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert_eq!(reader.metadata().file_metadata().schema_descr().num_columns(), 4);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+use crate::types::*;
+use alloy_primitives::{keccak256, Address, PrimitiveSignature as Signature, B256, U256};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Domain separator for core action signing hashes
+const CORE_ACTION_DOMAIN: &[u8] = b"OPENLIQUID_CORE_ACTION_V1";
+
+/// What a session key may do on behalf of its account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionScope {
+    /// Place and cancel orders only
+    OrderOnly,
+    /// Anything except moving funds out and managing keys
+    NoWithdrawal,
+}
+
+/// Hot key registered by an account's master key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionKey {
+    pub key: Address,
+    pub scope: SessionScope,
+    /// Block timestamp after which the key is rejected
+    pub expires_at: u64,
+}
+
+/// User operation dispatched by `CoreStateMachine::dispatch`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CoreAction {
+    PlaceLimitOrder { asset: AssetId, side: Side, price: Price, size: Size },
+    PlaceMarketOrder { asset: AssetId, side: Side, size: Size },
+    CancelOrder { asset: AssetId, order_id: OrderId },
+    SetOrderExpiry { asset: AssetId, order_id: OrderId, expires_at: u64 },
+    Withdraw { asset: AssetId, amount: U256 },
+    RegisterSessionKey { key: Address, scope: SessionScope, expires_at: u64 },
+    RevokeSessionKey { key: Address },
+}
+
+impl CoreAction {
+    /// Whether a session key with `scope` may perform this action
+    pub fn allowed_for(&self, scope: SessionScope) -> bool {
+        match self {
+            Self::PlaceLimitOrder { .. } | Self::PlaceMarketOrder { .. } | Self::CancelOrder { .. } => true,
+            Self::SetOrderExpiry { .. } => scope == SessionScope::NoWithdrawal,
+            // Master key only
+            Self::Withdraw { .. } | Self::RegisterSessionKey { .. } | Self::RevokeSessionKey { .. } => false,
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::PlaceLimitOrder { asset, side, price, size } => {
+                buf.push(0);
+                buf.extend_from_slice(&asset.0.to_be_bytes());
+                buf.push(*side as u8);
+                buf.extend_from_slice(&price.0.to_be_bytes());
+                buf.extend_from_slice(&size.0.to_be_bytes::<32>());
+            }
+            Self::PlaceMarketOrder { asset, side, size } => {
+                buf.push(1);
+                buf.extend_from_slice(&asset.0.to_be_bytes());
+                buf.push(*side as u8);
+                buf.extend_from_slice(&size.0.to_be_bytes::<32>());
+            }
+            Self::CancelOrder { asset, order_id } => {
+                buf.push(2);
+                buf.extend_from_slice(&asset.0.to_be_bytes());
+                buf.extend_from_slice(&order_id.to_be_bytes());
+            }
+            Self::SetOrderExpiry { asset, order_id, expires_at } => {
+                buf.push(3);
+                buf.extend_from_slice(&asset.0.to_be_bytes());
+                buf.extend_from_slice(&order_id.to_be_bytes());
+                buf.extend_from_slice(&expires_at.to_be_bytes());
+            }
+            Self::Withdraw { asset, amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&asset.0.to_be_bytes());
+                buf.extend_from_slice(&amount.to_be_bytes::<32>());
+            }
+            Self::RegisterSessionKey { key, scope, expires_at } => {
+                buf.push(5);
+                buf.extend_from_slice(key.as_slice());
+                buf.push(*scope as u8);
+                buf.extend_from_slice(&expires_at.to_be_bytes());
+            }
+            Self::RevokeSessionKey { key } => {
+                buf.push(6);
+                buf.extend_from_slice(key.as_slice());
+            }
+        }
+    }
+}
+
+/// Result of a dispatched action
+#[derive(Debug, Clone)]
+pub enum ActionOutcome {
+    OrderPlaced { order_id: OrderId, fills: Vec<Fill> },
+    MarketFilled { fills: Vec<Fill> },
+    OrderCancelled { order: Order },
+    OrderExpirySet,
+    Withdrawn,
+    SessionKeyRegistered,
+    SessionKeyRevoked,
+}
+
+/// Action signed by an account's master key or one of its session keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedAction {
+    pub account: Address,
+    /// Per-account API nonce for replay protection
+    pub nonce: u64,
+    pub action: CoreAction,
+    pub signature: Signature,
+}
+
+impl SignedAction {
+    /// Hash the signer signs over
+    ///
+    /// Prefixed with the domain tag and chain ID so a signature can't be
+    /// replayed as another message type or on another chain.
+    pub fn signing_hash(chain_id: u64, account: &Address, nonce: u64, action: &CoreAction) -> B256 {
+        let mut buf = Vec::with_capacity(CORE_ACTION_DOMAIN.len() + 104);
+        buf.extend_from_slice(CORE_ACTION_DOMAIN);
+        buf.extend_from_slice(&chain_id.to_be_bytes());
+        buf.extend_from_slice(account.as_slice());
+        buf.extend_from_slice(&nonce.to_be_bytes());
+        action.encode(&mut buf);
+        keccak256(&buf)
+    }
+
+    /// Recover the signing key's address for an action on `chain_id`
+    pub fn signer(&self, chain_id: u64) -> Result<Address> {
+        let hash = Self::signing_hash(chain_id, &self.account, self.nonce, &self.action);
+        self.signature
+            .recover_address_from_prehash(&hash)
+            .map_err(|e| anyhow!("Invalid action signature: {}", e))
+    }
+}
+
+/// Per-account API nonces and session keys
+#[derive(Debug, Clone, Default)]
+pub struct SessionRegistry {
+    /// Next expected nonce per account
+    nonces: HashMap<Address, u64>,
+    /// Session keys per account
+    sessions: HashMap<Address, Vec<SessionKey>>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get next expected nonce for account
+    pub fn next_nonce(&self, account: &Address) -> u64 {
+        self.nonces.get(account).copied().unwrap_or(0)
+    }
+
+    /// Get an account's session keys
+    pub fn session_keys(&self, account: &Address) -> &[SessionKey] {
+        self.sessions.get(account).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Check the signature, nonce and signer permissions of an action
+    pub fn authorize(&self, chain_id: u64, signed: &SignedAction, now: u64) -> Result<()> {
+        let expected = self.next_nonce(&signed.account);
+        if signed.nonce != expected {
+            return Err(anyhow!(
+                "Invalid action nonce: expected {}, got {}",
+                expected,
+                signed.nonce
+            ));
+        }
+        self.authorize_signer(chain_id, signed, now)
+    }
+
+    /// Check the signature and signer permissions of an action, ignoring
+    /// its nonce
+    pub fn authorize_signer(&self, chain_id: u64, signed: &SignedAction, now: u64) -> Result<()> {
+        let signer = signed.signer(chain_id)?;
+        if signer == signed.account {
+            return Ok(());
+        }
+
+        let session = self
+            .session_keys(&signed.account)
+            .iter()
+            .find(|s| s.key == signer)
+            .ok_or_else(|| anyhow!("Signer is not authorized for account"))?;
+        if now > session.expires_at {
+            return Err(anyhow!("Session key expired"));
+        }
+        if !signed.action.allowed_for(session.scope) {
+            return Err(anyhow!("Session key scope does not allow this action"));
+        }
+        Ok(())
+    }
+
+    /// Restore nonces and session keys loaded from storage
+    pub fn restore(
+        &mut self,
+        nonces: impl IntoIterator<Item = (Address, u64)>,
+        sessions: impl IntoIterator<Item = (Address, Vec<SessionKey>)>,
+    ) {
+        self.nonces.extend(nonces);
+        self.sessions.extend(sessions);
+    }
+
+    /// Consume an account's nonce
+    pub fn consume_nonce(&mut self, account: Address) {
+        *self.nonces.entry(account).or_insert(0) += 1;
+    }
+
+    /// Add or replace a session key
+    pub fn register(&mut self, account: Address, session: SessionKey) -> Result<()> {
+        if session.key == account {
+            return Err(anyhow!("Master key cannot be registered as a session key"));
+        }
+        let keys = self.sessions.entry(account).or_default();
+        keys.retain(|s| s.key != session.key);
+        keys.push(session);
+        Ok(())
+    }
+
+    /// Remove a session key
+    pub fn revoke(&mut self, account: &Address, key: &Address) -> Result<()> {
+        let keys = self.sessions.get_mut(account).ok_or_else(|| anyhow!("Session key not found"))?;
+        let before = keys.len();
+        keys.retain(|s| s.key != *key);
+        if keys.len() == before {
+            return Err(anyhow!("Session key not found"));
+        }
+        Ok(())
+    }
+
+    /// Drop session keys that expired before `now`, returning the accounts
+    /// that lost keys in address order
+    pub fn prune_expired(&mut self, now: u64) -> Vec<Address> {
+        let mut pruned = Vec::new();
+        for (account, keys) in self.sessions.iter_mut() {
+            let before = keys.len();
+            keys.retain(|s| s.expires_at >= now);
+            if keys.len() != before {
+                pruned.push(*account);
+            }
+        }
+        self.sessions.retain(|_, keys| !keys.is_empty());
+        pruned.sort();
+        pruned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+
+    fn key(seed: u8) -> (SigningKey, Address) {
+        let sk = SigningKey::from_slice(&[seed; 32]).unwrap();
+        let addr = Address::from_private_key(&sk);
+        (sk, addr)
+    }
+
+    fn sign(sk: &SigningKey, account: Address, nonce: u64, action: CoreAction) -> SignedAction {
+        let hash = SignedAction::signing_hash(DEFAULT_CHAIN_ID, &account, nonce, &action);
+        let (sig, recid) = sk.sign_prehash_recoverable(hash.as_slice()).unwrap();
+        SignedAction {
+            account,
+            nonce,
+            action,
+            signature: Signature::from_signature_and_parity(sig, recid.is_y_odd()),
+        }
+    }
+
+    #[test]
+    fn test_session_scope_and_expiry() {
+        let (master_sk, account) = key(1);
+        let (hot_sk, hot) = key(2);
+        let mut registry = SessionRegistry::new();
+        let order = CoreAction::CancelOrder { asset: AssetId(1), order_id: 7 };
+        let withdraw = CoreAction::Withdraw { asset: AssetId(0), amount: U256::from(1) };
+
+        // Unregistered hot key
+        assert!(registry.authorize(DEFAULT_CHAIN_ID, &sign(&hot_sk, account, 0, order.clone()), 0).is_err());
+
+        registry
+            .register(account, SessionKey { key: hot, scope: SessionScope::OrderOnly, expires_at: 100 })
+            .unwrap();
+        registry.authorize(DEFAULT_CHAIN_ID, &sign(&hot_sk, account, 0, order.clone()), 100).unwrap();
+        assert!(registry.authorize(DEFAULT_CHAIN_ID, &sign(&hot_sk, account, 0, withdraw.clone()), 100).is_err());
+        assert!(registry.authorize(DEFAULT_CHAIN_ID, &sign(&hot_sk, account, 0, order.clone()), 101).is_err());
+        registry.authorize(DEFAULT_CHAIN_ID, &sign(&master_sk, account, 0, withdraw.clone()), 101).unwrap();
+
+        // Signatures are bound to the chain they were made for
+        assert!(registry.authorize(DEFAULT_CHAIN_ID + 1, &sign(&master_sk, account, 0, withdraw), 101).is_err());
+
+        // Nonces are sequential per account
+        registry.consume_nonce(account);
+        assert!(registry.authorize(DEFAULT_CHAIN_ID, &sign(&master_sk, account, 0, order.clone()), 0).is_err());
+        registry.authorize(DEFAULT_CHAIN_ID, &sign(&master_sk, account, 1, order), 0).unwrap();
+
+        assert_eq!(registry.prune_expired(101), vec![account]);
+        assert!(registry.session_keys(&account).is_empty());
+        assert!(registry.revoke(&account, &hot).is_err());
+    }
+}
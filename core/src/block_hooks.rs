@@ -0,0 +1,88 @@
+use crate::analytics::liquidity::MarketMode;
+use crate::delisting::DelistingAction;
+use crate::fees::FeeSettlement;
+use crate::funding::{FundingPayment, NetFundingSettlement};
+use crate::options::OptionSettlement;
+use crate::types::*;
+use anyhow::Result;
+
+/// Block lifecycle hooks invoked by the node orchestrator around the
+/// transactions of each committed block.
+///
+/// Implementations must be deterministic: every node runs the same
+/// scheduled tasks, in the same order, for the same block.
+pub trait BlockHooks {
+    /// Called before the block's transactions are applied
+    fn on_block_begin(&mut self, height: u64, timestamp: u64) -> Result<BlockBeginReport>;
+
+    /// Called after the block's transactions are applied
+    fn on_block_end(&mut self) -> Result<BlockEndReport>;
+}
+
+/// Scheduled work done at the start of a block
+#[derive(Debug, Clone, Default)]
+pub struct BlockBeginReport {
+    pub height: u64,
+    /// Good-til-time orders cancelled by the expiry sweep
+    pub expired_orders: Vec<(AssetId, OrderId)>,
+    /// Delisting steps executed
+    pub delisting_actions: Vec<DelistingAction>,
+    /// Markets due for settlement whose TWAP is not available yet; they
+    /// are retried at the next block
+    pub deferred_settlements: Vec<AssetId>,
+    /// Emissions epoch finalized at this block, if any
+    pub emissions_epoch: Option<u64>,
+}
+
+/// Triggered advanced order and its execution result
+#[derive(Debug, Clone)]
+pub struct TriggeredOrder {
+    pub id: OrderId,
+    pub asset: AssetId,
+    /// Fills on success, error message if execution was rejected
+    pub result: std::result::Result<Vec<Fill>, String>,
+}
+
+/// Market switched to a new trading mode by the liquidity monitor
+#[derive(Debug, Clone)]
+pub struct MarketModeChange {
+    pub asset: AssetId,
+    pub mode: MarketMode,
+    /// Fills of the uncrossing auction when the market left auction mode
+    pub auction_fills: Vec<Fill>,
+}
+
+/// Batch auction run at the end of a block
+#[derive(Debug, Clone)]
+pub struct BatchAuction {
+    pub asset: AssetId,
+    /// Single price every fill of the batch printed at
+    pub clearing_price: Price,
+    pub fills: Vec<Fill>,
+}
+
+/// Scheduled work done at the end of a block
+#[derive(Debug, Clone, Default)]
+pub struct BlockEndReport {
+    pub height: u64,
+    /// Advanced orders triggered this block, in order ID order
+    pub triggered_orders: Vec<TriggeredOrder>,
+    /// Funding accrued this block per position, for accounts not netted
+    pub funding_payments: Vec<FundingPayment>,
+    /// Funding netted per cross margin account this block
+    pub funding_settlements: Vec<NetFundingSettlement>,
+    /// Option positions cash-settled at expiry this block
+    pub option_settlements: Vec<OptionSettlement>,
+    /// Positions liquidated by margin monitoring
+    pub liquidations: Vec<Liquidation>,
+    /// Epoch of the account snapshots taken this block, if any
+    pub snapshot_epoch: Option<u64>,
+    /// Timestamp of the order book depth snapshot taken this block, if any
+    pub depth_snapshot: Option<u64>,
+    /// Markets whose trading mode changed on their liquidity SLA
+    pub market_mode_changes: Vec<MarketModeChange>,
+    /// Batch auctions that cleared this block, by asset
+    pub batch_auctions: Vec<BatchAuction>,
+    /// Fees collected this block and their distribution
+    pub fee_settlement: FeeSettlement,
+}
@@ -0,0 +1,331 @@
+//! Order book reconstruction from the L3 event stream.
+//!
+//! Every change to a resting order of an asset (add, cancel, fill) is
+//! published with a per-asset sequence number that increases by one per
+//! event. [`BookBuilder`] applies them to a local [`OrderBook`], so the
+//! market-data server, read replicas and client consumers all rebuild books
+//! the same way.
+//!
+//! A skipped sequence number means the local book can no longer be trusted.
+//! The builder then stops applying events and buffers them until it is
+//! resynced from an [`L3Snapshot`]; buffered events newer than the snapshot
+//! are replayed on top of it.
+
+use crate::orderbook::OrderBook;
+use crate::types::*;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Events buffered while waiting for a snapshot
+pub const DEFAULT_MAX_PENDING: usize = 10_000;
+
+/// Change to one resting order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum L3Change {
+    /// Order starts resting on the book (with any fill it received on entry)
+    Add(Order),
+    /// Order leaves the book unfilled
+    Cancel { order_id: OrderId },
+    /// Resting order is (partially) filled; it leaves the book once fully filled
+    Fill { order_id: OrderId, size: Size },
+}
+
+/// One entry of an asset's L3 stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct L3Event {
+    pub asset: AssetId,
+    /// Position in the asset's stream, starting at 1
+    pub seq: u64,
+    pub change: L3Change,
+}
+
+/// Every resting order of an asset as of a stream position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct L3Snapshot {
+    pub asset: AssetId,
+    /// Sequence number of the last event reflected in `orders`
+    pub seq: u64,
+    /// Price-time order, bids then asks
+    pub orders: Vec<Order>,
+}
+
+impl L3Snapshot {
+    /// Snapshot a book that has applied the stream up to `seq`
+    pub fn from_book(book: &OrderBook, seq: u64) -> Self {
+        Self {
+            asset: book.asset,
+            seq,
+            orders: book.orders().cloned().collect(),
+        }
+    }
+}
+
+/// Whether the builder's book follows the stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookStatus {
+    /// Every event up to `seq()` has been applied
+    Live,
+    /// An event was missed or could not be applied; a snapshot at or after
+    /// `seq()` is needed before events are applied again
+    Stale,
+}
+
+/// Outcome of feeding one event to the builder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applied {
+    /// Applied to the book
+    Applied,
+    /// Already reflected in the book (redelivery or older than the snapshot)
+    Duplicate,
+    /// Sequence gap detected; the event is buffered and the book is stale
+    Gap { expected: u64, received: u64 },
+    /// Buffered until the next resync
+    Buffered,
+}
+
+/// Reconstructs one asset's book from its L3 stream
+#[derive(Debug, Clone)]
+pub struct BookBuilder {
+    book: OrderBook,
+    seq: u64,
+    status: BookStatus,
+    pending: VecDeque<L3Event>,
+    max_pending: usize,
+}
+
+impl BookBuilder {
+    /// Build from the start of the stream (an empty book at sequence 0)
+    pub fn new(asset: AssetId) -> Self {
+        Self {
+            book: OrderBook::with_self_trade_prevention(asset, false),
+            seq: 0,
+            status: BookStatus::Live,
+            pending: VecDeque::new(),
+            max_pending: DEFAULT_MAX_PENDING,
+        }
+    }
+
+    /// Build from a snapshot, applying the events that follow it
+    pub fn from_snapshot(snapshot: L3Snapshot) -> Result<Self> {
+        let mut builder = Self::new(snapshot.asset);
+        builder.resync(snapshot)?;
+        Ok(builder)
+    }
+
+    /// Limit the events buffered while stale; the oldest are dropped first
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+
+    pub fn asset(&self) -> AssetId {
+        self.book.asset
+    }
+
+    /// Sequence number of the last applied event
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn status(&self) -> BookStatus {
+        self.status
+    }
+
+    /// Reconstructed book; only current while `Live`
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    /// Snapshot of the reconstructed book, e.g. to serve downstream consumers
+    pub fn snapshot(&self) -> L3Snapshot {
+        L3Snapshot::from_book(&self.book, self.seq)
+    }
+
+    /// Feed the next event of the stream. Errors for events of another asset
+    /// and for events the book cannot apply, which also mark it stale.
+    pub fn apply(&mut self, event: L3Event) -> Result<Applied> {
+        if event.asset != self.book.asset {
+            return Err(anyhow!("Event for asset {:?} fed to book of {:?}", event.asset, self.book.asset));
+        }
+        if event.seq <= self.seq {
+            return Ok(Applied::Duplicate);
+        }
+        if self.status == BookStatus::Stale {
+            self.buffer(event);
+            return Ok(Applied::Buffered);
+        }
+        if event.seq != self.seq + 1 {
+            let (expected, received) = (self.seq + 1, event.seq);
+            self.status = BookStatus::Stale;
+            self.buffer(event);
+            return Ok(Applied::Gap { expected, received });
+        }
+
+        self.apply_change(event)?;
+        Ok(Applied::Applied)
+    }
+
+    /// Replace the book with a snapshot and replay buffered events after it.
+    /// The book stays stale if the buffer does not continue the snapshot.
+    pub fn resync(&mut self, snapshot: L3Snapshot) -> Result<()> {
+        if snapshot.asset != self.book.asset {
+            return Err(anyhow!("Snapshot for asset {:?} fed to book of {:?}", snapshot.asset, self.book.asset));
+        }
+
+        let mut book = OrderBook::with_self_trade_prevention(snapshot.asset, false);
+        for order in snapshot.orders {
+            book.restore_order(order);
+        }
+        book.update_cache_after_restore();
+        self.book = book;
+        self.seq = snapshot.seq;
+        self.status = BookStatus::Live;
+
+        let mut pending: Vec<L3Event> = std::mem::take(&mut self.pending).into();
+        pending.sort_by_key(|event| event.seq);
+        let mut pending = pending.into_iter();
+        while let Some(event) = pending.next() {
+            if event.seq <= self.seq {
+                continue;
+            }
+            if event.seq != self.seq + 1 {
+                self.status = BookStatus::Stale;
+                self.pending.push_back(event);
+                self.pending.extend(pending);
+                break;
+            }
+            self.apply_change(event)?;
+        }
+        Ok(())
+    }
+
+    fn apply_change(&mut self, event: L3Event) -> Result<()> {
+        let applied = match event.change {
+            L3Change::Add(order) if order.asset != self.book.asset || self.book.contains_order(order.id) => {
+                Err(anyhow!("Cannot add order {}", order.id))
+            }
+            L3Change::Add(order) => {
+                self.book.restore_order(order);
+                Ok(())
+            }
+            L3Change::Cancel { order_id } => self.book.cancel_order(order_id).map(|_| ()),
+            L3Change::Fill { order_id, size } => self.book.fill_order(order_id, size).map(|_| ()),
+        };
+        match applied {
+            Ok(()) => {
+                self.seq = event.seq;
+                Ok(())
+            }
+            Err(e) => {
+                // The book diverged from the stream
+                self.status = BookStatus::Stale;
+                Err(anyhow!("Event {} of asset {:?} does not apply: {}", event.seq, event.asset, e))
+            }
+        }
+    }
+
+    fn buffer(&mut self, event: L3Event) {
+        if self.pending.len() >= self.max_pending {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, U256};
+
+    const ASSET: AssetId = AssetId(1);
+
+    fn order(id: OrderId, side: Side, price: u64, size: u64) -> Order {
+        Order::new(id, ASSET, Address::ZERO, side, Price(price), Size(U256::from(size)), id)
+    }
+
+    fn add(seq: u64, order: Order) -> L3Event {
+        L3Event { asset: ASSET, seq, change: L3Change::Add(order) }
+    }
+
+    fn fill(seq: u64, order_id: OrderId, size: u64) -> L3Event {
+        L3Event { asset: ASSET, seq, change: L3Change::Fill { order_id, size: Size(U256::from(size)) } }
+    }
+
+    fn cancel(seq: u64, order_id: OrderId) -> L3Event {
+        L3Event { asset: ASSET, seq, change: L3Change::Cancel { order_id } }
+    }
+
+    #[test]
+    fn test_builds_book_from_events() {
+        let mut builder = BookBuilder::new(ASSET);
+        builder.apply(add(1, order(1, Side::Bid, 100, 10))).unwrap();
+        builder.apply(add(2, order(2, Side::Bid, 101, 5))).unwrap();
+        builder.apply(add(3, order(3, Side::Ask, 105, 7))).unwrap();
+        assert_eq!(builder.apply(fill(4, 2, 5)).unwrap(), Applied::Applied);
+        builder.apply(fill(5, 1, 4)).unwrap();
+        builder.apply(cancel(6, 3)).unwrap();
+
+        assert_eq!(builder.seq(), 6);
+        assert_eq!(builder.book().get_best_bid(), Some((Price(100), U256::from(6))));
+        assert_eq!(builder.book().best_ask(), None);
+        assert_eq!(builder.book().order_count(), 1);
+
+        // Redelivery is ignored; an impossible event marks the book stale
+        assert_eq!(builder.apply(cancel(6, 3)).unwrap(), Applied::Duplicate);
+        assert!(builder.apply(fill(7, 1, 7)).is_err());
+        assert_eq!(builder.status(), BookStatus::Stale);
+    }
+
+    #[test]
+    fn test_gap_buffers_until_snapshot_resync() {
+        let mut source = BookBuilder::new(ASSET);
+        let mut replica = BookBuilder::new(ASSET);
+        let events = vec![
+            add(1, order(1, Side::Bid, 100, 10)),
+            add(2, order(2, Side::Ask, 105, 10)),
+            fill(3, 1, 3),
+            add(4, order(3, Side::Bid, 99, 1)),
+            cancel(5, 2),
+        ];
+        for event in &events {
+            source.apply(event.clone()).unwrap();
+        }
+
+        replica.apply(events[0].clone()).unwrap();
+        // Event 2 is lost
+        assert_eq!(
+            replica.apply(events[2].clone()).unwrap(),
+            Applied::Gap { expected: 2, received: 3 }
+        );
+        assert_eq!(replica.apply(events[3].clone()).unwrap(), Applied::Buffered);
+        assert_eq!(replica.apply(events[4].clone()).unwrap(), Applied::Buffered);
+
+        // A snapshot after event 3 is continued by buffered events 4 and 5
+        let mut at_three = BookBuilder::new(ASSET);
+        for event in &events[..3] {
+            at_three.apply(event.clone()).unwrap();
+        }
+        replica.resync(at_three.snapshot()).unwrap();
+        assert_eq!(replica.status(), BookStatus::Live);
+        assert_eq!(replica.seq(), 5);
+        assert_eq!(replica.snapshot().orders.len(), source.snapshot().orders.len());
+        let (rebuilt, expected) = (replica.book().snapshot(10), source.book().snapshot(10));
+        assert_eq!(rebuilt.bids, expected.bids);
+        assert_eq!(rebuilt.asks, expected.asks);
+    }
+
+    #[test]
+    fn test_resync_stays_stale_when_buffer_has_a_gap() {
+        let mut builder = BookBuilder::new(ASSET).with_max_pending(1);
+        builder.apply(add(1, order(1, Side::Bid, 100, 10))).unwrap();
+        builder.apply(add(3, order(3, Side::Bid, 100, 1))).unwrap();
+        // Overflow drops event 3
+        builder.apply(add(4, order(4, Side::Bid, 100, 1))).unwrap();
+
+        let snapshot = L3Snapshot { asset: ASSET, seq: 2, orders: vec![order(1, Side::Bid, 100, 10)] };
+        builder.resync(snapshot).unwrap();
+        assert_eq!(builder.status(), BookStatus::Stale);
+        assert_eq!(builder.seq(), 2);
+    }
+}
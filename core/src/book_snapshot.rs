@@ -0,0 +1,331 @@
+//! Compact binary order book snapshots for checkpoints.
+//!
+//! Resting orders are written column by column with fixed-width fields, in
+//! price-time order (bids, then asks). Neighbouring values in a column are
+//! close to each other, which keeps the columns small under general-purpose
+//! compression, and fixed widths let [`BookSnapshotView`] read orders
+//! straight out of the buffer without deserializing it first.
+//!
+//! Layout (integers little-endian, sizes big-endian and trimmed to the
+//! widest size in the book):
+//!
+//! ```text
+//! magic "OLBK" | version u8 | flags u8 | payload (zstd if flags & 1)
+//! payload: asset u32 | next_order_id u64 | traders u32 | orders u32 | size_width u8
+//!          trader addresses [20; traders]
+//!          ids [u64] | prices [u64] | timestamps [u64] | trader index [u32]
+//!          sides [u8] | sizes [size_width] | filled [size_width]
+//! ```
+
+use crate::orderbook::OrderBook;
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+const MAGIC: &[u8; 4] = b"OLBK";
+const FORMAT_VERSION: u8 = 1;
+const FLAG_ZSTD: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 1;
+const PAYLOAD_HEADER_LEN: usize = 4 + 8 + 4 + 4 + 1;
+
+/// Compression applied to snapshot payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapshotCompression {
+    /// Raw columns, read in place on restore
+    #[default]
+    None,
+    /// zstd at the given level
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+/// Encode all resting orders of a book
+pub fn encode_book(book: &OrderBook, compression: SnapshotCompression) -> Result<Vec<u8>> {
+    let orders: Vec<&Order> = book.orders().collect();
+
+    let mut traders = Vec::new();
+    let mut trader_index = HashMap::new();
+    for order in &orders {
+        trader_index.entry(order.trader).or_insert_with(|| {
+            traders.push(order.trader);
+            traders.len() as u32 - 1
+        });
+    }
+    let size_width = orders
+        .iter()
+        .map(|order| byte_width(order.size.0).max(byte_width(order.filled.0)))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let n = orders.len();
+    let mut payload = Vec::with_capacity(
+        PAYLOAD_HEADER_LEN + traders.len() * 20 + n * (8 + 8 + 8 + 4 + 1 + 2 * size_width),
+    );
+    payload.extend_from_slice(&book.asset.0.to_le_bytes());
+    payload.extend_from_slice(&book.next_order_id.to_le_bytes());
+    payload.extend_from_slice(&(traders.len() as u32).to_le_bytes());
+    payload.extend_from_slice(&(n as u32).to_le_bytes());
+    payload.push(size_width as u8);
+    for trader in &traders {
+        payload.extend_from_slice(trader.as_slice());
+    }
+    for order in &orders {
+        payload.extend_from_slice(&order.id.to_le_bytes());
+    }
+    for order in &orders {
+        payload.extend_from_slice(&order.price.0.to_le_bytes());
+    }
+    for order in &orders {
+        payload.extend_from_slice(&order.timestamp.to_le_bytes());
+    }
+    for order in &orders {
+        payload.extend_from_slice(&trader_index[&order.trader].to_le_bytes());
+    }
+    for order in &orders {
+        payload.push(matches!(order.side, Side::Ask) as u8);
+    }
+    for order in &orders {
+        payload.extend_from_slice(&order.size.0.to_be_bytes::<32>()[32 - size_width..]);
+    }
+    for order in &orders {
+        payload.extend_from_slice(&order.filled.0.to_be_bytes::<32>()[32 - size_width..]);
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    match compression {
+        SnapshotCompression::None => {
+            out.push(0);
+            out.extend_from_slice(&payload);
+        }
+        #[cfg(feature = "zstd")]
+        SnapshotCompression::Zstd(level) => {
+            out.push(FLAG_ZSTD);
+            out.extend_from_slice(&zstd::bulk::compress(&payload, level)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Get the column payload of an encoded snapshot, decompressing it if
+/// needed. Uncompressed snapshots are borrowed as-is.
+pub fn snapshot_payload(bytes: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(anyhow!("Not an order book snapshot"));
+    }
+    if bytes[4] != FORMAT_VERSION {
+        return Err(anyhow!("Unsupported book snapshot version {}", bytes[4]));
+    }
+    let body = &bytes[HEADER_LEN..];
+    if bytes[5] & FLAG_ZSTD == 0 {
+        return Ok(Cow::Borrowed(body));
+    }
+    decompress(body).map(Cow::Owned)
+}
+
+#[cfg(feature = "zstd")]
+fn decompress(body: &[u8]) -> Result<Vec<u8>> {
+    Ok(zstd::stream::decode_all(body)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress(_body: &[u8]) -> Result<Vec<u8>> {
+    Err(anyhow!("Book snapshot is zstd-compressed but the zstd feature is disabled"))
+}
+
+/// Read-only view of snapshot columns, borrowing the payload buffer
+#[derive(Debug, Clone, Copy)]
+pub struct BookSnapshotView<'a> {
+    pub asset: AssetId,
+    pub next_order_id: OrderId,
+    len: usize,
+    size_width: usize,
+    traders: &'a [u8],
+    ids: &'a [u8],
+    prices: &'a [u8],
+    timestamps: &'a [u8],
+    trader_index: &'a [u8],
+    sides: &'a [u8],
+    sizes: &'a [u8],
+    filled: &'a [u8],
+}
+
+impl<'a> BookSnapshotView<'a> {
+    /// Validate the column lengths of a payload and borrow them
+    pub fn parse(payload: &'a [u8]) -> Result<Self> {
+        if payload.len() < PAYLOAD_HEADER_LEN {
+            return Err(anyhow!("Truncated book snapshot header"));
+        }
+        let asset = AssetId(u32::from_le_bytes(payload[0..4].try_into()?));
+        let next_order_id = u64::from_le_bytes(payload[4..12].try_into()?);
+        let trader_count = u32::from_le_bytes(payload[12..16].try_into()?) as usize;
+        let len = u32::from_le_bytes(payload[16..20].try_into()?) as usize;
+        let size_width = payload[20] as usize;
+        if !(1..=32).contains(&size_width) {
+            return Err(anyhow!("Invalid book snapshot size width {}", size_width));
+        }
+
+        let mut rest = &payload[PAYLOAD_HEADER_LEN..];
+        let mut column = |width: usize| -> Result<&'a [u8]> {
+            if rest.len() < width {
+                return Err(anyhow!("Truncated book snapshot column"));
+            }
+            let (head, tail) = rest.split_at(width);
+            rest = tail;
+            Ok(head)
+        };
+        let view = Self {
+            asset,
+            next_order_id,
+            len,
+            size_width,
+            traders: column(trader_count * 20)?,
+            ids: column(len * 8)?,
+            prices: column(len * 8)?,
+            timestamps: column(len * 8)?,
+            trader_index: column(len * 4)?,
+            sides: column(len)?,
+            sizes: column(len * size_width)?,
+            filled: column(len * size_width)?,
+        };
+        if !rest.is_empty() {
+            return Err(anyhow!("Trailing bytes after book snapshot"));
+        }
+        if (0..len).any(|i| view.trader_index(i) >= trader_count) {
+            return Err(anyhow!("Book snapshot trader index out of range"));
+        }
+        Ok(view)
+    }
+
+    /// Number of orders
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Read the `i`th order
+    pub fn order(&self, i: usize) -> Order {
+        let trader = self.trader_index(i);
+        Order {
+            id: read_u64(self.ids, i),
+            asset: self.asset,
+            trader: Address::from_slice(&self.traders[trader * 20..trader * 20 + 20]),
+            side: if self.sides[i] == 0 { Side::Bid } else { Side::Ask },
+            price: Price(read_u64(self.prices, i)),
+            size: Size(self.read_size(self.sizes, i)),
+            filled: Size(self.read_size(self.filled, i)),
+            timestamp: read_u64(self.timestamps, i),
+        }
+    }
+
+    /// Iterate over orders in book order
+    pub fn orders(&self) -> impl Iterator<Item = Order> + '_ {
+        (0..self.len).map(|i| self.order(i))
+    }
+
+    /// Rebuild the order book
+    pub fn restore(&self) -> OrderBook {
+        let mut book = OrderBook::new(self.asset);
+        for order in self.orders() {
+            book.restore_order(order);
+        }
+        book.next_order_id = book.next_order_id.max(self.next_order_id);
+        book.update_cache_after_restore();
+        book
+    }
+
+    fn trader_index(&self, i: usize) -> usize {
+        u32::from_le_bytes(self.trader_index[i * 4..i * 4 + 4].try_into().unwrap()) as usize
+    }
+
+    fn read_size(&self, column: &[u8], i: usize) -> U256 {
+        let w = self.size_width;
+        U256::from_be_slice(&column[i * w..i * w + w])
+    }
+}
+
+/// Decode an encoded snapshot into an order book
+pub fn decode_book(bytes: &[u8]) -> Result<OrderBook> {
+    let payload = snapshot_payload(bytes)?;
+    Ok(BookSnapshotView::parse(&payload)?.restore())
+}
+
+fn read_u64(column: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(column[i * 8..i * 8 + 8].try_into().unwrap())
+}
+
+/// Bytes needed to hold a value
+fn byte_width(value: U256) -> usize {
+    value.bit_len().div_ceil(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deep_book(levels: u64) -> OrderBook {
+        let mut book = OrderBook::new(AssetId(3));
+        for i in 0..levels {
+            let trader = Address::from([(i % 7) as u8 + 1; 20]);
+            book.add_limit_order(trader, Side::Bid, Price(1_000_000 - i * 100), Size(U256::from(10 + i)), i);
+            book.add_limit_order(trader, Side::Ask, Price(1_000_100 + i * 100), Size(U256::from(20 + i)), i);
+        }
+        book
+    }
+
+    fn fields(order: &Order) -> (OrderId, Address, Side, Price, Size, Size, u64) {
+        (order.id, order.trader, order.side, order.price, order.size, order.filled, order.timestamp)
+    }
+
+    #[test]
+    fn test_roundtrip_reads_in_place() {
+        let mut book = deep_book(50);
+        // Partially filled order carries `filled` over
+        let mut partial = Order::new(500, AssetId(3), Address::from([9u8; 20]), Side::Ask, Price(1_000_100), Size(U256::from(30)), 100);
+        partial.filled = Size(U256::from(5));
+        book.restore_order(partial);
+
+        let encoded = encode_book(&book, SnapshotCompression::None).unwrap();
+        let payload = snapshot_payload(&encoded).unwrap();
+        assert!(matches!(payload, Cow::Borrowed(_)));
+
+        let view = BookSnapshotView::parse(&payload).unwrap();
+        assert_eq!(view.len(), 101);
+        assert_eq!(view.orders().find(|order| order.id == 500).unwrap().filled, Size(U256::from(5)));
+
+        let restored = view.restore();
+        assert_eq!(restored.best_bid(), book.best_bid());
+        assert_eq!(restored.best_ask(), book.best_ask());
+        assert_eq!(restored.next_order_id, book.next_order_id);
+        let original: Vec<_> = book.orders().map(fields).collect();
+        let copied: Vec<_> = restored.orders().map(fields).collect();
+        assert_eq!(original, copied);
+
+        // Sizes fit in one byte, far smaller than JSON per order
+        let json: usize = book.orders().map(|o| serde_json::to_vec(o).unwrap().len()).sum();
+        assert!(encoded.len() * 4 < json);
+
+        assert!(decode_book(&encoded[..encoded.len() - 1]).is_err());
+        assert!(decode_book(b"nope").is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_roundtrip() {
+        let book = deep_book(500);
+        let raw = encode_book(&book, SnapshotCompression::None).unwrap();
+        let compressed = encode_book(&book, SnapshotCompression::Zstd(3)).unwrap();
+        assert!(compressed.len() < raw.len());
+
+        let restored = decode_book(&compressed).unwrap();
+        assert_eq!(restored.orders().count(), 1000);
+        assert_eq!(restored.best_ask(), book.best_ask());
+    }
+}
@@ -1,5 +1,6 @@
+use crate::book_snapshot::{self, BookSnapshotView, SnapshotCompression};
 use crate::orderbook::OrderBook;
-use crate::storage::{CheckpointMetadata, CoreStorage};
+use crate::storage::{CheckpointMetadata, CoreStorage, StorageBatch};
 use crate::types::*;
 use anyhow::Result;
 use std::sync::Arc;
@@ -8,12 +9,18 @@ use std::sync::Arc;
 pub struct CheckpointManager {
     storage: Arc<CoreStorage>,
     interval: u64,  // Checkpoint every N blocks
+    compression: SnapshotCompression,
 }
 
 impl CheckpointManager {
     /// Create a new checkpoint manager
     pub fn new(storage: Arc<CoreStorage>, interval: u64) -> Self {
-        Self { storage, interval }
+        Self { storage, interval, compression: SnapshotCompression::None }
+    }
+    
+    /// Set the compression of order book snapshots written from now on
+    pub fn set_compression(&mut self, compression: SnapshotCompression) {
+        self.compression = compression;
     }
     
     /// Check if a checkpoint should be created at this height
@@ -21,63 +28,78 @@ impl CheckpointManager {
         height % self.interval == 0
     }
     
-    /// Create a checkpoint of an order book
-    pub fn checkpoint_book(&self, book: &OrderBook, height: u64) -> Result<()> {
-        let snapshot = book.snapshot(usize::MAX);
-        
-        // Store all active orders by scanning the book
-        // We need to iterate through all price levels and orders
-        let order_count = snapshot.bids.len() + snapshot.asks.len();
+    /// Create a checkpoint of an order book.
+    ///
+    /// Writes a compact snapshot of every resting order into `batch` and
+    /// replaces the asset's previous snapshot, so the checkpoint commits
+    /// with the block that produced it. Take checkpoints at the end of a
+    /// block; `timestamp` is that block's time.
+    pub fn checkpoint_book(
+        &self,
+        batch: &mut StorageBatch,
+        book: &OrderBook,
+        height: u64,
+        timestamp: u64,
+    ) -> Result<()> {
+        let previous = self.storage.load_latest_checkpoint(book.asset)?;
+        let snapshot = book_snapshot::encode_book(book, self.compression)?;
+        batch.put_book_snapshot(book.asset, height, &snapshot);
         
         // Store checkpoint metadata
         let metadata = CheckpointMetadata {
             asset: book.asset,
             height,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            order_count,
+            timestamp,
+            order_count: book.order_count(),
+            snapshot_bytes: snapshot.len(),
         };
         
-        self.storage.store_checkpoint(book.asset, height, &metadata)?;
+        batch.put_checkpoint(&metadata)?;
+        if let Some(previous) = previous.filter(|p| p.height != height) {
+            batch.delete_book_snapshot(book.asset, previous.height);
+        }
         
         Ok(())
     }
     
-    /// Restore an order book from checkpoint
+    /// Restore an order book from the snapshot of its latest checkpoint.
+    ///
+    /// Returns the checkpoint height with the book; blocks after it must be
+    /// replayed by the caller.
+    pub fn restore_book_from_snapshot(&self, asset: AssetId) -> Result<Option<(u64, OrderBook)>> {
+        let Some(metadata) = self.storage.load_latest_checkpoint(asset)? else {
+            return Ok(None);
+        };
+        Ok(self.load_snapshot(asset, metadata.height)?.map(|book| (metadata.height, book)))
+    }
+    
+    /// Restore an order book from checkpoint.
+    ///
+    /// Uses the checkpoint snapshot when it was taken at the last committed
+    /// height, and otherwise rebuilds the book from the stored orders.
     pub fn restore_book(&self, asset: AssetId) -> Result<OrderBook> {
+        let committed = self.storage.load_committed_height()?;
+        let latest = self.storage.load_latest_checkpoint(asset)?;
+        if let Some(height) = latest.map(|m| m.height).filter(|h| committed == Some(*h)) {
+            if let Some(book) = self.load_snapshot(asset, height)? {
+                return Ok(book);
+            }
+        }
+        
         let mut book = OrderBook::new(asset);
         
         // Load all active orders for this asset
         let orders = self.storage.load_orders(asset)?;
         
-        // Rebuild order book by re-inserting orders
-        // We need to track the highest order ID to set next_order_id correctly
-        let mut max_order_id = 0u64;
-        
+        // Rebuild order book by re-inserting orders. This keeps the original
+        // order IDs and timestamps, and advances next_order_id past the
+        // highest ID restored.
         for order in orders {
             if !order.is_filled() {
-                max_order_id = max_order_id.max(order.id);
-                
-                // Re-insert order into the book
-                // We manually add it to maintain the original order ID and timestamp
-                let tree = match order.side {
-                    Side::Bid => book.bids_mut(),
-                    Side::Ask => book.asks_mut(),
-                };
-                
-                tree.entry(order.price)
-                    .or_insert_with(|| crate::orderbook::PriceLevel::new(order.price))
-                    .add_order(order.clone());
-                
-                book.order_index_mut().insert(order.id, (order.price, order.side));
+                book.restore_order(order);
             }
         }
         
-        // Set next_order_id to one past the highest ID we found
-        book.next_order_id = max_order_id + 1;
-        
         // IMPORTANT: Update the cache after restoring all orders
         // This ensures best_bid(), best_ask(), etc. work correctly
         book.update_cache_after_restore();
@@ -85,6 +107,14 @@ impl CheckpointManager {
         Ok(book)
     }
     
+    fn load_snapshot(&self, asset: AssetId, height: u64) -> Result<Option<OrderBook>> {
+        let Some(bytes) = self.storage.load_book_snapshot(asset, height)? else {
+            return Ok(None);
+        };
+        let payload = book_snapshot::snapshot_payload(&bytes)?;
+        Ok(Some(BookSnapshotView::parse(&payload)?.restore()))
+    }
+    
     /// Get the latest checkpoint metadata for an asset
     pub fn get_latest_checkpoint(&self, asset: AssetId) -> Result<Option<CheckpointMetadata>> {
         self.storage.load_latest_checkpoint(asset)
@@ -109,6 +139,12 @@ mod tests {
         format!("/tmp/openliquid_test_checkpoint_{}_{}", timestamp, counter)
     }
 
+    fn checkpoint(mgr: &CheckpointManager, book: &OrderBook, height: u64, timestamp: u64) {
+        let mut batch = StorageBatch::new();
+        mgr.checkpoint_book(&mut batch, book, height, timestamp).unwrap();
+        mgr.storage.write_batch(batch).unwrap();
+    }
+
     #[test]
     fn test_should_checkpoint() {
         let path = temp_db_path();
@@ -132,7 +168,7 @@ mod tests {
         let mgr = CheckpointManager::new(storage.clone(), 100);
         
         let book = OrderBook::new(AssetId(1));
-        mgr.checkpoint_book(&book, 100).unwrap();
+        checkpoint(&mgr, &book, 100, 1000);
         
         let metadata = mgr.get_latest_checkpoint(AssetId(1)).unwrap();
         assert!(metadata.is_some());
@@ -170,20 +206,13 @@ mod tests {
         
         // Store the orders in storage (checkpoint needs them)
         for (price, side) in [(Price::from_float(1.0), Side::Bid), (Price::from_float(1.01), Side::Ask)] {
-            let tree = match side {
-                Side::Bid => book.bids_mut(),
-                Side::Ask => book.asks_mut(),
-            };
-            
-            if let Some(level) = tree.get(&price) {
-                for order in &level.orders {
-                    storage.store_order(order).unwrap();
-                }
+            for order in book.level_orders(side, price) {
+                storage.store_order(order).unwrap();
             }
         }
         
         // Checkpoint the book
-        mgr.checkpoint_book(&book, 1000).unwrap();
+        checkpoint(&mgr, &book, 1000, 10000);
         
         // Restore the book
         let restored = mgr.restore_book(AssetId(1)).unwrap();
@@ -226,9 +255,9 @@ mod tests {
         let book = OrderBook::new(AssetId(1));
         
         // Create multiple checkpoints
-        mgr.checkpoint_book(&book, 100).unwrap();
-        mgr.checkpoint_book(&book, 200).unwrap();
-        mgr.checkpoint_book(&book, 300).unwrap();
+        checkpoint(&mgr, &book, 100, 1000);
+        checkpoint(&mgr, &book, 200, 2000);
+        checkpoint(&mgr, &book, 300, 3000);
         
         let latest = mgr.get_latest_checkpoint(AssetId(1)).unwrap().unwrap();
         assert_eq!(latest.height, 300);
@@ -255,10 +284,8 @@ mod tests {
         );
         
         // Store order
-        if let Some(level) = book.bids_mut().get(&Price::from_float(1.0)) {
-            for order in &level.orders {
-                storage.store_order(order).unwrap();
-            }
+        for order in book.level_orders(Side::Bid, Price::from_float(1.0)) {
+            storage.store_order(order).unwrap();
         }
         
         let original_next_id = book.next_order_id;
@@ -272,5 +299,36 @@ mod tests {
         // Cleanup
         let _ = std::fs::remove_dir_all(path);
     }
-}
 
+    #[test]
+    fn test_restore_from_snapshot_at_committed_height() {
+        let path = temp_db_path();
+        let storage = Arc::new(CoreStorage::new(&path).unwrap());
+        let mgr = CheckpointManager::new(storage.clone(), 100);
+        
+        // Orders are only in the snapshot, not in the per-order store
+        let mut book = OrderBook::new(AssetId(1));
+        for i in 0..20u64 {
+            book.add_limit_order(Address::from([1u8; 20]), Side::Bid, Price(1_000_000 - i), Size(U256::from(10)), i);
+        }
+        checkpoint(&mgr, &OrderBook::new(AssetId(1)), 100, 1000);
+        checkpoint(&mgr, &book, 200, 2000);
+        assert!(storage.load_book_snapshot(AssetId(1), 100).unwrap().is_none());
+        assert_eq!(mgr.get_latest_checkpoint(AssetId(1)).unwrap().unwrap().order_count, 20);
+        
+        // Stale snapshot: fall back to the stored orders
+        assert_eq!(mgr.restore_book(AssetId(1)).unwrap().order_count(), 0);
+        let (height, restored) = mgr.restore_book_from_snapshot(AssetId(1)).unwrap().unwrap();
+        assert_eq!((height, restored.order_count()), (200, 20));
+        
+        let mut batch = StorageBatch::new();
+        batch.set_committed_height(200);
+        storage.write_batch(batch).unwrap();
+        let restored = mgr.restore_book(AssetId(1)).unwrap();
+        assert_eq!(restored.best_bid(), book.best_bid());
+        assert_eq!(restored.order_count(), 20);
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,139 @@
+// Chain Clock
+//
+// Deterministic engine time, sourced from committed block timestamps
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Engine time, advanced only by committed blocks.
+///
+/// Time-dependent engine logic (order timestamps, funding, expiries,
+/// snapshots, checkpoint metadata) reads this clock instead of the wall
+/// clock, so every node sees the same time for the same block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainClock {
+    height: u64,
+    now: u64,
+    /// Timestamp of the previous block (0 before the first advance)
+    previous: u64,
+}
+
+impl ChainClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clock positioned at a known block (e.g. after recovery)
+    pub fn at(height: u64, timestamp: u64) -> Self {
+        Self { height, now: timestamp, previous: timestamp }
+    }
+
+    /// Timestamp of the current block
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Height of the current block
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Timestamp of the previous block
+    pub fn previous(&self) -> u64 {
+        self.previous
+    }
+
+    /// Seconds since the previous block (0 for the first block)
+    pub fn elapsed(&self) -> u64 {
+        if self.previous == 0 {
+            return 0;
+        }
+        self.now - self.previous
+    }
+
+    /// Advance to the block at `height` with `timestamp`.
+    ///
+    /// Block timestamps must not go backwards; blocks may share a timestamp.
+    pub fn advance(&mut self, height: u64, timestamp: u64) -> Result<()> {
+        if timestamp < self.now {
+            return Err(anyhow!(
+                "Block {} timestamp {} is before previous block time {}",
+                height,
+                timestamp,
+                self.now
+            ));
+        }
+        self.previous = self.now;
+        self.now = timestamp;
+        self.height = height;
+        Ok(())
+    }
+
+    /// Whether `deadline` has been reached
+    pub fn is_past(&self, deadline: u64) -> bool {
+        self.now >= deadline
+    }
+
+    /// Index of the `interval`-second period containing the current block
+    pub fn interval_index(&self, interval: u64) -> u64 {
+        self.now / interval.max(1)
+    }
+
+    /// Whether an `interval` boundary was crossed since the previous block
+    pub fn crossed_interval(&self, interval: u64) -> bool {
+        let interval = interval.max(1);
+        self.previous / interval < self.now / interval
+    }
+
+    /// Start of the session containing the current block, for sessions of
+    /// `length` seconds starting at `offset` past each multiple of `length`
+    pub fn session_start(&self, length: u64, offset: u64) -> u64 {
+        let length = length.max(1);
+        let offset = offset % length;
+        if self.now < offset {
+            return 0;
+        }
+        (self.now - offset) / length * length + offset
+    }
+
+    /// First `interval` boundary strictly after the current block time
+    pub fn next_boundary(&self, interval: u64) -> u64 {
+        (self.interval_index(interval) + 1) * interval.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_is_monotonic() {
+        let mut clock = ChainClock::new();
+        clock.advance(1, 100).unwrap();
+        assert_eq!(clock.elapsed(), 0);
+
+        clock.advance(2, 100).unwrap();
+        clock.advance(3, 130).unwrap();
+        assert_eq!((clock.height(), clock.now(), clock.elapsed()), (3, 130, 30));
+
+        // A block from the past leaves the clock untouched
+        assert!(clock.advance(4, 129).is_err());
+        assert_eq!((clock.height(), clock.now()), (3, 130));
+    }
+
+    #[test]
+    fn test_clock_intervals_and_sessions() {
+        let mut clock = ChainClock::at(10, 3_590);
+        clock.advance(11, 3_610).unwrap();
+        assert!(clock.crossed_interval(3_600));
+        assert!(!clock.crossed_interval(86_400));
+        assert_eq!(clock.interval_index(3_600), 1);
+        assert_eq!(clock.next_boundary(3_600), 7_200);
+        assert!(clock.is_past(3_600) && !clock.is_past(3_611));
+
+        // Daily sessions opening at 01:00
+        assert_eq!(clock.session_start(86_400, 3_600), 3_600);
+        assert_eq!(ChainClock::at(1, 1_000).session_start(86_400, 3_600), 0);
+        assert_eq!(ChainClock::at(1, 90_000).session_start(86_400, 3_600), 90_000);
+    }
+}
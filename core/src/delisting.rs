@@ -0,0 +1,224 @@
+use crate::types::*;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Governance parameters for a market delisting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DelistingParams {
+    /// Blocks between announcement (reduce-only) and cancellation of resting orders
+    pub cancel_delay: u64,
+    /// Blocks between order cancellation and forced settlement
+    pub settlement_delay: u64,
+    /// TWAP window for the settlement price (seconds)
+    pub twap_window: u64,
+}
+
+impl Default for DelistingParams {
+    fn default() -> Self {
+        Self {
+            cancel_delay: 100,
+            settlement_delay: 100,
+            twap_window: 3600,  // 1 hour
+        }
+    }
+}
+
+/// Delisting phase of a market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DelistingPhase {
+    /// Announced: only position-reducing orders accepted
+    ReduceOnly,
+    /// Resting orders cancelled: trading halted, awaiting settlement
+    Halted,
+    /// Remaining positions force-settled
+    Settled,
+}
+
+/// Delisting schedule for an asset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delisting {
+    pub asset: AssetId,
+    pub params: DelistingParams,
+    /// Announcement height (H)
+    pub announced_at: u64,
+    pub phase: DelistingPhase,
+    /// Settlement price once settled
+    pub settlement_price: Option<Price>,
+}
+
+impl Delisting {
+    /// Height at which resting orders are cancelled (H + k)
+    pub fn cancel_height(&self) -> u64 {
+        self.announced_at.saturating_add(self.params.cancel_delay)
+    }
+
+    /// Height at which remaining positions are settled
+    pub fn settlement_height(&self) -> u64 {
+        self.cancel_height().saturating_add(self.params.settlement_delay)
+    }
+}
+
+/// Step the state machine must execute for a delisting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelistingAction {
+    CancelOrders(AssetId),
+    Settle(AssetId),
+}
+
+/// Tracks market delistings and drives their phases by block height
+#[derive(Debug, Clone, Default)]
+pub struct DelistingManager {
+    delistings: HashMap<AssetId, Delisting>,
+}
+
+impl DelistingManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Announce delisting of an asset at the given height
+    pub fn announce(&mut self, asset: AssetId, height: u64, params: DelistingParams) -> Result<()> {
+        if self.delistings.contains_key(&asset) {
+            return Err(anyhow!("Asset {} already delisting", asset.0));
+        }
+
+        self.delistings.insert(asset, Delisting {
+            asset,
+            params,
+            announced_at: height,
+            phase: DelistingPhase::ReduceOnly,
+            settlement_price: None,
+        });
+
+        Ok(())
+    }
+
+    /// Get delisting for asset
+    pub fn get(&self, asset: AssetId) -> Option<&Delisting> {
+        self.delistings.get(&asset)
+    }
+
+    /// Get current phase for asset (None if not delisting)
+    pub fn phase(&self, asset: AssetId) -> Option<DelistingPhase> {
+        self.delistings.get(&asset).map(|d| d.phase)
+    }
+
+    /// Check if only reduce-only orders are accepted
+    pub fn is_reduce_only(&self, asset: AssetId) -> bool {
+        self.phase(asset) == Some(DelistingPhase::ReduceOnly)
+    }
+
+    /// Check if trading is halted (cancelled or settled)
+    pub fn is_halted(&self, asset: AssetId) -> bool {
+        matches!(
+            self.phase(asset),
+            Some(DelistingPhase::Halted) | Some(DelistingPhase::Settled)
+        )
+    }
+
+    /// Actions due at `height`, in deterministic (asset id) order.
+    ///
+    /// A market whose cancel and settlement heights have both passed yields
+    /// both actions in sequence.
+    pub fn due_actions(&self, height: u64) -> Vec<DelistingAction> {
+        let mut delistings: Vec<_> = self.delistings.values().collect();
+        delistings.sort_by_key(|d| d.asset.0);
+
+        let mut actions = Vec::new();
+        for d in delistings {
+            if d.phase == DelistingPhase::ReduceOnly && height >= d.cancel_height() {
+                actions.push(DelistingAction::CancelOrders(d.asset));
+            }
+            if d.phase != DelistingPhase::Settled && height >= d.settlement_height() {
+                actions.push(DelistingAction::Settle(d.asset));
+            }
+        }
+
+        actions
+    }
+
+    /// Mark resting orders as cancelled
+    pub fn mark_halted(&mut self, asset: AssetId) -> Result<()> {
+        let d = self.delistings
+            .get_mut(&asset)
+            .ok_or_else(|| anyhow!("Asset not delisting"))?;
+        d.phase = DelistingPhase::Halted;
+        Ok(())
+    }
+
+    /// Mark positions as settled at the given price
+    pub fn mark_settled(&mut self, asset: AssetId, price: Price) -> Result<()> {
+        let d = self.delistings
+            .get_mut(&asset)
+            .ok_or_else(|| anyhow!("Asset not delisting"))?;
+        d.phase = DelistingPhase::Settled;
+        d.settlement_price = Some(price);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> DelistingParams {
+        DelistingParams {
+            cancel_delay: 10,
+            settlement_delay: 5,
+            twap_window: 60,
+        }
+    }
+
+    #[test]
+    fn test_announce_and_schedule() {
+        let mut mgr = DelistingManager::new();
+        let asset = AssetId(1);
+
+        mgr.announce(asset, 100, params()).unwrap();
+        assert!(mgr.announce(asset, 101, params()).is_err());
+
+        let d = mgr.get(asset).unwrap();
+        assert_eq!(d.cancel_height(), 110);
+        assert_eq!(d.settlement_height(), 115);
+        assert!(mgr.is_reduce_only(asset));
+        assert!(!mgr.is_halted(asset));
+    }
+
+    #[test]
+    fn test_due_actions_progression() {
+        let mut mgr = DelistingManager::new();
+        let asset = AssetId(1);
+        mgr.announce(asset, 100, params()).unwrap();
+
+        assert!(mgr.due_actions(109).is_empty());
+        assert_eq!(mgr.due_actions(110), vec![DelistingAction::CancelOrders(asset)]);
+
+        mgr.mark_halted(asset).unwrap();
+        assert!(mgr.is_halted(asset));
+        assert!(mgr.due_actions(114).is_empty());
+        assert_eq!(mgr.due_actions(115), vec![DelistingAction::Settle(asset)]);
+
+        mgr.mark_settled(asset, Price::from_float(10.0)).unwrap();
+        assert!(mgr.due_actions(200).is_empty());
+        assert_eq!(mgr.get(asset).unwrap().settlement_price, Some(Price::from_float(10.0)));
+    }
+
+    #[test]
+    fn test_due_actions_deterministic_order() {
+        let mut mgr = DelistingManager::new();
+        mgr.announce(AssetId(3), 0, params()).unwrap();
+        mgr.announce(AssetId(1), 0, params()).unwrap();
+
+        // Skipped past both heights: cancel then settle, ordered by asset
+        assert_eq!(
+            mgr.due_actions(20),
+            vec![
+                DelistingAction::CancelOrders(AssetId(1)),
+                DelistingAction::Settle(AssetId(1)),
+                DelistingAction::CancelOrders(AssetId(3)),
+                DelistingAction::Settle(AssetId(3)),
+            ]
+        );
+    }
+}
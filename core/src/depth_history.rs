@@ -0,0 +1,365 @@
+use crate::orderbook::OrderBook;
+use crate::storage::CoreStorage;
+use crate::types::*;
+use alloy_primitives::U256;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Encoding version of `DepthSnapshot::encode`
+const DEPTH_FORMAT_VERSION: u8 = 1;
+
+/// How often and how deep books are sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthConfig {
+    /// Seconds between snapshots (0 disables capture)
+    pub interval: u64,
+    /// Price levels kept per side
+    pub levels: usize,
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            interval: 60,
+            levels: 20,
+        }
+    }
+}
+
+/// Aggregated book depth of one asset at one point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthSnapshot {
+    pub asset: AssetId,
+    pub timestamp: u64,
+    /// Best first (descending prices)
+    pub bids: Vec<(Price, U256)>,
+    /// Best first (ascending prices)
+    pub asks: Vec<(Price, U256)>,
+}
+
+impl DepthSnapshot {
+    /// Capture the top `levels` of a book
+    pub fn capture(book: &OrderBook, timestamp: u64, levels: usize) -> Self {
+        let snapshot = book.snapshot(levels);
+        Self {
+            asset: snapshot.asset,
+            timestamp,
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+        }
+    }
+
+    /// Best ask minus best bid
+    pub fn spread(&self) -> Option<Price> {
+        let (bid, _) = self.bids.first()?;
+        let (ask, _) = self.asks.first()?;
+        Some(Price(ask.0.saturating_sub(bid.0)))
+    }
+
+    /// Total size on each side within `range` of the mid price
+    pub fn liquidity_within(&self, range: Price) -> Option<(U256, U256)> {
+        let (bid, _) = self.bids.first()?;
+        let (ask, _) = self.asks.first()?;
+        let mid = (bid.0 + ask.0) / 2;
+
+        let sum = |levels: &[(Price, U256)]| {
+            levels
+                .iter()
+                .filter(|(price, _)| price.0.abs_diff(mid) <= range.0)
+                .fold(U256::ZERO, |acc, (_, size)| acc.saturating_add(*size))
+        };
+        Some((sum(&self.bids), sum(&self.asks)))
+    }
+
+    /// Columnar encoding: header, then the bid and ask price columns
+    /// (delta-encoded from the best price), then the size column, all as
+    /// LEB128 varints.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(16 + 6 * (self.bids.len() + self.asks.len()));
+        buf.push(DEPTH_FORMAT_VERSION);
+        write_varint(&mut buf, U256::from(self.asset.0));
+        write_varint(&mut buf, U256::from(self.timestamp));
+        write_varint(&mut buf, U256::from(self.bids.len()));
+        write_varint(&mut buf, U256::from(self.asks.len()));
+
+        for levels in [&self.bids, &self.asks] {
+            let mut prev: Option<u64> = None;
+            for (price, _) in levels.iter() {
+                // Levels move away from the best price, so deltas are positive
+                let value = prev.map_or(price.0, |p| p.abs_diff(price.0));
+                write_varint(&mut buf, U256::from(value));
+                prev = Some(price.0);
+            }
+        }
+        for (_, size) in self.bids.iter().chain(&self.asks) {
+            write_varint(&mut buf, *size);
+        }
+        buf
+    }
+
+    /// Decode a snapshot written by `encode`
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let (&version, mut rest) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow!("Empty depth snapshot"))?;
+        if version != DEPTH_FORMAT_VERSION {
+            return Err(anyhow!("Unsupported depth snapshot version {}", version));
+        }
+
+        let next_u64 = |rest: &mut &[u8]| -> Result<u64> {
+            let value = read_varint(rest)?;
+            u64::try_from(value).map_err(|_| anyhow!("Depth snapshot field out of range"))
+        };
+        let asset = AssetId(next_u64(&mut rest)? as u32);
+        let timestamp = next_u64(&mut rest)?;
+        let bid_count = next_u64(&mut rest)? as usize;
+        let ask_count = next_u64(&mut rest)? as usize;
+
+        let mut prices = Vec::with_capacity(bid_count + ask_count);
+        for (count, descending) in [(bid_count, true), (ask_count, false)] {
+            let mut prev: Option<u64> = None;
+            for _ in 0..count {
+                let value = next_u64(&mut rest)?;
+                let price = match prev {
+                    None => value,
+                    Some(p) if descending => p.checked_sub(value).ok_or_else(|| anyhow!("Corrupt bid prices"))?,
+                    Some(p) => p.checked_add(value).ok_or_else(|| anyhow!("Corrupt ask prices"))?,
+                };
+                prices.push(Price(price));
+                prev = Some(price);
+            }
+        }
+
+        let mut levels = Vec::with_capacity(prices.len());
+        for price in prices {
+            levels.push((price, read_varint(&mut rest)?));
+        }
+        if !rest.is_empty() {
+            return Err(anyhow!("Trailing bytes in depth snapshot"));
+        }
+        let asks = levels.split_off(bid_count);
+
+        Ok(Self {
+            asset,
+            timestamp,
+            bids: levels,
+            asks,
+        })
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: U256) {
+    loop {
+        let byte = (value.as_limbs()[0] & 0x7f) as u8;
+        value >>= 7;
+        if value == U256::ZERO {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<U256> {
+    let mut value = U256::ZERO;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow!("Truncated depth snapshot"))?;
+        *bytes = rest;
+        if shift >= 256 {
+            return Err(anyhow!("Varint too long"));
+        }
+        value |= U256::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Liquidity by price bucket over time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepthHeatmap {
+    /// Lower bound of each price bucket, ascending
+    pub buckets: Vec<Price>,
+    /// Snapshot timestamps, ascending
+    pub timestamps: Vec<u64>,
+    /// `cells[t][b]`: resting size in bucket `b` at `timestamps[t]`
+    pub cells: Vec<Vec<U256>>,
+}
+
+/// How often a market met a quoting obligation over a period
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObligationReport {
+    /// Snapshots in the period
+    pub samples: usize,
+    /// Snapshots meeting both the spread and size requirement
+    pub compliant: usize,
+}
+
+impl ObligationReport {
+    /// Compliant share of samples in basis points
+    pub fn uptime_bps(&self) -> u64 {
+        if self.samples == 0 {
+            return 0;
+        }
+        (self.compliant * 10000 / self.samples) as u64
+    }
+}
+
+/// Query layer over persisted depth snapshots
+pub struct DepthHistory {
+    storage: Arc<CoreStorage>,
+}
+
+impl DepthHistory {
+    /// Create a new depth history manager
+    pub fn new(storage: Arc<CoreStorage>) -> Self {
+        Self { storage }
+    }
+
+    /// Snapshots of an asset taken in `[from, to]`, oldest first
+    pub fn range(&self, asset: AssetId, from: u64, to: u64) -> Result<Vec<DepthSnapshot>> {
+        self.storage.load_depth_snapshots(asset, from, to)
+    }
+
+    /// Bucket resting size by price for every snapshot in `[from, to]`
+    pub fn heatmap(&self, asset: AssetId, from: u64, to: u64, bucket_width: Price) -> Result<DepthHeatmap> {
+        if bucket_width.0 == 0 {
+            return Err(anyhow!("Bucket width must be positive"));
+        }
+        let snapshots = self.range(asset, from, to)?;
+
+        let mut rows = Vec::with_capacity(snapshots.len());
+        let mut buckets = std::collections::BTreeSet::new();
+        for snapshot in &snapshots {
+            let mut row: BTreeMap<u64, U256> = BTreeMap::new();
+            for (price, size) in snapshot.bids.iter().chain(&snapshot.asks) {
+                let bucket = price.0 / bucket_width.0 * bucket_width.0;
+                let cell = row.entry(bucket).or_insert(U256::ZERO);
+                *cell = cell.saturating_add(*size);
+                buckets.insert(bucket);
+            }
+            rows.push(row);
+        }
+
+        Ok(DepthHeatmap {
+            cells: rows
+                .iter()
+                .map(|row| buckets.iter().map(|b| row.get(b).copied().unwrap_or(U256::ZERO)).collect())
+                .collect(),
+            buckets: buckets.into_iter().map(Price).collect(),
+            timestamps: snapshots.iter().map(|s| s.timestamp).collect(),
+        })
+    }
+
+    /// Check after the fact how often the book quoted within `max_spread`
+    /// with at least `min_size` on both sides within `max_spread` of mid
+    pub fn verify_obligation(
+        &self,
+        asset: AssetId,
+        from: u64,
+        to: u64,
+        max_spread: Price,
+        min_size: U256,
+    ) -> Result<ObligationReport> {
+        let snapshots = self.range(asset, from, to)?;
+        let compliant = snapshots
+            .iter()
+            .filter(|s| {
+                s.spread().is_some_and(|spread| spread <= max_spread)
+                    && s.liquidity_within(max_spread)
+                        .is_some_and(|(bids, asks)| bids >= min_size && asks >= min_size)
+            })
+            .count();
+
+        Ok(ObligationReport {
+            samples: snapshots.len(),
+            compliant,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageBatch;
+
+    fn temp_db_path() -> String {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::time::{SystemTime, UNIX_EPOCH};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
+        format!("/tmp/openliquid_test_depth_history_{}_{}", timestamp, counter)
+    }
+
+    fn snapshot(asset: u32, timestamp: u64, spread: u64, size: u64) -> DepthSnapshot {
+        DepthSnapshot {
+            asset: AssetId(asset),
+            timestamp,
+            bids: vec![(Price(1_000_000), U256::from(size)), (Price(990_000), U256::from(7))],
+            asks: vec![(Price(1_000_000 + spread), U256::from(size))],
+        }
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let snap = DepthSnapshot {
+            asset: AssetId(3),
+            timestamp: 1_700_000_000,
+            bids: vec![(Price(100_000_000), U256::from(5)), (Price(99_500_000), U256::MAX)],
+            asks: vec![(Price(100_100_000), U256::from(1)), (Price(101_000_000), U256::ZERO)],
+        };
+        let bytes = snap.encode();
+        assert_eq!(DepthSnapshot::decode(&bytes).unwrap(), snap);
+        assert!(DepthSnapshot::decode(&bytes[..bytes.len() - 1]).is_err());
+
+        let empty = DepthSnapshot { asset: AssetId(1), timestamp: 0, bids: vec![], asks: vec![] };
+        assert_eq!(DepthSnapshot::decode(&empty.encode()).unwrap(), empty);
+    }
+
+    #[test]
+    fn test_range_heatmap_and_obligations() {
+        let path = temp_db_path();
+        let storage = Arc::new(CoreStorage::new(&path).unwrap());
+
+        let mut batch = StorageBatch::new();
+        for snap in [
+            snapshot(1, 60, 1_000, 10),
+            snapshot(1, 120, 50_000, 10),
+            snapshot(1, 180, 1_000, 1),
+            snapshot(2, 120, 1_000, 10),
+        ] {
+            batch.put_depth_snapshot(&snap);
+        }
+        storage.write_batch(batch).unwrap();
+
+        let history = DepthHistory::new(storage);
+        let range = history.range(AssetId(1), 60, 120).unwrap();
+        assert_eq!(range.iter().map(|s| s.timestamp).collect::<Vec<_>>(), vec![60, 120]);
+
+        let heatmap = history.heatmap(AssetId(1), 0, 1000, Price(10_000)).unwrap();
+        assert_eq!(heatmap.timestamps, vec![60, 120, 180]);
+        assert_eq!(heatmap.buckets, vec![Price(990_000), Price(1_000_000), Price(1_050_000)]);
+        // Bid and ask at 1.000 and 1.001 share a bucket
+        assert_eq!(heatmap.cells[0], vec![U256::from(7), U256::from(20), U256::ZERO]);
+
+        // Spread too wide at 120, too thin at 180
+        let report = history
+            .verify_obligation(AssetId(1), 0, 1000, Price(2_000), U256::from(5))
+            .unwrap();
+        assert_eq!(report, ObligationReport { samples: 3, compliant: 1 });
+        assert_eq!(report.uptime_bps(), 3333);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::governance::Governance;
+use crate::types::*;
+use alloy_primitives::Address;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Operation type that emergency controls can pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Operation {
+    NewOrders,
+    Cancels,
+    Withdrawals,
+}
+
+/// Where a pause applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PauseScope {
+    /// Every market and asset
+    Global,
+    /// One market (for withdrawals: one collateral asset)
+    Market(AssetId),
+}
+
+/// Emergency action, executed once enough authorities approve it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmergencyAction {
+    /// Pause `operations` in `scope`
+    Pause { scope: PauseScope, operations: Vec<Operation> },
+    /// Resume `operations` in `scope`
+    Resume { scope: PauseScope, operations: Vec<Operation> },
+    /// Stop new orders and withdrawals in `scope`; cancels stay open, so
+    /// the scope drops into cancel-only mode
+    KillSwitch { scope: PauseScope },
+}
+
+/// What happened to an emergency action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditEvent {
+    Proposed,
+    Approved,
+    Executed,
+}
+
+/// Audit log entry of an emergency control invocation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub proposal_id: u64,
+    pub action: EmergencyAction,
+    pub event: AuditEvent,
+    /// Authority that proposed or approved (the final approver for executions)
+    pub by: Address,
+    pub height: u64,
+    pub timestamp: u64,
+}
+
+/// Emergency action awaiting approvals
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmergencyProposal {
+    pub action: EmergencyAction,
+    pub approvals: BTreeSet<Address>,
+}
+
+/// Exchange-wide kill switch and scoped pause controls
+///
+/// Actions are proposed and approved by governance authorities and take
+/// effect once `threshold` distinct authorities have signed off. Every
+/// proposal, approval and execution is appended to the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmergencyControls {
+    /// Approvals an action needs before it executes
+    threshold: usize,
+    proposals: BTreeMap<u64, EmergencyProposal>,
+    next_proposal_id: u64,
+    /// Paused operations per scope
+    paused: HashMap<PauseScope, BTreeSet<Operation>>,
+    audit_log: Vec<AuditEntry>,
+}
+
+impl Default for EmergencyControls {
+    fn default() -> Self {
+        Self {
+            threshold: 1,
+            proposals: BTreeMap::new(),
+            next_proposal_id: 0,
+            paused: HashMap::new(),
+            audit_log: Vec::new(),
+        }
+    }
+}
+
+impl EmergencyControls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Approvals an action needs before it executes
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Change the approval threshold (governance only); it must be
+    /// reachable by the current authorities
+    pub fn set_threshold(&mut self, governance: &Governance, caller: &Address, threshold: usize) -> Result<()> {
+        governance.ensure_authorized(caller)?;
+        let authorities = governance.authorities().count();
+        if threshold == 0 || threshold > authorities {
+            return Err(anyhow!("Threshold must be between 1 and {} authorities", authorities));
+        }
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    /// Propose an action, counting the proposer's approval
+    ///
+    /// Returns the proposal ID; the action executes immediately if the
+    /// threshold is one.
+    pub fn propose(
+        &mut self,
+        governance: &Governance,
+        caller: &Address,
+        action: EmergencyAction,
+        height: u64,
+        timestamp: u64,
+    ) -> Result<u64> {
+        governance.ensure_authorized(caller)?;
+        let id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        self.proposals.insert(id, EmergencyProposal {
+            action: action.clone(),
+            approvals: BTreeSet::from([*caller]),
+        });
+        self.log(id, action, AuditEvent::Proposed, *caller, height, timestamp);
+        self.execute_if_approved(id, *caller, height, timestamp);
+        Ok(id)
+    }
+
+    /// Approve a pending action; returns whether it executed
+    pub fn approve(
+        &mut self,
+        governance: &Governance,
+        caller: &Address,
+        proposal_id: u64,
+        height: u64,
+        timestamp: u64,
+    ) -> Result<bool> {
+        governance.ensure_authorized(caller)?;
+        let proposal = self
+            .proposals
+            .get_mut(&proposal_id)
+            .ok_or_else(|| anyhow!("Emergency proposal {} not found", proposal_id))?;
+        if !proposal.approvals.insert(*caller) {
+            return Err(anyhow!("{} already approved proposal {}", caller, proposal_id));
+        }
+        let action = proposal.action.clone();
+        self.log(proposal_id, action, AuditEvent::Approved, *caller, height, timestamp);
+        Ok(self.execute_if_approved(proposal_id, *caller, height, timestamp))
+    }
+
+    /// Pending proposal by ID
+    pub fn proposal(&self, proposal_id: u64) -> Option<&EmergencyProposal> {
+        self.proposals.get(&proposal_id)
+    }
+
+    /// Whether `operation` is paused globally or, with `asset`, in that market
+    pub fn is_paused(&self, operation: Operation, asset: Option<AssetId>) -> bool {
+        let paused_in = |scope: PauseScope| self.paused.get(&scope).is_some_and(|ops| ops.contains(&operation));
+        paused_in(PauseScope::Global) || asset.is_some_and(|asset| paused_in(PauseScope::Market(asset)))
+    }
+
+    /// Fail if `operation` is paused for `asset`
+    pub fn ensure_allowed(&self, operation: Operation, asset: Option<AssetId>) -> Result<()> {
+        if self.is_paused(operation, asset) {
+            return Err(match asset {
+                Some(asset) => anyhow!("{:?} are paused for market {}", operation, asset.0),
+                None => anyhow!("{:?} are paused", operation),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether a market accepts cancels but no new orders
+    pub fn is_cancel_only(&self, asset: AssetId) -> bool {
+        self.is_paused(Operation::NewOrders, Some(asset)) && !self.is_paused(Operation::Cancels, Some(asset))
+    }
+
+    /// Every proposal, approval and execution, oldest first
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit_log
+    }
+
+    fn execute_if_approved(&mut self, proposal_id: u64, by: Address, height: u64, timestamp: u64) -> bool {
+        if self.proposals.get(&proposal_id).is_none_or(|p| p.approvals.len() < self.threshold) {
+            return false;
+        }
+        let Some(proposal) = self.proposals.remove(&proposal_id) else {
+            return false;
+        };
+
+        match &proposal.action {
+            EmergencyAction::Pause { scope, operations } => {
+                self.paused.entry(*scope).or_default().extend(operations.iter().copied());
+            }
+            EmergencyAction::Resume { scope, operations } => {
+                if let Some(paused) = self.paused.get_mut(scope) {
+                    for operation in operations {
+                        paused.remove(operation);
+                    }
+                    if paused.is_empty() {
+                        self.paused.remove(scope);
+                    }
+                }
+            }
+            EmergencyAction::KillSwitch { scope } => {
+                self.paused
+                    .entry(*scope)
+                    .or_default()
+                    .extend([Operation::NewOrders, Operation::Withdrawals]);
+            }
+        }
+        self.log(proposal_id, proposal.action, AuditEvent::Executed, by, height, timestamp);
+        true
+    }
+
+    fn log(&mut self, proposal_id: u64, action: EmergencyAction, event: AuditEvent, by: Address, height: u64, timestamp: u64) {
+        self.audit_log.push(AuditEntry {
+            proposal_id,
+            action,
+            event,
+            by,
+            height,
+            timestamp,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_approval_and_scoped_pauses() {
+        let (a, b, c) = (Address::from([1; 20]), Address::from([2; 20]), Address::from([3; 20]));
+        let governance = Governance::new([a, b, c]);
+        let mut controls = EmergencyControls::new();
+        assert!(controls.set_threshold(&governance, &a, 4).is_err());
+        controls.set_threshold(&governance, &a, 2).unwrap();
+
+        // Outsiders cannot propose; one authority alone cannot pull the switch
+        let market = AssetId(1);
+        let kill = EmergencyAction::KillSwitch { scope: PauseScope::Market(market) };
+        assert!(controls.propose(&governance, &Address::ZERO, kill.clone(), 1, 10).is_err());
+        let id = controls.propose(&governance, &a, kill, 1, 10).unwrap();
+        assert!(!controls.is_paused(Operation::NewOrders, Some(market)));
+        assert!(controls.approve(&governance, &a, id, 1, 10).is_err());
+        assert!(controls.approve(&governance, &b, id, 2, 20).unwrap());
+
+        // The market is cancel-only; other markets trade normally
+        assert!(controls.is_cancel_only(market));
+        assert!(controls.ensure_allowed(Operation::Withdrawals, Some(market)).is_err());
+        assert!(controls.ensure_allowed(Operation::NewOrders, Some(AssetId(2))).is_ok());
+
+        // A global cancel pause takes the market out of cancel-only
+        let pause = EmergencyAction::Pause { scope: PauseScope::Global, operations: vec![Operation::Cancels] };
+        let id = controls.propose(&governance, &c, pause, 3, 30).unwrap();
+        controls.approve(&governance, &a, id, 3, 30).unwrap();
+        assert!(!controls.is_cancel_only(market));
+        assert!(controls.is_paused(Operation::Cancels, Some(AssetId(2))));
+
+        let resume = EmergencyAction::Resume { scope: PauseScope::Market(market), operations: vec![Operation::NewOrders] };
+        let id = controls.propose(&governance, &b, resume, 4, 40).unwrap();
+        controls.approve(&governance, &c, id, 4, 40).unwrap();
+        assert!(!controls.is_paused(Operation::NewOrders, Some(market)));
+        assert!(controls.is_paused(Operation::Withdrawals, Some(market)));
+
+        let events: Vec<_> = controls.audit_log().iter().map(|e| (e.proposal_id, e.event, e.by)).collect();
+        assert_eq!(&events[..3], &[
+            (0, AuditEvent::Proposed, a),
+            (0, AuditEvent::Approved, b),
+            (0, AuditEvent::Executed, b),
+        ]);
+        assert_eq!(events.len(), 9);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::types::*;
+use alloy_primitives::{keccak256, Address, B256, U256};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Emissions schedule and scoring parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmissionsConfig {
+    /// Epoch length in seconds
+    pub epoch_length: u64,
+    /// Reward tokens emitted per epoch
+    pub reward_per_epoch: U256,
+    /// Asset credited on claim
+    pub reward_asset: AssetId,
+    /// Share of each epoch's rewards paid on fee-weighted volume (bps);
+    /// the rest is paid on maker uptime
+    pub trader_share_bps: u64,
+    /// Maximum share of a user's score that may come from trades with a
+    /// single counterparty (bps)
+    pub max_counterparty_share_bps: u64,
+    /// Minimum distinct counterparties for volume to score at all
+    pub min_counterparties: usize,
+}
+
+impl Default for EmissionsConfig {
+    fn default() -> Self {
+        Self {
+            epoch_length: 24 * 60 * 60,  // 1 day
+            reward_per_epoch: U256::from(1_000_000),
+            reward_asset: AssetId(0),
+            trader_share_bps: 7000,
+            max_counterparty_share_bps: 5000,
+            min_counterparties: 2,
+        }
+    }
+}
+
+/// Finalized rewards of one epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochRewards {
+    pub epoch: u64,
+    /// Merkle root over `reward_leaf(epoch, user, amount)` leaves
+    pub root: B256,
+    /// Total distributed (may be below `reward_per_epoch` due to rounding)
+    pub total: U256,
+    /// Reward per user, sorted by user
+    pub rewards: Vec<(Address, U256)>,
+}
+
+/// Merkle leaf committing to a user's reward for an epoch
+pub fn reward_leaf(epoch: u64, user: &Address, amount: U256) -> B256 {
+    let mut data = Vec::with_capacity(60);
+    data.extend_from_slice(&epoch.to_be_bytes());
+    data.extend_from_slice(user.as_slice());
+    data.extend_from_slice(&amount.to_be_bytes::<32>());
+    keccak256(&data)
+}
+
+/// Hash a node pair in sorted order, so proofs need no position bits
+fn hash_pair(a: B256, b: B256) -> B256 {
+    let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(lo.as_slice());
+    data[32..].copy_from_slice(hi.as_slice());
+    keccak256(data)
+}
+
+/// Build all Merkle tree levels, leaves first. An odd node is carried up.
+fn merkle_levels(leaves: Vec<B256>) -> Vec<Vec<B256>> {
+    let mut levels = vec![leaves];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => hash_pair(*a, *b),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Check a Merkle proof for `leaf` against `root`
+pub fn verify_proof(root: B256, leaf: B256, proof: &[B256]) -> bool {
+    proof.iter().fold(leaf, |node, sibling| hash_pair(node, *sibling)) == root
+}
+
+/// Per-epoch liquidity mining and trading rewards.
+///
+/// Traders score by fees paid (fee-weighted volume) and makers by two-sided
+/// quoting time. At the end of each epoch rewards are split pro rata, and
+/// only the Merkle root is needed to check a claim.
+///
+/// Wash-trading heuristics applied to volume:
+/// - self-matched fills never score
+/// - volume from one counterparty is capped at `max_counterparty_share_bps`
+///   of the user's score
+/// - users with fewer than `min_counterparties` counterparties do not score
+#[derive(Debug, Clone)]
+pub struct EmissionsEngine {
+    config: EmissionsConfig,
+    /// Fees paid this epoch: user -> counterparty -> fees
+    volume_scores: BTreeMap<Address, BTreeMap<Address, U256>>,
+    /// Two-sided quoting time this epoch, in seconds
+    uptime: BTreeMap<Address, u64>,
+    /// Self-matched fills seen this epoch
+    self_trades: BTreeMap<Address, u64>,
+    /// Volume score reductions for this epoch (bps), e.g. from surveillance
+    penalties: BTreeMap<Address, u64>,
+    /// Finalized epochs
+    epochs: BTreeMap<u64, EpochRewards>,
+    /// Claimed (epoch, user) pairs
+    claimed: BTreeSet<(u64, Address)>,
+}
+
+impl EmissionsEngine {
+    pub fn new(config: EmissionsConfig) -> Self {
+        Self {
+            config,
+            volume_scores: BTreeMap::new(),
+            uptime: BTreeMap::new(),
+            self_trades: BTreeMap::new(),
+            penalties: BTreeMap::new(),
+            epochs: BTreeMap::new(),
+            claimed: BTreeSet::new(),
+        }
+    }
+
+    pub fn config(&self) -> &EmissionsConfig {
+        &self.config
+    }
+
+    /// Epoch containing `timestamp`
+    pub fn epoch_of(&self, timestamp: u64) -> u64 {
+        timestamp / self.config.epoch_length.max(1)
+    }
+
+    /// Record a fill with the fee each side paid
+    pub fn record_fill(&mut self, fill: &Fill, maker_fee: U256, taker_fee: U256) {
+        if fill.maker == fill.taker {
+            *self.self_trades.entry(fill.maker).or_insert(0) += 1;
+            return;
+        }
+
+        for (user, counterparty, fee) in [
+            (fill.maker, fill.taker, maker_fee),
+            (fill.taker, fill.maker, taker_fee),
+        ] {
+            let score = self
+                .volume_scores
+                .entry(user)
+                .or_default()
+                .entry(counterparty)
+                .or_insert(U256::ZERO);
+            *score = score.saturating_add(fee);
+        }
+    }
+
+    /// Credit a maker with two-sided quoting time
+    pub fn record_uptime(&mut self, maker: Address, seconds: u64) {
+        *self.uptime.entry(maker).or_insert(0) += seconds;
+    }
+
+    /// Self-matched fills seen this epoch for a user
+    pub fn self_trade_count(&self, user: &Address) -> u64 {
+        self.self_trades.get(user).copied().unwrap_or(0)
+    }
+
+    /// Reduce a user's volume score for the open epoch (bps, 10000 = excluded)
+    pub fn set_penalty(&mut self, user: Address, bps: u64) {
+        self.penalties.insert(user, bps.min(10000));
+    }
+
+    /// Penalty on a user's volume score for the open epoch (bps)
+    pub fn penalty(&self, user: &Address) -> u64 {
+        self.penalties.get(user).copied().unwrap_or(0)
+    }
+
+    /// Volume scores for the open epoch after wash-trading heuristics
+    pub fn volume_scores(&self) -> BTreeMap<Address, U256> {
+        let cap_bps = U256::from(self.config.max_counterparty_share_bps);
+
+        self.volume_scores
+            .iter()
+            .filter(|(_, by_counterparty)| by_counterparty.len() >= self.config.min_counterparties)
+            .map(|(user, by_counterparty)| {
+                let raw = by_counterparty
+                    .values()
+                    .fold(U256::ZERO, |acc, fee| acc.saturating_add(*fee));
+                let cap = raw.saturating_mul(cap_bps) / U256::from(10000);
+                let score = by_counterparty
+                    .values()
+                    .fold(U256::ZERO, |acc, fee| acc.saturating_add((*fee).min(cap)));
+                let kept = U256::from(10000 - self.penalty(user));
+                (*user, score.saturating_mul(kept) / U256::from(10000))
+            })
+            .filter(|(_, score)| *score > U256::ZERO)
+            .collect()
+    }
+
+    /// Split the open epoch's rewards, commit them to a Merkle root and
+    /// reset the scores for the next epoch
+    pub fn finalize_epoch(&mut self, epoch: u64) -> Result<&EpochRewards> {
+        if self.epochs.contains_key(&epoch) {
+            return Err(anyhow!("Epoch {} already finalized", epoch));
+        }
+
+        let reward = self.config.reward_per_epoch;
+        let trader_pool = reward.saturating_mul(U256::from(self.config.trader_share_bps)) / U256::from(10000);
+        let maker_pool = reward - trader_pool;
+
+        let uptime = self
+            .uptime
+            .iter()
+            .filter(|(_, seconds)| **seconds > 0)
+            .map(|(user, seconds)| (*user, U256::from(*seconds)))
+            .collect();
+
+        let mut rewards: BTreeMap<Address, U256> = BTreeMap::new();
+        for (pool, scores) in [(trader_pool, self.volume_scores()), (maker_pool, uptime)] {
+            let total = scores.values().fold(U256::ZERO, |acc, s| acc.saturating_add(*s));
+            if total == U256::ZERO {
+                continue;
+            }
+            for (user, score) in scores {
+                let amount = pool.saturating_mul(score) / total;
+                if amount > U256::ZERO {
+                    *rewards.entry(user).or_insert(U256::ZERO) += amount;
+                }
+            }
+        }
+
+        let rewards: Vec<_> = rewards.into_iter().collect();
+        let leaves = rewards
+            .iter()
+            .map(|(user, amount)| reward_leaf(epoch, user, *amount))
+            .collect();
+        let root = merkle_levels(leaves)
+            .last()
+            .and_then(|level| level.first().copied())
+            .unwrap_or(B256::ZERO);
+
+        self.volume_scores.clear();
+        self.uptime.clear();
+        self.self_trades.clear();
+        self.penalties.clear();
+
+        let total = rewards.iter().fold(U256::ZERO, |acc, (_, amount)| acc + *amount);
+        Ok(self.epochs.entry(epoch).or_insert(EpochRewards {
+            epoch,
+            root,
+            total,
+            rewards,
+        }))
+    }
+
+    /// Get a finalized epoch
+    pub fn epoch_rewards(&self, epoch: u64) -> Option<&EpochRewards> {
+        self.epochs.get(&epoch)
+    }
+
+    /// A user's reward for an epoch and its Merkle proof
+    pub fn proof(&self, epoch: u64, user: &Address) -> Option<(U256, Vec<B256>)> {
+        let epoch_rewards = self.epochs.get(&epoch)?;
+        let mut index = epoch_rewards.rewards.iter().position(|(u, _)| u == user)?;
+        let amount = epoch_rewards.rewards[index].1;
+
+        let leaves = epoch_rewards
+            .rewards
+            .iter()
+            .map(|(u, a)| reward_leaf(epoch, u, *a))
+            .collect();
+        let mut proof = Vec::new();
+        for level in merkle_levels(leaves).iter().filter(|level| level.len() > 1) {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some((amount, proof))
+    }
+
+    /// Check a claim against the epoch root and mark it claimed
+    pub fn claim(&mut self, epoch: u64, user: Address, amount: U256, proof: &[B256]) -> Result<()> {
+        let root = self
+            .epochs
+            .get(&epoch)
+            .ok_or_else(|| anyhow!("Epoch {} not finalized", epoch))?
+            .root;
+        if self.claimed.contains(&(epoch, user)) {
+            return Err(anyhow!("Rewards already claimed"));
+        }
+        if !verify_proof(root, reward_leaf(epoch, &user, amount), proof) {
+            return Err(anyhow!("Invalid reward proof"));
+        }
+        self.claimed.insert((epoch, user));
+        Ok(())
+    }
+
+    /// Whether a user has claimed an epoch's rewards
+    pub fn is_claimed(&self, epoch: u64, user: &Address) -> bool {
+        self.claimed.contains(&(epoch, *user))
+    }
+
+    /// Restore finalized epochs and claims from storage
+    pub fn restore(&mut self, epochs: Vec<EpochRewards>, claims: Vec<(u64, Address)>) {
+        for epoch_rewards in epochs {
+            self.epochs.insert(epoch_rewards.epoch, epoch_rewards);
+        }
+        self.claimed.extend(claims);
+    }
+}
+
+impl Default for EmissionsEngine {
+    fn default() -> Self {
+        Self::new(EmissionsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(maker: u8, taker: u8) -> Fill {
+        Fill {
+            order_id: 1,
+            price: Price(1_000_000),
+            size: Size(U256::from(1)),
+            maker: Address::repeat_byte(maker),
+            taker: Address::repeat_byte(taker),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_wash_trading_heuristics() {
+        let mut engine = EmissionsEngine::default();
+        let (a, b, c) = (Address::repeat_byte(1), Address::repeat_byte(2), Address::repeat_byte(3));
+
+        // Self-match never scores
+        engine.record_fill(&fill(1, 1), U256::from(100), U256::from(100));
+        assert_eq!(engine.self_trade_count(&a), 1);
+
+        // a trades with b and c; b and c only with a
+        engine.record_fill(&fill(1, 2), U256::from(10), U256::from(90));
+        engine.record_fill(&fill(3, 1), U256::from(10), U256::from(10));
+
+        let scores = engine.volume_scores();
+        // a: raw 20 (10 + 10), each counterparty capped at 50% = 10
+        assert_eq!(scores[&a], U256::from(20));
+        // b and c only traded with one counterparty
+        assert!(!scores.contains_key(&b));
+        assert!(!scores.contains_key(&c));
+
+        engine.set_penalty(a, 2500);
+        assert_eq!(engine.volume_scores()[&a], U256::from(15));
+    }
+
+    #[test]
+    fn test_finalize_and_claim_with_proof() {
+        let mut engine = EmissionsEngine::new(EmissionsConfig {
+            reward_per_epoch: U256::from(1000),
+            min_counterparties: 1,
+            max_counterparty_share_bps: 10000,
+            ..EmissionsConfig::default()
+        });
+        engine.record_fill(&fill(1, 2), U256::from(10), U256::from(30));
+        engine.record_fill(&fill(3, 2), U256::from(20), U256::from(0));
+        engine.record_uptime(Address::repeat_byte(1), 100);
+        engine.record_uptime(Address::repeat_byte(4), 300);
+
+        let root = engine.finalize_epoch(5).unwrap().root;
+        let rewards = engine.epoch_rewards(5).unwrap().rewards.clone();
+        // Traders: 700 over scores 10/30/20; makers: 300 over uptime 100/300
+        assert_eq!(
+            rewards,
+            vec![
+                (Address::repeat_byte(1), U256::from(116 + 75)),
+                (Address::repeat_byte(2), U256::from(350)),
+                (Address::repeat_byte(3), U256::from(233)),
+                (Address::repeat_byte(4), U256::from(225)),
+            ]
+        );
+        assert!(engine.finalize_epoch(5).is_err());
+        assert!(engine.volume_scores().is_empty());
+
+        for (user, amount) in &rewards {
+            let (proved, proof) = engine.proof(5, user).unwrap();
+            assert_eq!(proved, *amount);
+            assert!(verify_proof(root, reward_leaf(5, user, proved), &proof));
+        }
+
+        let user = Address::repeat_byte(3);
+        let (amount, proof) = engine.proof(5, &user).unwrap();
+        assert!(engine.claim(5, user, amount + U256::from(1), &proof).is_err());
+        assert!(engine.claim(6, user, amount, &proof).is_err());
+        engine.claim(5, user, amount, &proof).unwrap();
+        assert!(engine.is_claimed(5, &user));
+        assert!(engine.claim(5, user, amount, &proof).is_err());
+    }
+}
@@ -1,6 +1,8 @@
+use crate::rounding::RoundingPolicy;
 use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Fee tier based on 30-day trading volume
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +61,69 @@ impl Default for FeeConfig {
     }
 }
 
+/// Where collected fees are routed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum FeeDestination {
+    /// Protocol treasury
+    Treasury,
+    /// Insurance fund
+    Insurance,
+    /// Staker rewards
+    Stakers,
+    /// Removed from supply (never claimable)
+    Burn,
+}
+
+/// Split of collected fees across destinations, in basis points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRouting {
+    pub treasury_bps: u64,
+    pub insurance_bps: u64,
+    pub staker_bps: u64,
+    pub burn_bps: u64,
+}
+
+impl Default for FeeRouting {
+    fn default() -> Self {
+        Self {
+            treasury_bps: 4000,
+            insurance_bps: 3000,
+            staker_bps: 3000,
+            burn_bps: 0,
+        }
+    }
+}
+
+impl FeeRouting {
+    /// Check that the split sums to 100%
+    pub fn validate(&self) -> Result<()> {
+        let total = self.treasury_bps + self.insurance_bps + self.staker_bps + self.burn_bps;
+        if total != 10000 {
+            return Err(anyhow!("Fee routing must sum to 10000 bps, got {}", total));
+        }
+        Ok(())
+    }
+
+    /// Share of each destination in basis points
+    fn shares(&self) -> [(FeeDestination, u64); 4] {
+        [
+            (FeeDestination::Treasury, self.treasury_bps),
+            (FeeDestination::Insurance, self.insurance_bps),
+            (FeeDestination::Stakers, self.staker_bps),
+            (FeeDestination::Burn, self.burn_bps),
+        ]
+    }
+}
+
+/// Fees distributed by one settlement
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeeSettlement {
+    /// Fees collected since the previous settlement
+    pub collected: U256,
+    /// Amount credited to each destination
+    pub distributed: BTreeMap<FeeDestination, U256>,
+}
+
 /// Volume window tracking
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct VolumeEntry {
@@ -67,6 +132,7 @@ struct VolumeEntry {
 }
 
 /// Fee engine for calculating and collecting trading fees
+#[derive(Clone)]
 pub struct FeeEngine {
     /// Fee configuration
     config: FeeConfig,
@@ -78,6 +144,14 @@ pub struct FeeEngine {
     user_fees_paid: HashMap<Address, U256>,
     /// 30-day window in seconds (30 days = 2592000 seconds)
     volume_window: u64,
+    /// Split applied at settlement
+    routing: FeeRouting,
+    /// Fees collected since the last settlement
+    pending_fees: U256,
+    /// Settled, unclaimed balance per destination (Burn: total burned)
+    destination_balances: BTreeMap<FeeDestination, U256>,
+    /// Fees round up (debit); settlement splits use the internal mode
+    rounding: RoundingPolicy,
 }
 
 impl FeeEngine {
@@ -94,6 +168,10 @@ impl FeeEngine {
             total_fees_collected: U256::ZERO,
             user_fees_paid: HashMap::new(),
             volume_window: 30 * 24 * 60 * 60, // 30 days
+            routing: FeeRouting::default(),
+            pending_fees: U256::ZERO,
+            destination_balances: BTreeMap::new(),
+            rounding: RoundingPolicy::default(),
         }
     }
     
@@ -139,8 +217,8 @@ impl FeeEngine {
         let tier = self.get_fee_tier(user, current_time);
         let fee_bps = if is_maker { tier.maker_fee_bps } else { tier.taker_fee_bps };
         
-        // Fee = notional * fee_bps / 10000
-        notional_value.saturating_mul(U256::from(fee_bps)) / U256::from(10000)
+        // Fee = notional * fee_bps / 10000, rounded up
+        self.rounding.debit(notional_value, U256::from(fee_bps), U256::from(10000))
     }
     
     /// Record trade and collect fee
@@ -163,6 +241,7 @@ impl FeeEngine {
         
         // Collect fee
         self.total_fees_collected = self.total_fees_collected.saturating_add(fee);
+        self.pending_fees = self.pending_fees.saturating_add(fee);
         
         let user_total = self.user_fees_paid.entry(user).or_insert(U256::ZERO);
         *user_total = user_total.saturating_add(fee);
@@ -203,6 +282,86 @@ impl FeeEngine {
     pub fn update_config(&mut self, config: FeeConfig) {
         self.config = config;
     }
+    
+    /// Get fee routing
+    pub fn routing(&self) -> FeeRouting {
+        self.routing
+    }
+    
+    /// Update fee routing (applies from the next settlement)
+    pub fn set_routing(&mut self, routing: FeeRouting) -> Result<()> {
+        routing.validate()?;
+        self.routing = routing;
+        Ok(())
+    }
+    
+    /// Rounding applied to fees and settlement splits
+    pub fn rounding_policy(&self) -> RoundingPolicy {
+        self.rounding
+    }
+    
+    /// Update the rounding policy
+    pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) -> Result<()> {
+        policy.validate()?;
+        self.rounding = policy;
+        Ok(())
+    }
+    
+    /// Fees collected but not yet settled
+    pub fn pending_fees(&self) -> U256 {
+        self.pending_fees
+    }
+    
+    /// Distribute pending fees across destinations.
+    ///
+    /// Shares round with the policy's internal mode; the dust goes to the
+    /// treasury so nothing is lost.
+    pub fn settle_fees(&mut self) -> FeeSettlement {
+        let collected = std::mem::take(&mut self.pending_fees);
+        let mut settlement = FeeSettlement {
+            collected,
+            distributed: BTreeMap::new(),
+        };
+        if collected == U256::ZERO {
+            return settlement;
+        }
+        
+        let mut remaining = collected;
+        for (destination, bps) in self.routing.shares() {
+            let share = self
+                .rounding
+                .internal
+                .mul_div(collected, U256::from(bps), U256::from(10000))
+                .min(remaining);
+            remaining -= share;
+            settlement.distributed.insert(destination, share);
+        }
+        *settlement.distributed.entry(FeeDestination::Treasury).or_insert(U256::ZERO) += remaining;
+        
+        for (destination, amount) in &settlement.distributed {
+            let balance = self.destination_balances.entry(*destination).or_insert(U256::ZERO);
+            *balance = balance.saturating_add(*amount);
+        }
+        settlement
+    }
+    
+    /// Settled balance of a destination (total burned for `Burn`)
+    pub fn destination_balance(&self, destination: FeeDestination) -> U256 {
+        self.destination_balances.get(&destination).copied().unwrap_or(U256::ZERO)
+    }
+    
+    /// Withdraw from a destination's settled balance
+    pub fn claim(&mut self, destination: FeeDestination, amount: U256) -> Result<()> {
+        if destination == FeeDestination::Burn {
+            return Err(anyhow!("Burned fees cannot be claimed"));
+        }
+        let balance = self.destination_balances.entry(destination).or_insert(U256::ZERO);
+        if *balance < amount {
+            return Err(anyhow!("Insufficient {:?} balance", destination));
+        }
+        *balance -= amount;
+        Ok(())
+    }
 }
 
 impl Default for FeeEngine {
@@ -431,11 +590,11 @@ mod tests {
         // Multiple trades
         engine.record_trade(user, U256::from(10000), true, 1000);  // 5
         engine.record_trade(user, U256::from(20000), false, 2000); // 20
-        engine.record_trade(user, U256::from(15000), true, 3000);  // 7.5 = 7
+        engine.record_trade(user, U256::from(15000), true, 3000);  // 7.5 = 8 (rounded up)
         
-        // Total fees = 5 + 20 + 7 = 32
-        assert_eq!(engine.get_user_fees(&user), U256::from(32));
-        assert_eq!(engine.get_total_fees(), U256::from(32));
+        // Total fees = 5 + 20 + 8 = 33
+        assert_eq!(engine.get_user_fees(&user), U256::from(33));
+        assert_eq!(engine.get_total_fees(), U256::from(33));
     }
 
     #[test]
@@ -455,4 +614,39 @@ mod tests {
         engine.update_config(new_config);
         assert_eq!(engine.config.default_maker_bps, 8);
     }
+
+    #[test]
+    fn test_settle_fees_routes_by_split() {
+        let mut engine = FeeEngine::new();
+        assert!(engine.set_routing(FeeRouting { burn_bps: 1, ..FeeRouting::default() }).is_err());
+        engine
+            .set_routing(FeeRouting {
+                treasury_bps: 5000,
+                insurance_bps: 2500,
+                staker_bps: 1500,
+                burn_bps: 1000,
+            })
+            .unwrap();
+        
+        // Fees round up: 1001 notional taker = 2 fee; 10010 taker = 11 fee
+        engine.record_trade(Address::ZERO, U256::from(10010), false, 1000);
+        engine.record_trade(Address::ZERO, U256::from(1001), false, 1000);
+        assert_eq!(engine.pending_fees(), U256::from(13));
+        
+        let settlement = engine.settle_fees();
+        assert_eq!(settlement.collected, U256::from(13));
+        // Shares round half-even (6.5 -> 6, 3.25 -> 3, 1.95 -> 2, 1.3 -> 1);
+        // the dust (13 - 6 - 3 - 2 - 1 = 1) goes to the treasury
+        assert_eq!(settlement.distributed[&FeeDestination::Treasury], U256::from(7));
+        assert_eq!(settlement.distributed[&FeeDestination::Insurance], U256::from(3));
+        assert_eq!(settlement.distributed[&FeeDestination::Stakers], U256::from(2));
+        assert_eq!(settlement.distributed[&FeeDestination::Burn], U256::from(1));
+        assert_eq!(engine.pending_fees(), U256::ZERO);
+        assert!(engine.settle_fees().distributed.is_empty());
+        
+        engine.claim(FeeDestination::Treasury, U256::from(4)).unwrap();
+        assert_eq!(engine.destination_balance(FeeDestination::Treasury), U256::from(3));
+        assert!(engine.claim(FeeDestination::Treasury, U256::from(4)).is_err());
+        assert!(engine.claim(FeeDestination::Burn, U256::from(1)).is_err());
+    }
 }
@@ -1,8 +1,9 @@
+use crate::rounding::RoundingPolicy;
 use crate::types::*;
 use alloy_primitives::Address;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Funding rate configuration
 #[derive(Debug, Clone)]
@@ -25,6 +26,56 @@ impl Default for FundingConfig {
     }
 }
 
+impl FundingConfig {
+    /// Check the parameters are usable
+    pub fn validate(&self) -> Result<()> {
+        if self.interval == 0 {
+            return Err(anyhow!("Funding interval must be positive"));
+        }
+        if !(self.max_rate > 0.0 && self.max_rate < 1.0) {
+            return Err(anyhow!("Max funding rate must be in (0, 1)"));
+        }
+        if !(0.0..1.0).contains(&self.dampening) {
+            return Err(anyhow!("Funding dampening must be in [0, 1)"));
+        }
+        Ok(())
+    }
+}
+
+/// Bounds on how fast a new funding configuration takes effect
+#[derive(Debug, Clone)]
+pub struct FundingChangeGuard {
+    /// Number of funding intervals a change is phased in over
+    pub phase_in_intervals: u32,
+    /// Maximum change of the interval per step, in seconds
+    pub max_interval_step: u64,
+    /// Maximum change of the rate cap per step
+    pub max_rate_step: f64,
+    /// Maximum change of the dampening factor per step
+    pub max_dampening_step: f64,
+}
+
+impl Default for FundingChangeGuard {
+    fn default() -> Self {
+        Self {
+            phase_in_intervals: 3,
+            max_interval_step: 3600,     // 1 hour
+            max_rate_step: 0.0005,       // 0.05%
+            max_dampening_step: 0.05,
+        }
+    }
+}
+
+/// Funding configuration being phased in
+#[derive(Debug, Clone)]
+struct PendingFundingConfig {
+    target: FundingConfig,
+    /// Steps left to reach the target if no bound applies
+    remaining: u32,
+    /// Timestamp of the last step (or of scheduling)
+    last_step: u64,
+}
+
 /// Funding payment record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FundingPayment {
@@ -35,7 +86,39 @@ pub struct FundingPayment {
     pub timestamp: u64,
 }
 
+/// Funding of one account in one interval, netted across its positions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetFundingSettlement {
+    pub user: Address,
+    /// Net amount, rounded once; positive = receive, negative = pay
+    pub amount: i64,
+    /// Assets whose funding was netted, by asset ID
+    pub assets: Vec<AssetId>,
+    pub timestamp: u64,
+}
+
+/// Payments of one funding round across assets
+#[derive(Debug, Clone, Default)]
+pub struct FundingRound {
+    /// Per-position payments of accounts that are not netted
+    pub payments: Vec<FundingPayment>,
+    /// One settlement per netted account, sorted by account
+    pub settlements: Vec<NetFundingSettlement>,
+}
+
+/// Funding round of an asset, persisted for history queries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FundingRecord {
+    pub asset: AssetId,
+    pub timestamp: u64,
+    /// Rate applied in this round
+    pub rate: f64,
+    pub mark_price: Price,
+    pub index_price: Price,
+}
+
 /// Funding rate engine
+#[derive(Clone)]
 pub struct FundingEngine {
     config: FundingConfig,
     /// Current funding rates by asset
@@ -46,6 +129,16 @@ pub struct FundingEngine {
     cumulative_premium: HashMap<AssetId, f64>,
     /// Payment history
     payments: Vec<FundingPayment>,
+    /// Netted settlement history
+    settlements: Vec<NetFundingSettlement>,
+    /// Collateral asset netted settlements are paid in
+    settlement_asset: AssetId,
+    /// Rate-of-change bounds for configuration updates
+    guard: FundingChangeGuard,
+    /// Configuration change being phased in
+    pending_config: Option<PendingFundingConfig>,
+    /// Payments received round down, payments owed round up
+    rounding: RoundingPolicy,
 }
 
 impl FundingEngine {
@@ -56,7 +149,94 @@ impl FundingEngine {
             last_funding: HashMap::new(),
             cumulative_premium: HashMap::new(),
             payments: Vec::new(),
+            settlements: Vec::new(),
+            settlement_asset: AssetId(0),
+            guard: FundingChangeGuard::default(),
+            pending_config: None,
+            rounding: RoundingPolicy::default(),
+        }
+    }
+    
+    /// Get the configuration in effect
+    pub fn config(&self) -> &FundingConfig {
+        &self.config
+    }
+    
+    /// Get the configuration being phased in, if any
+    pub fn pending_config(&self) -> Option<&FundingConfig> {
+        self.pending_config.as_ref().map(|p| &p.target)
+    }
+    
+    /// Collateral asset netted settlements are paid in
+    pub fn settlement_asset(&self) -> AssetId {
+        self.settlement_asset
+    }
+    
+    /// Set the collateral asset netted settlements are paid in
+    pub fn set_settlement_asset(&mut self, asset: AssetId) {
+        self.settlement_asset = asset;
+    }
+    
+    /// Set the rounding policy for payments and settlements
+    pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) -> Result<()> {
+        policy.validate()?;
+        self.rounding = policy;
+        Ok(())
+    }
+    
+    /// Set the rate-of-change bounds for configuration updates
+    pub fn set_change_guard(&mut self, guard: FundingChangeGuard) {
+        self.guard = guard;
+    }
+    
+    /// Schedule a new configuration. It is phased in over the guard's number
+    /// of funding intervals, one bounded step per elapsed interval, so open
+    /// positions never see a sudden change. Replaces any pending change.
+    pub fn schedule_config(&mut self, target: FundingConfig, timestamp: u64) -> Result<()> {
+        target.validate()?;
+        self.pending_config = Some(PendingFundingConfig {
+            target,
+            remaining: self.guard.phase_in_intervals.max(1),
+            last_step: timestamp,
+        });
+        Ok(())
+    }
+    
+    /// Move the configuration one step toward a pending change once a
+    /// funding interval has elapsed since the last step. Returns whether
+    /// the configuration changed.
+    pub fn step_config(&mut self, timestamp: u64) -> bool {
+        let Some(pending) = self.pending_config.as_mut() else {
+            return false;
+        };
+        if timestamp.saturating_sub(pending.last_step) < self.config.interval {
+            return false;
         }
+        
+        let remaining = pending.remaining;
+        let target = &pending.target;
+        let config = &mut self.config;
+        
+        let interval_delta = (target.interval as i128 - config.interval as i128) / remaining as i128;
+        let max_interval = self.guard.max_interval_step as i128;
+        config.interval = (config.interval as i128 + interval_delta.clamp(-max_interval, max_interval)) as u64;
+        config.max_rate += ((target.max_rate - config.max_rate) / remaining as f64)
+            .clamp(-self.guard.max_rate_step, self.guard.max_rate_step);
+        config.dampening += ((target.dampening - config.dampening) / remaining as f64)
+            .clamp(-self.guard.max_dampening_step, self.guard.max_dampening_step);
+        
+        pending.remaining = remaining.saturating_sub(1).max(1);
+        pending.last_step = timestamp;
+        
+        // Snap float parameters once within rounding of the target
+        let reached = config.interval == target.interval
+            && (config.max_rate - target.max_rate).abs() < 1e-12
+            && (config.dampening - target.dampening).abs() < 1e-12;
+        if reached {
+            *config = target.clone();
+            self.pending_config = None;
+        }
+        true
     }
     
     /// Update funding rate based on mark vs index
@@ -82,13 +262,19 @@ impl FundingEngine {
         Ok(rate)
     }
     
-    /// Calculate funding payment for a position
+    /// Calculate funding payment for a position, rounded toward the
+    /// protocol
     pub fn calculate_payment(
         &self,
         asset: AssetId,
         position_size: i64,
         mark_price: Price,
     ) -> i64 {
+        self.rounding.signed(self.unrounded_payment(asset, position_size, mark_price))
+    }
+    
+    /// Funding payment for a position before rounding
+    fn unrounded_payment(&self, asset: AssetId, position_size: i64, mark_price: Price) -> f64 {
         let rate = self.current_rates.get(&asset).copied().unwrap_or(0.0);
         
         // Payment = position_size * mark_price * funding_rate
@@ -97,13 +283,13 @@ impl FundingEngine {
         
         // Longs pay when rate is positive, receive when negative
         // Shorts receive when rate is positive, pay when negative
-        -(payment as i64)
+        -payment
     }
     
     /// Check if funding is due
     pub fn is_funding_due(&self, asset: AssetId, timestamp: u64) -> bool {
         if let Some(last) = self.last_funding.get(&asset) {
-            timestamp - last >= self.config.interval
+            timestamp.saturating_sub(*last) >= self.config.interval
         } else {
             true  // First funding
         }
@@ -140,6 +326,115 @@ impl FundingEngine {
         Ok(payment)
     }
     
+    /// Settle one funding round for every position in `asset`.
+    ///
+    /// Unlike `apply_funding`, all positions are paid before the round is
+    /// marked done. Payments follow the order of `positions`.
+    pub fn settle_funding(
+        &mut self,
+        asset: AssetId,
+        positions: &[(Address, i64)],
+        mark_price: Price,
+        timestamp: u64,
+    ) -> Vec<FundingPayment> {
+        if !self.is_funding_due(asset, timestamp) {
+            return Vec::new();
+        }
+        
+        let rate = self.get_rate(asset);
+        let mut settled = Vec::new();
+        for &(user, size) in positions {
+            if size == 0 {
+                continue;
+            }
+            
+            let payment = FundingPayment {
+                user,
+                asset,
+                amount: self.calculate_payment(asset, size, mark_price),
+                rate,
+                timestamp,
+            };
+            self.payments.push(payment.clone());
+            settled.push(payment);
+        }
+        
+        self.last_funding.insert(asset, timestamp);
+        settled
+    }
+    
+    /// Settle one funding round across every due market in `markets`
+    /// (asset, mark price) for `positions` (user, asset, size).
+    ///
+    /// Accounts for which `netted` holds get their payments in all due
+    /// markets summed and rounded once into a single settlement, so a cross
+    /// margin account is adjusted and recorded once per interval however
+    /// many positions it holds. Other accounts are paid per position, as by
+    /// `settle_funding`.
+    pub fn settle_round(
+        &mut self,
+        markets: &[(AssetId, Price)],
+        positions: &[(Address, AssetId, i64)],
+        netted: impl Fn(&Address) -> bool,
+        timestamp: u64,
+    ) -> FundingRound {
+        let due: HashMap<AssetId, Price> = markets
+            .iter()
+            .filter(|(asset, _)| self.is_funding_due(*asset, timestamp))
+            .copied()
+            .collect();
+        
+        let mut round = FundingRound::default();
+        let mut net: BTreeMap<Address, (f64, Vec<AssetId>)> = BTreeMap::new();
+        for &(user, asset, size) in positions {
+            let Some(&mark_price) = due.get(&asset) else {
+                continue;
+            };
+            if size == 0 {
+                continue;
+            }
+            
+            if netted(&user) {
+                let (amount, assets) = net.entry(user).or_default();
+                *amount += self.unrounded_payment(asset, size, mark_price);
+                assets.push(asset);
+            } else {
+                let payment = FundingPayment {
+                    user,
+                    asset,
+                    amount: self.calculate_payment(asset, size, mark_price),
+                    rate: self.get_rate(asset),
+                    timestamp,
+                };
+                self.payments.push(payment.clone());
+                round.payments.push(payment);
+            }
+        }
+        
+        for (user, (amount, mut assets)) in net {
+            assets.sort_by_key(|a| a.0);
+            assets.dedup();
+            let settlement = NetFundingSettlement { user, amount: self.rounding.signed(amount), assets, timestamp };
+            self.settlements.push(settlement.clone());
+            round.settlements.push(settlement);
+        }
+        
+        for asset in due.keys() {
+            self.last_funding.insert(*asset, timestamp);
+        }
+        round
+    }
+    
+    /// Get netted settlement history for user
+    pub fn get_user_settlements(&self, user: &Address) -> Vec<&NetFundingSettlement> {
+        self.settlements.iter().filter(|s| s.user == *user).collect()
+    }
+    
+    /// Get all netted settlements
+    pub fn get_settlements(&self) -> &[NetFundingSettlement] {
+        &self.settlements
+    }
+    
     /// Get current funding rate
     pub fn get_rate(&self, asset: AssetId) -> f64 {
         self.current_rates.get(&asset).copied().unwrap_or(0.0)
@@ -187,6 +482,68 @@ mod tests {
         assert!(rate < 0.01);
     }
 
+    #[test]
+    fn test_config_change_phases_in_with_bounded_steps() {
+        let mut engine = FundingEngine::default();
+        let target = FundingConfig { interval: 3600, max_rate: 0.0035, dampening: 0.95 };
+        assert!(engine.schedule_config(FundingConfig { interval: 0, ..target.clone() }, 0).is_err());
+        engine.schedule_config(target, 0).unwrap();
+        
+        // Nothing changes before a funding interval has elapsed
+        assert!(!engine.step_config(28_799));
+        assert_eq!(engine.config().interval, 28_800);
+        
+        // Each step covers a third of the remaining gap, within the bounds
+        assert!(engine.step_config(28_800));
+        assert_eq!(engine.config().interval, 25_200);
+        assert!((engine.config().max_rate - 0.001).abs() < 1e-12);
+        
+        let mut now = 28_800;
+        while engine.pending_config().is_some() {
+            now += engine.config().interval;
+            assert!(engine.step_config(now));
+        }
+        assert_eq!(engine.config().interval, 3600);
+        assert_eq!(engine.config().max_rate, 0.0035);
+    }
+
+    #[test]
+    fn test_round_nets_account_funding_and_rounds_once() {
+        let mut engine = FundingEngine::default();
+        let (btc, eth, sol) = (AssetId(1), AssetId(2), AssetId(3));
+        for asset in [btc, eth, sol] {
+            engine.current_rates.insert(asset, 0.0005);
+        }
+        engine.last_funding.insert(sol, 0);
+        let (cross, isolated) = (Address::from([1u8; 20]), Address::from([2u8; 20]));
+        let mark = Price::from_float(100.0);
+        
+        // 10 * 100 * 0.05% = 0.5 per position, which rounds up to 1 alone
+        let positions = [
+            (cross, btc, 10),
+            (cross, eth, 10),
+            (cross, sol, 10),
+            (isolated, btc, 10),
+            (isolated, eth, 10),
+        ];
+        let markets = [(btc, mark), (eth, mark), (sol, mark)];
+        let round = engine.settle_round(&markets, &positions, |user| *user == cross, 1000);
+        
+        // SOL is not due; BTC and ETH net to one settlement of exactly -1
+        assert_eq!(
+            round.settlements,
+            vec![NetFundingSettlement { user: cross, amount: -1, assets: vec![btc, eth], timestamp: 1000 }]
+        );
+        assert_eq!(round.payments.len(), 2);
+        assert!(round.payments.iter().all(|p| p.user == isolated && p.amount == -1));
+        assert_eq!(engine.get_user_settlements(&cross).len(), 1);
+        assert!(engine.get_user_payments(&cross).is_empty());
+        
+        // The round is done for the due markets only
+        assert!(!engine.is_funding_due(btc, 1000));
+        assert_eq!(engine.get_last_funding(sol), Some(0));
+    }
+
     #[test]
     fn test_funding_payment_positive_rate() {
         let mut engine = FundingEngine::default();
@@ -323,5 +680,27 @@ mod tests {
         let payment = engine.calculate_payment(asset, -100, Price::from_float(100.0));
         assert!(payment > 0); // Positive = receive
     }
-}
 
+    #[test]
+    fn test_settle_funding_pays_all_positions_once() {
+        let mut engine = FundingEngine::default();
+        let asset = AssetId(1);
+        engine.current_rates.insert(asset, 0.001);
+        
+        let long = Address::from([1u8; 20]);
+        let short = Address::from([2u8; 20]);
+        let positions = [(long, 100), (short, -100), (Address::ZERO, 0)];
+        
+        let paid = engine.settle_funding(asset, &positions, Price::from_float(100.0), 1000);
+        assert_eq!(paid.len(), 2);
+        assert_eq!(paid[0].user, long);
+        assert!(paid[0].amount < 0);
+        assert_eq!(paid[1].amount, -paid[0].amount);
+        
+        // Round done until the next interval
+        assert!(engine.settle_funding(asset, &positions, Price::from_float(100.0), 1001).is_empty());
+        let next = 1000 + FundingConfig::default().interval;
+        assert_eq!(engine.settle_funding(asset, &positions, Price::from_float(100.0), next).len(), 2);
+        assert_eq!(engine.get_payments().len(), 4);
+    }
+}
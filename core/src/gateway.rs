@@ -0,0 +1,275 @@
+use crate::auth::{CoreAction, SignedAction};
+use crate::simulation::OrderSimulationRequest;
+use crate::state_machine::CoreStateMachine;
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, VecDeque};
+
+/// Order entry rules of one market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketRules {
+    /// Prices must be a multiple of this (0 = any price)
+    pub tick_size: u64,
+    /// Sizes must be a multiple of this (0 = any size)
+    pub lot_size: U256,
+    /// Largest size of a single order
+    pub max_order_size: Option<U256>,
+    /// Largest notional of a single order
+    pub max_notional: Option<U256>,
+}
+
+impl Default for MarketRules {
+    fn default() -> Self {
+        Self {
+            tick_size: 0,
+            lot_size: U256::ZERO,
+            max_order_size: None,
+            max_notional: None,
+        }
+    }
+}
+
+/// Order gateway configuration
+#[derive(Debug, Clone)]
+pub struct GatewayConfig {
+    /// Rules for markets without their own entry
+    pub default_rules: MarketRules,
+    /// Per-market rules
+    pub markets: HashMap<AssetId, MarketRules>,
+    /// Actions held for forwarding before new ones are refused
+    pub max_queue: usize,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            default_rules: MarketRules::default(),
+            markets: HashMap::new(),
+            max_queue: 10_000,
+        }
+    }
+}
+
+impl GatewayConfig {
+    /// Rules that apply to `asset`
+    pub fn rules(&self, asset: AssetId) -> MarketRules {
+        self.markets.get(&asset).copied().unwrap_or(self.default_rules)
+    }
+}
+
+/// Counters of gateway decisions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GatewayStats {
+    pub accepted: u64,
+    pub rejected: u64,
+    pub forwarded: u64,
+}
+
+/// Order entry gateway
+///
+/// Accepts signed actions ahead of consensus and runs the checks the engine
+/// would run on them (signature, nonce, tick and lot size, risk caps, order
+/// limits and a margin simulation) against a read-only replica of the core
+/// state. Actions that pass are queued for the mempool, so invalid orders
+/// are turned away before they take block space.
+#[derive(Debug, Default)]
+pub struct OrderGateway {
+    config: GatewayConfig,
+    /// Accepted actions waiting to be forwarded
+    queue: VecDeque<SignedAction>,
+    /// Next nonce per account after the actions accepted here
+    next_nonces: HashMap<Address, u64>,
+    stats: GatewayStats,
+}
+
+impl OrderGateway {
+    pub fn new(config: GatewayConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &GatewayConfig {
+        &self.config
+    }
+
+    /// Get decision counters
+    pub fn stats(&self) -> GatewayStats {
+        self.stats
+    }
+
+    /// Actions waiting to be forwarded
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Check an action against the replica and queue it for forwarding
+    pub fn submit(&mut self, replica: &CoreStateMachine, signed: SignedAction) -> Result<()> {
+        let result = if self.queue.len() >= self.config.max_queue {
+            Err(anyhow!("Gateway queue is full"))
+        } else {
+            self.precheck(replica, &signed)
+        };
+        if let Err(e) = result {
+            self.stats.rejected += 1;
+            return Err(e);
+        }
+
+        self.stats.accepted += 1;
+        self.next_nonces.insert(signed.account, signed.nonce + 1);
+        self.queue.push_back(signed);
+        Ok(())
+    }
+
+    /// Take up to `max` accepted actions, oldest first, for the mempool
+    pub fn forward(&mut self, max: usize) -> Vec<SignedAction> {
+        let count = max.min(self.queue.len());
+        self.stats.forwarded += count as u64;
+        self.queue.drain(..count).collect()
+    }
+
+    /// Run the pre-trade checks without queueing
+    ///
+    /// Nonces continue from actions already accepted here, since those
+    /// may not have reached the replica yet.
+    pub fn precheck(&self, replica: &CoreStateMachine, signed: &SignedAction) -> Result<()> {
+        let now = replica.clock().now();
+        let expected = replica
+            .sessions()
+            .next_nonce(&signed.account)
+            .max(self.next_nonces.get(&signed.account).copied().unwrap_or(0));
+        if signed.nonce != expected {
+            return Err(anyhow!(
+                "Invalid action nonce: expected {}, got {}",
+                expected,
+                signed.nonce
+            ));
+        }
+        replica.sessions().authorize_signer(replica.chain_id(), signed, now)?;
+
+        match signed.action {
+            CoreAction::PlaceLimitOrder { asset, side, price, size } => {
+                replica.order_limits().check_order(&signed.account, asset)?;
+                self.check_order(replica, signed.account, asset, side, size, Some(price), now)
+            }
+            CoreAction::PlaceMarketOrder { asset, side, size } => {
+                replica.order_limits().check_submission_rate(&signed.account)?;
+                self.check_order(replica, signed.account, asset, side, size, None, now)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn check_order(
+        &self,
+        replica: &CoreStateMachine,
+        trader: Address,
+        asset: AssetId,
+        side: Side,
+        size: Size,
+        limit_price: Option<Price>,
+        timestamp: u64,
+    ) -> Result<()> {
+        let rules = self.config.rules(asset);
+        if size.0 == U256::ZERO {
+            return Err(anyhow!("Order size must be greater than zero"));
+        }
+        if let Some(price) = limit_price {
+            if rules.tick_size > 0 && price.0 % rules.tick_size != 0 {
+                return Err(anyhow!("Price {} is not a multiple of tick size {}", price.0, rules.tick_size));
+            }
+        }
+        if rules.lot_size > U256::ZERO && size.0 % rules.lot_size != U256::ZERO {
+            return Err(anyhow!("Size {} is not a multiple of lot size {}", size.0, rules.lot_size));
+        }
+        if rules.max_order_size.is_some_and(|max| size.0 > max) {
+            return Err(anyhow!("Order size {} exceeds the gateway limit", size.0));
+        }
+
+        let simulation = replica.simulate_order(&OrderSimulationRequest {
+            trader,
+            asset,
+            side,
+            size,
+            limit_price,
+            timestamp,
+        })?;
+        // Resting size counts at its limit price
+        let notional = match limit_price {
+            Some(price) => size.0 * U256::from(price.0) / U256::from(Price::SCALE),
+            None => simulation.notional,
+        };
+        if rules.max_notional.is_some_and(|max| notional > max) {
+            return Err(anyhow!("Order notional {} exceeds the gateway limit", notional));
+        }
+        if !simulation.margin_sufficient {
+            return Err(anyhow!("Insufficient margin for order"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::PrimitiveSignature as Signature;
+    use k256::ecdsa::SigningKey;
+
+    fn sign(sk: &SigningKey, nonce: u64, action: CoreAction) -> SignedAction {
+        let account = Address::from_private_key(sk);
+        let hash = SignedAction::signing_hash(DEFAULT_CHAIN_ID, &account, nonce, &action);
+        let (sig, recid) = sk.sign_prehash_recoverable(hash.as_slice()).unwrap();
+        SignedAction {
+            account,
+            nonce,
+            action,
+            signature: Signature::from_signature_and_parity(sig, recid.is_y_odd()),
+        }
+    }
+
+    #[test]
+    fn test_gateway_rejects_invalid_orders_before_forwarding() {
+        let sk = SigningKey::from_slice(&[1; 32]).unwrap();
+        let account = Address::from_private_key(&sk);
+        let asset = AssetId(1);
+        let mut replica = CoreStateMachine::new();
+        replica.deposit_collateral(account, asset, U256::from(1_000_000)).unwrap();
+
+        let mut config = GatewayConfig::default();
+        config.markets.insert(asset, MarketRules {
+            tick_size: 1_000,
+            lot_size: U256::from(10),
+            max_order_size: None,
+            max_notional: Some(U256::from(500_000)),
+        });
+        let mut gateway = OrderGateway::new(config);
+        let limit = |price: u64, size: u64| CoreAction::PlaceLimitOrder {
+            asset,
+            side: Side::Bid,
+            price: Price(price),
+            size: Size(U256::from(size)),
+        };
+
+        // Off-tick, off-lot and over-notional orders never leave the gateway
+        assert!(gateway.submit(&replica, sign(&sk, 0, limit(1_000_500, 10))).is_err());
+        assert!(gateway.submit(&replica, sign(&sk, 0, limit(1_000_000, 15))).is_err());
+        assert!(gateway.submit(&replica, sign(&sk, 0, limit(1_000_000, 600_000))).is_err());
+
+        // Valid orders pipeline on consecutive nonces ahead of the replica
+        gateway.submit(&replica, sign(&sk, 0, limit(1_000_000, 10))).unwrap();
+        gateway.submit(&replica, sign(&sk, 1, limit(1_000_000, 20))).unwrap();
+        assert!(gateway.submit(&replica, sign(&sk, 1, limit(1_000_000, 20))).is_err());
+        assert_eq!(gateway.stats(), GatewayStats { accepted: 2, rejected: 4, forwarded: 0 });
+
+        let forwarded = gateway.forward(10);
+        assert_eq!(forwarded.iter().map(|a| a.nonce).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(gateway.queue_len(), 0);
+        for signed in &forwarded {
+            replica.dispatch(signed).unwrap();
+        }
+    }
+}
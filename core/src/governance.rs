@@ -0,0 +1,56 @@
+use alloy_primitives::Address;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Addresses authorized to change protocol parameters and move protocol funds.
+///
+/// Configured at genesis; with no authorities every governed action is
+/// rejected.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Governance {
+    authorities: BTreeSet<Address>,
+}
+
+impl Governance {
+    pub fn new(authorities: impl IntoIterator<Item = Address>) -> Self {
+        Self {
+            authorities: authorities.into_iter().collect(),
+        }
+    }
+
+    /// Whether `caller` may perform governed actions
+    pub fn is_authorized(&self, caller: &Address) -> bool {
+        self.authorities.contains(caller)
+    }
+
+    /// Fail unless `caller` is a governance authority
+    pub fn ensure_authorized(&self, caller: &Address) -> Result<()> {
+        if !self.is_authorized(caller) {
+            return Err(anyhow!("{} is not a governance authority", caller));
+        }
+        Ok(())
+    }
+
+    /// Add an authority; must be called by an existing one
+    pub fn add_authority(&mut self, caller: &Address, authority: Address) -> Result<()> {
+        self.ensure_authorized(caller)?;
+        self.authorities.insert(authority);
+        Ok(())
+    }
+
+    /// Remove an authority; the last one cannot be removed
+    pub fn remove_authority(&mut self, caller: &Address, authority: &Address) -> Result<()> {
+        self.ensure_authorized(caller)?;
+        if self.authorities.len() == 1 && self.authorities.contains(authority) {
+            return Err(anyhow!("Cannot remove the last governance authority"));
+        }
+        self.authorities.remove(authority);
+        Ok(())
+    }
+
+    /// Current authorities
+    pub fn authorities(&self) -> impl Iterator<Item = &Address> {
+        self.authorities.iter()
+    }
+}
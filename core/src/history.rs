@@ -30,11 +30,6 @@ impl OrderHistory {
         Ok(vec![])
     }
     
-    /// Store a fill
-    pub fn store_fill(&self, fill: &Fill) -> Result<()> {
-        self.storage.store_fill(fill)
-    }
-    
     /// Get fill count for an order
     pub fn get_fill_count(&self, order_id: OrderId) -> Result<usize> {
         let fills = self.get_order_fills(order_id)?;
@@ -45,6 +40,7 @@ impl OrderHistory {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::StorageBatch;
     use alloy_primitives::U256;
     use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -60,11 +56,22 @@ mod tests {
         format!("/tmp/openliquid_test_history_{}_{}", timestamp, counter)
     }
 
+    /// Fills are written by the state machine, which owns the sequence
+    fn store_fills(storage: &CoreStorage, fills: &[Fill]) {
+        let mut batch = StorageBatch::new();
+        let first = storage.load_next_fill_seq().unwrap();
+        for (i, fill) in fills.iter().enumerate() {
+            batch.put_fill(first + i as u64, fill).unwrap();
+        }
+        batch.set_next_fill_seq(first + fills.len() as u64);
+        storage.write_batch(batch).unwrap();
+    }
+
     #[test]
     fn test_store_and_get_fill() {
         let path = temp_db_path();
         let storage = Arc::new(CoreStorage::new(&path).unwrap());
-        let history = OrderHistory::new(storage);
+        let history = OrderHistory::new(storage.clone());
         
         let fill = Fill {
             order_id: 1,
@@ -75,7 +82,7 @@ mod tests {
             timestamp: 1000,
         };
         
-        history.store_fill(&fill).unwrap();
+        store_fills(&storage, &[fill]);
         let fills = history.get_order_fills(1).unwrap();
         
         assert_eq!(fills.len(), 1);
@@ -90,7 +97,7 @@ mod tests {
     fn test_multiple_fills_for_order() {
         let path = temp_db_path();
         let storage = Arc::new(CoreStorage::new(&path).unwrap());
-        let history = OrderHistory::new(storage);
+        let history = OrderHistory::new(storage.clone());
         
         // Store multiple fills for same order
         for i in 1..=3 {
@@ -102,7 +109,7 @@ mod tests {
                 taker: Address::from([2u8; 20]),
                 timestamp: 1000 + i,
             };
-            history.store_fill(&fill).unwrap();
+            store_fills(&storage, &[fill]);
         }
         
         let fills = history.get_order_fills(1).unwrap();
@@ -116,7 +123,7 @@ mod tests {
     fn test_separate_orders() {
         let path = temp_db_path();
         let storage = Arc::new(CoreStorage::new(&path).unwrap());
-        let history = OrderHistory::new(storage);
+        let history = OrderHistory::new(storage.clone());
         
         // Store fills for different orders
         for order_id in 1..=3 {
@@ -128,7 +135,7 @@ mod tests {
                 taker: Address::from([2u8; 20]),
                 timestamp: 1000,
             };
-            history.store_fill(&fill).unwrap();
+            store_fills(&storage, &[fill]);
         }
         
         let fills1 = history.get_order_fills(1).unwrap();
@@ -147,7 +154,7 @@ mod tests {
     fn test_get_fill_count() {
         let path = temp_db_path();
         let storage = Arc::new(CoreStorage::new(&path).unwrap());
-        let history = OrderHistory::new(storage);
+        let history = OrderHistory::new(storage.clone());
         
         // No fills initially
         let count = history.get_fill_count(1).unwrap();
@@ -162,7 +169,7 @@ mod tests {
             taker: Address::from([2u8; 20]),
             timestamp: 1000,
         };
-        history.store_fill(&fill).unwrap();
+        store_fills(&storage, &[fill]);
         
         let count = history.get_fill_count(1).unwrap();
         assert_eq!(count, 1);
@@ -175,7 +182,7 @@ mod tests {
     fn test_empty_order_fills() {
         let path = temp_db_path();
         let storage = Arc::new(CoreStorage::new(&path).unwrap());
-        let history = OrderHistory::new(storage);
+        let history = OrderHistory::new(storage.clone());
         
         let fills = history.get_order_fills(999).unwrap();
         assert_eq!(fills.len(), 0);
@@ -188,7 +195,7 @@ mod tests {
     fn test_get_user_fills() {
         let path = temp_db_path();
         let storage = Arc::new(CoreStorage::new(&path).unwrap());
-        let history = OrderHistory::new(storage);
+        let history = OrderHistory::new(storage.clone());
         
         let user = Address::from([1u8; 20]);
         let fills = history.get_user_fills(&user).unwrap();
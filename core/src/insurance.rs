@@ -1,12 +1,17 @@
+use crate::types::AssetId;
 use alloy_primitives::U256;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Insurance fund contribution record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contribution {
     pub amount: U256,
     pub timestamp: u64,
+    /// Market sub-fund credited, `None` for the shared backstop
+    #[serde(default)]
+    pub market: Option<AssetId>,
 }
 
 /// Insurance fund payout record
@@ -14,12 +19,78 @@ pub struct Contribution {
 pub struct Payout {
     pub amount: U256,
     pub timestamp: u64,
+    /// Market whose bad debt was covered, `None` for backstop-level debt
+    #[serde(default)]
+    pub market: Option<AssetId>,
+    /// Part of `amount` drawn from the shared backstop
+    #[serde(default)]
+    pub subsidy: U256,
+}
+
+/// How much a market may draw from the shared backstop once its own
+/// sub-fund is exhausted
+///
+/// The default is isolated: bad debt beyond the market's sub-fund is left
+/// uncovered (for ADL) rather than taken from the backstop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrossSubsidyRule {
+    /// Largest share of the backstop balance one payout may take (bps)
+    pub max_share_bps: u64,
+    /// Cumulative subsidy the market may ever receive
+    pub max_total: U256,
+}
+
+impl CrossSubsidyRule {
+    /// No subsidy from the backstop
+    pub fn isolated() -> Self {
+        Self::default()
+    }
+}
+
+/// Balance and flows of one market's sub-fund
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketInsuranceStats {
+    pub balance: U256,
+    pub contributions: U256,
+    /// Bad debt covered, including subsidy
+    pub payouts: U256,
+    /// Drawn from the shared backstop
+    pub subsidy_received: U256,
+    /// Bad debt left uncovered
+    pub shortfall: U256,
+}
+
+/// Insurance fund balances and flows, per market and for the backstop
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsuranceStats {
+    pub backstop_balance: U256,
+    pub backstop_contributions: U256,
+    /// Paid from the backstop, including subsidies to markets
+    pub backstop_payouts: U256,
+    pub markets: HashMap<AssetId, MarketInsuranceStats>,
+}
+
+impl InsuranceStats {
+    /// Backstop plus every market sub-fund
+    pub fn total_balance(&self) -> U256 {
+        self.markets
+            .values()
+            .fold(self.backstop_balance, |acc, m| acc.saturating_add(m.balance))
+    }
 }
 
 /// Insurance fund for bad debt
+///
+/// Each market has its own sub-fund that covers its bad debt first. The
+/// shared backstop covers the rest only as far as the market's
+/// `CrossSubsidyRule` allows, so a long-tail market cannot drain it.
 pub struct InsuranceFund {
-    /// Total fund balance
+    /// Shared backstop balance
     balance: U256,
+    /// Per-market sub-funds
+    markets: HashMap<AssetId, MarketInsuranceStats>,
+    /// Cross-subsidy rules by market (isolated when absent)
+    subsidy_rules: HashMap<AssetId, CrossSubsidyRule>,
     /// Contributions (from liquidation fees)
     contributions: Vec<Contribution>,
     /// Payouts (for bad debt)
@@ -30,41 +101,93 @@ impl InsuranceFund {
     pub fn new() -> Self {
         Self {
             balance: U256::ZERO,
+            markets: HashMap::new(),
+            subsidy_rules: HashMap::new(),
             contributions: Vec::new(),
             payouts: Vec::new(),
         }
     }
     
-    /// Add funds to insurance pool
+    /// Add funds to the shared backstop
     pub fn contribute(&mut self, amount: U256, timestamp: u64) {
         self.balance = self.balance.saturating_add(amount);
-        self.contributions.push(Contribution { amount, timestamp });
+        self.contributions.push(Contribution { amount, timestamp, market: None });
     }
     
-    /// Use insurance fund to cover bad debt
+    /// Add funds to a market's sub-fund
+    pub fn contribute_to_market(&mut self, asset: AssetId, amount: U256, timestamp: u64) {
+        let market = self.markets.entry(asset).or_default();
+        market.balance = market.balance.saturating_add(amount);
+        market.contributions = market.contributions.saturating_add(amount);
+        self.contributions.push(Contribution { amount, timestamp, market: Some(asset) });
+    }
+    
+    /// Use the shared backstop to cover bad debt
     pub fn cover_bad_debt(
         &mut self,
         amount: U256,
         timestamp: u64,
     ) -> Result<U256> {
-        if self.balance < amount {
-            // Partial coverage
-            let covered = self.balance;
-            self.balance = U256::ZERO;
-            self.payouts.push(Payout { amount: covered, timestamp });
-            return Ok(covered);
-        }
+        let covered = amount.min(self.balance);
+        self.balance -= covered;
+        self.payouts.push(Payout { amount: covered, timestamp, market: None, subsidy: covered });
+        Ok(covered)
+    }
+    
+    /// Cover a market's bad debt from its sub-fund, then from the backstop
+    /// within the market's cross-subsidy rule; returns the amount covered
+    pub fn cover_market_bad_debt(
+        &mut self,
+        asset: AssetId,
+        amount: U256,
+        timestamp: u64,
+    ) -> Result<U256> {
+        let rule = self.subsidy_rule(asset);
+        let backstop = self.balance;
+        let market = self.markets.entry(asset).or_default();
+        
+        let own = amount.min(market.balance);
+        market.balance -= own;
         
-        self.balance = self.balance.saturating_sub(amount);
-        self.payouts.push(Payout { amount, timestamp });
-        Ok(amount)
+        let share_cap = backstop.saturating_mul(U256::from(rule.max_share_bps.min(10_000))) / U256::from(10_000);
+        let subsidy = (amount - own)
+            .min(share_cap)
+            .min(rule.max_total.saturating_sub(market.subsidy_received));
+        market.subsidy_received = market.subsidy_received.saturating_add(subsidy);
+        
+        let covered = own + subsidy;
+        market.payouts = market.payouts.saturating_add(covered);
+        market.shortfall = market.shortfall.saturating_add(amount - covered);
+        self.balance -= subsidy;
+        self.payouts.push(Payout { amount: covered, timestamp, market: Some(asset), subsidy });
+        Ok(covered)
+    }
+    
+    /// Set a market's cross-subsidy rule
+    pub fn set_subsidy_rule(&mut self, asset: AssetId, rule: CrossSubsidyRule) {
+        self.subsidy_rules.insert(asset, rule);
     }
     
-    /// Get current balance
+    /// Get a market's cross-subsidy rule
+    pub fn subsidy_rule(&self, asset: AssetId) -> CrossSubsidyRule {
+        self.subsidy_rules.get(&asset).copied().unwrap_or_default()
+    }
+    
+    /// Get current balance across the backstop and every sub-fund
     pub fn get_balance(&self) -> U256 {
+        self.stats().total_balance()
+    }
+    
+    /// Get the shared backstop balance
+    pub fn backstop_balance(&self) -> U256 {
         self.balance
     }
     
+    /// Get a market's sub-fund balance
+    pub fn market_balance(&self, asset: AssetId) -> U256 {
+        self.markets.get(&asset).map_or(U256::ZERO, |m| m.balance)
+    }
+    
     /// Get total contributions
     pub fn total_contributions(&self) -> U256 {
         self.contributions.iter().fold(U256::ZERO, |acc, c| acc.saturating_add(c.amount))
@@ -85,10 +208,24 @@ impl InsuranceFund {
         &self.payouts
     }
     
-    /// Check if fund can cover amount
+    /// Check if the backstop can cover amount
     pub fn can_cover(&self, amount: U256) -> bool {
         self.balance >= amount
     }
+    
+    /// Balances and flows per market and for the backstop
+    pub fn stats(&self) -> InsuranceStats {
+        InsuranceStats {
+            backstop_balance: self.balance,
+            backstop_contributions: self
+                .contributions
+                .iter()
+                .filter(|c| c.market.is_none())
+                .fold(U256::ZERO, |acc, c| acc.saturating_add(c.amount)),
+            backstop_payouts: self.payouts.iter().fold(U256::ZERO, |acc, p| acc.saturating_add(p.subsidy)),
+            markets: self.markets.clone(),
+        }
+    }
 }
 
 impl Default for InsuranceFund {
@@ -197,6 +334,47 @@ mod tests {
         assert_eq!(fund.total_contributions(), U256::from(1500));
         assert_eq!(fund.total_payouts(), U256::from(600));
     }
-}
 
+    #[test]
+    fn test_market_bad_debt_isolated_by_default() {
+        let mut fund = InsuranceFund::new();
+        let (btc, meme) = (AssetId(1), AssetId(9));
+        fund.contribute(U256::from(1000), 0);
+        fund.contribute_to_market(meme, U256::from(100), 0);
+        
+        // The long-tail market only has its own sub-fund
+        let covered = fund.cover_market_bad_debt(meme, U256::from(300), 1).unwrap();
+        assert_eq!(covered, U256::from(100));
+        assert_eq!(fund.backstop_balance(), U256::from(1000));
+        
+        // A major may draw up to 50% of the backstop per payout, 600 in total
+        fund.set_subsidy_rule(btc, CrossSubsidyRule { max_share_bps: 5000, max_total: U256::from(600) });
+        assert_eq!(fund.cover_market_bad_debt(btc, U256::from(800), 2).unwrap(), U256::from(500));
+        assert_eq!(fund.cover_market_bad_debt(btc, U256::from(800), 3).unwrap(), U256::from(100));
+        assert_eq!(fund.backstop_balance(), U256::from(400));
+        assert_eq!(fund.get_balance(), U256::from(400));
+    }
 
+    #[test]
+    fn test_stats_report_per_market_flows() {
+        let mut fund = InsuranceFund::new();
+        let btc = AssetId(1);
+        fund.contribute(U256::from(1000), 0);
+        fund.contribute_to_market(btc, U256::from(200), 0);
+        fund.set_subsidy_rule(btc, CrossSubsidyRule { max_share_bps: 10_000, max_total: U256::MAX });
+        fund.cover_market_bad_debt(btc, U256::from(500), 1).unwrap();
+        
+        let stats = fund.stats();
+        assert_eq!(stats.backstop_balance, U256::from(700));
+        assert_eq!(stats.backstop_contributions, U256::from(1000));
+        assert_eq!(stats.backstop_payouts, U256::from(300));
+        assert_eq!(stats.markets[&btc], MarketInsuranceStats {
+            balance: U256::ZERO,
+            contributions: U256::from(200),
+            payouts: U256::from(500),
+            subsidy_received: U256::from(300),
+            shortfall: U256::ZERO,
+        });
+        assert_eq!(stats.total_balance(), U256::from(700));
+    }
+}
@@ -14,62 +14,134 @@
 
 pub mod adl;
 pub mod analytics;
+pub mod arena;
+pub mod audit_export;
+pub mod auth;
 pub mod batch;
+pub mod block_hooks;
+pub mod book_builder;
+pub mod book_snapshot;
 pub mod checkpoint;
+pub mod clock;
+pub mod delisting;
+pub mod depth_history;
+pub mod emergency;
+pub mod emissions;
 pub mod fees;
 pub mod funding;
+pub mod gateway;
+pub mod governance;
 pub mod grid_strategy;
 pub mod history;
 pub mod insurance;
 pub mod liquidation;
 pub mod liquidity_pool;
+pub mod listing;
 pub mod margin;
 pub mod matching;
 pub mod mm_analytics;
+pub mod options;
 pub mod oracle;
+pub mod order_limits;
 pub mod orders;
 pub mod orderbook;
+pub mod pnl_history;
 pub mod position_manager;
 pub mod price_protection;
+pub mod query;
 pub mod quote_manager;
 pub mod rebate;
 pub mod risk;
+pub mod rounding;
+pub mod router;
+pub mod scheduling;
+pub mod simulation;
 pub mod state_machine;
 pub mod storage;
+pub mod stress;
+pub mod tokens;
+pub mod transfer;
 pub mod types;
 pub mod vault;
 
 // Re-export commonly used types
 pub use adl::{ADLCandidate, ADLEngine};
+pub use analytics::liquidity::{
+    LiquidityMonitor, LiquiditySample, LiquiditySla, LiquidityStats, MarketMode,
+};
+pub use analytics::pools::{LpPoolReport, PoolEpochStats, SECONDS_PER_YEAR};
+pub use analytics::surveillance::{
+    FlagKind, Surveillance, SurveillanceConfig, SurveillanceFlag, SurveillanceReport,
+};
 pub use analytics::{Analytics, AssetStats, UserStats};
+pub use arena::{OrderArena, OrderIdHasher, OrderIdMap, SlotId};
+pub use audit_export::{AuditExporter, ExportFormat, ExportPartition, ExportRecord, EXPORT_SCHEMA_VERSION};
+pub use auth::{ActionOutcome, CoreAction, SessionKey, SessionRegistry, SessionScope, SignedAction};
 pub use batch::{
     BatchCancelRequest, BatchCancelResult, BatchOperations, BatchOrderBuilder, BatchOrderRequest,
     BatchResult, OrderRequest,
 };
+pub use block_hooks::{BatchAuction, BlockBeginReport, BlockEndReport, BlockHooks, MarketModeChange, TriggeredOrder};
+pub use book_builder::{Applied, BookBuilder, BookStatus, L3Change, L3Event, L3Snapshot};
+pub use book_snapshot::{decode_book, encode_book, BookSnapshotView, SnapshotCompression};
 pub use checkpoint::CheckpointManager;
-pub use fees::{FeeConfig, FeeEngine, FeeTier};
-pub use funding::{FundingConfig, FundingEngine, FundingPayment};
+pub use clock::ChainClock;
+pub use delisting::{
+    Delisting, DelistingAction, DelistingManager, DelistingParams, DelistingPhase,
+};
+pub use depth_history::{DepthConfig, DepthHeatmap, DepthHistory, DepthSnapshot, ObligationReport};
+pub use emergency::{AuditEntry, AuditEvent, EmergencyAction, EmergencyControls, EmergencyProposal, Operation, PauseScope};
+pub use emissions::{EmissionsConfig, EmissionsEngine, EpochRewards};
+pub use fees::{FeeConfig, FeeDestination, FeeEngine, FeeRouting, FeeSettlement, FeeTier};
+pub use funding::{
+    FundingChangeGuard, FundingConfig, FundingEngine, FundingPayment, FundingRecord, FundingRound,
+    NetFundingSettlement,
+};
+pub use gateway::{GatewayConfig, GatewayStats, MarketRules, OrderGateway};
+pub use governance::Governance;
 pub use grid_strategy::{GridConfig, GridStats, GridStrategy, GridStrategyManager};
 pub use history::OrderHistory;
-pub use insurance::InsuranceFund;
+pub use insurance::{CrossSubsidyRule, InsuranceFund, InsuranceStats, MarketInsuranceStats};
 pub use liquidation::{LiquidationEngine, LiquidationMode};
 pub use liquidity_pool::{LPToken, LiquidityPool, PoolId, PoolManager};
-pub use margin::{MarginConfig, MarginEngine, MarginMode};
+pub use listing::{BasketComponent, BasketDefinition, Listing, ListingKind, ListingRegistry};
+pub use margin::{MarginConfig, MarginEngine, MarginMode, PositionPreview};
 pub use matching::MatchingEngine;
 pub use mm_analytics::{AggregateStats, MMAnalytics, MMPerformanceMetrics, TradeRecord};
+pub use options::{
+    OptionKind, OptionOrder, OptionPosition, OptionSeries, OptionSettlement, OptionsConfig,
+    OptionsEngine, OptionsState,
+};
 pub use oracle::{OracleConfig, OracleEngine, PriceSource};
-pub use orders::{AdvancedOrder, AdvancedOrderType, LimitOrderParams, OrderManager, TimeInForce};
-pub use orderbook::{OrderBook, OrderBookCache, OrderBookSnapshot, PriceLevel};
+pub use order_limits::{OrderLimits, OrderLimitsConfig};
+pub use orders::{
+    AdvancedOrder, AdvancedOrderStatus, AdvancedOrderType, Bracket, BracketLeg, BracketOrderRequest, LimitOrderParams, OrderManager,
+    OrderManagerState, TimeInForce, TriggerPrices, TriggerReference,
+};
+pub use orderbook::{LevelIter, OrderBook, OrderBookCache, OrderBookSnapshot, PriceLevel};
+pub use pnl_history::{AccountSnapshot, PnlHistory, PositionSnapshot};
 pub use position_manager::{ManagedPosition, PositionId, PositionManager};
 pub use price_protection::{PriceProtection, PriceProtectionConfig};
-pub use quote_manager::{Quote, QuoteConfig, QuoteManager};
+pub use query::{BalanceMap, PositionMap, SnapshotConfig, SnapshotStore, StateSnapshot};
+pub use quote_manager::{MmpConfig, MmpEvent, Quote, QuoteConfig, QuoteManager};
 pub use rebate::{RebateEngine, RebateTier, VolumeStats};
-pub use risk::{AssetRiskLimits, LeverageTier, PortfolioRiskLimits, RiskEngine};
-pub use state_machine::CoreStateMachine;
-pub use storage::{CheckpointMetadata, CoreStorage};
+pub use risk::{
+    AssetRiskLimits, LeverageTier, OptionMarginParams, PortfolioRiskLimits, RiskEngine,
+};
+pub use rounding::{Rounding, RoundingPolicy};
+pub use router::{OrderRouter, RoutePlan, RoutedFill, RoutedOrder, RouterConfig, Venue};
+pub use scheduling::SchedulingPolicy;
+pub use simulation::{OrderSimulation, OrderSimulationRequest, OrderSimulator, SimulatedFill};
+pub use state_machine::{CoreCheckpoint, CoreStateMachine};
+pub use storage::{CheckpointMetadata, CoreStorage, StorageBatch};
+pub use stress::{AdlImpact, MarketImpact, StressLiquidation, StressReport, StressScenario, StressTester};
+pub use tokens::{TokenAmount, TokenInfo, TokenRegistry, CANONICAL_DECIMALS, MAX_TOKEN_DECIMALS};
+pub use transfer::{
+    LedgerEntry, LedgerEntryKind, PositionTransfer, SignedPositionTransfer, TransferLedger,
+};
 pub use types::{
-    AssetId, CollateralAccount, Fill, Liquidation, MarginRequirements, Order, OrderId, OrderType,
+    AssetId, CollateralAccount, DEFAULT_CHAIN_ID, Fill, Liquidation, MarginRequirements, Order, OrderId, OrderType,
     Position, Price, Side, Size,
 };
-pub use vault::{MMVault, VaultId, VaultManager, VaultStrategy};
+pub use vault::{MMVault, VaultId, VaultManager, VaultState, VaultStrategy};
 
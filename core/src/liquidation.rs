@@ -12,6 +12,7 @@ pub enum LiquidationMode {
 }
 
 /// Liquidation engine for undercollateralized positions
+#[derive(Clone)]
 pub struct LiquidationEngine {
     /// Historical liquidations
     liquidations: Vec<Liquidation>,
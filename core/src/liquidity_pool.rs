@@ -1,3 +1,5 @@
+use crate::analytics::pools::{rolling_apr_bps, LpPoolReport, PoolEpochStats};
+use crate::rounding::RoundingPolicy;
 use crate::types::*;
 use alloy_primitives::{Address, U256};
 use anyhow::{anyhow, Result};
@@ -26,6 +28,21 @@ pub struct LiquidityPool {
     pub grid_levels: Vec<Price>,
     pub size_per_level: Size,
     pub created_at: u64,
+    /// Liquidity deposited over the pool's life
+    #[serde(default)]
+    pub total_deposited: U256,
+    /// Liquidity withdrawn over the pool's life
+    #[serde(default)]
+    pub total_withdrawn: U256,
+    /// Net base bought from takers (negative = sold)
+    #[serde(default)]
+    pub base_inventory: i128,
+    /// Net quote received from takers for that base, excluding fees
+    #[serde(default)]
+    pub trade_cash: i128,
+    /// LP tokens minted and liquidity redeemed round down (credits)
+    #[serde(default)]
+    pub rounding: RoundingPolicy,
 }
 
 impl LiquidityPool {
@@ -46,6 +63,11 @@ impl LiquidityPool {
             grid_levels,
             size_per_level,
             created_at: timestamp,
+            total_deposited: U256::ZERO,
+            total_withdrawn: U256::ZERO,
+            base_inventory: 0,
+            trade_cash: 0,
+            rounding: RoundingPolicy::default(),
         }
     }
 
@@ -60,14 +82,15 @@ impl LiquidityPool {
             amount
         } else {
             // Subsequent deposits: proportional to pool share
-            amount
-                .saturating_mul(self.total_supply)
-                .checked_div(self.total_liquidity)
-                .ok_or_else(|| anyhow!("Division overflow"))?
+            if self.total_liquidity.is_zero() {
+                return Err(anyhow!("Division overflow"));
+            }
+            self.rounding.credit(amount, self.total_supply, self.total_liquidity)
         };
 
         self.total_liquidity = self.total_liquidity.saturating_add(amount);
         self.total_supply = self.total_supply.saturating_add(lp_tokens);
+        self.total_deposited = self.total_deposited.saturating_add(amount);
 
         let current_tokens = self.lp_tokens.entry(provider).or_insert(U256::ZERO);
         *current_tokens = current_tokens.saturating_add(lp_tokens);
@@ -96,13 +119,11 @@ impl LiquidityPool {
         }
 
         // Calculate share of pool
-        let amount = lp_tokens
-            .saturating_mul(self.total_liquidity)
-            .checked_div(self.total_supply)
-            .ok_or_else(|| anyhow!("Division overflow"))?;
+        let amount = self.rounding.credit(lp_tokens, self.total_liquidity, self.total_supply);
 
         self.total_liquidity = self.total_liquidity.saturating_sub(amount);
         self.total_supply = self.total_supply.saturating_sub(lp_tokens);
+        self.total_withdrawn = self.total_withdrawn.saturating_add(amount);
 
         let current_tokens = self.lp_tokens.get_mut(&provider).unwrap();
         *current_tokens = current_tokens.saturating_sub(lp_tokens);
@@ -116,6 +137,41 @@ impl LiquidityPool {
         self.total_liquidity = self.total_liquidity.saturating_add(fee_amount);
     }
 
+    /// Record a taker fill against the pool's grid: a buying taker takes
+    /// base from the pool for quote, a selling taker the reverse
+    pub fn record_fill(&mut self, taker_side: Side, price: Price, size: Size) {
+        let size = size.0.saturating_to::<u128>() as i128;
+        let notional = size * price.0 as i128 / Price::SCALE as i128;
+        match taker_side {
+            Side::Bid => {
+                self.base_inventory -= size;
+                self.trade_cash += notional;
+            }
+            Side::Ask => {
+                self.base_inventory += size;
+                self.trade_cash -= notional;
+            }
+        }
+    }
+
+    /// Deposits less withdrawals: what LPs would hold had they not provided
+    pub fn net_deposits(&self) -> i128 {
+        self.total_deposited.saturating_to::<u128>() as i128 - self.total_withdrawn.saturating_to::<u128>() as i128
+    }
+
+    /// PnL of the pool's trades against takers, with inventory marked at
+    /// `mark_price` (valued at zero without one). Negative is impermanent
+    /// loss against holding the deposits.
+    pub fn trading_pnl(&self, mark_price: Option<Price>) -> i128 {
+        let mark = mark_price.map_or(0, |p| p.0 as i128);
+        self.trade_cash + self.base_inventory * mark / Price::SCALE as i128
+    }
+
+    /// Liquidity (deposits and fees) plus trading PnL at `mark_price`
+    pub fn value(&self, mark_price: Option<Price>) -> i128 {
+        self.total_liquidity.saturating_to::<u128>() as i128 + self.trading_pnl(mark_price)
+    }
+
     /// Get user's share of pool
     pub fn get_user_share(&self, user: &Address) -> f64 {
         if self.total_supply.is_zero() {
@@ -146,10 +202,46 @@ impl LiquidityPool {
             .copied()
             .unwrap_or(U256::ZERO);
 
-        user_tokens
-            .saturating_mul(self.total_liquidity)
-            .checked_div(self.total_supply)
-            .unwrap_or(U256::ZERO)
+        self.rounding.credit(user_tokens, self.total_liquidity, self.total_supply)
+    }
+
+    /// Grid levels the pool quotes to a taker on `side`, best first: asks
+    /// above `reference` for buyers, bids below it for sellers. Each level
+    /// offers `size_per_level` while the pool's liquidity covers its notional.
+    pub fn quotes(&self, side: Side, reference: Price) -> Vec<(Price, Size)> {
+        let mut levels: Vec<Price> = self
+            .grid_levels
+            .iter()
+            .copied()
+            .filter(|p| match side {
+                Side::Bid => *p > reference,
+                Side::Ask => *p < reference,
+            })
+            .collect();
+        levels.sort();
+        if side == Side::Ask {
+            levels.reverse();
+        }
+
+        let mut available = self.total_liquidity;
+        let mut quotes = Vec::new();
+        for price in levels {
+            if price.0 == 0 {
+                continue;
+            }
+            let notional = self.size_per_level.0.saturating_mul(U256::from(price.0)) / U256::from(Price::SCALE);
+            let size = if notional <= available {
+                self.size_per_level.0
+            } else {
+                available * U256::from(Price::SCALE) / U256::from(price.0)
+            };
+            if size.is_zero() {
+                break;
+            }
+            available = available.saturating_sub(notional);
+            quotes.push((price, Size(size)));
+        }
+        quotes
     }
 
     /// Get number of LP holders
@@ -162,10 +254,15 @@ impl LiquidityPool {
 }
 
 /// Pool manager for managing multiple liquidity pools
+#[derive(Clone)]
 pub struct PoolManager {
     pools: HashMap<PoolId, LiquidityPool>,
     next_id: PoolId,
     asset_pools: HashMap<AssetId, Vec<PoolId>>,
+    /// Per-epoch performance of each pool, oldest first
+    epoch_stats: HashMap<PoolId, Vec<PoolEpochStats>>,
+    /// Rounding applied by every pool
+    rounding: RoundingPolicy,
 }
 
 impl PoolManager {
@@ -174,9 +271,21 @@ impl PoolManager {
             pools: HashMap::new(),
             next_id: 1,
             asset_pools: HashMap::new(),
+            epoch_stats: HashMap::new(),
+            rounding: RoundingPolicy::default(),
         }
     }
 
+    /// Set the rounding policy of existing and future pools
+    pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) -> Result<()> {
+        policy.validate()?;
+        self.rounding = policy;
+        for pool in self.pools.values_mut() {
+            pool.rounding = policy;
+        }
+        Ok(())
+    }
+
     /// Create new liquidity pool
     pub fn create_pool(
         &mut self,
@@ -192,7 +301,8 @@ impl PoolManager {
         let id = self.next_id;
         self.next_id += 1;
 
-        let pool = LiquidityPool::new(id, asset, grid_levels, size_per_level, timestamp);
+        let mut pool = LiquidityPool::new(id, asset, grid_levels, size_per_level, timestamp);
+        pool.rounding = self.rounding;
 
         self.pools.insert(id, pool);
         self.asset_pools
@@ -266,6 +376,68 @@ impl PoolManager {
         Ok(())
     }
 
+    /// Record a taker fill against a pool's grid
+    pub fn record_fill(&mut self, pool_id: PoolId, taker_side: Side, price: Price, size: Size) -> Result<()> {
+        let pool = self
+            .pools
+            .get_mut(&pool_id)
+            .ok_or_else(|| anyhow!("Pool not found"))?;
+
+        pool.record_fill(taker_side, price, size);
+        Ok(())
+    }
+
+    /// Capture every pool's performance for `epoch`, marking inventory at
+    /// `mark_price(asset)`. Pools already captured for `epoch` are skipped.
+    pub fn capture_epoch(
+        &mut self,
+        epoch: u64,
+        timestamp: u64,
+        mark_price: impl Fn(AssetId) -> Option<Price>,
+    ) -> Vec<PoolEpochStats> {
+        let mut ids: Vec<PoolId> = self.pools.keys().copied().collect();
+        ids.sort_unstable();
+
+        let mut captured = Vec::new();
+        for id in ids {
+            let pool = &self.pools[&id];
+            let history = self.epoch_stats.entry(id).or_default();
+            if history.last().is_some_and(|last| last.epoch >= epoch) {
+                continue;
+            }
+            let stats = PoolEpochStats::capture(pool, epoch, timestamp, mark_price(pool.asset), history.last());
+            history.push(stats.clone());
+            captured.push(stats);
+        }
+        captured
+    }
+
+    /// Per-epoch performance of a pool, oldest first
+    pub fn epoch_stats(&self, pool_id: PoolId) -> &[PoolEpochStats] {
+        self.epoch_stats.get(&pool_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Fee APR of a pool over its last `epochs` epochs (bps)
+    pub fn rolling_apr_bps(&self, pool_id: PoolId, epochs: usize) -> u64 {
+        rolling_apr_bps(self.epoch_stats(pool_id), epochs)
+    }
+
+    /// An LP token holder's fees, impermanent loss and rolling APR
+    pub fn lp_report(
+        &self,
+        pool_id: PoolId,
+        holder: Address,
+        mark_price: Option<Price>,
+        epochs: usize,
+    ) -> Result<LpPoolReport> {
+        let pool = self
+            .pools
+            .get(&pool_id)
+            .ok_or_else(|| anyhow!("Pool not found"))?;
+
+        Ok(LpPoolReport::new(pool, holder, mark_price, self.epoch_stats(pool_id), epochs))
+    }
+
     /// Get total liquidity across all pools
     pub fn get_total_liquidity(&self) -> U256 {
         self.pools
@@ -692,6 +864,36 @@ mod tests {
         assert_eq!(total, U256::from(3000));
     }
 
+    #[test]
+    fn test_pool_manager_capture_epoch() {
+        let mut manager = PoolManager::new();
+        let pool_id = manager
+            .create_pool(AssetId(1), vec![Price(1000)], Size(U256::from(100)), 0)
+            .unwrap();
+        manager
+            .add_liquidity(pool_id, test_address(1), U256::from(1000))
+            .unwrap();
+        manager.distribute_fees(pool_id, U256::from(10)).unwrap();
+
+        assert_eq!(manager.capture_epoch(0, 100, |_| None).len(), 1);
+        // Already captured
+        assert!(manager.capture_epoch(0, 150, |_| None).is_empty());
+
+        manager.distribute_fees(pool_id, U256::from(5)).unwrap();
+        manager.capture_epoch(1, 200, |_| None);
+
+        let history = manager.epoch_stats(pool_id);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].epoch_fees, U256::from(5));
+        assert_eq!(history[1].period_start, 100);
+        assert!(manager.rolling_apr_bps(pool_id, 2) > 0);
+
+        let report = manager.lp_report(pool_id, test_address(1), None, 2).unwrap();
+        assert_eq!(report.share_bps, 10_000);
+        assert_eq!(report.fees_earned, U256::from(15));
+        assert!(manager.lp_report(99, test_address(1), None, 2).is_err());
+    }
+
     #[test]
     fn test_pool_manager_get_pool_count() {
         let mut manager = PoolManager::new();
@@ -0,0 +1,190 @@
+use crate::types::*;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Constituent of a basket index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BasketComponent {
+    pub asset: AssetId,
+    /// Share of the index (bps, components sum to 10000)
+    pub weight_bps: u64,
+    /// Component price at which the index was at its base level
+    pub reference_price: Price,
+}
+
+/// Weighted basket of oracle assets tracked by an index perpetual.
+///
+/// `index = base_level * sum(weight_i * price_i / reference_price_i)`, so each
+/// component moves the index by its weight times its own return.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BasketDefinition {
+    pub components: Vec<BasketComponent>,
+    /// Index price when every component is at its reference price
+    pub base_level: Price,
+}
+
+impl BasketDefinition {
+    /// Check weights and prices
+    pub fn validate(&self) -> Result<()> {
+        if self.components.is_empty() {
+            return Err(anyhow!("Basket has no components"));
+        }
+        if self.base_level.0 == 0 {
+            return Err(anyhow!("Basket base level must be positive"));
+        }
+        let mut seen = Vec::with_capacity(self.components.len());
+        for component in &self.components {
+            if component.weight_bps == 0 || component.reference_price.0 == 0 {
+                return Err(anyhow!("Basket component {} needs a weight and reference price", component.asset.0));
+            }
+            if seen.contains(&component.asset.0) {
+                return Err(anyhow!("Duplicate basket component {}", component.asset.0));
+            }
+            seen.push(component.asset.0);
+        }
+        let total: u64 = self.components.iter().map(|c| c.weight_bps).sum();
+        if total != 10000 {
+            return Err(anyhow!("Basket weights sum to {} bps, expected 10000", total));
+        }
+        Ok(())
+    }
+
+    /// Index price from component prices (None if any component is missing)
+    pub fn index_price(&self, price_of: impl Fn(AssetId) -> Option<Price>) -> Option<Price> {
+        let mut weighted: u128 = 0;
+        for component in &self.components {
+            let price = price_of(component.asset)?;
+            weighted += price.0 as u128 * component.weight_bps as u128 * Price::SCALE as u128
+                / component.reference_price.0 as u128;
+        }
+        let index = weighted * self.base_level.0 as u128 / 10000 / Price::SCALE as u128;
+        Some(Price(u64::try_from(index).ok()?))
+    }
+}
+
+/// What a listed market trades
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ListingKind {
+    /// Perpetual on a single oracle asset
+    Perpetual,
+    /// Index perpetual on a weighted basket
+    Basket(BasketDefinition),
+}
+
+/// Listed market
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Listing {
+    pub asset: AssetId,
+    pub symbol: String,
+    pub kind: ListingKind,
+}
+
+/// Registry of listed markets
+#[derive(Debug, Clone, Default)]
+pub struct ListingRegistry {
+    listings: BTreeMap<u32, Listing>,
+}
+
+impl ListingRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// List a market. Basket components must already be listed as
+    /// single-asset perpetuals.
+    pub fn list(&mut self, listing: Listing) -> Result<()> {
+        if self.listings.contains_key(&listing.asset.0) {
+            return Err(anyhow!("Asset {} is already listed", listing.asset.0));
+        }
+        if let ListingKind::Basket(basket) = &listing.kind {
+            basket.validate()?;
+            for component in &basket.components {
+                match self.listings.get(&component.asset.0) {
+                    Some(Listing { kind: ListingKind::Perpetual, .. }) => {}
+                    _ => {
+                        return Err(anyhow!(
+                            "Basket component {} is not a listed perpetual",
+                            component.asset.0
+                        ))
+                    }
+                }
+            }
+        }
+        self.listings.insert(listing.asset.0, listing);
+        Ok(())
+    }
+
+    /// Get a listing
+    pub fn get(&self, asset: AssetId) -> Option<&Listing> {
+        self.listings.get(&asset.0)
+    }
+
+    /// Get the basket of an index perpetual
+    pub fn basket(&self, asset: AssetId) -> Option<&BasketDefinition> {
+        match &self.get(asset)?.kind {
+            ListingKind::Basket(basket) => Some(basket),
+            ListingKind::Perpetual => None,
+        }
+    }
+
+    /// Index perpetuals and their baskets, by asset ID
+    pub fn baskets(&self) -> impl Iterator<Item = (AssetId, &BasketDefinition)> {
+        self.listings.values().filter_map(|listing| match &listing.kind {
+            ListingKind::Basket(basket) => Some((listing.asset, basket)),
+            ListingKind::Perpetual => None,
+        })
+    }
+
+    /// All listings, by asset ID
+    pub fn listings(&self) -> impl Iterator<Item = &Listing> {
+        self.listings.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perp(asset: u32) -> Listing {
+        Listing { asset: AssetId(asset), symbol: format!("A{}", asset), kind: ListingKind::Perpetual }
+    }
+
+    fn basket(weights: &[(u32, u64)]) -> BasketDefinition {
+        BasketDefinition {
+            components: weights
+                .iter()
+                .map(|&(asset, weight_bps)| BasketComponent {
+                    asset: AssetId(asset),
+                    weight_bps,
+                    reference_price: Price::from_float(100.0 * asset as f64),
+                })
+                .collect(),
+            base_level: Price::from_float(1000.0),
+        }
+    }
+
+    #[test]
+    fn test_basket_listing_and_index() {
+        let mut registry = ListingRegistry::new();
+        registry.list(perp(1)).unwrap();
+        registry.list(perp(2)).unwrap();
+
+        let index = Listing { asset: AssetId(10), symbol: "IDX".into(), kind: ListingKind::Basket(basket(&[(1, 6000), (2, 4000)])) };
+        // Unlisted component, bad weights
+        assert!(registry.list(Listing { kind: ListingKind::Basket(basket(&[(1, 5000), (3, 5000)])), ..index.clone() }).is_err());
+        assert!(registry.list(Listing { kind: ListingKind::Basket(basket(&[(1, 6000), (2, 3000)])), ..index.clone() }).is_err());
+        registry.list(index).unwrap();
+        // Baskets cannot nest
+        assert!(registry.list(Listing { asset: AssetId(11), symbol: "IDX2".into(), kind: ListingKind::Basket(basket(&[(10, 10000)])) }).is_err());
+
+        let definition = registry.basket(AssetId(10)).unwrap();
+        let at_reference = |asset: AssetId| Some(Price::from_float(100.0 * asset.0 as f64));
+        assert_eq!(definition.index_price(at_reference), Some(Price::from_float(1000.0)));
+
+        // Asset 1 up 10% moves the index by 6%
+        let moved = |asset: AssetId| Some(Price::from_float(if asset.0 == 1 { 110.0 } else { 200.0 }));
+        assert_eq!(definition.index_price(moved), Some(Price::from_float(1060.0)));
+        assert_eq!(definition.index_price(|a| moved(a).filter(|_| a.0 == 1)), None);
+    }
+}
@@ -2,6 +2,7 @@ use crate::types::*;
 use alloy_primitives::{Address, U256};
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Margin mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -19,6 +20,9 @@ pub struct MarginConfig {
     pub maintenance_margin_ratio: f64,
     /// Maximum leverage allowed
     pub max_leverage: u32,
+    /// Share of unrealized profit excluded from margin (e.g., 0.1 = only
+    /// 90% of profit is usable); losses always count in full
+    pub unrealized_profit_haircut: f64,
 }
 
 impl Default for MarginConfig {
@@ -27,17 +31,34 @@ impl Default for MarginConfig {
             initial_margin_ratio: 0.1,      // 10% = 10x leverage
             maintenance_margin_ratio: 0.05,  // 5%
             max_leverage: 10,
+            unrealized_profit_haircut: 0.1,
         }
     }
 }
 
+/// Result of previewing a position change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionPreview {
+    /// Position size after the change
+    pub size: i64,
+    /// Entry price after the change
+    pub entry_price: Price,
+    /// Total margin used across the account
+    pub used_margin: U256,
+    /// Margin left for new positions
+    pub available_margin: U256,
+    /// Estimated liquidation price of the position
+    pub liquidation_price: Option<Price>,
+}
+
 /// Margin engine for collateral and position management
+#[derive(Clone)]
 pub struct MarginEngine {
     config: MarginConfig,
     /// User collateral accounts
     collateral: HashMap<Address, CollateralAccount>,
-    /// User positions by asset
-    positions: HashMap<(Address, AssetId), Position>,
+    /// User positions by asset, shared copy-on-write with state snapshots
+    positions: Arc<HashMap<(Address, AssetId), Position>>,
     /// Margin mode per user
     margin_modes: HashMap<Address, MarginMode>,
     /// Isolated collateral per position
@@ -49,12 +70,17 @@ impl MarginEngine {
         Self {
             config,
             collateral: HashMap::new(),
-            positions: HashMap::new(),
+            positions: Arc::new(HashMap::new()),
             margin_modes: HashMap::new(),
             isolated_collateral: HashMap::new(),
         }
     }
     
+    /// Get margin configuration
+    pub fn config(&self) -> &MarginConfig {
+        &self.config
+    }
+    
     /// Deposit collateral
     pub fn deposit(
         &mut self,
@@ -85,6 +111,18 @@ impl MarginEngine {
         user: Address,
         asset: AssetId,
         amount: U256,
+    ) -> Result<()> {
+        self.withdraw_with_pnl(user, asset, amount, &HashMap::new())
+    }
+    
+    /// Withdraw collateral, requiring the account to stay healthy with
+    /// haircut unrealized PnL at `mark_prices`
+    pub fn withdraw_with_pnl(
+        &mut self,
+        user: Address,
+        asset: AssetId,
+        amount: U256,
+        mark_prices: &HashMap<AssetId, Price>,
     ) -> Result<()> {
         let account = self.collateral
             .get_mut(&user)
@@ -103,13 +141,49 @@ impl MarginEngine {
         self.update_account_value(user)?;
         
         // Check if withdrawal leaves account healthy
-        if !self.is_account_healthy(&user)? {
+        if !self.is_account_healthy_with_pnl(&user, mark_prices)? {
+            // Restore the deposit so a rejected withdrawal has no effect
+            if let Some(account) = self.collateral.get_mut(&user) {
+                let current = account.deposits.entry(asset).or_insert(U256::ZERO);
+                *current = current.saturating_add(amount);
+            }
+            self.update_account_value(user)?;
             return Err(anyhow!("Withdrawal would cause undercollateralization"));
         }
         
         Ok(())
     }
     
+    /// Remove collateral without a margin check, e.g. to undo a deposit
+    /// that was rolled back
+    pub fn debit(
+        &mut self,
+        user: Address,
+        asset: AssetId,
+        amount: U256,
+    ) -> Result<()> {
+        let current = self.get_deposit(&user, asset);
+        if current < amount {
+            return Err(anyhow!("Insufficient balance"));
+        }
+        if let Some(deposit) = self.collateral
+            .get_mut(&user)
+            .and_then(|account| account.deposits.get_mut(&asset))
+        {
+            *deposit = current - amount;
+        }
+        self.update_account_value(user)
+    }
+    
+    /// Get deposited collateral for user and asset
+    pub fn get_deposit(&self, user: &Address, asset: AssetId) -> U256 {
+        self.collateral
+            .get(user)
+            .and_then(|account| account.deposits.get(&asset))
+            .copied()
+            .unwrap_or(U256::ZERO)
+    }
+    
     /// Open or modify a position
     pub fn update_position(
         &mut self,
@@ -141,7 +215,7 @@ impl MarginEngine {
         }
         
         // Now update or create position
-        let position = self.positions
+        let position = Arc::make_mut(&mut self.positions)
             .entry((user, asset))
             .or_insert_with(|| Position {
                 user,
@@ -190,8 +264,40 @@ impl MarginEngine {
         Ok(account.available_margin >= required)
     }
     
+    /// Check available margin counting haircut unrealized PnL at `mark_prices`
+    pub fn has_available_margin_with_pnl(
+        &self,
+        user: &Address,
+        required: U256,
+        mark_prices: &HashMap<AssetId, Price>,
+    ) -> Result<bool> {
+        Ok(self.get_available_margin_with_pnl(user, mark_prices)? >= required)
+    }
+    
+    /// Get margin left for new positions, counting haircut unrealized PnL
+    pub fn get_available_margin_with_pnl(
+        &self,
+        user: &Address,
+        mark_prices: &HashMap<AssetId, Price>,
+    ) -> Result<U256> {
+        let account = self.collateral.get(user)
+            .ok_or_else(|| anyhow!("Account not found"))?;
+        let value = self.get_account_value_with_pnl(user, mark_prices)?;
+        Ok(value.saturating_sub(account.used_margin))
+    }
+    
     /// Check if account meets maintenance margin
     pub fn is_account_healthy(&self, user: &Address) -> Result<bool> {
+        self.is_account_healthy_with_pnl(user, &HashMap::new())
+    }
+    
+    /// Check if account meets maintenance margin, counting haircut
+    /// unrealized PnL at `mark_prices`
+    pub fn is_account_healthy_with_pnl(
+        &self,
+        user: &Address,
+        mark_prices: &HashMap<AssetId, Price>,
+    ) -> Result<bool> {
         let account = self.collateral.get(user)
             .ok_or_else(|| anyhow!("Account not found"))?;
         
@@ -199,10 +305,11 @@ impl MarginEngine {
             return Ok(true);
         }
         
-        // Calculate margin ratio: total_value / used_margin
+        // Calculate margin ratio: account value / used_margin
         // Should be >= maintenance_margin_ratio
+        let value = self.get_account_value_with_pnl(user, mark_prices)?;
         let margin_ratio = if account.used_margin > U256::ZERO {
-            account.total_value.saturating_mul(U256::from(10000))
+            value.saturating_mul(U256::from(10000))
                 .checked_div(account.used_margin)
                 .unwrap_or(U256::ZERO)
         } else {
@@ -279,28 +386,31 @@ impl MarginEngine {
     ) -> Result<()> {
         if let Some(position) = self.positions.get(&(user, asset)) {
             let pnl = self.calculate_unrealized_pnl(position, mark_price);
-            if let Some(position) = self.positions.get_mut(&(user, asset)) {
+            if let Some(position) = Arc::make_mut(&mut self.positions).get_mut(&(user, asset)) {
                 position.unrealized_pnl = pnl;
             }
         }
         Ok(())
     }
     
-    /// Get total account value including unrealized PnL
+    /// Get total account value including unrealized PnL, with profits
+    /// reduced by the configured haircut
     pub fn get_account_value_with_pnl(
         &self,
         user: &Address,
         mark_prices: &HashMap<AssetId, Price>,
     ) -> Result<U256> {
         let mut total = self.get_account_equity(user)?;
+        let usable_profit = (1.0 - self.config.unrealized_profit_haircut).clamp(0.0, 1.0);
         
         // Add unrealized PnL from all positions
-        for ((pos_user, asset), position) in &self.positions {
+        for ((pos_user, asset), position) in self.positions.iter() {
             if pos_user == user {
                 if let Some(mark_price) = mark_prices.get(asset) {
                     let pnl = self.calculate_unrealized_pnl(position, *mark_price);
                     if pnl >= 0 {
-                        total = total.saturating_add(U256::from(pnl as u64));
+                        let usable = (pnl as f64 * usable_profit) as u64;
+                        total = total.saturating_add(U256::from(usable));
                     } else {
                         total = total.saturating_sub(U256::from((-pnl) as u64));
                     }
@@ -342,6 +452,314 @@ impl MarginEngine {
             .collect()
     }
     
+    /// Get users with a collateral account or a position, sorted
+    pub fn get_accounts(&self) -> Vec<Address> {
+        let mut users: Vec<_> = self.collateral
+            .keys()
+            .chain(self.positions.keys().map(|(user, _)| user))
+            .copied()
+            .collect();
+        users.sort();
+        users.dedup();
+        users
+    }
+    
+    /// Share every position without copying; later updates copy the map
+    /// only while a shared handle is still alive
+    pub fn shared_positions(&self) -> Arc<HashMap<(Address, AssetId), Position>> {
+        Arc::clone(&self.positions)
+    }
+    
+    /// Get non-zero positions in an asset as (user, size), sorted by user
+    pub fn get_asset_positions(&self, asset: AssetId) -> Vec<(Address, i64)> {
+        let mut positions: Vec<_> = self.positions
+            .iter()
+            .filter(|((_, a), p)| *a == asset && p.size != 0)
+            .map(|((user, _), p)| (*user, p.size))
+            .collect();
+        positions.sort_by_key(|(user, _)| *user);
+        positions
+    }
+    
+    /// Get assets with at least one open position, sorted by asset ID
+    pub fn get_open_assets(&self) -> Vec<AssetId> {
+        let mut assets: Vec<_> = self.positions
+            .iter()
+            .filter(|(_, p)| p.size != 0)
+            .map(|((_, asset), _)| *asset)
+            .collect();
+        assets.sort_by_key(|a| a.0);
+        assets.dedup();
+        assets
+    }
+    
+    /// Add realized PnL (e.g. accrued funding) to a position
+    pub fn accrue_realized_pnl(&mut self, user: Address, asset: AssetId, amount: i64) {
+        if let Some(position) = Arc::make_mut(&mut self.positions).get_mut(&(user, asset)) {
+            position.realized_pnl = position.realized_pnl.saturating_add(amount);
+        }
+    }
+    
+    /// Apply a netted funding settlement to the account's `asset` deposit
+    pub fn apply_funding(&mut self, user: Address, asset: AssetId, amount: i64) -> Result<()> {
+        self.settle_cash(user, asset, amount)
+    }
+    
+    /// Credit or debit a signed cash amount to the account's `asset` deposit
+    ///
+    /// A debit is capped at the deposit; an account short of collateral is
+    /// left to liquidation.
+    pub fn settle_cash(&mut self, user: Address, asset: AssetId, amount: i64) -> Result<()> {
+        if amount >= 0 {
+            return self.deposit(user, asset, U256::from(amount as u64));
+        }
+        let Some(account) = self.collateral.get_mut(&user) else {
+            return Ok(());
+        };
+        if let Some(deposit) = account.deposits.get_mut(&asset) {
+            *deposit = deposit.saturating_sub(U256::from(amount.unsigned_abs()));
+        }
+        self.update_account_value(user)
+    }
+    
+    /// Preview a position after applying `fills` (signed size, price)
+    /// without mutating any state.
+    ///
+    /// Entry price follows the same rules as `update_position`, so the
+    /// resulting margin usage matches what execution would produce.
+    pub fn preview_position(
+        &self,
+        user: &Address,
+        asset: AssetId,
+        fills: &[(i64, Price)],
+    ) -> Result<PositionPreview> {
+        let (mut size, mut entry_price) = match self.positions.get(&(*user, asset)) {
+            Some(p) => (p.size, p.entry_price),
+            None => (0, fills.first().map(|(_, p)| *p).unwrap_or(Price(0))),
+        };
+
+        for (size_delta, price) in fills {
+            if size_delta.signum() == size.signum() && *size_delta != 0 {
+                entry_price = *price;
+            }
+            size += size_delta;
+        }
+
+        // Margin used by the user's other positions is unaffected
+        let mut used_margin = U256::ZERO;
+        let mut other_maintenance = 0.0;
+        for ((pos_user, pos_asset), position) in self.positions.iter() {
+            if pos_user != user || *pos_asset == asset || position.size == 0 {
+                continue;
+            }
+            used_margin = used_margin.saturating_add(self.calculate_required_margin(
+                *pos_asset,
+                position.size.unsigned_abs(),
+                position.entry_price,
+            )?);
+            other_maintenance += position.size.unsigned_abs() as f64
+                * position.entry_price.to_float()
+                * self.config.maintenance_margin_ratio;
+        }
+        if size != 0 {
+            used_margin = used_margin.saturating_add(
+                self.calculate_required_margin(asset, size.unsigned_abs(), entry_price)?,
+            );
+        }
+
+        let equity = self.collateral
+            .get(user)
+            .map(|a| a.total_value)
+            .unwrap_or(U256::ZERO);
+
+        // Collateral backing this position
+        let collateral = match self.get_margin_mode(user) {
+            MarginMode::Isolated => self.get_isolated_collateral(user, asset).saturating_to::<u128>() as f64,
+            MarginMode::Cross => (equity.saturating_to::<u128>() as f64 - other_maintenance).max(0.0),
+        };
+
+        Ok(PositionPreview {
+            size,
+            entry_price,
+            used_margin,
+            available_margin: equity.saturating_sub(used_margin),
+            liquidation_price: self.estimate_liquidation_price(size, entry_price, collateral),
+        })
+    }
+
+    /// Price at which `collateral` plus unrealized PnL falls to the
+    /// maintenance requirement (None if flat or never reached)
+    fn estimate_liquidation_price(
+        &self,
+        size: i64,
+        entry_price: Price,
+        collateral: f64,
+    ) -> Option<Price> {
+        if size == 0 {
+            return None;
+        }
+
+        let qty = size.unsigned_abs() as f64;
+        let entry = entry_price.to_float();
+        let mmr = self.config.maintenance_margin_ratio;
+
+        let price = if size > 0 {
+            // collateral + qty * (p - entry) = mmr * qty * p
+            (qty * entry - collateral) / (qty * (1.0 - mmr))
+        } else {
+            // collateral + qty * (entry - p) = mmr * qty * p
+            (collateral + qty * entry) / (qty * (1.0 + mmr))
+        };
+
+        if price.is_finite() && price > 0.0 {
+            Some(Price::from_float(price))
+        } else {
+            None
+        }
+    }
+
+    /// Transfer part or all of a position (and its pro-rata isolated
+    /// collateral) from one account to another.
+    ///
+    /// The operation is atomic: if the destination fails its margin re-check
+    /// (or the source's remaining isolated position becomes undercollateralized)
+    /// all state is rolled back. Returns the isolated collateral moved.
+    pub fn transfer_position(
+        &mut self,
+        from: Address,
+        to: Address,
+        asset: AssetId,
+        size: i64,
+        timestamp: u64,
+    ) -> Result<U256> {
+        if from == to {
+            return Err(anyhow!("Cannot transfer position to the same account"));
+        }
+        if size == 0 {
+            return Err(anyhow!("Transfer size must be non-zero"));
+        }
+
+        let mode = self.get_margin_mode(&from);
+        if self.get_margin_mode(&to) != mode {
+            return Err(anyhow!("Margin mode mismatch between accounts"));
+        }
+        if !self.collateral.contains_key(&to) {
+            return Err(anyhow!("Destination account not found"));
+        }
+
+        let source = self.positions
+            .get(&(from, asset))
+            .cloned()
+            .ok_or_else(|| anyhow!("Position not found"))?;
+
+        if source.size.signum() != size.signum() || size.unsigned_abs() > source.size.unsigned_abs() {
+            return Err(anyhow!("Transfer size exceeds position"));
+        }
+
+        let dest = self.positions.get(&(to, asset)).cloned();
+        if let Some(dest) = &dest {
+            if dest.size != 0 && dest.size.signum() != size.signum() {
+                return Err(anyhow!("Destination holds an opposite position"));
+            }
+        }
+
+        // Isolated collateral moves pro-rata with the transferred size
+        let source_isolated = self.get_isolated_collateral(&from, asset);
+        let dest_isolated = self.get_isolated_collateral(&to, asset);
+        let moved_collateral = source_isolated * U256::from(size.unsigned_abs())
+            / U256::from(source.size.unsigned_abs());
+
+        // Apply to source
+        let mut new_source = source.clone();
+        new_source.size -= size;
+        new_source.timestamp = timestamp;
+
+        // Apply to destination (size-weighted entry price)
+        let new_dest = match &dest {
+            Some(d) if d.size != 0 => {
+                let total = d.size.unsigned_abs() as u128 + size.unsigned_abs() as u128;
+                let weighted = d.entry_price.0 as u128 * d.size.unsigned_abs() as u128
+                    + source.entry_price.0 as u128 * size.unsigned_abs() as u128;
+                Position {
+                    size: d.size
+                        .checked_add(size)
+                        .ok_or_else(|| anyhow!("Destination position size overflow"))?,
+                    entry_price: Price((weighted / total) as u64),
+                    timestamp,
+                    ..d.clone()
+                }
+            }
+            _ => Position {
+                user: to,
+                asset,
+                size,
+                entry_price: source.entry_price,
+                realized_pnl: 0,
+                unrealized_pnl: 0,
+                timestamp,
+            },
+        };
+
+        Arc::make_mut(&mut self.positions).insert((from, asset), new_source.clone());
+        Arc::make_mut(&mut self.positions).insert((to, asset), new_dest.clone());
+        self.isolated_collateral.insert((from, asset), source_isolated - moved_collateral);
+        self.isolated_collateral.insert((to, asset), dest_isolated.saturating_add(moved_collateral));
+        self.update_margin_usage(from)?;
+        self.update_margin_usage(to)?;
+
+        // Margin re-checks
+        let mut check = self.check_transfer_margin(&to, &new_dest, mode);
+        if check.is_ok() && new_source.size != 0 {
+            check = self.check_transfer_margin(&from, &new_source, mode);
+        }
+
+        if let Err(e) = check {
+            // Roll back
+            Arc::make_mut(&mut self.positions).insert((from, asset), source);
+            match dest {
+                Some(d) => Arc::make_mut(&mut self.positions).insert((to, asset), d),
+                None => Arc::make_mut(&mut self.positions).remove(&(to, asset)),
+            };
+            self.isolated_collateral.insert((from, asset), source_isolated);
+            self.isolated_collateral.insert((to, asset), dest_isolated);
+            self.update_margin_usage(from)?;
+            self.update_margin_usage(to)?;
+            return Err(e);
+        }
+
+        Ok(moved_collateral)
+    }
+
+    /// Margin check for an account receiving or retaining a transferred position
+    fn check_transfer_margin(
+        &self,
+        user: &Address,
+        position: &Position,
+        mode: MarginMode,
+    ) -> Result<()> {
+        match mode {
+            MarginMode::Isolated => {
+                let required = self.calculate_required_margin(
+                    position.asset,
+                    position.size.unsigned_abs(),
+                    position.entry_price,
+                )?;
+                if self.get_isolated_collateral(user, position.asset) < required {
+                    return Err(anyhow!("Insufficient isolated margin after transfer"));
+                }
+            }
+            MarginMode::Cross => {
+                let account = self.collateral.get(user)
+                    .ok_or_else(|| anyhow!("Account not found"))?;
+                if account.used_margin > account.total_value {
+                    return Err(anyhow!("Insufficient margin after transfer"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Update account value (simplified - assumes 1:1 USD pricing)
     fn update_account_value(&mut self, user: Address) -> Result<()> {
         let account = self.collateral.get_mut(&user)
@@ -363,7 +781,7 @@ impl MarginEngine {
         let mut used = U256::ZERO;
         
         // Calculate total margin used across all positions
-        for ((pos_user, asset), position) in &self.positions {
+        for ((pos_user, asset), position) in self.positions.iter() {
             if pos_user == &user && position.size != 0 {
                 let margin = self.calculate_required_margin(
                     *asset,
@@ -711,6 +1129,25 @@ mod tests {
         assert!(account_value > U256::from(1000)); // Should include profit
     }
 
+    #[test]
+    fn test_unrealized_profit_haircut() {
+        let mut engine = MarginEngine::new(MarginConfig::default());
+        let user = Address::ZERO;
+        
+        engine.deposit(user, AssetId(1), U256::from(1000)).unwrap();
+        engine.update_position(user, AssetId(1), 100, Price(1000), 0).unwrap();
+        
+        // Only 90% of a 1000 profit counts
+        let marks = HashMap::from([(AssetId(1), Price(1010))]);
+        assert_eq!(engine.get_account_value_with_pnl(&user, &marks).unwrap(), U256::from(1900));
+        assert!(engine.has_available_margin_with_pnl(&user, U256::from(1900), &marks).unwrap());
+        assert!(!engine.has_available_margin_with_pnl(&user, U256::from(1901), &marks).unwrap());
+        
+        // Losses count in full
+        let marks = HashMap::from([(AssetId(1), Price(995))]);
+        assert_eq!(engine.get_account_value_with_pnl(&user, &marks).unwrap(), U256::from(500));
+    }
+
     #[test]
     fn test_isolated_collateral_deposit() {
         let mut engine = MarginEngine::new(MarginConfig::default());
@@ -735,5 +1172,89 @@ mod tests {
         let positions = engine.get_user_positions(&user);
         assert_eq!(positions.len(), 2);
     }
-}
 
+    #[test]
+    fn test_transfer_position_full() {
+        let mut engine = MarginEngine::new(MarginConfig::default());
+        let from = Address::from([1u8; 20]);
+        let to = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        engine.deposit(from, asset, U256::from(1000)).unwrap();
+        engine.deposit(to, asset, U256::from(200)).unwrap();
+        engine.update_position(from, asset, 10, Price::from_float(100.0), 0).unwrap();
+        
+        engine.transfer_position(from, to, asset, 10, 1).unwrap();
+        
+        assert_eq!(engine.get_position(&from, asset).unwrap().size, 0);
+        let dest = engine.get_position(&to, asset).unwrap();
+        assert_eq!(dest.size, 10);
+        assert_eq!(dest.entry_price, Price::from_float(100.0));
+        assert_eq!(engine.collateral.get(&to).unwrap().used_margin, U256::from(100));
+    }
+
+    #[test]
+    fn test_transfer_position_rolls_back_on_insufficient_margin() {
+        let mut engine = MarginEngine::new(MarginConfig::default());
+        let from = Address::from([1u8; 20]);
+        let to = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        engine.deposit(from, asset, U256::from(1000)).unwrap();
+        engine.deposit(to, asset, U256::from(50)).unwrap();
+        engine.update_position(from, asset, 10, Price::from_float(100.0), 0).unwrap();
+        
+        assert!(engine.transfer_position(from, to, asset, 10, 1).is_err());
+        
+        // Nothing moved
+        assert_eq!(engine.get_position(&from, asset).unwrap().size, 10);
+        assert!(engine.get_position(&to, asset).is_none());
+        assert_eq!(engine.collateral.get(&from).unwrap().used_margin, U256::from(100));
+        assert_eq!(engine.collateral.get(&to).unwrap().used_margin, U256::ZERO);
+    }
+
+    #[test]
+    fn test_transfer_position_validation() {
+        let mut engine = MarginEngine::new(MarginConfig::default());
+        let from = Address::from([1u8; 20]);
+        let to = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        engine.deposit(from, asset, U256::from(1000)).unwrap();
+        engine.update_position(from, asset, 10, Price::from_float(100.0), 0).unwrap();
+        
+        // Destination account must exist
+        assert!(engine.transfer_position(from, to, asset, 5, 1).is_err());
+        
+        engine.deposit(to, asset, U256::from(1000)).unwrap();
+        
+        // Wrong direction and oversized transfers
+        assert!(engine.transfer_position(from, to, asset, -5, 1).is_err());
+        assert!(engine.transfer_position(from, to, asset, 11, 1).is_err());
+        assert!(engine.transfer_position(from, from, asset, 5, 1).is_err());
+        assert!(engine.transfer_position(from, to, asset, i64::MIN, 1).is_err());
+    }
+
+    #[test]
+    fn test_transfer_isolated_collateral_pro_rata() {
+        let mut engine = MarginEngine::new(MarginConfig::default());
+        let from = Address::from([1u8; 20]);
+        let to = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        engine.set_margin_mode(from, MarginMode::Isolated).unwrap();
+        engine.set_margin_mode(to, MarginMode::Isolated).unwrap();
+        engine.deposit(from, asset, U256::from(1000)).unwrap();
+        engine.deposit(to, asset, U256::from(1000)).unwrap();
+        engine.deposit_isolated(from, asset, U256::from(400)).unwrap();
+        engine.update_position(from, asset, -10, Price::from_float(100.0), 0).unwrap();
+        
+        let moved = engine.transfer_position(from, to, asset, -5, 1).unwrap();
+        
+        assert_eq!(moved, U256::from(200));
+        assert_eq!(engine.get_isolated_collateral(&from, asset), U256::from(200));
+        assert_eq!(engine.get_isolated_collateral(&to, asset), U256::from(200));
+        assert_eq!(engine.get_position(&from, asset).unwrap().size, -5);
+        assert_eq!(engine.get_position(&to, asset).unwrap().size, -5);
+    }
+}
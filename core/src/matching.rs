@@ -28,16 +28,7 @@ impl MatchingEngine {
                         None => break, // No more liquidity
                     };
                     
-                    let new_fills = Self::match_at_price(
-                        book,
-                        best_ask,
-                        Side::Ask,
-                        &mut size,
-                        trader,
-                        timestamp,
-                    )?;
-                    
-                    fills.extend(new_fills);
+                    book.fill_level(Side::Ask, best_ask, &mut size, trader, timestamp, &mut fills);
                     
                     if size.0 == U256::ZERO {
                         break;
@@ -52,16 +43,7 @@ impl MatchingEngine {
                         None => break,
                     };
                     
-                    let new_fills = Self::match_at_price(
-                        book,
-                        best_bid,
-                        Side::Bid,
-                        &mut size,
-                        trader,
-                        timestamp,
-                    )?;
-                    
-                    fills.extend(new_fills);
+                    book.fill_level(Side::Bid, best_bid, &mut size, trader, timestamp, &mut fills);
                     
                     if size.0 == U256::ZERO {
                         break;
@@ -73,102 +55,6 @@ impl MatchingEngine {
         Ok(fills)
     }
     
-    /// Match against a specific price level
-    fn match_at_price(
-        book: &mut OrderBook,
-        price: Price,
-        side: Side,
-        remaining: &mut Size,
-        taker: Address,
-        timestamp: u64,
-    ) -> Result<Vec<Fill>> {
-        let mut fills = Vec::new();
-        let mut orders_to_remove = Vec::new();
-        
-        // Scope the mutable borrow of the tree
-        {
-            // Get the appropriate tree
-            let tree = match side {
-                Side::Bid => book.bids_mut(),
-                Side::Ask => book.asks_mut(),
-            };
-            
-            // Get the price level
-            let level = match tree.get_mut(&price) {
-                Some(l) => l,
-                None => return Ok(fills),
-            };
-            
-            // Process orders in FIFO order
-            for order in level.orders.iter_mut() {
-                if remaining.0 == U256::ZERO {
-                    break;
-                }
-                
-                let order_remaining = order.remaining();
-                let fill_size = if remaining.0 < order_remaining.0 {
-                    remaining.0
-                } else {
-                    order_remaining.0
-                };
-                
-                // Create fill
-                let fill = Fill {
-                    order_id: order.id,
-                    price,
-                    size: Size(fill_size),
-                    maker: order.trader,
-                    taker,
-                    timestamp,
-                };
-                
-                // Update order
-                order.filled.0 += fill_size;
-                
-                // Update remaining
-                remaining.0 -= fill_size;
-                
-                // Update level total size
-                level.total_size -= fill_size;
-                
-                fills.push(fill);
-                
-                // Mark fully filled orders for removal
-                if order.is_filled() {
-                    orders_to_remove.push(order.id);
-                }
-            }
-            
-            // Remove from the level's queue
-            level.orders.retain(|o| !orders_to_remove.contains(&o.id));
-        }
-        
-        // Now we can safely access order_index_mut
-        // Remove filled orders from the order index
-        for order_id in &orders_to_remove {
-            book.order_index_mut().remove(order_id);
-        }
-        
-        // Clean up empty price level
-        let should_remove = {
-            let tree = match side {
-                Side::Bid => book.bids_mut(),
-                Side::Ask => book.asks_mut(),
-            };
-            tree.get(&price).map_or(false, |l| l.is_empty())
-        };
-        
-        if should_remove {
-            let tree = match side {
-                Side::Bid => book.bids_mut(),
-                Side::Ask => book.asks_mut(),
-            };
-            tree.remove(&price);
-        }
-        
-        Ok(fills)
-    }
-    
     /// Execute a limit order (add to book + match if crosses)
     pub fn execute_limit_order(
         book: &mut OrderBook,
@@ -225,16 +111,7 @@ impl MatchingEngine {
                 Side::Ask => Side::Bid,
             };
             
-            let new_fills = Self::match_at_price(
-                book,
-                match_price,
-                opposite_side,
-                &mut remaining,
-                trader,
-                timestamp,
-            )?;
-            
-            fills.extend(new_fills);
+            book.fill_level(opposite_side, match_price, &mut remaining, trader, timestamp, &mut fills);
             
             // Check if still crosses
             crosses = match side {
@@ -0,0 +1,818 @@
+use crate::matching::MatchingEngine;
+use crate::oracle::OracleEngine;
+use crate::orderbook::OrderBook;
+use crate::risk::RiskEngine;
+use crate::rounding::RoundingPolicy;
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Option type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
+/// A listed, cash-settled European option series.
+///
+/// Each series trades on its own order book keyed by `id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionSeries {
+    /// Book identifier for this series
+    pub id: AssetId,
+    /// Underlying perp asset
+    pub underlying: AssetId,
+    pub kind: OptionKind,
+    pub strike: Price,
+    /// Expiry timestamp (exercise only at expiry)
+    pub expiry: u64,
+}
+
+impl OptionSeries {
+    /// Intrinsic value per contract at the given underlying price
+    pub fn intrinsic_value(&self, underlying_price: Price) -> Price {
+        match self.kind {
+            OptionKind::Call => Price(underlying_price.0.saturating_sub(self.strike.0)),
+            OptionKind::Put => Price(self.strike.0.saturating_sub(underlying_price.0)),
+        }
+    }
+}
+
+/// Limit order on an option series
+#[derive(Debug, Clone, Copy)]
+pub struct OptionOrder {
+    pub trader: Address,
+    pub series: AssetId,
+    pub side: Side,
+    /// Limit premium per contract
+    pub premium: Price,
+    pub size: u64,
+}
+
+/// Options configuration
+#[derive(Debug, Clone)]
+pub struct OptionsConfig {
+    /// TWAP window for the expiry settlement price (seconds)
+    pub settlement_twap_window: u64,
+    /// Collateral asset premiums and settlements are paid in
+    pub collateral_asset: AssetId,
+}
+
+impl Default for OptionsConfig {
+    fn default() -> Self {
+        Self {
+            settlement_twap_window: 1800,  // 30 minutes
+            collateral_asset: AssetId(0),
+        }
+    }
+}
+
+/// Option position (positive = long, negative = short)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionPosition {
+    pub user: Address,
+    pub series: AssetId,
+    pub size: i64,
+    /// Net premium cash flow (positive = received)
+    pub premium: i64,
+}
+
+/// Cash settlement of an option position at expiry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionSettlement {
+    pub user: Address,
+    pub series: AssetId,
+    pub size: i64,
+    pub settlement_price: Price,
+    /// Cash paid to (positive) or by (negative) the holder
+    pub payout: i64,
+    pub timestamp: u64,
+}
+
+/// Options engine state, persisted so listed series, positions and
+/// resting orders survive a restart
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OptionsState {
+    /// Listed series, sorted by ID
+    pub series: Vec<OptionSeries>,
+    /// Next order ID per series book, sorted by series
+    pub next_order_ids: Vec<(AssetId, OrderId)>,
+    /// Resting orders in book priority order
+    pub orders: Vec<Order>,
+    /// Positions, sorted by (user, series)
+    pub positions: Vec<OptionPosition>,
+    /// Settlement price of each settled series, sorted by series
+    pub settled: Vec<(AssetId, Price)>,
+}
+
+/// European options engine
+///
+/// Series trade on regular order books through the matching engine, are
+/// margined via `RiskEngine::calculate_option_margin`, and are cash-settled
+/// at expiry against the oracle TWAP of the underlying.
+#[derive(Clone)]
+pub struct OptionsEngine {
+    config: OptionsConfig,
+    /// Listed series
+    series: HashMap<AssetId, OptionSeries>,
+    /// Order book per series
+    books: HashMap<AssetId, OrderBook>,
+    /// Positions by (user, series)
+    positions: HashMap<(Address, AssetId), OptionPosition>,
+    /// Expired and settled series
+    settled: HashMap<AssetId, Price>,
+    /// Settlement history
+    settlements: Vec<OptionSettlement>,
+    /// Long payouts round down, short obligations round up
+    rounding: RoundingPolicy,
+}
+
+impl OptionsEngine {
+    pub fn new(config: OptionsConfig) -> Self {
+        Self {
+            config,
+            series: HashMap::new(),
+            books: HashMap::new(),
+            positions: HashMap::new(),
+            settled: HashMap::new(),
+            settlements: Vec::new(),
+            rounding: RoundingPolicy::default(),
+        }
+    }
+
+    /// Set the rounding policy for settlement payouts
+    pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) -> Result<()> {
+        policy.validate()?;
+        self.rounding = policy;
+        Ok(())
+    }
+
+    /// List a new option series
+    pub fn list_series(&mut self, series: OptionSeries) -> Result<()> {
+        if self.series.contains_key(&series.id) {
+            return Err(anyhow!("Series {} already listed", series.id.0));
+        }
+        if series.strike.0 == 0 {
+            return Err(anyhow!("Strike must be non-zero"));
+        }
+
+        self.books.insert(series.id, OrderBook::new(series.id));
+        self.series.insert(series.id, series);
+        Ok(())
+    }
+
+    /// Get series
+    pub fn get_series(&self, id: AssetId) -> Option<&OptionSeries> {
+        self.series.get(&id)
+    }
+
+    /// Underlying assets of the listed series, in asset order
+    pub fn underlyings(&self) -> Vec<AssetId> {
+        let mut assets: Vec<AssetId> = self.series.values().map(|s| s.underlying).collect();
+        assets.sort_by_key(|a| a.0);
+        assets.dedup();
+        assets
+    }
+
+    /// Collateral asset premiums and settlements are paid in
+    pub fn collateral_asset(&self) -> AssetId {
+        self.config.collateral_asset
+    }
+
+    /// Get order book for series
+    pub fn get_book(&self, id: AssetId) -> Option<&OrderBook> {
+        self.books.get(&id)
+    }
+
+    /// Cancel the resting bids on `series_id` of every trader whose `funds`
+    /// no longer cover the premium of all their resting bids there, so an
+    /// unfunded bid cannot block incoming asks
+    pub fn cancel_unfunded_bids(
+        &mut self,
+        series_id: AssetId,
+        funds: impl Fn(&Address) -> U256,
+    ) -> Result<Vec<Order>> {
+        let book = self.books.get_mut(&series_id).ok_or_else(|| anyhow!("Series not found"))?;
+        let mut owed: BTreeMap<Address, u128> = BTreeMap::new();
+        for order in book.orders().filter(|o| o.side == Side::Bid) {
+            *owed.entry(order.trader).or_default() += Self::bid_cost(order);
+        }
+        let unfunded: Vec<OrderId> = book
+            .orders()
+            .filter(|o| o.side == Side::Bid && U256::from(owed[&o.trader]) > funds(&o.trader))
+            .map(|o| o.id)
+            .collect();
+        unfunded.into_iter().map(|id| book.cancel_order(id)).collect()
+    }
+
+    /// Premium owed by the remaining size of a resting bid
+    fn bid_cost(order: &Order) -> u128 {
+        order.remaining().0.to::<u128>() * order.price.0 as u128 / Price::SCALE as u128
+    }
+
+    /// Get option position
+    pub fn get_position(&self, user: &Address, series: AssetId) -> Option<&OptionPosition> {
+        self.positions.get(&(*user, series))
+    }
+
+    /// Margin required for a user's short option positions
+    pub fn required_margin(
+        &self,
+        risk: &RiskEngine,
+        user: &Address,
+        underlying_prices: &HashMap<AssetId, Price>,
+    ) -> Result<U256> {
+        let mut total = U256::ZERO;
+
+        for ((u, id), position) in &self.positions {
+            if u != user || position.size >= 0 {
+                continue;
+            }
+            total = total.saturating_add(self.short_margin(risk, *id, position.size, underlying_prices)?);
+        }
+
+        Ok(total)
+    }
+
+    /// Margin for a (possibly hypothetical) position size in a series
+    fn short_margin(
+        &self,
+        risk: &RiskEngine,
+        id: AssetId,
+        size: i64,
+        underlying_prices: &HashMap<AssetId, Price>,
+    ) -> Result<U256> {
+        if size >= 0 {
+            return Ok(U256::ZERO);
+        }
+
+        let series = self.series.get(&id).ok_or_else(|| anyhow!("Series not found"))?;
+        let underlying = underlying_prices
+            .get(&series.underlying)
+            .copied()
+            .ok_or_else(|| anyhow!("No price for underlying {}", series.underlying.0))?;
+        let premium = self.books
+            .get(&id)
+            .and_then(|b| b.get_mid_price())
+            .unwrap_or(Price(0));
+
+        Ok(risk.calculate_option_margin(
+            series.kind,
+            size.unsigned_abs(),
+            series.strike,
+            underlying,
+            premium,
+        ))
+    }
+
+    /// Place a limit order on an option series.
+    ///
+    /// Orders that could increase the trader's short exposure are checked
+    /// against `collateral` using the worst case (all resting and new sell
+    /// size filled). Bids need the premium of all resting and new bids
+    /// covered by `collateral` on top of the trader's short margin.
+    pub fn place_order(
+        &mut self,
+        risk: &RiskEngine,
+        order: OptionOrder,
+        underlying_prices: &HashMap<AssetId, Price>,
+        collateral: U256,
+        timestamp: u64,
+    ) -> Result<(OrderId, Vec<Fill>)> {
+        let OptionOrder { trader, series: series_id, side, premium, size } = order;
+        let series = self.series
+            .get(&series_id)
+            .ok_or_else(|| anyhow!("Series not found"))?;
+        if self.settled.contains_key(&series_id) || timestamp >= series.expiry {
+            return Err(anyhow!("Series {} has expired", series_id.0));
+        }
+        if size == 0 {
+            return Err(anyhow!("Order size must be non-zero"));
+        }
+
+        risk.check_order_risk(series_id, size, premium, 0)?;
+
+        if side == Side::Ask {
+            let current = self.get_position(&trader, series_id).map(|p| p.size).unwrap_or(0);
+            let resting_asks: u64 = self.books[&series_id]
+                .orders()
+                .filter(|o| o.trader == trader && o.side == Side::Ask)
+                .map(|o| o.remaining().0.to::<u64>())
+                .sum();
+            let worst_case = current - resting_asks as i64 - size as i64;
+
+            // Margin on other series plus the worst case on this one
+            let mut required = self.short_margin(risk, series_id, worst_case, underlying_prices)?;
+            for ((u, id), position) in &self.positions {
+                if *u == trader && *id != series_id {
+                    required = required.saturating_add(
+                        self.short_margin(risk, *id, position.size, underlying_prices)?,
+                    );
+                }
+            }
+            if required > collateral {
+                return Err(anyhow!("Insufficient margin for short option exposure"));
+            }
+        } else {
+            let resting_cost: u128 = self.books[&series_id]
+                .orders()
+                .filter(|o| o.trader == trader && o.side == Side::Bid)
+                .map(Self::bid_cost)
+                .sum();
+            let cost = resting_cost + size as u128 * premium.0 as u128 / Price::SCALE as u128;
+            let reserved = self.required_margin(risk, &trader, underlying_prices)?;
+            if U256::from(cost).saturating_add(reserved) > collateral {
+                return Err(anyhow!("Insufficient collateral for option premium"));
+            }
+        }
+
+        let book = self.books.get_mut(&series_id).ok_or_else(|| anyhow!("Book not found"))?;
+        let (order_id, fills) = MatchingEngine::execute_limit_order(
+            book,
+            trader,
+            side,
+            premium,
+            Size(U256::from(size)),
+            timestamp,
+        )?;
+
+        for fill in &fills {
+            let qty = fill.size.0.to::<u64>() as i64;
+            let cash = Self::fill_premium(fill);
+            let (buyer, seller) = Self::counterparties(side, fill);
+            self.apply_trade(buyer, series_id, qty, -cash);
+            self.apply_trade(seller, series_id, -qty, cash);
+        }
+
+        Ok((order_id, fills))
+    }
+
+    /// Net premium cash flow per account for the fills of an order placed
+    /// on `side` (positive = received), in address order
+    pub fn premium_flows(side: Side, fills: &[Fill]) -> Vec<(Address, i64)> {
+        let mut flows: BTreeMap<Address, i64> = BTreeMap::new();
+        for fill in fills {
+            let cash = Self::fill_premium(fill);
+            let (buyer, seller) = Self::counterparties(side, fill);
+            *flows.entry(buyer).or_default() -= cash;
+            *flows.entry(seller).or_default() += cash;
+        }
+        flows.into_iter().filter(|(_, amount)| *amount != 0).collect()
+    }
+
+    /// Premium paid for a fill: contracts times premium per contract
+    fn fill_premium(fill: &Fill) -> i64 {
+        let qty = fill.size.0.to::<u64>() as i128;
+        (qty * fill.price.0 as i128 / Price::SCALE as i128) as i64
+    }
+
+    /// Buyer and seller of a fill of an order placed on `side`
+    fn counterparties(side: Side, fill: &Fill) -> (Address, Address) {
+        match side {
+            Side::Bid => (fill.taker, fill.maker),
+            Side::Ask => (fill.maker, fill.taker),
+        }
+    }
+
+    /// Cancel a resting option order
+    pub fn cancel_order(&mut self, series_id: AssetId, order_id: OrderId) -> Result<Order> {
+        self.books
+            .get_mut(&series_id)
+            .ok_or_else(|| anyhow!("Series not found"))?
+            .cancel_order(order_id)
+    }
+
+    fn apply_trade(&mut self, user: Address, series: AssetId, size_delta: i64, premium_delta: i64) {
+        let position = self.positions
+            .entry((user, series))
+            .or_insert_with(|| OptionPosition {
+                user,
+                series,
+                size: 0,
+                premium: 0,
+            });
+        position.size += size_delta;
+        position.premium += premium_delta;
+    }
+
+    /// Exercise all series expired at `timestamp` against the oracle TWAP
+    /// of their underlying. Resting orders are cancelled and every open
+    /// position is cash-settled at intrinsic value.
+    ///
+    /// Writers pay at most their `funds`; when they fall short, holders of
+    /// the series share what was collected pro rata, so payouts never
+    /// exceed what was paid in. A series whose underlying has no TWAP yet
+    /// is left for a later call.
+    pub fn settle_expired(
+        &mut self,
+        oracle: &OracleEngine,
+        timestamp: u64,
+        funds: impl Fn(&Address) -> U256,
+    ) -> Result<Vec<OptionSettlement>> {
+        let mut expired: Vec<AssetId> = self.series
+            .values()
+            .filter(|s| s.expiry <= timestamp && !self.settled.contains_key(&s.id))
+            .map(|s| s.id)
+            .collect();
+        expired.sort_by_key(|id| id.0);
+
+        let mut results = Vec::new();
+
+        for id in expired {
+            let series = self.series[&id].clone();
+            let Ok(settlement_price) = oracle.get_twap(
+                series.underlying,
+                self.config.settlement_twap_window,
+                series.expiry,
+            ) else {
+                continue;
+            };
+            let intrinsic = series.intrinsic_value(settlement_price);
+
+            // Cancel resting orders
+            self.books.insert(id, OrderBook::new(id));
+
+            let mut holders: Vec<Address> = self.positions
+                .iter()
+                .filter(|((_, s), p)| *s == id && p.size != 0)
+                .map(|((u, _), _)| *u)
+                .collect();
+            holders.sort();
+
+            // Intrinsic payouts, with writers capped at their funds
+            let mut payouts: Vec<(Address, i64, i64)> = Vec::new();
+            let (mut owed, mut collected) = (0i128, 0i128);
+            for user in holders {
+                let size = self.positions[&(user, id)].size;
+                let mut payout = self.rounding.signed_div(
+                    size as i128 * intrinsic.0 as i128,
+                    Price::SCALE as i128,
+                ) as i64;
+                if payout < 0 {
+                    let available = funds(&user).min(U256::from(u64::MAX)).to::<u64>();
+                    payout = -(payout.unsigned_abs().min(available) as i64);
+                    collected += payout.unsigned_abs() as i128;
+                } else {
+                    owed += payout as i128;
+                }
+                payouts.push((user, size, payout));
+            }
+
+            for (user, size, mut payout) in payouts {
+                if payout > 0 && collected < owed {
+                    payout = (payout as i128 * collected / owed) as i64;
+                }
+                results.push(OptionSettlement {
+                    user,
+                    series: id,
+                    size,
+                    settlement_price,
+                    payout,
+                    timestamp,
+                });
+                if let Some(position) = self.positions.get_mut(&(user, id)) {
+                    position.size = 0;
+                }
+            }
+
+            self.settled.insert(id, settlement_price);
+        }
+
+        self.settlements.extend(results.iter().cloned());
+        Ok(results)
+    }
+
+    /// Get settlement price for an expired series
+    pub fn get_settlement_price(&self, id: AssetId) -> Option<Price> {
+        self.settled.get(&id).copied()
+    }
+
+    /// Get settlement history
+    pub fn get_settlements(&self) -> &[OptionSettlement] {
+        &self.settlements
+    }
+
+    /// Persistable state: series, resting orders, positions and
+    /// settlement prices
+    pub fn state(&self) -> OptionsState {
+        let mut series: Vec<OptionSeries> = self.series.values().cloned().collect();
+        series.sort_by_key(|s| s.id.0);
+        let mut books: Vec<&OrderBook> = self.books.values().collect();
+        books.sort_by_key(|b| b.asset.0);
+        let mut positions: Vec<OptionPosition> = self.positions.values().cloned().collect();
+        positions.sort_by_key(|p| (p.user, p.series.0));
+        let mut settled: Vec<(AssetId, Price)> = self.settled.iter().map(|(a, p)| (*a, *p)).collect();
+        settled.sort_by_key(|(asset, _)| asset.0);
+
+        OptionsState {
+            series,
+            next_order_ids: books.iter().map(|b| (b.asset, b.next_order_id)).collect(),
+            orders: books.iter().flat_map(|&b| b.orders().cloned()).collect(),
+            positions,
+            settled,
+        }
+    }
+
+    /// Replace series, books, positions and settlement prices with a
+    /// persisted state, keeping the configuration
+    pub fn restore(&mut self, state: OptionsState) {
+        self.books = state.series.iter().map(|s| (s.id, OrderBook::new(s.id))).collect();
+        self.series = state.series.into_iter().map(|s| (s.id, s)).collect();
+        for (asset, next_id) in state.next_order_ids {
+            if let Some(book) = self.books.get_mut(&asset) {
+                book.next_order_id = next_id;
+            }
+        }
+        for order in state.orders {
+            if let Some(book) = self.books.get_mut(&order.asset) {
+                book.restore_order(order);
+            }
+        }
+        for book in self.books.values_mut() {
+            book.update_cache_after_restore();
+        }
+        self.positions = state.positions.into_iter().map(|p| ((p.user, p.series), p)).collect();
+        self.settled = state.settled.into_iter().collect();
+    }
+}
+
+impl Default for OptionsEngine {
+    fn default() -> Self {
+        Self::new(OptionsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNDERLYING: AssetId = AssetId(1);
+    const CALL: AssetId = AssetId(1001);
+    const PUT: AssetId = AssetId(1002);
+
+    fn setup() -> (OptionsEngine, RiskEngine, HashMap<AssetId, Price>) {
+        let mut engine = OptionsEngine::default();
+        engine.list_series(OptionSeries {
+            id: CALL,
+            underlying: UNDERLYING,
+            kind: OptionKind::Call,
+            strike: Price::from_float(100.0),
+            expiry: 1000,
+        }).unwrap();
+        engine.list_series(OptionSeries {
+            id: PUT,
+            underlying: UNDERLYING,
+            kind: OptionKind::Put,
+            strike: Price::from_float(100.0),
+            expiry: 1000,
+        }).unwrap();
+
+        let mut prices = HashMap::new();
+        prices.insert(UNDERLYING, Price::from_float(100.0));
+        (engine, RiskEngine::new(), prices)
+    }
+
+    #[test]
+    fn test_list_series() {
+        let (mut engine, _, _) = setup();
+        assert!(engine.get_series(CALL).is_some());
+        assert!(engine.get_book(CALL).is_some());
+
+        let dup = engine.get_series(CALL).unwrap().clone();
+        assert!(engine.list_series(dup).is_err());
+    }
+
+    #[test]
+    fn test_intrinsic_value() {
+        let (engine, _, _) = setup();
+        let call = engine.get_series(CALL).unwrap();
+        let put = engine.get_series(PUT).unwrap();
+
+        assert_eq!(call.intrinsic_value(Price::from_float(120.0)), Price::from_float(20.0));
+        assert_eq!(call.intrinsic_value(Price::from_float(80.0)), Price(0));
+        assert_eq!(put.intrinsic_value(Price::from_float(80.0)), Price::from_float(20.0));
+    }
+
+    #[test]
+    fn test_trade_updates_positions_and_premium() {
+        let (mut engine, risk, prices) = setup();
+        let writer = Address::from([1u8; 20]);
+        let buyer = Address::from([2u8; 20]);
+
+        engine.place_order(
+            &risk,
+            OptionOrder { trader: writer, series: CALL, side: Side::Ask, premium: Price::from_float(5.0), size: 10 },
+            &prices,
+            U256::from(1000),
+            0,
+        )
+            .unwrap();
+        let (_, fills) = engine
+            .place_order(
+            &risk,
+            OptionOrder { trader: buyer, series: CALL, side: Side::Bid, premium: Price::from_float(5.0), size: 10 },
+            &prices,
+            U256::from(1000),
+            1,
+        )
+            .unwrap();
+
+        assert_eq!(fills.len(), 1);
+        let short = engine.get_position(&writer, CALL).unwrap();
+        assert_eq!(short.size, -10);
+        assert_eq!(short.premium, 50);
+        let long = engine.get_position(&buyer, CALL).unwrap();
+        assert_eq!(long.size, 10);
+        assert_eq!(long.premium, -50);
+
+        assert!(engine.required_margin(&risk, &writer, &prices).unwrap() > U256::ZERO);
+        assert_eq!(engine.required_margin(&risk, &buyer, &prices).unwrap(), U256::ZERO);
+        assert_eq!(OptionsEngine::premium_flows(Side::Bid, &fills), vec![(writer, 50), (buyer, -50)]);
+    }
+
+    #[test]
+    fn test_bid_requires_premium() {
+        let (mut engine, risk, prices) = setup();
+        let buyer = Address::from([2u8; 20]);
+        let bid = |size| OptionOrder { trader: buyer, series: CALL, side: Side::Bid, premium: Price::from_float(5.0), size };
+
+        // 10 contracts at 5 = 50 premium
+        assert!(engine.place_order(&risk, bid(10), &prices, U256::from(49), 0).is_err());
+        engine.place_order(&risk, bid(10), &prices, U256::from(50), 0).unwrap();
+
+        // Resting bids count toward the premium owed
+        assert!(engine.place_order(&risk, bid(1), &prices, U256::from(50), 0).is_err());
+    }
+
+    #[test]
+    fn test_short_requires_margin() {
+        let (mut engine, risk, prices) = setup();
+        let writer = Address::from([1u8; 20]);
+
+        // ATM call, 10 contracts: 10 * (0 + 15) = 150 required
+        let result = engine.place_order(
+            &risk,
+            OptionOrder { trader: writer, series: CALL, side: Side::Ask, premium: Price::from_float(5.0), size: 10 },
+            &prices,
+            U256::from(100),
+            0,
+        );
+        assert!(result.is_err());
+
+        let result = engine.place_order(
+            &risk,
+            OptionOrder { trader: writer, series: CALL, side: Side::Ask, premium: Price::from_float(5.0), size: 10 },
+            &prices,
+            U256::from(150),
+            0,
+        );
+        assert!(result.is_ok());
+
+        // Resting asks count toward worst-case exposure
+        let result = engine.place_order(
+            &risk,
+            OptionOrder { trader: writer, series: CALL, side: Side::Ask, premium: Price::from_float(5.0), size: 1 },
+            &prices,
+            U256::from(150),
+            0,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_expired_series_rejects_orders() {
+        let (mut engine, risk, prices) = setup();
+        let buyer = Address::from([2u8; 20]);
+
+        let result = engine.place_order(
+            &risk,
+            OptionOrder { trader: buyer, series: CALL, side: Side::Bid, premium: Price::from_float(5.0), size: 1 },
+            &prices,
+            U256::ZERO,
+            1000,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_settle_expired_at_oracle_twap() {
+        let (mut engine, risk, prices) = setup();
+        let writer = Address::from([1u8; 20]);
+        let buyer = Address::from([2u8; 20]);
+        let mut oracle = OracleEngine::default();
+
+        engine.place_order(
+            &risk,
+            OptionOrder { trader: writer, series: CALL, side: Side::Ask, premium: Price::from_float(5.0), size: 10 },
+            &prices,
+            U256::from(1000),
+            0,
+        )
+            .unwrap();
+        engine.place_order(
+            &risk,
+            OptionOrder { trader: buyer, series: CALL, side: Side::Bid, premium: Price::from_float(5.0), size: 10 },
+            &prices,
+            U256::from(1000),
+            1,
+        )
+            .unwrap();
+        engine.place_order(
+            &risk,
+            OptionOrder { trader: buyer, series: PUT, side: Side::Bid, premium: Price::from_float(1.0), size: 5 },
+            &prices,
+            U256::from(1000),
+            1,
+        )
+            .unwrap();
+
+        oracle.update_price(UNDERLYING, Price::from_float(110.0), 0).unwrap();
+        oracle.update_price(UNDERLYING, Price::from_float(130.0), 100).unwrap();
+
+        // Nothing expired yet
+        assert!(engine.settle_expired(&oracle, 999, |_| U256::MAX).unwrap().is_empty());
+
+        let settlements = engine.settle_expired(&oracle, 1000, |_| U256::MAX).unwrap();
+        // TWAP over [-800, 1000] clipped to samples: 100s at 110, 900s at 130 = 128
+        assert_eq!(engine.get_settlement_price(CALL), Some(Price::from_float(128.0)));
+
+        // Call holders settle at 28 intrinsic; resting put bid cancelled
+        assert_eq!(settlements.len(), 2);
+        let buyer_settlement = settlements.iter().find(|s| s.user == buyer).unwrap();
+        assert_eq!(buyer_settlement.payout, 280);
+        let writer_settlement = settlements.iter().find(|s| s.user == writer).unwrap();
+        assert_eq!(writer_settlement.payout, -280);
+
+        assert_eq!(engine.get_position(&buyer, CALL).unwrap().size, 0);
+        assert_eq!(engine.get_book(PUT).unwrap().orders().count(), 0);
+
+        // Idempotent
+        assert!(engine.settle_expired(&oracle, 2000, |_| U256::MAX).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_writer_shortfall_is_shared_by_holders() {
+        let (mut engine, risk, prices) = setup();
+        let writer = Address::from([1u8; 20]);
+        let (a, b) = (Address::from([2u8; 20]), Address::from([3u8; 20]));
+        let mut oracle = OracleEngine::default();
+
+        let order = |trader, side, size| OptionOrder { trader, series: CALL, side, premium: Price::from_float(5.0), size };
+        engine.place_order(&risk, order(writer, Side::Ask, 10), &prices, U256::from(1000), 0).unwrap();
+        engine.place_order(&risk, order(a, Side::Bid, 6), &prices, U256::from(1000), 1).unwrap();
+        engine.place_order(&risk, order(b, Side::Bid, 4), &prices, U256::from(1000), 1).unwrap();
+        oracle.update_price(UNDERLYING, Price::from_float(128.0), 0).unwrap();
+
+        // Writer owes 280 but only holds 140: holders get half of 168 and 112
+        let settlements = engine
+            .settle_expired(&oracle, 1000, |user| if *user == writer { U256::from(140) } else { U256::MAX })
+            .unwrap();
+        let payout = |user| settlements.iter().find(|s| s.user == user).unwrap().payout;
+        assert_eq!(payout(writer), -140);
+        assert_eq!(payout(a), 84);
+        assert_eq!(payout(b), 56);
+    }
+
+    #[test]
+    fn test_cancel_unfunded_bids() {
+        let (mut engine, risk, prices) = setup();
+        let (funded, broke) = (Address::from([2u8; 20]), Address::from([3u8; 20]));
+        let bid = |trader, size| OptionOrder { trader, series: CALL, side: Side::Bid, premium: Price::from_float(5.0), size };
+        engine.place_order(&risk, bid(broke, 10), &prices, U256::from(50), 0).unwrap();
+        engine.place_order(&risk, bid(funded, 10), &prices, U256::from(50), 0).unwrap();
+
+        // The broke bidder's 50 premium is no longer backed
+        let cancelled = engine
+            .cancel_unfunded_bids(CALL, |user| if *user == broke { U256::from(49) } else { U256::from(50) })
+            .unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].trader, broke);
+        let resting: Vec<Address> = engine.get_book(CALL).unwrap().orders().map(|o| o.trader).collect();
+        assert_eq!(resting, vec![funded]);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let (mut engine, risk, prices) = setup();
+        let writer = Address::from([1u8; 20]);
+        let buyer = Address::from([2u8; 20]);
+        let order = |trader, side, size| OptionOrder { trader, series: CALL, side, premium: Price::from_float(5.0), size };
+        engine.place_order(&risk, order(writer, Side::Ask, 10), &prices, U256::from(1000), 0).unwrap();
+        engine.place_order(&risk, order(buyer, Side::Bid, 4), &prices, U256::from(1000), 1).unwrap();
+
+        let bytes = serde_json::to_vec(&engine.state()).unwrap();
+        let mut restored = OptionsEngine::default();
+        restored.restore(serde_json::from_slice(&bytes).unwrap());
+
+        assert!(restored.get_series(PUT).is_some());
+        assert_eq!(restored.get_position(&writer, CALL).unwrap().size, -4);
+        assert_eq!(restored.get_position(&buyer, CALL).unwrap().premium, -20);
+        let book = restored.get_book(CALL).unwrap();
+        assert_eq!(book.get_best_ask(), Some((Price::from_float(5.0), U256::from(6))));
+        // Order IDs keep counting past the filled bid
+        assert_eq!(book.next_order_id, engine.get_book(CALL).unwrap().next_order_id);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::types::*;
+use alloy_primitives::Address;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 /// Price source for mark price calculation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -20,6 +21,8 @@ pub struct OracleConfig {
     pub max_price_age: u64,
     /// Minimum spread to accept price (basis points)
     pub min_spread_bps: u64,
+    /// Maximum external price samples retained per asset (for TWAP)
+    pub max_history: usize,
 }
 
 impl Default for OracleConfig {
@@ -28,17 +31,23 @@ impl Default for OracleConfig {
             sources: HashMap::new(),
             max_price_age: 60,  // 60 seconds
             min_spread_bps: 10,  // 0.1%
+            max_history: 1024,
         }
     }
 }
 
 /// Mark price oracle
+#[derive(Clone)]
 pub struct OracleEngine {
     config: OracleConfig,
     /// External prices by asset
     external_prices: HashMap<AssetId, (Price, u64)>, // (price, timestamp)
     /// Index prices (spot reference)
     index_prices: HashMap<AssetId, Price>,
+    /// External price samples by asset, oldest first (for TWAP)
+    price_history: HashMap<AssetId, VecDeque<(u64, Price)>>,
+    /// Addresses allowed to post prices, by asset
+    reporters: HashMap<AssetId, HashSet<Address>>,
 }
 
 impl OracleEngine {
@@ -47,6 +56,8 @@ impl OracleEngine {
             config,
             external_prices: HashMap::new(),
             index_prices: HashMap::new(),
+            price_history: HashMap::new(),
+            reporters: HashMap::new(),
         }
     }
     
@@ -58,9 +69,60 @@ impl OracleEngine {
         timestamp: u64,
     ) -> Result<()> {
         self.external_prices.insert(asset, (price, timestamp));
+        
+        let history = self.price_history.entry(asset).or_default();
+        history.push_back((timestamp, price));
+        while history.len() > self.config.max_history {
+            history.pop_front();
+        }
+        
         Ok(())
     }
     
+    /// Time-weighted average of external prices over `[timestamp - window, timestamp]`.
+    ///
+    /// Each sample is weighted by how long it remained the latest price within
+    /// the window; the sample in effect at the window start is included.
+    pub fn get_twap(&self, asset: AssetId, window: u64, timestamp: u64) -> Result<Price> {
+        let history = self.price_history
+            .get(&asset)
+            .filter(|h| !h.is_empty())
+            .ok_or_else(|| anyhow!("No price history for asset"))?;
+        
+        let start = timestamp.saturating_sub(window);
+        let mut weighted_sum: u128 = 0;
+        let mut total_weight: u128 = 0;
+        
+        for (i, (ts, price)) in history.iter().enumerate() {
+            if *ts > timestamp {
+                break;
+            }
+            let next_ts = history
+                .get(i + 1)
+                .map(|(t, _)| (*t).min(timestamp))
+                .unwrap_or(timestamp);
+            let from = (*ts).max(start);
+            if next_ts <= from {
+                continue;
+            }
+            let weight = (next_ts - from) as u128;
+            weighted_sum += price.0 as u128 * weight;
+            total_weight += weight;
+        }
+        
+        if total_weight == 0 {
+            // All samples at (or after) the query time: use the latest one in range
+            return history
+                .iter()
+                .rev()
+                .find(|(ts, _)| *ts <= timestamp)
+                .map(|(_, p)| *p)
+                .ok_or_else(|| anyhow!("No price history in window"));
+        }
+        
+        Ok(Price((weighted_sum / total_weight) as u64))
+    }
+    
     /// Get mark price for margin calculations
     pub fn get_mark_price(
         &self,
@@ -98,6 +160,14 @@ impl OracleEngine {
         }
     }
     
+    /// Get the latest external price if it is fresh at `timestamp`
+    pub fn get_external_price(&self, asset: AssetId, timestamp: u64) -> Option<Price> {
+        self.external_prices
+            .get(&asset)
+            .filter(|(_, ts)| timestamp.saturating_sub(*ts) <= self.config.max_price_age)
+            .map(|(price, _)| *price)
+    }
+    
     /// Get index price (spot reference)
     pub fn get_index_price(&self, asset: AssetId) -> Option<Price> {
         self.index_prices.get(&asset).copied()
@@ -113,6 +183,44 @@ impl OracleEngine {
         self.config.sources.insert(asset, source);
     }
     
+    /// Allow `reporter` to post prices for `asset`
+    pub fn authorize_reporter(&mut self, asset: AssetId, reporter: Address) {
+        self.reporters.entry(asset).or_default().insert(reporter);
+    }
+    
+    /// Stop `reporter` posting prices for `asset`
+    pub fn revoke_reporter(&mut self, asset: AssetId, reporter: &Address) {
+        if let Some(reporters) = self.reporters.get_mut(&asset) {
+            reporters.remove(reporter);
+            if reporters.is_empty() {
+                self.reporters.remove(&asset);
+            }
+        }
+    }
+    
+    /// Check whether `reporter` may post prices for `asset`
+    pub fn is_reporter(&self, asset: AssetId, reporter: &Address) -> bool {
+        self.reporters.get(&asset).is_some_and(|r| r.contains(reporter))
+    }
+    
+    /// Every (asset, reporter) pair, sorted
+    pub fn reporters(&self) -> Vec<(AssetId, Address)> {
+        let mut reporters: Vec<_> = self.reporters
+            .iter()
+            .flat_map(|(asset, reporters)| reporters.iter().map(|reporter| (*asset, *reporter)))
+            .collect();
+        reporters.sort_by_key(|(asset, reporter)| (asset.0, *reporter));
+        reporters
+    }
+    
+    /// Replace the reporters with `reporters`
+    pub fn restore_reporters(&mut self, reporters: impl IntoIterator<Item = (AssetId, Address)>) {
+        self.reporters.clear();
+        for (asset, reporter) in reporters {
+            self.authorize_reporter(asset, reporter);
+        }
+    }
+    
     /// Check if external price is stale
     pub fn is_price_stale(&self, asset: AssetId, timestamp: u64) -> bool {
         if let Some((_, ts)) = self.external_prices.get(&asset) {
@@ -219,6 +327,27 @@ mod tests {
         // Should fallback to book price
         assert_eq!(mark, Price::from_float(100.0));
     }
-}
-
 
+    #[test]
+    fn test_twap() {
+        let mut oracle = OracleEngine::default();
+        let asset = AssetId(1);
+        
+        oracle.update_price(asset, Price::from_float(100.0), 0).unwrap();
+        oracle.update_price(asset, Price::from_float(110.0), 30).unwrap();
+        
+        // 30s at 100, 30s at 110
+        let twap = oracle.get_twap(asset, 60, 60).unwrap();
+        assert_eq!(twap, Price::from_float(105.0));
+        
+        // Window starting at 45 only sees 110
+        let twap = oracle.get_twap(asset, 15, 60).unwrap();
+        assert_eq!(twap, Price::from_float(110.0));
+        
+        // Single sample at query time
+        oracle.update_price(AssetId(2), Price::from_float(50.0), 10).unwrap();
+        assert_eq!(oracle.get_twap(AssetId(2), 60, 10).unwrap(), Price::from_float(50.0));
+        
+        assert!(oracle.get_twap(AssetId(3), 60, 10).is_err());
+    }
+}
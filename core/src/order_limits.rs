@@ -0,0 +1,289 @@
+use crate::orderbook::OrderBook;
+use crate::types::*;
+use alloy_primitives::Address;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Order flow limits configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderLimitsConfig {
+    /// Maximum resting orders per account per asset
+    pub max_open_orders_per_asset: usize,
+    /// Maximum order submissions per account per block
+    pub max_orders_per_block: usize,
+    /// Multiplier applied to both limits for registered market makers
+    /// (0 = market makers are fully exempt)
+    pub market_maker_multiplier: usize,
+}
+
+impl Default for OrderLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_open_orders_per_asset: 200,
+            max_orders_per_block: 100,
+            market_maker_multiplier: 0,  // Fully exempt
+        }
+    }
+}
+
+/// Per-account order rate and open-order cap enforcement
+///
+/// Protects book memory and matching latency by bounding how many orders a
+/// single account can keep resting on a book and how many it can submit
+/// within one block.
+#[derive(Debug, Clone)]
+pub struct OrderLimits {
+    config: OrderLimitsConfig,
+    /// Resting order count by (account, asset)
+    open_orders: HashMap<(Address, AssetId), usize>,
+    /// Submissions in the current block by account
+    block_submissions: HashMap<Address, usize>,
+    /// Registered market makers
+    market_makers: HashSet<Address>,
+    /// Block height the submission counters belong to
+    current_height: u64,
+}
+
+impl OrderLimits {
+    pub fn new(config: OrderLimitsConfig) -> Self {
+        Self {
+            config,
+            open_orders: HashMap::new(),
+            block_submissions: HashMap::new(),
+            market_makers: HashSet::new(),
+            current_height: 0,
+        }
+    }
+
+    /// Get configuration
+    pub fn config(&self) -> &OrderLimitsConfig {
+        &self.config
+    }
+
+    /// Update configuration
+    pub fn set_config(&mut self, config: OrderLimitsConfig) {
+        self.config = config;
+    }
+
+    /// Register a market maker (exempt from or granted raised limits)
+    pub fn register_market_maker(&mut self, account: Address) {
+        self.market_makers.insert(account);
+    }
+
+    /// Remove a market maker registration
+    pub fn unregister_market_maker(&mut self, account: &Address) -> bool {
+        self.market_makers.remove(account)
+    }
+
+    /// Check if account is a registered market maker
+    pub fn is_market_maker(&self, account: &Address) -> bool {
+        self.market_makers.contains(account)
+    }
+
+    /// Effective limit for an account (None = unlimited)
+    fn effective_limit(&self, account: &Address, base: usize) -> Option<usize> {
+        if !self.is_market_maker(account) {
+            return Some(base);
+        }
+
+        match self.config.market_maker_multiplier {
+            0 => None,
+            m => Some(base.saturating_mul(m)),
+        }
+    }
+
+    /// Check if account can submit another order this block
+    pub fn check_submission_rate(&self, account: &Address) -> Result<()> {
+        if let Some(limit) = self.effective_limit(account, self.config.max_orders_per_block) {
+            let submitted = self.block_submissions.get(account).copied().unwrap_or(0);
+            if submitted >= limit {
+                return Err(anyhow!(
+                    "Order rate limit reached: {} orders in block {} (max {})",
+                    submitted,
+                    self.current_height,
+                    limit
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if account can add another resting order on asset
+    pub fn check_open_orders(&self, account: &Address, asset: AssetId) -> Result<()> {
+        if let Some(limit) = self.effective_limit(account, self.config.max_open_orders_per_asset) {
+            let open = self.open_order_count(account, asset);
+            if open >= limit {
+                return Err(anyhow!(
+                    "Open order limit reached: {} orders on asset {} (max {})",
+                    open,
+                    asset.0,
+                    limit
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pre-trade check for a limit order (rate + open-order cap)
+    pub fn check_order(&self, account: &Address, asset: AssetId) -> Result<()> {
+        self.check_submission_rate(account)?;
+        self.check_open_orders(account, asset)
+    }
+
+    /// Record an order submission in the current block
+    pub fn record_submission(&mut self, account: Address) {
+        *self.block_submissions.entry(account).or_insert(0) += 1;
+    }
+
+    /// Record a new resting order
+    pub fn record_open(&mut self, account: Address, asset: AssetId) {
+        *self.open_orders.entry((account, asset)).or_insert(0) += 1;
+    }
+
+    /// Record a resting order leaving the book (filled or cancelled)
+    pub fn record_closed(&mut self, account: Address, asset: AssetId) {
+        if let Some(count) = self.open_orders.get_mut(&(account, asset)) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.open_orders.remove(&(account, asset));
+            }
+        }
+    }
+
+    /// Get number of resting orders for account on asset
+    pub fn open_order_count(&self, account: &Address, asset: AssetId) -> usize {
+        self.open_orders.get(&(*account, asset)).copied().unwrap_or(0)
+    }
+
+    /// Get number of submissions by account in the current block
+    pub fn block_submission_count(&self, account: &Address) -> usize {
+        self.block_submissions.get(account).copied().unwrap_or(0)
+    }
+
+    /// Advance to a new block, resetting per-block counters
+    pub fn on_new_block(&mut self, height: u64) {
+        if height != self.current_height {
+            self.current_height = height;
+            self.block_submissions.clear();
+        }
+    }
+
+    /// Rebuild open order counts for an asset from a book (after recovery)
+    pub fn rebuild_from_book(&mut self, book: &OrderBook) {
+        let asset = book.asset;
+        self.open_orders.retain(|(_, a), _| *a != asset);
+
+        for order in book.orders() {
+            self.record_open(order.trader, asset);
+        }
+    }
+}
+
+impl Default for OrderLimits {
+    fn default() -> Self {
+        Self::new(OrderLimitsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::U256;
+
+    fn limits(max_open: usize, max_per_block: usize) -> OrderLimits {
+        OrderLimits::new(OrderLimitsConfig {
+            max_open_orders_per_asset: max_open,
+            max_orders_per_block: max_per_block,
+            market_maker_multiplier: 0,
+        })
+    }
+
+    #[test]
+    fn test_open_order_cap() {
+        let mut ol = limits(2, 100);
+        let user = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+
+        ol.record_open(user, asset);
+        assert!(ol.check_open_orders(&user, asset).is_ok());
+
+        ol.record_open(user, asset);
+        assert!(ol.check_open_orders(&user, asset).is_err());
+
+        // Other assets are tracked separately
+        assert!(ol.check_open_orders(&user, AssetId(2)).is_ok());
+
+        ol.record_closed(user, asset);
+        assert!(ol.check_open_orders(&user, asset).is_ok());
+        assert_eq!(ol.open_order_count(&user, asset), 1);
+    }
+
+    #[test]
+    fn test_block_rate_limit_resets() {
+        let mut ol = limits(100, 2);
+        let user = Address::from([1u8; 20]);
+
+        ol.on_new_block(1);
+        ol.record_submission(user);
+        ol.record_submission(user);
+        assert!(ol.check_submission_rate(&user).is_err());
+
+        // Same height does not reset
+        ol.on_new_block(1);
+        assert!(ol.check_submission_rate(&user).is_err());
+
+        ol.on_new_block(2);
+        assert!(ol.check_submission_rate(&user).is_ok());
+        assert_eq!(ol.block_submission_count(&user), 0);
+    }
+
+    #[test]
+    fn test_market_maker_exempt() {
+        let mut ol = limits(1, 1);
+        let mm = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+
+        ol.register_market_maker(mm);
+        ol.record_submission(mm);
+        ol.record_open(mm, asset);
+        assert!(ol.check_order(&mm, asset).is_ok());
+
+        assert!(ol.unregister_market_maker(&mm));
+        assert!(ol.check_order(&mm, asset).is_err());
+    }
+
+    #[test]
+    fn test_market_maker_multiplier() {
+        let mut ol = OrderLimits::new(OrderLimitsConfig {
+            max_open_orders_per_asset: 1,
+            max_orders_per_block: 2,
+            market_maker_multiplier: 3,
+        });
+        let mm = Address::from([2u8; 20]);
+        ol.register_market_maker(mm);
+
+        for _ in 0..6 {
+            assert!(ol.check_submission_rate(&mm).is_ok());
+            ol.record_submission(mm);
+        }
+        assert!(ol.check_submission_rate(&mm).is_err());
+    }
+
+    #[test]
+    fn test_rebuild_from_book() {
+        let mut ol = OrderLimits::default();
+        let user = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        let mut book = OrderBook::new(asset);
+
+        book.add_limit_order(user, Side::Bid, Price::from_float(1.0), Size(U256::from(10)), 0);
+        book.add_limit_order(user, Side::Ask, Price::from_float(2.0), Size(U256::from(10)), 0);
+
+        ol.record_open(user, asset);
+        ol.rebuild_from_book(&book);
+        assert_eq!(ol.open_order_count(&user, asset), 2);
+    }
+}
@@ -1,8 +1,9 @@
+use crate::arena::{OrderArena, OrderIdMap, SlotId};
 use crate::types::*;
 use alloy_primitives::{Address, U256};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Cached best bid/ask for O(1) access
 #[derive(Debug, Clone)]
@@ -12,59 +13,113 @@ pub struct OrderBookCache {
     pub mid_price: Option<Price>,
 }
 
-/// Price level containing orders at a specific price
+/// Price level containing orders at a specific price.
+///
+/// Orders live in the book's `OrderArena`; the level only holds the head
+/// and tail of an intrusive FIFO list threaded through the arena nodes.
 #[derive(Debug, Clone)]
 pub struct PriceLevel {
     pub price: Price,
-    pub orders: VecDeque<Order>,  // FIFO queue
     pub total_size: U256,
+    head: Option<SlotId>,
+    tail: Option<SlotId>,
+    len: usize,
 }
 
 impl PriceLevel {
     pub fn new(price: Price) -> Self {
         Self {
             price,
-            orders: VecDeque::new(),
             total_size: U256::ZERO,
+            head: None,
+            tail: None,
+            len: 0,
         }
     }
     
-    pub fn add_order(&mut self, order: Order) {
+    /// Append an order to the back of the queue
+    pub fn push_back(&mut self, arena: &mut OrderArena, order: Order) -> SlotId {
         self.total_size += order.remaining().0;
-        self.orders.push_back(order);
+        let slot = arena.insert(order);
+        
+        if let Some(tail) = self.tail {
+            if let Some(node) = arena.node_mut(tail) {
+                node.next = Some(slot);
+            }
+            if let Some(node) = arena.node_mut(slot) {
+                node.prev = Some(tail);
+            }
+        } else {
+            self.head = Some(slot);
+        }
+        self.tail = Some(slot);
+        self.len += 1;
+        
+        slot
     }
     
-    pub fn remove_order(&mut self, order_id: OrderId) -> Option<Order> {
-        if let Some(pos) = self.orders.iter().position(|o| o.id == order_id) {
-            let order = self.orders.remove(pos)?;
-            self.total_size -= order.remaining().0;
-            Some(order)
-        } else {
-            None
+    /// Unlink an order from the queue and free its slot - O(1)
+    pub fn remove(&mut self, arena: &mut OrderArena, slot: SlotId) -> Option<Order> {
+        let (prev, next) = {
+            let node = arena.node(slot)?;
+            (node.prev, node.next)
+        };
+        
+        match prev {
+            Some(p) => arena.node_mut(p)?.next = next,
+            None => self.head = next,
         }
+        match next {
+            Some(n) => arena.node_mut(n)?.prev = prev,
+            None => self.tail = prev,
+        }
+        
+        let order = arena.remove(slot)?;
+        self.total_size -= order.remaining().0;
+        self.len -= 1;
+        
+        Some(order)
     }
     
     pub fn is_empty(&self) -> bool {
-        self.orders.is_empty()
+        self.len == 0
+    }
+    
+    /// Number of orders at this level
+    pub fn len(&self) -> usize {
+        self.len
     }
     
-    /// Get the first order in the queue (for matching)
-    pub fn front(&self) -> Option<&Order> {
-        self.orders.front()
+    /// Slot of the first order in the queue (for matching)
+    pub fn front_slot(&self) -> Option<SlotId> {
+        self.head
     }
     
-    /// Get mutable reference to first order
-    pub fn front_mut(&mut self) -> Option<&mut Order> {
-        self.orders.front_mut()
+    /// Get the first order in the queue
+    pub fn front<'a>(&self, arena: &'a OrderArena) -> Option<&'a Order> {
+        self.head.and_then(|slot| arena.get(slot))
     }
     
     /// Remove the first order from the queue
-    pub fn pop_front(&mut self) -> Option<Order> {
-        if let Some(order) = self.orders.pop_front() {
-            self.total_size -= order.remaining().0;
-            Some(order)
-        } else {
-            None
+    pub fn pop_front(&mut self, arena: &mut OrderArena) -> Option<Order> {
+        let node = arena.take(self.head?)?;
+        
+        self.head = node.next;
+        match node.next {
+            Some(n) => arena.node_mut(n)?.prev = None,
+            None => self.tail = None,
+        }
+        self.total_size -= node.order.remaining().0;
+        self.len -= 1;
+        
+        Some(node.order)
+    }
+    
+    /// Iterate orders in FIFO order
+    pub fn iter<'a>(&self, arena: &'a OrderArena) -> LevelIter<'a> {
+        LevelIter {
+            arena,
+            next: self.head,
         }
     }
     
@@ -74,15 +129,42 @@ impl PriceLevel {
     }
 }
 
+/// FIFO iterator over the orders of a price level
+pub struct LevelIter<'a> {
+    arena: &'a OrderArena,
+    next: Option<SlotId>,
+}
+
+impl<'a> Iterator for LevelIter<'a> {
+    type Item = &'a Order;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.arena.node(self.next?)?;
+        self.next = node.next;
+        Some(&node.order)
+    }
+}
+
+/// Location of a resting order
+#[derive(Debug, Clone, Copy)]
+struct OrderLocation {
+    price: Price,
+    side: Side,
+    slot: SlotId,
+}
+
 /// Order book for a single asset
+#[derive(Debug, Clone)]
 pub struct OrderBook {
     pub asset: AssetId,
     /// Bids sorted descending (highest first)
     bids: BTreeMap<Price, PriceLevel>,
     /// Asks sorted ascending (lowest first)
     asks: BTreeMap<Price, PriceLevel>,
-    /// Order ID -> (Price, Side) for fast lookups
-    order_index: HashMap<OrderId, (Price, Side)>,
+    /// Pooled storage for all resting orders
+    arena: OrderArena,
+    /// Order ID -> location for O(1) lookups and cancels
+    order_index: OrderIdMap<OrderLocation>,
     /// Next order ID
     pub(crate) next_order_id: OrderId,
     /// Cached best prices for performance
@@ -97,7 +179,8 @@ impl OrderBook {
             asset,
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
-            order_index: HashMap::new(),
+            arena: OrderArena::new(),
+            order_index: OrderIdMap::default(),
             next_order_id: 1,
             cache: OrderBookCache {
                 best_bid: None,
@@ -116,7 +199,7 @@ impl OrderBook {
     }
     
     /// Update cache after order book change
-    fn update_cache(&mut self) {
+    pub(crate) fn update_cache(&mut self) {
         self.cache.best_bid = self.bids.iter().next_back()
             .map(|(p, level)| (*p, level.total_size));
         self.cache.best_ask = self.asks.iter().next()
@@ -129,6 +212,14 @@ impl OrderBook {
         }
     }
     
+    /// Whether a change at `price` on `side` can move the cached best price
+    fn touches_top(&self, side: Side, price: Price) -> bool {
+        match side {
+            Side::Bid => self.cache.best_bid.is_none_or(|(best, _)| price >= best),
+            Side::Ask => self.cache.best_ask.is_none_or(|(best, _)| price <= best),
+        }
+    }
+    
     /// Public method to update cache after restoration from storage
     /// This should only be called when manually reconstructing the order book
     pub fn update_cache_after_restore(&mut self) {
@@ -170,25 +261,23 @@ impl OrderBook {
     
     /// Check if order would match with same user's orders (self-trade)
     pub fn would_self_trade(&self, trader: &Address, side: Side) -> bool {
-        let tree = match side {
-            Side::Bid => &self.asks,  // Bid would match asks
-            Side::Ask => &self.bids,  // Ask would match bids
+        // Bid would match asks, ask would match bids
+        let opposite = match side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
         };
         
-        tree.values()
-            .flat_map(|level| &level.orders)
-            .any(|o| o.trader == *trader)
+        self.side_orders(opposite).any(|o| o.trader == *trader)
     }
     
     /// Get user's orders that would self-trade with new order
     pub fn get_self_trade_orders(&self, trader: &Address, side: Side) -> Vec<OrderId> {
-        let tree = match side {
-            Side::Bid => &self.asks,
-            Side::Ask => &self.bids,
+        let opposite = match side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
         };
         
-        tree.values()
-            .flat_map(|level| &level.orders)
+        self.side_orders(opposite)
             .filter(|o| o.trader == *trader)
             .map(|o| o.id)
             .collect()
@@ -225,55 +314,177 @@ impl OrderBook {
         self.next_order_id += 1;
         
         let order = Order::new(order_id, self.asset, trader, side, price, size, timestamp);
+        self.insert_order(order);
+        
+        // Update cache if the order joined or improved the top of book
+        if self.touches_top(side, price) {
+            self.update_cache();
+        }
+        
+        order_id
+    }
+    
+    /// Insert an existing order (keeping its ID), e.g. when restoring from
+    /// storage. Call `update_cache_after_restore` once done.
+    pub fn restore_order(&mut self, order: Order) {
+        self.next_order_id = self.next_order_id.max(order.id + 1);
+        self.insert_order(order);
+    }
+    
+    fn insert_order(&mut self, order: Order) {
+        let (id, price, side) = (order.id, order.price, order.side);
         
         let tree = match side {
             Side::Bid => &mut self.bids,
             Side::Ask => &mut self.asks,
         };
         
-        tree.entry(price)
+        let slot = tree
+            .entry(price)
             .or_insert_with(|| PriceLevel::new(price))
-            .add_order(order);
+            .push_back(&mut self.arena, order);
         
-        self.order_index.insert(order_id, (price, side));
-        
-        // Update cache after adding order
-        self.update_cache();
-        
-        order_id
+        self.order_index.insert(id, OrderLocation { price, side, slot });
     }
     
-    /// Cancel an order
+    /// Cancel an order - O(1) within its level
     pub fn cancel_order(&mut self, order_id: OrderId) -> Result<Order> {
-        let (price, side) = self
+        let location = self
             .order_index
             .remove(&order_id)
             .ok_or_else(|| anyhow!("Order not found"))?;
         
-        let tree = match side {
+        let tree = match location.side {
             Side::Bid => &mut self.bids,
             Side::Ask => &mut self.asks,
         };
         
         let level = tree
-            .get_mut(&price)
+            .get_mut(&location.price)
             .ok_or_else(|| anyhow!("Price level not found"))?;
         
         let order = level
-            .remove_order(order_id)
+            .remove(&mut self.arena, location.slot)
             .ok_or_else(|| anyhow!("Order not in level"))?;
         
         // Clean up empty levels
         if level.is_empty() {
-            tree.remove(&price);
+            tree.remove(&location.price);
         }
         
-        // Update cache after removing order
-        self.update_cache();
+        // Update cache if the order was at the top of book
+        if self.touches_top(location.side, location.price) {
+            self.update_cache();
+        }
         
         Ok(order)
     }
     
+    /// Fill `size` of a resting order regardless of its queue position,
+    /// removing it once fully filled. Returns the order after the fill.
+    pub fn fill_order(&mut self, order_id: OrderId, size: Size) -> Result<Order> {
+        let location = *self
+            .order_index
+            .get(&order_id)
+            .ok_or_else(|| anyhow!("Order not found"))?;
+        
+        let order = self
+            .arena
+            .get_mut(location.slot)
+            .ok_or_else(|| anyhow!("Order not in arena"))?;
+        if size.0 > order.remaining().0 {
+            return Err(anyhow!("Fill of {} exceeds remaining {}", size.0, order.remaining().0));
+        }
+        order.filled.0 += size.0;
+        let filled = order.clone();
+        
+        let tree = match location.side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        let level = tree
+            .get_mut(&location.price)
+            .ok_or_else(|| anyhow!("Price level not found"))?;
+        level.update_size(size.0);
+        
+        if filled.is_filled() {
+            level.remove(&mut self.arena, location.slot);
+            self.order_index.remove(&order_id);
+            if level.is_empty() {
+                tree.remove(&location.price);
+            }
+        }
+        
+        if self.touches_top(location.side, location.price) {
+            self.update_cache();
+        }
+        
+        Ok(filled)
+    }
+    
+    /// Match `remaining` against the FIFO queue at `price` on `side`,
+    /// appending fills to `fills`. Filled makers are removed from the book.
+    pub(crate) fn fill_level(
+        &mut self,
+        side: Side,
+        price: Price,
+        remaining: &mut Size,
+        taker: Address,
+        timestamp: u64,
+        fills: &mut Vec<Fill>,
+    ) {
+        let tree = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        
+        let level = match tree.get_mut(&price) {
+            Some(l) => l,
+            None => return,
+        };
+        
+        while remaining.0 > U256::ZERO {
+            let slot = match level.front_slot() {
+                Some(slot) => slot,
+                None => break,
+            };
+            let order = match self.arena.get_mut(slot) {
+                Some(order) => order,
+                None => break,
+            };
+            
+            let fill_size = remaining.0.min(order.remaining().0);
+            order.filled.0 += fill_size;
+            remaining.0 -= fill_size;
+            level.update_size(fill_size);
+            
+            fills.push(Fill {
+                order_id: order.id,
+                price,
+                size: Size(fill_size),
+                maker: order.trader,
+                taker,
+                timestamp,
+            });
+            
+            if !order.is_filled() {
+                break;
+            }
+            
+            // Fully filled maker leaves the book; remaining size is zero
+            let order_id = order.id;
+            level.pop_front(&mut self.arena);
+            self.order_index.remove(&order_id);
+        }
+        
+        if level.is_empty() {
+            tree.remove(&price);
+        }
+        
+        // Keep cached best prices in sync with the level just consumed
+        self.update_cache();
+    }
+    
     /// Get total depth at a price level
     pub fn depth_at_price(&self, price: Price, side: Side) -> U256 {
         let tree = match side {
@@ -286,22 +497,83 @@ impl OrderBook {
             .unwrap_or(U256::ZERO)
     }
     
+    /// Single clearing price of a crossed book, with the volume that clears
+    /// there: the price maximizing matched volume, then minimizing the
+    /// unmatched imbalance, then the lowest such price
+    pub fn auction_clearing_price(&self) -> Option<(Price, U256)> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        if bid < ask {
+            return None;
+        }
+        
+        let candidates: BTreeSet<Price> = self.bids.range(ask..=bid)
+            .chain(self.asks.range(ask..=bid))
+            .map(|(price, _)| *price)
+            .collect();
+        
+        let mut best: Option<(Price, U256, U256)> = None;
+        for price in candidates {
+            let demand = self.bids.range(price..).fold(U256::ZERO, |acc, (_, l)| acc + l.total_size);
+            let supply = self.asks.range(..=price).fold(U256::ZERO, |acc, (_, l)| acc + l.total_size);
+            let volume = demand.min(supply);
+            let imbalance = demand.max(supply) - volume;
+            if best.is_none_or(|(_, v, i)| volume > v || (volume == v && imbalance < i)) {
+                best = Some((price, volume, imbalance));
+            }
+        }
+        
+        best.map(|(price, volume, _)| (price, volume))
+    }
+    
+    /// Match a crossed book at `price`: bids at or above it buy from asks at
+    /// or below it in price-time priority and every fill prints at `price`.
+    /// Asks are recorded as makers and bids as takers.
+    pub(crate) fn uncross(&mut self, price: Price, timestamp: u64) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        
+        loop {
+            let (bid, ask) = match (self.best_bid(), self.best_ask()) {
+                (Some(bid), Some(ask)) if bid >= price && ask <= price => (bid, ask),
+                _ => break,
+            };
+            let (buyer, size) = match self.bids.get(&bid).and_then(|l| l.front(&self.arena)) {
+                Some(order) => (order.trader, order.remaining()),
+                None => break,
+            };
+            
+            let mut remaining = size;
+            self.fill_level(Side::Ask, ask, &mut remaining, buyer, timestamp, &mut fills);
+            
+            // Reduce the buyer by what it bought; its mirror fills are dropped
+            let mut executed = Size(size.0 - remaining.0);
+            self.fill_level(Side::Bid, bid, &mut executed, buyer, timestamp, &mut Vec::new());
+        }
+        
+        for fill in &mut fills {
+            fill.price = price;
+        }
+        fills
+    }
+    
     /// Get order book snapshot (top N levels)
     pub fn snapshot(&self, depth: usize) -> OrderBookSnapshot {
-        let bids: Vec<_> = self
-            .bids
-            .iter()
-            .rev()
-            .take(depth)
-            .map(|(price, level)| (*price, level.total_size))
-            .collect();
+        // Size buffers exactly so collection never reallocates
+        let mut bids = Vec::with_capacity(depth.min(self.bids.len()));
+        bids.extend(
+            self.bids
+                .iter()
+                .rev()
+                .take(depth)
+                .map(|(price, level)| (*price, level.total_size)),
+        );
         
-        let asks: Vec<_> = self
-            .asks
-            .iter()
-            .take(depth)
-            .map(|(price, level)| (*price, level.total_size))
-            .collect();
+        let mut asks = Vec::with_capacity(depth.min(self.asks.len()));
+        asks.extend(
+            self.asks
+                .iter()
+                .take(depth)
+                .map(|(price, level)| (*price, level.total_size)),
+        );
         
         OrderBookSnapshot {
             asset: self.asset,
@@ -310,19 +582,58 @@ impl OrderBook {
         }
     }
     
-    /// Get mutable reference to bid levels
-    pub fn bids_mut(&mut self) -> &mut BTreeMap<Price, PriceLevel> {
-        &mut self.bids
+    /// Iterate over price levels on one side, best price first
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = &PriceLevel> + '_> {
+        match side {
+            Side::Bid => Box::new(self.bids.values().rev()),
+            Side::Ask => Box::new(self.asks.values()),
+        }
     }
-    
-    /// Get mutable reference to ask levels
-    pub fn asks_mut(&mut self) -> &mut BTreeMap<Price, PriceLevel> {
-        &mut self.asks
+
+    /// Iterate orders at a price level in FIFO order
+    pub fn level_orders(&self, side: Side, price: Price) -> impl Iterator<Item = &Order> {
+        let tree = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        
+        tree.get(&price)
+            .into_iter()
+            .flat_map(|level| level.iter(&self.arena))
     }
-    
-    /// Get the order index for cleanup after fills
-    pub fn order_index_mut(&mut self) -> &mut HashMap<OrderId, (Price, Side)> {
-        &mut self.order_index
+
+    /// Check if an order is still resting on the book
+    pub fn contains_order(&self, order_id: OrderId) -> bool {
+        self.order_index.contains_key(&order_id)
+    }
+
+    /// Get a resting order by ID - O(1)
+    pub fn get_order(&self, order_id: OrderId) -> Option<&Order> {
+        let location = self.order_index.get(&order_id)?;
+        self.arena.get(location.slot)
+    }
+
+    /// Number of resting orders
+    pub fn order_count(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Iterate over all resting orders (bids then asks)
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.bids
+            .values()
+            .chain(self.asks.values())
+            .flat_map(|level| level.iter(&self.arena))
+    }
+
+    /// Iterate over all resting orders on one side, in level order
+    fn side_orders(&self, side: Side) -> impl Iterator<Item = &Order> {
+        let tree = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        
+        tree.values().flat_map(|level| level.iter(&self.arena))
     }
 }
 
@@ -341,6 +652,7 @@ mod tests {
 
     #[test]
     fn test_price_level_add_order() {
+        let mut arena = OrderArena::new();
         let mut level = PriceLevel::new(Price(1_000_000));
         let order = Order::new(
             1,
@@ -352,13 +664,14 @@ mod tests {
             0,
         );
         
-        level.add_order(order);
+        level.push_back(&mut arena, order);
         assert_eq!(level.total_size, U256::from(100));
-        assert_eq!(level.orders.len(), 1);
+        assert_eq!(level.len(), 1);
     }
 
     #[test]
     fn test_price_level_remove_order() {
+        let mut arena = OrderArena::new();
         let mut level = PriceLevel::new(Price(1_000_000));
         let order = Order::new(
             1,
@@ -370,8 +683,8 @@ mod tests {
             0,
         );
         
-        level.add_order(order);
-        let removed = level.remove_order(1);
+        let slot = level.push_back(&mut arena, order);
+        let removed = level.remove(&mut arena, slot);
         
         assert!(removed.is_some());
         assert_eq!(level.total_size, U256::ZERO);
@@ -380,6 +693,7 @@ mod tests {
 
     #[test]
     fn test_price_level_fifo() {
+        let mut arena = OrderArena::new();
         let mut level = PriceLevel::new(Price(1_000_000));
         
         for i in 1..=3 {
@@ -392,17 +706,17 @@ mod tests {
                 Size(U256::from(100)),
                 i as u64,
             );
-            level.add_order(order);
+            level.push_back(&mut arena, order);
         }
         
         // First order should be ID 1
-        assert_eq!(level.front().unwrap().id, 1);
+        assert_eq!(level.front(&arena).unwrap().id, 1);
         
-        level.pop_front();
-        assert_eq!(level.front().unwrap().id, 2);
+        level.pop_front(&mut arena);
+        assert_eq!(level.front(&arena).unwrap().id, 2);
         
-        level.pop_front();
-        assert_eq!(level.front().unwrap().id, 3);
+        level.pop_front(&mut arena);
+        assert_eq!(level.front(&arena).unwrap().id, 3);
     }
 
     #[test]
@@ -696,5 +1010,26 @@ mod tests {
         assert_eq!(price, Price(1_000_000));
         assert_eq!(size, U256::from(300));  // Total size at level
     }
-}
 
+    #[test]
+    fn test_cancel_middle_keeps_fifo_and_reuses_slot() {
+        let mut book = OrderBook::new(AssetId(1));
+        let price = Price(1_000_000);
+        let ids: Vec<_> = (0..3)
+            .map(|i| book.add_limit_order(Address::ZERO, Side::Bid, price, Size(U256::from(100)), i))
+            .collect();
+        
+        book.cancel_order(ids[1]).unwrap();
+        let queue: Vec<_> = book.level_orders(Side::Bid, price).map(|o| o.id).collect();
+        assert_eq!(queue, vec![ids[0], ids[2]]);
+        assert_eq!(book.depth_at_price(price, Side::Bid), U256::from(200));
+        
+        // Freed slot is reused; new order joins the back of the queue
+        let id = book.add_limit_order(Address::ZERO, Side::Bid, price, Size(U256::from(50)), 3);
+        assert_eq!(book.arena.capacity(), 3);
+        assert_eq!(book.order_count(), 3);
+        let queue: Vec<_> = book.level_orders(Side::Bid, price).map(|o| o.id).collect();
+        assert_eq!(queue, vec![ids[0], ids[2], id]);
+        assert_eq!(book.get_order(id).unwrap().size, Size(U256::from(50)));
+    }
+}
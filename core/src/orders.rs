@@ -1,8 +1,8 @@
 use crate::types::*;
-use alloy_primitives::Address;
+use alloy_primitives::{Address, U256};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Time-in-force for orders
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -81,6 +81,92 @@ pub enum AdvancedOrderType {
         activation_price: Option<Price>,
         highest_price: Price,  // Track highest price seen
     },
+    /// Market-if-touched (no execution price) or limit-if-touched - buys
+    /// trigger when price falls to the threshold, sells when it rises to it
+    IfTouched {
+        trigger_price: Price,
+        execution_price: Option<Price>,
+    },
+    /// Exit leg attached to a bracket entry - triggers in the direction of
+    /// the position it closes
+    BracketExit {
+        leg: BracketLeg,
+        trigger_price: Price,
+        /// Book order ID of the entry
+        entry: OrderId,
+    },
+}
+
+/// Limit entry with stop-loss and take-profit exits armed as it fills
+#[derive(Debug, Clone)]
+pub struct BracketOrderRequest {
+    pub trader: Address,
+    pub asset: AssetId,
+    pub side: Side,
+    pub price: Price,
+    pub size: Size,
+    pub stop_loss: Price,
+    pub take_profit: Price,
+    pub timestamp: u64,
+}
+
+/// Exit leg of a bracket order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BracketLeg {
+    StopLoss,
+    TakeProfit,
+}
+
+/// Bracket attached to a resting or filling entry order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bracket {
+    pub user: Address,
+    pub asset: AssetId,
+    /// Side of the entry order
+    pub side: Side,
+    /// Entry order size
+    pub size: Size,
+    /// Entry size filled so far (and covered by the exit legs)
+    pub filled: Size,
+    pub stop_loss: Price,
+    pub take_profit: Price,
+    /// Advanced order IDs of the (stop-loss, take-profit) legs once created
+    pub legs: Option<(OrderId, OrderId)>,
+}
+
+/// Price feed an advanced order's trigger is evaluated against
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TriggerReference {
+    /// Price of the asset's last trade
+    LastTrade,
+    /// Oracle mark price (book mid blended with the external price)
+    #[default]
+    Mark,
+    /// Oracle index (spot reference) price
+    Index,
+}
+
+/// Reference prices of an asset at trigger evaluation
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TriggerPrices {
+    pub last_trade: Option<Price>,
+    pub mark: Option<Price>,
+    pub index: Option<Price>,
+}
+
+impl TriggerPrices {
+    /// The same price for every reference
+    pub fn uniform(price: Price) -> Self {
+        Self { last_trade: Some(price), mark: Some(price), index: Some(price) }
+    }
+    
+    pub fn get(&self, reference: TriggerReference) -> Option<Price> {
+        match reference {
+            TriggerReference::LastTrade => self.last_trade,
+            TriggerReference::Mark => self.mark,
+            TriggerReference::Index => self.index,
+        }
+    }
 }
 
 /// Advanced order
@@ -94,9 +180,66 @@ pub struct AdvancedOrder {
     pub size: Size,
     pub timestamp: u64,
     pub triggered: bool,
+    /// Price feed the trigger is evaluated against
+    #[serde(default)]
+    pub trigger_reference: TriggerReference,
+    /// Book order placed when the order triggered, while it rests
+    #[serde(default)]
+    pub spawned: Option<OrderId>,
+    /// Size filled since the order triggered
+    #[serde(default = "zero_size")]
+    pub filled: Size,
+}
+
+fn zero_size() -> Size {
+    Size(U256::ZERO)
+}
+
+impl AdvancedOrder {
+    /// Size still to be filled
+    pub fn remaining(&self) -> Size {
+        Size(self.size.0.saturating_sub(self.filled.0))
+    }
+    
+    pub fn status(&self) -> AdvancedOrderStatus {
+        if !self.triggered {
+            AdvancedOrderStatus::Working
+        } else if self.filled.0.is_zero() {
+            AdvancedOrderStatus::Triggered
+        } else {
+            AdvancedOrderStatus::PartiallyFilled
+        }
+    }
+}
+
+/// Lifecycle of an advanced order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AdvancedOrderStatus {
+    /// Waiting for its trigger
+    Working,
+    /// Triggered, nothing filled yet
+    Triggered,
+    /// Triggered, and its book order rests partially filled
+    PartiallyFilled,
+    /// Fully filled, cancelled, or its book order closed
+    Done,
+}
+
+/// Order manager state besides the orders themselves, persisted with them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrderManagerState {
+    pub next_id: OrderId,
+    /// Highest price seen per asset, sorted by asset
+    pub highest_prices: Vec<(AssetId, Price)>,
+    /// Brackets by entry (asset, book order ID), sorted
+    pub brackets: Vec<(AssetId, OrderId, Bracket)>,
+    /// Last trade price per asset, sorted by asset
+    #[serde(default)]
+    pub last_trade_prices: Vec<(AssetId, Price)>,
 }
 
 /// Order manager for advanced order types
+#[derive(Clone)]
 pub struct OrderManager {
     /// Pending advanced orders
     advanced_orders: HashMap<OrderId, AdvancedOrder>,
@@ -104,6 +247,19 @@ pub struct OrderManager {
     next_id: OrderId,
     /// Track highest prices for trailing stops
     highest_prices: HashMap<AssetId, Price>,
+    /// Last trade price per asset, for `TriggerReference::LastTrade`
+    last_trade_prices: HashMap<AssetId, Price>,
+    /// Brackets by entry (asset, book order ID)
+    brackets: HashMap<(AssetId, OrderId), Bracket>,
+    /// One-cancels-other links between bracket exit legs
+    oco_links: HashMap<OrderId, OrderId>,
+    /// Resting book order (asset, book order ID) -> advanced order it was
+    /// spawned by
+    spawned: HashMap<(AssetId, OrderId), OrderId>,
+    /// Orders inserted, updated or removed since the last `take_dirty`
+    dirty: BTreeSet<OrderId>,
+    /// Whether `state()` changed since the last `take_dirty`
+    state_dirty: bool,
 }
 
 impl OrderManager {
@@ -112,9 +268,77 @@ impl OrderManager {
             advanced_orders: HashMap::new(),
             next_id: 1,
             highest_prices: HashMap::new(),
+            last_trade_prices: HashMap::new(),
+            brackets: HashMap::new(),
+            oco_links: HashMap::new(),
+            spawned: HashMap::new(),
+            dirty: BTreeSet::new(),
+            state_dirty: false,
+        }
+    }
+    
+    /// Rebuild a manager from persisted orders and state. Bracket exit legs
+    /// still paired in `orders` are linked one-cancels-other again, and
+    /// triggered orders to their resting book orders.
+    pub fn restore(orders: Vec<AdvancedOrder>, state: OrderManagerState) -> Self {
+        let mut manager = Self::new();
+        manager.next_id = state.next_id.max(1);
+        manager.highest_prices = state.highest_prices.into_iter().collect();
+        manager.last_trade_prices = state.last_trade_prices.into_iter().collect();
+        manager.brackets = state
+            .brackets
+            .into_iter()
+            .map(|(asset, entry, bracket)| ((asset, entry), bracket))
+            .collect();
+        
+        let mut legs: BTreeMap<(u32, OrderId), Vec<OrderId>> = BTreeMap::new();
+        for order in orders {
+            if let AdvancedOrderType::BracketExit { entry, .. } = order.order_type {
+                legs.entry((order.asset.0, entry)).or_default().push(order.id);
+            }
+            if let Some(book_order) = order.spawned {
+                manager.spawned.insert((order.asset, book_order), order.id);
+            }
+            manager.next_id = manager.next_id.max(order.id + 1);
+            manager.advanced_orders.insert(order.id, order);
+        }
+        for pair in legs.values() {
+            if let [a, b] = pair[..] {
+                manager.oco_links.insert(a, b);
+                manager.oco_links.insert(b, a);
+            }
+        }
+        manager
+    }
+    
+    /// Persistable state besides the orders
+    pub fn state(&self) -> OrderManagerState {
+        let sorted = |prices: &HashMap<AssetId, Price>| {
+            let mut prices: Vec<_> = prices.iter().map(|(a, p)| (*a, *p)).collect();
+            prices.sort_by_key(|(asset, _)| asset.0);
+            prices
+        };
+        let mut brackets: Vec<_> = self
+            .brackets
+            .iter()
+            .map(|((asset, entry), bracket)| (*asset, *entry, bracket.clone()))
+            .collect();
+        brackets.sort_by_key(|(asset, entry, _)| (asset.0, *entry));
+        OrderManagerState {
+            next_id: self.next_id,
+            highest_prices: sorted(&self.highest_prices),
+            brackets,
+            last_trade_prices: sorted(&self.last_trade_prices),
         }
     }
     
+    /// Take the IDs of orders changed since the last call (an ID with no
+    /// order left was removed), and whether `state()` changed
+    pub fn take_dirty(&mut self) -> (Vec<OrderId>, bool) {
+        let ids = std::mem::take(&mut self.dirty).into_iter().collect();
+        (ids, std::mem::take(&mut self.state_dirty))
+    }
+    
     /// Check if order params are valid for the given position
     pub fn validate_order_params(
         &self,
@@ -171,25 +395,11 @@ impl OrderManager {
         execution_price: Option<Price>,
         timestamp: u64,
     ) -> OrderId {
-        let id = self.next_id;
-        self.next_id += 1;
-        
-        let order = AdvancedOrder {
-            id,
-            user,
-            asset,
-            order_type: AdvancedOrderType::StopLoss {
-                trigger_price,
-                execution_price,
-            },
-            side,
-            size,
-            timestamp,
-            triggered: false,
+        let order_type = AdvancedOrderType::StopLoss {
+            trigger_price,
+            execution_price,
         };
-        
-        self.advanced_orders.insert(id, order);
-        id
+        self.insert(user, asset, order_type, side, size, timestamp)
     }
     
     /// Place take-profit order
@@ -203,25 +413,11 @@ impl OrderManager {
         execution_price: Option<Price>,
         timestamp: u64,
     ) -> OrderId {
-        let id = self.next_id;
-        self.next_id += 1;
-        
-        let order = AdvancedOrder {
-            id,
-            user,
-            asset,
-            order_type: AdvancedOrderType::TakeProfit {
-                trigger_price,
-                execution_price,
-            },
-            side,
-            size,
-            timestamp,
-            triggered: false,
+        let order_type = AdvancedOrderType::TakeProfit {
+            trigger_price,
+            execution_price,
         };
-        
-        self.advanced_orders.insert(id, order);
-        id
+        self.insert(user, asset, order_type, side, size, timestamp)
     }
     
     /// Place trailing stop order
@@ -240,47 +436,223 @@ impl OrderManager {
             return Err(anyhow!("Invalid callback rate, must be between 0 and 1"));
         }
         
+        let order_type = AdvancedOrderType::TrailingStop {
+            callback_rate,
+            activation_price,
+            highest_price: current_price,
+        };
+        Ok(self.insert(user, asset, order_type, side, size, timestamp))
+    }
+    
+    /// Place market-if-touched (`execution_price` None) or limit-if-touched order
+    #[allow(clippy::too_many_arguments)]
+    pub fn place_if_touched(
+        &mut self,
+        user: Address,
+        asset: AssetId,
+        side: Side,
+        size: Size,
+        trigger_price: Price,
+        execution_price: Option<Price>,
+        timestamp: u64,
+    ) -> OrderId {
+        let order_type = AdvancedOrderType::IfTouched {
+            trigger_price,
+            execution_price,
+        };
+        self.insert(user, asset, order_type, side, size, timestamp)
+    }
+    
+    /// Check that bracket exits sit on the right sides of an entry
+    pub fn validate_bracket(side: Side, stop_loss: Price, take_profit: Price) -> Result<()> {
+        let valid = match side {
+            Side::Bid => stop_loss < take_profit,
+            Side::Ask => stop_loss > take_profit,
+        };
+        if !valid {
+            return Err(anyhow!("Bracket stop-loss and take-profit are on the wrong sides"));
+        }
+        Ok(())
+    }
+    
+    /// Attach stop-loss and take-profit exits to an entry order. The legs
+    /// are created as the entry fills.
+    pub fn attach_bracket(
+        &mut self,
+        user: Address,
+        asset: AssetId,
+        entry: OrderId,
+        side: Side,
+        size: Size,
+        exits: (Price, Price),
+    ) -> Result<()> {
+        let (stop_loss, take_profit) = exits;
+        Self::validate_bracket(side, stop_loss, take_profit)?;
+        if self.brackets.contains_key(&(asset, entry)) {
+            return Err(anyhow!("Order already has a bracket"));
+        }
+        
+        self.state_dirty = true;
+        self.brackets.insert((asset, entry), Bracket {
+            user,
+            asset,
+            side,
+            size,
+            filled: Size(alloy_primitives::U256::ZERO),
+            stop_loss,
+            take_profit,
+            legs: None,
+        });
+        Ok(())
+    }
+    
+    /// Get the bracket attached to an entry order
+    pub fn get_bracket(&self, asset: AssetId, entry: OrderId) -> Option<&Bracket> {
+        self.brackets.get(&(asset, entry))
+    }
+    
+    /// Record a fill of a bracket entry: the first fill creates both exit
+    /// legs, later fills grow them to the filled size. The bracket is
+    /// released once the entry is fully filled. Returns the leg IDs.
+    pub fn on_entry_fill(&mut self, asset: AssetId, entry: OrderId, size: Size, timestamp: u64) -> Option<(OrderId, OrderId)> {
+        let bracket = self.brackets.get_mut(&(asset, entry))?;
+        bracket.filled.0 += size.0;
+        let bracket = bracket.clone();
+        self.state_dirty = true;
+        
+        let legs = match bracket.legs {
+            Some((stop, profit)) => {
+                for id in [stop, profit] {
+                    if let Some(order) = self.advanced_orders.get_mut(&id) {
+                        order.size = bracket.filled;
+                        self.dirty.insert(id);
+                    }
+                }
+                (stop, profit)
+            }
+            None => {
+                let exit_side = match bracket.side {
+                    Side::Bid => Side::Ask,
+                    Side::Ask => Side::Bid,
+                };
+                let mut leg = |leg, trigger_price| {
+                    let order_type = AdvancedOrderType::BracketExit { leg, trigger_price, entry };
+                    self.insert(bracket.user, asset, order_type, exit_side, bracket.filled, timestamp)
+                };
+                let stop = leg(BracketLeg::StopLoss, bracket.stop_loss);
+                let profit = leg(BracketLeg::TakeProfit, bracket.take_profit);
+                self.oco_links.insert(stop, profit);
+                self.oco_links.insert(profit, stop);
+                if let Some(b) = self.brackets.get_mut(&(asset, entry)) {
+                    b.legs = Some((stop, profit));
+                }
+                (stop, profit)
+            }
+        };
+        
+        if bracket.filled.0 >= bracket.size.0 {
+            self.brackets.remove(&(asset, entry));
+        }
+        Some(legs)
+    }
+    
+    /// Release the bracket of a cancelled or expired entry. Legs already
+    /// covering filled size stay active.
+    pub fn on_entry_closed(&mut self, asset: AssetId, entry: OrderId) {
+        if self.brackets.remove(&(asset, entry)).is_some() {
+            self.state_dirty = true;
+        }
+    }
+    
+    fn insert(
+        &mut self,
+        user: Address,
+        asset: AssetId,
+        order_type: AdvancedOrderType,
+        side: Side,
+        size: Size,
+        timestamp: u64,
+    ) -> OrderId {
         let id = self.next_id;
         self.next_id += 1;
+        self.dirty.insert(id);
+        self.state_dirty = true;
         
-        let order = AdvancedOrder {
+        self.advanced_orders.insert(id, AdvancedOrder {
             id,
             user,
             asset,
-            order_type: AdvancedOrderType::TrailingStop {
-                callback_rate,
-                activation_price,
-                highest_price: current_price,
-            },
+            order_type,
             side,
             size,
             timestamp,
             triggered: false,
-        };
-        
-        self.advanced_orders.insert(id, order);
-        Ok(id)
+            trigger_reference: TriggerReference::default(),
+            spawned: None,
+            filled: zero_size(),
+        });
+        id
+    }
+    
+    /// Record a trade, the reference of `TriggerReference::LastTrade` triggers
+    pub fn record_trade(&mut self, asset: AssetId, price: Price) {
+        if self.last_trade_prices.insert(asset, price) != Some(price) {
+            self.state_dirty = true;
+        }
+    }
+    
+    /// Last recorded trade price of an asset
+    pub fn last_trade_price(&self, asset: AssetId) -> Option<Price> {
+        self.last_trade_prices.get(&asset).copied()
+    }
+    
+    /// Choose the price feed an order's trigger is evaluated against
+    pub fn set_trigger_reference(&mut self, id: OrderId, reference: TriggerReference) -> Result<()> {
+        let order = self.advanced_orders.get_mut(&id).ok_or_else(|| anyhow!("Order not found"))?;
+        if order.triggered {
+            return Err(anyhow!("Order already triggered"));
+        }
+        order.trigger_reference = reference;
+        self.dirty.insert(id);
+        Ok(())
     }
     
-    /// Update price and check if any orders should be triggered
+    /// Update price and check if any orders should be triggered, with
+    /// `current_price` as every reference price
     pub fn check_triggers(
         &mut self,
         asset: AssetId,
         current_price: Price,
     ) -> Vec<OrderId> {
+        self.check_triggers_at(asset, &TriggerPrices::uniform(current_price))
+    }
+    
+    /// Check each order of `asset` against the price of its trigger
+    /// reference. Orders whose reference price is unavailable are skipped.
+    pub fn check_triggers_at(&mut self, asset: AssetId, prices: &TriggerPrices) -> Vec<OrderId> {
         let mut triggered = Vec::new();
         
         // Update highest price for trailing stops
-        let highest = self.highest_prices.entry(asset).or_insert(current_price);
-        if current_price > *highest {
-            *highest = current_price;
+        if let Some(mark) = prices.mark {
+            let highest = self.highest_prices.entry(asset).or_insert_with(|| {
+                self.state_dirty = true;
+                mark
+            });
+            if mark > *highest {
+                *highest = mark;
+                self.state_dirty = true;
+            }
         }
         
         for (id, order) in &mut self.advanced_orders {
             if order.asset != asset || order.triggered {
                 continue;
             }
+            let Some(current_price) = prices.get(order.trigger_reference) else {
+                continue;
+            };
             
+            let mut changed = false;
             let should_trigger = match &mut order.order_type {
                 AdvancedOrderType::StopLoss { trigger_price, .. } => {
                     // Stop-loss triggers when price drops below trigger
@@ -294,6 +666,7 @@ impl OrderManager {
                     // Update highest price for this order
                     if current_price > *highest_price {
                         *highest_price = current_price;
+                        changed = true;
                     }
                     
                     // Check if activated
@@ -302,6 +675,7 @@ impl OrderManager {
                         // Once activated, clear the activation price so it stays activated
                         if activated {
                             *activation_price = None;
+                            changed = true;
                         }
                         activated
                     } else {
@@ -319,11 +693,30 @@ impl OrderManager {
                         current_price <= trigger_price
                     }
                 }
+                AdvancedOrderType::IfTouched { trigger_price, .. } => match order.side {
+                    Side::Bid => current_price <= *trigger_price,
+                    Side::Ask => current_price >= *trigger_price,
+                },
+                AdvancedOrderType::BracketExit { leg, trigger_price, .. } => {
+                    // Sells close longs, buys close shorts
+                    match (order.side, *leg) {
+                        (Side::Ask, BracketLeg::StopLoss) | (Side::Bid, BracketLeg::TakeProfit) => {
+                            current_price <= *trigger_price
+                        }
+                        (Side::Ask, BracketLeg::TakeProfit) | (Side::Bid, BracketLeg::StopLoss) => {
+                            current_price >= *trigger_price
+                        }
+                    }
+                }
             };
             
             if should_trigger {
                 order.triggered = true;
                 triggered.push(*id);
+                changed = true;
+            }
+            if changed {
+                self.dirty.insert(*id);
             }
         }
         
@@ -335,15 +728,47 @@ impl OrderManager {
         self.advanced_orders.get(&id)
     }
     
-    /// Cancel order
+    /// Status of an order (None for IDs never assigned)
+    pub fn status(&self, id: OrderId) -> Option<AdvancedOrderStatus> {
+        match self.advanced_orders.get(&id) {
+            Some(order) => Some(order.status()),
+            None if id > 0 && id < self.next_id => Some(AdvancedOrderStatus::Done),
+            None => None,
+        }
+    }
+    
+    /// Cancel order (and its one-cancels-other sibling). A book order it
+    /// spawned is unlinked; cancelling it is up to the caller.
     pub fn cancel_order(&mut self, id: OrderId) -> Result<()> {
-        if self.advanced_orders.remove(&id).is_some() {
+        if let Some(order) = self.advanced_orders.remove(&id) {
+            if let Some(book_order) = order.spawned {
+                self.spawned.remove(&(order.asset, book_order));
+            }
+            self.dirty.insert(id);
+            self.cancel_sibling(id);
             Ok(())
         } else {
             Err(anyhow!("Order not found"))
         }
     }
     
+    /// Drop the other leg of a bracket, and the bracket itself so later
+    /// entry fills do not re-arm it
+    fn cancel_sibling(&mut self, id: OrderId) {
+        let order = match self.oco_links.remove(&id) {
+            Some(sibling) => {
+                self.oco_links.remove(&sibling);
+                self.dirty.insert(sibling);
+                self.advanced_orders.remove(&sibling)
+            }
+            None => None,
+        };
+        if let Some(AdvancedOrderType::BracketExit { entry, .. }) = order.map(|o| o.order_type) {
+            self.brackets.retain(|(_, e), b| *e != entry || b.legs.is_none_or(|(s, p)| s != id && p != id));
+            self.state_dirty = true;
+        }
+    }
+    
     /// Get all orders for user
     pub fn get_user_orders(&self, user: &Address) -> Vec<&AdvancedOrder> {
         self.advanced_orders.values()
@@ -365,9 +790,66 @@ impl OrderManager {
             .collect()
     }
     
-    /// Remove triggered order after execution
+    /// Remove triggered order after execution (cancels its bracket sibling)
     pub fn remove_triggered(&mut self, id: OrderId) {
         self.advanced_orders.remove(&id);
+        self.dirty.insert(id);
+        self.cancel_sibling(id);
+    }
+    
+    /// Record the execution of a triggered order: `filled` traded
+    /// immediately and `resting` is the book order left with the remainder,
+    /// if any. The order stays tracked until its book order is done.
+    pub fn on_triggered_executed(&mut self, id: OrderId, filled: Size, resting: Option<OrderId>) {
+        self.cancel_sibling(id);
+        self.dirty.insert(id);
+        let Some(order) = self.advanced_orders.get_mut(&id) else {
+            return;
+        };
+        order.filled.0 += filled.0;
+        match resting {
+            Some(book_order) if order.filled.0 < order.size.0 => {
+                order.spawned = Some(book_order);
+                self.spawned.insert((order.asset, book_order), id);
+            }
+            _ => {
+                self.advanced_orders.remove(&id);
+            }
+        }
+    }
+    
+    /// Record a fill of a book order; credits the advanced order that
+    /// spawned it, which is done once fully filled
+    pub fn on_spawned_fill(&mut self, asset: AssetId, book_order: OrderId, size: Size) {
+        let Some(&id) = self.spawned.get(&(asset, book_order)) else {
+            return;
+        };
+        self.dirty.insert(id);
+        let done = match self.advanced_orders.get_mut(&id) {
+            Some(order) => {
+                order.filled.0 += size.0;
+                order.filled.0 >= order.size.0
+            }
+            None => true,
+        };
+        if done {
+            self.spawned.remove(&(asset, book_order));
+            self.advanced_orders.remove(&id);
+        }
+    }
+    
+    /// Record that a book order left the book unfilled (cancelled or
+    /// expired); the advanced order that spawned it is done
+    pub fn on_spawned_closed(&mut self, asset: AssetId, book_order: OrderId) {
+        if let Some(id) = self.spawned.remove(&(asset, book_order)) {
+            self.advanced_orders.remove(&id);
+            self.dirty.insert(id);
+        }
+    }
+    
+    /// Advanced order that spawned a resting book order
+    pub fn spawned_by(&self, asset: AssetId, book_order: OrderId) -> Option<OrderId> {
+        self.spawned.get(&(asset, book_order)).copied()
     }
     
     /// Count active orders
@@ -653,6 +1135,33 @@ mod tests {
         assert_eq!(triggered.len(), 0);
     }
 
+    #[test]
+    fn test_spawned_order_lifecycle() {
+        let mut manager = OrderManager::new();
+        let user = Address::ZERO;
+        let asset = AssetId(1);
+        
+        let id = manager.place_stop_loss(user, asset, Side::Ask, Size(U256::from(10)), Price(100), Some(Price(95)), 0);
+        let closed = manager.place_stop_loss(user, asset, Side::Ask, Size(U256::from(5)), Price(100), Some(Price(95)), 0);
+        manager.check_triggers(asset, Price(99));
+        assert_eq!(manager.status(id), Some(AdvancedOrderStatus::Triggered));
+        
+        manager.on_triggered_executed(id, Size(U256::from(4)), Some(7));
+        manager.on_triggered_executed(closed, Size(U256::ZERO), Some(8));
+        assert_eq!(manager.status(id), Some(AdvancedOrderStatus::PartiallyFilled));
+        assert_eq!(manager.get_order(id).unwrap().remaining(), Size(U256::from(6)));
+        
+        // Restored links keep crediting fills
+        let orders = manager.advanced_orders.values().cloned().collect();
+        let mut manager = OrderManager::restore(orders, manager.state());
+        manager.on_spawned_fill(asset, 7, Size(U256::from(6)));
+        manager.on_spawned_closed(asset, 8);
+        assert_eq!(manager.status(id), Some(AdvancedOrderStatus::Done));
+        assert_eq!(manager.status(closed), Some(AdvancedOrderStatus::Done));
+        assert_eq!(manager.status(99), None);
+        assert_eq!(manager.count_orders(), 0);
+    }
+    
     #[test]
     fn test_time_in_force_gtc_default() {
         let tif = TimeInForce::default();
@@ -733,4 +1242,70 @@ mod tests {
         assert!(manager.is_fok(&TimeInForce::FOK));
         assert!(!manager.is_fok(&TimeInForce::GTC));
     }
+
+    #[test]
+    fn test_if_touched_triggers_by_side() {
+        let mut manager = OrderManager::new();
+        let size = Size(U256::from(1));
+        
+        let buy = manager.place_if_touched(Address::ZERO, AssetId(1), Side::Bid, size, Price::from_float(95.0), None, 0);
+        let sell = manager.place_if_touched(
+            Address::ZERO, AssetId(1), Side::Ask, size, Price::from_float(105.0), Some(Price::from_float(104.0)), 0,
+        );
+        
+        assert!(manager.check_triggers(AssetId(1), Price::from_float(100.0)).is_empty());
+        assert_eq!(manager.check_triggers(AssetId(1), Price::from_float(95.0)), vec![buy]);
+        assert_eq!(manager.check_triggers(AssetId(1), Price::from_float(106.0)), vec![sell]);
+    }
+
+    #[test]
+    fn test_trigger_reference_selects_price() {
+        let mut manager = OrderManager::new();
+        let asset = AssetId(1);
+        let stop = |manager: &mut OrderManager| {
+            manager.place_stop_loss(Address::ZERO, asset, Side::Ask, Size(U256::from(1)), Price::from_float(95.0), None, 0)
+        };
+        let on_mark = stop(&mut manager);
+        let on_trade = stop(&mut manager);
+        let on_index = stop(&mut manager);
+        manager.set_trigger_reference(on_trade, TriggerReference::LastTrade).unwrap();
+        manager.set_trigger_reference(on_index, TriggerReference::Index).unwrap();
+        assert_eq!(manager.get_order(on_mark).unwrap().trigger_reference, TriggerReference::Mark);
+        
+        // A print below the stop fires only the last-trade order
+        let prices = TriggerPrices {
+            last_trade: Some(Price::from_float(94.0)),
+            mark: Some(Price::from_float(96.0)),
+            index: None,
+        };
+        assert_eq!(manager.check_triggers_at(asset, &prices), vec![on_trade]);
+        assert!(manager.set_trigger_reference(on_trade, TriggerReference::Mark).is_err());
+        
+        let prices = TriggerPrices { index: Some(Price::from_float(95.0)), ..prices };
+        assert_eq!(manager.check_triggers_at(asset, &prices), vec![on_index]);
+    }
+
+    #[test]
+    fn test_bracket_legs_follow_entry_fills() {
+        let mut manager = OrderManager::new();
+        let (asset, entry) = (AssetId(1), 7);
+        let exits = (Price::from_float(110.0), Price::from_float(90.0));
+        
+        // Short entry: stop above, target below
+        assert!(manager.attach_bracket(Address::ZERO, asset, entry, Side::Bid, Size(U256::from(10)), exits).is_err());
+        manager.attach_bracket(Address::ZERO, asset, entry, Side::Ask, Size(U256::from(10)), exits).unwrap();
+        assert_eq!(manager.count_orders(), 0);
+        
+        let (stop, profit) = manager.on_entry_fill(asset, entry, Size(U256::from(4)), 1).unwrap();
+        assert_eq!(manager.on_entry_fill(asset, entry, Size(U256::from(6)), 2), Some((stop, profit)));
+        assert_eq!(manager.get_order(stop).unwrap().size, Size(U256::from(10)));
+        assert_eq!(manager.get_order(profit).unwrap().side, Side::Bid);
+        // Fully filled: the bracket is released, the legs stay
+        assert!(manager.get_bracket(asset, entry).is_none());
+        
+        assert!(manager.check_triggers(asset, Price::from_float(100.0)).is_empty());
+        assert_eq!(manager.check_triggers(asset, Price::from_float(111.0)), vec![stop]);
+        manager.remove_triggered(stop);
+        assert_eq!(manager.count_orders(), 0);
+    }
 }
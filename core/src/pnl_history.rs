@@ -0,0 +1,224 @@
+use crate::margin::MarginEngine;
+use crate::storage::CoreStorage;
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Position state captured in an account snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionSnapshot {
+    pub asset: AssetId,
+    pub size: i64,
+    pub entry_price: Price,
+    /// Mark used for unrealized PnL (None = no mark, last known PnL kept)
+    pub mark_price: Option<Price>,
+    pub realized_pnl: i64,
+    pub unrealized_pnl: i64,
+}
+
+/// Per-account position and PnL state at the end of an epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub user: Address,
+    pub epoch: u64,
+    pub timestamp: u64,
+    /// Deposited collateral
+    pub collateral: U256,
+    pub realized_pnl: i64,
+    pub unrealized_pnl: i64,
+    /// Positions sorted by asset
+    pub positions: Vec<PositionSnapshot>,
+}
+
+impl AccountSnapshot {
+    /// Capture an account's positions marked at `mark_prices`
+    pub fn capture(
+        margin_engine: &MarginEngine,
+        user: Address,
+        epoch: u64,
+        timestamp: u64,
+        mark_prices: &HashMap<AssetId, Price>,
+    ) -> Self {
+        let mut positions: Vec<_> = margin_engine
+            .get_user_positions(&user)
+            .into_iter()
+            .map(|position| {
+                let mark_price = mark_prices.get(&position.asset).copied();
+                let unrealized_pnl = match mark_price {
+                    Some(mark) => margin_engine.calculate_unrealized_pnl(position, mark),
+                    None => position.unrealized_pnl,
+                };
+                PositionSnapshot {
+                    asset: position.asset,
+                    size: position.size,
+                    entry_price: position.entry_price,
+                    mark_price,
+                    realized_pnl: position.realized_pnl,
+                    unrealized_pnl,
+                }
+            })
+            .collect();
+        positions.sort_by_key(|p| p.asset.0);
+
+        Self {
+            user,
+            epoch,
+            timestamp,
+            collateral: margin_engine.get_account_equity(&user).unwrap_or(U256::ZERO),
+            realized_pnl: positions.iter().map(|p| p.realized_pnl).sum(),
+            unrealized_pnl: positions.iter().map(|p| p.unrealized_pnl).sum(),
+            positions,
+        }
+    }
+
+    /// Realized plus unrealized PnL
+    pub fn total_pnl(&self) -> i64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+
+    /// Net asset value: collateral plus unrealized PnL
+    pub fn nav(&self) -> i128 {
+        self.collateral.saturating_to::<u128>() as i128 + self.unrealized_pnl as i128
+    }
+}
+
+/// Query layer over persisted account snapshots
+pub struct PnlHistory {
+    storage: Arc<CoreStorage>,
+}
+
+impl PnlHistory {
+    /// Create a new PnL history manager
+    pub fn new(storage: Arc<CoreStorage>) -> Self {
+        Self { storage }
+    }
+
+    /// Get all snapshots for a user, oldest first
+    pub fn get_snapshots(&self, user: &Address) -> Result<Vec<AccountSnapshot>> {
+        self.storage.load_account_snapshots(user)
+    }
+
+    /// Latest snapshot taken at or before `timestamp`
+    pub fn snapshot_at(&self, user: &Address, timestamp: u64) -> Result<Option<AccountSnapshot>> {
+        Ok(self
+            .get_snapshots(user)?
+            .into_iter()
+            .take_while(|s| s.timestamp <= timestamp)
+            .last())
+    }
+
+    /// PnL accrued over `[now - window, now]`, e.g. the last 30 days.
+    ///
+    /// Uses the latest snapshot at or before each bound; an account with no
+    /// snapshot before the window start counts from zero.
+    pub fn pnl_over(&self, user: &Address, window: u64, now: u64) -> Result<i64> {
+        let snapshots = self.get_snapshots(user)?;
+        let at = |ts: u64| {
+            snapshots
+                .iter()
+                .take_while(|s| s.timestamp <= ts)
+                .last()
+                .map(|s| s.total_pnl())
+                .unwrap_or(0)
+        };
+
+        Ok(at(now) - at(now.saturating_sub(window)))
+    }
+
+    /// NAV per snapshot as (timestamp, nav), oldest first
+    pub fn nav_history(&self, user: &Address) -> Result<Vec<(u64, i128)>> {
+        Ok(self
+            .get_snapshots(user)?
+            .iter()
+            .map(|s| (s.timestamp, s.nav()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::margin::MarginConfig;
+    use crate::storage::StorageBatch;
+
+    fn temp_db_path() -> String {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::time::{SystemTime, UNIX_EPOCH};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
+        format!("/tmp/openliquid_test_pnl_history_{}_{}", timestamp, counter)
+    }
+
+    fn snapshot(user: Address, epoch: u64, realized_pnl: i64, unrealized_pnl: i64) -> AccountSnapshot {
+        AccountSnapshot {
+            user,
+            epoch,
+            timestamp: epoch * 100,
+            collateral: U256::from(1000),
+            realized_pnl,
+            unrealized_pnl,
+            positions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_capture_marks_positions() {
+        let mut margin = MarginEngine::new(MarginConfig::default());
+        let user = Address::from([1u8; 20]);
+        margin.deposit(user, AssetId(0), U256::from(10_000)).unwrap();
+        margin.update_position(user, AssetId(2), -5, Price(2_000_000), 0).unwrap();
+        margin.update_position(user, AssetId(1), 10, Price(1_000_000), 0).unwrap();
+
+        let marks = HashMap::from([(AssetId(1), Price(1_500_000))]);
+        let snap = AccountSnapshot::capture(&margin, user, 3, 300, &marks);
+
+        assert_eq!(snap.positions.len(), 2);
+        assert_eq!(snap.positions[0].asset, AssetId(1));
+        assert_eq!(snap.positions[0].unrealized_pnl, 5_000_000);
+        // No mark for asset 2: last known PnL
+        assert_eq!(snap.positions[1].mark_price, None);
+        assert_eq!(snap.unrealized_pnl, 5_000_000);
+        assert_eq!(snap.collateral, U256::from(10_000));
+    }
+
+    #[test]
+    fn test_pnl_over_window() {
+        let path = temp_db_path();
+        let storage = Arc::new(CoreStorage::new(&path).unwrap());
+        let user = Address::from([1u8; 20]);
+        let other = Address::from([2u8; 20]);
+
+        let mut batch = StorageBatch::new();
+        for snap in [
+            snapshot(user, 1, 10, 0),
+            snapshot(user, 2, 10, 5),
+            snapshot(user, 10, 40, -5),
+            snapshot(other, 2, 999, 0),
+        ] {
+            batch.put_account_snapshot(&snap).unwrap();
+        }
+        storage.write_batch(batch).unwrap();
+
+        let history = PnlHistory::new(storage);
+        let snaps = history.get_snapshots(&user).unwrap();
+        assert_eq!(snaps.iter().map(|s| s.epoch).collect::<Vec<_>>(), vec![1, 2, 10]);
+
+        // (40 - 5) - (10 + 5)
+        assert_eq!(history.pnl_over(&user, 750, 1000).unwrap(), 20);
+        // Window starts before the first snapshot
+        assert_eq!(history.pnl_over(&user, 1000, 1000).unwrap(), 35);
+        assert_eq!(history.snapshot_at(&user, 250).unwrap().unwrap().epoch, 2);
+        assert!(history.snapshot_at(&user, 50).unwrap().is_none());
+        assert_eq!(history.nav_history(&user).unwrap()[2], (1000, 995));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::orderbook::{OrderBook, OrderBookSnapshot};
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+
+/// Balances by (user, asset)
+pub type BalanceMap = HashMap<(Address, AssetId), U256>;
+
+/// Positions by (user, asset)
+pub type PositionMap = HashMap<(Address, AssetId), Position>;
+
+/// Snapshot publishing configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotConfig {
+    /// Committed heights kept for queries
+    pub retained_heights: usize,
+    /// Price levels kept per side of each book
+    pub book_depth: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            retained_heights: 16,
+            book_depth: 50,
+        }
+    }
+}
+
+/// Immutable view of the core state at a committed block height
+///
+/// Balances and positions share their maps with the live state machine,
+/// which copies a map only when it writes to it while a snapshot still
+/// holds it. Reads never touch the state machine.
+#[derive(Debug, Clone)]
+pub struct StateSnapshot {
+    pub height: u64,
+    /// Block time the snapshot was taken at
+    pub timestamp: u64,
+    balances: Arc<BalanceMap>,
+    positions: Arc<PositionMap>,
+    books: HashMap<AssetId, Arc<OrderBookSnapshot>>,
+}
+
+impl StateSnapshot {
+    /// Capture a snapshot from shared state maps and the live books
+    pub fn capture<'a>(
+        height: u64,
+        timestamp: u64,
+        balances: Arc<BalanceMap>,
+        positions: Arc<PositionMap>,
+        books: impl IntoIterator<Item = &'a OrderBook>,
+        book_depth: usize,
+    ) -> Self {
+        let books = books
+            .into_iter()
+            .map(|book| (book.asset, Arc::new(book.snapshot(book_depth))))
+            .collect();
+        Self {
+            height,
+            timestamp,
+            balances,
+            positions,
+            books,
+        }
+    }
+
+    /// Get user balance
+    pub fn get_balance(&self, user: &Address, asset: AssetId) -> U256 {
+        self.balances.get(&(*user, asset)).copied().unwrap_or(U256::ZERO)
+    }
+
+    /// Get user position
+    pub fn get_position(&self, user: &Address, asset: AssetId) -> Option<&Position> {
+        self.positions.get(&(*user, asset))
+    }
+
+    /// Get a user's open positions, sorted by asset ID
+    pub fn get_user_positions(&self, user: &Address) -> Vec<&Position> {
+        let mut positions: Vec<_> = self
+            .positions
+            .values()
+            .filter(|p| p.user == *user && p.size != 0)
+            .collect();
+        positions.sort_by_key(|p| p.asset.0);
+        positions
+    }
+
+    /// Get the book snapshot of `asset`, truncated to `depth` levels
+    pub fn get_book(&self, asset: AssetId, depth: usize) -> Option<OrderBookSnapshot> {
+        self.books.get(&asset).map(|book| OrderBookSnapshot {
+            asset,
+            bids: book.bids.iter().take(depth).copied().collect(),
+            asks: book.asks.iter().take(depth).copied().collect(),
+        })
+    }
+
+    /// Assets with a book in the snapshot
+    pub fn assets(&self) -> Vec<AssetId> {
+        let mut assets: Vec<_> = self.books.keys().copied().collect();
+        assets.sort_by_key(|asset| asset.0);
+        assets
+    }
+}
+
+/// Versioned store of committed state snapshots
+///
+/// Handles are cheap to clone, so the API layer keeps its own and reads
+/// consistent block-height views without borrowing the state machine.
+/// The lock is held only to swap or clone an `Arc`, never while matching.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotStore {
+    snapshots: Arc<RwLock<VecDeque<Arc<StateSnapshot>>>>,
+    config: SnapshotConfig,
+}
+
+impl SnapshotStore {
+    pub fn new(config: SnapshotConfig) -> Self {
+        Self {
+            snapshots: Arc::new(RwLock::new(VecDeque::new())),
+            config,
+        }
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> SnapshotConfig {
+        self.config
+    }
+
+    /// Publish the snapshot of a newly committed height, replacing any
+    /// earlier one at the same or a higher height and evicting the oldest
+    /// beyond the retention limit
+    pub fn publish(&self, snapshot: StateSnapshot) {
+        let mut snapshots = self.snapshots.write().unwrap_or_else(|e| e.into_inner());
+        while snapshots.back().is_some_and(|s| s.height >= snapshot.height) {
+            snapshots.pop_back();
+        }
+        snapshots.push_back(Arc::new(snapshot));
+        while snapshots.len() > self.config.retained_heights.max(1) {
+            snapshots.pop_front();
+        }
+    }
+
+    /// Latest committed snapshot
+    pub fn latest(&self) -> Option<Arc<StateSnapshot>> {
+        self.snapshots.read().unwrap_or_else(|e| e.into_inner()).back().cloned()
+    }
+
+    /// Snapshot at exactly `height`, if still retained
+    pub fn at_height(&self, height: u64) -> Option<Arc<StateSnapshot>> {
+        let snapshots = self.snapshots.read().unwrap_or_else(|e| e.into_inner());
+        snapshots.iter().rev().find(|s| s.height == height).cloned()
+    }
+
+    /// Range of retained heights as (oldest, latest)
+    pub fn retained(&self) -> Option<(u64, u64)> {
+        let snapshots = self.snapshots.read().unwrap_or_else(|e| e.into_inner());
+        Some((snapshots.front()?.height, snapshots.back()?.height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_machine::CoreStateMachine;
+    use std::thread;
+
+    #[test]
+    fn test_snapshots_stay_consistent_while_state_advances() {
+        let user = Address::with_last_byte(1);
+        let asset = AssetId(1);
+        let mut sm = CoreStateMachine::new();
+        let store = sm.snapshot_store();
+        assert!(store.latest().is_none());
+
+        sm.begin_block(1).unwrap();
+        sm.set_balance(user, asset, U256::from(100));
+        sm.place_limit_order(user, asset, Side::Bid, Price::from_float(10.0), Size(U256::from(5)), 0)
+            .unwrap();
+        sm.commit_block().unwrap();
+
+        // The next block's writes do not leak into the committed view
+        sm.begin_block(2).unwrap();
+        sm.set_balance(user, asset, U256::from(40));
+        let at_one = store.latest().unwrap();
+        assert_eq!(at_one.height, 1);
+        assert_eq!(at_one.get_balance(&user, asset), U256::from(100));
+        assert_eq!(at_one.get_book(asset, 10).unwrap().bids.len(), 1);
+        sm.commit_block().unwrap();
+
+        // Readers on other threads see whole heights without the state machine
+        let reader = store.clone();
+        let balances = thread::spawn(move || {
+            [1, 2].map(|height| reader.at_height(height).unwrap().get_balance(&user, asset))
+        })
+        .join()
+        .unwrap();
+        assert_eq!(balances, [U256::from(100), U256::from(40)]);
+        assert_eq!(at_one.get_balance(&user, asset), U256::from(100));
+        assert_eq!(store.retained(), Some((1, 2)));
+    }
+
+    #[test]
+    fn test_store_evicts_beyond_retention() {
+        let store = SnapshotStore::new(SnapshotConfig { retained_heights: 2, book_depth: 5 });
+        for height in 1..=4 {
+            let snapshot = StateSnapshot::capture(height, 0, Arc::default(), Arc::default(), [], 5);
+            store.publish(snapshot);
+        }
+        assert_eq!(store.retained(), Some((3, 4)));
+        assert!(store.at_height(2).is_none());
+
+        // Re-publishing a height after a rollback drops the newer views
+        store.publish(StateSnapshot::capture(3, 0, Arc::default(), Arc::default(), [], 5));
+        assert_eq!(store.retained(), Some((3, 3)));
+    }
+}
@@ -2,7 +2,7 @@ use crate::types::*;
 use alloy_primitives::{Address, U256};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 /// Two-sided quote
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,12 +67,46 @@ impl Default for QuoteConfig {
     }
 }
 
+/// Market maker protection thresholds for one maker and asset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MmpConfig {
+    /// Rolling window in seconds
+    pub window: u64,
+    /// Trigger when quotes are hit more than this many times in the window
+    pub max_fills: u32,
+    /// Trigger when filled notional in the window exceeds this
+    pub max_notional: U256,
+}
+
+/// Market maker protection trip: the maker's remaining quotes for the
+/// asset were pulled and quoting is frozen until reset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MmpEvent {
+    pub user: Address,
+    pub asset: AssetId,
+    /// Fills counted in the window
+    pub fills: u32,
+    /// Notional filled in the window
+    pub notional: U256,
+    pub timestamp: u64,
+    /// Quotes pulled (their resting orders should be cancelled)
+    pub pulled: Vec<Quote>,
+}
+
 /// Quote manager for market making
 pub struct QuoteManager {
     config: QuoteConfig,
     active_quotes: HashMap<AssetId, Quote>,
     user_quotes: HashMap<Address, Vec<AssetId>>,
     quote_history: Vec<Quote>,
+    /// MMP thresholds per (maker, asset)
+    mmp_configs: HashMap<(Address, AssetId), MmpConfig>,
+    /// Recent quote fills as (timestamp, notional) per (maker, asset)
+    mmp_fills: HashMap<(Address, AssetId), VecDeque<(u64, U256)>>,
+    /// Makers frozen by MMP until reset
+    mmp_frozen: HashSet<(Address, AssetId)>,
+    /// MMP trips since the last `take_mmp_events`
+    mmp_events: Vec<MmpEvent>,
 }
 
 impl QuoteManager {
@@ -82,6 +116,10 @@ impl QuoteManager {
             active_quotes: HashMap::new(),
             user_quotes: HashMap::new(),
             quote_history: Vec::new(),
+            mmp_configs: HashMap::new(),
+            mmp_fills: HashMap::new(),
+            mmp_frozen: HashSet::new(),
+            mmp_events: Vec::new(),
         }
     }
 
@@ -113,6 +151,10 @@ impl QuoteManager {
             return Err(anyhow!("Size must be non-zero"));
         }
 
+        if self.mmp_frozen.contains(&(user, asset)) {
+            return Err(anyhow!("Market maker protection triggered; reset before quoting"));
+        }
+
         // Check if there's an existing quote and if enough time has passed
         if let Some(existing_quote) = self.active_quotes.get(&asset) {
             if timestamp - existing_quote.updated_at < self.config.update_interval {
@@ -330,6 +372,86 @@ impl QuoteManager {
     pub fn clear_history(&mut self) {
         self.quote_history.clear();
     }
+
+    /// Set a maker's protection thresholds for an asset
+    pub fn set_mmp(&mut self, user: Address, asset: AssetId, config: MmpConfig) -> Result<()> {
+        if config.window == 0 {
+            return Err(anyhow!("MMP window must be positive"));
+        }
+        self.mmp_configs.insert((user, asset), config);
+        Ok(())
+    }
+
+    /// Remove a maker's protection for an asset
+    pub fn clear_mmp(&mut self, user: Address, asset: AssetId) {
+        self.mmp_configs.remove(&(user, asset));
+        self.mmp_fills.remove(&(user, asset));
+    }
+
+    /// Unfreeze a maker after an MMP trip
+    pub fn reset_mmp(&mut self, user: Address, asset: AssetId) {
+        self.mmp_frozen.remove(&(user, asset));
+        self.mmp_fills.remove(&(user, asset));
+    }
+
+    /// Whether a maker is frozen by MMP
+    pub fn is_mmp_frozen(&self, user: &Address, asset: AssetId) -> bool {
+        self.mmp_frozen.contains(&(*user, asset))
+    }
+
+    /// Record a fill against a maker's quote. If fills in the rolling window
+    /// exceed the maker's count or notional threshold, their remaining
+    /// quotes for the asset are pulled and the event is returned.
+    pub fn record_quote_fill(
+        &mut self,
+        user: Address,
+        asset: AssetId,
+        price: Price,
+        size: Size,
+        timestamp: u64,
+    ) -> Option<MmpEvent> {
+        let config = self.mmp_configs.get(&(user, asset))?;
+        if self.mmp_frozen.contains(&(user, asset)) {
+            return None;
+        }
+
+        let notional = size.0 * U256::from(price.0) / U256::from(Price::SCALE);
+        let fills = self.mmp_fills.entry((user, asset)).or_default();
+        fills.push_back((timestamp, notional));
+        while fills
+            .front()
+            .is_some_and(|(t, _)| timestamp.saturating_sub(*t) >= config.window)
+        {
+            fills.pop_front();
+        }
+
+        let count = fills.len() as u32;
+        let total = fills.iter().fold(U256::ZERO, |acc, (_, n)| acc.saturating_add(*n));
+        if count <= config.max_fills && total <= config.max_notional {
+            return None;
+        }
+
+        self.mmp_fills.remove(&(user, asset));
+        self.mmp_frozen.insert((user, asset));
+
+        // Pull the maker's remaining quotes for the asset
+        let mut pulled = Vec::new();
+        if let Some(assets) = self.user_quotes.get_mut(&user) {
+            if assets.contains(&asset) {
+                assets.retain(|a| *a != asset);
+                pulled.extend(self.active_quotes.remove(&asset));
+            }
+        }
+
+        let event = MmpEvent { user, asset, fills: count, notional: total, timestamp, pulled };
+        self.mmp_events.push(event.clone());
+        Some(event)
+    }
+
+    /// Take MMP trips since the last call
+    pub fn take_mmp_events(&mut self) -> Vec<MmpEvent> {
+        std::mem::take(&mut self.mmp_events)
+    }
 }
 
 #[cfg(test)]
@@ -340,6 +462,48 @@ mod tests {
         Address::repeat_byte(seed)
     }
 
+    #[test]
+    fn test_mmp_pulls_quotes_on_fill_count() {
+        let mut manager = QuoteManager::new(QuoteConfig::default());
+        let maker = test_address(1);
+        let asset = AssetId(1);
+        let config = MmpConfig { window: 10, max_fills: 2, max_notional: U256::from(1_000_000) };
+        manager.set_mmp(maker, asset, config).unwrap();
+        manager.post_quote(maker, asset, Price(1_000_000), 20, Size(U256::from(100)), 0).unwrap();
+
+        let fill = |m: &mut QuoteManager, t| m.record_quote_fill(maker, asset, Price(1_000_000), Size(U256::from(1)), t);
+        assert!(fill(&mut manager, 0).is_none());
+        assert!(fill(&mut manager, 5).is_none());
+        // The first fill has left the window
+        assert!(fill(&mut manager, 10).is_none());
+
+        let event = fill(&mut manager, 11).unwrap();
+        assert_eq!((event.fills, event.notional), (3, U256::from(3)));
+        assert_eq!(event.pulled.len(), 1);
+        assert!(manager.get_quote(asset).is_none());
+        assert_eq!(manager.take_mmp_events().len(), 1);
+
+        // Frozen until reset
+        assert!(manager.post_quote(maker, asset, Price(1_000_000), 20, Size(U256::from(100)), 20).is_err());
+        manager.reset_mmp(maker, asset);
+        manager.post_quote(maker, asset, Price(1_000_000), 20, Size(U256::from(100)), 20).unwrap();
+    }
+
+    #[test]
+    fn test_mmp_notional_threshold() {
+        let mut manager = QuoteManager::new(QuoteConfig::default());
+        let maker = test_address(1);
+        let asset = AssetId(1);
+        let config = MmpConfig { window: 60, max_fills: 100, max_notional: U256::from(500) };
+        manager.set_mmp(maker, asset, config).unwrap();
+
+        assert!(manager.record_quote_fill(maker, asset, Price(2_000_000), Size(U256::from(250)), 0).is_none());
+        let event = manager.record_quote_fill(maker, asset, Price(2_000_000), Size(U256::from(1)), 1).unwrap();
+        assert_eq!(event.notional, U256::from(502));
+        assert!(event.pulled.is_empty());
+        assert!(manager.is_mmp_frozen(&maker, asset));
+    }
+
     #[test]
     fn test_quote_mid_price() {
         let quote = Quote {
@@ -1,3 +1,5 @@
+use crate::listing::BasketDefinition;
+use crate::options::OptionKind;
 use crate::types::*;
 use alloy_primitives::{Address, U256};
 use anyhow::{anyhow, Result};
@@ -64,7 +66,24 @@ impl Default for PortfolioRiskLimits {
     }
 }
 
+/// Short option margin parameters (basis points of underlying price)
+#[derive(Debug, Clone)]
+pub struct OptionMarginParams {
+    pub short_ratio_bps: u64,  // e.g., 1500 = 15%
+    pub min_ratio_bps: u64,    // e.g., 1000 = 10%
+}
+
+impl Default for OptionMarginParams {
+    fn default() -> Self {
+        Self {
+            short_ratio_bps: 1500,
+            min_ratio_bps: 1000,
+        }
+    }
+}
+
 /// Risk engine
+#[derive(Clone)]
 pub struct RiskEngine {
     /// Per-asset limits
     asset_limits: HashMap<AssetId, AssetRiskLimits>,
@@ -74,6 +93,8 @@ pub struct RiskEngine {
     default_asset_limits: AssetRiskLimits,
     /// Default portfolio limits
     default_portfolio_limits: PortfolioRiskLimits,
+    /// Short option margin parameters
+    option_margin: OptionMarginParams,
 }
 
 impl RiskEngine {
@@ -83,6 +104,7 @@ impl RiskEngine {
             portfolio_limits: HashMap::new(),
             default_asset_limits: AssetRiskLimits::default(),
             default_portfolio_limits: PortfolioRiskLimits::default(),
+            option_margin: OptionMarginParams::default(),
         }
     }
     
@@ -100,6 +122,25 @@ impl RiskEngine {
         self.asset_limits.get(&asset).unwrap_or(&self.default_asset_limits)
     }
     
+    /// Derive an index perpetual's limits from its components: leverage is
+    /// capped by the most restrictive component, and notional by each
+    /// component's limit scaled up by its weight in the basket
+    pub fn set_basket_limits(&mut self, basket: AssetId, definition: &BasketDefinition) {
+        let mut limits = self.get_asset_limits(basket).clone();
+        for component in &definition.components {
+            let component_limits = self.get_asset_limits(component.asset);
+            limits.max_leverage = limits.max_leverage.min(component_limits.max_leverage);
+            limits.max_notional_value = limits.max_notional_value.min(
+                component_limits.max_notional_value.saturating_mul(U256::from(10_000))
+                    / U256::from(component.weight_bps.max(1)),
+            );
+        }
+        for tier in &mut limits.leverage_tiers {
+            tier.max_leverage = tier.max_leverage.min(limits.max_leverage);
+        }
+        self.asset_limits.insert(basket, limits);
+    }
+    
     /// Set portfolio limits for user
     pub fn set_portfolio_limits(
         &mut self,
@@ -215,6 +256,46 @@ impl RiskEngine {
         limits.max_leverage
     }
     
+    /// Set option margin parameters
+    pub fn set_option_margin(&mut self, params: OptionMarginParams) {
+        self.option_margin = params;
+    }
+    
+    /// Get option margin parameters
+    pub fn get_option_margin(&self) -> &OptionMarginParams {
+        &self.option_margin
+    }
+    
+    /// Margin required for a short option position (cash-settled)
+    ///
+    /// Per contract: `premium + max(short_ratio * S - OTM amount, min_ratio * S)`.
+    /// Long options need no margin beyond the premium already paid.
+    pub fn calculate_option_margin(
+        &self,
+        kind: OptionKind,
+        short_size: u64,
+        strike: Price,
+        underlying_price: Price,
+        premium: Price,
+    ) -> U256 {
+        if short_size == 0 {
+            return U256::ZERO;
+        }
+        
+        let s = underlying_price.0 as u128;
+        let k = strike.0 as u128;
+        let otm = match kind {
+            OptionKind::Call => k.saturating_sub(s),
+            OptionKind::Put => s.saturating_sub(k),
+        };
+        
+        let base = (s * self.option_margin.short_ratio_bps as u128 / 10_000).saturating_sub(otm);
+        let floor = s * self.option_margin.min_ratio_bps as u128 / 10_000;
+        let per_contract = premium.0 as u128 + base.max(floor);
+        
+        U256::from(short_size as u128 * per_contract / Price::SCALE as u128)
+    }
+    
     /// Check if position size allows requested leverage (tiered)
     pub fn check_tiered_leverage(
         &self,
@@ -470,5 +551,29 @@ mod tests {
         assert_eq!(engine.get_max_leverage_for_notional(asset, U256::from(50_000)), 25);
         assert_eq!(engine.get_max_leverage_for_notional(asset, U256::from(200_000)), 10);
     }
-}
 
+    #[test]
+    fn test_option_margin() {
+        let engine = RiskEngine::new();
+        let strike = Price::from_float(100.0);
+        let premium = Price::from_float(5.0);
+        
+        // ATM call: 5 + 15% * 100 = 20 per contract
+        let margin = engine.calculate_option_margin(
+            OptionKind::Call, 10, strike, Price::from_float(100.0), premium,
+        );
+        assert_eq!(margin, U256::from(200));
+        
+        // Deep OTM put: 5 + max(15 - 40, 10% * 140) = 19 per contract
+        let margin = engine.calculate_option_margin(
+            OptionKind::Put, 10, strike, Price::from_float(140.0), premium,
+        );
+        assert_eq!(margin, U256::from(190));
+        
+        // No short exposure
+        let margin = engine.calculate_option_margin(
+            OptionKind::Call, 0, strike, Price::from_float(100.0), premium,
+        );
+        assert_eq!(margin, U256::ZERO);
+    }
+}
@@ -0,0 +1,227 @@
+use alloy_primitives::U256;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Relative distance from an integer below which a float amount is taken
+/// as exact
+const FLOAT_TOLERANCE: f64 = 1e-9;
+
+/// How an inexact quotient is rounded to an integer amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rounding {
+    /// Toward negative infinity
+    Floor,
+    /// Toward positive infinity
+    Ceil,
+    /// To nearest, ties to even (banker's rounding): unbiased over many
+    /// roundings, but may round either way
+    HalfEven,
+}
+
+impl Rounding {
+    /// `a * b / d` rounded (zero when `d` is zero; the product saturates)
+    pub fn mul_div(self, a: U256, b: U256, d: U256) -> U256 {
+        if d.is_zero() {
+            return U256::ZERO;
+        }
+        let product = a.saturating_mul(b);
+        let (quotient, remainder) = product.div_rem(d);
+        if remainder.is_zero() {
+            return quotient;
+        }
+        let round_up = match self {
+            Rounding::Floor => false,
+            Rounding::Ceil => true,
+            Rounding::HalfEven => {
+                let twice = remainder.saturating_mul(U256::from(2));
+                twice > d || (twice == d && quotient.bit(0))
+            }
+        };
+        if round_up {
+            quotient.saturating_add(U256::from(1))
+        } else {
+            quotient
+        }
+    }
+
+    /// `n / d` rounded (zero when `d` is zero)
+    pub fn div(self, n: i128, d: i128) -> i128 {
+        if d == 0 {
+            return 0;
+        }
+        let (n, d) = if d < 0 { (-n, -d) } else { (n, d) };
+        let floor = n.div_euclid(d);
+        let remainder = n.rem_euclid(d);
+        if remainder == 0 {
+            return floor;
+        }
+        let round_up = match self {
+            Rounding::Floor => false,
+            Rounding::Ceil => true,
+            Rounding::HalfEven => 2 * remainder > d || (2 * remainder == d && floor % 2 != 0),
+        };
+        floor + round_up as i128
+    }
+
+    /// Round a fractional amount (saturating at the `i64` range). Values
+    /// within float noise of an integer are taken as that integer, so an
+    /// exact amount never rounds up or down by one.
+    pub fn round(self, x: f64) -> i64 {
+        let nearest = x.round();
+        if (x - nearest).abs() <= FLOAT_TOLERANCE * nearest.abs().max(1.0) {
+            return nearest as i64;
+        }
+        let rounded = match self {
+            Rounding::Floor => x.floor(),
+            Rounding::Ceil => x.ceil(),
+            Rounding::HalfEven => {
+                let floor = x.floor();
+                let diff = x - floor;
+                if diff > 0.5 || (diff == 0.5 && floor % 2.0 != 0.0) {
+                    floor + 1.0
+                } else {
+                    floor
+                }
+            }
+        };
+        rounded as i64
+    }
+}
+
+/// Protocol-wide rounding rules
+///
+/// Amounts paid to users are rounded down and amounts taken from them
+/// rounded up, so rounding dust always stays with the protocol. Splits
+/// between protocol accounts (e.g. fee routing) move no user value and use
+/// `internal`, banker's rounding by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundingPolicy {
+    /// User credits: funding received, settlement payouts, LP tokens
+    /// minted, liquidity redeemed, profit shares
+    pub credit: Rounding,
+    /// User debits: trading fees, funding paid, settlement losses
+    pub debit: Rounding,
+    /// Splits between protocol accounts
+    pub internal: Rounding,
+}
+
+impl Default for RoundingPolicy {
+    fn default() -> Self {
+        Self {
+            credit: Rounding::Floor,
+            debit: Rounding::Ceil,
+            internal: Rounding::HalfEven,
+        }
+    }
+}
+
+impl RoundingPolicy {
+    /// Reject policies that always round credits up or debits down, which
+    /// would systematically pay users more (or charge them less) than they
+    /// are owed. Banker's rounding may be specified for either: unbiased on
+    /// average, though no longer dust-free per operation.
+    pub fn validate(&self) -> Result<()> {
+        if self.credit == Rounding::Ceil {
+            return Err(anyhow!("User credits must not round up"));
+        }
+        if self.debit == Rounding::Floor {
+            return Err(anyhow!("User debits must not round down"));
+        }
+        Ok(())
+    }
+
+    /// `a * b / d` owed to a user
+    pub fn credit(&self, a: U256, b: U256, d: U256) -> U256 {
+        self.credit.mul_div(a, b, d)
+    }
+
+    /// `a * b / d` owed by a user
+    pub fn debit(&self, a: U256, b: U256, d: U256) -> U256 {
+        self.debit.mul_div(a, b, d)
+    }
+
+    /// Signed user amount (positive = paid to the user): credits and debits
+    /// round toward the protocol by magnitude
+    pub fn signed(&self, amount: f64) -> i64 {
+        if amount >= 0.0 {
+            self.credit.round(amount)
+        } else {
+            -self.debit.round(-amount)
+        }
+    }
+
+    /// Signed user amount `n / d` (positive = paid to the user), rounded as
+    /// by `signed`
+    pub fn signed_div(&self, n: i128, d: i128) -> i128 {
+        let magnitude = |rounding: Rounding| rounding.div(n.abs(), d.abs());
+        if (n >= 0) == (d >= 0) {
+            magnitude(self.credit)
+        } else {
+            -magnitude(self.debit)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_rounding_modes() {
+        let d = U256::from(4);
+        let cases = [(9u64, 2u64, 3u64, 2u64), (10, 2, 3, 2), (11, 2, 3, 3), (6, 1, 2, 2)];
+        for (n, floor, ceil, half_even) in cases {
+            let n = U256::from(n);
+            assert_eq!(Rounding::Floor.mul_div(n, U256::from(1), d), U256::from(floor));
+            assert_eq!(Rounding::Ceil.mul_div(n, U256::from(1), d), U256::from(ceil));
+            assert_eq!(Rounding::HalfEven.mul_div(n, U256::from(1), d), U256::from(half_even));
+        }
+        assert_eq!(Rounding::HalfEven.div(-10, 4), -2);
+        assert_eq!(Rounding::HalfEven.div(-6, 4), -2);
+        assert_eq!(Rounding::Floor.div(-7, 2), -4);
+        assert_eq!(Rounding::Ceil.div(7, -2), -3);
+        assert_eq!(Rounding::HalfEven.round(2.5), 2);
+        assert_eq!(Rounding::HalfEven.round(3.5), 4);
+        assert_eq!(Rounding::HalfEven.round(-2.5), -2);
+        assert_eq!(Rounding::Ceil.round(10_000.0 * 0.001), 10);
+        assert_eq!(Rounding::Floor.round(0.1 + 0.2 - 0.3 + 1.0), 1);
+
+        let policy = RoundingPolicy::default();
+        assert_eq!(policy.signed(-1.2), -2);
+        assert_eq!(policy.signed(1.8), 1);
+        assert_eq!(policy.signed_div(-7, 2), -4);
+        assert_eq!(policy.signed_div(7, 2), 3);
+        assert!(policy.validate().is_ok());
+        assert!(RoundingPolicy { credit: Rounding::Ceil, ..policy }.validate().is_err());
+        assert!(RoundingPolicy { debit: Rounding::Floor, ..policy }.validate().is_err());
+    }
+
+    proptest! {
+        /// Credits never exceed, and debits never fall short of, the exact amount
+        #[test]
+        fn prop_policy_rounds_toward_protocol(a in 0u64.., b in 0u64..=10_000, d in 1u64..) {
+            let policy = RoundingPolicy::default();
+            let (a, b, d) = (U256::from(a), U256::from(b), U256::from(d));
+            let exact = a * b;
+            prop_assert!(policy.credit(a, b, d) * d <= exact);
+            prop_assert!(policy.debit(a, b, d) * d >= exact);
+            prop_assert!(policy.debit(a, b, d) - policy.credit(a, b, d) <= U256::from(1));
+        }
+
+        #[test]
+        fn prop_signed_rounds_toward_protocol(n in -1_000_000_000_000i128..1_000_000_000_000, d in 1i128..1_000_000) {
+            let policy = RoundingPolicy::default();
+            // Paid to the user: at most the exact amount; charged: at least
+            let paid = policy.signed_div(n, d);
+            prop_assert!(paid * d <= n);
+            prop_assert!(n - paid * d < d);
+        }
+
+        #[test]
+        fn prop_half_even_within_half(n in -1_000_000_000i128..1_000_000_000, d in 1i128..1_000_000) {
+            let rounded = Rounding::HalfEven.div(n, d);
+            prop_assert!((2 * (n - rounded * d)).abs() <= d);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::liquidity_pool::{LiquidityPool, PoolId};
+use crate::orderbook::OrderBook;
+use crate::rounding::RoundingPolicy;
+use crate::types::*;
+use alloy_primitives::U256;
+use serde::{Deserialize, Serialize};
+
+/// Where a routed fill executes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Venue {
+    /// Central limit order book
+    Book,
+    /// Liquidity pool grid
+    Pool(PoolId),
+}
+
+/// Fill at one price level of one venue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutedFill {
+    pub venue: Venue,
+    pub price: Price,
+    pub size: Size,
+    /// Venue fee on the fill
+    pub fee: U256,
+}
+
+/// Split of a taker order across venues
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePlan {
+    /// Fills in routing order (cheapest all-in price first)
+    pub fills: Vec<RoutedFill>,
+    pub filled_size: Size,
+    /// Size no venue could fill within the limit
+    pub unfilled_size: Size,
+    /// Size-weighted average fill price, before fees
+    pub average_price: Option<Price>,
+    /// Notional value of the fills
+    pub notional: U256,
+    /// Fees across venues
+    pub fees: U256,
+}
+
+impl RoutePlan {
+    /// Size routed to a venue
+    pub fn venue_size(&self, venue: Venue) -> Size {
+        Size(self.fills.iter().filter(|f| f.venue == venue).fold(U256::ZERO, |acc, f| acc + f.size.0))
+    }
+
+    /// Pools used, in ID order
+    pub fn pools(&self) -> Vec<PoolId> {
+        let mut pools: Vec<PoolId> = self
+            .fills
+            .iter()
+            .filter_map(|f| match f.venue {
+                Venue::Pool(id) => Some(id),
+                Venue::Book => None,
+            })
+            .collect();
+        pools.sort_unstable();
+        pools.dedup();
+        pools
+    }
+}
+
+/// Executed routed order
+#[derive(Debug, Clone)]
+pub struct RoutedOrder {
+    pub plan: RoutePlan,
+    /// Book fills of the order
+    pub book_fills: Vec<Fill>,
+}
+
+/// Venue fees compared when routing, in basis points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouterConfig {
+    pub book_fee_bps: u64,
+    pub pool_fee_bps: u64,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            book_fee_bps: 5,
+            pool_fee_bps: 30,
+        }
+    }
+}
+
+/// Best-execution router across the order book and liquidity pools
+///
+/// Routing is greedy over price levels ranked by all-in price (price
+/// adjusted by the venue fee). Ties go to the book, then to pools in ID
+/// order, so the same inputs always route the same way.
+#[derive(Debug, Clone, Default)]
+pub struct OrderRouter {
+    config: RouterConfig,
+    /// Venue fees round up (debit)
+    rounding: RoundingPolicy,
+}
+
+impl OrderRouter {
+    pub fn new(config: RouterConfig) -> Self {
+        Self { config, rounding: RoundingPolicy::default() }
+    }
+
+    pub fn config(&self) -> &RouterConfig {
+        &self.config
+    }
+
+    /// Set the rounding policy for venue fees
+    pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) {
+        self.rounding = policy;
+    }
+
+    /// Plan a taker order. Pools quote around `reference` (typically the
+    /// mark price); without one only the book is used.
+    pub fn plan(
+        &self,
+        book: Option<&OrderBook>,
+        pools: &[&LiquidityPool],
+        side: Side,
+        size: Size,
+        limit_price: Option<Price>,
+        reference: Option<Price>,
+    ) -> RoutePlan {
+        let opposite = match side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+
+        let mut levels: Vec<(Venue, Price, U256)> = Vec::new();
+        if let Some(book) = book {
+            levels.extend(book.levels(opposite).map(|l| (Venue::Book, l.price, l.total_size)));
+        }
+        if let Some(reference) = reference {
+            for pool in pools {
+                levels.extend(pool.quotes(side, reference).into_iter().map(|(p, s)| (Venue::Pool(pool.id), p, s.0)));
+            }
+        }
+        // Stable sort keeps each venue's levels in price order
+        levels.sort_by(|a, b| {
+            let (ka, kb) = (self.all_in_price(side, a.0, a.1), self.all_in_price(side, b.0, b.1));
+            let by_price = match side {
+                Side::Bid => ka.cmp(&kb),
+                Side::Ask => kb.cmp(&ka),
+            };
+            by_price.then(a.0.cmp(&b.0))
+        });
+
+        let mut fills = Vec::new();
+        let mut remaining = size.0;
+        let mut weighted_price = U256::ZERO;
+        let mut notional = U256::ZERO;
+        let mut fees = U256::ZERO;
+        for (venue, price, available) in levels {
+            if remaining.is_zero() {
+                break;
+            }
+            let crosses = match (side, limit_price) {
+                (_, None) => true,
+                (Side::Bid, Some(limit)) => price <= limit,
+                (Side::Ask, Some(limit)) => price >= limit,
+            };
+            if !crosses || available.is_zero() {
+                continue;
+            }
+
+            let fill_size = remaining.min(available);
+            remaining -= fill_size;
+            let fill_notional = fill_size * U256::from(price.0) / U256::from(Price::SCALE);
+            let fee = self.rounding.debit(fill_notional, U256::from(self.fee_bps(venue)), U256::from(10_000u64));
+            weighted_price += fill_size * U256::from(price.0);
+            notional += fill_notional;
+            fees += fee;
+            fills.push(RoutedFill { venue, price, size: Size(fill_size), fee });
+        }
+
+        let filled = size.0 - remaining;
+        RoutePlan {
+            fills,
+            filled_size: Size(filled),
+            unfilled_size: Size(remaining),
+            average_price: (!filled.is_zero()).then(|| Price((weighted_price / filled).as_limbs()[0])),
+            notional,
+            fees,
+        }
+    }
+
+    fn fee_bps(&self, venue: Venue) -> u64 {
+        match venue {
+            Venue::Book => self.config.book_fee_bps,
+            Venue::Pool(_) => self.config.pool_fee_bps,
+        }
+    }
+
+    /// Price including the venue fee, scaled by 10_000: higher for buys,
+    /// lower for sells
+    fn all_in_price(&self, side: Side, venue: Venue, price: Price) -> u128 {
+        let bps = self.fee_bps(venue) as u128;
+        match side {
+            Side::Bid => price.0 as u128 * (10_000 + bps),
+            Side::Ask => price.0 as u128 * 10_000u128.saturating_sub(bps),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::Address;
+
+    fn pool(id: PoolId, levels: &[f64], size: u64, liquidity: u64) -> LiquidityPool {
+        let levels = levels.iter().map(|p| Price::from_float(*p)).collect();
+        let mut pool = LiquidityPool::new(id, AssetId(1), levels, Size(U256::from(size)), 0);
+        pool.add_liquidity(Address::from([7u8; 20]), U256::from(liquidity)).unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_route_splits_by_all_in_price() {
+        let mut book = OrderBook::new(AssetId(1));
+        let maker = Address::from([9u8; 20]);
+        book.add_limit_order(maker, Side::Ask, Price::from_float(100.0), Size(U256::from(5)), 0);
+        book.add_limit_order(maker, Side::Ask, Price::from_float(103.0), Size(U256::from(5)), 0);
+        let pool = pool(1, &[98.0, 100.1, 101.0], 4, 10_000);
+
+        let router = OrderRouter::default();
+        let plan = router.plan(Some(&book), &[&pool], Side::Bid, Size(U256::from(12)), None, Some(Price::from_float(99.0)));
+
+        // Book 100 (100.05 all in), pool 100.1 (~100.4), pool 101 (~101.3)
+        let venues: Vec<_> = plan.fills.iter().map(|f| (f.venue, f.price)).collect();
+        assert_eq!(venues, vec![
+            (Venue::Book, Price::from_float(100.0)),
+            (Venue::Pool(1), Price::from_float(100.1)),
+            (Venue::Pool(1), Price::from_float(101.0)),
+        ]);
+        assert_eq!(plan.venue_size(Venue::Book), Size(U256::from(5)));
+        assert_eq!(plan.venue_size(Venue::Pool(1)), Size(U256::from(7)));
+        assert_eq!(plan.filled_size, Size(U256::from(12)));
+        assert_eq!(plan.pools(), vec![1]);
+
+        // Within a 100.5 limit only the first two levels fill
+        let plan = router.plan(Some(&book), &[&pool], Side::Bid, Size(U256::from(12)), Some(Price::from_float(100.5)), Some(Price::from_float(99.0)));
+        assert_eq!(plan.filled_size, Size(U256::from(9)));
+        assert_eq!(plan.unfilled_size, Size(U256::from(3)));
+    }
+
+    #[test]
+    fn test_pool_quotes_capped_by_liquidity() {
+        // 250 of liquidity covers two levels of size 1 at 100, not a third
+        let pool = pool(1, &[100.0, 100.0, 100.0, 90.0], 1, 250);
+        let quotes = pool.quotes(Side::Bid, Price::from_float(95.0));
+        let sizes: Vec<_> = quotes.iter().map(|(_, s)| s.0).collect();
+        assert_eq!(sizes, vec![U256::from(1), U256::from(1)]);
+        assert!(pool.quotes(Side::Ask, Price::from_float(80.0)).is_empty());
+    }
+}
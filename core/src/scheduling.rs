@@ -0,0 +1,58 @@
+// Block payload scheduling
+//
+// Decides the order in which a block's signed actions are dispatched. The
+// default keeps the proposer's order. Under `PriorityCancels`, cancels of
+// orders already resting when the block starts run before everything else,
+// so makers can pull stale quotes before the block's aggressive orders
+// reach them. Each account's actions keep their relative order, since
+// nonces must be consumed in sequence: only the cancels that lead an
+// account's actions in the block are moved forward. The schedule depends
+// only on the payload and the books at block start, so every replica
+// derives the same one.
+
+use crate::auth::{CoreAction, SignedAction};
+use crate::types::{AssetId, OrderId};
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// How a block's actions are ordered for dispatch (set by governance)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SchedulingPolicy {
+    /// Dispatch in payload order
+    #[default]
+    Sequential,
+    /// Dispatch cancels of resting orders first
+    PriorityCancels,
+}
+
+impl SchedulingPolicy {
+    /// Dispatch order of `actions`, as indices into the payload
+    ///
+    /// `resting` tells whether an order was on the book when the block
+    /// started.
+    pub fn schedule(&self, actions: &[SignedAction], resting: impl Fn(AssetId, OrderId) -> bool) -> Vec<usize> {
+        match self {
+            Self::Sequential => (0..actions.len()).collect(),
+            Self::PriorityCancels => {
+                // Accounts with an action already left in place
+                let mut blocked: HashSet<Address> = HashSet::new();
+                let (mut first, mut rest) = (Vec::new(), Vec::new());
+                for (index, signed) in actions.iter().enumerate() {
+                    let priority = match signed.action {
+                        CoreAction::CancelOrder { asset, order_id } => resting(asset, order_id),
+                        _ => false,
+                    };
+                    if priority && !blocked.contains(&signed.account) {
+                        first.push(index);
+                    } else {
+                        blocked.insert(signed.account);
+                        rest.push(index);
+                    }
+                }
+                first.extend(rest);
+                first
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use crate::fees::FeeEngine;
+use crate::margin::{MarginEngine, PositionPreview};
+use crate::orderbook::OrderBook;
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
+
+/// Proposed order to simulate
+#[derive(Debug, Clone)]
+pub struct OrderSimulationRequest {
+    pub trader: Address,
+    pub asset: AssetId,
+    pub side: Side,
+    pub size: Size,
+    /// Limit price (None = market order)
+    pub limit_price: Option<Price>,
+    pub timestamp: u64,
+}
+
+/// Expected fill against a resting price level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulatedFill {
+    pub price: Price,
+    pub size: Size,
+}
+
+/// Outcome of a simulated order
+#[derive(Debug, Clone)]
+pub struct OrderSimulation {
+    /// Expected fills, best price first
+    pub fills: Vec<SimulatedFill>,
+    /// Total size expected to fill immediately
+    pub filled_size: Size,
+    /// Size left over (rests for limit orders, dropped for market orders)
+    pub unfilled_size: Size,
+    /// Size-weighted average fill price
+    pub average_price: Option<Price>,
+    /// Notional value of the expected fills
+    pub notional: U256,
+    /// Taker fee on the expected fills
+    pub fee: U256,
+    /// Position and margin usage after the fills
+    pub position: PositionPreview,
+    /// Whether post-trade margin usage is covered by account equity
+    pub margin_sufficient: bool,
+}
+
+/// Read-only pre-trade simulator
+pub struct OrderSimulator;
+
+impl OrderSimulator {
+    /// Simulate an order against the book and the trader's positions
+    pub fn simulate(
+        book: Option<&OrderBook>,
+        margin_engine: &MarginEngine,
+        fee_engine: &FeeEngine,
+        request: &OrderSimulationRequest,
+    ) -> Result<OrderSimulation> {
+        if request.size.0 == U256::ZERO {
+            return Err(anyhow!("Order size must be positive"));
+        }
+
+        let fills = match book {
+            Some(book) => Self::expected_fills(book, request.side, request.size, request.limit_price),
+            None => Vec::new(),
+        };
+
+        let mut filled = U256::ZERO;
+        let mut weighted_price = U256::ZERO;
+        let mut notional = U256::ZERO;
+        let mut position_fills = Vec::with_capacity(fills.len());
+        for fill in &fills {
+            filled += fill.size.0;
+            weighted_price += fill.size.0 * U256::from(fill.price.0);
+            notional += fill.size.0 * U256::from(fill.price.0) / U256::from(Price::SCALE);
+
+            let size = fill.size.0.as_limbs()[0] as i64;
+            let size_delta = match request.side {
+                Side::Bid => size,
+                Side::Ask => -size,
+            };
+            position_fills.push((size_delta, fill.price));
+        }
+
+        let average_price = if filled > U256::ZERO {
+            Some(Price((weighted_price / filled).as_limbs()[0]))
+        } else {
+            None
+        };
+
+        let fee = fee_engine.calculate_fee(&request.trader, notional, false, request.timestamp);
+        let position = margin_engine.preview_position(&request.trader, request.asset, &position_fills)?;
+        let equity = margin_engine
+            .get_account_equity(&request.trader)
+            .unwrap_or(U256::ZERO);
+
+        Ok(OrderSimulation {
+            fills,
+            filled_size: Size(filled),
+            unfilled_size: Size(request.size.0 - filled),
+            average_price,
+            notional,
+            fee,
+            margin_sufficient: position.used_margin <= equity,
+            position,
+        })
+    }
+
+    /// Walk the opposite side of the book, aggregating fills per level
+    pub fn expected_fills(
+        book: &OrderBook,
+        side: Side,
+        size: Size,
+        limit_price: Option<Price>,
+    ) -> Vec<SimulatedFill> {
+        let opposite = match side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+
+        let mut remaining = size.0;
+        let mut fills = Vec::new();
+        for level in book.levels(opposite) {
+            if remaining == U256::ZERO {
+                break;
+            }
+
+            let crosses = match (side, limit_price) {
+                (_, None) => true,
+                (Side::Bid, Some(limit)) => level.price <= limit,
+                (Side::Ask, Some(limit)) => level.price >= limit,
+            };
+            if !crosses {
+                break;
+            }
+
+            let fill_size = remaining.min(level.total_size);
+            remaining -= fill_size;
+            fills.push(SimulatedFill {
+                price: level.price,
+                size: Size(fill_size),
+            });
+        }
+
+        fills
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::margin::MarginConfig;
+
+    fn book_with_asks() -> OrderBook {
+        let mut book = OrderBook::new(AssetId(1));
+        let maker = Address::from([9u8; 20]);
+        book.add_limit_order(maker, Side::Ask, Price::from_float(100.0), Size(U256::from(10)), 0);
+        book.add_limit_order(maker, Side::Ask, Price::from_float(101.0), Size(U256::from(10)), 0);
+        book
+    }
+
+    fn request(side: Side, size: u64, limit_price: Option<Price>) -> OrderSimulationRequest {
+        OrderSimulationRequest {
+            trader: Address::from([1u8; 20]),
+            asset: AssetId(1),
+            side,
+            size: Size(U256::from(size)),
+            limit_price,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_expected_fills_walk_levels() {
+        let book = book_with_asks();
+
+        let fills = OrderSimulator::expected_fills(&book, Side::Bid, Size(U256::from(15)), None);
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].size, Size(U256::from(10)));
+        assert_eq!(fills[1].price, Price::from_float(101.0));
+        assert_eq!(fills[1].size, Size(U256::from(5)));
+
+        // Limit stops at 100
+        let fills = OrderSimulator::expected_fills(
+            &book,
+            Side::Bid,
+            Size(U256::from(15)),
+            Some(Price::from_float(100.0)),
+        );
+        assert_eq!(fills.len(), 1);
+    }
+
+    #[test]
+    fn test_simulate_does_not_mutate() {
+        let book = book_with_asks();
+        let mut margin = MarginEngine::new(MarginConfig::default());
+        let req = request(Side::Bid, 15, None);
+        margin.deposit(req.trader, AssetId(0), U256::from(10_000)).unwrap();
+        let fees = FeeEngine::new();
+
+        let sim = OrderSimulator::simulate(Some(&book), &margin, &fees, &req).unwrap();
+
+        assert_eq!(sim.filled_size, Size(U256::from(15)));
+        assert_eq!(sim.unfilled_size, Size(U256::ZERO));
+        // (10 * 100 + 5 * 101) / 15
+        assert_eq!(sim.average_price, Some(Price(100_333_333)));
+        assert_eq!(sim.notional, U256::from(1505));
+        // 10 bps taker, 1.505 rounded up
+        assert_eq!(sim.fee, U256::from(2));
+        assert_eq!(sim.position.size, 15);
+        assert!(sim.margin_sufficient);
+        assert!(sim.position.liquidation_price.is_none());
+
+        // Nothing changed
+        assert!(margin.get_position(&req.trader, AssetId(1)).is_none());
+        assert_eq!(book.best_ask(), Some(Price::from_float(100.0)));
+    }
+
+    #[test]
+    fn test_simulate_liquidation_price_and_margin() {
+        let book = book_with_asks();
+        let mut margin = MarginEngine::new(MarginConfig::default());
+        let req = request(Side::Bid, 10, Some(Price::from_float(100.0)));
+        margin.deposit(req.trader, AssetId(0), U256::from(200)).unwrap();
+        let fees = FeeEngine::new();
+
+        let sim = OrderSimulator::simulate(Some(&book), &margin, &fees, &req).unwrap();
+
+        // 10 @ 100 with 200 collateral: (1000 - 200) / (10 * 0.95) ~= 84.21
+        let liq = sim.position.liquidation_price.unwrap().to_float();
+        assert!((liq - 84.21).abs() < 0.01);
+        assert_eq!(sim.position.used_margin, U256::from(100));
+        assert_eq!(sim.position.available_margin, U256::from(100));
+        assert!(sim.margin_sufficient);
+
+        // Without collateral the trade cannot be margined
+        let other = OrderSimulationRequest { trader: Address::from([2u8; 20]), ..req };
+        let sim = OrderSimulator::simulate(Some(&book), &margin, &fees, &other).unwrap();
+        assert!(!sim.margin_sufficient);
+    }
+
+    #[test]
+    fn test_simulate_short_and_empty_book() {
+        let margin = MarginEngine::new(MarginConfig::default());
+        let fees = FeeEngine::new();
+        let req = request(Side::Ask, 5, Some(Price::from_float(50.0)));
+
+        let sim = OrderSimulator::simulate(None, &margin, &fees, &req).unwrap();
+        assert!(sim.fills.is_empty());
+        assert_eq!(sim.unfilled_size, Size(U256::from(5)));
+        assert_eq!(sim.average_price, None);
+        assert_eq!(sim.position.size, 0);
+
+        assert!(OrderSimulator::simulate(None, &margin, &fees, &request(Side::Ask, 0, None)).is_err());
+    }
+}
@@ -1,22 +1,96 @@
+use crate::analytics::liquidity::{LiquidityMonitor, LiquiditySla, LiquidityStats, MarketMode};
+use crate::analytics::pools::LpPoolReport;
+use crate::audit_export::ExportRecord;
+use crate::auth::{ActionOutcome, CoreAction, SessionKey, SessionRegistry, SignedAction};
+use crate::block_hooks::{BatchAuction, BlockBeginReport, BlockEndReport, BlockHooks, MarketModeChange, TriggeredOrder};
 use crate::checkpoint::CheckpointManager;
+use crate::clock::ChainClock;
+use crate::delisting::{DelistingAction, DelistingManager, DelistingParams};
+use crate::depth_history::{DepthConfig, DepthHistory, DepthSnapshot};
+use crate::emergency::{EmergencyAction, EmergencyControls, Operation};
+use crate::emissions::EmissionsEngine;
+use crate::fees::{FeeDestination, FeeEngine, FeeRouting};
+use crate::funding::{FundingConfig, FundingEngine, FundingRecord, FundingRound};
+use crate::governance::Governance;
 use crate::history::OrderHistory;
+use crate::pnl_history::{AccountSnapshot, PnlHistory};
+use crate::query::{BalanceMap, SnapshotStore, StateSnapshot};
 use crate::liquidation::LiquidationEngine;
-use crate::margin::{MarginConfig, MarginEngine};
+use crate::liquidity_pool::{PoolId, PoolManager};
+use crate::listing::{Listing, ListingKind, ListingRegistry};
+use crate::margin::{MarginConfig, MarginEngine, MarginMode};
 use crate::matching::MatchingEngine;
+use crate::options::{OptionOrder, OptionSeries, OptionSettlement, OptionsEngine};
+use crate::oracle::{OracleEngine, PriceSource};
+use crate::risk::RiskEngine;
+use crate::rounding::RoundingPolicy;
+use crate::router::{OrderRouter, RoutePlan, RoutedOrder, RouterConfig, Venue};
+use crate::scheduling::SchedulingPolicy;
+use crate::order_limits::{OrderLimits, OrderLimitsConfig};
+use crate::orders::{AdvancedOrderType, BracketOrderRequest, OrderManager, TriggerPrices};
 use crate::orderbook::OrderBook;
-use crate::storage::CoreStorage;
+use crate::simulation::{OrderSimulation, OrderSimulationRequest, OrderSimulator};
+use crate::storage::{CoreStorage, StorageBatch};
+use crate::stress::{StressReport, StressScenario, StressTester};
+use crate::tokens::{TokenInfo, TokenRegistry};
+use crate::transfer::{SignedPositionTransfer, TransferLedger};
 use crate::types::*;
-use alloy_primitives::{Address, U256};
+use crate::vault::{VaultId, VaultManager, VaultState, VaultStrategy};
+use alloy_primitives::{Address, B256, U256};
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
 
+/// In-memory state as it was before the block in progress, put back if
+/// the block aborts
+struct BlockStart {
+    books: HashMap<AssetId, OrderBook>,
+    balances: Arc<BalanceMap>,
+    current_height: u64,
+    margin_engine: MarginEngine,
+    liquidation_engine: LiquidationEngine,
+    order_limits: OrderLimits,
+    transfer_ledger: TransferLedger,
+    sessions: SessionRegistry,
+    oracle: OracleEngine,
+    delistings: DelistingManager,
+    fee_engine: FeeEngine,
+    governance: Governance,
+    emergency: EmergencyControls,
+    scheduling_policy: SchedulingPolicy,
+    rounding_policy: RoundingPolicy,
+    clock: ChainClock,
+    next_fill_seq: u64,
+    block_randomness: B256,
+    funding_engine: FundingEngine,
+    advanced_orders: OrderManager,
+    order_expiries: BTreeMap<u64, Vec<(AssetId, OrderId)>>,
+    snapshot_interval: u64,
+    last_snapshot_epoch: Option<u64>,
+    emissions: EmissionsEngine,
+    emissions_epoch: Option<u64>,
+    depth_config: DepthConfig,
+    last_depth_slot: Option<u64>,
+    liquidity_monitor: LiquidityMonitor,
+    listings: ListingRegistry,
+    tokens: TokenRegistry,
+    risk_engine: RiskEngine,
+    options: OptionsEngine,
+    vaults: VaultManager,
+    pools: PoolManager,
+    router: OrderRouter,
+    batch_auctions: HashMap<AssetId, u64>,
+}
+
+/// In-memory state captured by `CoreStateMachine::checkpoint`
+pub struct CoreCheckpoint(Box<BlockStart>);
+
 /// OpenCore state machine
 pub struct CoreStateMachine {
     /// Order books by asset
     books: HashMap<AssetId, OrderBook>,
-    /// User balances by asset
-    balances: HashMap<(Address, AssetId), U256>,
+    /// User balances by asset, shared copy-on-write with state snapshots
+    balances: Arc<BalanceMap>,
     /// Storage layer (optional for in-memory mode)
     storage: Option<Arc<CoreStorage>>,
     /// Checkpoint manager
@@ -29,20 +103,135 @@ pub struct CoreStateMachine {
     margin_engine: MarginEngine,
     /// Liquidation engine for risk management
     liquidation_engine: LiquidationEngine,
+    /// Per-account order rate and open-order caps
+    order_limits: OrderLimits,
+    /// Position transfer nonces and ledger entries
+    transfer_ledger: TransferLedger,
+    /// API nonces and session keys for signed actions
+    sessions: SessionRegistry,
+    /// Oracle for external prices (settlement TWAP)
+    oracle: OracleEngine,
+    /// Market delisting schedules
+    delistings: DelistingManager,
+    /// Fee schedule (used for pre-trade estimates)
+    fee_engine: FeeEngine,
+    /// Authorities for fee routing and protocol fund claims
+    governance: Governance,
+    /// Kill switch and scoped pauses, invoked by governance authorities
+    emergency: EmergencyControls,
+    /// Dispatch order of block payloads (set by governance)
+    scheduling_policy: SchedulingPolicy,
+    /// Rounding of user credits and debits across fees, funding and pools
+    rounding_policy: RoundingPolicy,
+    /// Chain signed transfers and actions must be bound to
+    chain_id: u64,
+    /// Storage writes buffered for the block in progress
+    pending_batch: Option<StorageBatch>,
+    /// Engine time, advanced by block timestamps
+    clock: ChainClock,
+    /// In-memory state before the block in progress, restored if it aborts
+    block_start: Option<Box<BlockStart>>,
+    /// Sequence number of the next persisted fill
+    next_fill_seq: u64,
+    /// Consensus randomness beacon of the block in progress
+    block_randomness: B256,
+    /// Funding rate engine (accrued at block end)
+    funding_engine: FundingEngine,
+    /// Stop-loss / take-profit / trailing-stop orders awaiting triggers
+    advanced_orders: OrderManager,
+    /// Good-til-time expiries: expiry timestamp -> resting orders
+    order_expiries: BTreeMap<u64, Vec<(AssetId, OrderId)>>,
+    /// Persisted position/PnL snapshots (if storage enabled)
+    pnl_history: Option<PnlHistory>,
+    /// Account snapshot epoch length in seconds (0 = disabled)
+    snapshot_interval: u64,
+    /// Last epoch snapshotted
+    last_snapshot_epoch: Option<u64>,
+    /// Trading and liquidity mining rewards
+    emissions: EmissionsEngine,
+    /// Emissions epoch currently accumulating scores
+    emissions_epoch: Option<u64>,
+    /// Persisted order book depth snapshots (if storage enabled)
+    depth_history: Option<DepthHistory>,
+    /// Depth snapshot interval and levels
+    depth_config: DepthConfig,
+    /// Last depth snapshot slot (timestamp / interval)
+    last_depth_slot: Option<u64>,
+    /// Spread/depth SLA monitoring and market trading modes
+    liquidity_monitor: LiquidityMonitor,
+    /// Listed markets, including basket definitions of index perpetuals
+    listings: ListingRegistry,
+    /// ERC-20 tokens and decimals backing assets
+    tokens: TokenRegistry,
+    /// Per-asset leverage and notional limits
+    risk_engine: RiskEngine,
+    /// Listed option series, their books and positions
+    options: OptionsEngine,
+    /// Market-making vaults and their depositors' shares
+    vaults: VaultManager,
+    /// Liquidity pools routed to alongside the books
+    pools: PoolManager,
+    /// Best-execution router across books and pools
+    router: OrderRouter,
+    /// Markets matching in frequent batch auctions: asset -> interval in
+    /// seconds of block time (0 = every block)
+    batch_auctions: HashMap<AssetId, u64>,
+    /// Committed state snapshots served to queries
+    snapshots: SnapshotStore,
 }
 
+/// Default account snapshot epoch: one hour
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 3600;
+
 impl CoreStateMachine {
     /// Create a new in-memory state machine (no persistence)
     pub fn new() -> Self {
         Self {
             books: HashMap::new(),
-            balances: HashMap::new(),
+            balances: Arc::new(HashMap::new()),
             storage: None,
             checkpoint_mgr: None,
             history: None,
             current_height: 0,
             margin_engine: MarginEngine::new(MarginConfig::default()),
             liquidation_engine: LiquidationEngine::new(),
+            order_limits: OrderLimits::default(),
+            transfer_ledger: TransferLedger::new(),
+            sessions: SessionRegistry::new(),
+            oracle: OracleEngine::default(),
+            delistings: DelistingManager::new(),
+            fee_engine: FeeEngine::new(),
+            governance: Governance::default(),
+            emergency: EmergencyControls::new(),
+            scheduling_policy: SchedulingPolicy::default(),
+            rounding_policy: RoundingPolicy::default(),
+            chain_id: DEFAULT_CHAIN_ID,
+            pending_batch: None,
+            clock: ChainClock::new(),
+            block_start: None,
+            next_fill_seq: 0,
+            block_randomness: B256::ZERO,
+            funding_engine: FundingEngine::default(),
+            advanced_orders: OrderManager::new(),
+            order_expiries: BTreeMap::new(),
+            pnl_history: None,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            last_snapshot_epoch: None,
+            emissions: EmissionsEngine::default(),
+            emissions_epoch: None,
+            depth_history: None,
+            depth_config: DepthConfig::default(),
+            last_depth_slot: None,
+            liquidity_monitor: LiquidityMonitor::default(),
+            listings: ListingRegistry::new(),
+            tokens: TokenRegistry::new(),
+            risk_engine: RiskEngine::new(),
+            options: OptionsEngine::default(),
+            vaults: VaultManager::new(),
+            pools: PoolManager::new(),
+            router: OrderRouter::default(),
+            batch_auctions: HashMap::new(),
+            snapshots: SnapshotStore::default(),
         }
     }
     
@@ -50,13 +239,50 @@ impl CoreStateMachine {
     pub fn new_with_margin_config(config: MarginConfig) -> Self {
         Self {
             books: HashMap::new(),
-            balances: HashMap::new(),
+            balances: Arc::new(HashMap::new()),
             storage: None,
             checkpoint_mgr: None,
             history: None,
             current_height: 0,
             margin_engine: MarginEngine::new(config),
             liquidation_engine: LiquidationEngine::new(),
+            order_limits: OrderLimits::default(),
+            transfer_ledger: TransferLedger::new(),
+            sessions: SessionRegistry::new(),
+            oracle: OracleEngine::default(),
+            delistings: DelistingManager::new(),
+            fee_engine: FeeEngine::new(),
+            governance: Governance::default(),
+            emergency: EmergencyControls::new(),
+            scheduling_policy: SchedulingPolicy::default(),
+            rounding_policy: RoundingPolicy::default(),
+            chain_id: DEFAULT_CHAIN_ID,
+            pending_batch: None,
+            clock: ChainClock::new(),
+            block_start: None,
+            next_fill_seq: 0,
+            block_randomness: B256::ZERO,
+            funding_engine: FundingEngine::default(),
+            advanced_orders: OrderManager::new(),
+            order_expiries: BTreeMap::new(),
+            pnl_history: None,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            last_snapshot_epoch: None,
+            emissions: EmissionsEngine::default(),
+            emissions_epoch: None,
+            depth_history: None,
+            depth_config: DepthConfig::default(),
+            last_depth_slot: None,
+            liquidity_monitor: LiquidityMonitor::default(),
+            listings: ListingRegistry::new(),
+            tokens: TokenRegistry::new(),
+            risk_engine: RiskEngine::new(),
+            options: OptionsEngine::default(),
+            vaults: VaultManager::new(),
+            pools: PoolManager::new(),
+            router: OrderRouter::default(),
+            batch_auctions: HashMap::new(),
+            snapshots: SnapshotStore::default(),
         }
     }
     
@@ -65,21 +291,87 @@ impl CoreStateMachine {
         let storage = Arc::new(CoreStorage::new(storage_path)?);
         let checkpoint_mgr = CheckpointManager::new(storage.clone(), checkpoint_interval);
         let history = OrderHistory::new(storage.clone());
+        let pnl_history = PnlHistory::new(storage.clone());
+        let depth_history = DepthHistory::new(storage.clone());
         
         Ok(Self {
             books: HashMap::new(),
-            balances: HashMap::new(),
+            balances: Arc::new(HashMap::new()),
             storage: Some(storage),
             checkpoint_mgr: Some(checkpoint_mgr),
             history: Some(history),
             current_height: 0,
             margin_engine: MarginEngine::new(MarginConfig::default()),
             liquidation_engine: LiquidationEngine::new(),
+            order_limits: OrderLimits::default(),
+            transfer_ledger: TransferLedger::new(),
+            sessions: SessionRegistry::new(),
+            oracle: OracleEngine::default(),
+            delistings: DelistingManager::new(),
+            fee_engine: FeeEngine::new(),
+            governance: Governance::default(),
+            emergency: EmergencyControls::new(),
+            scheduling_policy: SchedulingPolicy::default(),
+            rounding_policy: RoundingPolicy::default(),
+            chain_id: DEFAULT_CHAIN_ID,
+            pending_batch: None,
+            clock: ChainClock::new(),
+            block_start: None,
+            next_fill_seq: 0,
+            block_randomness: B256::ZERO,
+            funding_engine: FundingEngine::default(),
+            advanced_orders: OrderManager::new(),
+            order_expiries: BTreeMap::new(),
+            pnl_history: Some(pnl_history),
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            last_snapshot_epoch: None,
+            emissions: EmissionsEngine::default(),
+            emissions_epoch: None,
+            depth_history: Some(depth_history),
+            depth_config: DepthConfig::default(),
+            last_depth_slot: None,
+            liquidity_monitor: LiquidityMonitor::default(),
+            listings: ListingRegistry::new(),
+            tokens: TokenRegistry::new(),
+            risk_engine: RiskEngine::new(),
+            options: OptionsEngine::default(),
+            vaults: VaultManager::new(),
+            pools: PoolManager::new(),
+            router: OrderRouter::default(),
+            batch_auctions: HashMap::new(),
+            snapshots: SnapshotStore::default(),
         })
     }
     
     /// Recover state from storage
     pub fn recover(&mut self) -> Result<Vec<AssetId>> {
+        // Storage only ever holds fully committed blocks
+        if let Some(storage) = &self.storage {
+            for (user, asset, amount) in storage.load_balances()? {
+                Arc::make_mut(&mut self.balances).insert((user, asset), amount);
+            }
+            if let Some(height) = storage.load_committed_height()? {
+                self.current_height = height;
+            }
+            self.next_fill_seq = storage.load_next_fill_seq()?;
+            self.transfer_ledger.restore_nonces(storage.load_transfer_nonces()?);
+            self.sessions.restore(storage.load_api_nonces()?, storage.load_session_keys()?);
+            self.emissions
+                .restore(storage.load_emission_epochs()?, storage.load_emission_claims()?);
+            let advanced_orders = storage.load_advanced_orders()?;
+            let state = storage.load_order_manager_state()?;
+            if !advanced_orders.is_empty() || state.is_some() {
+                self.advanced_orders = OrderManager::restore(advanced_orders, state.unwrap_or_default());
+            }
+            if let Some(options) = storage.load_options_state()? {
+                self.options.restore(options);
+            }
+            if let Some(vaults) = storage.load_vault_state()? {
+                self.vaults.restore(vaults);
+            }
+            self.oracle.restore_reporters(storage.load_oracle_reporters()?);
+        }
+        
         if let Some(checkpoint_mgr) = &self.checkpoint_mgr {
             // For now, we need to manually specify which assets to recover
             // In a real system, we would maintain a list of active assets in storage
@@ -89,8 +381,14 @@ impl CoreStateMachine {
             // Try to recover common assets (1-10 for example)
             for asset_id in 1..=10 {
                 let asset = AssetId(asset_id);
-                if let Ok(Some(_)) = checkpoint_mgr.get_latest_checkpoint(asset) {
+                if let Ok(Some(metadata)) = checkpoint_mgr.get_latest_checkpoint(asset) {
+                    // Resume the clock from the latest checkpoint so block
+                    // times stay monotonic across restarts
+                    if metadata.timestamp > self.clock.now() {
+                        self.clock = ChainClock::at(self.current_height, metadata.timestamp);
+                    }
                     let book = checkpoint_mgr.restore_book(asset)?;
+                    self.order_limits.rebuild_from_book(&book);
                     self.books.insert(asset, book);
                     recovered_assets.push(asset);
                 }
@@ -105,6 +403,7 @@ impl CoreStateMachine {
     /// Set current block height (for checkpointing)
     pub fn set_height(&mut self, height: u64) {
         self.current_height = height;
+        self.order_limits.on_new_block(height);
     }
     
     /// Get current block height
@@ -112,21 +411,349 @@ impl CoreStateMachine {
         self.current_height
     }
     
+    /// Engine clock (time of the last block begun)
+    pub fn clock(&self) -> &ChainClock {
+        &self.clock
+    }
+    
+    /// Begin a block at `height`.
+    ///
+    /// Until `commit_block`, storage writes from persistent operations are
+    /// buffered, so a crash mid-block leaves storage at the previous block.
+    pub fn begin_block(&mut self, height: u64) -> Result<()> {
+        if self.pending_batch.is_some() {
+            return Err(anyhow::anyhow!("Block already in progress"));
+        }
+        
+        self.set_height(height);
+        self.pending_batch = Some(StorageBatch::new());
+        Ok(())
+    }
+    
+    /// Commit all buffered writes of the current block in one atomic batch
+    pub fn commit_block(&mut self) -> Result<()> {
+        let mut batch = self
+            .pending_batch
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("No block in progress"))?;
+        
+        if let Some(storage) = &self.storage {
+            batch.set_committed_height(self.current_height);
+            storage.write_batch(batch)?;
+        }
+        self.block_start = None;
+        
+        self.snapshots.publish(self.capture_snapshot());
+        Ok(())
+    }
+    
+    /// Abandon the block in progress: drop its buffered writes and put the
+    /// in-memory state back to where it was before the block began
+    ///
+    /// Memory then matches storage, which stays at the last committed block,
+    /// so the block can be retried.
+    pub fn abort_block(&mut self) {
+        self.pending_batch = None;
+        if let Some(start) = self.block_start.take() {
+            self.restore_block_start(*start);
+        }
+    }
+    
+    /// Copy of the in-memory state, taken as a block begins
+    fn capture_block_start(&self) -> BlockStart {
+        BlockStart {
+            books: self.books.clone(),
+            balances: Arc::clone(&self.balances),
+            current_height: self.current_height,
+            margin_engine: self.margin_engine.clone(),
+            liquidation_engine: self.liquidation_engine.clone(),
+            order_limits: self.order_limits.clone(),
+            transfer_ledger: self.transfer_ledger.clone(),
+            sessions: self.sessions.clone(),
+            oracle: self.oracle.clone(),
+            delistings: self.delistings.clone(),
+            fee_engine: self.fee_engine.clone(),
+            governance: self.governance.clone(),
+            emergency: self.emergency.clone(),
+            scheduling_policy: self.scheduling_policy,
+            rounding_policy: self.rounding_policy,
+            clock: self.clock,
+            next_fill_seq: self.next_fill_seq,
+            block_randomness: self.block_randomness,
+            funding_engine: self.funding_engine.clone(),
+            advanced_orders: self.advanced_orders.clone(),
+            order_expiries: self.order_expiries.clone(),
+            snapshot_interval: self.snapshot_interval,
+            last_snapshot_epoch: self.last_snapshot_epoch,
+            emissions: self.emissions.clone(),
+            emissions_epoch: self.emissions_epoch,
+            depth_config: self.depth_config,
+            last_depth_slot: self.last_depth_slot,
+            liquidity_monitor: self.liquidity_monitor.clone(),
+            listings: self.listings.clone(),
+            tokens: self.tokens.clone(),
+            risk_engine: self.risk_engine.clone(),
+            options: self.options.clone(),
+            vaults: self.vaults.clone(),
+            pools: self.pools.clone(),
+            router: self.router.clone(),
+            batch_auctions: self.batch_auctions.clone(),
+        }
+    }
+    
+    fn restore_block_start(&mut self, start: BlockStart) {
+        let BlockStart {
+            books,
+            balances,
+            current_height,
+            margin_engine,
+            liquidation_engine,
+            order_limits,
+            transfer_ledger,
+            sessions,
+            oracle,
+            delistings,
+            fee_engine,
+            governance,
+            emergency,
+            scheduling_policy,
+            rounding_policy,
+            clock,
+            next_fill_seq,
+            block_randomness,
+            funding_engine,
+            advanced_orders,
+            order_expiries,
+            snapshot_interval,
+            last_snapshot_epoch,
+            emissions,
+            emissions_epoch,
+            depth_config,
+            last_depth_slot,
+            liquidity_monitor,
+            listings,
+            tokens,
+            risk_engine,
+            options,
+            vaults,
+            pools,
+            router,
+            batch_auctions,
+        } = start;
+        self.books = books;
+        self.balances = balances;
+        self.current_height = current_height;
+        self.margin_engine = margin_engine;
+        self.liquidation_engine = liquidation_engine;
+        self.order_limits = order_limits;
+        self.transfer_ledger = transfer_ledger;
+        self.sessions = sessions;
+        self.oracle = oracle;
+        self.delistings = delistings;
+        self.fee_engine = fee_engine;
+        self.governance = governance;
+        self.emergency = emergency;
+        self.scheduling_policy = scheduling_policy;
+        self.rounding_policy = rounding_policy;
+        self.clock = clock;
+        self.next_fill_seq = next_fill_seq;
+        self.block_randomness = block_randomness;
+        self.funding_engine = funding_engine;
+        self.advanced_orders = advanced_orders;
+        self.order_expiries = order_expiries;
+        self.snapshot_interval = snapshot_interval;
+        self.last_snapshot_epoch = last_snapshot_epoch;
+        self.emissions = emissions;
+        self.emissions_epoch = emissions_epoch;
+        self.depth_config = depth_config;
+        self.last_depth_slot = last_depth_slot;
+        self.liquidity_monitor = liquidity_monitor;
+        self.listings = listings;
+        self.tokens = tokens;
+        self.risk_engine = risk_engine;
+        self.options = options;
+        self.vaults = vaults;
+        self.pools = pools;
+        self.router = router;
+        self.batch_auctions = batch_auctions;
+    }
+    
+    /// Capture the in-memory state, for `restore_checkpoint` to roll back
+    /// to, e.g. around a block of EVM calls into core
+    pub fn checkpoint(&self) -> CoreCheckpoint {
+        CoreCheckpoint(Box::new(self.capture_block_start()))
+    }
+    
+    /// Put the in-memory state back to `checkpoint`, rewriting the stored
+    /// options, vault and oracle reporter state to match
+    ///
+    /// Callers into core that do not open core blocks use this to undo
+    /// their changes; a core block of their own is undone by `abort_block`.
+    pub fn restore_checkpoint(&mut self, checkpoint: CoreCheckpoint) -> Result<()> {
+        self.restore_block_start(*checkpoint.0);
+        self.persist_options()?;
+        self.persist_vaults()?;
+        self.persist_oracle_reporters()
+    }
+    
+    /// Handle to the committed state snapshots
+    ///
+    /// A snapshot is published at every `commit_block`. The handle can be
+    /// kept by the query layer and read from any thread.
+    pub fn snapshot_store(&self) -> SnapshotStore {
+        self.snapshots.clone()
+    }
+    
+    /// Capture the current state as an immutable snapshot
+    pub fn capture_snapshot(&self) -> StateSnapshot {
+        StateSnapshot::capture(
+            self.current_height,
+            self.clock.now(),
+            Arc::clone(&self.balances),
+            self.margin_engine.shared_positions(),
+            self.books.values(),
+            self.snapshots.config().book_depth,
+        )
+    }
+    
+    /// Check if a block is in progress
+    pub fn in_block(&self) -> bool {
+        self.pending_batch.is_some()
+    }
+    
+    /// Persist writes built by `f`: buffered into the open block, or
+    /// written as one atomic batch when no block is in progress
+    fn persist<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&Self, &mut StorageBatch) -> Result<()>,
+    {
+        let storage = match &self.storage {
+            Some(storage) => storage.clone(),
+            None => return Ok(()),
+        };
+        
+        match self.pending_batch.take() {
+            Some(mut batch) => {
+                let result = f(self, &mut batch);
+                self.pending_batch = Some(batch);
+                result
+            }
+            None => {
+                let mut batch = StorageBatch::new();
+                f(self, &mut batch)?;
+                storage.write_batch(batch)
+            }
+        }
+    }
+    
+    /// Reserve sequence numbers for `count` fills, returning the first
+    ///
+    /// The state machine is the only allocator; the counter reaches storage
+    /// in the same batch as the fills numbered from it.
+    fn reserve_fill_seqs(&mut self, count: usize) -> u64 {
+        let first = self.next_fill_seq;
+        self.next_fill_seq += count as u64;
+        first
+    }
+    
+    /// Add all storage effects of a trade: the taker's resting order,
+    /// fills (numbered from `first_seq`), maker order updates, and balances
+    /// of every party
+    fn write_trade_effects(
+        &self,
+        batch: &mut StorageBatch,
+        asset: AssetId,
+        taker_order: Option<OrderId>,
+        fills: &[Fill],
+        first_seq: u64,
+    ) -> Result<()> {
+        let book = self.books.get(&asset);
+        
+        if let Some(order) = taker_order.and_then(|id| book.and_then(|b| b.get_order(id))) {
+            batch.put_order(order)?;
+        }
+        
+        let mut parties = BTreeSet::new();
+        for (seq, fill) in (first_seq..).zip(fills) {
+            batch.put_fill(seq, fill)?;
+            
+            match book.and_then(|b| b.get_order(fill.order_id)) {
+                Some(maker_order) => batch.put_order(maker_order)?,
+                None => batch.delete_order(asset, fill.order_id),
+            }
+            
+            parties.insert(fill.maker);
+            parties.insert(fill.taker);
+        }
+        
+        for user in parties {
+            batch.put_balance(user, asset, self.get_balance(&user, asset))?;
+        }
+        if !fills.is_empty() {
+            batch.set_next_fill_seq(first_seq + fills.len() as u64);
+        }
+        
+        Ok(())
+    }
+    
     /// Checkpoint all order books if needed
+    ///
+    /// Advanced orders changed outside a block are flushed first, so the
+    /// checkpoint covers every trigger order.
     pub fn checkpoint_if_needed(&mut self) -> Result<Vec<AssetId>> {
-        if let Some(checkpoint_mgr) = &self.checkpoint_mgr {
-            if checkpoint_mgr.should_checkpoint(self.current_height) {
-                let mut checkpointed = Vec::new();
-                
-                for (asset, book) in &self.books {
-                    checkpoint_mgr.checkpoint_book(book, self.current_height)?;
-                    checkpointed.push(*asset);
+        if !self.in_block() {
+            self.persist_advanced_orders()?;
+        }
+        let due = self.checkpoint_mgr.as_ref().is_some_and(|m| m.should_checkpoint(self.current_height));
+        if !due {
+            return Ok(vec![]);
+        }
+        
+        // Inside a block the snapshots commit with the block's writes
+        self.persist(|sm, batch| {
+            if let Some(checkpoint_mgr) = &sm.checkpoint_mgr {
+                for book in sm.books.values() {
+                    checkpoint_mgr.checkpoint_book(batch, book, sm.current_height, sm.clock.now())?;
                 }
-                
-                return Ok(checkpointed);
             }
+            Ok(())
+        })?;
+        Ok(self.books.keys().copied().collect())
+    }
+    
+    /// Write the options engine state: series, books and positions
+    fn persist_options(&mut self) -> Result<()> {
+        self.persist(|sm, batch| batch.put_options_state(&sm.options.state()))
+    }
+    
+    /// Write the vault state: vaults, stats and shares
+    fn persist_vaults(&mut self) -> Result<()> {
+        self.persist(|sm, batch| batch.put_vault_state(&sm.vaults.state()))
+    }
+    
+    /// Write the oracle's whitelisted reporters
+    fn persist_oracle_reporters(&mut self) -> Result<()> {
+        self.persist(|sm, batch| batch.put_oracle_reporters(&sm.oracle.reporters()))
+    }
+    
+    /// Write advanced orders changed since the last call
+    fn persist_advanced_orders(&mut self) -> Result<()> {
+        let (ids, state_changed) = self.advanced_orders.take_dirty();
+        if ids.is_empty() && !state_changed {
+            return Ok(());
         }
-        Ok(vec![])
+        self.persist(|sm, batch| {
+            for id in ids {
+                match sm.advanced_orders.get_order(id) {
+                    Some(order) => batch.put_advanced_order(order)?,
+                    None => batch.delete_advanced_order(id),
+                }
+            }
+            if state_changed {
+                batch.put_order_manager_state(&sm.advanced_orders.state())?;
+            }
+            Ok(())
+        })
     }
     
     /// Get or create order book for asset
@@ -146,7 +773,20 @@ impl CoreStateMachine {
     
     /// Set user balance (for testing/initialization)
     pub fn set_balance(&mut self, user: Address, asset: AssetId, balance: U256) {
-        self.balances.insert((user, asset), balance);
+        Arc::make_mut(&mut self.balances).insert((user, asset), balance);
+    }
+    
+    /// Timestamp of an order placed now.
+    ///
+    /// Inside a block orders are stamped with the block time, whatever the
+    /// caller passed, so order priority and expiries never depend on a
+    /// node's wall clock.
+    fn order_timestamp(&self, timestamp: u64) -> u64 {
+        if self.in_block() {
+            self.clock.now()
+        } else {
+            timestamp
+        }
     }
     
     /// Place a limit order
@@ -159,6 +799,16 @@ impl CoreStateMachine {
         size: Size,
         timestamp: u64,
     ) -> Result<(OrderId, Vec<Fill>)> {
+        self.emergency.ensure_allowed(Operation::NewOrders, Some(asset))?;
+        self.check_delisting(&trader, asset, side, size)?;
+        let mode = self.liquidity_monitor.mode(asset);
+        if mode == MarketMode::PostOnly && self.would_cross(asset, side, price) {
+            return Err(anyhow::anyhow!("Market {} is post-only", asset.0));
+        }
+        
+        // Anti-spam: per-block rate and open-order caps
+        self.order_limits.check_order(&trader, asset)?;
+        
         // Validate balance (simplified - just check non-zero)
         let balance = self.get_balance(&trader, asset);
         if balance == U256::ZERO && size.0 > U256::ZERO {
@@ -166,15 +816,22 @@ impl CoreStateMachine {
             // Full margin system will be implemented in Phase 3.3
         }
         
+        let timestamp = self.order_timestamp(timestamp);
+        let batched = self.batch_auctions.contains_key(&asset);
         let book = self.get_or_create_book(asset);
-        let (order_id, fills) = MatchingEngine::execute_limit_order(
-            book,
-            trader,
-            side,
-            price,
-            size,
-            timestamp,
-        )?;
+        let (order_id, fills) = if mode == MarketMode::Auction || batched {
+            // Orders queue until the auction or next batch uncrosses
+            (book.add_limit_order(trader, side, price, size, timestamp), Vec::new())
+        } else {
+            MatchingEngine::execute_limit_order(book, trader, side, price, size, timestamp)?
+        };
+        let resting = book.contains_order(order_id);
+        
+        self.order_limits.record_submission(trader);
+        if resting {
+            self.order_limits.record_open(trader, asset);
+        }
+        self.track_fills(asset, &fills);
         
         // Apply fills to balances (simplified settlement)
         for fill in &fills {
@@ -196,34 +853,30 @@ impl CoreStateMachine {
     ) -> Result<(OrderId, Vec<Fill>)> {
         let (order_id, fills) = self.place_limit_order(trader, asset, side, price, size, timestamp)?;
         
-        // Persist order if storage is available
-        if let Some(storage) = &self.storage {
-            // Get mutable reference to the book
-            if let Some(book) = self.books.get_mut(&asset) {
-                // Find and store the order
-                if let Some(&(order_price, order_side)) = book.order_index_mut().get(&order_id) {
-                    let tree = match order_side {
-                        Side::Bid => book.bids_mut(),
-                        Side::Ask => book.asks_mut(),
-                    };
-                    
-                    if let Some(level) = tree.get(&order_price) {
-                        for order in &level.orders {
-                            if order.id == order_id {
-                                storage.store_order(order)?;
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-            
-            // Store fills using history manager
-            if let Some(history) = &self.history {
-                for fill in &fills {
-                    history.store_fill(fill)?;
-                }
-            }
+        // Persist resting order, fills, maker updates and balances together
+        let first_seq = self.reserve_fill_seqs(fills.len());
+        self.persist(|sm, batch| sm.write_trade_effects(batch, asset, Some(order_id), &fills, first_seq))?;
+        
+        Ok((order_id, fills))
+    }
+    
+    /// Place a limit entry with attached stop-loss and take-profit exits.
+    ///
+    /// Exit legs are created for whatever fills, immediately or while the
+    /// entry rests, and cancel each other when one triggers.
+    pub fn place_bracket_order(&mut self, request: &BracketOrderRequest) -> Result<(OrderId, Vec<Fill>)> {
+        let BracketOrderRequest { trader, asset, side, price, size, stop_loss, take_profit, timestamp } = request.clone();
+        OrderManager::validate_bracket(side, stop_loss, take_profit)?;
+        
+        let (order_id, fills) = self.place_limit_order_persistent(trader, asset, side, price, size, timestamp)?;
+        self.advanced_orders.attach_bracket(trader, asset, order_id, side, size, (stop_loss, take_profit))?;
+        
+        let filled = fills.iter().fold(U256::ZERO, |acc, f| acc + f.size.0);
+        if filled > U256::ZERO {
+            self.advanced_orders.on_entry_fill(asset, order_id, Size(filled), timestamp);
+        }
+        if !self.books.get(&asset).is_some_and(|b| b.contains_order(order_id)) {
+            self.advanced_orders.on_entry_closed(asset, order_id);
         }
         
         Ok((order_id, fills))
@@ -238,6 +891,20 @@ impl CoreStateMachine {
         size: Size,
         timestamp: u64,
     ) -> Result<Vec<Fill>> {
+        self.emergency.ensure_allowed(Operation::NewOrders, Some(asset))?;
+        self.check_delisting(&trader, asset, side, size)?;
+        let mode = self.liquidity_monitor.mode(asset);
+        if mode != MarketMode::Continuous {
+            return Err(anyhow::anyhow!("Market {} does not accept market orders in {:?} mode", asset.0, mode));
+        }
+        if self.batch_auctions.contains_key(&asset) {
+            return Err(anyhow::anyhow!("Market {} matches in batch auctions; use limit orders", asset.0));
+        }
+        
+        // Anti-spam: market orders never rest, so only the rate limit applies
+        self.order_limits.check_submission_rate(&trader)?;
+        
+        let timestamp = self.order_timestamp(timestamp);
         let book = self.get_or_create_book(asset);
         let fills = MatchingEngine::execute_market_order(
             book,
@@ -247,6 +914,9 @@ impl CoreStateMachine {
             timestamp,
         )?;
         
+        self.order_limits.record_submission(trader);
+        self.track_fills(asset, &fills);
+        
         // Apply fills to balances
         for fill in &fills {
             self.apply_fill(fill, asset);
@@ -266,12 +936,9 @@ impl CoreStateMachine {
     ) -> Result<Vec<Fill>> {
         let fills = self.place_market_order(trader, asset, side, size, timestamp)?;
         
-        // Store fills using history manager
-        if let Some(history) = &self.history {
-            for fill in &fills {
-                history.store_fill(fill)?;
-            }
-        }
+        // Persist fills, maker updates and balances together
+        let first_seq = self.reserve_fill_seqs(fills.len());
+        self.persist(|sm, batch| sm.write_trade_effects(batch, asset, None, &fills, first_seq))?;
         
         Ok(fills)
     }
@@ -283,7 +950,12 @@ impl CoreStateMachine {
             .get_mut(&asset)
             .ok_or_else(|| anyhow::anyhow!("Asset not found"))?;
         
-        book.cancel_order(order_id)
+        let order = book.cancel_order(order_id)?;
+        self.order_limits.record_closed(order.trader, asset);
+        self.advanced_orders.on_entry_closed(asset, order_id);
+        self.advanced_orders.on_spawned_closed(asset, order_id);
+        
+        Ok(order)
     }
     
     /// Cancel an order with persistence
@@ -291,9 +963,10 @@ impl CoreStateMachine {
         let order = self.cancel_order(asset, order_id)?;
         
         // Delete from storage
-        if let Some(storage) = &self.storage {
-            storage.delete_order(asset, order_id)?;
-        }
+        self.persist(|_, batch| {
+            batch.delete_order(asset, order_id);
+            Ok(())
+        })?;
         
         Ok(order)
     }
@@ -317,8 +990,97 @@ impl CoreStateMachine {
         self.books.get(&asset)
     }
     
+    /// Reject orders on halted markets and non-reducing orders on reduce-only
+    /// markets. The trader's resting orders on the same side count towards
+    /// the reduction, so stacked orders cannot flip the position.
+    fn check_delisting(&self, trader: &Address, asset: AssetId, side: Side, size: Size) -> Result<()> {
+        if self.delistings.is_halted(asset) {
+            return Err(anyhow::anyhow!("Market {} is delisted", asset.0));
+        }
+        
+        if self.delistings.is_reduce_only(asset) {
+            let position = self
+                .margin_engine
+                .get_position(trader, asset)
+                .map(|p| p.size)
+                .unwrap_or(0);
+            let reduces = match side {
+                Side::Bid => position < 0,
+                Side::Ask => position > 0,
+            };
+            let resting: U256 = self
+                .books
+                .get(&asset)
+                .map(|book| {
+                    book.orders()
+                        .filter(|o| o.trader == *trader && o.side == side)
+                        .map(|o| o.remaining().0)
+                        .fold(U256::ZERO, |total, remaining| total + remaining)
+                })
+                .unwrap_or_default();
+            if !reduces || size.0 + resting > U256::from(position.unsigned_abs()) {
+                return Err(anyhow::anyhow!("Market {} is reduce-only", asset.0));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Whether a limit order at `price` would match resting orders
+    fn would_cross(&self, asset: AssetId, side: Side, price: Price) -> bool {
+        let book = match self.books.get(&asset) {
+            Some(book) => book,
+            None => return false,
+        };
+        match side {
+            Side::Bid => book.best_ask().is_some_and(|ask| price >= ask),
+            Side::Ask => book.best_bid().is_some_and(|bid| price <= bid),
+        }
+    }
+    
+    /// Record the last trade price, and release open-order slots for maker
+    /// orders fully filled by these fills
+    fn track_fills(&mut self, asset: AssetId, fills: &[Fill]) {
+        if let Some(fill) = fills.last() {
+            self.advanced_orders.record_trade(asset, fill.price);
+        }
+        if let Some(book) = self.books.get(&asset) {
+            for fill in fills {
+                if !book.contains_order(fill.order_id) {
+                    self.order_limits.record_closed(fill.maker, asset);
+                }
+            }
+        }
+    }
+    
+    /// Get order flow limits
+    pub fn order_limits(&self) -> &OrderLimits {
+        &self.order_limits
+    }
+    
+    /// Update order flow limits configuration
+    pub fn set_order_limits_config(&mut self, config: OrderLimitsConfig) {
+        self.order_limits.set_config(config);
+    }
+    
+    /// Register a market maker for order limit exemptions
+    pub fn register_market_maker(&mut self, account: Address) {
+        self.order_limits.register_market_maker(account);
+    }
+    
     /// Apply a fill to user balances (simplified)
-    fn apply_fill(&mut self, fill: &Fill, _asset: AssetId) {
+    fn apply_fill(&mut self, fill: &Fill, asset: AssetId) {
+        // Resting bracket entries arm their exits as they fill, and orders
+        // spawned by triggers count toward their advanced order
+        self.advanced_orders.on_entry_fill(asset, fill.order_id, fill.size, fill.timestamp);
+        self.advanced_orders.on_spawned_fill(asset, fill.order_id, fill.size);
+        
+        // Score both sides for emissions by the fee they pay
+        let notional = fill.size.0.saturating_mul(U256::from(fill.price.0)) / U256::from(Price::SCALE);
+        let maker_fee = self.fee_engine.calculate_fee(&fill.maker, notional, true, fill.timestamp);
+        let taker_fee = self.fee_engine.calculate_fee(&fill.taker, notional, false, fill.timestamp);
+        self.emissions.record_fill(fill, maker_fee, taker_fee);
+        
         // Simplified balance updates
         // In a real system, this would handle:
         // - Base asset transfers
@@ -343,6 +1105,24 @@ impl CoreStateMachine {
         self.margin_engine.deposit(user, asset, amount)
     }
     
+    /// Deposit collateral given in the asset's token base units
+    ///
+    /// Returns the amount credited in engine precision.
+    pub fn deposit_token(&mut self, user: Address, asset: AssetId, token_amount: U256) -> Result<U256> {
+        let amount = self.tokens.to_canonical(asset, token_amount)?;
+        self.deposit_collateral(user, asset, amount)?;
+        Ok(amount)
+    }
+    
+    /// Withdraw collateral given in the asset's token base units
+    ///
+    /// Returns the amount debited in engine precision.
+    pub fn withdraw_token(&mut self, user: Address, asset: AssetId, token_amount: U256) -> Result<U256> {
+        let amount = self.tokens.to_canonical(asset, token_amount)?;
+        self.withdraw_collateral(user, asset, amount)?;
+        Ok(amount)
+    }
+    
     /// Withdraw collateral
     pub fn withdraw_collateral(
         &mut self,
@@ -350,7 +1130,35 @@ impl CoreStateMachine {
         asset: AssetId,
         amount: U256,
     ) -> Result<()> {
-        self.margin_engine.withdraw(user, asset, amount)
+        self.emergency.ensure_allowed(Operation::Withdrawals, Some(asset))?;
+        if asset == self.options.collateral_asset() {
+            // Short option margin stays deposited
+            let prices = self.option_underlying_prices();
+            let reserved = self.options.required_margin(&self.risk_engine, &user, &prices)?;
+            if self.margin_engine.get_deposit(&user, asset).saturating_sub(amount) < reserved {
+                return Err(anyhow::anyhow!("Withdrawal would undercollateralize short options"));
+            }
+        }
+        let marks = self.position_marks(&user);
+        self.margin_engine.withdraw_with_pnl(user, asset, amount, &marks)
+    }
+    
+    /// Undo a collateral deposit that was rolled back
+    ///
+    /// Skips the emergency and margin checks of `withdraw_collateral`; the
+    /// deposit being undone never happened as far as the caller is concerned.
+    pub fn revert_collateral_deposit(
+        &mut self,
+        user: Address,
+        asset: AssetId,
+        amount: U256,
+    ) -> Result<()> {
+        self.margin_engine.debit(user, asset, amount)
+    }
+    
+    /// Get deposited collateral
+    pub fn get_collateral(&self, user: &Address, asset: AssetId) -> U256 {
+        self.margin_engine.get_deposit(user, asset)
     }
     
     /// Place limit order with margin checks
@@ -363,6 +1171,8 @@ impl CoreStateMachine {
         size: Size,
         timestamp: u64,
     ) -> Result<(OrderId, Vec<Fill>)> {
+        self.check_basket_risk(&trader, asset, size, price)?;
+        
         // Check margin requirements
         let required_margin = self.margin_engine.calculate_required_margin(
             asset,
@@ -370,7 +1180,8 @@ impl CoreStateMachine {
             price,
         )?;
         
-        if !self.margin_engine.has_available_margin(&trader, required_margin)? {
+        let marks = self.position_marks(&trader);
+        if !self.margin_engine.has_available_margin_with_pnl(&trader, required_margin, &marks)? {
             return Err(anyhow::anyhow!("Insufficient margin"));
         }
         
@@ -414,6 +1225,7 @@ impl CoreStateMachine {
             Side::Bid => book.best_ask().unwrap_or(Price::from_float(1000000.0)),
             Side::Ask => book.best_bid().unwrap_or(Price::from_float(0.000001)),
         };
+        self.check_basket_risk(&trader, asset, size, estimated_price)?;
         
         let required_margin = self.margin_engine.calculate_required_margin(
             asset,
@@ -421,7 +1233,8 @@ impl CoreStateMachine {
             estimated_price,
         )?;
         
-        if !self.margin_engine.has_available_margin(&trader, required_margin)? {
+        let marks = self.position_marks(&trader);
+        if !self.margin_engine.has_available_margin_with_pnl(&trader, required_margin, &marks)? {
             return Err(anyhow::anyhow!("Insufficient margin"));
         }
         
@@ -447,6 +1260,28 @@ impl CoreStateMachine {
         Ok(fills)
     }
     
+    /// Hold index perpetual orders to the basket's derived risk limits
+    fn check_basket_risk(&self, trader: &Address, asset: AssetId, size: Size, price: Price) -> Result<()> {
+        if self.listings.basket(asset).is_none() {
+            return Ok(());
+        }
+        
+        let current = self.margin_engine
+            .get_position(trader, asset)
+            .map(|p| p.size.unsigned_abs())
+            .unwrap_or(0);
+        let new_size = current.saturating_add(size.0.saturating_to::<u64>());
+        self.risk_engine.check_order_risk(asset, new_size, price, 0)?;
+        
+        let notional = U256::from(new_size) * U256::from(price.0) / U256::from(Price::SCALE);
+        let equity = self.margin_engine.get_account_equity(trader).unwrap_or(U256::ZERO);
+        if equity.is_zero() {
+            return Err(anyhow::anyhow!("No collateral for index perpetual"));
+        }
+        let leverage = self.risk_engine.calculate_leverage(notional, equity);
+        self.risk_engine.check_tiered_leverage(asset, notional, leverage)
+    }
+    
     /// Get account equity
     pub fn get_account_equity(&self, user: &Address) -> Result<U256> {
         self.margin_engine.get_account_equity(user)
@@ -457,105 +1292,2264 @@ impl CoreStateMachine {
         self.margin_engine.get_position(user, asset)
     }
     
+    /// Get a user's open positions, by asset
+    pub fn get_user_positions(&self, user: &Address) -> Vec<Position> {
+        let mut positions: Vec<Position> = self
+            .margin_engine
+            .get_user_positions(user)
+            .into_iter()
+            .filter(|position| position.size != 0)
+            .cloned()
+            .collect();
+        positions.sort_by_key(|position| position.asset.0);
+        positions
+    }
+    
+    /// Set a user's margin mode; only possible without open positions
+    pub fn set_margin_mode(&mut self, user: Address, mode: MarginMode) -> Result<()> {
+        self.margin_engine.set_margin_mode(user, mode)
+    }
+    
+    /// Get a user's margin mode
+    pub fn get_margin_mode(&self, user: &Address) -> MarginMode {
+        self.margin_engine.get_margin_mode(user)
+    }
+    
     /// Check if account is healthy
     pub fn is_account_healthy(&self, user: &Address) -> Result<bool> {
         self.margin_engine.is_account_healthy(user)
     }
     
-    /// Check for liquidations
-    pub fn check_liquidations(
+    /// Transfer a position (and its isolated collateral) to another account.
+    /// Both parties must sign; the destination is re-checked for margin.
+    pub fn transfer_position(
         &mut self,
-        current_prices: &HashMap<AssetId, Price>,
+        signed: &SignedPositionTransfer,
         timestamp: u64,
-    ) -> Result<Vec<Liquidation>> {
-        // Get all users with collateral accounts
-        let users = self.margin_engine.get_users();
-        
-        let to_liquidate = self.liquidation_engine.check_liquidations(
-            &self.margin_engine,
-            &users,
-            current_prices,
+    ) -> Result<U256> {
+        signed.verify(self.chain_id)?;
+        self.transfer_ledger.check_nonce(&signed.transfer)?;
+        
+        let transfer = &signed.transfer;
+        let price = self
+            .margin_engine
+            .get_position(&transfer.from, transfer.asset)
+            .map(|p| p.entry_price)
+            .ok_or_else(|| anyhow::anyhow!("Position not found"))?;
+        
+        let collateral = self.margin_engine.transfer_position(
+            transfer.from,
+            transfer.to,
+            transfer.asset,
+            transfer.size,
             timestamp,
         )?;
         
-        let mut liquidations = Vec::new();
+        self.transfer_ledger.record_transfer(transfer, price, collateral, timestamp);
+        self.persist(|sm, batch| {
+            batch.put_transfer_nonce(transfer.from, sm.transfer_ledger.next_nonce(&transfer.from));
+            Ok(())
+        })?;
         
-        // Execute liquidations
-        for (user, asset) in to_liquidate {
-            if let Some(position) = self.margin_engine.get_position(&user, asset) {
-                let liq = self.liquidation_engine.liquidate_position(
-                    user,
-                    asset,
-                    position.size,
-                    position.entry_price,
-                    timestamp,
-                )?;
-                
-                // Close the position
-                self.margin_engine.update_position(
-                    user,
-                    asset,
-                    -position.size,
-                    position.entry_price,
-                    timestamp,
-                )?;
-                
-                liquidations.push(liq);
+        Ok(collateral)
+    }
+    
+    /// Get position transfer ledger
+    pub fn get_transfer_ledger(&self) -> &TransferLedger {
+        &self.transfer_ledger
+    }
+    
+    // ==================== Authorization ====================
+    
+    /// Get API nonces and session keys
+    pub fn sessions(&self) -> &SessionRegistry {
+        &self.sessions
+    }
+    
+    /// Authorize and execute a signed user action.
+    ///
+    /// Session keys may only act within their scope until they expire;
+    /// withdrawals and key management need the account's master key. The
+    /// nonce is consumed only if the action succeeds.
+    pub fn dispatch(&mut self, signed: &SignedAction) -> Result<ActionOutcome> {
+        self.sessions.authorize(self.chain_id, signed, self.clock.now())?;
+        
+        let account = signed.account;
+        let timestamp = self.clock.now();
+        let outcome = match signed.action.clone() {
+            CoreAction::PlaceLimitOrder { asset, side, price, size } => {
+                let (order_id, fills) =
+                    self.place_limit_order_persistent(account, asset, side, price, size, timestamp)?;
+                ActionOutcome::OrderPlaced { order_id, fills }
             }
-        }
+            CoreAction::PlaceMarketOrder { asset, side, size } => {
+                let fills = self.place_market_order_persistent(account, asset, side, size, timestamp)?;
+                ActionOutcome::MarketFilled { fills }
+            }
+            CoreAction::CancelOrder { asset, order_id } => {
+                self.emergency.ensure_allowed(Operation::Cancels, Some(asset))?;
+                self.ensure_order_owner(account, asset, order_id)?;
+                let order = self.cancel_order_persistent(asset, order_id)?;
+                ActionOutcome::OrderCancelled { order }
+            }
+            CoreAction::SetOrderExpiry { asset, order_id, expires_at } => {
+                self.ensure_order_owner(account, asset, order_id)?;
+                self.set_order_expiry(asset, order_id, expires_at)?;
+                ActionOutcome::OrderExpirySet
+            }
+            CoreAction::Withdraw { asset, amount } => {
+                self.withdraw_collateral(account, asset, amount)?;
+                ActionOutcome::Withdrawn
+            }
+            CoreAction::RegisterSessionKey { key, scope, expires_at } => {
+                self.sessions.register(account, SessionKey { key, scope, expires_at })?;
+                ActionOutcome::SessionKeyRegistered
+            }
+            CoreAction::RevokeSessionKey { key } => {
+                self.sessions.revoke(&account, &key)?;
+                ActionOutcome::SessionKeyRevoked
+            }
+        };
         
-        Ok(liquidations)
+        self.sessions.consume_nonce(account);
+        self.persist_session(account)?;
+        Ok(outcome)
     }
     
-    /// Get liquidation history
-    pub fn get_liquidations(&self) -> &[Liquidation] {
-        self.liquidation_engine.get_liquidations()
+    /// Persist an account's API nonce and session keys
+    fn persist_session(&mut self, account: Address) -> Result<()> {
+        self.persist(|sm, batch| {
+            batch.put_api_nonce(account, sm.sessions.next_nonce(&account));
+            batch.put_session_keys(account, sm.sessions.session_keys(&account))
+        })
     }
-}
-
-impl Default for CoreStateMachine {
-    fn default() -> Self {
-        Self::new()
+    
+    /// Dispatch a block's signed actions in the order the scheduling
+    /// policy gives them; outcomes are returned in payload order
+    pub fn dispatch_block(&mut self, actions: &[SignedAction]) -> Vec<Result<ActionOutcome>> {
+        let books = &self.books;
+        let schedule = self
+            .scheduling_policy
+            .schedule(actions, |asset, order_id| books.get(&asset).is_some_and(|b| b.get_order(order_id).is_some()));
+        
+        let mut outcomes: Vec<Option<Result<ActionOutcome>>> = actions.iter().map(|_| None).collect();
+        for index in schedule {
+            outcomes[index] = Some(self.dispatch(&actions[index]));
+        }
+        outcomes.into_iter().map(|outcome| outcome.expect("every action is scheduled")).collect()
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_create_state_machine() {
-        let sm = CoreStateMachine::new();
-        assert_eq!(sm.books.len(), 0);
+    
+    /// Get the block payload scheduling policy
+    pub fn scheduling_policy(&self) -> SchedulingPolicy {
+        self.scheduling_policy
     }
+    
+    /// Change how block payloads are ordered for dispatch (governance only)
+    pub fn set_scheduling_policy(&mut self, caller: &Address, policy: SchedulingPolicy) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.scheduling_policy = policy;
+        Ok(())
+    }
+    
+    /// Get the protocol rounding policy
+    pub fn rounding_policy(&self) -> RoundingPolicy {
+        self.rounding_policy
+    }
+    
+    /// Change how user credits and debits are rounded (governance only).
+    /// Policies that would round in users' favour are rejected.
+    pub fn set_rounding_policy(&mut self, caller: &Address, policy: RoundingPolicy) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        policy.validate()?;
+        self.fee_engine.set_rounding_policy(policy)?;
+        self.funding_engine.set_rounding_policy(policy)?;
+        self.pools.set_rounding_policy(policy)?;
+        self.router.set_rounding_policy(policy);
+        self.rounding_policy = policy;
+        Ok(())
+    }
+    
+    fn ensure_order_owner(&self, account: Address, asset: AssetId, order_id: OrderId) -> Result<()> {
+        let order = self
+            .books
+            .get(&asset)
+            .and_then(|b| b.get_order(order_id))
+            .ok_or_else(|| anyhow::anyhow!("Order {} not found", order_id))?;
+        if order.trader != account {
+            return Err(anyhow::anyhow!("Order {} belongs to another account", order_id));
+        }
+        Ok(())
+    }
+    
+    // ==================== Options ====================
+    
+    /// Get the options engine
+    pub fn options(&self) -> &OptionsEngine {
+        &self.options
+    }
+    
+    /// List an option series (governance only)
+    pub fn list_option_series(&mut self, caller: &Address, series: OptionSeries) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.options.list_series(series)?;
+        self.persist_options()
+    }
+    
+    /// Place an option order at block time, paying premiums in the options
+    /// collateral asset
+    ///
+    /// The trader's deposit (capped at their available margin) must cover
+    /// short option margin and the premium of their bids. Before an ask
+    /// matches, resting bids whose owners no longer hold the premium they
+    /// owe are cancelled, so an unfunded buyer cannot block sellers.
+    pub fn place_option_order(&mut self, order: OptionOrder) -> Result<(OrderId, Vec<Fill>)> {
+        self.emergency.ensure_allowed(Operation::NewOrders, Some(order.series))?;
+        let timestamp = self.clock.now();
+        let asset = self.options.collateral_asset();
+        let prices = self.option_underlying_prices();
+        let marks = self.position_marks(&order.trader);
+        let available = self
+            .margin_engine
+            .get_available_margin_with_pnl(&order.trader, &marks)
+            .unwrap_or_default();
+        let collateral = self.margin_engine.get_deposit(&order.trader, asset).min(available);
+        
+        if order.side == Side::Ask {
+            let margin = &self.margin_engine;
+            self.options.cancel_unfunded_bids(order.series, |user| margin.get_deposit(user, asset))?;
+        }
+        
+        let saved = self.options.clone();
+        let (order_id, fills) =
+            self.options.place_order(&self.risk_engine, order, &prices, collateral, timestamp)?;
+        
+        let flows = OptionsEngine::premium_flows(order.side, &fills);
+        for (user, amount) in &flows {
+            if *amount < 0 && self.margin_engine.get_deposit(user, asset) < U256::from(amount.unsigned_abs()) {
+                self.options = saved;
+                return Err(anyhow::anyhow!("Insufficient collateral for option premium"));
+            }
+        }
+        for (user, amount) in flows {
+            if amount >= 0 {
+                self.margin_engine.deposit(user, asset, U256::from(amount as u64))?;
+            } else {
+                self.margin_engine.debit(user, asset, U256::from(amount.unsigned_abs()))?;
+            }
+        }
+        
+        self.persist_options()?;
+        Ok((order_id, fills))
+    }
+    
+    /// Cancel a resting option order of `trader`
+    pub fn cancel_option_order(&mut self, trader: Address, series: AssetId, order_id: OrderId) -> Result<Order> {
+        self.emergency.ensure_allowed(Operation::Cancels, Some(series))?;
+        let owner = self
+            .options
+            .get_book(series)
+            .and_then(|book| book.get_order(order_id))
+            .map(|order| order.trader);
+        if owner != Some(trader) {
+            return Err(anyhow::anyhow!("Order {} not found for trader", order_id));
+        }
+        let order = self.options.cancel_order(series, order_id)?;
+        self.persist_options()?;
+        Ok(order)
+    }
+    
+    /// Mark prices of the option underlyings that have one
+    fn option_underlying_prices(&self) -> HashMap<AssetId, Price> {
+        let timestamp = self.clock.now();
+        self.options
+            .underlyings()
+            .into_iter()
+            .filter_map(|asset| Some((asset, self.mark_price(asset, timestamp)?)))
+            .collect()
+    }
+    
+    /// Cash-settle expired option series into the options collateral asset
+    ///
+    /// Writers pay what their collateral deposit covers and holders share
+    /// any shortfall pro rata, so settlement never mints collateral.
+    fn settle_options(&mut self, timestamp: u64) -> Result<Vec<OptionSettlement>> {
+        let asset = self.options.collateral_asset();
+        let margin = &self.margin_engine;
+        let settlements = self
+            .options
+            .settle_expired(&self.oracle, timestamp, |user| margin.get_deposit(user, asset))?;
+        for settlement in &settlements {
+            self.margin_engine.settle_cash(settlement.user, asset, settlement.payout)?;
+        }
+        if !settlements.is_empty() {
+            self.persist_options()?;
+        }
+        Ok(settlements)
+    }
+    
+    // ==================== Vaults ====================
+    
+    /// Get the market-making vaults
+    pub fn vaults(&self) -> &VaultManager {
+        &self.vaults
+    }
+    
+    /// Create a vault funded with `collateral` of `asset` from the owner's
+    /// deposit, returning its ID; the owner gets shares at par
+    pub fn create_vault(
+        &mut self,
+        owner: Address,
+        manager: Address,
+        asset: AssetId,
+        profit_share_bps: u64,
+        collateral: U256,
+        timestamp: u64,
+    ) -> Result<VaultId> {
+        self.withdraw_collateral(owner, asset, collateral)?;
+        let created = self.vaults.create_vault(
+            owner,
+            manager,
+            VaultStrategy::Custom,
+            collateral,
+            profit_share_bps,
+            timestamp,
+        );
+        let vault_id = match created {
+            Ok(vault_id) => vault_id,
+            Err(e) => {
+                self.margin_engine.deposit(owner, asset, collateral)?;
+                return Err(e);
+            }
+        };
+        self.persist_vaults()?;
+        Ok(vault_id)
+    }
+    
+    /// Move `amount` of the user's `asset` deposit into a vault, returning
+    /// the shares minted
+    pub fn deposit_to_vault(
+        &mut self,
+        vault_id: VaultId,
+        user: Address,
+        asset: AssetId,
+        amount: U256,
+        timestamp: u64,
+    ) -> Result<U256> {
+        if self.vaults.get_vault(vault_id).is_none() {
+            return Err(anyhow::anyhow!("Vault not found"));
+        }
+        self.withdraw_collateral(user, asset, amount)?;
+        let shares = match self.vaults.deposit_for_shares(vault_id, user, amount, timestamp) {
+            Ok(shares) => shares,
+            Err(e) => {
+                self.margin_engine.deposit(user, asset, amount)?;
+                return Err(e);
+            }
+        };
+        self.persist_vaults()?;
+        Ok(shares)
+    }
+    
+    /// Redeem the user's vault shares into their `asset` deposit, returning
+    /// the amount paid out
+    pub fn withdraw_from_vault(
+        &mut self,
+        vault_id: VaultId,
+        user: Address,
+        asset: AssetId,
+        shares: U256,
+        timestamp: u64,
+    ) -> Result<U256> {
+        let saved = self.vaults.clone();
+        let amount = self.vaults.redeem_shares(vault_id, user, shares, timestamp)?;
+        if let Err(e) = self.margin_engine.deposit(user, asset, amount) {
+            self.vaults = saved;
+            return Err(e);
+        }
+        self.persist_vaults()?;
+        Ok(amount)
+    }
+    
+    /// Mark a vault's equity to market
+    pub fn update_vault_equity(&mut self, vault_id: VaultId, equity: U256, timestamp: u64) -> Result<()> {
+        self.vaults.update_equity(vault_id, equity, timestamp)?;
+        self.persist_vaults()
+    }
+    
+    /// Replace the vault state, e.g. to roll back vault calls of a failed
+    /// block; collateral moved by those calls is the caller's to restore
+    pub fn restore_vaults(&mut self, state: VaultState) -> Result<()> {
+        self.vaults.restore(state);
+        self.persist_vaults()
+    }
+    
+    // ==================== Delisting ====================
+    
+    /// Simulate an order without mutating state: expected fills, fees,
+    /// post-trade margin usage and estimated liquidation price
+    pub fn simulate_order(&self, request: &OrderSimulationRequest) -> Result<OrderSimulation> {
+        self.emergency.ensure_allowed(Operation::NewOrders, Some(request.asset))?;
+        self.check_delisting(&request.trader, request.asset, request.side, request.size)?;
+        
+        OrderSimulator::simulate(
+            self.books.get(&request.asset),
+            &self.margin_engine,
+            &self.fee_engine,
+            request,
+        )
+    }
+    
+    /// Plan a taker order across the book and the asset's liquidity pools,
+    /// without mutating state. Pools quote around the mark price.
+    pub fn plan_route(&self, asset: AssetId, side: Side, size: Size, limit_price: Option<Price>) -> RoutePlan {
+        let pools = self.pools.get_asset_pools(asset);
+        let reference = self.mark_price(asset, self.clock.now());
+        self.router.plan(self.books.get(&asset), &pools, side, size, limit_price, reference)
+    }
+    
+    /// Execute a taker order split across the book and liquidity pools by
+    /// the router. The book share executes as a market order of that size;
+    /// pool fills move the pool's inventory and credit their fee to it.
+    pub fn route_order(
+        &mut self,
+        trader: Address,
+        asset: AssetId,
+        side: Side,
+        size: Size,
+        limit_price: Option<Price>,
+        timestamp: u64,
+    ) -> Result<RoutedOrder> {
+        if size.0.is_zero() {
+            return Err(anyhow::anyhow!("Order size must be positive"));
+        }
+        self.emergency.ensure_allowed(Operation::NewOrders, Some(asset))?;
+        self.check_delisting(&trader, asset, side, size)?;
+        
+        let plan = self.plan_route(asset, side, size, limit_price);
+        let book_size = plan.venue_size(Venue::Book);
+        let book_fills = if book_size.0.is_zero() {
+            Vec::new()
+        } else {
+            self.place_market_order_persistent(trader, asset, side, book_size, timestamp)?
+        };
+        
+        for fill in &plan.fills {
+            if let Venue::Pool(pool_id) = fill.venue {
+                self.pools.record_fill(pool_id, side, fill.price, fill.size)?;
+                self.pools.distribute_fees(pool_id, fill.fee)?;
+            }
+        }
+        
+        Ok(RoutedOrder { plan, book_fills })
+    }
+    
+    /// Get liquidity pools
+    pub fn pools(&self) -> &PoolManager {
+        &self.pools
+    }
+    
+    /// Get mutable liquidity pools (to create pools and add liquidity)
+    pub fn pools_mut(&mut self) -> &mut PoolManager {
+        &mut self.pools
+    }
+    
+    /// An LP token holder's fees, impermanent loss (inventory at the
+    /// current mark) and fee APR over the last `epochs` account snapshot
+    /// epochs
+    pub fn pool_lp_report(&self, pool_id: PoolId, holder: Address, epochs: usize) -> Result<LpPoolReport> {
+        let pool = self.pools.get_pool(pool_id).ok_or_else(|| anyhow::anyhow!("Pool not found"))?;
+        let mark = self.mark_price(pool.asset, self.clock.now());
+        self.pools.lp_report(pool_id, holder, mark, epochs)
+    }
+    
+    /// Update router venue fees
+    pub fn set_router_config(&mut self, config: RouterConfig) {
+        self.router = OrderRouter::new(config);
+        self.router.set_rounding_policy(self.rounding_policy);
+    }
+    
+    /// Stress-test a price shock from the current marks: which accounts
+    /// would be liquidated, how far their flow would move the books, and how
+    /// much bad debt the insurance balance and ADL would absorb. Read-only.
+    pub fn stress_test(&self, scenario: &StressScenario) -> Result<StressReport> {
+        let mut marks = HashMap::new();
+        for asset in self.books.keys().copied().chain(self.margin_engine.get_open_assets()) {
+            if let Some(price) = self.mark_price(asset, self.clock.now()) {
+                marks.insert(asset, price);
+            }
+        }
+        
+        StressTester::run(
+            &self.margin_engine,
+            &self.books,
+            &marks,
+            self.fee_engine.destination_balance(FeeDestination::Insurance),
+            scenario,
+        )
+    }
+    
+    /// List a market (governance only). Index perpetuals mark against the
+    /// book blended with their basket index, fund against the basket index,
+    /// and take risk limits derived from their components.
+    pub fn list_market(&mut self, caller: &Address, listing: Listing) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        let asset = listing.asset;
+        let basket = match &listing.kind {
+            ListingKind::Basket(basket) => Some(basket.clone()),
+            ListingKind::Perpetual => None,
+        };
+        self.listings.list(listing)?;
+        
+        if let Some(basket) = basket {
+            self.risk_engine.set_basket_limits(asset, &basket);
+            self.oracle.set_price_source(asset, PriceSource::Weighted);
+        }
+        Ok(())
+    }
+    
+    /// Get the listing registry
+    pub fn listings(&self) -> &ListingRegistry {
+        &self.listings
+    }
+    
+    /// Register the ERC-20 token backing an asset (governance only)
+    pub fn register_token(&mut self, caller: &Address, token: TokenInfo) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.tokens.register(token)
+    }
+    
+    /// Get the token registry
+    pub fn tokens(&self) -> &TokenRegistry {
+        &self.tokens
+    }
+    
+    /// Get risk engine
+    pub fn risk_engine(&self) -> &RiskEngine {
+        &self.risk_engine
+    }
+    
+    /// Get mutable risk engine (to set per-asset limits)
+    pub fn risk_engine_mut(&mut self) -> &mut RiskEngine {
+        &mut self.risk_engine
+    }
+    
+    /// Publish each basket index as its perpetual's oracle and index price,
+    /// from fresh component oracle prices (falling back to index prices).
+    /// Baskets with a missing component keep their previous price.
+    fn update_basket_prices(&mut self, timestamp: u64) -> Result<()> {
+        let oracle = &self.oracle;
+        let prices: Vec<_> = self.listings
+            .baskets()
+            .filter_map(|(asset, basket)| {
+                let index = basket.index_price(|component| {
+                    oracle
+                        .get_external_price(component, timestamp)
+                        .or_else(|| oracle.get_index_price(component))
+                })?;
+                Some((asset, index))
+            })
+            .collect();
+        
+        for (asset, index) in prices {
+            self.oracle.set_index_price(asset, index);
+            self.oracle.update_price(asset, index, timestamp)?;
+        }
+        Ok(())
+    }
+    
+    /// Get fee engine
+    pub fn fee_engine(&self) -> &FeeEngine {
+        &self.fee_engine
+    }
+    
+    /// Get mutable fee engine
+    pub fn fee_engine_mut(&mut self) -> &mut FeeEngine {
+        &mut self.fee_engine
+    }
+    
+    /// Set the chain ID signatures are bound to (genesis configuration)
+    pub fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = chain_id;
+    }
+    
+    /// Chain ID signatures are bound to
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+    
+    /// Set governance authorities (genesis configuration)
+    pub fn set_governance(&mut self, governance: Governance) {
+        self.governance = governance;
+    }
+    
+    /// Get governance authorities
+    pub fn governance(&self) -> &Governance {
+        &self.governance
+    }
+    
+    /// Get mutable governance (authority changes are checked against the caller)
+    pub fn governance_mut(&mut self) -> &mut Governance {
+        &mut self.governance
+    }
+    
+    /// Get emergency controls and their audit log
+    pub fn emergency_controls(&self) -> &EmergencyControls {
+        &self.emergency
+    }
+    
+    /// Audit trail records stamped at or after `since`, for export
+    pub fn audit_records(&self, since: u64) -> Vec<ExportRecord> {
+        let audit = self.emergency.audit_log().iter().cloned().map(ExportRecord::Audit);
+        let ledger = self.transfer_ledger.entries().iter().cloned().map(ExportRecord::Ledger);
+        let funding = self.funding_engine.get_settlements().iter().cloned().map(ExportRecord::FundingSettlement);
+        let mut records: Vec<ExportRecord> = audit.chain(ledger).chain(funding).filter(|r| r.timestamp() >= since).collect();
+        records.sort_by_key(ExportRecord::timestamp);
+        records
+    }
+    
+    /// Set how many authorities must approve an emergency action
+    /// (governance only)
+    pub fn set_emergency_threshold(&mut self, caller: &Address, threshold: usize) -> Result<()> {
+        self.emergency.set_threshold(&self.governance, caller, threshold)
+    }
+    
+    /// Propose an emergency action (governance only). It takes effect once
+    /// the approval threshold is met, immediately for a threshold of one.
+    pub fn propose_emergency_action(&mut self, caller: &Address, action: EmergencyAction) -> Result<u64> {
+        let (height, now) = (self.current_height, self.clock.now());
+        self.emergency.propose(&self.governance, caller, action, height, now)
+    }
+    
+    /// Approve a pending emergency action (governance only); returns
+    /// whether it executed
+    pub fn approve_emergency_action(&mut self, caller: &Address, proposal_id: u64) -> Result<bool> {
+        let (height, now) = (self.current_height, self.clock.now());
+        self.emergency.approve(&self.governance, caller, proposal_id, height, now)
+    }
+    
+    /// Change how fees are split at block settlement (governance only)
+    pub fn set_fee_routing(&mut self, caller: &Address, routing: FeeRouting) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.fee_engine.set_routing(routing)
+    }
+    
+    /// Pay settled fees from a destination into `recipient`'s collateral
+    /// (governance only)
+    pub fn claim_fees(
+        &mut self,
+        caller: &Address,
+        destination: FeeDestination,
+        recipient: Address,
+        asset: AssetId,
+        amount: U256,
+    ) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.fee_engine.claim(destination, amount)?;
+        self.margin_engine.deposit(recipient, asset, amount)
+    }
+    
+    /// Get emissions engine
+    pub fn emissions(&self) -> &EmissionsEngine {
+        &self.emissions
+    }
+    
+    /// Get mutable emissions engine
+    pub fn emissions_mut(&mut self) -> &mut EmissionsEngine {
+        &mut self.emissions
+    }
+    
+    /// Claim finalized epoch rewards with a Merkle proof, crediting the
+    /// reward asset balance
+    pub fn claim_rewards(
+        &mut self,
+        user: Address,
+        epoch: u64,
+        amount: U256,
+        proof: &[B256],
+    ) -> Result<()> {
+        self.emissions.claim(epoch, user, amount, proof)?;
+        let asset = self.emissions.config().reward_asset;
+        let balance = self.get_balance(&user, asset).saturating_add(amount);
+        Arc::make_mut(&mut self.balances).insert((user, asset), balance);
+        
+        self.persist(|_, batch| {
+            batch.put_emission_claim(epoch, user)?;
+            batch.put_balance(user, asset, balance)
+        })
+    }
+    
+    /// Credit makers quoting both sides for the time since the last block,
+    /// then finalize the emissions epoch if `timestamp` starts a new one
+    fn advance_emissions(&mut self, timestamp: u64) -> Result<Option<u64>> {
+        let elapsed = self.clock.elapsed();
+        if elapsed > 0 {
+            let mut two_sided = BTreeSet::new();
+            for book in self.books.values() {
+                let mut bids = BTreeSet::new();
+                let mut asks = BTreeSet::new();
+                for order in book.orders() {
+                    match order.side {
+                        Side::Bid => bids.insert(order.trader),
+                        Side::Ask => asks.insert(order.trader),
+                    };
+                }
+                two_sided.extend(bids.intersection(&asks).copied());
+            }
+            for maker in two_sided {
+                self.emissions.record_uptime(maker, elapsed);
+            }
+        }
+        
+        let epoch = self.emissions.epoch_of(timestamp);
+        let open = *self.emissions_epoch.get_or_insert(epoch);
+        if open >= epoch {
+            return Ok(None);
+        }
+        
+        let rewards = self.emissions.finalize_epoch(open)?.clone();
+        self.persist(|_, batch| batch.put_emission_epoch(&rewards))?;
+        self.emissions_epoch = Some(epoch);
+        Ok(Some(open))
+    }
+    
+    /// Update external oracle price (used for settlement TWAP)
+    pub fn update_oracle_price(&mut self, asset: AssetId, price: Price, timestamp: u64) -> Result<()> {
+        self.oracle.update_price(asset, price, timestamp)
+    }
+    
+    /// Allow `reporter` to post prices for `asset` (governance only)
+    pub fn authorize_oracle_reporter(&mut self, caller: &Address, asset: AssetId, reporter: Address) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.oracle.authorize_reporter(asset, reporter);
+        self.persist_oracle_reporters()
+    }
+    
+    /// Stop `reporter` posting prices for `asset` (governance only)
+    pub fn revoke_oracle_reporter(&mut self, caller: &Address, asset: AssetId, reporter: &Address) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.oracle.revoke_reporter(asset, reporter);
+        self.persist_oracle_reporters()
+    }
+    
+    /// Whether `reporter` may post prices for `asset`
+    pub fn is_oracle_reporter(&self, asset: AssetId, reporter: &Address) -> bool {
+        self.oracle.is_reporter(asset, reporter)
+    }
+    
+    /// Latest external oracle price, if fresh at `timestamp`
+    pub fn get_oracle_price(&self, asset: AssetId, timestamp: u64) -> Option<Price> {
+        self.oracle.get_external_price(asset, timestamp)
+    }
+    
+    /// Time-weighted average external oracle price over the `window`
+    /// seconds before `timestamp`
+    pub fn get_oracle_twap(&self, asset: AssetId, window: u64, timestamp: u64) -> Result<Price> {
+        self.oracle.get_twap(asset, window, timestamp)
+    }
+    
+    /// Mark price used for margin, from the book mid and oracle
+    pub fn get_mark_price(&self, asset: AssetId, timestamp: u64) -> Option<Price> {
+        self.mark_price(asset, timestamp)
+    }
+    
+    /// Index (spot reference) price used for funding
+    pub fn get_index_price(&self, asset: AssetId) -> Option<Price> {
+        self.oracle.get_index_price(asset)
+    }
+    
+    /// Announce delisting of a market at the current height (governance
+    /// only). The market immediately becomes reduce-only.
+    pub fn announce_delisting(
+        &mut self,
+        caller: &Address,
+        asset: AssetId,
+        params: DelistingParams,
+    ) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.delistings.announce(asset, self.current_height, params)
+    }
+    
+    /// Get delisting schedules
+    pub fn delistings(&self) -> &DelistingManager {
+        &self.delistings
+    }
+    
+    /// Execute delisting steps due at the current height, returning those
+    /// executed
+    ///
+    /// A settlement whose TWAP is not available yet is skipped, and retried
+    /// at the next block; the other steps still run.
+    pub fn process_delistings(&mut self, timestamp: u64) -> Result<Vec<DelistingAction>> {
+        let mut settlement_prices = HashMap::new();
+        let mut actions = self.delistings.due_actions(self.current_height);
+        actions.retain(|action| {
+            let DelistingAction::Settle(asset) = *action else {
+                return true;
+            };
+            let window = self
+                .delistings
+                .get(asset)
+                .map(|d| d.params.twap_window)
+                .unwrap_or_default();
+            match self.oracle.get_twap(asset, window, timestamp) {
+                Ok(price) => {
+                    settlement_prices.insert(asset, price);
+                    true
+                }
+                Err(_) => false,
+            }
+        });
+        
+        for action in &actions {
+            match *action {
+                DelistingAction::CancelOrders(asset) => {
+                    let order_ids: Vec<OrderId> = self
+                        .books
+                        .get(&asset)
+                        .map(|book| book.orders().map(|o| o.id).collect())
+                        .unwrap_or_default();
+                    for order_id in order_ids {
+                        self.cancel_order(asset, order_id)?;
+                    }
+                    self.delistings.mark_halted(asset)?;
+                }
+                DelistingAction::Settle(asset) => {
+                    let price = settlement_prices[&asset];
+                    
+                    let mut users = self.margin_engine.get_users();
+                    users.sort();
+                    for user in users {
+                        let size = self
+                            .margin_engine
+                            .get_position(&user, asset)
+                            .map(|p| p.size)
+                            .unwrap_or(0);
+                        if size != 0 {
+                            self.margin_engine
+                                .update_position(user, asset, -size, price, timestamp)?;
+                        }
+                    }
+                    self.delistings.mark_settled(asset, price)?;
+                }
+            }
+        }
+        
+        Ok(actions)
+    }
+    
+    /// Check for liquidations
+    pub fn check_liquidations(
+        &mut self,
+        current_prices: &HashMap<AssetId, Price>,
+        timestamp: u64,
+    ) -> Result<Vec<Liquidation>> {
+        // Get all users with collateral accounts
+        let mut users = self.margin_engine.get_users();
+        users.sort();
+        
+        let mut to_liquidate = self.liquidation_engine.check_liquidations(
+            &self.margin_engine,
+            &users,
+            current_prices,
+            timestamp,
+        )?;
+        // Deterministic execution order regardless of map iteration
+        to_liquidate.sort_by_key(|(user, asset)| (*user, asset.0));
+        
+        let mut liquidations = Vec::new();
+        
+        // Execute liquidations
+        for (user, asset) in to_liquidate {
+            if let Some(position) = self.margin_engine.get_position(&user, asset) {
+                let liq = self.liquidation_engine.liquidate_position(
+                    user,
+                    asset,
+                    position.size,
+                    position.entry_price,
+                    timestamp,
+                )?;
+                
+                // Close the position
+                self.margin_engine.update_position(
+                    user,
+                    asset,
+                    -position.size,
+                    position.entry_price,
+                    timestamp,
+                )?;
+                
+                liquidations.push(liq);
+            }
+        }
+        
+        Ok(liquidations)
+    }
+    
+    /// Get liquidation history
+    pub fn get_liquidations(&self) -> &[Liquidation] {
+        self.liquidation_engine.get_liquidations()
+    }
+    
+    // ==================== Block Scheduling ====================
+    
+    /// Expire a resting order at `expires_at` (good-til-time)
+    pub fn set_order_expiry(&mut self, asset: AssetId, order_id: OrderId, expires_at: u64) -> Result<()> {
+        let resting = self.books.get(&asset).is_some_and(|b| b.contains_order(order_id));
+        if !resting {
+            return Err(anyhow::anyhow!("Order {} is not resting", order_id));
+        }
+        if self.clock.height() > 0 && self.clock.is_past(expires_at) {
+            return Err(anyhow::anyhow!("Expiry {} is not after block time {}", expires_at, self.clock.now()));
+        }
+        
+        self.order_expiries.entry(expires_at).or_default().push((asset, order_id));
+        Ok(())
+    }
+    
+    /// Set index (spot reference) price used for funding
+    pub fn set_index_price(&mut self, asset: AssetId, price: Price) {
+        self.oracle.set_index_price(asset, price);
+    }
+    
+    /// Get funding engine
+    pub fn funding_engine(&self) -> &FundingEngine {
+        &self.funding_engine
+    }
+    
+    /// Get mutable funding engine
+    pub fn funding_engine_mut(&mut self) -> &mut FundingEngine {
+        &mut self.funding_engine
+    }
+    
+    /// Change funding parameters (governance only). The change phases in
+    /// over several funding intervals with bounded per-interval steps.
+    pub fn set_funding_config(&mut self, caller: &Address, config: FundingConfig) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.funding_engine.schedule_config(config, self.clock.now())
+    }
+    
+    /// Get advanced order manager
+    pub fn advanced_orders(&self) -> &OrderManager {
+        &self.advanced_orders
+    }
+    
+    /// Get mutable advanced order manager (to place stop/take-profit orders)
+    pub fn advanced_orders_mut(&mut self) -> &mut OrderManager {
+        &mut self.advanced_orders
+    }
+    
+    /// Set account snapshot epoch length in seconds (0 disables snapshots)
+    pub fn set_snapshot_interval(&mut self, interval: u64) {
+        self.snapshot_interval = interval;
+    }
+    
+    /// Get persisted position/PnL history (None without storage)
+    pub fn pnl_history(&self) -> Option<&PnlHistory> {
+        self.pnl_history.as_ref()
+    }
+    
+    /// Set how often and how deep order book depth is snapshotted
+    pub fn set_depth_config(&mut self, config: DepthConfig) {
+        self.depth_config = config;
+    }
+    
+    /// Get an asset's persisted funding rounds in `[from, to]` (empty
+    /// without storage)
+    pub fn funding_history(&self, asset: AssetId, from: u64, to: u64) -> Result<Vec<FundingRecord>> {
+        match &self.storage {
+            Some(storage) => storage.load_funding_records(asset, from, to),
+            None => Ok(vec![]),
+        }
+    }
+    
+    /// Get persisted order book depth history (None without storage)
+    pub fn depth_history(&self) -> Option<&DepthHistory> {
+        self.depth_history.as_ref()
+    }
+    
+    /// Get the liquidity monitor (published spread, depth and uptime)
+    pub fn liquidity_monitor(&self) -> &LiquidityMonitor {
+        &self.liquidity_monitor
+    }
+    
+    /// Get a market's spread, depth, uptime and trading mode
+    pub fn liquidity_stats(&self, asset: AssetId) -> Option<LiquidityStats> {
+        self.liquidity_monitor.stats(asset)
+    }
+    
+    /// Set a market's liquidity SLA (governance only)
+    pub fn set_liquidity_sla(&mut self, caller: &Address, asset: AssetId, sla: LiquiditySla) -> Result<()> {
+        self.governance.ensure_authorized(caller)?;
+        self.liquidity_monitor.set_sla(asset, sla);
+        Ok(())
+    }
+    
+    /// Force a market's trading mode (governance only). Leaving auction mode
+    /// uncrosses the book; returns the auction fills.
+    pub fn set_market_mode(&mut self, caller: &Address, asset: AssetId, mode: MarketMode) -> Result<Vec<Fill>> {
+        self.governance.ensure_authorized(caller)?;
+        let previous = self.liquidity_monitor.mode(asset);
+        self.liquidity_monitor.set_mode(asset, mode);
+        
+        if previous == MarketMode::Auction && mode != MarketMode::Auction {
+            return self.uncross_auction(asset, self.clock.now());
+        }
+        Ok(Vec::new())
+    }
+    
+    /// Match a market in frequent batch auctions (governance only): orders
+    /// rest without matching and the book uncrosses at a single price at
+    /// the end of each block crossing an `interval`-second boundary
+    /// (0 = every block). `None` returns the market to continuous matching,
+    /// uncrossing the pending batch; returns its fills.
+    pub fn set_batch_auction(&mut self, caller: &Address, asset: AssetId, interval: Option<u64>) -> Result<Vec<Fill>> {
+        self.governance.ensure_authorized(caller)?;
+        match interval {
+            Some(interval) => {
+                self.batch_auctions.insert(asset, interval);
+                Ok(Vec::new())
+            }
+            None if self.batch_auctions.remove(&asset).is_some() => self.uncross_auction(asset, self.clock.now()),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// Batch auction interval of a market, if it matches in batches
+    pub fn batch_auction_interval(&self, asset: AssetId) -> Option<u64> {
+        self.batch_auctions.get(&asset).copied()
+    }
+    
+    /// Uncross batch auction markets whose interval elapsed this block.
+    /// Markets in auction mode wait for the auction to end.
+    fn run_batch_auctions(&mut self, timestamp: u64) -> Result<Vec<BatchAuction>> {
+        let mut assets: Vec<_> = self.batch_auctions
+            .iter()
+            .filter(|(_, interval)| **interval == 0 || self.clock.crossed_interval(**interval))
+            .map(|(asset, _)| *asset)
+            .filter(|asset| self.liquidity_monitor.mode(*asset) != MarketMode::Auction)
+            .collect();
+        assets.sort_by_key(|a| a.0);
+        
+        let mut auctions = Vec::new();
+        for asset in assets {
+            let fills = self.uncross_auction(asset, timestamp)?;
+            if let Some(fill) = fills.first() {
+                auctions.push(BatchAuction { asset, clearing_price: fill.price, fills });
+            }
+        }
+        Ok(auctions)
+    }
+    
+    /// Match a book crossed during an auction at its single clearing price
+    fn uncross_auction(&mut self, asset: AssetId, timestamp: u64) -> Result<Vec<Fill>> {
+        let book = match self.books.get_mut(&asset) {
+            Some(book) => book,
+            None => return Ok(Vec::new()),
+        };
+        let price = match book.auction_clearing_price() {
+            Some((price, _)) => price,
+            None => return Ok(Vec::new()),
+        };
+        
+        // Bids are the takers of the auction, so track them separately
+        let bids: Vec<(OrderId, Address)> = book.levels(Side::Bid)
+            .take_while(|level| level.price >= price)
+            .flat_map(|level| book.level_orders(Side::Bid, level.price))
+            .map(|order| (order.id, order.trader))
+            .collect();
+        let fills = book.uncross(price, timestamp);
+        
+        self.track_fills(asset, &fills);
+        for (order_id, trader) in &bids {
+            if !self.books[&asset].contains_order(*order_id) {
+                self.order_limits.record_closed(*trader, asset);
+            }
+        }
+        for fill in &fills {
+            self.apply_fill(fill, asset);
+        }
+        
+        let first_seq = self.reserve_fill_seqs(fills.len());
+        self.persist(|sm, batch| {
+            sm.write_trade_effects(batch, asset, None, &fills, first_seq)?;
+            for (order_id, _) in &bids {
+                match sm.books[&asset].get_order(*order_id) {
+                    Some(order) => batch.put_order(order)?,
+                    None => batch.delete_order(asset, *order_id),
+                }
+            }
+            Ok(())
+        })?;
+        
+        Ok(fills)
+    }
+    
+    /// Sample every market against its liquidity SLA and apply mode changes
+    fn monitor_liquidity(&mut self, timestamp: u64) -> Result<Vec<MarketModeChange>> {
+        let mut assets: Vec<_> = self.books.keys().copied().collect();
+        assets.sort_by_key(|a| a.0);
+        
+        let mut changes = Vec::new();
+        for asset in assets {
+            self.liquidity_monitor.sample(&self.books[&asset], timestamp);
+            let previous = self.liquidity_monitor.mode(asset);
+            let mode = match self.liquidity_monitor.evaluate(asset) {
+                Some(mode) => mode,
+                None => continue,
+            };
+            
+            let auction_fills = if previous == MarketMode::Auction {
+                self.uncross_auction(asset, timestamp)?
+            } else {
+                Vec::new()
+            };
+            changes.push(MarketModeChange { asset, mode, auction_fills });
+        }
+        
+        Ok(changes)
+    }
+    
+    /// Set the consensus randomness beacon of the block in progress.
+    ///
+    /// Called by the orchestrator before `on_block_begin`; seeds
+    /// deterministic tie-breaking such as ADL queue ordering.
+    pub fn set_block_randomness(&mut self, randomness: B256) {
+        self.block_randomness = randomness;
+    }
+    
+    /// Get the randomness beacon of the block in progress
+    pub fn block_randomness(&self) -> B256 {
+        self.block_randomness
+    }
+    
+    /// Snapshot every account, and the performance of every liquidity pool,
+    /// once per epoch, at the first block of the epoch
+    fn snapshot_accounts(&mut self, timestamp: u64) -> Result<Option<u64>> {
+        if self.snapshot_interval == 0 {
+            return Ok(None);
+        }
+        let epoch = self.clock.interval_index(self.snapshot_interval);
+        if self.last_snapshot_epoch.is_some_and(|last| last >= epoch) {
+            return Ok(None);
+        }
+        
+        // Open assets for positions, listed assets for pool inventory
+        let mut marks = HashMap::new();
+        for asset in self.books.keys().copied().chain(self.margin_engine.get_open_assets()) {
+            if let Some(price) = self.mark_price(asset, timestamp) {
+                marks.insert(asset, price);
+            }
+        }
+        
+        let snapshots: Vec<_> = self.margin_engine
+            .get_accounts()
+            .into_iter()
+            .map(|user| AccountSnapshot::capture(&self.margin_engine, user, epoch, timestamp, &marks))
+            .collect();
+        let pool_stats = self.pools.capture_epoch(epoch, timestamp, |asset| marks.get(&asset).copied());
+        self.persist(|_, batch| {
+            for snapshot in &snapshots {
+                batch.put_account_snapshot(snapshot)?;
+            }
+            for stats in &pool_stats {
+                batch.put_pool_epoch_stats(stats)?;
+            }
+            Ok(())
+        })?;
+        
+        self.last_snapshot_epoch = Some(epoch);
+        Ok(Some(epoch))
+    }
+    
+    /// Persist the depth of every book once per interval, at the first
+    /// block end of the interval
+    fn snapshot_depth(&mut self, timestamp: u64) -> Result<Option<u64>> {
+        let DepthConfig { interval, levels } = self.depth_config;
+        if interval == 0 || self.storage.is_none() {
+            return Ok(None);
+        }
+        let slot = self.clock.interval_index(interval);
+        if self.last_depth_slot.is_some_and(|last| last >= slot) {
+            return Ok(None);
+        }
+        
+        let mut assets: Vec<_> = self.books.keys().copied().collect();
+        assets.sort_by_key(|a| a.0);
+        self.persist(|sm, batch| {
+            for asset in &assets {
+                batch.put_depth_snapshot(&DepthSnapshot::capture(&sm.books[asset], timestamp, levels));
+            }
+            Ok(())
+        })?;
+        
+        self.last_depth_slot = Some(slot);
+        Ok(Some(timestamp))
+    }
+    
+    /// Current mark prices of a user's open positions, for counting their
+    /// unrealized PnL toward margin
+    fn position_marks(&self, user: &Address) -> HashMap<AssetId, Price> {
+        let timestamp = self.clock.now();
+        self.margin_engine
+            .get_user_positions(user)
+            .into_iter()
+            .filter(|p| p.size != 0)
+            .filter_map(|p| Some((p.asset, self.mark_price(p.asset, timestamp)?)))
+            .collect()
+    }
+    
+    /// Mark price for an asset from the book mid and oracle
+    fn mark_price(&self, asset: AssetId, timestamp: u64) -> Option<Price> {
+        let book_mid = self.books.get(&asset).and_then(|b| b.get_mid_price());
+        self.oracle.get_mark_price(asset, book_mid, timestamp).ok()
+    }
+    
+    /// Cancel good-til-time orders that expired at or before `timestamp`
+    fn sweep_expired_orders(&mut self, timestamp: u64) -> Result<Vec<(AssetId, OrderId)>> {
+        let pending = self.order_expiries.split_off(&timestamp.saturating_add(1));
+        let due = std::mem::replace(&mut self.order_expiries, pending);
+        
+        let mut expired = Vec::new();
+        for (asset, order_id) in due.into_values().flatten() {
+            // Skip orders already filled or cancelled
+            if self.books.get(&asset).is_some_and(|b| b.contains_order(order_id)) {
+                self.cancel_order_persistent(asset, order_id)?;
+                expired.push((asset, order_id));
+            }
+        }
+        
+        Ok(expired)
+    }
+    
+    /// Execute advanced orders whose triggers fired at their reference
+    /// price (last trade, mark or index)
+    fn execute_triggered_orders(&mut self, timestamp: u64) -> Vec<TriggeredOrder> {
+        let mut assets: Vec<_> = self.books.keys().copied().collect();
+        assets.sort_by_key(|a| a.0);
+        
+        let mut executed = Vec::new();
+        for asset in assets {
+            let prices = TriggerPrices {
+                last_trade: self.advanced_orders.last_trade_price(asset),
+                mark: self.mark_price(asset, timestamp),
+                index: self.oracle.get_index_price(asset),
+            };
+            if prices == TriggerPrices::default() {
+                continue;
+            }
+            
+            let mut ids = self.advanced_orders.check_triggers_at(asset, &prices);
+            ids.sort_unstable();
+            
+            for id in ids {
+                let order = match self.advanced_orders.get_order(id) {
+                    Some(order) => order.clone(),
+                    None => continue,
+                };
+                let execution_price = match order.order_type {
+                    AdvancedOrderType::StopLoss { execution_price, .. }
+                    | AdvancedOrderType::TakeProfit { execution_price, .. }
+                    | AdvancedOrderType::IfTouched { execution_price, .. } => execution_price,
+                    AdvancedOrderType::TrailingStop { .. } | AdvancedOrderType::BracketExit { .. } => None,
+                };
+                
+                // Limit executions may rest; the advanced order then tracks
+                // its book order until done
+                let result = match execution_price {
+                    Some(limit) => self
+                        .place_limit_order_persistent(order.user, asset, order.side, limit, order.remaining(), timestamp)
+                        .map(|(book_order, fills)| {
+                            let resting = self.books.get(&asset).is_some_and(|b| b.contains_order(book_order));
+                            (resting.then_some(book_order), fills)
+                        }),
+                    None => self
+                        .place_market_order_persistent(order.user, asset, order.side, order.remaining(), timestamp)
+                        .map(|fills| (None, fills)),
+                };
+                match &result {
+                    Ok((resting, fills)) => {
+                        let filled = fills.iter().fold(U256::ZERO, |acc, f| acc + f.size.0);
+                        self.advanced_orders.on_triggered_executed(id, Size(filled), *resting);
+                    }
+                    Err(_) => self.advanced_orders.remove_triggered(id),
+                }
+                let result = result.map(|(_, fills)| fills);
+                
+                executed.push(TriggeredOrder {
+                    id,
+                    asset,
+                    result: result.map_err(|e| e.to_string()),
+                });
+            }
+        }
+        
+        executed
+    }
+    
+    /// Accrue funding for every asset with open positions and an index price,
+    /// recording each funding round
+    ///
+    /// Cross margin accounts get their funding across all due assets netted
+    /// into one collateral adjustment; isolated positions accrue theirs
+    /// to the position.
+    fn accrue_funding(&mut self, timestamp: u64) -> Result<FundingRound> {
+        let mut markets = Vec::new();
+        let mut records = Vec::new();
+        self.funding_engine.step_config(timestamp);
+        
+        for asset in self.margin_engine.get_open_assets() {
+            let (mark, index) = match (self.mark_price(asset, timestamp), self.oracle.get_index_price(asset)) {
+                (Some(mark), Some(index)) => (mark, index),
+                _ => continue,
+            };
+            
+            let due = self.funding_engine.is_funding_due(asset, timestamp);
+            let rate = self.funding_engine.update_rate(asset, mark, index, timestamp)?;
+            if due {
+                records.push(FundingRecord { asset, timestamp, rate, mark_price: mark, index_price: index });
+                markets.push((asset, mark));
+            }
+        }
+        
+        let positions: Vec<(Address, AssetId, i64)> = markets
+            .iter()
+            .flat_map(|&(asset, _)| {
+                self.margin_engine
+                    .get_asset_positions(asset)
+                    .into_iter()
+                    .map(move |(user, size)| (user, asset, size))
+            })
+            .collect();
+        let margin_engine = &self.margin_engine;
+        let round = self.funding_engine.settle_round(
+            &markets,
+            &positions,
+            |user| margin_engine.get_margin_mode(user) == MarginMode::Cross,
+            timestamp,
+        );
+        for payment in &round.payments {
+            self.margin_engine.accrue_realized_pnl(payment.user, payment.asset, payment.amount);
+        }
+        let settlement_asset = self.funding_engine.settlement_asset();
+        for settlement in &round.settlements {
+            self.margin_engine.apply_funding(settlement.user, settlement_asset, settlement.amount)?;
+        }
+        
+        records.sort_by_key(|r| r.asset.0);
+        self.persist(|_, batch| {
+            for record in &records {
+                batch.put_funding_record(record)?;
+            }
+            Ok(())
+        })?;
+        
+        Ok(round)
+    }
+    
+    /// Scheduled work of `on_block_begin` once the block is open
+    fn run_block_begin(&mut self, height: u64, timestamp: u64) -> Result<BlockBeginReport> {
+        let emissions_epoch = self.advance_emissions(timestamp)?;
+        for account in self.sessions.prune_expired(timestamp) {
+            self.persist_session(account)?;
+        }
+        
+        let expired_orders = self.sweep_expired_orders(timestamp)?;
+        let delisting_actions = self.process_delistings(timestamp)?;
+        // Settlements still due are waiting for a TWAP
+        let deferred_settlements = self
+            .delistings
+            .due_actions(height)
+            .into_iter()
+            .filter_map(|action| match action {
+                DelistingAction::Settle(asset) => Some(asset),
+                DelistingAction::CancelOrders(_) => None,
+            })
+            .collect();
+        
+        Ok(BlockBeginReport {
+            height,
+            expired_orders,
+            delisting_actions,
+            deferred_settlements,
+            emissions_epoch,
+        })
+    }
+    
+    /// Scheduled work of `on_block_end`, ending with the commit
+    fn run_block_end(&mut self) -> Result<BlockEndReport> {
+        let timestamp = self.clock.now();
+        
+        self.update_basket_prices(timestamp)?;
+        let batch_auctions = self.run_batch_auctions(timestamp)?;
+        let triggered_orders = self.execute_triggered_orders(timestamp);
+        let funding = self.accrue_funding(timestamp)?;
+        let option_settlements = self.settle_options(timestamp)?;
+        
+        let mut prices = HashMap::new();
+        for asset in self.margin_engine.get_open_assets() {
+            if let Some(price) = self.mark_price(asset, timestamp) {
+                prices.insert(asset, price);
+            }
+        }
+        let liquidations = self.check_liquidations(&prices, timestamp)?;
+        let snapshot_epoch = self.snapshot_accounts(timestamp)?;
+        let market_mode_changes = self.monitor_liquidity(timestamp)?;
+        let depth_snapshot = self.snapshot_depth(timestamp)?;
+        let fee_settlement = self.fee_engine.settle_fees();
+        self.persist_advanced_orders()?;
+        
+        self.commit_block()?;
+        
+        Ok(BlockEndReport {
+            height: self.current_height,
+            triggered_orders,
+            funding_payments: funding.payments,
+            funding_settlements: funding.settlements,
+            option_settlements,
+            liquidations,
+            snapshot_epoch,
+            depth_snapshot,
+            market_mode_changes,
+            batch_auctions,
+            fee_settlement,
+        })
+    }
+}
+
+impl BlockHooks for CoreStateMachine {
+    /// Open the block and advance the clock, then sweep expired orders and
+    /// run due delisting steps
+    ///
+    /// If a step fails the block is aborted, so the next block can begin.
+    fn on_block_begin(&mut self, height: u64, timestamp: u64) -> Result<BlockBeginReport> {
+        let start = self.capture_block_start();
+        // Open the block first, so a block already in progress leaves the
+        // clock untouched
+        self.begin_block(height)?;
+        self.block_start = Some(Box::new(start));
+        
+        let report = self
+            .clock
+            .advance(height, timestamp)
+            .and_then(|_| self.run_block_begin(height, timestamp));
+        if report.is_err() {
+            self.abort_block();
+        }
+        report
+    }
+    
+    /// Refresh basket indices, then run triggers, funding, option expiry
+    /// settlement, margin and liquidity monitoring, account and depth
+    /// snapshots, then persist advanced order changes and commit the block
+    ///
+    /// If a step fails the block is aborted, so the next block can begin.
+    fn on_block_end(&mut self) -> Result<BlockEndReport> {
+        if !self.in_block() {
+            return Err(anyhow::anyhow!("No block in progress"));
+        }
+        
+        let report = self.run_block_end();
+        if report.is_err() {
+            self.abort_block();
+        }
+        report
+    }
+}
+
+impl Default for CoreStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_state_machine() {
+        let sm = CoreStateMachine::new();
+        assert_eq!(sm.books.len(), 0);
+    }
+
+    #[test]
+    fn test_set_and_get_balance() {
+        let mut sm = CoreStateMachine::new();
+        let user = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        sm.set_balance(user, asset, U256::from(1000));
+        assert_eq!(sm.get_balance(&user, asset), U256::from(1000));
+    }
+
+    #[test]
+    fn test_get_balance_nonexistent() {
+        let sm = CoreStateMachine::new();
+        let user = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        assert_eq!(sm.get_balance(&user, asset), U256::ZERO);
+    }
+
+    #[test]
+    fn test_place_limit_order() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        let (order_id, fills) = sm
+            .place_limit_order(
+                trader,
+                asset,
+                Side::Bid,
+                Price::from_float(1.0),
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+        
+        assert!(order_id > 0);
+        assert_eq!(fills.len(), 0); // No fills on empty book
+        
+        // Check order is in book
+        let book = sm.get_book(asset).unwrap();
+        assert_eq!(book.best_bid(), Some(Price::from_float(1.0)));
+    }
+
+    #[test]
+    fn test_place_market_order() {
+        let mut sm = CoreStateMachine::new();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        // Add liquidity
+        sm.place_limit_order(
+            maker,
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        // Execute market order
+        let fills = sm
+            .place_market_order(
+                taker,
+                asset,
+                Side::Bid,
+                Size(U256::from(50)),
+                1,
+            )
+            .unwrap();
+        
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].size.0, U256::from(50));
+        assert_eq!(fills[0].maker, maker);
+        assert_eq!(fills[0].taker, taker);
+    }
+
+    #[test]
+    fn test_cancel_order() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        let (order_id, _) = sm
+            .place_limit_order(
+                trader,
+                asset,
+                Side::Bid,
+                Price::from_float(1.0),
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+        
+        let order = sm.cancel_order(asset, order_id).unwrap();
+        assert_eq!(order.id, order_id);
+        
+        // Order should be removed from book
+        let book = sm.get_book(asset).unwrap();
+        assert_eq!(book.best_bid(), None);
+    }
+
+    #[test]
+    fn test_cancel_nonexistent_order() {
+        let mut sm = CoreStateMachine::new();
+        let asset = AssetId(1);
+        
+        let result = sm.cancel_order(asset, 999);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_get_snapshot() {
+        let mut sm = CoreStateMachine::new();
+        let asset = AssetId(1);
+        
+        // Add orders
+        sm.place_limit_order(
+            Address::from([1u8; 20]),
+            asset,
+            Side::Bid,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        sm.place_limit_order(
+            Address::from([2u8; 20]),
+            asset,
+            Side::Ask,
+            Price::from_float(1.01),
+            Size(U256::from(150)),
+            1,
+        )
+        .unwrap();
+        
+        let snapshot = sm.get_snapshot(asset, 10).unwrap();
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.asks.len(), 1);
+        assert_eq!(snapshot.bids[0].0, Price::from_float(1.0));
+        assert_eq!(snapshot.asks[0].0, Price::from_float(1.01));
+    }
+
+    #[test]
+    fn test_multiple_assets() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset1 = AssetId(1);
+        let asset2 = AssetId(2);
+        
+        // Add orders to different assets
+        sm.place_limit_order(
+            trader,
+            asset1,
+            Side::Bid,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        sm.place_limit_order(
+            trader,
+            asset2,
+            Side::Bid,
+            Price::from_float(2.0),
+            Size(U256::from(200)),
+            1,
+        )
+        .unwrap();
+        
+        // Check both books exist independently
+        let book1 = sm.get_book(asset1).unwrap();
+        let book2 = sm.get_book(asset2).unwrap();
+        
+        assert_eq!(book1.best_bid(), Some(Price::from_float(1.0)));
+        assert_eq!(book2.best_bid(), Some(Price::from_float(2.0)));
+    }
+
+    #[test]
+    fn test_limit_order_with_immediate_fill() {
+        let mut sm = CoreStateMachine::new();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        // Add ask
+        sm.place_limit_order(
+            maker,
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        // Add crossing bid
+        let (order_id, fills) = sm
+            .place_limit_order(
+                taker,
+                asset,
+                Side::Bid,
+                Price::from_float(1.0),
+                Size(U256::from(50)),
+                1,
+            )
+            .unwrap();
+        
+        assert!(order_id > 0);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].size.0, U256::from(50));
+        
+        // Remaining ask should be in book
+        let book = sm.get_book(asset).unwrap();
+        assert_eq!(book.depth_at_price(Price::from_float(1.0), Side::Ask), U256::from(50));
+    }
+
+    #[test]
+    fn test_market_order_empty_book() {
+        let mut sm = CoreStateMachine::new();
+        let taker = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        let fills = sm
+            .place_market_order(
+                taker,
+                asset,
+                Side::Bid,
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+        
+        assert_eq!(fills.len(), 0);
+    }
+
+    #[test]
+    fn test_get_nonexistent_snapshot() {
+        let sm = CoreStateMachine::new();
+        let snapshot = sm.get_snapshot(AssetId(999), 10);
+        assert!(snapshot.is_none());
+    }
+
+    #[test]
+    fn test_get_nonexistent_book() {
+        let sm = CoreStateMachine::new();
+        let book = sm.get_book(AssetId(999));
+        assert!(book.is_none());
+    }
+
+    #[test]
+    fn test_multiple_fills_single_order() {
+        let mut sm = CoreStateMachine::new();
+        let asset = AssetId(1);
+        
+        // Add multiple ask orders
+        sm.place_limit_order(
+            Address::from([1u8; 20]),
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(50)),
+            0,
+        )
+        .unwrap();
+        
+        sm.place_limit_order(
+            Address::from([2u8; 20]),
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(50)),
+            1,
+        )
+        .unwrap();
+        
+        // Market buy that hits both
+        let fills = sm
+            .place_market_order(
+                Address::from([10u8; 20]),
+                asset,
+                Side::Bid,
+                Size(U256::from(100)),
+                2,
+            )
+            .unwrap();
+        
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].maker, Address::from([1u8; 20]));
+        assert_eq!(fills[1].maker, Address::from([2u8; 20]));
+    }
+
+    #[test]
+    fn test_order_id_increments() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        let (id1, _) = sm
+            .place_limit_order(
+                trader,
+                asset,
+                Side::Bid,
+                Price::from_float(1.0),
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+        
+        let (id2, _) = sm
+            .place_limit_order(
+                trader,
+                asset,
+                Side::Bid,
+                Price::from_float(0.99),
+                Size(U256::from(100)),
+                1,
+            )
+            .unwrap();
+        
+        assert_eq!(id2, id1 + 1);
+    }
+
+    // Persistence tests
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_db_path() -> String {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
+        format!("/tmp/openliquid_test_sm_{}_{}", timestamp, counter)
+    }
+
+    #[test]
+    fn test_create_with_storage() {
+        let path = temp_db_path();
+        let sm = CoreStateMachine::new_with_storage(&path, 100);
+        assert!(sm.is_ok());
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_persistent_limit_order() {
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        let (order_id, fills) = sm
+            .place_limit_order_persistent(
+                trader,
+                asset,
+                Side::Bid,
+                Price::from_float(1.0),
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+        
+        assert!(order_id > 0);
+        assert_eq!(fills.len(), 0);
+        
+        // Check order is in book
+        let book = sm.get_book(asset).unwrap();
+        assert_eq!(book.best_bid(), Some(Price::from_float(1.0)));
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_persistent_market_order() {
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        // Add liquidity
+        sm.place_limit_order_persistent(
+            maker,
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        // Execute market order
+        let fills = sm
+            .place_market_order_persistent(
+                taker,
+                asset,
+                Side::Bid,
+                Size(U256::from(50)),
+                1,
+            )
+            .unwrap();
+        
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].size.0, U256::from(50));
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_cancel_order_persistent() {
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        let (order_id, _) = sm
+            .place_limit_order_persistent(
+                trader,
+                asset,
+                Side::Bid,
+                Price::from_float(1.0),
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+        
+        let order = sm.cancel_order_persistent(asset, order_id).unwrap();
+        assert_eq!(order.id, order_id);
+        
+        // Order should be removed from book
+        let book = sm.get_book(asset).unwrap();
+        assert_eq!(book.best_bid(), None);
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_checkpoint_if_needed() {
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        // Add an order
+        sm.place_limit_order_persistent(
+            trader,
+            asset,
+            Side::Bid,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        // Set height to checkpoint height
+        sm.set_height(100);
+        
+        // Should checkpoint
+        let checkpointed = sm.checkpoint_if_needed().unwrap();
+        assert_eq!(checkpointed.len(), 1);
+        assert_eq!(checkpointed[0], asset);
+        
+        // Inside a block the checkpoint is written only when the block commits
+        let latest = |sm: &CoreStateMachine| {
+            sm.checkpoint_mgr.as_ref().unwrap().get_latest_checkpoint(asset).unwrap().unwrap().height
+        };
+        sm.begin_block(200).unwrap();
+        assert_eq!(sm.checkpoint_if_needed().unwrap(), vec![asset]);
+        assert_eq!(latest(&sm), 100);
+        sm.abort_block();
+        assert_eq!(latest(&sm), 100);
+        sm.begin_block(200).unwrap();
+        sm.checkpoint_if_needed().unwrap();
+        sm.commit_block().unwrap();
+        assert_eq!(latest(&sm), 200);
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_recover_from_storage() {
+        let path = temp_db_path();
+        
+        // Create and populate
+        {
+            let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+            let trader = Address::from([1u8; 20]);
+            let asset = AssetId(1);
+            
+            sm.place_limit_order_persistent(
+                trader,
+                asset,
+                Side::Bid,
+                Price::from_float(1.0),
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+            
+            sm.set_height(100);
+            sm.checkpoint_if_needed().unwrap();
+        }
+        
+        // Recover
+        {
+            let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+            let recovered = sm.recover().unwrap();
+            
+            assert!(recovered.contains(&AssetId(1)));
+            
+            let book = sm.get_book(AssetId(1)).unwrap();
+            assert_eq!(book.best_bid(), Some(Price::from_float(1.0)));
+        }
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_recover_advanced_orders() {
+        let path = temp_db_path();
+        let (maker, trader) = (Address::from([1u8; 20]), Address::from([2u8; 20]));
+        let asset = AssetId(1);
+        let (entry, legs, stop) = {
+            let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+            sm.on_block_begin(1, 10).unwrap();
+            let request = BracketOrderRequest {
+                trader,
+                asset,
+                side: Side::Bid,
+                price: Price::from_float(100.0),
+                size: Size(U256::from(5)),
+                stop_loss: Price::from_float(90.0),
+                take_profit: Price::from_float(110.0),
+                timestamp: 10,
+            };
+            let (entry, _) = sm.place_bracket_order(&request).unwrap();
+            sm.place_market_order_persistent(maker, asset, Side::Ask, Size(U256::from(3)), 10).unwrap();
+            let stop = sm.advanced_orders_mut().place_stop_loss(
+                trader, asset, Side::Ask, Size(U256::from(1)), Price::from_float(50.0), None, 10,
+            );
+            let cancelled = sm.advanced_orders_mut().place_stop_loss(
+                trader, asset, Side::Ask, Size(U256::from(1)), Price::from_float(40.0), None, 10,
+            );
+            sm.on_block_end().unwrap();
+            
+            sm.on_block_begin(2, 20).unwrap();
+            sm.advanced_orders_mut().cancel_order(cancelled).unwrap();
+            sm.on_block_end().unwrap();
+            let legs = sm.advanced_orders().get_bracket(asset, entry).unwrap().legs.unwrap();
+            (entry, legs, stop)
+        };
+        
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        let orders = sm.advanced_orders();
+        assert_eq!(orders.count_orders(), 3);
+        assert_eq!(orders.get_order(stop).unwrap().size, Size(U256::from(1)));
+        assert_eq!(orders.get_bracket(asset, entry).unwrap().filled, Size(U256::from(3)));
+        
+        // Bracket legs are still one-cancels-other, and IDs are not reused
+        sm.advanced_orders_mut().cancel_order(legs.0).unwrap();
+        assert!(sm.advanced_orders().get_order(legs.1).is_none());
+        let next = sm.advanced_orders_mut().place_stop_loss(
+            trader, asset, Side::Ask, Size(U256::from(1)), Price::from_float(50.0), None, 30,
+        );
+        assert!(next > legs.1.max(stop) + 1);
+        
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_get_order_fills() {
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        // Add liquidity
+        let (order_id, _) = sm
+            .place_limit_order_persistent(
+                maker,
+                asset,
+                Side::Ask,
+                Price::from_float(1.0),
+                Size(U256::from(100)),
+                0,
+            )
+            .unwrap();
+        
+        // Execute market order (creates fills)
+        sm.place_market_order_persistent(
+            taker,
+            asset,
+            Side::Bid,
+            Size(U256::from(50)),
+            1,
+        )
+        .unwrap();
+        
+        // Get fills for the order
+        let fills = sm.get_order_fills(order_id).unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].order_id, order_id);
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_height_management() {
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        
+        assert_eq!(sm.get_height(), 0);
+        
+        sm.set_height(100);
+        assert_eq!(sm.get_height(), 100);
+        
+        sm.set_height(200);
+        assert_eq!(sm.get_height(), 200);
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_block_writes_are_atomic() {
+        let path = temp_db_path();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        {
+            let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+            sm.set_balance(maker, asset, U256::from(500));
+            
+            sm.begin_block(1).unwrap();
+            assert!(sm.begin_block(1).is_err());
+            let (order_id, _) = sm
+                .place_limit_order_persistent(maker, asset, Side::Ask, Price::from_float(1.0), Size(U256::from(100)), 0)
+                .unwrap();
+            sm.commit_block().unwrap();
+            assert!(sm.commit_block().is_err());
+            
+            // Block 2 partially fills the maker but never commits (crash)
+            sm.begin_block(2).unwrap();
+            sm.place_market_order_persistent(taker, asset, Side::Bid, Size(U256::from(40)), 1).unwrap();
+            assert_eq!(sm.get_order_fills(order_id).unwrap().len(), 0);
+            
+            let storage = sm.storage.clone().unwrap();
+            assert_eq!(storage.load_committed_height().unwrap(), Some(1));
+            assert_eq!(storage.load_orders(asset).unwrap()[0].filled, Size(U256::ZERO));
+        }
+        
+        // Storage reflects block 1 only
+        let storage = CoreStorage::new(&path).unwrap();
+        let orders = storage.load_orders(asset).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].filled, Size(U256::ZERO));
+        assert!(storage.load_balances().unwrap().is_empty());
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_commit_persists_fills_makers_and_balances() {
+        let path = temp_db_path();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.set_balance(maker, asset, U256::from(500));
+        
+        sm.begin_block(1).unwrap();
+        let (partial, _) = sm
+            .place_limit_order_persistent(maker, asset, Side::Ask, Price::from_float(1.0), Size(U256::from(100)), 0)
+            .unwrap();
+        let (full, _) = sm
+            .place_limit_order_persistent(maker, asset, Side::Ask, Price::from_float(0.9), Size(U256::from(50)), 0)
+            .unwrap();
+        sm.place_market_order_persistent(taker, asset, Side::Bid, Size(U256::from(70)), 1).unwrap();
+        sm.commit_block().unwrap();
+        
+        let storage = sm.storage.clone().unwrap();
+        assert_eq!(storage.load_committed_height().unwrap(), Some(1));
+        
+        // Fully filled maker deleted, partially filled maker updated
+        let orders = storage.load_orders(asset).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, partial);
+        assert_eq!(orders[0].filled, Size(U256::from(20)));
+        assert_eq!(sm.get_order_fills(full).unwrap().len(), 1);
+        
+        let mut balances = storage.load_balances().unwrap();
+        balances.sort_by_key(|(user, _, _)| *user);
+        assert_eq!(balances, vec![
+            (maker, asset, U256::from(500)),
+            (taker, asset, U256::ZERO),
+        ]);
+        
+        // Recovery restores balances and the committed height
+        sm.set_height(100);
+        sm.checkpoint_if_needed().unwrap();
+        drop(sm);
+        let mut recovered = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        recovered.recover().unwrap();
+        assert_eq!(recovered.get_height(), 1);
+        assert_eq!(recovered.get_balance(&maker, asset), U256::from(500));
+        assert_eq!(recovered.get_book(asset).unwrap().best_ask(), Some(Price::from_float(1.0)));
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    // ==================== Margin System Integration Tests ====================
 
     #[test]
-    fn test_set_and_get_balance() {
+    fn test_deposit_collateral() {
         let mut sm = CoreStateMachine::new();
-        let user = Address::from([1u8; 20]);
-        let asset = AssetId(1);
+        let user = Address::ZERO;
         
-        sm.set_balance(user, asset, U256::from(1000));
-        assert_eq!(sm.get_balance(&user, asset), U256::from(1000));
+        sm.deposit_collateral(user, AssetId(1), U256::from(10000)).unwrap();
+        
+        let equity = sm.get_account_equity(&user).unwrap();
+        assert_eq!(equity, U256::from(10000));
     }
 
     #[test]
-    fn test_get_balance_nonexistent() {
-        let sm = CoreStateMachine::new();
-        let user = Address::from([1u8; 20]);
-        let asset = AssetId(1);
+    fn test_withdraw_collateral() {
+        let mut sm = CoreStateMachine::new();
+        let user = Address::ZERO;
         
-        assert_eq!(sm.get_balance(&user, asset), U256::ZERO);
+        sm.deposit_collateral(user, AssetId(1), U256::from(10000)).unwrap();
+        sm.withdraw_collateral(user, AssetId(1), U256::from(3000)).unwrap();
+        
+        let equity = sm.get_account_equity(&user).unwrap();
+        assert_eq!(equity, U256::from(7000));
     }
 
     #[test]
-    fn test_place_limit_order() {
+    fn test_withdraw_collateral_insufficient_balance() {
+        let mut sm = CoreStateMachine::new();
+        let user = Address::ZERO;
+        
+        sm.deposit_collateral(user, AssetId(1), U256::from(5000)).unwrap();
+        
+        let result = sm.withdraw_collateral(user, AssetId(1), U256::from(6000));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_place_limit_order_with_margin() {
         let mut sm = CoreStateMachine::new();
         let trader = Address::from([1u8; 20]);
         let asset = AssetId(1);
         
+        // Deposit collateral
+        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
+        
+        // Place order with margin check
         let (order_id, fills) = sm
-            .place_limit_order(
+            .place_limit_order_with_margin(
                 trader,
                 asset,
                 Side::Bid,
@@ -566,23 +3560,229 @@ mod tests {
             .unwrap();
         
         assert!(order_id > 0);
-        assert_eq!(fills.len(), 0); // No fills on empty book
+        assert_eq!(fills.len(), 0);
+    }
+
+    #[test]
+    fn test_place_limit_order_with_insufficient_margin() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        // Deposit small amount
+        sm.deposit_collateral(trader, AssetId(1), U256::from(5)).unwrap();
+        
+        // Try to place large order - should fail
+        let result = sm.place_limit_order_with_margin(
+            trader,
+            asset,
+            Side::Bid,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        );
+        
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_place_market_order_with_margin() {
+        let mut sm = CoreStateMachine::new();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        // Setup collateral for both
+        sm.deposit_collateral(maker, AssetId(1), U256::from(10000)).unwrap();
+        sm.deposit_collateral(taker, AssetId(1), U256::from(10000)).unwrap();
+        
+        // Add liquidity
+        sm.place_limit_order(
+            maker,
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        // Execute market order with margin check
+        let fills = sm
+            .place_market_order_with_margin(
+                taker,
+                asset,
+                Side::Bid,
+                Size(U256::from(50)),
+                1,
+            )
+            .unwrap();
+        
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].size.0, U256::from(50));
+    }
+
+    #[test]
+    fn test_position_tracking_after_trade() {
+        let mut sm = CoreStateMachine::new();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        // Setup collateral
+        sm.deposit_collateral(maker, AssetId(1), U256::from(10000)).unwrap();
+        sm.deposit_collateral(taker, AssetId(1), U256::from(10000)).unwrap();
+        
+        // Add ask order
+        sm.place_limit_order(
+            maker,
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        // Execute bid with margin
+        sm.place_market_order_with_margin(
+            taker,
+            asset,
+            Side::Bid,
+            Size(U256::from(50)),
+            1,
+        )
+        .unwrap();
+        
+        // Check taker's position (long)
+        let position = sm.get_position(&taker, asset).unwrap();
+        assert_eq!(position.size, 50);
+        assert_eq!(position.entry_price, Price::from_float(1.0));
+    }
+
+    #[test]
+    fn test_long_and_short_positions() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let counterparty = Address::from([2u8; 20]);
+        let asset = AssetId(1);
+        
+        // Setup collateral
+        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
+        sm.deposit_collateral(counterparty, AssetId(1), U256::from(10000)).unwrap();
+        
+        // Trader goes long
+        sm.place_limit_order(
+            counterparty,
+            asset,
+            Side::Ask,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        sm.place_market_order_with_margin(
+            trader,
+            asset,
+            Side::Bid,
+            Size(U256::from(50)),
+            1,
+        )
+        .unwrap();
+        
+        let position = sm.get_position(&trader, asset).unwrap();
+        assert_eq!(position.size, 50); // Long 50
+    }
+
+    #[test]
+    fn test_account_health_after_position() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        // Deposit collateral
+        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
+        
+        // Place order with margin
+        sm.place_limit_order_with_margin(
+            trader,
+            asset,
+            Side::Bid,
+            Price::from_float(1.0),
+            Size(U256::from(100)),
+            0,
+        )
+        .unwrap();
+        
+        // Account should still be healthy
+        assert!(sm.is_account_healthy(&trader).unwrap());
+    }
+
+    #[test]
+    fn test_check_liquidations_healthy_account() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
+        
+        // Deposit large collateral
+        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
+        
+        // Small position
+        sm.place_limit_order_with_margin(
+            trader,
+            asset,
+            Side::Bid,
+            Price::from_float(1.0),
+            Size(U256::from(10)),
+            0,
+        )
+        .unwrap();
+        
+        let mut prices = HashMap::new();
+        prices.insert(asset, Price::from_float(1.0));
+        
+        let liquidations = sm.check_liquidations(&prices, 0).unwrap();
+        assert_eq!(liquidations.len(), 0);
+    }
+
+    #[test]
+    fn test_get_liquidations() {
+        let sm = CoreStateMachine::new();
+        let liquidations = sm.get_liquidations();
+        assert_eq!(liquidations.len(), 0);
+    }
+
+    #[test]
+    fn test_margin_config_custom() {
+        let config = MarginConfig {
+            initial_margin_ratio: 0.2,  // 20%
+            maintenance_margin_ratio: 0.1,  // 10%
+            max_leverage: 5,
+            unrealized_profit_haircut: 0.1,
+        };
         
-        // Check order is in book
-        let book = sm.get_book(asset).unwrap();
-        assert_eq!(book.best_bid(), Some(Price::from_float(1.0)));
+        let sm = CoreStateMachine::new_with_margin_config(config);
+        assert_eq!(sm.books.len(), 0);
     }
 
     #[test]
-    fn test_place_market_order() {
+    fn test_full_margin_workflow() {
         let mut sm = CoreStateMachine::new();
-        let maker = Address::from([1u8; 20]);
-        let taker = Address::from([2u8; 20]);
+        let trader = Address::from([1u8; 20]);
+        let counterparty = Address::from([2u8; 20]);
         let asset = AssetId(1);
         
-        // Add liquidity
+        // 1. Deposit collateral
+        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
+        sm.deposit_collateral(counterparty, AssetId(1), U256::from(10000)).unwrap();
+        
+        let equity = sm.get_account_equity(&trader).unwrap();
+        assert_eq!(equity, U256::from(10000));
+        
+        // 2. Open position
         sm.place_limit_order(
-            maker,
+            counterparty,
             asset,
             Side::Ask,
             Price::from_float(1.0),
@@ -591,305 +3791,191 @@ mod tests {
         )
         .unwrap();
         
-        // Execute market order
-        let fills = sm
-            .place_market_order(
-                taker,
-                asset,
-                Side::Bid,
-                Size(U256::from(50)),
-                1,
-            )
-            .unwrap();
+        sm.place_market_order_with_margin(
+            trader,
+            asset,
+            Side::Bid,
+            Size(U256::from(100)),
+            1,
+        )
+        .unwrap();
         
-        assert_eq!(fills.len(), 1);
-        assert_eq!(fills[0].size.0, U256::from(50));
-        assert_eq!(fills[0].maker, maker);
-        assert_eq!(fills[0].taker, taker);
-    }
-
-    #[test]
-    fn test_cancel_order() {
-        let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
-        let asset = AssetId(1);
+        let position = sm.get_position(&trader, asset).unwrap();
+        assert_eq!(position.size, 100);
         
-        let (order_id, _) = sm
-            .place_limit_order(
-                trader,
-                asset,
-                Side::Bid,
-                Price::from_float(1.0),
-                Size(U256::from(100)),
-                0,
-            )
-            .unwrap();
+        // 3. Account should be healthy
+        assert!(sm.is_account_healthy(&trader).unwrap());
         
-        let order = sm.cancel_order(asset, order_id).unwrap();
-        assert_eq!(order.id, order_id);
+        // 4. Try to withdraw (should work)
+        sm.withdraw_collateral(trader, AssetId(1), U256::from(1000)).unwrap();
         
-        // Order should be removed from book
-        let book = sm.get_book(asset).unwrap();
-        assert_eq!(book.best_bid(), None);
+        let new_equity = sm.get_account_equity(&trader).unwrap();
+        assert_eq!(new_equity, U256::from(9000));
     }
 
     #[test]
-    fn test_cancel_nonexistent_order() {
+    fn test_margin_prevents_overleveraging() {
         let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
         let asset = AssetId(1);
         
-        let result = sm.cancel_order(asset, 999);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_get_snapshot() {
-        let mut sm = CoreStateMachine::new();
-        let asset = AssetId(1);
+        // Small collateral
+        sm.deposit_collateral(trader, AssetId(1), U256::from(100)).unwrap();
         
-        // Add orders
-        sm.place_limit_order(
-            Address::from([1u8; 20]),
+        // Try to open huge position - should fail
+        let result = sm.place_limit_order_with_margin(
+            trader,
             asset,
             Side::Bid,
             Price::from_float(1.0),
-            Size(U256::from(100)),
+            Size(U256::from(10000)),
             0,
-        )
-        .unwrap();
-        
-        sm.place_limit_order(
-            Address::from([2u8; 20]),
-            asset,
-            Side::Ask,
-            Price::from_float(1.01),
-            Size(U256::from(150)),
-            1,
-        )
-        .unwrap();
+        );
         
-        let snapshot = sm.get_snapshot(asset, 10).unwrap();
-        assert_eq!(snapshot.bids.len(), 1);
-        assert_eq!(snapshot.asks.len(), 1);
-        assert_eq!(snapshot.bids[0].0, Price::from_float(1.0));
-        assert_eq!(snapshot.asks[0].0, Price::from_float(1.01));
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_multiple_assets() {
+    fn test_multiple_positions_different_assets() {
         let mut sm = CoreStateMachine::new();
         let trader = Address::from([1u8; 20]);
+        let counterparty = Address::from([2u8; 20]);
         let asset1 = AssetId(1);
         let asset2 = AssetId(2);
         
-        // Add orders to different assets
+        // Deposit collateral
+        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
+        sm.deposit_collateral(counterparty, AssetId(1), U256::from(10000)).unwrap();
+        
+        // Add liquidity for asset 1
         sm.place_limit_order(
-            trader,
+            counterparty,
             asset1,
-            Side::Bid,
+            Side::Ask,
             Price::from_float(1.0),
             Size(U256::from(100)),
             0,
         )
         .unwrap();
         
-        sm.place_limit_order(
+        // Open position on asset 1
+        sm.place_market_order_with_margin(
             trader,
-            asset2,
+            asset1,
             Side::Bid,
-            Price::from_float(2.0),
-            Size(U256::from(200)),
+            Size(U256::from(50)),
             1,
         )
         .unwrap();
         
-        // Check both books exist independently
-        let book1 = sm.get_book(asset1).unwrap();
-        let book2 = sm.get_book(asset2).unwrap();
-        
-        assert_eq!(book1.best_bid(), Some(Price::from_float(1.0)));
-        assert_eq!(book2.best_bid(), Some(Price::from_float(2.0)));
-    }
-
-    #[test]
-    fn test_limit_order_with_immediate_fill() {
-        let mut sm = CoreStateMachine::new();
-        let maker = Address::from([1u8; 20]);
-        let taker = Address::from([2u8; 20]);
-        let asset = AssetId(1);
-        
-        // Add ask
+        // Add liquidity for asset 2
         sm.place_limit_order(
-            maker,
-            asset,
+            counterparty,
+            asset2,
             Side::Ask,
-            Price::from_float(1.0),
+            Price::from_float(2.0),
             Size(U256::from(100)),
-            0,
+            2,
         )
         .unwrap();
         
-        // Add crossing bid
-        let (order_id, fills) = sm
-            .place_limit_order(
-                taker,
-                asset,
-                Side::Bid,
-                Price::from_float(1.0),
-                Size(U256::from(50)),
-                1,
-            )
-            .unwrap();
-        
-        assert!(order_id > 0);
-        assert_eq!(fills.len(), 1);
-        assert_eq!(fills[0].size.0, U256::from(50));
+        // Open position on asset 2
+        sm.place_market_order_with_margin(
+            trader,
+            asset2,
+            Side::Bid,
+            Size(U256::from(25)),
+            3,
+        )
+        .unwrap();
         
-        // Remaining ask should be in book
-        let book = sm.get_book(asset).unwrap();
-        assert_eq!(book.depth_at_price(Price::from_float(1.0), Side::Ask), U256::from(50));
+        // Check both positions exist
+        assert!(sm.get_position(&trader, asset1).is_some());
+        assert!(sm.get_position(&trader, asset2).is_some());
     }
 
     #[test]
-    fn test_market_order_empty_book() {
+    fn test_withdraw_blocked_by_margin_requirements() {
         let mut sm = CoreStateMachine::new();
-        let taker = Address::from([1u8; 20]);
+        let trader = Address::from([1u8; 20]);
+        let counterparty = Address::from([2u8; 20]);
         let asset = AssetId(1);
         
-        let fills = sm
-            .place_market_order(
-                taker,
-                asset,
-                Side::Bid,
-                Size(U256::from(100)),
-                0,
-            )
-            .unwrap();
-        
-        assert_eq!(fills.len(), 0);
-    }
-
-    #[test]
-    fn test_get_nonexistent_snapshot() {
-        let sm = CoreStateMachine::new();
-        let snapshot = sm.get_snapshot(AssetId(999), 10);
-        assert!(snapshot.is_none());
-    }
-
-    #[test]
-    fn test_get_nonexistent_book() {
-        let sm = CoreStateMachine::new();
-        let book = sm.get_book(AssetId(999));
-        assert!(book.is_none());
-    }
-
-    #[test]
-    fn test_multiple_fills_single_order() {
-        let mut sm = CoreStateMachine::new();
-        let asset = AssetId(1);
+        // Deposit collateral: $1100 (enough for position + buffer)
+        sm.deposit_collateral(trader, AssetId(1), U256::from(1100)).unwrap();
+        sm.deposit_collateral(counterparty, AssetId(1), U256::from(100000)).unwrap();
         
-        // Add multiple ask orders
+        // Open large position: 10000 units @ $1 = $10000 notional, 10% margin = $1000
         sm.place_limit_order(
-            Address::from([1u8; 20]),
+            counterparty,
             asset,
             Side::Ask,
             Price::from_float(1.0),
-            Size(U256::from(50)),
+            Size(U256::from(10000)),
             0,
         )
         .unwrap();
         
-        sm.place_limit_order(
-            Address::from([2u8; 20]),
+        sm.place_market_order_with_margin(
+            trader,
             asset,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(50)),
-            1,
-        )
-        .unwrap();
-        
-        // Market buy that hits both
-        let fills = sm
-            .place_market_order(
-                Address::from([10u8; 20]),
-                asset,
-                Side::Bid,
-                Size(U256::from(100)),
-                2,
-            )
-            .unwrap();
+            Side::Bid,
+            Size(U256::from(10000)),
+            1,
+        )
+        .unwrap();
         
-        assert_eq!(fills.len(), 2);
-        assert_eq!(fills[0].maker, Address::from([1u8; 20]));
-        assert_eq!(fills[1].maker, Address::from([2u8; 20]));
+        // Margin used: $1000
+        // Maintenance requirement: $1000 * 0.05 = $50
+        // Can withdraw at most: $1100 - $50 = $1050
+        // Try to withdraw $1051 - should fail
+        let result = sm.withdraw_collateral(trader, AssetId(1), U256::from(1051));
+        assert!(result.is_err());
+        
+        // Rejected withdrawal leaves the deposit untouched
+        assert_eq!(sm.get_collateral(&trader, AssetId(1)), U256::from(1100));
+        assert_eq!(sm.get_account_equity(&trader).unwrap(), U256::from(1100));
     }
 
     #[test]
-    fn test_order_id_increments() {
+    fn test_withdrawal_counts_marked_losses() {
         let mut sm = CoreStateMachine::new();
         let trader = Address::from([1u8; 20]);
+        let counterparty = Address::from([2u8; 20]);
         let asset = AssetId(1);
         
-        let (id1, _) = sm
-            .place_limit_order(
-                trader,
-                asset,
-                Side::Bid,
-                Price::from_float(1.0),
-                Size(U256::from(100)),
-                0,
-            )
-            .unwrap();
-        
-        let (id2, _) = sm
-            .place_limit_order(
-                trader,
-                asset,
-                Side::Bid,
-                Price::from_float(0.99),
-                Size(U256::from(100)),
-                1,
-            )
-            .unwrap();
+        sm.deposit_collateral(trader, asset, U256::from(20_000)).unwrap();
+        sm.place_limit_order(counterparty, asset, Side::Ask, Price(1_000_000), Size(U256::from(10)), 0).unwrap();
+        sm.place_market_order_with_margin(trader, asset, Side::Bid, Size(U256::from(10)), 1).unwrap();
         
-        assert_eq!(id2, id1 + 1);
-    }
-
-    // Persistence tests
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    fn temp_db_path() -> String {
-        use std::sync::atomic::{AtomicU64, Ordering};
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        // Book mid drops to 0.999, a 10_000 unrealized loss
+        sm.place_limit_order(counterparty, asset, Side::Bid, Price(998_990), Size(U256::from(1)), 2).unwrap();
+        sm.place_limit_order(counterparty, asset, Side::Ask, Price(999_010), Size(U256::from(1)), 2).unwrap();
         
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
-        format!("/tmp/openliquid_test_sm_{}_{}", timestamp, counter)
+        assert!(sm.withdraw_collateral(trader, asset, U256::from(15_000)).is_err());
+        sm.withdraw_collateral(trader, asset, U256::from(9_000)).unwrap();
+        assert_eq!(sm.get_collateral(&trader, asset), U256::from(11_000));
     }
 
     #[test]
-    fn test_create_with_storage() {
-        let path = temp_db_path();
-        let sm = CoreStateMachine::new_with_storage(&path, 100);
-        assert!(sm.is_ok());
+    fn test_position_with_no_collateral_account() {
+        let sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
+        let asset = AssetId(1);
         
-        // Cleanup
-        let _ = std::fs::remove_dir_all(path);
+        // No position should exist
+        assert!(sm.get_position(&trader, asset).is_none());
     }
 
     #[test]
-    fn test_persistent_limit_order() {
-        let path = temp_db_path();
-        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+    fn test_backward_compatibility_regular_orders() {
+        let mut sm = CoreStateMachine::new();
         let trader = Address::from([1u8; 20]);
         let asset = AssetId(1);
         
+        // Old API should still work (no margin checks)
         let (order_id, fills) = sm
-            .place_limit_order_persistent(
+            .place_limit_order(
                 trader,
                 asset,
                 Side::Bid,
@@ -902,669 +3988,1339 @@ mod tests {
         assert!(order_id > 0);
         assert_eq!(fills.len(), 0);
         
-        // Check order is in book
-        let book = sm.get_book(asset).unwrap();
-        assert_eq!(book.best_bid(), Some(Price::from_float(1.0)));
-        
-        // Cleanup
-        let _ = std::fs::remove_dir_all(path);
+        // Should work even without collateral deposit
+        let equity_result = sm.get_account_equity(&trader);
+        assert!(equity_result.is_err()); // No account created
     }
 
     #[test]
-    fn test_persistent_market_order() {
-        let path = temp_db_path();
-        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
-        let maker = Address::from([1u8; 20]);
-        let taker = Address::from([2u8; 20]);
+    fn test_open_order_limit_enforced() {
+        let mut sm = CoreStateMachine::new();
+        let trader = Address::from([1u8; 20]);
         let asset = AssetId(1);
         
-        // Add liquidity
-        sm.place_limit_order_persistent(
-            maker,
-            asset,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
+        sm.set_order_limits_config(OrderLimitsConfig {
+            max_open_orders_per_asset: 2,
+            max_orders_per_block: 100,
+            market_maker_multiplier: 0,
+        });
         
-        // Execute market order
-        let fills = sm
-            .place_market_order_persistent(
-                taker,
-                asset,
-                Side::Bid,
-                Size(U256::from(50)),
-                1,
-            )
+        let (first, _) = sm
+            .place_limit_order(trader, asset, Side::Bid, Price::from_float(1.0), Size(U256::from(10)), 0)
+            .unwrap();
+        sm.place_limit_order(trader, asset, Side::Bid, Price::from_float(0.9), Size(U256::from(10)), 0)
             .unwrap();
         
-        assert_eq!(fills.len(), 1);
-        assert_eq!(fills[0].size.0, U256::from(50));
+        let result = sm.place_limit_order(trader, asset, Side::Bid, Price::from_float(0.8), Size(U256::from(10)), 0);
+        assert!(result.is_err());
         
-        // Cleanup
-        let _ = std::fs::remove_dir_all(path);
+        // Cancelling frees a slot
+        sm.cancel_order(asset, first).unwrap();
+        assert_eq!(sm.order_limits().open_order_count(&trader, asset), 1);
+        assert!(sm
+            .place_limit_order(trader, asset, Side::Bid, Price::from_float(0.8), Size(U256::from(10)), 0)
+            .is_ok());
     }
 
     #[test]
-    fn test_cancel_order_persistent() {
-        let path = temp_db_path();
-        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
-        let trader = Address::from([1u8; 20]);
+    fn test_filled_maker_order_frees_slot() {
+        let mut sm = CoreStateMachine::new();
+        let maker = Address::from([1u8; 20]);
+        let taker = Address::from([2u8; 20]);
         let asset = AssetId(1);
         
-        let (order_id, _) = sm
-            .place_limit_order_persistent(
-                trader,
-                asset,
-                Side::Bid,
-                Price::from_float(1.0),
-                Size(U256::from(100)),
-                0,
-            )
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(1.0), Size(U256::from(100)), 0)
             .unwrap();
+        assert_eq!(sm.order_limits().open_order_count(&maker, asset), 1);
         
-        let order = sm.cancel_order_persistent(asset, order_id).unwrap();
-        assert_eq!(order.id, order_id);
-        
-        // Order should be removed from book
-        let book = sm.get_book(asset).unwrap();
-        assert_eq!(book.best_bid(), None);
-        
-        // Cleanup
-        let _ = std::fs::remove_dir_all(path);
+        sm.place_market_order(taker, asset, Side::Bid, Size(U256::from(100)), 1)
+            .unwrap();
+        assert_eq!(sm.order_limits().open_order_count(&maker, asset), 0);
     }
 
     #[test]
-    fn test_checkpoint_if_needed() {
-        let path = temp_db_path();
-        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+    fn test_per_block_order_limit() {
+        let mut sm = CoreStateMachine::new();
         let trader = Address::from([1u8; 20]);
+        let mm = Address::from([3u8; 20]);
         let asset = AssetId(1);
         
-        // Add an order
-        sm.place_limit_order_persistent(
-            trader,
-            asset,
-            Side::Bid,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
-        
-        // Set height to checkpoint height
-        sm.set_height(100);
-        
-        // Should checkpoint
-        let checkpointed = sm.checkpoint_if_needed().unwrap();
-        assert_eq!(checkpointed.len(), 1);
-        assert_eq!(checkpointed[0], asset);
-        
-        // Cleanup
-        let _ = std::fs::remove_dir_all(path);
-    }
-
-    #[test]
-    fn test_recover_from_storage() {
-        let path = temp_db_path();
-        
-        // Create and populate
-        {
-            let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
-            let trader = Address::from([1u8; 20]);
-            let asset = AssetId(1);
-            
-            sm.place_limit_order_persistent(
-                trader,
-                asset,
-                Side::Bid,
-                Price::from_float(1.0),
-                Size(U256::from(100)),
-                0,
-            )
-            .unwrap();
-            
-            sm.set_height(100);
-            sm.checkpoint_if_needed().unwrap();
-        }
-        
-        // Recover
-        {
-            let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
-            let recovered = sm.recover().unwrap();
-            
-            assert!(recovered.contains(&AssetId(1)));
-            
-            let book = sm.get_book(AssetId(1)).unwrap();
-            assert_eq!(book.best_bid(), Some(Price::from_float(1.0)));
+        sm.set_order_limits_config(OrderLimitsConfig {
+            max_open_orders_per_asset: 100,
+            max_orders_per_block: 2,
+            market_maker_multiplier: 0,
+        });
+        sm.register_market_maker(mm);
+        sm.set_height(1);
+        
+        // Registered market makers are exempt
+        for i in 0..3 {
+            let price = Price::from_float(1.0 - i as f64 * 0.1);
+            sm.place_limit_order(mm, asset, Side::Bid, price, Size(U256::from(10)), 0)
+                .unwrap();
         }
         
-        // Cleanup
-        let _ = std::fs::remove_dir_all(path);
+        sm.place_limit_order(trader, asset, Side::Bid, Price::from_float(0.5), Size(U256::from(10)), 0)
+            .unwrap();
+        sm.place_limit_order(trader, asset, Side::Bid, Price::from_float(0.4), Size(U256::from(10)), 0)
+            .unwrap();
+        assert!(sm
+            .place_market_order(trader, asset, Side::Ask, Size(U256::from(1)), 0)
+            .is_err());
+        
+        // Next block resets the submission counter
+        sm.set_height(2);
+        assert!(sm
+            .place_limit_order(trader, asset, Side::Bid, Price::from_float(0.3), Size(U256::from(10)), 0)
+            .is_ok());
     }
 
     #[test]
-    fn test_get_order_fills() {
+    fn test_signed_position_transfer() {
+        use crate::transfer::{LedgerEntryKind, PositionTransfer};
+        use alloy_primitives::PrimitiveSignature as Signature;
+        use k256::ecdsa::SigningKey;
+        
+        let sign = |sk: &SigningKey, transfer: &PositionTransfer| {
+            let hash = transfer.signing_hash(DEFAULT_CHAIN_ID);
+            let (sig, recid) = sk.sign_prehash_recoverable(hash.as_slice()).unwrap();
+            Signature::from_signature_and_parity(sig, recid.is_y_odd())
+        };
+        
         let path = temp_db_path();
         let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
-        let maker = Address::from([1u8; 20]);
-        let taker = Address::from([2u8; 20]);
+        let from_sk = SigningKey::from_slice(&[1u8; 32]).unwrap();
+        let to_sk = SigningKey::from_slice(&[2u8; 32]).unwrap();
+        let from = Address::from_private_key(&from_sk);
+        let to = Address::from_private_key(&to_sk);
         let asset = AssetId(1);
         
-        // Add liquidity
-        let (order_id, _) = sm
-            .place_limit_order_persistent(
-                maker,
-                asset,
-                Side::Ask,
-                Price::from_float(1.0),
-                Size(U256::from(100)),
-                0,
-            )
+        sm.deposit_collateral(from, asset, U256::from(1000)).unwrap();
+        sm.deposit_collateral(to, asset, U256::from(1000)).unwrap();
+        sm.margin_engine
+            .update_position(from, asset, 10, Price::from_float(100.0), 0)
             .unwrap();
         
-        // Execute market order (creates fills)
-        sm.place_market_order_persistent(
-            taker,
-            asset,
-            Side::Bid,
-            Size(U256::from(50)),
-            1,
-        )
-        .unwrap();
+        let transfer = PositionTransfer { from, to, asset, size: 4, nonce: 0 };
+        let signed = SignedPositionTransfer {
+            transfer: transfer.clone(),
+            from_signature: sign(&from_sk, &transfer),
+            to_signature: sign(&to_sk, &transfer),
+        };
         
-        // Get fills for the order
-        let fills = sm.get_order_fills(order_id).unwrap();
-        assert_eq!(fills.len(), 1);
-        assert_eq!(fills[0].order_id, order_id);
+        // Signed for the default chain: refused by a node on another one
+        sm.set_chain_id(DEFAULT_CHAIN_ID + 1);
+        assert!(sm.transfer_position(&signed, 1).is_err());
+        sm.set_chain_id(DEFAULT_CHAIN_ID);
+        
+        sm.transfer_position(&signed, 1).unwrap();
+        assert_eq!(sm.get_position(&from, asset).unwrap().size, 6);
+        assert_eq!(sm.get_position(&to, asset).unwrap().size, 4);
+        
+        let entries = sm.get_transfer_ledger().account_entries(&to);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, LedgerEntryKind::PositionTransferIn);
+        
+        // Replay is rejected
+        assert!(sm.transfer_position(&signed, 2).is_err());
+        
+        // Unilateral transfer is rejected
+        let transfer = PositionTransfer { nonce: 1, ..transfer };
+        let forged = SignedPositionTransfer {
+            transfer: transfer.clone(),
+            from_signature: sign(&from_sk, &transfer),
+            to_signature: sign(&from_sk, &transfer),
+        };
+        assert!(sm.transfer_position(&forged, 2).is_err());
+        drop(sm);
+        
+        // Consumed nonces survive a restart
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        assert_eq!(sm.get_transfer_ledger().next_nonce(&from), 1);
+        assert!(sm.transfer_position(&signed, 3).is_err());
         
         // Cleanup
         let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_height_management() {
-        let path = temp_db_path();
-        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
-        
-        assert_eq!(sm.get_height(), 0);
+    fn test_delisting_workflow() {
+        use crate::delisting::DelistingPhase;
         
-        sm.set_height(100);
-        assert_eq!(sm.get_height(), 100);
+        let mut sm = CoreStateMachine::new();
+        let long = Address::from([1u8; 20]);
+        let maker = Address::from([2u8; 20]);
+        let council = Address::from([9u8; 20]);
+        let asset = AssetId(1);
+        sm.set_governance(Governance::new([council]));
         
-        sm.set_height(200);
-        assert_eq!(sm.get_height(), 200);
+        sm.deposit_collateral(long, asset, U256::from(10_000)).unwrap();
+        sm.margin_engine
+            .update_position(long, asset, 10, Price::from_float(100.0), 0)
+            .unwrap();
+        sm.place_limit_order(maker, asset, Side::Bid, Price::from_float(90.0), Size(U256::from(5)), 0)
+            .unwrap();
         
-        // Cleanup
-        let _ = std::fs::remove_dir_all(path);
+        sm.set_height(100);
+        let params = DelistingParams {
+            cancel_delay: 10,
+            settlement_delay: 10,
+            twap_window: 60,
+        };
+        // Only governance may delist
+        assert!(sm.announce_delisting(&maker, asset, params).is_err());
+        assert!(sm.delistings().get(asset).is_none());
+        sm.announce_delisting(&council, asset, params).unwrap();
+        
+        // Reduce-only: opening or increasing is rejected, reducing is allowed
+        assert!(sm
+            .place_limit_order(maker, asset, Side::Ask, Price::from_float(110.0), Size(U256::from(1)), 1)
+            .is_err());
+        assert!(sm
+            .place_limit_order(long, asset, Side::Bid, Price::from_float(80.0), Size(U256::from(1)), 1)
+            .is_err());
+        assert!(sm
+            .place_limit_order(long, asset, Side::Ask, Price::from_float(110.0), Size(U256::from(11)), 1)
+            .is_err());
+        assert!(sm
+            .place_limit_order(long, asset, Side::Ask, Price::from_float(110.0), Size(U256::from(5)), 1)
+            .is_ok());
+        // Resting reducing orders count against the position
+        assert!(sm
+            .place_limit_order(long, asset, Side::Ask, Price::from_float(110.0), Size(U256::from(6)), 1)
+            .is_err());
+        assert!(sm
+            .place_limit_order(long, asset, Side::Ask, Price::from_float(111.0), Size(U256::from(5)), 1)
+            .is_ok());
+        
+        // Cancellation at H + k
+        sm.set_height(110);
+        let actions = sm.process_delistings(1000).unwrap();
+        assert_eq!(actions, vec![DelistingAction::CancelOrders(asset)]);
+        assert_eq!(sm.get_book(asset).unwrap().orders().count(), 0);
+        assert!(sm
+            .place_limit_order(long, asset, Side::Ask, Price::from_float(110.0), Size(U256::from(1)), 2)
+            .is_err());
+        
+        // Forced settlement at TWAP
+        sm.update_oracle_price(asset, Price::from_float(100.0), 1000).unwrap();
+        sm.update_oracle_price(asset, Price::from_float(120.0), 1030).unwrap();
+        sm.set_height(120);
+        let actions = sm.process_delistings(1060).unwrap();
+        assert_eq!(actions, vec![DelistingAction::Settle(asset)]);
+        
+        assert_eq!(sm.get_position(&long, asset).unwrap().size, 0);
+        let delisting = sm.delistings().get(asset).unwrap();
+        assert_eq!(delisting.phase, DelistingPhase::Settled);
+        assert_eq!(delisting.settlement_price, Some(Price::from_float(110.0)));
+        
+        // Nothing left to do
+        assert!(sm.process_delistings(1100).unwrap().is_empty());
     }
 
-    // ==================== Margin System Integration Tests ====================
-
     #[test]
-    fn test_deposit_collateral() {
+    fn test_route_order_across_book_and_pool() {
         let mut sm = CoreStateMachine::new();
-        let user = Address::ZERO;
-        
-        sm.deposit_collateral(user, AssetId(1), U256::from(10000)).unwrap();
+        let maker = Address::from([1u8; 20]);
+        let trader = Address::from([2u8; 20]);
+        let asset = AssetId(1);
         
-        let equity = sm.get_account_equity(&user).unwrap();
-        assert_eq!(equity, U256::from(10000));
+        sm.place_limit_order(maker, asset, Side::Bid, Price::from_float(98.0), Size(U256::from(10)), 0)
+            .unwrap();
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(100.0), Size(U256::from(4)), 0)
+            .unwrap();
+        let pool = sm.pools_mut().create_pool(
+            asset, vec![Price::from_float(100.2), Price::from_float(105.0)], Size(U256::from(300)), 0,
+        ).unwrap();
+        sm.pools_mut().add_liquidity(pool, maker, U256::from(100_000)).unwrap();
+        
+        // 4 from the book at 100, 300 from the pool at 100.2; 105 is past the limit
+        let routed = sm
+            .route_order(trader, asset, Side::Bid, Size(U256::from(400)), Some(Price::from_float(101.0)), 10)
+            .unwrap();
+        assert_eq!(routed.plan.venue_size(Venue::Book), Size(U256::from(4)));
+        assert_eq!(routed.plan.venue_size(Venue::Pool(pool)), Size(U256::from(300)));
+        assert_eq!(routed.plan.unfilled_size, Size(U256::from(96)));
+        // 30_060 notional at 30 bps, rounded up
+        assert_eq!(routed.plan.fills[1].fee, U256::from(91));
+        assert_eq!(routed.book_fills.len(), 1);
+        assert_eq!(sm.get_book(asset).unwrap().best_ask(), None);
+        assert_eq!(sm.pools().get_pool(pool).unwrap().accumulated_fees, routed.plan.fills[1].fee);
     }
 
     #[test]
-    fn test_withdraw_collateral() {
+    fn test_set_rounding_policy_is_governed_and_propagates() {
+        use crate::rounding::Rounding;
+
         let mut sm = CoreStateMachine::new();
-        let user = Address::ZERO;
-        
-        sm.deposit_collateral(user, AssetId(1), U256::from(10000)).unwrap();
-        sm.withdraw_collateral(user, AssetId(1), U256::from(3000)).unwrap();
-        
-        let equity = sm.get_account_equity(&user).unwrap();
-        assert_eq!(equity, U256::from(7000));
+        let council = Address::from([9u8; 20]);
+        sm.set_governance(Governance::new([council]));
+        let pool = sm.pools_mut().create_pool(AssetId(1), vec![Price::from_float(100.0)], Size(U256::from(1)), 0).unwrap();
+
+        // Maker 5 bps on 25_000 = 12.5, rounded up by default
+        assert_eq!(sm.fee_engine().calculate_fee(&Address::ZERO, U256::from(25_000), true, 0), U256::from(13));
+
+        let half_even = RoundingPolicy { debit: Rounding::HalfEven, ..RoundingPolicy::default() };
+        assert!(sm.set_rounding_policy(&Address::ZERO, half_even).is_err());
+        let favours_users = RoundingPolicy { debit: Rounding::Floor, ..RoundingPolicy::default() };
+        assert!(sm.set_rounding_policy(&council, favours_users).is_err());
+        assert_eq!(sm.rounding_policy(), RoundingPolicy::default());
+
+        sm.set_rounding_policy(&council, half_even).unwrap();
+        assert_eq!(sm.rounding_policy(), half_even);
+        assert_eq!(sm.fee_engine().calculate_fee(&Address::ZERO, U256::from(25_000), true, 0), U256::from(12));
+        assert_eq!(sm.pools().get_pool(pool).unwrap().rounding, half_even);
     }
 
     #[test]
-    fn test_withdraw_collateral_insufficient_balance() {
+    fn test_triggered_limit_tracks_resting_remainder() {
         let mut sm = CoreStateMachine::new();
-        let user = Address::ZERO;
-        
-        sm.deposit_collateral(user, AssetId(1), U256::from(5000)).unwrap();
+        let maker = Address::from([1u8; 20]);
+        let trader = Address::from([2u8; 20]);
+        let asset = AssetId(1);
         
-        let result = sm.withdraw_collateral(user, AssetId(1), U256::from(6000));
-        assert!(result.is_err());
+        sm.place_limit_order(maker, asset, Side::Bid, Price::from_float(90.0), Size(U256::from(10)), 0)
+            .unwrap();
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(100.0), Size(U256::from(3)), 0)
+            .unwrap();
+        let id = sm.advanced_orders_mut().place_take_profit(
+            trader, asset, Side::Bid, Size(U256::from(10)), Price::from_float(95.0), Some(Price::from_float(100.0)), 0,
+        );
+        assert_eq!(sm.advanced_orders().status(id), Some(crate::orders::AdvancedOrderStatus::Working));
+        
+        // Triggers at mid 95: 3 fill against the ask, 7 rest at 100
+        sm.on_block_begin(1, 10).unwrap();
+        sm.on_block_end().unwrap();
+        let order = sm.advanced_orders().get_order(id).unwrap().clone();
+        assert_eq!(order.status(), crate::orders::AdvancedOrderStatus::PartiallyFilled);
+        assert_eq!(order.remaining(), Size(U256::from(7)));
+        let book_order = order.spawned.unwrap();
+        assert_eq!(sm.advanced_orders().spawned_by(asset, book_order), Some(id));
+        
+        sm.place_market_order(maker, asset, Side::Ask, Size(U256::from(4)), 20).unwrap();
+        assert_eq!(sm.advanced_orders().get_order(id).unwrap().remaining(), Size(U256::from(3)));
+        sm.place_market_order(maker, asset, Side::Ask, Size(U256::from(3)), 20).unwrap();
+        assert_eq!(sm.advanced_orders().status(id), Some(crate::orders::AdvancedOrderStatus::Done));
+        assert_eq!(sm.advanced_orders().spawned_by(asset, book_order), None);
     }
-
+    
     #[test]
-    fn test_place_limit_order_with_margin() {
+    fn test_block_hooks_expire_and_trigger_orders() {
         let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
+        let maker = Address::from([1u8; 20]);
+        let trader = Address::from([2u8; 20]);
         let asset = AssetId(1);
         
-        // Deposit collateral
-        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
-        
-        // Place order with margin check
-        let (order_id, fills) = sm
-            .place_limit_order_with_margin(
-                trader,
-                asset,
-                Side::Bid,
-                Price::from_float(1.0),
-                Size(U256::from(100)),
-                0,
-            )
+        let (bid, _) = sm
+            .place_limit_order(maker, asset, Side::Bid, Price::from_float(90.0), Size(U256::from(10)), 0)
+            .unwrap();
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(100.0), Size(U256::from(10)), 0)
             .unwrap();
+        sm.set_order_expiry(asset, bid, 50).unwrap();
+        assert!(sm.set_order_expiry(asset, 999, 50).is_err());
         
-        assert!(order_id > 0);
-        assert_eq!(fills.len(), 0);
+        // Mid is 95: the take-profit at 95 fires, the one at 96 does not
+        let fired = sm.advanced_orders_mut().place_take_profit(
+            trader, asset, Side::Bid, Size(U256::from(4)), Price::from_float(95.0), None, 0,
+        );
+        let pending = sm.advanced_orders_mut().place_take_profit(
+            trader, asset, Side::Bid, Size(U256::from(4)), Price::from_float(96.0), None, 0,
+        );
+        
+        let begin = sm.on_block_begin(1, 10).unwrap();
+        assert!(begin.expired_orders.is_empty());
+        assert!(sm.on_block_begin(1, 10).is_err());
+        
+        let end = sm.on_block_end().unwrap();
+        assert_eq!(end.height, 1);
+        assert_eq!(end.triggered_orders.len(), 1);
+        assert_eq!(end.triggered_orders[0].id, fired);
+        assert_eq!(end.triggered_orders[0].result.as_ref().unwrap()[0].size, Size(U256::from(4)));
+        assert!(sm.advanced_orders().get_order(fired).is_none());
+        assert!(sm.advanced_orders().get_order(pending).is_some());
+        assert!(!sm.in_block());
+        assert!(sm.on_block_end().is_err());
+        
+        // The bid expires once the block time passes its expiry
+        let begin = sm.on_block_begin(2, 50).unwrap();
+        assert_eq!(begin.expired_orders, vec![(asset, bid)]);
+        assert!(!sm.get_book(asset).unwrap().contains_order(bid));
+        sm.on_block_end().unwrap();
     }
 
     #[test]
-    fn test_place_limit_order_with_insufficient_margin() {
+    fn test_block_clock_stamps_orders() {
         let mut sm = CoreStateMachine::new();
         let trader = Address::from([1u8; 20]);
         let asset = AssetId(1);
         
-        // Deposit small amount
-        sm.deposit_collateral(trader, AssetId(1), U256::from(5)).unwrap();
-        
-        // Try to place large order - should fail
-        let result = sm.place_limit_order_with_margin(
-            trader,
-            asset,
-            Side::Bid,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        );
-        
-        assert!(result.is_err());
+        sm.on_block_begin(1, 100).unwrap();
+        // Beginning a block while one is open leaves the clock alone
+        assert!(sm.on_block_begin(2, 150).is_err());
+        assert!(sm.in_block());
+        assert_eq!(sm.clock().now(), 100);
+        // A skewed caller timestamp is replaced by the block time
+        let (order_id, _) = sm
+            .place_limit_order(trader, asset, Side::Bid, Price::from_float(1.0), Size(U256::from(1)), 999)
+            .unwrap();
+        assert_eq!(sm.get_book(asset).unwrap().get_order(order_id).unwrap().timestamp, 100);
+        assert!(sm.set_order_expiry(asset, order_id, 100).is_err());
+        sm.set_order_expiry(asset, order_id, 101).unwrap();
+        sm.on_block_end().unwrap();
+        
+        // Block time never goes backwards
+        assert!(sm.on_block_begin(2, 99).is_err());
+        assert!(!sm.in_block());
+        assert_eq!(sm.clock().now(), 100);
+        
+        let begin = sm.on_block_begin(2, 101).unwrap();
+        assert_eq!(begin.expired_orders, vec![(asset, order_id)]);
+        sm.on_block_end().unwrap();
     }
 
     #[test]
-    fn test_place_market_order_with_margin() {
+    fn test_missing_settlement_twap_defers_settlement() {
+        use crate::delisting::DelistingPhase;
+        
         let mut sm = CoreStateMachine::new();
+        let council = Address::from([9u8; 20]);
+        let maker = Address::from([1u8; 20]);
+        let (halting, settling) = (AssetId(1), AssetId(2));
+        sm.set_governance(Governance::new([council]));
+        let params = |settlement_delay| DelistingParams { cancel_delay: 1, settlement_delay, twap_window: 60 };
+        
+        sm.on_block_begin(1, 100).unwrap();
+        sm.announce_delisting(&council, settling, params(1)).unwrap();
+        sm.on_block_end().unwrap();
+        sm.on_block_begin(2, 200).unwrap();
+        sm.place_limit_order(maker, halting, Side::Bid, Price::from_float(90.0), Size(U256::from(5)), 200)
+            .unwrap();
+        sm.announce_delisting(&council, halting, params(5)).unwrap();
+        sm.on_block_end().unwrap();
+        
+        // Settlement needs a TWAP the oracle does not have yet: it is put
+        // off, while the other market's step still runs
+        let begin = sm.on_block_begin(3, 300).unwrap();
+        assert_eq!(begin.delisting_actions, vec![DelistingAction::CancelOrders(halting)]);
+        assert_eq!(begin.deferred_settlements, vec![settling]);
+        assert_eq!(sm.get_book(halting).unwrap().orders().count(), 0);
+        assert_eq!(sm.delistings().get(settling).unwrap().phase, DelistingPhase::Halted);
+        sm.update_oracle_price(settling, Price::from_float(100.0), 290).unwrap();
+        sm.on_block_end().unwrap();
+        
+        // A failing step aborts the block
+        assert!(sm.on_block_begin(4, 250).is_err());
+        assert!(!sm.in_block());
+        assert_eq!(sm.get_height(), 3);
+        
+        let begin = sm.on_block_begin(4, 320).unwrap();
+        assert_eq!(begin.delisting_actions, vec![DelistingAction::Settle(settling)]);
+        assert!(begin.deferred_settlements.is_empty());
+        assert_eq!(sm.delistings().get(settling).unwrap().settlement_price, Some(Price::from_float(100.0)));
+        sm.on_block_end().unwrap();
+    }
+
+    #[test]
+    fn test_aborted_block_leaves_memory_matching_storage() {
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
         let maker = Address::from([1u8; 20]);
         let taker = Address::from([2u8; 20]);
         let asset = AssetId(1);
+        let stored_orders = |sm: &CoreStateMachine| -> Vec<(OrderId, Size)> {
+            let mut orders: Vec<_> = sm.storage.as_ref().unwrap().load_orders(asset).unwrap()
+                .into_iter()
+                .map(|o| (o.id, o.filled))
+                .collect();
+            orders.sort_by_key(|(id, _)| *id);
+            orders
+        };
+        let book_orders = |sm: &CoreStateMachine| -> Vec<(OrderId, Size)> {
+            let mut orders: Vec<_> = sm.get_book(asset).unwrap().orders().map(|o| (o.id, o.filled)).collect();
+            orders.sort_by_key(|(id, _)| *id);
+            orders
+        };
         
-        // Setup collateral for both
-        sm.deposit_collateral(maker, AssetId(1), U256::from(10000)).unwrap();
-        sm.deposit_collateral(taker, AssetId(1), U256::from(10000)).unwrap();
-        
-        // Add liquidity
-        sm.place_limit_order(
-            maker,
-            asset,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
-        
-        // Execute market order with margin check
-        let fills = sm
-            .place_market_order_with_margin(
-                taker,
-                asset,
-                Side::Bid,
-                Size(U256::from(50)),
-                1,
-            )
+        sm.on_block_begin(1, 100).unwrap();
+        let (maker_order, _) = sm
+            .place_limit_order_persistent(maker, asset, Side::Ask, Price::from_float(1.0), Size(U256::from(100)), 100)
             .unwrap();
+        sm.deposit_collateral(maker, AssetId(0), U256::from(1_000)).unwrap();
+        sm.on_block_end().unwrap();
+        let collateral = sm.get_collateral(&maker, AssetId(0));
         
+        // The block trades and moves collateral, then fails part-way
+        sm.on_block_begin(2, 200).unwrap();
+        let fills = sm.place_market_order_persistent(taker, asset, Side::Bid, Size(U256::from(40)), 200).unwrap();
         assert_eq!(fills.len(), 1);
-        assert_eq!(fills[0].size.0, U256::from(50));
+        sm.deposit_collateral(maker, AssetId(0), U256::from(500)).unwrap();
+        sm.abort_block();
+        
+        let storage = sm.storage.clone().unwrap();
+        assert_eq!(sm.get_height(), storage.load_committed_height().unwrap().unwrap());
+        assert_eq!(sm.clock().now(), 100);
+        assert_eq!(book_orders(&sm), stored_orders(&sm));
+        assert_eq!(sm.next_fill_seq, storage.load_next_fill_seq().unwrap());
+        assert_eq!(sm.get_collateral(&maker, AssetId(0)), collateral);
+        assert!(storage.load_fills(maker_order).unwrap().is_empty());
+        
+        // Retrying the block applies its effects once
+        sm.on_block_begin(2, 200).unwrap();
+        sm.place_market_order_persistent(taker, asset, Side::Bid, Size(U256::from(40)), 200).unwrap();
+        sm.on_block_end().unwrap();
+        assert_eq!(book_orders(&sm), vec![(maker_order, Size(U256::from(40)))]);
+        assert_eq!(book_orders(&sm), stored_orders(&sm));
+        assert_eq!(storage.load_fills(maker_order).unwrap().len(), 1);
+        
+        let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_position_tracking_after_trade() {
+    fn test_options_move_premiums_and_settlements() {
+        use crate::options::{OptionKind, OptionOrder, OptionSeries};
+        
+        let council = Address::from([9u8; 20]);
+        let writer = Address::from([1u8; 20]);
+        let buyer = Address::from([2u8; 20]);
+        let (underlying, call, cash) = (AssetId(1), AssetId(1001), AssetId(0));
         let mut sm = CoreStateMachine::new();
-        let maker = Address::from([1u8; 20]);
-        let taker = Address::from([2u8; 20]);
-        let asset = AssetId(1);
+        sm.set_governance(Governance::new([council]));
+        sm.oracle.set_price_source(underlying, PriceSource::External);
+        sm.update_oracle_price(underlying, Price::from_float(120.0), 0).unwrap();
+        
+        let series = OptionSeries {
+            id: call,
+            underlying,
+            kind: OptionKind::Call,
+            strike: Price::from_float(100.0),
+            expiry: 1000,
+        };
+        assert!(sm.list_option_series(&writer, series.clone()).is_err());
+        sm.list_option_series(&council, series).unwrap();
+        sm.deposit_collateral(writer, cash, U256::from(1000)).unwrap();
+        sm.deposit_collateral(buyer, cash, U256::from(40)).unwrap();
+        
+        sm.on_block_begin(1, 10).unwrap();
+        let order = |trader, side, size| OptionOrder { trader, series: call, side, premium: Price::from_float(5.0), size };
+        sm.place_option_order(order(writer, Side::Ask, 10)).unwrap();
+        // 10 contracts at 5 need 50 of premium
+        assert!(sm.place_option_order(order(buyer, Side::Bid, 10)).is_err());
+        sm.place_option_order(order(buyer, Side::Bid, 8)).unwrap();
+        assert_eq!(sm.get_collateral(&buyer, cash), U256::ZERO);
+        assert_eq!(sm.get_collateral(&writer, cash), U256::from(1040));
+        
+        // Short option margin stays deposited
+        assert!(sm.withdraw_collateral(writer, cash, U256::from(1040)).is_err());
+        sm.withdraw_collateral(writer, cash, U256::from(800)).unwrap();
+        sm.on_block_end().unwrap();
+        
+        // Expiry settles 8 contracts at 20 intrinsic
+        sm.on_block_begin(2, 1000).unwrap();
+        let end = sm.on_block_end().unwrap();
+        assert_eq!(end.option_settlements.len(), 2);
+        assert_eq!(sm.get_collateral(&buyer, cash), U256::from(160));
+        assert_eq!(sm.get_collateral(&writer, cash), U256::from(80));
+    }
+
+    #[test]
+    fn test_unfunded_option_bids_cancelled_and_options_recovered() {
+        use crate::options::{OptionKind, OptionOrder, OptionSeries};
         
-        // Setup collateral
-        sm.deposit_collateral(maker, AssetId(1), U256::from(10000)).unwrap();
-        sm.deposit_collateral(taker, AssetId(1), U256::from(10000)).unwrap();
+        let path = temp_db_path();
+        let council = Address::from([9u8; 20]);
+        let writer = Address::from([1u8; 20]);
+        let (broke, buyer) = (Address::from([2u8; 20]), Address::from([3u8; 20]));
+        let (underlying, call, cash) = (AssetId(1), AssetId(1001), AssetId(0));
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.set_governance(Governance::new([council]));
+        sm.oracle.set_price_source(underlying, PriceSource::External);
+        sm.update_oracle_price(underlying, Price::from_float(120.0), 0).unwrap();
+        sm.list_option_series(&council, OptionSeries {
+            id: call,
+            underlying,
+            kind: OptionKind::Call,
+            strike: Price::from_float(100.0),
+            expiry: 1000,
+        }).unwrap();
+        sm.deposit_collateral(writer, cash, U256::from(1000)).unwrap();
+        sm.deposit_collateral(broke, cash, U256::from(50)).unwrap();
+        sm.deposit_collateral(buyer, cash, U256::from(50)).unwrap();
+        
+        sm.on_block_begin(1, 10).unwrap();
+        let order = |trader, side, size| OptionOrder { trader, series: call, side, premium: Price::from_float(5.0), size };
+        sm.place_option_order(order(broke, Side::Bid, 10)).unwrap();
+        sm.withdraw_collateral(broke, cash, U256::from(50)).unwrap();
+        
+        // The unbacked bid is cancelled instead of rejecting the ask
+        let (_, fills) = sm.place_option_order(order(writer, Side::Ask, 10)).unwrap();
+        assert!(fills.is_empty());
+        sm.place_option_order(order(buyer, Side::Bid, 4)).unwrap();
+        assert_eq!(sm.get_collateral(&writer, cash), U256::from(1020));
+        sm.on_block_end().unwrap();
+        drop(sm);
+        
+        // Positions and resting orders survive a restart
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        assert_eq!(sm.options().get_position(&writer, call).unwrap().size, -4);
+        assert_eq!(sm.options().get_position(&buyer, call).unwrap().size, 4);
+        assert!(sm.options().get_position(&broke, call).is_none());
+        let book = sm.options().get_book(call).unwrap();
+        assert_eq!(book.orders().map(|o| o.trader).collect::<Vec<_>>(), vec![writer]);
+        assert_eq!(book.get_best_ask(), Some((Price::from_float(5.0), U256::from(6))));
         
-        // Add ask order
-        sm.place_limit_order(
-            maker,
-            asset,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_vaults_roll_back_with_aborted_blocks_and_recover() {
+        let path = temp_db_path();
+        let (owner, depositor) = (Address::from([1u8; 20]), Address::from([2u8; 20]));
+        let cash = AssetId(0);
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.deposit_collateral(owner, cash, U256::from(10_000)).unwrap();
+        sm.deposit_collateral(depositor, cash, U256::from(5_000)).unwrap();
+        
+        sm.on_block_begin(1, 10).unwrap();
+        let vault_id = sm.create_vault(owner, owner, cash, 1000, U256::from(10_000), 10).unwrap();
+        sm.on_block_end().unwrap();
+        
+        // A failed block's deposit is undone with the block
+        sm.on_block_begin(2, 20).unwrap();
+        sm.deposit_to_vault(vault_id, depositor, cash, U256::from(5_000), 20).unwrap();
+        sm.abort_block();
+        assert!(sm.vaults().shares_of(vault_id, &depositor).is_zero());
+        assert_eq!(sm.get_collateral(&depositor, cash), U256::from(5_000));
+        
+        sm.on_block_begin(2, 20).unwrap();
+        let shares = sm.deposit_to_vault(vault_id, depositor, cash, U256::from(4_000), 20).unwrap();
+        sm.on_block_end().unwrap();
+        drop(sm);
+        
+        // Vaults and shares survive a restart
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        assert_eq!(sm.vaults().shares_of(vault_id, &depositor), shares);
+        assert_eq!(sm.vaults().total_shares(vault_id), U256::from(14_000));
+        let amount = sm.withdraw_from_vault(vault_id, depositor, cash, shares, 30).unwrap();
+        assert_eq!(amount, U256::from(4_000));
         
-        // Execute bid with margin
-        sm.place_market_order_with_margin(
-            taker,
-            asset,
-            Side::Bid,
-            Size(U256::from(50)),
-            1,
-        )
-        .unwrap();
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_restore_checkpoint_undoes_changes_in_memory_and_storage() {
+        let path = temp_db_path();
+        let user = Address::from([1u8; 20]);
+        let cash = AssetId(0);
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.deposit_collateral(user, cash, U256::from(1_000)).unwrap();
+        
+        let checkpoint = sm.checkpoint();
+        sm.create_vault(user, user, cash, 0, U256::from(600), 10).unwrap();
+        sm.set_index_price(AssetId(1), Price::from_float(5.0));
+        sm.restore_checkpoint(checkpoint).unwrap();
+        assert_eq!(sm.get_collateral(&user, cash), U256::from(1_000));
+        assert!(sm.vaults().get_vault(1).is_none());
+        assert_eq!(sm.get_index_price(AssetId(1)), None);
+        drop(sm);
+        
+        // The vault written when it was created is overwritten too
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        assert!(sm.vaults().get_vault(1).is_none());
         
-        // Check taker's position (long)
-        let position = sm.get_position(&taker, asset).unwrap();
-        assert_eq!(position.size, 50);
-        assert_eq!(position.entry_price, Price::from_float(1.0));
+        let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_long_and_short_positions() {
-        let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
-        let counterparty = Address::from([2u8; 20]);
+    fn test_oracle_reporters_are_governed_and_committed() {
+        let path = temp_db_path();
+        let governor = Address::from([1u8; 20]);
+        let reporter = Address::from([2u8; 20]);
         let asset = AssetId(1);
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.set_governance(Governance::new([governor]));
         
-        // Setup collateral
-        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
-        sm.deposit_collateral(counterparty, AssetId(1), U256::from(10000)).unwrap();
+        assert!(sm.authorize_oracle_reporter(&reporter, asset, reporter).is_err());
+        sm.authorize_oracle_reporter(&governor, asset, reporter).unwrap();
+        assert!(sm.is_oracle_reporter(asset, &reporter));
         
-        // Trader goes long
-        sm.place_limit_order(
-            counterparty,
-            asset,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
+        // Rolled back with the rest of core
+        let checkpoint = sm.checkpoint();
+        sm.revoke_oracle_reporter(&governor, asset, &reporter).unwrap();
+        sm.restore_checkpoint(checkpoint).unwrap();
+        assert!(sm.is_oracle_reporter(asset, &reporter));
+        drop(sm);
         
-        sm.place_market_order_with_margin(
-            trader,
-            asset,
-            Side::Bid,
-            Size(U256::from(50)),
-            1,
-        )
-        .unwrap();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        assert!(sm.is_oracle_reporter(asset, &reporter));
+        assert!(!sm.is_oracle_reporter(AssetId(2), &reporter));
         
-        let position = sm.get_position(&trader, asset).unwrap();
-        assert_eq!(position.size, 50); // Long 50
+        let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_account_health_after_position() {
+    fn test_block_end_accrues_funding() {
         let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
+        let maker = Address::from([1u8; 20]);
+        let long = Address::from([2u8; 20]);
+        let short = Address::from([3u8; 20]);
         let asset = AssetId(1);
         
-        // Deposit collateral
-        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
-        
-        // Place order with margin
-        sm.place_limit_order_with_margin(
-            trader,
-            asset,
-            Side::Bid,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
+        // Mark (book mid) 105 against index 100
+        sm.place_limit_order(maker, asset, Side::Bid, Price::from_float(100.0), Size(U256::from(1)), 0)
+            .unwrap();
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(110.0), Size(U256::from(1)), 0)
+            .unwrap();
+        sm.set_index_price(asset, Price::from_float(100.0));
+        sm.funding_engine_mut().set_settlement_asset(asset);
+        
+        for (user, size) in [(long, 1000), (short, -1000)] {
+            sm.deposit_collateral(user, asset, U256::from(1_000_000)).unwrap();
+            sm.margin_engine
+                .update_position(user, asset, size, Price::from_float(105.0), 0)
+                .unwrap();
+        }
         
-        // Account should still be healthy
-        assert!(sm.is_account_healthy(&trader).unwrap());
+        sm.on_block_begin(1, 28_800).unwrap();
+        let end = sm.on_block_end().unwrap();
+        
+        // Rate clamped to 0.05%: 105_000 notional -> 52.5, netted into the
+        // cross margin accounts' collateral. The payer is rounded up and the
+        // receiver down, so the half unit stays with the protocol.
+        assert!(end.funding_payments.is_empty());
+        assert_eq!(end.funding_settlements.len(), 2);
+        assert_eq!(end.funding_settlements[0].user, long);
+        assert_eq!(end.funding_settlements[0].amount, -53);
+        assert_eq!(end.funding_settlements[1].amount, 52);
+        assert_eq!(sm.get_collateral(&long, asset), U256::from(1_000_000 - 53));
+        assert_eq!(sm.get_collateral(&short, asset), U256::from(1_000_000 + 52));
+        assert!(end.liquidations.is_empty());
+        
+        // Not due again until the next interval
+        sm.on_block_begin(2, 28_801).unwrap();
+        assert!(sm.on_block_end().unwrap().funding_settlements.is_empty());
     }
 
     #[test]
-    fn test_check_liquidations_healthy_account() {
+    fn test_funding_config_change_phases_in() {
         let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
-        let asset = AssetId(1);
+        let council = Address::from([9u8; 20]);
+        sm.set_governance(Governance::new([council]));
+        
+        let target = FundingConfig { interval: 3600, ..FundingConfig::default() };
+        assert!(sm.set_funding_config(&Address::ZERO, target.clone()).is_err());
+        sm.set_funding_config(&council, target).unwrap();
+        
+        // One bounded step per elapsed funding interval
+        sm.on_block_begin(1, 28_800).unwrap();
+        sm.on_block_end().unwrap();
+        assert_eq!(sm.funding_engine().config().interval, 25_200);
+        sm.on_block_begin(2, 28_801).unwrap();
+        sm.on_block_end().unwrap();
+        assert_eq!(sm.funding_engine().config().interval, 25_200);
+    }
+
+    #[test]
+    fn test_token_deposits_are_decimals_aware() {
+        let council = Address::from([9u8; 20]);
+        let user = Address::from([1u8; 20]);
+        let (usdc, weth) = (AssetId(1), AssetId(2));
+        let mut sm = CoreStateMachine::new();
+        sm.set_governance(Governance::new([council]));
+        let token = |asset: AssetId, decimals: u8| TokenInfo {
+            asset,
+            address: Address::with_last_byte(asset.0 as u8),
+            symbol: format!("T{}", asset.0),
+            name: String::new(),
+            decimals,
+        };
+        assert!(sm.register_token(&user, token(usdc, 6)).is_err());
+        sm.register_token(&council, token(usdc, 6)).unwrap();
+        sm.register_token(&council, token(weth, 18)).unwrap();
+        
+        // 100 of each token is the same collateral in engine precision
+        let hundred_usdc = sm.deposit_token(user, usdc, U256::from(100_000_000u64)).unwrap();
+        let hundred_weth = sm.deposit_token(user, weth, U256::from(100u64) * U256::from(10).pow(U256::from(18))).unwrap();
+        assert_eq!(hundred_usdc, hundred_weth);
+        assert_eq!(sm.get_collateral(&user, usdc), hundred_usdc);
+        
+        sm.withdraw_token(user, usdc, U256::from(40_000_000u64)).unwrap();
+        let left = sm.tokens().from_canonical(usdc, sm.get_collateral(&user, usdc)).unwrap();
+        assert_eq!(left.amount, U256::from(60_000_000u64));
+    }
+    
+    #[test]
+    fn test_kill_switch_leaves_market_cancel_only() {
+        use crate::emergency::PauseScope;
+        use alloy_primitives::PrimitiveSignature as Signature;
+        use k256::ecdsa::SigningKey;
+        
+        let sk = SigningKey::from_slice(&[1; 32]).unwrap();
+        let trader = Address::from_private_key(&sk);
+        let sign = |nonce: u64, action: CoreAction| {
+            let hash = SignedAction::signing_hash(DEFAULT_CHAIN_ID, &trader, nonce, &action);
+            let (sig, recid) = sk.sign_prehash_recoverable(hash.as_slice()).unwrap();
+            SignedAction { account: trader, nonce, action, signature: Signature::from_signature_and_parity(sig, recid.is_y_odd()) }
+        };
+        let (council, asset) = (Address::from([9u8; 20]), AssetId(1));
+        let mut sm = CoreStateMachine::new();
+        sm.set_governance(Governance::new([council]));
+        sm.deposit_collateral(trader, asset, U256::from(1_000)).unwrap();
+        let (order_id, _) = sm
+            .place_limit_order(trader, asset, Side::Bid, Price::from_float(1.0), Size(U256::from(10)), 0)
+            .unwrap();
         
-        // Deposit large collateral
-        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
+        let kill = EmergencyAction::KillSwitch { scope: PauseScope::Market(asset) };
+        assert!(sm.propose_emergency_action(&trader, kill.clone()).is_err());
+        sm.propose_emergency_action(&council, kill).unwrap();
         
-        // Small position
-        sm.place_limit_order_with_margin(
-            trader,
-            asset,
-            Side::Bid,
-            Price::from_float(1.0),
-            Size(U256::from(10)),
-            0,
-        )
-        .unwrap();
+        assert!(sm.place_limit_order(trader, asset, Side::Bid, Price::from_float(1.0), Size(U256::from(10)), 0).is_err());
+        assert!(sm.withdraw_collateral(trader, asset, U256::from(1)).is_err());
+        assert!(sm.emergency_controls().is_cancel_only(asset));
+        sm.dispatch(&sign(0, CoreAction::CancelOrder { asset, order_id })).unwrap();
+        assert_eq!(sm.emergency_controls().audit_log().len(), 2);
+    }
+    
+    #[test]
+    fn test_priority_cancels_run_before_block_orders() {
+        use crate::scheduling::SchedulingPolicy;
+        use alloy_primitives::PrimitiveSignature as Signature;
+        use k256::ecdsa::SigningKey;
+        
+        let key = |seed: u8| {
+            let sk = SigningKey::from_slice(&[seed; 32]).unwrap();
+            let addr = Address::from_private_key(&sk);
+            (sk, addr)
+        };
+        let sign = |sk: &SigningKey, account: Address, nonce: u64, action: CoreAction| {
+            let hash = SignedAction::signing_hash(DEFAULT_CHAIN_ID, &account, nonce, &action);
+            let (sig, recid) = sk.sign_prehash_recoverable(hash.as_slice()).unwrap();
+            SignedAction { account, nonce, action, signature: Signature::from_signature_and_parity(sig, recid.is_y_odd()) }
+        };
+        let (maker_sk, maker) = key(1);
+        let (taker_sk, taker) = key(2);
+        let (council, asset) = (Address::from([9u8; 20]), AssetId(1));
+        
+        let run = |policy: SchedulingPolicy| {
+            let mut sm = CoreStateMachine::new();
+            sm.set_governance(Governance::new([council]));
+            assert!(sm.set_scheduling_policy(&maker, policy).is_err());
+            sm.set_scheduling_policy(&council, policy).unwrap();
+            sm.deposit_collateral(maker, AssetId(0), U256::from(1_000_000)).unwrap();
+            sm.deposit_collateral(taker, AssetId(0), U256::from(1_000_000)).unwrap();
+            sm.on_block_begin(1, 100).unwrap();
+            let price = Price::from_float(100.0);
+            let order_id = match sm
+                .dispatch(&sign(&maker_sk, maker, 0, CoreAction::PlaceLimitOrder { asset, side: Side::Ask, price, size: Size(U256::from(1)) }))
+                .unwrap()
+            {
+                ActionOutcome::OrderPlaced { order_id, .. } => order_id,
+                outcome => panic!("unexpected outcome {:?}", outcome),
+            };
+            sm.on_block_end().unwrap();
+            
+            // The taker's order is ahead of the maker's cancel in the payload
+            sm.on_block_begin(2, 101).unwrap();
+            sm.dispatch_block(&[
+                sign(&taker_sk, taker, 0, CoreAction::PlaceMarketOrder { asset, side: Side::Bid, size: Size(U256::from(1)) }),
+                sign(&maker_sk, maker, 1, CoreAction::CancelOrder { asset, order_id }),
+            ])
+        };
         
-        let mut prices = HashMap::new();
-        prices.insert(asset, Price::from_float(1.0));
+        let sequential = run(SchedulingPolicy::Sequential);
+        assert!(matches!(&sequential[0], Ok(ActionOutcome::MarketFilled { fills }) if fills.len() == 1));
+        assert!(sequential[1].is_err());
         
-        let liquidations = sm.check_liquidations(&prices, 0).unwrap();
-        assert_eq!(liquidations.len(), 0);
+        // With priority cancels the quote is pulled before the taker arrives
+        let prioritized = run(SchedulingPolicy::PriorityCancels);
+        assert!(matches!(&prioritized[0], Ok(ActionOutcome::MarketFilled { fills }) if fills.is_empty()));
+        assert!(matches!(prioritized[1], Ok(ActionOutcome::OrderCancelled { .. })));
     }
-
+    
     #[test]
-    fn test_get_liquidations() {
-        let sm = CoreStateMachine::new();
-        let liquidations = sm.get_liquidations();
-        assert_eq!(liquidations.len(), 0);
+    fn test_block_end_settles_fees_under_governance() {
+        let mut sm = CoreStateMachine::new();
+        let council = Address::from([9u8; 20]);
+        let treasury = Address::from([8u8; 20]);
+        let routing = FeeRouting {
+            treasury_bps: 6000,
+            insurance_bps: 2000,
+            staker_bps: 2000,
+            burn_bps: 0,
+        };
+        
+        // Nothing is governed until authorities are configured
+        assert!(sm.set_fee_routing(&council, routing).is_err());
+        sm.set_governance(Governance::new([council]));
+        assert!(sm.set_fee_routing(&treasury, routing).is_err());
+        sm.set_fee_routing(&council, routing).unwrap();
+        
+        sm.on_block_begin(1, 1000).unwrap();
+        sm.fee_engine_mut().record_trade(Address::ZERO, U256::from(100_000), false, 1000);
+        let end = sm.on_block_end().unwrap();
+        assert_eq!(end.fee_settlement.collected, U256::from(100));
+        assert_eq!(end.fee_settlement.distributed[&FeeDestination::Treasury], U256::from(60));
+        
+        assert!(sm
+            .claim_fees(&treasury, FeeDestination::Treasury, treasury, AssetId(0), U256::from(60))
+            .is_err());
+        sm.claim_fees(&council, FeeDestination::Treasury, treasury, AssetId(0), U256::from(60))
+            .unwrap();
+        assert_eq!(sm.get_collateral(&treasury, AssetId(0)), U256::from(60));
+        assert_eq!(sm.fee_engine().destination_balance(FeeDestination::Treasury), U256::ZERO);
+        assert_eq!(sm.fee_engine().destination_balance(FeeDestination::Stakers), U256::from(20));
     }
 
     #[test]
-    fn test_margin_config_custom() {
-        let config = MarginConfig {
-            initial_margin_ratio: 0.2,  // 20%
-            maintenance_margin_ratio: 0.1,  // 10%
-            max_leverage: 5,
-        };
+    fn test_emissions_epoch_rewards_and_claim() {
+        use crate::emissions::EmissionsConfig;
         
-        let sm = CoreStateMachine::new_with_margin_config(config);
-        assert_eq!(sm.books.len(), 0);
+        let mut sm = CoreStateMachine::new();
+        *sm.emissions_mut() = EmissionsEngine::new(EmissionsConfig {
+            epoch_length: 100,
+            reward_per_epoch: U256::from(1000),
+            ..EmissionsConfig::default()
+        });
+        let maker = Address::from([1u8; 20]);
+        let buyer = Address::from([2u8; 20]);
+        let seller = Address::from([3u8; 20]);
+        let asset = AssetId(1);
+        let size = Size(U256::from(1000));
+        
+        sm.on_block_begin(1, 10).unwrap();
+        sm.place_limit_order(maker, asset, Side::Bid, Price::from_float(99.0), size, 10).unwrap();
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(101.0), size, 10).unwrap();
+        sm.place_market_order(buyer, asset, Side::Bid, Size(U256::from(500)), 10).unwrap();
+        sm.place_market_order(seller, asset, Side::Ask, Size(U256::from(500)), 10).unwrap();
+        sm.on_block_end().unwrap();
+        
+        sm.on_block_begin(2, 60).unwrap();
+        sm.on_block_end().unwrap();
+        assert_eq!(sm.emissions().volume_scores().len(), 1);
+        
+        // First block of epoch 1 finalizes epoch 0
+        let begin = sm.on_block_begin(3, 120).unwrap();
+        sm.on_block_end().unwrap();
+        assert_eq!(begin.emissions_epoch, Some(0));
+        
+        // Takers have a single counterparty each, so only the two-sided
+        // maker scores in both pools
+        let rewards = sm.emissions().epoch_rewards(0).unwrap();
+        assert_eq!(rewards.rewards, vec![(maker, U256::from(1000))]);
+        
+        let (amount, proof) = sm.emissions().proof(0, &maker).unwrap();
+        assert!(sm.claim_rewards(buyer, 0, amount, &proof).is_err());
+        sm.claim_rewards(maker, 0, amount, &proof).unwrap();
+        assert_eq!(sm.get_balance(&maker, AssetId(0)), U256::from(1000));
+        assert!(sm.claim_rewards(maker, 0, amount, &proof).is_err());
     }
 
     #[test]
-    fn test_full_margin_workflow() {
-        let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
-        let counterparty = Address::from([2u8; 20]);
+    fn test_dispatch_enforces_session_key_scope() {
+        use crate::auth::SessionScope;
+        use alloy_primitives::PrimitiveSignature as Signature;
+        use k256::ecdsa::SigningKey;
+        
+        let key = |seed: u8| {
+            let sk = SigningKey::from_slice(&[seed; 32]).unwrap();
+            let addr = Address::from_private_key(&sk);
+            (sk, addr)
+        };
+        let sign = |sk: &SigningKey, account: Address, nonce: u64, action: CoreAction| {
+            let hash = SignedAction::signing_hash(DEFAULT_CHAIN_ID, &account, nonce, &action);
+            let (sig, recid) = sk.sign_prehash_recoverable(hash.as_slice()).unwrap();
+            let signature = Signature::from_signature_and_parity(sig, recid.is_y_odd());
+            SignedAction { account, nonce, action, signature }
+        };
+        let (master_sk, account) = key(1);
+        let (hot_sk, hot) = key(2);
+        let (other_sk, other) = key(3);
         let asset = AssetId(1);
         
-        // 1. Deposit collateral
-        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
-        sm.deposit_collateral(counterparty, AssetId(1), U256::from(10000)).unwrap();
-        
-        let equity = sm.get_account_equity(&trader).unwrap();
-        assert_eq!(equity, U256::from(10000));
+        let path = temp_db_path();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.deposit_collateral(account, AssetId(0), U256::from(1000)).unwrap();
+        sm.on_block_begin(1, 100).unwrap();
         
-        // 2. Open position
-        sm.place_limit_order(
-            counterparty,
-            asset,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
+        let register = CoreAction::RegisterSessionKey { key: hot, scope: SessionScope::OrderOnly, expires_at: 200 };
+        // Session keys cannot grant themselves access
+        assert!(sm.dispatch(&sign(&hot_sk, account, 0, register.clone())).is_err());
+        sm.dispatch(&sign(&master_sk, account, 0, register)).unwrap();
         
-        sm.place_market_order_with_margin(
-            trader,
+        let place = CoreAction::PlaceLimitOrder {
             asset,
-            Side::Bid,
-            Size(U256::from(100)),
-            1,
-        )
-        .unwrap();
-        
-        let position = sm.get_position(&trader, asset).unwrap();
-        assert_eq!(position.size, 100);
+            side: Side::Bid,
+            price: Price::from_float(100.0),
+            size: Size(U256::from(1)),
+        };
+        let order_id = match sm.dispatch(&sign(&hot_sk, account, 1, place)).unwrap() {
+            ActionOutcome::OrderPlaced { order_id, .. } => order_id,
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        };
+        assert_eq!(sm.sessions().next_nonce(&account), 2);
+        
+        // Hot key cannot withdraw; a failed action leaves the nonce unused
+        let withdraw = CoreAction::Withdraw { asset: AssetId(0), amount: U256::from(400) };
+        assert!(sm.dispatch(&sign(&hot_sk, account, 2, withdraw.clone())).is_err());
+        sm.dispatch(&sign(&master_sk, account, 2, withdraw)).unwrap();
+        assert_eq!(sm.get_collateral(&account, AssetId(0)), U256::from(600));
+        
+        // Other accounts cannot cancel the order, even with their own key
+        let cancel = CoreAction::CancelOrder { asset, order_id };
+        assert!(sm.dispatch(&sign(&other_sk, other, 0, cancel.clone())).is_err());
+        assert!(sm.dispatch(&sign(&hot_sk, account, 2, cancel.clone())).is_err());
+        sm.dispatch(&sign(&hot_sk, account, 3, cancel)).unwrap();
+        sm.on_block_end().unwrap();
+        drop(sm);
+        
+        // Nonces and session keys survive a restart
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        assert_eq!(sm.sessions().next_nonce(&account), 4);
+        assert_eq!(sm.sessions().session_keys(&account).len(), 1);
         
-        // 3. Account should be healthy
-        assert!(sm.is_account_healthy(&trader).unwrap());
+        // Expired session keys are pruned at block begin
+        sm.on_block_begin(2, 201).unwrap();
+        assert!(sm.sessions().session_keys(&account).is_empty());
+        sm.on_block_end().unwrap();
+        drop(sm);
         
-        // 4. Try to withdraw (should work)
-        sm.withdraw_collateral(trader, AssetId(1), U256::from(1000)).unwrap();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.recover().unwrap();
+        assert!(sm.sessions().session_keys(&account).is_empty());
         
-        let new_equity = sm.get_account_equity(&trader).unwrap();
-        assert_eq!(new_equity, U256::from(9000));
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_margin_prevents_overleveraging() {
-        let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
+    fn test_pool_epoch_stats_persisted_with_snapshots() {
+        let path = temp_db_path();
+        let maker = Address::from([1u8; 20]);
+        let trader = Address::from([2u8; 20]);
         let asset = AssetId(1);
         
-        // Small collateral
-        sm.deposit_collateral(trader, AssetId(1), U256::from(100)).unwrap();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        for (side, price) in [(Side::Bid, 99.0), (Side::Ask, 101.0)] {
+            sm.place_limit_order(maker, asset, side, Price::from_float(price), Size(U256::from(1)), 0)
+                .unwrap();
+        }
+        let pool = sm.pools_mut().create_pool(asset, vec![Price::from_float(100.2)], Size(U256::from(300)), 0).unwrap();
+        sm.pools_mut().add_liquidity(pool, maker, U256::from(100_000)).unwrap();
         
-        // Try to open huge position - should fail
-        let result = sm.place_limit_order_with_margin(
-            trader,
-            asset,
-            Side::Bid,
-            Price::from_float(1.0),
-            Size(U256::from(10000)),
-            0,
-        );
+        // The pool sells 300 at 100.2 (fee 90)
+        let routed = sm
+            .route_order(trader, asset, Side::Bid, Size(U256::from(300)), Some(Price::from_float(100.5)), 10)
+            .unwrap();
+        assert_eq!(routed.plan.venue_size(Venue::Pool(pool)), Size(U256::from(300)));
         
-        assert!(result.is_err());
+        sm.on_block_begin(1, 3600).unwrap();
+        assert_eq!(sm.on_block_end().unwrap().snapshot_epoch, Some(1));
+        
+        // Marked at the 100 mid, selling at 100.2 beat holding
+        let stats = sm.pools().epoch_stats(pool)[0].clone();
+        assert_eq!(stats.epoch_fees, U256::from(91));
+        assert_eq!(stats.impermanent_loss, 60);
+        assert_eq!(stats.hodl_value, 100_000);
+        assert_eq!(sm.storage.as_ref().unwrap().load_pool_epoch_stats(pool).unwrap(), vec![stats.clone()]);
+        
+        let report = sm.pool_lp_report(pool, maker, 1).unwrap();
+        assert_eq!(report.fees_earned, U256::from(91));
+        assert_eq!(report.rolling_apr_bps, stats.fee_apr_bps);
+        
+        let _ = std::fs::remove_dir_all(path);
     }
-
+    
     #[test]
-    fn test_multiple_positions_different_assets() {
-        let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
-        let counterparty = Address::from([2u8; 20]);
-        let asset1 = AssetId(1);
-        let asset2 = AssetId(2);
+    fn test_block_end_snapshots_accounts_per_epoch() {
+        let path = temp_db_path();
+        let maker = Address::from([1u8; 20]);
+        let trader = Address::from([2u8; 20]);
+        let asset = AssetId(1);
         
-        // Deposit collateral
-        sm.deposit_collateral(trader, AssetId(1), U256::from(10000)).unwrap();
-        sm.deposit_collateral(counterparty, AssetId(1), U256::from(10000)).unwrap();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.deposit_collateral(trader, AssetId(0), U256::from(1_000_000)).unwrap();
+        sm.margin_engine
+            .update_position(trader, asset, 10, Price::from_float(100.0), 0)
+            .unwrap();
+        let quote = |sm: &mut CoreStateMachine, bid: f64, ask: f64, ts: u64| {
+            for (side, price) in [(Side::Bid, bid), (Side::Ask, ask)] {
+                sm.place_limit_order(maker, asset, side, Price::from_float(price), Size(U256::from(1)), ts)
+                    .unwrap();
+            }
+        };
         
-        // Add liquidity for asset 1
-        sm.place_limit_order(
-            counterparty,
-            asset1,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(100)),
-            0,
-        )
-        .unwrap();
+        // Epoch 1 snapshot at mark 100
+        quote(&mut sm, 99.0, 101.0, 3600);
+        sm.on_block_begin(1, 3600).unwrap();
+        assert_eq!(sm.on_block_end().unwrap().snapshot_epoch, Some(1));
         
-        // Open position on asset 1
-        sm.place_market_order_with_margin(
-            trader,
-            asset1,
-            Side::Bid,
-            Size(U256::from(50)),
-            1,
-        )
-        .unwrap();
+        // Same epoch: no new snapshot
+        sm.on_block_begin(2, 3700).unwrap();
+        assert_eq!(sm.on_block_end().unwrap().snapshot_epoch, None);
         
-        // Add liquidity for asset 2
-        sm.place_limit_order(
-            counterparty,
-            asset2,
-            Side::Ask,
-            Price::from_float(2.0),
-            Size(U256::from(100)),
-            2,
-        )
-        .unwrap();
+        // Epoch 3 snapshot at mark 105: the 109 bid lifts the 101 ask, leaving 99 / 111
+        quote(&mut sm, 109.0, 111.0, 10_800);
+        sm.on_block_begin(3, 10_800).unwrap();
+        assert_eq!(sm.on_block_end().unwrap().snapshot_epoch, Some(3));
         
-        // Open position on asset 2
-        sm.place_market_order_with_margin(
-            trader,
-            asset2,
-            Side::Bid,
-            Size(U256::from(25)),
-            3,
-        )
-        .unwrap();
+        let history = sm.pnl_history().unwrap();
+        let snapshots = history.get_snapshots(&trader).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].unrealized_pnl, 0);
+        assert_eq!(snapshots[1].positions[0].mark_price, Some(Price::from_float(105.0)));
+        assert_eq!(history.pnl_over(&trader, 7200, 10_800).unwrap(), 50_000_000);
+        assert_eq!(history.get_snapshots(&maker).unwrap().len(), 0);
         
-        // Check both positions exist
-        assert!(sm.get_position(&trader, asset1).is_some());
-        assert!(sm.get_position(&trader, asset2).is_some());
+        let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_withdraw_blocked_by_margin_requirements() {
-        let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
-        let counterparty = Address::from([2u8; 20]);
+    fn test_funding_history_range_queries() {
+        let path = temp_db_path();
+        let maker = Address::from([1u8; 20]);
+        let long = Address::from([2u8; 20]);
         let asset = AssetId(1);
         
-        // Deposit collateral: $1100 (enough for position + buffer)
-        sm.deposit_collateral(trader, AssetId(1), U256::from(1100)).unwrap();
-        sm.deposit_collateral(counterparty, AssetId(1), U256::from(100000)).unwrap();
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.place_limit_order(maker, asset, Side::Bid, Price::from_float(100.0), Size(U256::from(1)), 0)
+            .unwrap();
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(110.0), Size(U256::from(1)), 0)
+            .unwrap();
+        sm.set_index_price(asset, Price::from_float(100.0));
+        sm.deposit_collateral(long, asset, U256::from(1_000_000)).unwrap();
+        sm.margin_engine
+            .update_position(long, asset, 1000, Price::from_float(105.0), 0)
+            .unwrap();
         
-        // Open large position: 10000 units @ $1 = $10000 notional, 10% margin = $1000
-        sm.place_limit_order(
-            counterparty,
-            asset,
-            Side::Ask,
-            Price::from_float(1.0),
-            Size(U256::from(10000)),
-            0,
-        )
-        .unwrap();
+        // Rounds at 28_800 and 57_600; the block in between is not a round
+        for (height, ts) in [(1, 28_800), (2, 30_000), (3, 57_600)] {
+            sm.on_block_begin(height, ts).unwrap();
+            sm.on_block_end().unwrap();
+        }
         
-        sm.place_market_order_with_margin(
-            trader,
-            asset,
-            Side::Bid,
-            Size(U256::from(10000)),
-            1,
-        )
-        .unwrap();
+        let history = sm.funding_history(asset, 0, u64::MAX).unwrap();
+        assert_eq!(history.iter().map(|r| r.timestamp).collect::<Vec<_>>(), vec![28_800, 57_600]);
+        assert_eq!(history[0].rate, 0.0005);
+        assert_eq!(history[0].mark_price, Price::from_float(105.0));
+        assert_eq!(history[0].index_price, Price::from_float(100.0));
         
-        // Margin used: $1000
-        // Maintenance requirement: $1000 * 0.05 = $50
-        // Can withdraw at most: $1100 - $50 = $1050
-        // Try to withdraw $1051 - should fail
-        let result = sm.withdraw_collateral(trader, AssetId(1), U256::from(1051));
-        assert!(result.is_err());
+        assert_eq!(sm.funding_history(asset, 30_000, 60_000).unwrap().len(), 1);
+        assert!(sm.funding_history(AssetId(2), 0, u64::MAX).unwrap().is_empty());
+        
+        let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_position_with_no_collateral_account() {
-        let sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
+    fn test_block_end_persists_depth_snapshots() {
+        let path = temp_db_path();
+        let maker = Address::from([1u8; 20]);
         let asset = AssetId(1);
         
-        // No position should exist
-        assert!(sm.get_position(&trader, asset).is_none());
+        let mut sm = CoreStateMachine::new_with_storage(&path, 100).unwrap();
+        sm.set_depth_config(DepthConfig { interval: 60, levels: 1 });
+        for (side, price) in [(Side::Bid, 99.0), (Side::Bid, 98.0), (Side::Ask, 101.0)] {
+            sm.place_limit_order(maker, asset, side, Price::from_float(price), Size(U256::from(2)), 0)
+                .unwrap();
+        }
+        
+        sm.on_block_begin(1, 60).unwrap();
+        assert_eq!(sm.on_block_end().unwrap().depth_snapshot, Some(60));
+        sm.on_block_begin(2, 90).unwrap();
+        assert_eq!(sm.on_block_end().unwrap().depth_snapshot, None);
+        sm.on_block_begin(3, 125).unwrap();
+        assert_eq!(sm.on_block_end().unwrap().depth_snapshot, Some(125));
+        
+        let snapshots = sm.depth_history().unwrap().range(asset, 0, 1000).unwrap();
+        assert_eq!(snapshots.iter().map(|s| s.timestamp).collect::<Vec<_>>(), vec![60, 125]);
+        assert_eq!(snapshots[0].bids, vec![(Price::from_float(99.0), U256::from(2))]);
+        assert_eq!(snapshots[0].spread(), Some(Price::from_float(2.0)));
+        
+        let _ = std::fs::remove_dir_all(path);
     }
 
     #[test]
-    fn test_backward_compatibility_regular_orders() {
+    fn test_batch_auction_clears_block_at_single_price() {
+        let council = Address::from([9u8; 20]);
+        let (alice, bob, carol) = (Address::from([1u8; 20]), Address::from([2u8; 20]), Address::from([3u8; 20]));
+        let asset = AssetId(1);
+        let size = |n: u64| Size(U256::from(n));
+        
         let mut sm = CoreStateMachine::new();
-        let trader = Address::from([1u8; 20]);
+        sm.set_governance(Governance::new([council]));
+        assert!(sm.set_batch_auction(&alice, asset, Some(0)).is_err());
+        sm.set_batch_auction(&council, asset, Some(0)).unwrap();
+        
+        // Takers arriving in the same block do not race each other
+        sm.on_block_begin(1, 10).unwrap();
+        sm.place_limit_order(carol, asset, Side::Ask, Price::from_float(100.0), size(5), 10).unwrap();
+        let (_, fills) = sm.place_limit_order(alice, asset, Side::Bid, Price::from_float(101.0), size(3), 10).unwrap();
+        assert!(fills.is_empty());
+        sm.place_limit_order(bob, asset, Side::Bid, Price::from_float(102.0), size(3), 10).unwrap();
+        assert!(sm.place_market_order(bob, asset, Side::Bid, size(1), 10).is_err());
+        
+        let report = sm.on_block_end().unwrap();
+        assert_eq!(report.batch_auctions.len(), 1);
+        let auction = &report.batch_auctions[0];
+        assert_eq!(auction.clearing_price, Price::from_float(100.0));
+        assert!(auction.fills.iter().all(|f| f.price == auction.clearing_price));
+        assert_eq!(auction.fills.iter().map(|f| f.size.0).sum::<U256>(), U256::from(5));
+        
+        // Back to continuous matching
+        sm.set_batch_auction(&council, asset, None).unwrap();
+        assert_eq!(sm.batch_auction_interval(asset), None);
+        let fills = sm.place_market_order(carol, asset, Side::Ask, size(1), 20).unwrap();
+        assert_eq!(fills[0].price, Price::from_float(101.0));
+    }
+    
+    #[test]
+    fn test_thin_market_auction_and_post_only_modes() {
+        let council = Address::from([9u8; 20]);
+        let (alice, bob, carol) = (Address::from([1u8; 20]), Address::from([2u8; 20]), Address::from([3u8; 20]));
         let asset = AssetId(1);
+        let size = |n: u64| Size(U256::from(n));
         
-        // Old API should still work (no margin checks)
-        let (order_id, fills) = sm
-            .place_limit_order(
-                trader,
-                asset,
-                Side::Bid,
-                Price::from_float(1.0),
-                Size(U256::from(100)),
-                0,
-            )
+        let mut sm = CoreStateMachine::new();
+        sm.set_governance(Governance::new([council]));
+        assert!(sm.set_market_mode(&alice, asset, MarketMode::Auction).is_err());
+        sm.set_market_mode(&council, asset, MarketMode::Auction).unwrap();
+        
+        // Crossing orders queue without matching; market orders are rejected
+        let (_, fills) = sm.place_limit_order(alice, asset, Side::Bid, Price::from_float(101.0), size(3), 0).unwrap();
+        assert!(fills.is_empty());
+        sm.place_limit_order(bob, asset, Side::Ask, Price::from_float(99.0), size(2), 0).unwrap();
+        sm.place_limit_order(carol, asset, Side::Ask, Price::from_float(100.0), size(2), 0).unwrap();
+        assert!(sm.place_market_order(alice, asset, Side::Bid, size(1), 0).is_err());
+        
+        // Uncross at the single price clearing the most volume
+        let fills = sm.set_market_mode(&council, asset, MarketMode::PostOnly).unwrap();
+        assert_eq!(fills.len(), 2);
+        assert!(fills.iter().all(|f| f.price == Price::from_float(100.0) && f.taker == alice));
+        assert_eq!(fills.iter().map(|f| f.size.0).sum::<U256>(), U256::from(3));
+        let book = sm.get_book(asset).unwrap();
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.get_best_ask(), Some((Price::from_float(100.0), U256::from(1))));
+        
+        // Post-only rejects crossing orders but accepts resting ones
+        assert!(sm.place_limit_order(alice, asset, Side::Bid, Price::from_float(100.0), size(1), 0).is_err());
+        sm.place_limit_order(alice, asset, Side::Bid, Price::from_float(99.5), size(1), 0).unwrap();
+        
+        // The monitor restores continuous trading once the SLA is met
+        sm.set_liquidity_sla(&council, asset, LiquiditySla {
+            min_samples: 1,
+            thin_market_mode: Some(MarketMode::PostOnly),
+            ..LiquiditySla::default()
+        }).unwrap();
+        sm.on_block_begin(1, 10).unwrap();
+        let report = sm.on_block_end().unwrap();
+        assert_eq!(report.market_mode_changes.len(), 1);
+        assert_eq!(report.market_mode_changes[0].mode, MarketMode::Continuous);
+        let stats = sm.liquidity_stats(asset).unwrap();
+        assert_eq!((stats.latest.spread_bps, stats.uptime_bps), (Some(50), 10000));
+        sm.place_market_order(alice, asset, Side::Bid, size(1), 20).unwrap();
+    }
+
+    #[test]
+    fn test_basket_perp_index_funding_and_risk() {
+        let council = Address::from([9u8; 20]);
+        let (maker, trader) = (Address::from([1u8; 20]), Address::from([2u8; 20]));
+        let basket_asset = AssetId(10);
+        
+        let mut sm = CoreStateMachine::new();
+        sm.set_governance(Governance::new([council]));
+        for asset in [1, 2] {
+            let listing = Listing { asset: AssetId(asset), symbol: format!("A{}", asset), kind: ListingKind::Perpetual };
+            sm.list_market(&council, listing).unwrap();
+        }
+        sm.risk_engine_mut().set_asset_limits(AssetId(1), crate::risk::AssetRiskLimits {
+            max_leverage: 2,
+            ..Default::default()
+        });
+        let component = |asset: u32, weight_bps: u64, reference: f64| crate::listing::BasketComponent {
+            asset: AssetId(asset),
+            weight_bps,
+            reference_price: Price::from_float(reference),
+        };
+        let basket = crate::listing::BasketDefinition {
+            components: vec![component(1, 5000, 100.0), component(2, 5000, 10.0)],
+            base_level: Price::from_float(1000.0),
+        };
+        let listing = Listing { asset: basket_asset, symbol: "IDX".into(), kind: ListingKind::Basket(basket) };
+        assert!(sm.list_market(&maker, listing.clone()).is_err());
+        sm.list_market(&council, listing).unwrap();
+        assert_eq!(sm.risk_engine().get_asset_limits(basket_asset).max_leverage, 2);
+        
+        // Component 1 up 20%, component 2 flat: index 1100
+        sm.update_oracle_price(AssetId(1), Price::from_float(120.0), 0).unwrap();
+        sm.update_oracle_price(AssetId(2), Price::from_float(10.0), 0).unwrap();
+        for (side, price) in [(Side::Bid, 1190.0), (Side::Ask, 1210.0)] {
+            sm.place_limit_order(maker, basket_asset, side, Price::from_float(price), Size(U256::from(5)), 0)
+                .unwrap();
+        }
+        sm.deposit_collateral(trader, AssetId(0), U256::from(1_000_000)).unwrap();
+        sm.margin_engine
+            .update_position(trader, basket_asset, 10, Price::from_float(1100.0), 0)
             .unwrap();
         
-        assert!(order_id > 0);
-        assert_eq!(fills.len(), 0);
+        sm.on_block_begin(1, 10).unwrap();
+        sm.on_block_end().unwrap();
+        assert_eq!(sm.oracle.get_index_price(basket_asset), Some(Price::from_float(1100.0)));
+        // Book at 1200 over a 1100 index: longs pay
+        assert!(sm.funding_engine().get_rate(basket_asset) > 0.0);
+        
+        // Basket leverage is capped by its most restrictive component
+        sm.deposit_collateral(maker, AssetId(0), U256::from(10_000)).unwrap();
+        assert!(sm
+            .place_limit_order_with_margin(maker, basket_asset, Side::Bid, Price::from_float(1000.0), Size(U256::from(30)), 20)
+            .is_err());
+        sm.place_limit_order_with_margin(maker, basket_asset, Side::Bid, Price::from_float(1000.0), Size(U256::from(10)), 20)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_bracket_entry_arms_exits_on_fill() {
+        let mut sm = CoreStateMachine::new();
+        let (maker, trader) = (Address::from([1u8; 20]), Address::from([2u8; 20]));
+        let asset = AssetId(1);
         
-        // Should work even without collateral deposit
-        let equity_result = sm.get_account_equity(&trader);
-        assert!(equity_result.is_err()); // No account created
+        let request = BracketOrderRequest {
+            trader,
+            asset,
+            side: Side::Bid,
+            price: Price::from_float(100.0),
+            size: Size(U256::from(5)),
+            stop_loss: Price::from_float(90.0),
+            take_profit: Price::from_float(110.0),
+            timestamp: 0,
+        };
+        let (entry, fills) = sm.place_bracket_order(&request).unwrap();
+        assert!(fills.is_empty());
+        assert_eq!(sm.advanced_orders().count_orders(), 0);
+        
+        // A seller hits the resting entry: exits are armed for the filled size
+        sm.place_market_order(maker, asset, Side::Ask, Size(U256::from(3)), 1).unwrap();
+        let legs = sm.advanced_orders().get_bracket(asset, entry).unwrap().legs.unwrap();
+        assert_eq!(sm.advanced_orders().get_order(legs.1).unwrap().size, Size(U256::from(3)));
+        
+        // Cancelling the rest keeps the armed exits
+        sm.cancel_order(asset, entry).unwrap();
+        assert!(sm.advanced_orders().get_bracket(asset, entry).is_none());
+        assert_eq!(sm.advanced_orders().count_orders(), 2);
+        
+        // Mid 111 fires the take-profit, which cancels the stop-loss
+        sm.place_limit_order(maker, asset, Side::Bid, Price::from_float(110.0), Size(U256::from(3)), 2).unwrap();
+        sm.place_limit_order(maker, asset, Side::Ask, Price::from_float(112.0), Size(U256::from(3)), 2).unwrap();
+        sm.on_block_begin(1, 10).unwrap();
+        let report = sm.on_block_end().unwrap();
+        assert_eq!(report.triggered_orders.len(), 1);
+        assert_eq!(report.triggered_orders[0].id, legs.1);
+        assert_eq!(sm.advanced_orders().count_orders(), 0);
+        
+        let invalid = BracketOrderRequest { stop_loss: Price::from_float(120.0), ..request };
+        assert!(sm.place_bracket_order(&invalid).is_err());
     }
 }
-
@@ -1,29 +1,310 @@
+use crate::depth_history::DepthSnapshot;
+use crate::emissions::EpochRewards;
+use crate::analytics::pools::PoolEpochStats;
+use crate::auth::SessionKey;
+use crate::funding::FundingRecord;
+use crate::liquidity_pool::PoolId;
+use crate::options::OptionsState;
+use crate::orders::{AdvancedOrder, OrderManagerState};
+use crate::pnl_history::AccountSnapshot;
 use crate::types::*;
+use crate::vault::VaultState;
+use alloy_primitives::{Address, U256};
 use anyhow::Result;
-use rocksdb::{IteratorMode, Options, DB};
+use kvstore::{Direction, MemoryBackend, RocksDbBackend, StorageBackend, WriteBatch, DEFAULT_CF};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
 
-/// Core storage layer using RocksDB
+/// Key of the last fully committed block height
+const COMMITTED_HEIGHT_KEY: &[u8] = b"meta:committed_height";
+
+/// Key of the advanced order manager state (next ID, brackets)
+const ORDER_MANAGER_STATE_KEY: &[u8] = b"meta:order_manager";
+
+/// Key of the options engine state (series, books, positions)
+const OPTIONS_STATE_KEY: &[u8] = b"meta:options";
+
+/// Key of the vault state (vaults, stats, shares)
+const VAULT_STATE_KEY: &[u8] = b"meta:vaults";
+
+/// Key of the oracle's whitelisted (asset, reporter) pairs
+const ORACLE_REPORTERS_KEY: &[u8] = b"meta:oracle_reporters";
+
+/// Key of the next fill sequence number
+const FILL_SEQ_KEY: &[u8] = b"meta:fill_seq";
+
+fn order_key(asset: AssetId, order_id: OrderId) -> String {
+    format!("order:{}:{}", asset.0, order_id)
+}
+
+/// Fills are keyed by a global sequence, since an order can fill several
+/// times at the same timestamp; it is zero-padded so fills sort in order
+fn fill_key(order_id: OrderId, seq: u64) -> String {
+    format!("fill:{}:{:020}", order_id, seq)
+}
+
+fn balance_key(user: &Address, asset: AssetId) -> String {
+    format!("balance:{:x}:{}", user, asset.0)
+}
+
+/// Epoch is zero-padded so a user's snapshots sort by epoch
+fn account_snapshot_key(user: &Address, epoch: u64) -> String {
+    format!("pnl_snapshot:{:x}:{:020}", user, epoch)
+}
+
+fn emission_epoch_key(epoch: u64) -> String {
+    format!("emission_epoch:{:020}", epoch)
+}
+
+fn emission_claim_key(epoch: u64, user: &Address) -> String {
+    format!("emission_claim:{:020}:{:x}", epoch, user)
+}
+
+/// Asset and timestamp are zero-padded so an asset's snapshots sort by time
+fn depth_snapshot_key(asset: AssetId, timestamp: u64) -> String {
+    format!("depth:{:010}:{:020}", asset.0, timestamp)
+}
+
+/// Asset and timestamp are zero-padded so an asset's rounds sort by time
+fn funding_record_key(asset: AssetId, timestamp: u64) -> String {
+    format!("funding:{:010}:{:020}", asset.0, timestamp)
+}
+
+/// Epoch is zero-padded so a pool's stats sort by epoch
+fn pool_epoch_key(pool_id: PoolId, epoch: u64) -> String {
+    format!("pool_epoch:{:020}:{:020}", pool_id, epoch)
+}
+
+fn transfer_nonce_key(account: &Address) -> String {
+    format!("transfer_nonce:{:x}", account)
+}
+
+fn api_nonce_key(account: &Address) -> String {
+    format!("api_nonce:{:x}", account)
+}
+
+fn session_keys_key(account: &Address) -> String {
+    format!("session_keys:{:x}", account)
+}
+
+fn advanced_order_key(id: OrderId) -> String {
+    format!("advanced_order:{:020}", id)
+}
+
+/// Metadata of an asset's checkpoint at `height`
+fn checkpoint_key(asset: AssetId, height: u64) -> String {
+    format!("snapshot:{}:{}", asset.0, height)
+}
+
+/// Order book snapshot written with a checkpoint
+fn book_snapshot_key(asset: AssetId, height: u64) -> String {
+    format!("book:{:010}:{:020}", asset.0, height)
+}
+
+/// Persisted balance record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BalanceRecord {
+    user: Address,
+    asset: AssetId,
+    amount: U256,
+}
+
+/// A set of storage writes applied atomically by `CoreStorage::write_batch`
+#[derive(Default)]
+pub struct StorageBatch {
+    batch: WriteBatch,
+}
+
+impl StorageBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Store (or overwrite) an order
+    pub fn put_order(&mut self, order: &Order) -> Result<()> {
+        self.batch.put(DEFAULT_CF, order_key(order.asset, order.id), serde_json::to_vec(order)?);
+        Ok(())
+    }
+    
+    /// Delete an order
+    pub fn delete_order(&mut self, asset: AssetId, order_id: OrderId) {
+        self.batch.delete(DEFAULT_CF, order_key(asset, order_id));
+    }
+    
+    /// Store a fill under sequence number `seq`
+    pub fn put_fill(&mut self, seq: u64, fill: &Fill) -> Result<()> {
+        self.batch.put(DEFAULT_CF, fill_key(fill.order_id, seq), serde_json::to_vec(fill)?);
+        Ok(())
+    }
+    
+    /// Store the next fill sequence number
+    pub fn set_next_fill_seq(&mut self, seq: u64) {
+        self.batch.put(DEFAULT_CF, FILL_SEQ_KEY, seq.to_be_bytes());
+    }
+    
+    /// Store a user balance
+    pub fn put_balance(&mut self, user: Address, asset: AssetId, amount: U256) -> Result<()> {
+        let record = BalanceRecord { user, asset, amount };
+        self.batch.put(DEFAULT_CF, balance_key(&user, asset), serde_json::to_vec(&record)?);
+        Ok(())
+    }
+    
+    /// Store an account position/PnL snapshot
+    pub fn put_account_snapshot(&mut self, snapshot: &AccountSnapshot) -> Result<()> {
+        self.batch.put(DEFAULT_CF, 
+            account_snapshot_key(&snapshot.user, snapshot.epoch),
+            serde_json::to_vec(snapshot)?,
+        );
+        Ok(())
+    }
+    
+    /// Store a finalized emissions epoch (rewards and Merkle root)
+    pub fn put_emission_epoch(&mut self, rewards: &EpochRewards) -> Result<()> {
+        self.batch.put(DEFAULT_CF, emission_epoch_key(rewards.epoch), serde_json::to_vec(rewards)?);
+        Ok(())
+    }
+    
+    /// Record that a user claimed an epoch's rewards
+    pub fn put_emission_claim(&mut self, epoch: u64, user: Address) -> Result<()> {
+        self.batch.put(DEFAULT_CF, emission_claim_key(epoch, &user), serde_json::to_vec(&(epoch, user))?);
+        Ok(())
+    }
+    
+    /// Store a columnar order book depth snapshot
+    pub fn put_depth_snapshot(&mut self, snapshot: &DepthSnapshot) {
+        self.batch.put(DEFAULT_CF, depth_snapshot_key(snapshot.asset, snapshot.timestamp), snapshot.encode());
+    }
+    
+    /// Store a funding round
+    pub fn put_funding_record(&mut self, record: &FundingRecord) -> Result<()> {
+        self.batch.put(DEFAULT_CF, 
+            funding_record_key(record.asset, record.timestamp),
+            serde_json::to_vec(record)?,
+        );
+        Ok(())
+    }
+    
+    /// Store a liquidity pool's performance for an epoch
+    pub fn put_pool_epoch_stats(&mut self, stats: &PoolEpochStats) -> Result<()> {
+        self.batch.put(DEFAULT_CF, pool_epoch_key(stats.pool_id, stats.epoch), serde_json::to_vec(stats)?);
+        Ok(())
+    }
+    
+    /// Store (or overwrite) an advanced (trigger) order
+    pub fn put_advanced_order(&mut self, order: &AdvancedOrder) -> Result<()> {
+        self.batch.put(DEFAULT_CF, advanced_order_key(order.id), serde_json::to_vec(order)?);
+        Ok(())
+    }
+    
+    /// Delete an advanced order
+    pub fn delete_advanced_order(&mut self, id: OrderId) {
+        self.batch.delete(DEFAULT_CF, advanced_order_key(id));
+    }
+    
+    /// Store the next expected position transfer nonce of `account`
+    pub fn put_transfer_nonce(&mut self, account: Address, nonce: u64) {
+        self.batch.put(DEFAULT_CF, transfer_nonce_key(&account), nonce.to_be_bytes());
+    }
+    
+    /// Store the next expected API nonce of `account`
+    pub fn put_api_nonce(&mut self, account: Address, nonce: u64) {
+        self.batch.put(DEFAULT_CF, api_nonce_key(&account), nonce.to_be_bytes());
+    }
+    
+    /// Store the session keys of `account`, deleting the entry when it has none
+    pub fn put_session_keys(&mut self, account: Address, keys: &[SessionKey]) -> Result<()> {
+        if keys.is_empty() {
+            self.batch.delete(DEFAULT_CF, session_keys_key(&account));
+        } else {
+            self.batch.put(DEFAULT_CF, session_keys_key(&account), serde_json::to_vec(keys)?);
+        }
+        Ok(())
+    }
+    
+    /// Store the advanced order manager state
+    pub fn put_order_manager_state(&mut self, state: &OrderManagerState) -> Result<()> {
+        self.batch.put(DEFAULT_CF, ORDER_MANAGER_STATE_KEY, serde_json::to_vec(state)?);
+        Ok(())
+    }
+    
+    /// Store the options engine state
+    pub fn put_options_state(&mut self, state: &OptionsState) -> Result<()> {
+        self.batch.put(DEFAULT_CF, OPTIONS_STATE_KEY, serde_json::to_vec(state)?);
+        Ok(())
+    }
+    
+    /// Store the vault state
+    pub fn put_vault_state(&mut self, state: &VaultState) -> Result<()> {
+        self.batch.put(DEFAULT_CF, VAULT_STATE_KEY, serde_json::to_vec(state)?);
+        Ok(())
+    }
+    
+    /// Store the oracle's whitelisted reporters
+    pub fn put_oracle_reporters(&mut self, reporters: &[(AssetId, Address)]) -> Result<()> {
+        self.batch.put(DEFAULT_CF, ORACLE_REPORTERS_KEY, serde_json::to_vec(reporters)?);
+        Ok(())
+    }
+    
+    /// Store checkpoint metadata
+    pub fn put_checkpoint(&mut self, metadata: &CheckpointMetadata) -> Result<()> {
+        self.batch.put(DEFAULT_CF, checkpoint_key(metadata.asset, metadata.height), serde_json::to_vec(metadata)?);
+        Ok(())
+    }
+    
+    /// Store the encoded order book snapshot of a checkpoint
+    pub fn put_book_snapshot(&mut self, asset: AssetId, height: u64, snapshot: &[u8]) {
+        self.batch.put(DEFAULT_CF, book_snapshot_key(asset, height), snapshot);
+    }
+    
+    /// Delete the order book snapshot of a checkpoint
+    pub fn delete_book_snapshot(&mut self, asset: AssetId, height: u64) {
+        self.batch.delete(DEFAULT_CF, book_snapshot_key(asset, height));
+    }
+    
+    /// Mark `height` as fully committed
+    pub fn set_committed_height(&mut self, height: u64) {
+        self.batch.put(DEFAULT_CF, COMMITTED_HEIGHT_KEY, height.to_be_bytes());
+    }
+    
+    /// Number of writes in the batch
+    pub fn len(&self) -> usize {
+        self.batch.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+}
+
+/// Core storage layer over a pluggable key-value backend (RocksDB by
+/// default); everything lives in the default column family
 pub struct CoreStorage {
-    db: DB,
+    backend: Arc<dyn StorageBackend>,
 }
 
 impl CoreStorage {
-    /// Create a new storage instance
+    /// Create a new storage instance backed by RocksDB at `path`
     pub fn new(path: &str) -> Result<Self> {
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
-        opts.create_missing_column_families(true);
-        
-        let db = DB::open(&opts, path)?;
-        Ok(Self { db })
+        let backend = RocksDbBackend::open(Path::new(path), &[DEFAULT_CF])?;
+        Ok(Self::with_backend(Arc::new(backend)))
+    }
+    
+    /// Create a storage instance that keeps everything in memory
+    pub fn in_memory() -> Self {
+        Self::with_backend(Arc::new(MemoryBackend::new()))
+    }
+    
+    /// Create a storage instance over any key-value backend
+    pub fn with_backend(backend: Arc<dyn StorageBackend>) -> Self {
+        Self { backend }
     }
     
     /// Store an order
     pub fn store_order(&self, order: &Order) -> Result<()> {
-        let key = format!("order:{}:{}", order.asset.0, order.id);
+        let key = order_key(order.asset, order.id);
         let value = serde_json::to_vec(order)?;
-        self.db.put(key.as_bytes(), value)?;
+        self.backend.put(DEFAULT_CF, key.as_bytes(), &value)?;
         Ok(())
     }
     
@@ -31,43 +312,27 @@ impl CoreStorage {
     pub fn load_orders(&self, asset: AssetId) -> Result<Vec<Order>> {
         let prefix = format!("order:{}:", asset.0);
         let mut orders = Vec::new();
-        
-        let iter = self.db.iterator(IteratorMode::Start);
-        for item in iter {
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix.as_bytes()), Direction::Forward)? {
             let (key, value) = item?;
-            let key_str = String::from_utf8_lossy(&key);
-            
-            if key_str.starts_with(&prefix) {
-                let order: Order = serde_json::from_slice(&value)?;
-                orders.push(order);
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
             }
+            orders.push(serde_json::from_slice(&value)?);
         }
         
         Ok(orders)
     }
     
-    /// Store a fill
-    pub fn store_fill(&self, fill: &Fill) -> Result<()> {
-        let key = format!("fill:{}:{}", fill.order_id, fill.timestamp);
-        let value = serde_json::to_vec(fill)?;
-        self.db.put(key.as_bytes(), value)?;
-        Ok(())
-    }
-    
     /// Load all fills for an order
     pub fn load_fills(&self, order_id: OrderId) -> Result<Vec<Fill>> {
         let prefix = format!("fill:{}:", order_id);
         let mut fills = Vec::new();
-        
-        let iter = self.db.iterator(IteratorMode::Start);
-        for item in iter {
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix.as_bytes()), Direction::Forward)? {
             let (key, value) = item?;
-            let key_str = String::from_utf8_lossy(&key);
-            
-            if key_str.starts_with(&prefix) {
-                let fill: Fill = serde_json::from_slice(&value)?;
-                fills.push(fill);
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
             }
+            fills.push(serde_json::from_slice(&value)?);
         }
         
         Ok(fills)
@@ -75,17 +340,14 @@ impl CoreStorage {
     
     /// Delete an order (when canceled or filled)
     pub fn delete_order(&self, asset: AssetId, order_id: OrderId) -> Result<()> {
-        let key = format!("order:{}:{}", asset.0, order_id);
-        self.db.delete(key.as_bytes())?;
+        let key = order_key(asset, order_id);
+        self.backend.delete(DEFAULT_CF, key.as_bytes())?;
         Ok(())
     }
     
-    /// Store checkpoint metadata
-    pub fn store_checkpoint(&self, asset: AssetId, height: u64, metadata: &CheckpointMetadata) -> Result<()> {
-        let key = format!("snapshot:{}:{}", asset.0, height);
-        let value = serde_json::to_vec(metadata)?;
-        self.db.put(key.as_bytes(), value)?;
-        Ok(())
+    /// Load the encoded order book snapshot of a checkpoint
+    pub fn load_book_snapshot(&self, asset: AssetId, height: u64) -> Result<Option<Vec<u8>>> {
+        Ok(self.backend.get(DEFAULT_CF, book_snapshot_key(asset, height).as_bytes())?)
     }
     
     /// Load latest checkpoint for an asset
@@ -94,26 +356,266 @@ impl CoreStorage {
         let mut latest: Option<CheckpointMetadata> = None;
         let mut latest_height = 0u64;
         
-        let iter = self.db.iterator(IteratorMode::Start);
-        for item in iter {
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix.as_bytes()), Direction::Forward)? {
             let (key, value) = item?;
-            let key_str = String::from_utf8_lossy(&key);
-            
-            if key_str.starts_with(&prefix) {
-                let metadata: CheckpointMetadata = serde_json::from_slice(&value)?;
-                if metadata.height > latest_height {
-                    latest_height = metadata.height;
-                    latest = Some(metadata);
-                }
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            let metadata: CheckpointMetadata = serde_json::from_slice(&value)?;
+            if metadata.height > latest_height {
+                latest_height = metadata.height;
+                latest = Some(metadata);
             }
         }
         
         Ok(latest)
     }
     
-    /// Get reference to the underlying DB (for advanced operations)
-    pub fn db(&self) -> &DB {
-        &self.db
+    /// Apply all writes in `batch` atomically
+    pub fn write_batch(&self, batch: StorageBatch) -> Result<()> {
+        self.backend.write(batch.batch, false)?;
+        Ok(())
+    }
+    
+    /// Load all persisted balances
+    pub fn load_balances(&self) -> Result<Vec<(Address, AssetId, U256)>> {
+        let prefix = b"balance:";
+        let mut balances = Vec::new();
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            let record: BalanceRecord = serde_json::from_slice(&value)?;
+            balances.push((record.user, record.asset, record.amount));
+        }
+        
+        Ok(balances)
+    }
+    
+    /// Load all account snapshots for a user, oldest epoch first
+    pub fn load_account_snapshots(&self, user: &Address) -> Result<Vec<AccountSnapshot>> {
+        let prefix = format!("pnl_snapshot:{:x}:", user);
+        let mut snapshots = Vec::new();
+        
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix.as_bytes()), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            snapshots.push(serde_json::from_slice(&value)?);
+        }
+        
+        snapshots.sort_by_key(|s| s.epoch);
+        Ok(snapshots)
+    }
+    
+    /// Load all finalized emissions epochs, oldest first
+    pub fn load_emission_epochs(&self) -> Result<Vec<EpochRewards>> {
+        let prefix = b"emission_epoch:";
+        let mut epochs = Vec::new();
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            epochs.push(serde_json::from_slice::<EpochRewards>(&value)?);
+        }
+        
+        epochs.sort_by_key(|e| e.epoch);
+        Ok(epochs)
+    }
+    
+    /// Load all emissions claims as (epoch, user)
+    pub fn load_emission_claims(&self) -> Result<Vec<(u64, Address)>> {
+        let prefix = b"emission_claim:";
+        let mut claims = Vec::new();
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            claims.push(serde_json::from_slice(&value)?);
+        }
+        
+        Ok(claims)
+    }
+    
+    /// Load an asset's depth snapshots taken in `[from, to]`, oldest first
+    pub fn load_depth_snapshots(&self, asset: AssetId, from: u64, to: u64) -> Result<Vec<DepthSnapshot>> {
+        let start = depth_snapshot_key(asset, from);
+        let end = depth_snapshot_key(asset, to);
+        let mut snapshots = Vec::new();
+        
+        for item in self.backend.iter(DEFAULT_CF, Some(start.as_bytes()), Direction::Forward)? {
+            let (key, value) = item?;
+            if key.as_slice() > end.as_bytes() {
+                break;
+            }
+            snapshots.push(DepthSnapshot::decode(&value)?);
+        }
+        
+        Ok(snapshots)
+    }
+    
+    /// Load an asset's funding rounds in `[from, to]`, oldest first
+    pub fn load_funding_records(&self, asset: AssetId, from: u64, to: u64) -> Result<Vec<FundingRecord>> {
+        let start = funding_record_key(asset, from);
+        let end = funding_record_key(asset, to);
+        let mut records = Vec::new();
+        
+        for item in self.backend.iter(DEFAULT_CF, Some(start.as_bytes()), Direction::Forward)? {
+            let (key, value) = item?;
+            if key.as_slice() > end.as_bytes() {
+                break;
+            }
+            records.push(serde_json::from_slice(&value)?);
+        }
+        
+        Ok(records)
+    }
+    
+    /// Load a liquidity pool's performance per epoch, oldest first
+    pub fn load_pool_epoch_stats(&self, pool_id: PoolId) -> Result<Vec<PoolEpochStats>> {
+        let prefix = format!("pool_epoch:{:020}:", pool_id);
+        let mut stats = Vec::new();
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix.as_bytes()), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            stats.push(serde_json::from_slice(&value)?);
+        }
+        
+        Ok(stats)
+    }
+    
+    /// Load all advanced orders, by ID
+    pub fn load_advanced_orders(&self) -> Result<Vec<AdvancedOrder>> {
+        let prefix = b"advanced_order:";
+        let mut orders = Vec::new();
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            orders.push(serde_json::from_slice(&value)?);
+        }
+        
+        Ok(orders)
+    }
+    
+    /// Load the next expected position transfer nonce of every account
+    pub fn load_transfer_nonces(&self) -> Result<Vec<(Address, u64)>> {
+        self.load_account_nonces(b"transfer_nonce:")
+    }
+    
+    /// Load the next expected API nonce of every account
+    pub fn load_api_nonces(&self) -> Result<Vec<(Address, u64)>> {
+        self.load_account_nonces(b"api_nonce:")
+    }
+    
+    /// Load the session keys of every account
+    pub fn load_session_keys(&self) -> Result<Vec<(Address, Vec<SessionKey>)>> {
+        let prefix = b"session_keys:";
+        let mut sessions = Vec::new();
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            let account: Address = std::str::from_utf8(&key[prefix.len()..])?.parse()?;
+            sessions.push((account, serde_json::from_slice(&value)?));
+        }
+        
+        Ok(sessions)
+    }
+    
+    /// Load per-account nonces stored under `prefix` followed by the account
+    fn load_account_nonces(&self, prefix: &[u8]) -> Result<Vec<(Address, u64)>> {
+        let mut nonces = Vec::new();
+        for item in self.backend.iter(DEFAULT_CF, Some(prefix), Direction::Forward)? {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            let account: Address = std::str::from_utf8(&key[prefix.len()..])?.parse()?;
+            let bytes: [u8; 8] = value
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Corrupt nonce"))?;
+            nonces.push((account, u64::from_be_bytes(bytes)));
+        }
+        
+        Ok(nonces)
+    }
+    
+    /// Load the advanced order manager state
+    pub fn load_order_manager_state(&self) -> Result<Option<OrderManagerState>> {
+        self.backend
+            .get(DEFAULT_CF, ORDER_MANAGER_STATE_KEY)?
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()
+            .map_err(Into::into)
+    }
+    
+    /// Load the options engine state
+    pub fn load_options_state(&self) -> Result<Option<OptionsState>> {
+        self.backend
+            .get(DEFAULT_CF, OPTIONS_STATE_KEY)?
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()
+            .map_err(Into::into)
+    }
+    
+    /// Load the vault state, if any was stored
+    pub fn load_vault_state(&self) -> Result<Option<VaultState>> {
+        self.backend
+            .get(DEFAULT_CF, VAULT_STATE_KEY)?
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()
+            .map_err(Into::into)
+    }
+    
+    /// Load the oracle's whitelisted reporters (none if never stored)
+    pub fn load_oracle_reporters(&self) -> Result<Vec<(AssetId, Address)>> {
+        match self.backend.get(DEFAULT_CF, ORACLE_REPORTERS_KEY)? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// Load the next fill sequence number (0 if no fill was stored)
+    pub fn load_next_fill_seq(&self) -> Result<u64> {
+        match self.backend.get(DEFAULT_CF, FILL_SEQ_KEY)? {
+            Some(bytes) => {
+                let bytes: [u8; 8] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Corrupt fill sequence"))?;
+                Ok(u64::from_be_bytes(bytes))
+            }
+            None => Ok(0),
+        }
+    }
+    
+    /// Load the last fully committed block height
+    pub fn load_committed_height(&self) -> Result<Option<u64>> {
+        match self.backend.get(DEFAULT_CF, COMMITTED_HEIGHT_KEY)? {
+            Some(bytes) => {
+                let bytes: [u8; 8] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Corrupt committed height"))?;
+                Ok(Some(u64::from_be_bytes(bytes)))
+            }
+            None => Ok(None),
+        }
+    }
+    
+    /// Get reference to the underlying backend (for advanced operations)
+    pub fn backend(&self) -> &dyn StorageBackend {
+        self.backend.as_ref()
     }
 }
 
@@ -124,6 +626,9 @@ pub struct CheckpointMetadata {
     pub height: u64,
     pub timestamp: u64,
     pub order_count: usize,
+    /// Encoded size of the order book snapshot (0 if none was written)
+    #[serde(default)]
+    pub snapshot_bytes: usize,
 }
 
 #[cfg(test)]
@@ -245,13 +750,24 @@ mod tests {
             timestamp: 1000,
         };
         
-        storage.store_fill(&fill).unwrap();
+        let mut batch = StorageBatch::new();
+        batch.put_fill(0, &fill).unwrap();
+        batch.set_next_fill_seq(1);
+        storage.write_batch(batch).unwrap();
         let loaded = storage.load_fills(1).unwrap();
         
         assert_eq!(loaded.len(), 1);
         assert_eq!(loaded[0].order_id, fill.order_id);
         assert_eq!(loaded[0].size.0, fill.size.0);
         
+        // A second fill of the order at the same timestamp is kept too
+        let mut batch = StorageBatch::new();
+        batch.put_fill(1, &fill).unwrap();
+        batch.set_next_fill_seq(2);
+        storage.write_batch(batch).unwrap();
+        assert_eq!(storage.load_fills(1).unwrap().len(), 2);
+        assert_eq!(storage.load_next_fill_seq().unwrap(), 2);
+        
         // Cleanup
         let _ = std::fs::remove_dir_all(path);
     }
@@ -266,9 +782,12 @@ mod tests {
             height: 100,
             timestamp: 1000,
             order_count: 5,
+            snapshot_bytes: 0,
         };
         
-        storage.store_checkpoint(AssetId(1), 100, &metadata).unwrap();
+        let mut batch = StorageBatch::new();
+        batch.put_checkpoint(&metadata).unwrap();
+        storage.write_batch(batch).unwrap();
         let loaded = storage.load_latest_checkpoint(AssetId(1)).unwrap();
         
         assert!(loaded.is_some());
@@ -292,8 +811,11 @@ mod tests {
                 height,
                 timestamp: height * 10,
                 order_count: 5,
+                snapshot_bytes: 0,
             };
-            storage.store_checkpoint(AssetId(1), height, &metadata).unwrap();
+            let mut batch = StorageBatch::new();
+            batch.put_checkpoint(&metadata).unwrap();
+            storage.write_batch(batch).unwrap();
         }
         
         let latest = storage.load_latest_checkpoint(AssetId(1)).unwrap().unwrap();
@@ -342,5 +864,57 @@ mod tests {
         // Cleanup
         let _ = std::fs::remove_dir_all(path);
     }
-}
 
+    #[test]
+    fn test_write_batch_is_atomic_unit() {
+        let path = temp_db_path();
+        let storage = CoreStorage::new(&path).unwrap();
+        let user = Address::from([1u8; 20]);
+        
+        let order = Order::new(1, AssetId(1), user, Side::Bid, Price::from_float(1.0), Size(U256::from(100)), 0);
+        storage.store_order(&order).unwrap();
+        
+        let fill = Fill {
+            order_id: 1,
+            price: Price::from_float(1.0),
+            size: Size(U256::from(100)),
+            maker: user,
+            taker: Address::from([2u8; 20]),
+            timestamp: 5,
+        };
+        
+        let mut batch = StorageBatch::new();
+        batch.delete_order(AssetId(1), 1);
+        batch.put_fill(0, &fill).unwrap();
+        batch.put_balance(user, AssetId(1), U256::from(42)).unwrap();
+        batch.set_committed_height(7);
+        assert_eq!(batch.len(), 4);
+        
+        // Nothing visible until the batch is written
+        assert_eq!(storage.load_orders(AssetId(1)).unwrap().len(), 1);
+        assert_eq!(storage.load_committed_height().unwrap(), None);
+        
+        storage.write_batch(batch).unwrap();
+        
+        assert!(storage.load_orders(AssetId(1)).unwrap().is_empty());
+        assert_eq!(storage.load_fills(1).unwrap().len(), 1);
+        assert_eq!(storage.load_balances().unwrap(), vec![(user, AssetId(1), U256::from(42))]);
+        assert_eq!(storage.load_committed_height().unwrap(), Some(7));
+        
+        // Cleanup
+        let _ = std::fs::remove_dir_all(path);
+    }
+    
+    #[test]
+    fn test_in_memory_storage() {
+        let storage = CoreStorage::in_memory();
+        let order = Order::new(1, AssetId(1), Address::from([1u8; 20]), Side::Bid, Price::from_float(1.0), Size(U256::from(100)), 0);
+        storage.store_order(&order).unwrap();
+        storage.store_order(&Order { id: 2, asset: AssetId(2), ..order.clone() }).unwrap();
+        
+        assert_eq!(storage.load_orders(AssetId(1)).unwrap().len(), 1);
+        storage.delete_order(AssetId(1), 1).unwrap();
+        assert!(storage.load_orders(AssetId(1)).unwrap().is_empty());
+        assert_eq!(storage.load_orders(AssetId(2)).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,439 @@
+use crate::adl::calculate_adl_priority;
+use crate::margin::{MarginEngine, MarginMode};
+use crate::matching::MatchingEngine;
+use crate::orderbook::OrderBook;
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// Hypothetical price shock to stress the book of accounts with
+#[derive(Debug, Clone)]
+pub struct StressScenario {
+    /// Price move per asset in basis points (-2000 = -20%)
+    pub shocks: BTreeMap<u32, i64>,
+    /// Liquidation rounds to follow the cascade for
+    pub max_rounds: usize,
+}
+
+impl StressScenario {
+    pub fn new() -> Self {
+        Self {
+            shocks: BTreeMap::new(),
+            max_rounds: 10,
+        }
+    }
+
+    /// Add a price shock for an asset
+    pub fn with_shock(mut self, asset: AssetId, shock_bps: i64) -> Self {
+        self.shocks.insert(asset.0, shock_bps);
+        self
+    }
+}
+
+impl Default for StressScenario {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position that would be liquidated under the scenario
+#[derive(Debug, Clone, PartialEq)]
+pub struct StressLiquidation {
+    /// Cascade round (0 = direct effect of the shock)
+    pub round: usize,
+    pub user: Address,
+    pub asset: AssetId,
+    pub size: i64,
+    /// Mark price that breached maintenance
+    pub mark_price: Price,
+    /// Average price closing against the book (None if the book was empty)
+    pub execution_price: Option<Price>,
+    /// Size the book could not absorb (closed at the mark)
+    pub unfilled: u64,
+}
+
+/// How liquidations moved a market
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketImpact {
+    pub asset: AssetId,
+    /// Mark price right after the shock
+    pub shocked_price: Price,
+    /// Price the liquidation flow pushed the book to
+    pub final_price: Price,
+    /// Resting size consumed by liquidations
+    pub volume: U256,
+}
+
+/// Profitable position that would be auto-deleveraged
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdlImpact {
+    pub user: Address,
+    pub asset: AssetId,
+    /// Size closed against bankrupt positions
+    pub size: i64,
+    pub priority: u64,
+}
+
+/// Outcome of a stress test
+#[derive(Debug, Clone, Default)]
+pub struct StressReport {
+    pub liquidations: Vec<StressLiquidation>,
+    /// Per-asset impact, by asset ID
+    pub market_impact: Vec<MarketImpact>,
+    /// Losses beyond the collateral of liquidated accounts
+    pub bad_debt: U256,
+    /// Bad debt the insurance fund would absorb
+    pub insurance_drawdown: U256,
+    /// Insurance fund left afterwards
+    pub insurance_remaining: U256,
+    /// Bad debt left for ADL
+    pub adl_shortfall: U256,
+    /// Positions deleveraged to cover the shortfall, in priority order
+    pub adl: Vec<AdlImpact>,
+    /// Rounds the cascade ran for
+    pub rounds: usize,
+}
+
+/// Collateral and positions margined together: a cross account, or one
+/// isolated position
+struct MarginUnit {
+    user: Address,
+    collateral: f64,
+    /// (asset, size, entry price)
+    positions: Vec<(AssetId, i64, Price)>,
+}
+
+impl MarginUnit {
+    fn equity(&self, prices: &HashMap<AssetId, Price>) -> f64 {
+        self.collateral
+            + self.positions.iter()
+                .map(|(asset, size, entry)| {
+                    let mark = prices.get(asset).copied().unwrap_or(*entry);
+                    *size as f64 * (mark.to_float() - entry.to_float())
+                })
+                .sum::<f64>()
+    }
+
+    fn maintenance(&self, prices: &HashMap<AssetId, Price>, ratio: f64) -> f64 {
+        self.positions.iter()
+            .map(|(asset, size, entry)| {
+                let mark = prices.get(asset).copied().unwrap_or(*entry);
+                size.unsigned_abs() as f64 * mark.to_float() * ratio
+            })
+            .sum()
+    }
+}
+
+/// Read-only liquidation cascade simulator.
+///
+/// Margin follows the same float model as `MarginEngine::preview_position`:
+/// equity is collateral plus `size * (mark - entry)`, and an account is
+/// liquidated once equity falls below `maintenance_margin_ratio` of its
+/// notional at the mark. Liquidations close against clones of the books, and
+/// the price they push a book to is the next round's mark.
+pub struct StressTester;
+
+impl StressTester {
+    /// Run `scenario` from the current `marks`
+    pub fn run(
+        margin_engine: &MarginEngine,
+        books: &HashMap<AssetId, OrderBook>,
+        marks: &HashMap<AssetId, Price>,
+        insurance_balance: U256,
+        scenario: &StressScenario,
+    ) -> Result<StressReport> {
+        let ratio = margin_engine.config().maintenance_margin_ratio;
+
+        let mut prices = marks.clone();
+        for (&asset, &shock) in &scenario.shocks {
+            let asset = AssetId(asset);
+            let mark = marks
+                .get(&asset)
+                .ok_or_else(|| anyhow!("No mark price for asset {}", asset.0))?;
+            let shocked = (mark.0 as i128 * (10000 + shock as i128) / 10000).max(0);
+            prices.insert(asset, Price(shocked as u64));
+        }
+        let shocked_prices = prices.clone();
+
+        let mut units = margin_units(margin_engine);
+        let mut books: HashMap<AssetId, OrderBook> = books.clone();
+        let mut volumes: BTreeMap<u32, U256> = BTreeMap::new();
+        let mut report = StressReport::default();
+        // Uncovered losses and the asset they are charged to for ADL
+        let mut deficits: BTreeMap<u32, f64> = BTreeMap::new();
+
+        for round in 0..scenario.max_rounds {
+            let (breached, healthy): (Vec<_>, Vec<_>) = units
+                .into_iter()
+                .partition(|u| u.equity(&prices) < u.maintenance(&prices, ratio));
+            units = healthy;
+            if breached.is_empty() {
+                break;
+            }
+            report.rounds = round + 1;
+
+            let mut round_prices = HashMap::new();
+            for unit in breached {
+                let mut equity = unit.collateral;
+                let mut worst: Option<(f64, AssetId)> = None;
+
+                for &(asset, size, entry) in &unit.positions {
+                    let mark = prices.get(&asset).copied().unwrap_or(entry);
+                    let (execution_price, filled, last_price) = close_against_book(books.get_mut(&asset), size);
+                    if let Some(price) = last_price {
+                        round_prices.insert(asset, price);
+                    }
+                    let volume = volumes.entry(asset.0).or_insert(U256::ZERO);
+                    *volume = volume.saturating_add(U256::from(filled));
+
+                    // Unabsorbed size is closed at the mark
+                    let unfilled = size.unsigned_abs() - filled;
+                    let exit = match execution_price {
+                        Some(price) => (price.to_float() * filled as f64 + mark.to_float() * unfilled as f64)
+                            / size.unsigned_abs() as f64,
+                        None => mark.to_float(),
+                    };
+                    let pnl = size as f64 * (exit - entry.to_float());
+                    equity += pnl;
+                    if worst.is_none_or(|(loss, _)| pnl < loss) {
+                        worst = Some((pnl, asset));
+                    }
+
+                    report.liquidations.push(StressLiquidation {
+                        round,
+                        user: unit.user,
+                        asset,
+                        size,
+                        mark_price: mark,
+                        execution_price,
+                        unfilled,
+                    });
+                }
+
+                if let (true, Some((_, asset))) = (equity < 0.0, worst) {
+                    *deficits.entry(asset.0).or_insert(0.0) += -equity;
+                }
+            }
+
+            // Liquidation flow moves the marks seen by the next round
+            prices.extend(round_prices);
+        }
+
+        let bad_debt: f64 = deficits.values().sum();
+        report.bad_debt = to_u256(bad_debt);
+        report.insurance_drawdown = report.bad_debt.min(insurance_balance);
+        report.insurance_remaining = insurance_balance - report.insurance_drawdown;
+        report.adl_shortfall = report.bad_debt - report.insurance_drawdown;
+
+        // Insurance absorbs losses in asset order; ADL covers the rest
+        let mut insurance = insurance_balance.saturating_to::<u128>() as f64;
+        for (&asset, &deficit) in &deficits {
+            let covered = deficit.min(insurance);
+            insurance -= covered;
+            let shortfall = deficit - covered;
+            if shortfall > 0.0 {
+                report.adl.extend(deleverage(&units, AssetId(asset), shortfall, &prices));
+            }
+        }
+
+        let mut assets: Vec<u32> = shocked_prices.keys().map(|a| a.0).collect();
+        assets.extend(volumes.keys());
+        assets.sort_unstable();
+        assets.dedup();
+        report.market_impact = assets
+            .into_iter()
+            .filter_map(|asset| {
+                let asset = AssetId(asset);
+                Some(MarketImpact {
+                    asset,
+                    shocked_price: *shocked_prices.get(&asset)?,
+                    final_price: *prices.get(&asset)?,
+                    volume: volumes.get(&asset.0).copied().unwrap_or(U256::ZERO),
+                })
+            })
+            .collect();
+
+        Ok(report)
+    }
+}
+
+/// Cross accounts and isolated positions with open positions, by user
+fn margin_units(margin_engine: &MarginEngine) -> Vec<MarginUnit> {
+    let mut units = Vec::new();
+    for user in margin_engine.get_accounts() {
+        let mut positions: Vec<_> = margin_engine
+            .get_user_positions(&user)
+            .into_iter()
+            .filter(|p| p.size != 0)
+            .map(|p| (p.asset, p.size, p.entry_price))
+            .collect();
+        positions.sort_by_key(|(asset, _, _)| asset.0);
+        if positions.is_empty() {
+            continue;
+        }
+
+        match margin_engine.get_margin_mode(&user) {
+            MarginMode::Cross => units.push(MarginUnit {
+                user,
+                collateral: margin_engine
+                    .get_account_equity(&user)
+                    .map(|e| e.saturating_to::<u128>() as f64)
+                    .unwrap_or(0.0),
+                positions,
+            }),
+            MarginMode::Isolated => {
+                for position in positions {
+                    units.push(MarginUnit {
+                        user,
+                        collateral: margin_engine.get_isolated_collateral(&user, position.0).saturating_to::<u128>() as f64,
+                        positions: vec![position],
+                    });
+                }
+            }
+        }
+    }
+    units
+}
+
+/// Close `size` with a market order on a cloned book.
+/// Returns (average price, filled size, last fill price).
+fn close_against_book(book: Option<&mut OrderBook>, size: i64) -> (Option<Price>, u64, Option<Price>) {
+    let book = match book {
+        Some(book) => book,
+        None => return (None, 0, None),
+    };
+    let side = if size > 0 { Side::Ask } else { Side::Bid };
+    let fills = MatchingEngine::execute_market_order(
+        book,
+        Address::ZERO,
+        side,
+        Size(U256::from(size.unsigned_abs())),
+        0,
+    )
+    .unwrap_or_default();
+
+    let filled: u64 = fills.iter().map(|f| f.size.0.saturating_to::<u64>()).sum();
+    if filled == 0 {
+        return (None, 0, None);
+    }
+    let notional: f64 = fills
+        .iter()
+        .map(|f| f.price.to_float() * f.size.0.saturating_to::<u64>() as f64)
+        .sum();
+    (
+        Some(Price::from_float(notional / filled as f64)),
+        filled,
+        fills.last().map(|f| f.price),
+    )
+}
+
+/// Profitable surviving positions in `asset` closed, highest ADL priority
+/// first, until their PnL covers `shortfall`
+fn deleverage(
+    units: &[MarginUnit],
+    asset: AssetId,
+    shortfall: f64,
+    prices: &HashMap<AssetId, Price>,
+) -> Vec<AdlImpact> {
+    let mark = match prices.get(&asset) {
+        Some(mark) => mark.to_float(),
+        None => return Vec::new(),
+    };
+
+    let mut candidates: Vec<(u64, Address, i64, f64)> = units
+        .iter()
+        .flat_map(|unit| {
+            let equity = unit.equity(prices).max(1.0);
+            unit.positions.iter().filter(move |(a, _, _)| *a == asset).filter_map(move |&(_, size, entry)| {
+                let pnl = size as f64 * (mark - entry.to_float());
+                if pnl <= 0.0 {
+                    return None;
+                }
+                let leverage = (size.unsigned_abs() as f64 * mark / equity).max(1.0) as u32;
+                Some((calculate_adl_priority(pnl as i64, leverage), unit.user, size, pnl))
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut remaining = shortfall;
+    let mut impacts = Vec::new();
+    for (priority, user, size, pnl) in candidates {
+        if remaining <= 0.0 {
+            break;
+        }
+        let fraction = (remaining / pnl).min(1.0);
+        let closed = ((size.unsigned_abs() as f64 * fraction).ceil() as i64).min(size.abs()) * size.signum();
+        remaining -= pnl * fraction;
+        impacts.push(AdlImpact { user, asset, size: closed, priority });
+    }
+    impacts
+}
+
+fn to_u256(value: f64) -> U256 {
+    U256::from(value.max(0.0).round() as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::margin::MarginConfig;
+
+    fn addr(n: u8) -> Address {
+        Address::from([n; 20])
+    }
+
+    #[test]
+    fn test_cascade_insurance_and_adl() {
+        let asset = AssetId(1);
+        let mut margin = MarginEngine::new(MarginConfig::default());
+        // Thin long at 10x, healthier long, and a profitable short
+        for (user, collateral) in [(addr(1), 100u64), (addr(2), 150), (addr(3), 1_000)] {
+            margin.deposit(user, AssetId(0), U256::from(collateral)).unwrap();
+        }
+        margin.update_position(addr(1), asset, 10, Price::from_float(100.0), 0).unwrap();
+        margin.update_position(addr(2), asset, 10, Price::from_float(100.0), 0).unwrap();
+        margin.update_position(addr(3), asset, -10, Price::from_float(110.0), 0).unwrap();
+
+        let mut book = OrderBook::new(asset);
+        for (price, size) in [(93.0, 5), (85.0, 10)] {
+            book.add_limit_order(addr(9), Side::Bid, Price::from_float(price), Size(U256::from(size)), 0);
+        }
+        let books = HashMap::from([(asset, book)]);
+        let marks = HashMap::from([(asset, Price::from_float(100.0))]);
+        let before = books[&asset].snapshot(10);
+
+        // -6%: only the 10x long breaches, its sale drives the mark to 85
+        // and takes the second long down with it
+        let scenario = StressScenario::new().with_shock(asset, -600);
+        let report = StressTester::run(&margin, &books, &marks, U256::from(100), &scenario).unwrap();
+
+        assert_eq!(report.rounds, 2);
+        let liquidated: Vec<_> = report.liquidations.iter().map(|l| (l.round, l.user)).collect();
+        assert_eq!(liquidated, vec![(0, addr(1)), (1, addr(2))]);
+        assert_eq!(report.liquidations[0].execution_price, Some(Price::from_float(89.0)));
+        assert_eq!(report.liquidations[1].unfilled, 5);
+
+        let impact = &report.market_impact[0];
+        assert_eq!(impact.shocked_price, Price::from_float(94.0));
+        assert_eq!(impact.final_price, Price::from_float(85.0));
+        assert_eq!(impact.volume, U256::from(15));
+
+        // Losses: 100 - 110 = -10, then 150 - 150 (5 @ 85, 5 at the 85 mark) = 0
+        assert_eq!(report.bad_debt, U256::from(10));
+        assert_eq!(report.insurance_drawdown, U256::from(10));
+        assert_eq!(report.insurance_remaining, U256::from(90));
+        assert!(report.adl.is_empty());
+
+        // Without insurance the short is deleveraged
+        let report = StressTester::run(&margin, &books, &marks, U256::ZERO, &scenario).unwrap();
+        assert_eq!(report.adl_shortfall, U256::from(10));
+        assert_eq!(report.adl.len(), 1);
+        assert_eq!((report.adl[0].user, report.adl[0].size), (addr(3), -1));
+
+        // Nothing was mutated
+        assert_eq!(books[&asset].snapshot(10).bids, before.bids);
+        assert_eq!(margin.get_position(&addr(1), asset).unwrap().size, 10);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::types::*;
+use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Decimals of every amount inside the engine (sizes, balances, collateral)
+///
+/// Token amounts are scaled to this precision on the way in and back to
+/// the token's own decimals on the way out, so 6- and 18-decimal assets
+/// can be netted, margined and priced against each other.
+pub const CANONICAL_DECIMALS: u8 = 18;
+
+/// Largest supported token precision
+pub const MAX_TOKEN_DECIMALS: u8 = 36;
+
+/// ERC-20 token backing an asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub asset: AssetId,
+    /// ERC-20 contract address
+    pub address: Address,
+    pub symbol: String,
+    pub name: String,
+    /// Decimals of the token's base unit
+    pub decimals: u8,
+}
+
+/// Amount converted out of the canonical precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAmount {
+    /// Amount in the token's base units
+    pub amount: U256,
+    /// Canonical amount below one base unit, not representable in the token
+    pub dust: U256,
+}
+
+/// Registry mapping assets to their ERC-20 tokens
+///
+/// Assets without an entry are treated as having `CANONICAL_DECIMALS`.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: BTreeMap<u32, TokenInfo>,
+    by_address: HashMap<Address, AssetId>,
+}
+
+impl TokenRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a token; assets and addresses map one to one
+    pub fn register(&mut self, token: TokenInfo) -> Result<()> {
+        if self.tokens.contains_key(&token.asset.0) {
+            return Err(anyhow!("Asset {} already has a token", token.asset.0));
+        }
+        if self.by_address.contains_key(&token.address) {
+            return Err(anyhow!("Token {} is already registered", token.address));
+        }
+        if token.decimals > MAX_TOKEN_DECIMALS {
+            return Err(anyhow!("Token decimals {} exceed {}", token.decimals, MAX_TOKEN_DECIMALS));
+        }
+        self.by_address.insert(token.address, token.asset);
+        self.tokens.insert(token.asset.0, token);
+        Ok(())
+    }
+
+    /// Get the token of an asset
+    pub fn get(&self, asset: AssetId) -> Option<&TokenInfo> {
+        self.tokens.get(&asset.0)
+    }
+
+    /// Get the asset backed by an ERC-20 address
+    pub fn asset_of(&self, address: &Address) -> Option<AssetId> {
+        self.by_address.get(address).copied()
+    }
+
+    /// Decimals of an asset's base unit
+    pub fn decimals(&self, asset: AssetId) -> u8 {
+        self.get(asset).map_or(CANONICAL_DECIMALS, |token| token.decimals)
+    }
+
+    /// All tokens, by asset ID
+    pub fn tokens(&self) -> impl Iterator<Item = &TokenInfo> {
+        self.tokens.values()
+    }
+
+    /// Scale a token amount up to the canonical precision
+    pub fn to_canonical(&self, asset: AssetId, amount: U256) -> Result<U256> {
+        let decimals = self.decimals(asset);
+        if decimals >= CANONICAL_DECIMALS {
+            let factor = pow10(decimals - CANONICAL_DECIMALS);
+            if amount % factor != U256::ZERO {
+                return Err(anyhow!("Amount {} is finer than the engine precision of asset {}", amount, asset.0));
+            }
+            return Ok(amount / factor);
+        }
+        amount
+            .checked_mul(pow10(CANONICAL_DECIMALS - decimals))
+            .ok_or_else(|| anyhow!("Amount {} of asset {} overflows", amount, asset.0))
+    }
+
+    /// Scale a canonical amount down to the token's base units, rounding
+    /// down and reporting the remainder
+    pub fn from_canonical(&self, asset: AssetId, amount: U256) -> Result<TokenAmount> {
+        let decimals = self.decimals(asset);
+        if decimals >= CANONICAL_DECIMALS {
+            let amount = amount
+                .checked_mul(pow10(decimals - CANONICAL_DECIMALS))
+                .ok_or_else(|| anyhow!("Amount {} of asset {} overflows", amount, asset.0))?;
+            return Ok(TokenAmount { amount, dust: U256::ZERO });
+        }
+        let factor = pow10(CANONICAL_DECIMALS - decimals);
+        Ok(TokenAmount {
+            amount: amount / factor,
+            dust: amount % factor,
+        })
+    }
+
+    /// Convert a price quoted in `quote` token base units per whole `base`
+    /// token into a `Price`
+    pub fn price_from_token_units(&self, quote: AssetId, raw: U256) -> Result<Price> {
+        let price = rescale(raw, self.decimals(quote), Price::DECIMALS as u8);
+        u64::try_from(price).map(Price).map_err(|_| anyhow!("Price {} overflows", raw))
+    }
+
+    /// Convert a `Price` into `quote` token base units per whole base token,
+    /// rounding down
+    pub fn price_to_token_units(&self, quote: AssetId, price: Price) -> U256 {
+        rescale(U256::from(price.0), Price::DECIMALS as u8, self.decimals(quote))
+    }
+
+    /// Format a canonical amount in whole tokens with the symbol, e.g.
+    /// `1.5 USDC`, trimming trailing zeros
+    pub fn display(&self, asset: AssetId, amount: U256) -> String {
+        let (decimals, symbol) = match self.get(asset) {
+            Some(token) => (token.decimals, token.symbol.as_str()),
+            None => (CANONICAL_DECIMALS, ""),
+        };
+        let units = self
+            .from_canonical(asset, amount)
+            .map_or(amount, |converted| converted.amount);
+        let factor = pow10(decimals);
+        let whole = units / factor;
+        let fraction = format!("{:0>width$}", (units % factor).to_string(), width = decimals as usize);
+        let fraction = fraction.trim_end_matches('0');
+
+        let mut text = whole.to_string();
+        if !fraction.is_empty() {
+            text.push('.');
+            text.push_str(fraction);
+        }
+        if !symbol.is_empty() {
+            text.push(' ');
+            text.push_str(symbol);
+        }
+        text
+    }
+}
+
+fn pow10(exponent: u8) -> U256 {
+    U256::from(10u64).pow(U256::from(exponent))
+}
+
+/// Move `value` from `from` to `to` decimals, rounding down
+fn rescale(value: U256, from: u8, to: u8) -> U256 {
+    if to >= from {
+        value.saturating_mul(pow10(to - from))
+    } else {
+        value / pow10(from - to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(asset: u32, decimals: u8, symbol: &str) -> TokenInfo {
+        TokenInfo {
+            asset: AssetId(asset),
+            address: Address::with_last_byte(asset as u8),
+            symbol: symbol.to_string(),
+            name: symbol.to_string(),
+            decimals,
+        }
+    }
+
+    #[test]
+    fn test_six_and_eighteen_decimal_tokens() {
+        let mut registry = TokenRegistry::new();
+        registry.register(token(1, 6, "USDC")).unwrap();
+        registry.register(token(2, 18, "WETH")).unwrap();
+        assert!(registry.register(token(1, 8, "WBTC")).is_err());
+        assert_eq!(registry.asset_of(&Address::with_last_byte(2)), Some(AssetId(2)));
+
+        // One whole token is the same canonical amount whatever its decimals
+        let usdc = AssetId(1);
+        let one = registry.to_canonical(usdc, U256::from(1_000_000)).unwrap();
+        assert_eq!(one, registry.to_canonical(AssetId(2), U256::from(10).pow(U256::from(18))).unwrap());
+
+        // Leaving the engine rounds down to the token's base unit
+        let converted = registry.from_canonical(usdc, one + U256::from(5)).unwrap();
+        assert_eq!(converted, TokenAmount { amount: U256::from(1_000_000), dust: U256::from(5) });
+        assert_eq!(registry.display(usdc, one * U256::from(3) / U256::from(2)), "1.5 USDC");
+
+        // 2000 USDC per WETH, quoted in USDC base units
+        let price = registry.price_from_token_units(usdc, U256::from(2_000_000_000u64)).unwrap();
+        assert_eq!(price, Price::from_float(2000.0));
+        assert_eq!(registry.price_to_token_units(usdc, price), U256::from(2_000_000_000u64));
+        let weth_quoted = registry.price_from_token_units(AssetId(2), U256::from(10).pow(U256::from(18))).unwrap();
+        assert_eq!(weth_quoted, Price::from_float(1.0));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::types::*;
+use alloy_primitives::{keccak256, Address, PrimitiveSignature as Signature, B256, U256};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Domain separator for position transfer signing hashes
+const POSITION_TRANSFER_DOMAIN: &[u8] = b"OPENLIQUID_POSITION_TRANSFER_V1";
+
+/// Request to move a position between accounts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionTransfer {
+    pub from: Address,
+    pub to: Address,
+    pub asset: AssetId,
+    /// Signed size to transfer (same sign as the source position)
+    pub size: i64,
+    /// Per-source-account nonce for replay protection
+    pub nonce: u64,
+}
+
+impl PositionTransfer {
+    /// Hash both parties sign over, bound to `chain_id` so a transfer
+    /// signed for one chain cannot be replayed on another
+    pub fn signing_hash(&self, chain_id: u64) -> B256 {
+        let mut buf = Vec::with_capacity(POSITION_TRANSFER_DOMAIN.len() + 68);
+        buf.extend_from_slice(POSITION_TRANSFER_DOMAIN);
+        buf.extend_from_slice(&chain_id.to_be_bytes());
+        buf.extend_from_slice(self.from.as_slice());
+        buf.extend_from_slice(self.to.as_slice());
+        buf.extend_from_slice(&self.asset.0.to_be_bytes());
+        buf.extend_from_slice(&self.size.to_be_bytes());
+        buf.extend_from_slice(&self.nonce.to_be_bytes());
+        keccak256(&buf)
+    }
+}
+
+/// Position transfer authorized by both source and destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedPositionTransfer {
+    pub transfer: PositionTransfer,
+    pub from_signature: Signature,
+    pub to_signature: Signature,
+}
+
+impl SignedPositionTransfer {
+    /// Verify both signatures recover to the respective parties on `chain_id`
+    pub fn verify(&self, chain_id: u64) -> Result<()> {
+        let hash = self.transfer.signing_hash(chain_id);
+
+        let from = self.from_signature
+            .recover_address_from_prehash(&hash)
+            .map_err(|e| anyhow!("Invalid source signature: {}", e))?;
+        if from != self.transfer.from {
+            return Err(anyhow!("Source signature does not match sender"));
+        }
+
+        let to = self.to_signature
+            .recover_address_from_prehash(&hash)
+            .map_err(|e| anyhow!("Invalid destination signature: {}", e))?;
+        if to != self.transfer.to {
+            return Err(anyhow!("Destination signature does not match recipient"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Kind of ledger entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedgerEntryKind {
+    PositionTransferOut,
+    PositionTransferIn,
+    CollateralTransferOut,
+    CollateralTransferIn,
+}
+
+/// Single account ledger entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub account: Address,
+    pub counterparty: Address,
+    pub asset: AssetId,
+    pub kind: LedgerEntryKind,
+    /// Position size change (signed)
+    pub size_delta: i64,
+    /// Isolated collateral moved
+    pub collateral: U256,
+    /// Entry price of the transferred position
+    pub price: Price,
+    pub nonce: u64,
+    pub timestamp: u64,
+}
+
+/// Tracks transfer nonces and records ledger entries
+#[derive(Debug, Clone, Default)]
+pub struct TransferLedger {
+    /// Next expected nonce per source account
+    nonces: HashMap<Address, u64>,
+    /// All ledger entries in execution order
+    entries: Vec<LedgerEntry>,
+}
+
+impl TransferLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get next expected nonce for account
+    pub fn next_nonce(&self, account: &Address) -> u64 {
+        self.nonces.get(account).copied().unwrap_or(0)
+    }
+
+    /// Restore next expected nonces loaded from storage
+    pub fn restore_nonces(&mut self, nonces: impl IntoIterator<Item = (Address, u64)>) {
+        self.nonces.extend(nonces);
+    }
+
+    /// Check transfer nonce is the next expected one
+    pub fn check_nonce(&self, transfer: &PositionTransfer) -> Result<()> {
+        let expected = self.next_nonce(&transfer.from);
+        if transfer.nonce != expected {
+            return Err(anyhow!(
+                "Invalid transfer nonce: expected {}, got {}",
+                expected,
+                transfer.nonce
+            ));
+        }
+        Ok(())
+    }
+
+    /// Record an executed transfer (consumes the nonce)
+    pub fn record_transfer(
+        &mut self,
+        transfer: &PositionTransfer,
+        price: Price,
+        collateral: U256,
+        timestamp: u64,
+    ) {
+        self.nonces.insert(transfer.from, transfer.nonce + 1);
+
+        let entry = |account, counterparty, kind, size_delta, collateral| LedgerEntry {
+            account,
+            counterparty,
+            asset: transfer.asset,
+            kind,
+            size_delta,
+            collateral,
+            price,
+            nonce: transfer.nonce,
+            timestamp,
+        };
+
+        self.entries.push(entry(
+            transfer.from,
+            transfer.to,
+            LedgerEntryKind::PositionTransferOut,
+            -transfer.size,
+            U256::ZERO,
+        ));
+        self.entries.push(entry(
+            transfer.to,
+            transfer.from,
+            LedgerEntryKind::PositionTransferIn,
+            transfer.size,
+            U256::ZERO,
+        ));
+
+        if !collateral.is_zero() {
+            self.entries.push(entry(
+                transfer.from,
+                transfer.to,
+                LedgerEntryKind::CollateralTransferOut,
+                0,
+                collateral,
+            ));
+            self.entries.push(entry(
+                transfer.to,
+                transfer.from,
+                LedgerEntryKind::CollateralTransferIn,
+                0,
+                collateral,
+            ));
+        }
+    }
+
+    /// Get all ledger entries
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// Get ledger entries for an account
+    pub fn account_entries(&self, account: &Address) -> Vec<&LedgerEntry> {
+        self.entries.iter().filter(|e| e.account == *account).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+
+    fn key(seed: u8) -> (SigningKey, Address) {
+        let sk = SigningKey::from_slice(&[seed; 32]).unwrap();
+        let addr = Address::from_private_key(&sk);
+        (sk, addr)
+    }
+
+    fn sign(sk: &SigningKey, hash: B256) -> Signature {
+        let (sig, recid) = sk.sign_prehash_recoverable(hash.as_slice()).unwrap();
+        Signature::from_signature_and_parity(sig, recid.is_y_odd())
+    }
+
+    #[test]
+    fn test_verify_signed_transfer() {
+        let (from_sk, from) = key(1);
+        let (to_sk, to) = key(2);
+        let transfer = PositionTransfer { from, to, asset: AssetId(1), size: 10, nonce: 0 };
+        let hash = transfer.signing_hash(DEFAULT_CHAIN_ID);
+
+        let signed = SignedPositionTransfer {
+            transfer: transfer.clone(),
+            from_signature: sign(&from_sk, hash),
+            to_signature: sign(&to_sk, hash),
+        };
+        assert!(signed.verify(DEFAULT_CHAIN_ID).is_ok());
+        // Not valid on another chain
+        assert!(signed.verify(DEFAULT_CHAIN_ID + 1).is_err());
+
+        // Missing destination consent
+        let bad = SignedPositionTransfer {
+            transfer,
+            from_signature: sign(&from_sk, hash),
+            to_signature: sign(&from_sk, hash),
+        };
+        assert!(bad.verify(DEFAULT_CHAIN_ID).is_err());
+    }
+
+    #[test]
+    fn test_signing_hash_binds_fields() {
+        let t1 = PositionTransfer {
+            from: Address::from([1u8; 20]),
+            to: Address::from([2u8; 20]),
+            asset: AssetId(1),
+            size: 10,
+            nonce: 0,
+        };
+        let t2 = PositionTransfer { size: 11, ..t1.clone() };
+        let t3 = PositionTransfer { nonce: 1, ..t1.clone() };
+
+        assert_ne!(t1.signing_hash(1), t2.signing_hash(1));
+        assert_ne!(t1.signing_hash(1), t3.signing_hash(1));
+        assert_ne!(t1.signing_hash(1), t1.signing_hash(2));
+    }
+
+    #[test]
+    fn test_ledger_nonce_and_entries() {
+        let mut ledger = TransferLedger::new();
+        let from = Address::from([1u8; 20]);
+        let to = Address::from([2u8; 20]);
+        let transfer = PositionTransfer { from, to, asset: AssetId(1), size: -5, nonce: 0 };
+
+        assert!(ledger.check_nonce(&transfer).is_ok());
+        ledger.record_transfer(&transfer, Price::from_float(100.0), U256::from(50), 1);
+
+        // Nonce consumed
+        assert!(ledger.check_nonce(&transfer).is_err());
+        assert_eq!(ledger.next_nonce(&from), 1);
+
+        assert_eq!(ledger.entries().len(), 4);
+        let out = ledger.account_entries(&from);
+        assert_eq!(out[0].kind, LedgerEntryKind::PositionTransferOut);
+        assert_eq!(out[0].size_delta, 5);
+        assert_eq!(out[1].collateral, U256::from(50));
+    }
+}
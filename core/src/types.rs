@@ -4,6 +4,10 @@ use serde::{Deserialize, Serialize};
 /// Unique order identifier
 pub type OrderId = u64;
 
+/// Chain ID signed transfers and actions are bound to unless configured
+/// otherwise (matches the EVM's default)
+pub const DEFAULT_CHAIN_ID: u64 = 1;
+
 /// Asset identifier (trading pair)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct AssetId(pub u32);
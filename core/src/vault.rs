@@ -1,3 +1,4 @@
+use crate::rounding::RoundingPolicy;
 use crate::types::*;
 use alloy_primitives::{Address, U256};
 use anyhow::{anyhow, Result};
@@ -42,7 +43,7 @@ pub enum VaultStrategy {
 }
 
 /// Vault performance statistics
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct VaultStats {
     pub total_pnl: i64,
     pub total_trades: u64,
@@ -52,12 +53,29 @@ pub struct VaultStats {
     pub rebates_earned: U256,
 }
 
+/// Persistent vault state: vaults, stats and shares, sorted by vault ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VaultState {
+    pub vaults: Vec<MMVault>,
+    pub next_id: VaultId,
+    pub stats: Vec<(VaultId, VaultStats)>,
+    /// Shares by vault and holder
+    pub shares: Vec<(VaultId, Address, U256)>,
+}
+
 /// Vault manager
+#[derive(Clone)]
 pub struct VaultManager {
     vaults: HashMap<VaultId, MMVault>,
     next_id: VaultId,
     user_vaults: HashMap<Address, Vec<VaultId>>,
     vault_stats: HashMap<VaultId, VaultStats>,
+    /// Shares by vault and holder
+    shares: HashMap<VaultId, HashMap<Address, U256>>,
+    /// Shares outstanding by vault
+    total_shares: HashMap<VaultId, U256>,
+    /// Manager profit shares round down (credit)
+    rounding: RoundingPolicy,
 }
 
 impl VaultManager {
@@ -67,9 +85,53 @@ impl VaultManager {
             next_id: 1,
             user_vaults: HashMap::new(),
             vault_stats: HashMap::new(),
+            shares: HashMap::new(),
+            total_shares: HashMap::new(),
+            rounding: RoundingPolicy::default(),
+        }
+    }
+
+    /// Capture the vaults, stats and shares for persistence
+    pub fn state(&self) -> VaultState {
+        let mut vaults: Vec<MMVault> = self.vaults.values().cloned().collect();
+        vaults.sort_by_key(|vault| vault.id);
+        let mut stats: Vec<(VaultId, VaultStats)> =
+            self.vault_stats.iter().map(|(id, stats)| (*id, stats.clone())).collect();
+        stats.sort_by_key(|(id, _)| *id);
+        let mut shares: Vec<(VaultId, Address, U256)> = self
+            .shares
+            .iter()
+            .flat_map(|(id, holders)| holders.iter().map(move |(user, shares)| (*id, *user, *shares)))
+            .collect();
+        shares.sort_by_key(|(id, user, _)| (*id, *user));
+        VaultState { vaults, next_id: self.next_id, stats, shares }
+    }
+
+    /// Replace the vaults, stats and shares with `state`, keeping the
+    /// rounding policy
+    pub fn restore(&mut self, state: VaultState) {
+        let rounding = self.rounding;
+        *self = Self::new();
+        self.rounding = rounding;
+        self.next_id = state.next_id.max(1);
+        for vault in state.vaults {
+            self.user_vaults.entry(vault.owner).or_default().push(vault.id);
+            self.vaults.insert(vault.id, vault);
+        }
+        self.vault_stats = state.stats.into_iter().collect();
+        for (vault_id, user, shares) in state.shares {
+            self.shares.entry(vault_id).or_default().insert(user, shares);
+            *self.total_shares.entry(vault_id).or_default() += shares;
         }
     }
 
+    /// Set the rounding policy for profit shares
+    pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) -> Result<()> {
+        policy.validate()?;
+        self.rounding = policy;
+        Ok(())
+    }
+
     /// Create new market maker vault
     pub fn create_vault(
         &mut self,
@@ -110,10 +172,93 @@ impl VaultManager {
             .or_insert_with(Vec::new)
             .push(id);
         self.vault_stats.insert(id, VaultStats::default());
+        // The owner's initial collateral buys shares at par
+        self.mint(id, owner, collateral);
 
         Ok(id)
     }
 
+    /// Shares `user` holds in a vault
+    pub fn shares_of(&self, vault_id: VaultId, user: &Address) -> U256 {
+        self.shares
+            .get(&vault_id)
+            .and_then(|holders| holders.get(user))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Shares outstanding in a vault
+    pub fn total_shares(&self, vault_id: VaultId) -> U256 {
+        self.total_shares.get(&vault_id).copied().unwrap_or_default()
+    }
+
+    /// Equity per share, scaled by `scale`
+    pub fn share_price(&self, vault_id: VaultId, scale: U256) -> Result<U256> {
+        let vault = self.get_vault(vault_id).ok_or_else(|| anyhow!("Vault not found"))?;
+        let total = self.total_shares(vault_id);
+        if total.is_zero() {
+            return Ok(scale);
+        }
+        Ok(vault.equity * scale / total)
+    }
+
+    /// Deposit `amount` for `user`, returning the shares minted
+    pub fn deposit_for_shares(
+        &mut self,
+        vault_id: VaultId,
+        user: Address,
+        amount: U256,
+        timestamp: u64,
+    ) -> Result<U256> {
+        let vault = self.get_vault(vault_id).ok_or_else(|| anyhow!("Vault not found"))?;
+        let total = self.total_shares(vault_id);
+        // Shares round down, in the vault's favour
+        let shares = if total.is_zero() || vault.equity.is_zero() {
+            amount
+        } else {
+            amount * total / vault.equity
+        };
+        if shares.is_zero() {
+            return Err(anyhow!("Deposit too small for one share"));
+        }
+
+        self.deposit(vault_id, amount, timestamp)?;
+        self.mint(vault_id, user, shares);
+        Ok(shares)
+    }
+
+    /// Redeem `shares` of `user`, returning the amount paid out
+    pub fn redeem_shares(
+        &mut self,
+        vault_id: VaultId,
+        user: Address,
+        shares: U256,
+        timestamp: u64,
+    ) -> Result<U256> {
+        let vault = self.get_vault(vault_id).ok_or_else(|| anyhow!("Vault not found"))?;
+        let held = self.shares_of(vault_id, &user);
+        if shares.is_zero() || shares > held {
+            return Err(anyhow!("Insufficient shares: have {}, redeeming {}", held, shares));
+        }
+        // Payout rounds down, in the vault's favour
+        let amount = shares * vault.equity / self.total_shares(vault_id);
+
+        self.withdraw(vault_id, amount, timestamp)?;
+        let holders = self.shares.entry(vault_id).or_default();
+        if held == shares {
+            holders.remove(&user);
+        } else {
+            holders.insert(user, held - shares);
+        }
+        *self.total_shares.entry(vault_id).or_default() -= shares;
+        Ok(amount)
+    }
+
+    fn mint(&mut self, vault_id: VaultId, user: Address, shares: U256) {
+        *self.shares.entry(vault_id).or_default().entry(user).or_default() += shares;
+        *self.total_shares.entry(vault_id).or_default() += shares;
+    }
+
     /// Get vault by ID
     pub fn get_vault(&self, vault_id: VaultId) -> Option<&MMVault> {
         self.vaults.get(&vault_id)
@@ -212,7 +357,7 @@ impl VaultManager {
 
         if vault.equity > vault.collateral {
             let profit = vault.equity.saturating_sub(vault.collateral);
-            Ok(profit * U256::from(vault.profit_share_bps) / U256::from(10000))
+            Ok(self.rounding.credit(profit, U256::from(vault.profit_share_bps), U256::from(10000)))
         } else {
             Ok(U256::ZERO)
         }
@@ -805,5 +950,38 @@ mod tests {
             _ => panic!("Wrong strategy type"),
         }
     }
+
+    #[test]
+    fn test_shares_survive_state_round_trip() {
+        let mut manager = VaultManager::new();
+        let owner = test_address(1);
+        let depositor = test_address(3);
+
+        let vault_id = manager
+            .create_vault(owner, test_address(2), VaultStrategy::Custom, U256::from(10000), 1000, 100)
+            .unwrap();
+        assert_eq!(manager.shares_of(vault_id, &owner), U256::from(10000));
+
+        // Equity doubles, so a deposit buys shares at twice par
+        manager.update_equity(vault_id, U256::from(20000), 110).unwrap();
+        let shares = manager.deposit_for_shares(vault_id, depositor, U256::from(4000), 120).unwrap();
+        assert_eq!(shares, U256::from(2000));
+        assert_eq!(manager.total_shares(vault_id), U256::from(12000));
+
+        let mut restored = VaultManager::new();
+        restored.restore(manager.state());
+        assert_eq!(restored.shares_of(vault_id, &depositor), U256::from(2000));
+        assert_eq!(restored.total_shares(vault_id), U256::from(12000));
+        assert_eq!(restored.get_user_vaults(&owner).len(), 1);
+        assert_eq!(restored.get_vault(vault_id).unwrap().equity, U256::from(24000));
+
+        let amount = restored.redeem_shares(vault_id, depositor, shares, 130).unwrap();
+        assert_eq!(amount, U256::from(4000));
+        assert!(restored.redeem_shares(vault_id, depositor, U256::from(1), 140).is_err());
+        let next = restored
+            .create_vault(owner, test_address(2), VaultStrategy::Custom, U256::from(1), 0, 150)
+            .unwrap();
+        assert_eq!(next, vault_id + 1);
+    }
 }
 
@@ -2,10 +2,13 @@
 //
 // Connects HotStuff consensus with EVM execution layer
 
-use crate::{Mempool, Transaction};
+use crate::{Mempool, ProposalBuilder, Transaction};
+use alloy_primitives::{Address, B256, U256};
 use anyhow::{anyhow, Result};
 use consensus::hotstuff::engine::ConsensusEngine;
 use consensus::hotstuff::types::Block;
+use k256::ecdsa::SigningKey;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -20,6 +23,8 @@ pub struct ConsensusEvmBridge {
     pub(crate) consensus: Arc<RwLock<ConsensusEngine>>,
     /// Transaction mempool
     pub(crate) mempool: Arc<RwLock<Mempool>>,
+    /// Committed blocks whose nonces the mempool has seen
+    synced_commits: AtomicUsize,
 }
 
 impl ConsensusEvmBridge {
@@ -31,6 +36,7 @@ impl ConsensusEvmBridge {
         Self {
             consensus,
             mempool,
+            synced_commits: AtomicUsize::new(0),
         }
     }
 
@@ -42,6 +48,28 @@ impl ConsensusEvmBridge {
             .map_err(|e| anyhow!("Failed to add transaction to mempool: {}", e))
     }
 
+    /// Submit a signed raw transaction, returning its hash
+    pub async fn submit_raw_transaction(&self, raw: &[u8]) -> Result<B256> {
+        let mut mempool = self.mempool.write().await;
+        mempool
+            .add_raw(raw)
+            .map_err(|e| anyhow!("Failed to add transaction to mempool: {}", e))
+    }
+
+    /// Sign a transaction with the sender's next nonce and submit it,
+    /// returning the nonce it was given
+    pub async fn submit_with_next_nonce(&self, tx: Transaction, key: &SigningKey) -> Result<u64> {
+        let mut mempool = self.mempool.write().await;
+        mempool
+            .add_with_next_nonce(tx, key)
+            .map_err(|e| anyhow!("Failed to add transaction to mempool: {}", e))
+    }
+
+    /// Nonce the sender's next transaction should use
+    pub async fn next_nonce(&self, sender: &Address) -> u64 {
+        self.mempool.read().await.next_nonce(sender)
+    }
+
     /// Propose a new block (leader only)
     /// 
     /// Gets transactions from mempool and creates a block proposal
@@ -60,8 +88,32 @@ impl ConsensusEvmBridge {
 
         // Propose block via consensus
         let mut consensus = self.consensus.write().await;
-        consensus.propose_block(tx_bytes).await
-            .map_err(|e| anyhow!("Failed to propose block: {}", e))
+        let block = consensus.propose_block(tx_bytes).await
+            .map_err(|e| anyhow!("Failed to propose block: {}", e))?;
+        self.sync_mempool(&consensus).await;
+        Ok(block)
+    }
+
+    /// Propose a block built from the mempool (leader only)
+    ///
+    /// Unlike `propose_block`, the mempool is left untouched when this node
+    /// is not the leader.
+    pub async fn build_proposal(&self, builder: &ProposalBuilder) -> Result<Block> {
+        let mut consensus = self.consensus.write().await;
+        if !consensus.is_leader() {
+            return Err(anyhow!("Not the leader for the current view"));
+        }
+
+        let pending = ProposalBuilder::pending_transactions(&consensus);
+        let tx_bytes = {
+            let mut mempool = self.mempool.write().await;
+            builder.select(&mut mempool, &pending)
+        };
+
+        let block = consensus.propose_block(tx_bytes).await
+            .map_err(|e| anyhow!("Failed to propose block: {}", e))?;
+        self.sync_mempool(&consensus).await;
+        Ok(block)
     }
 
     /// Process an incoming block
@@ -70,7 +122,29 @@ impl ConsensusEvmBridge {
     pub async fn process_block(&self, block: Block) -> Result<()> {
         let mut consensus = self.consensus.write().await;
         consensus.process_block(block).await
-            .map_err(|e| anyhow!("Failed to process block: {}", e))
+            .map_err(|e| anyhow!("Failed to process block: {}", e))?;
+        self.sync_mempool(&consensus).await;
+        Ok(())
+    }
+
+    /// Advance the mempool's account nonces past newly committed blocks and
+    /// move it to the base fee the next block charges
+    async fn sync_mempool(&self, consensus: &ConsensusEngine) {
+        let base_fee = U256::from(consensus.next_base_fee().await);
+        self.mempool.write().await.set_base_fee(base_fee);
+
+        let committed = consensus.committed_blocks();
+        let synced = self.synced_commits.swap(committed.len(), Ordering::Relaxed);
+        let transactions: Vec<Transaction> = committed
+            .get(synced..)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .filter_map(|bytes| serde_json::from_slice(bytes).ok())
+            .collect();
+        if !transactions.is_empty() {
+            self.mempool.write().await.on_committed(&transactions);
+        }
     }
 
     /// Check if this node is the current leader
@@ -94,12 +168,28 @@ impl ConsensusEvmBridge {
     /// Get mempool statistics
     pub async fn mempool_stats(&self) -> MempoolStats {
         let mempool = self.mempool.read().await;
+        #[cfg(feature = "metrics")]
+        consensus::metrics::global().set_mempool_depth(mempool.len());
         MempoolStats {
             pending_count: mempool.len(),
+            cancel_lane_count: mempool.cancel_lane_len(),
             is_empty: mempool.is_empty(),
         }
     }
 
+    /// Set the base fee the next block charges, so proposals order
+    /// transactions by the tip they pay
+    ///
+    /// Proposing and processing blocks keep it in step with the executor.
+    pub async fn set_base_fee(&self, base_fee: U256) {
+        self.mempool.write().await.set_base_fee(base_fee);
+    }
+
+    /// Set the account this node's proposals pay their priority fees to
+    pub async fn set_fee_recipient(&self, recipient: Address) {
+        self.consensus.write().await.set_fee_recipient(recipient.into());
+    }
+
     /// Clear mempool (useful for testing)
     pub async fn clear_mempool(&self) {
         let mut mempool = self.mempool.write().await;
@@ -117,13 +207,15 @@ impl ConsensusEvmBridge {
 #[derive(Debug, Clone)]
 pub struct MempoolStats {
     pub pending_count: usize,
+    /// Pending gas-free order cancellations (included in `pending_count`)
+    pub cancel_lane_count: usize,
     pub is_empty: bool,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use alloy_primitives::{Address, U256};
+    use crate::types::{test_key, test_sender};
     use consensus::crypto::bls::BLSKeyPair;
     use consensus::storage::state_machine::SimpleStateMachine;
     use consensus::storage::Storage;
@@ -148,9 +240,10 @@ mod tests {
     }
 
     fn create_test_tx(from_byte: u8, to_byte: u8, nonce: u64) -> Transaction {
-        let from = Address::repeat_byte(from_byte);
         let to = Address::repeat_byte(to_byte);
-        Transaction::transfer(from, to, U256::from(1000), nonce)
+        Transaction::transfer(test_sender(from_byte), to, U256::from(1000), nonce)
+            .sign(&test_key(from_byte))
+            .unwrap()
     }
 
     #[tokio::test]
@@ -255,6 +348,30 @@ mod tests {
         assert_eq!(stats.pending_count, 2);
     }
 
+    #[tokio::test]
+    async fn test_build_proposal_tracks_pending_payload() {
+        let follower = create_test_bridge(0).await;
+        let leader = create_test_bridge(1).await;
+        for bridge in [&follower, &leader] {
+            bridge.consensus.write().await.start().await.unwrap();
+            bridge.submit_transaction(create_test_tx(0x01, 0x02, 0)).await.unwrap();
+        }
+
+        // A follower keeps its mempool
+        let builder = ProposalBuilder::default();
+        assert!(follower.build_proposal(&builder).await.is_err());
+        assert_eq!(follower.mempool_stats().await.pending_count, 1);
+
+        let block = leader.build_proposal(&builder).await.unwrap();
+        assert_eq!(block.transactions.len(), 1);
+
+        // Once processed, the proposed transaction is pending in the tree
+        leader.process_block(block).await.unwrap();
+        let consensus = leader.consensus.read().await;
+        let pending = ProposalBuilder::pending_transactions(&consensus);
+        assert!(pending.contains(&(test_sender(0x01), 0)));
+    }
+
     #[tokio::test]
     async fn test_current_view() {
         let bridge = create_test_bridge(0).await;
@@ -321,8 +438,12 @@ mod tests {
         );
         
         // Process the block
+        bridge.set_base_fee(U256::from(100)).await;
         let result = bridge.process_block(block.clone()).await;
         assert!(result.is_ok());
+        
+        // The mempool follows the state machine's base fee (none here)
+        assert_eq!(bridge.mempool.read().await.base_fee(), U256::ZERO);
     }
 
     #[tokio::test]
@@ -0,0 +1,135 @@
+// EVM State Cache
+//
+// LRU caches of decoded accounts and storage slots in front of RocksDB.
+// `EvmStorage` writes through them, so cached entries never go stale, and
+// absent accounts are cached too so repeated misses skip the database.
+
+use alloy_primitives::{Address, U256};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+
+use crate::types::Account;
+
+/// State cache sizes; a size of zero disables that cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateCacheConfig {
+    /// Accounts kept in memory
+    pub account_capacity: usize,
+    /// Storage slots kept in memory
+    pub storage_capacity: usize,
+}
+
+impl Default for StateCacheConfig {
+    fn default() -> Self {
+        Self {
+            account_capacity: 10_000,
+            storage_capacity: 100_000,
+        }
+    }
+}
+
+/// Hit and miss counters of the state cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateCacheStats {
+    pub account_hits: u64,
+    pub account_misses: u64,
+    pub storage_hits: u64,
+    pub storage_misses: u64,
+}
+
+impl StateCacheStats {
+    /// Fraction of lookups served from memory
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.account_hits + self.storage_hits;
+        let lookups = hits + self.account_misses + self.storage_misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        hits as f64 / lookups as f64
+    }
+}
+
+/// LRU account and storage-slot cache
+#[derive(Debug)]
+pub struct StateCache {
+    /// Accounts by address; `None` records an account known to be absent
+    accounts: Option<LruCache<Address, Option<Account>>>,
+    storage: Option<LruCache<(Address, U256), U256>>,
+    stats: StateCacheStats,
+}
+
+impl StateCache {
+    /// Create a cache with the configured sizes
+    pub fn new(config: StateCacheConfig) -> Self {
+        Self {
+            accounts: NonZeroUsize::new(config.account_capacity).map(LruCache::new),
+            storage: NonZeroUsize::new(config.storage_capacity).map(LruCache::new),
+            stats: StateCacheStats::default(),
+        }
+    }
+
+    /// Cached account, counting the lookup; the outer `None` is a miss
+    pub fn get_account(&mut self, address: &Address) -> Option<Option<Account>> {
+        let cached = self.accounts.as_mut().and_then(|cache| cache.get(address).cloned());
+        if cached.is_some() {
+            self.stats.account_hits += 1;
+        } else {
+            self.stats.account_misses += 1;
+        }
+        #[cfg(feature = "metrics")]
+        consensus::metrics::global().state_cache_lookup(consensus::metrics::StateCacheKind::Account, cached.is_some());
+        cached
+    }
+
+    /// Record an account's current value
+    pub fn put_account(&mut self, address: Address, account: Option<Account>) {
+        if let Some(cache) = self.accounts.as_mut() {
+            cache.put(address, account);
+        }
+    }
+
+    /// Whether an account is cached, without counting a lookup or
+    /// refreshing its recency
+    pub fn contains_account(&self, address: &Address) -> bool {
+        self.accounts.as_ref().is_some_and(|cache| cache.contains(address))
+    }
+
+    /// Cached storage slot, counting the lookup
+    pub fn get_storage(&mut self, address: &Address, slot: &U256) -> Option<U256> {
+        let cached = self.storage.as_mut().and_then(|cache| cache.get(&(*address, *slot)).copied());
+        if cached.is_some() {
+            self.stats.storage_hits += 1;
+        } else {
+            self.stats.storage_misses += 1;
+        }
+        #[cfg(feature = "metrics")]
+        consensus::metrics::global().state_cache_lookup(consensus::metrics::StateCacheKind::Storage, cached.is_some());
+        cached
+    }
+
+    /// Record a storage slot's current value
+    pub fn put_storage(&mut self, address: Address, slot: U256, value: U256) {
+        if let Some(cache) = self.storage.as_mut() {
+            cache.put((address, slot), value);
+        }
+    }
+
+    /// Whether a storage slot is cached, without counting a lookup or
+    /// refreshing its recency
+    pub fn contains_storage(&self, address: &Address, slot: &U256) -> bool {
+        self.storage.as_ref().is_some_and(|cache| cache.contains(&(*address, *slot)))
+    }
+
+    /// Hit and miss counters so far
+    pub fn stats(&self) -> StateCacheStats {
+        self.stats
+    }
+
+    /// Number of cached accounts and storage slots
+    pub fn sizes(&self) -> (usize, usize) {
+        (
+            self.accounts.as_ref().map_or(0, LruCache::len),
+            self.storage.as_ref().map_or(0, LruCache::len),
+        )
+    }
+}
@@ -0,0 +1,209 @@
+// Block commitments
+//
+// Each executed block commits to the EVM state after it and to its receipts,
+// so light clients can verify accounts and receipts against a block. The
+// state root is a sparse Merkle tree (the one consensus authenticates its
+// own state with) keyed by address, whose leaves commit to the account's
+// nonce, balance, code hash and a storage root: a sparse Merkle tree over
+// the account's non-zero slots. The tree is built from the accounts and
+// slots persisted in storage and then updated with each block's changes, so
+// every node commits to the same state whatever it has cached. The receipts
+// root is the binary Merkle root consensus uses for transactions, over the
+// JSON-encoded receipts in block order.
+
+use alloy_primitives::{Address, B256, U256};
+use anyhow::Result;
+use consensus::crypto::{merkle_root, Hash, MerkleProof};
+use consensus::storage::state_machine::sparse_merkle::PLACEHOLDER_HASH;
+use consensus::storage::state_machine::{SparseMerkleProof, SparseMerkleTree};
+use revm::db::DbAccount;
+use revm::primitives::HashMap;
+
+use crate::storage::EvmStorage;
+use crate::types::Receipt;
+
+pub(crate) fn hash_to_b256(hash: Hash) -> B256 {
+    B256::from_slice(hash.as_bytes())
+}
+
+/// Sparse Merkle tree over an account's non-zero storage slots
+fn storage_tree(storage: &HashMap<U256, U256>) -> SparseMerkleTree {
+    let mut tree = SparseMerkleTree::new();
+    for (slot, value) in storage.iter().filter(|(_, value)| !value.is_zero()) {
+        tree.insert(slot.to_be_bytes::<32>().to_vec(), value.to_be_bytes::<32>().to_vec());
+    }
+    tree
+}
+
+fn leaf(nonce: u64, balance: U256, code_hash: B256, storage_root: Hash) -> Vec<u8> {
+    let mut leaf = Vec::with_capacity(8 + 32 * 3);
+    leaf.extend_from_slice(&nonce.to_be_bytes());
+    leaf.extend_from_slice(&balance.to_be_bytes::<32>());
+    leaf.extend_from_slice(code_hash.as_slice());
+    leaf.extend_from_slice(storage_root.as_bytes());
+    leaf
+}
+
+/// Leaf value committing to an account: nonce, balance, code hash and
+/// storage root
+pub fn account_leaf(account: &DbAccount) -> Vec<u8> {
+    let info = &account.info;
+    leaf(info.nonce, info.balance, info.code_hash, storage_tree(&account.storage).root())
+}
+
+/// State tree over the persisted accounts and their slots
+#[derive(Clone, Default)]
+pub struct StateCommitment {
+    accounts: SparseMerkleTree,
+    storage: HashMap<Address, SparseMerkleTree>,
+}
+
+impl StateCommitment {
+    /// Build the tree from the accounts and slots persisted in `storage`
+    pub fn load(storage: &EvmStorage) -> Result<Self> {
+        let mut commitment = Self::default();
+        for (address, slot, value) in storage.storage_slots()? {
+            commitment.set_slot(address, slot, value);
+        }
+        for (address, account) in storage.accounts()? {
+            commitment.set_account(address, account.nonce, account.balance, account.code_hash);
+        }
+        Ok(commitment)
+    }
+
+    /// Set a storage slot; the account's leaf picks it up on the next
+    /// `set_account`
+    pub fn set_slot(&mut self, address: Address, slot: U256, value: U256) {
+        let key = slot.to_be_bytes::<32>().to_vec();
+        if value.is_zero() {
+            if let Some(tree) = self.storage.get_mut(&address) {
+                tree.remove(&key);
+                if tree.is_empty() {
+                    self.storage.remove(&address);
+                }
+            }
+        } else {
+            self.storage
+                .entry(address)
+                .or_default()
+                .insert(key, value.to_be_bytes::<32>().to_vec());
+        }
+    }
+
+    /// Drop every slot of an account, returning the slots that were set
+    pub fn clear_storage(&mut self, address: &Address) -> Vec<U256> {
+        self.storage
+            .remove(address)
+            .map(|tree| tree.iter().map(|(key, _)| U256::from_be_slice(key)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Set an account's leaf, returning its storage root
+    pub fn set_account(&mut self, address: Address, nonce: u64, balance: U256, code_hash: B256) -> B256 {
+        let storage_root = self.storage.get(&address).map_or(PLACEHOLDER_HASH, SparseMerkleTree::root);
+        self.accounts
+            .insert(address.to_vec(), leaf(nonce, balance, code_hash, storage_root));
+        hash_to_b256(storage_root)
+    }
+
+    /// Remove an account and its slots
+    pub fn remove_account(&mut self, address: &Address) {
+        self.storage.remove(address);
+        self.accounts.remove(address.as_slice());
+    }
+
+    /// Root of the state tree
+    pub fn root(&self) -> B256 {
+        hash_to_b256(self.accounts.root())
+    }
+
+    /// Proof of an account's leaf, or of its absence, against `root`
+    pub fn prove(&self, address: &Address) -> SparseMerkleProof {
+        self.accounts.prove(address.as_slice())
+    }
+}
+
+fn receipt_leaves(receipts: &[Receipt]) -> Vec<Vec<u8>> {
+    receipts
+        .iter()
+        .map(|receipt| serde_json::to_vec(receipt).expect("receipts serialize"))
+        .collect()
+}
+
+/// Root of the Merkle tree over a block's receipts, in block order
+pub fn receipts_root(receipts: &[Receipt]) -> B256 {
+    hash_to_b256(merkle_root(&receipt_leaves(receipts)))
+}
+
+/// Proof that receipt `index` is part of a block, against `receipts_root`
+pub fn prove_receipt(receipts: &[Receipt], index: usize) -> Option<MerkleProof> {
+    MerkleProof::new(&receipt_leaves(receipts), index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::primitives::AccountInfo;
+
+    fn receipt(gas_used: u64) -> Receipt {
+        Receipt {
+            transaction_hash: B256::repeat_byte(gas_used as u8),
+            from: Address::repeat_byte(0x01),
+            to: None,
+            contract_address: None,
+            gas_used,
+            success: true,
+            output: Default::default(),
+            logs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_state_root_commits_to_accounts_and_storage() {
+        let address = Address::repeat_byte(0x01);
+        let mut commitment = StateCommitment::default();
+        let empty = commitment.root();
+
+        let mut account = DbAccount::from(AccountInfo { balance: U256::from(100), ..Default::default() });
+        let info = account.info.clone();
+        commitment.set_account(address, info.nonce, info.balance, info.code_hash);
+        let funded = commitment.root();
+        assert_ne!(funded, empty);
+
+        // Zero slots are absent, other slots change the root
+        commitment.set_slot(address, U256::from(1), U256::ZERO);
+        commitment.set_account(address, info.nonce, info.balance, info.code_hash);
+        assert_eq!(commitment.root(), funded);
+        commitment.set_slot(address, U256::from(1), U256::from(7));
+        commitment.set_account(address, info.nonce, info.balance, info.code_hash);
+        account.storage.insert(U256::from(1), U256::from(7));
+        let root = commitment.root();
+        assert_ne!(root, funded);
+
+        let root_hash = Hash::new(root.0);
+        let proof = commitment.prove(&address);
+        assert!(proof.verify_inclusion(&root_hash, address.as_slice(), &account_leaf(&account)));
+        let proof = commitment.prove(&Address::repeat_byte(0x02));
+        assert!(proof.verify_exclusion(&root_hash, Address::repeat_byte(0x02).as_slice()));
+
+        // Clearing the slots and removing the account undo them
+        assert_eq!(commitment.clear_storage(&address), vec![U256::from(1)]);
+        commitment.set_account(address, info.nonce, info.balance, info.code_hash);
+        assert_eq!(commitment.root(), funded);
+        commitment.remove_account(&address);
+        assert_eq!(commitment.root(), empty);
+    }
+
+    #[test]
+    fn test_receipts_root_and_proofs() {
+        let receipts = vec![receipt(21_000), receipt(50_000), receipt(30_000)];
+        let root = receipts_root(&receipts);
+        assert_eq!(receipts_root(&[]), B256::ZERO);
+        assert_ne!(root, receipts_root(&receipts[..2]));
+
+        let proof = prove_receipt(&receipts, 1).unwrap();
+        let leaf = serde_json::to_vec(&receipts[1]).unwrap();
+        assert!(proof.verify(&Hash::new(root.0), &leaf, receipts.len() as u64));
+        assert!(prove_receipt(&receipts, 3).is_none());
+    }
+}
@@ -2,21 +2,72 @@
 //
 // Handles EVM transaction execution using revm
 
-use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_primitives::{Address, Bytes, B256, U256, U64};
 use anyhow::{anyhow, Result};
+use consensus::storage::state_machine::SparseMerkleProof;
+use openliquid_core::{CoreCheckpoint, CoreStateMachine};
 use revm::{
-    db::CacheDB,
+    db::{AccountState, CacheDB},
+    handler::register::HandleRegisterBox,
+    inspector_handle_register,
     primitives::{
         Env, ExecutionResult, Output, ResultAndState, TxKind,
     },
-    Database, Evm,
+    Database, DatabaseCommit, Evm,
+};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::commitments::StateCommitment;
+use crate::fee_market::{FeeMarket, FeeMarketConfig};
+use crate::mempool::is_cancel_only;
+use crate::precompiles::collateral::{BridgeSnapshot, CollateralBridge, WithdrawalBatch};
+use crate::precompiles::oracle::PriceOracle;
+use crate::precompiles::randomness::RandomnessBeacon;
+use crate::precompiles::vault::{self, VaultPrecompile};
+use crate::precompiles::{
+    get_precompile, is_precompile, margin, Precompile, COLLATERAL_PRECOMPILE, MARGIN_PRECOMPILE,
+    ORACLE_PRECOMPILE, RANDOMNESS_PRECOMPILE, VAULT_PRECOMPILE,
 };
-use std::sync::{Arc, RwLock};
-
-use crate::precompiles::{get_precompile, is_precompile, Precompile};
 use crate::storage::EvmStorage;
-use crate::types::{Receipt, Transaction};
-use std::collections::HashMap;
+use crate::trace::{CallFrame, TraceConfig, Tracer, TransactionTrace};
+use crate::types::{
+    Account, AccountDiff, BundleSimulation, Log, Receipt, SimulatedTransaction, StateDiff, Transaction,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+/// Traces of executed transactions kept while tracing is enabled
+pub const MAX_TRACES: usize = 10_000;
+
+/// Gas limit of a block, and the most a call or estimate may use
+const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// Executor state captured by `snapshot`
+struct ExecutorSnapshot {
+    cache: CacheDB<EvmStorage>,
+    /// Snapshot of the storage's persisted writes
+    storage: usize,
+    block_number: u64,
+    block_timestamp: u64,
+    precompiles: HashMap<Address, Box<dyn Precompile>>,
+    randomness: RandomnessBeacon,
+    fee_market: FeeMarket,
+    bridge: Option<BridgeSnapshot>,
+    /// Checkpoint of the vault calls
+    vaults: Option<usize>,
+    /// Checkpoints of the core state machines behind the precompiles
+    cores: Vec<(Arc<RwLock<CoreStateMachine>>, CoreCheckpoint)>,
+    commitment: StateCommitment,
+    dirty: BTreeMap<Address, DirtyAccount>,
+}
+
+/// Changes to a cached account not yet written to storage
+#[derive(Clone, Default)]
+struct DirtyAccount {
+    /// Storage was wiped, by contract creation or self-destruct
+    storage_cleared: bool,
+    /// Slots written since the last `commit_state`
+    slots: BTreeSet<U256>,
+}
 
 /// EVM Executor manages transaction execution
 pub struct EvmExecutor {
@@ -28,30 +79,299 @@ pub struct EvmExecutor {
     block_timestamp: u64,
     /// Precompile instances (maintained across calls)
     precompiles: HashMap<Address, Box<dyn Precompile>>,
+    /// EVM <-> core collateral bridge (if attached)
+    collateral_bridge: Option<CollateralBridge>,
+    /// Core price oracle (if attached)
+    price_oracle: Option<PriceOracle>,
+    /// Market-making vaults (if attached), shared with the EVM handler
+    /// serving contract calls to them
+    vaults: Option<Arc<Mutex<VaultPrecompile>>>,
+    /// Per-block randomness from consensus
+    randomness: RandomnessBeacon,
+    /// Account the current block's priority fees are paid to
+    coinbase: Address,
+    /// Base fee and collected base fees
+    fee_market: FeeMarket,
+    /// State tree over the persisted accounts
+    commitment: StateCommitment,
+    /// Accounts changed in the cache since the last `commit_state`
+    dirty: BTreeMap<Address, DirtyAccount>,
+    /// Open snapshots, oldest first
+    snapshots: Vec<ExecutorSnapshot>,
+    /// Trace executed transactions (if enabled)
+    tracing: Option<TraceConfig>,
+    /// Traces of recently executed transactions, by hash
+    traces: HashMap<B256, TransactionTrace>,
+    /// Traced transaction hashes, oldest first
+    trace_order: VecDeque<B256>,
 }
 
 impl EvmExecutor {
     /// Create a new EVM executor
     pub fn new(storage: EvmStorage) -> Self {
+        let commitment = StateCommitment::load(&storage).unwrap_or_else(|e| {
+            log::error!("Failed to load the EVM state commitment: {}", e);
+            StateCommitment::default()
+        });
         Self {
             cache: Arc::new(RwLock::new(CacheDB::new(storage))),
             block_number: 0,
             block_timestamp: 0,
             precompiles: HashMap::new(),
+            collateral_bridge: None,
+            price_oracle: None,
+            vaults: None,
+            randomness: RandomnessBeacon::new(),
+            coinbase: Address::ZERO,
+            fee_market: FeeMarket::default(),
+            commitment,
+            dirty: BTreeMap::new(),
+            snapshots: Vec::new(),
+            tracing: None,
+            traces: HashMap::new(),
+            trace_order: VecDeque::new(),
         }
     }
 
+    /// Storage the executor reads and commits state to
+    pub fn storage(&self) -> EvmStorage {
+        self.cache.read().unwrap().db.clone()
+    }
+
+    /// Attach the collateral bridge serving `COLLATERAL_PRECOMPILE`
+    pub fn set_collateral_bridge(&mut self, bridge: CollateralBridge) {
+        self.collateral_bridge = Some(bridge);
+    }
+
+    /// Get the collateral bridge
+    pub fn collateral_bridge(&self) -> Option<&CollateralBridge> {
+        self.collateral_bridge.as_ref()
+    }
+
+    /// Attach the price oracle serving `ORACLE_PRECOMPILE`
+    pub fn set_price_oracle(&mut self, oracle: PriceOracle) {
+        self.price_oracle = Some(oracle);
+    }
+
+    /// Get the price oracle
+    pub fn price_oracle_mut(&mut self) -> Option<&mut PriceOracle> {
+        self.price_oracle.as_mut()
+    }
+
+    /// Attach the vaults serving `VAULT_PRECOMPILE`, to transactions and
+    /// contracts alike
+    pub fn set_vaults(&mut self, vaults: VaultPrecompile) {
+        self.vaults = Some(Arc::new(Mutex::new(vaults)));
+    }
+
+    /// Check bridge escrow and ledger invariants
+    pub fn check_collateral_invariants(&self) -> Result<()> {
+        match &self.collateral_bridge {
+            Some(bridge) => bridge.check_invariants(&mut self.cache.write().unwrap()),
+            None => Ok(()),
+        }
+    }
+
+    /// Settle queued bridge withdrawals whose delay has passed by
+    /// `finalized_height`
+    pub fn settle_withdrawals(&mut self, finalized_height: u64) -> Result<Option<WithdrawalBatch>> {
+        let Some(bridge) = &mut self.collateral_bridge else {
+            return Ok(None);
+        };
+        let batch = bridge.settle_withdrawals(&mut self.cache.write().unwrap(), finalized_height)?;
+        for withdrawal in &batch.settled {
+            self.dirty.entry(withdrawal.user).or_default();
+        }
+        self.dirty.entry(COLLATERAL_PRECOMPILE).or_default();
+        Ok(Some(batch))
+    }
+
     /// Set the current block context
     pub fn set_block_context(&mut self, number: u64, timestamp: u64) {
         self.block_number = number;
         self.block_timestamp = timestamp;
     }
 
+    /// Set the account the current block's priority fees are paid to
+    pub fn set_block_coinbase(&mut self, coinbase: Address) {
+        self.coinbase = coinbase;
+    }
+
     /// Get the current block number
     pub fn block_number(&self) -> u64 {
         self.block_number
     }
 
+    /// Replace the fee market parameters, restarting from their initial
+    /// base fee
+    pub fn set_fee_market_config(&mut self, config: FeeMarketConfig) {
+        self.fee_market = FeeMarket::new(config);
+    }
+
+    /// Get the fee market
+    pub fn fee_market(&self) -> &FeeMarket {
+        &self.fee_market
+    }
+
+    /// Base fee of the current block
+    pub fn base_fee(&self) -> U256 {
+        self.fee_market.base_fee()
+    }
+
+    /// Finish a block that used `gas_used` gas, returning the next block's
+    /// base fee
+    pub fn end_block(&mut self, gas_used: u64) -> U256 {
+        self.fee_market.end_block(gas_used)
+    }
+
+    /// Set the beacon randomness of the current block.
+    ///
+    /// Exposed to contracts through `RANDOMNESS_PRECOMPILE` and `PREVRANDAO`.
+    pub fn set_block_randomness(&mut self, randomness: B256) {
+        self.randomness.record(self.block_number, randomness);
+    }
+
+    /// Get the beacon randomness of a recent block
+    pub fn block_randomness(&self, number: u64) -> Option<B256> {
+        self.randomness.get(number)
+    }
+
+    /// Root committing to every account as of the last `commit_state`
+    pub fn state_root(&self) -> B256 {
+        self.commitment.root()
+    }
+
+    /// Proof of an account's leaf, or of its absence, against `state_root`
+    pub fn prove_account(&self, address: &Address) -> SparseMerkleProof {
+        self.commitment.prove(address)
+    }
+
+    /// Write the accounts and slots changed since the last call to storage
+    /// and fold them into the state tree, returning the new state root
+    ///
+    /// Only changed accounts are visited, and accounts merely read into the
+    /// cache are never committed, so the root is the same on every node.
+    pub fn commit_state(&mut self) -> Result<B256> {
+        let dirty = std::mem::take(&mut self.dirty);
+        let cache = self.cache.read().unwrap();
+        for (address, changes) in dirty {
+            let Some(account) = cache.accounts.get(&address) else {
+                continue;
+            };
+            let exists = account.account_state != AccountState::NotExisting;
+            if changes.storage_cleared || !exists {
+                for slot in self.commitment.clear_storage(&address) {
+                    cache.db.set_storage(&address, &slot, &U256::ZERO)?;
+                }
+            }
+            if !exists {
+                cache.db.delete_account(&address)?;
+                self.commitment.remove_account(&address);
+                continue;
+            }
+
+            let slots: Vec<U256> = if changes.storage_cleared {
+                account.storage.keys().copied().collect()
+            } else {
+                changes.slots.into_iter().collect()
+            };
+            for slot in slots {
+                let value = account.storage.get(&slot).copied().unwrap_or_default();
+                cache.db.set_storage(&address, &slot, &value)?;
+                self.commitment.set_slot(address, slot, value);
+            }
+
+            let info = &account.info;
+            let persisted_code = cache.db.get_account(&address)?.map(|persisted| persisted.code_hash);
+            if info.code_hash != revm::primitives::KECCAK_EMPTY && persisted_code != Some(info.code_hash) {
+                if let Some(code) = &info.code {
+                    cache.db.set_code(&address, &code.original_bytes())?;
+                }
+            }
+            let storage_root = self.commitment.set_account(address, info.nonce, info.balance, info.code_hash);
+            cache.db.set_account(&address, &Account {
+                nonce: info.nonce,
+                balance: info.balance,
+                code_hash: info.code_hash,
+                storage_root,
+            })?;
+        }
+        Ok(self.commitment.root())
+    }
+
+    /// Record the accounts and slots a committed EVM result changes
+    fn mark_dirty(&mut self, state: &revm::primitives::EvmState) {
+        for (address, account) in state.iter().filter(|(_, account)| account.is_touched()) {
+            let dirty = self.dirty.entry(*address).or_default();
+            if account.is_created() || account.is_selfdestructed() {
+                dirty.storage_cleared = true;
+            }
+            dirty.slots.extend(account.changed_storage_slots().map(|(slot, _)| *slot));
+        }
+    }
+
+    /// Trace committed transactions with `config` (or stop, with `None`),
+    /// keeping the latest `MAX_TRACES` for `trace`
+    pub fn set_tracing(&mut self, config: Option<TraceConfig>) {
+        self.tracing = config;
+        if config.is_none() {
+            self.traces.clear();
+            self.trace_order.clear();
+        }
+    }
+
+    /// Trace of a transaction committed while tracing was enabled
+    pub fn trace(&self, hash: &B256) -> Option<&TransactionTrace> {
+        self.traces.get(hash)
+    }
+
+    fn record_trace(&mut self, hash: B256, trace: TransactionTrace) {
+        if self.traces.insert(hash, trace).is_none() {
+            self.trace_order.push_back(hash);
+        }
+        while self.trace_order.len() > MAX_TRACES {
+            if let Some(oldest) = self.trace_order.pop_front() {
+                self.traces.remove(&oldest);
+            }
+        }
+    }
+
+    /// Trace of a precompile call, which runs outside the EVM: one frame
+    fn precompile_trace(tx: &Transaction, receipt: &Receipt) -> TransactionTrace {
+        TransactionTrace {
+            gas: receipt.gas_used,
+            failed: !receipt.success,
+            return_value: receipt.output.clone(),
+            call: CallFrame {
+                kind: "CALL".to_string(),
+                from: tx.from,
+                to: tx.to,
+                value: tx.value,
+                gas: U64::from(tx.gas_limit),
+                gas_used: U64::from(receipt.gas_used),
+                input: tx.data.clone(),
+                output: receipt.output.clone(),
+                ..Default::default()
+            },
+            struct_logs: Vec::new(),
+        }
+    }
+
+    /// Run an environment through the EVM under a tracer, without applying
+    /// its changes; vault changes are refused unless `writable`
+    fn inspect(&self, env: Env, config: TraceConfig, writable: bool) -> Result<(ResultAndState, Tracer)> {
+        let mut cache = self.cache.write().unwrap();
+        let mut evm = Evm::builder()
+            .with_db(&mut *cache)
+            .with_external_context(Tracer::new(config))
+            .with_env(Box::new(env))
+            .append_handler_register_box(self.vault_register(writable))
+            .append_handler_register(inspector_handle_register)
+            .build();
+        let result = evm.transact().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        Ok((result, std::mem::take(&mut evm.context.external)))
+    }
+
     /// Execute a transaction and return the result
     pub fn execute_transaction(&mut self, tx: &Transaction) -> Result<Receipt> {
         // Check if this is a precompile call
@@ -61,39 +381,59 @@ impl EvmExecutor {
             }
         }
 
+        let result = self.transact(tx, false)?;
+
+        // Process the result and build a receipt
+        self.build_receipt(tx, result.result)
+    }
+
+    /// Reject out-of-order nonces before touching the EVM
+    fn check_nonce(&self, tx: &Transaction) -> Result<()> {
+        let expected = self.get_nonce(&tx.from)?;
+        if tx.nonce != expected {
+            let kind = if tx.nonce < expected { "low" } else { "high" };
+            return Err(anyhow!("Nonce too {}: expected {}, got {}", kind, expected, tx.nonce));
+        }
+        Ok(())
+    }
+
+    /// Run a transaction through the EVM without applying its changes;
+    /// vault changes are refused unless `writable`
+    fn transact(&self, tx: &Transaction, writable: bool) -> Result<ResultAndState> {
+        self.check_nonce(tx)?;
+
         // Build the EVM environment
         let env = self.build_env(tx);
 
-        // Execute the transaction
-        let result = {
-            let mut cache = self.cache.write().unwrap();
-            let mut evm = Evm::builder()
-                .with_db(&mut *cache)
-                .with_env(Box::new(env.clone()))
-                .build();
+        let mut cache = self.cache.write().unwrap();
+        let mut evm = Evm::builder()
+            .with_db(&mut *cache)
+            .with_env(Box::new(env))
+            .append_handler_register_box(self.vault_register(writable))
+            .build();
 
-            evm.transact().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?
-        };
+        evm.transact().map_err(|e| anyhow!("EVM execution failed: {:?}", e))
+    }
 
-        // Process the result and build a receipt
-        self.build_receipt(tx, result)
+    /// Handler register serving contract calls to the vaults, if attached
+    fn vault_register<EXT, DB: Database>(&self, writable: bool) -> HandleRegisterBox<'static, EXT, DB> {
+        match &self.vaults {
+            Some(vaults) => vault::handle_register(vaults.clone(), self.block_timestamp, writable),
+            None => Box::new(|_| {}),
+        }
     }
 
     /// Execute a precompile call
+    ///
+    /// Like EVM transactions, the call must carry the sender's next nonce
+    /// and pays for its gas limit up front; the nonce is used and the gas
+    /// paid even if the call fails, and unused gas is refunded.
     fn execute_precompile(&mut self, tx: &Transaction, precompile_addr: Address) -> Result<Receipt> {
-        // Get or create the precompile instance
-        if !self.precompiles.contains_key(&precompile_addr) {
-            let precompile = get_precompile(&precompile_addr)
-                .ok_or_else(|| anyhow!("Precompile not found"))?;
-            self.precompiles.insert(precompile_addr, precompile);
-        }
-
-        let precompile = self.precompiles.get_mut(&precompile_addr).unwrap();
-
-        // Execute the precompile
-        let (output, gas_used) = precompile
-            .call(&tx.data, tx.gas_limit, tx.from)
-            .map_err(|e| anyhow!("Precompile execution failed: {}", e))?;
+        let price = self.buy_gas(tx)?;
+        let result = self.call_precompile(tx, precompile_addr);
+        let gas_used = result.as_ref().map_or(tx.gas_limit, |(_, gas_used, _)| (*gas_used).min(tx.gas_limit));
+        self.settle_gas(tx, price, gas_used)?;
+        let (output, gas_used, logs) = result?;
 
         // Build receipt
         Ok(Receipt {
@@ -104,48 +444,527 @@ impl EvmExecutor {
             gas_used,
             success: true,
             output,
-            logs: Vec::new(),
+            logs,
         })
     }
 
+    /// Check a precompile call's nonce, use it and charge its gas limit,
+    /// returning the price paid per gas
+    fn buy_gas(&mut self, tx: &Transaction) -> Result<U256> {
+        self.check_nonce(tx)?;
+        let base_fee = self.fee_market.base_fee();
+        let price = match tx.effective_gas_price(base_fee) {
+            Some(price) => price,
+            // Cancellations admitted to the mempool's gas-free lane
+            None if tx.max_fee().is_zero() && is_cancel_only(tx) => U256::ZERO,
+            None => return Err(anyhow!("Max fee per gas below the base fee {}", base_fee)),
+        };
+        let cost = price * U256::from(tx.gas_limit);
+
+        self.dirty.entry(tx.from).or_default();
+        let mut cache = self.cache.write().unwrap();
+        let account = cache.load_account(tx.from)?;
+        if account.info.balance < cost {
+            return Err(anyhow!(
+                "Insufficient balance for gas: need {}, have {}",
+                cost,
+                account.info.balance
+            ));
+        }
+        account.info.balance -= cost;
+        account.info.nonce += 1;
+        if account.account_state == AccountState::NotExisting {
+            account.account_state = AccountState::Touched;
+        }
+        Ok(price)
+    }
+
+    /// Refund the gas a precompile call did not use, and pay the tip and
+    /// base fee of the rest as the EVM does
+    fn settle_gas(&mut self, tx: &Transaction, price: U256, gas_used: u64) -> Result<()> {
+        let refund = price * U256::from(tx.gas_limit - gas_used);
+        self.credit(tx.from, refund)?;
+        let base_fee = self.fee_market.base_fee();
+        if price < base_fee {
+            // Gas-free
+            return Ok(());
+        }
+        self.credit(self.coinbase, (price - base_fee) * U256::from(gas_used))?;
+        if let Some((fund, amount)) = self.fee_market.collect(gas_used) {
+            self.credit(fund, amount)?;
+        }
+        Ok(())
+    }
+
+    /// Add to an account's balance in the cache
+    fn credit(&mut self, address: Address, amount: U256) -> Result<()> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        self.dirty.entry(address).or_default();
+        let mut cache = self.cache.write().unwrap();
+        let account = cache.load_account(address)?;
+        account.info.balance += amount;
+        if account.account_state == AccountState::NotExisting {
+            account.account_state = AccountState::Touched;
+        }
+        Ok(())
+    }
+
+    /// Dispatch a precompile call, returning its output, gas used and logs
+    fn call_precompile(&mut self, tx: &Transaction, precompile_addr: Address) -> Result<(Bytes, u64, Vec<Log>)> {
+        let mut logs = Vec::new();
+        if precompile_addr == COLLATERAL_PRECOMPILE || precompile_addr == MARGIN_PRECOMPILE {
+            // The bridge moves the caller's and the escrow's EVM balances
+            self.dirty.entry(tx.from).or_default();
+            self.dirty.entry(COLLATERAL_PRECOMPILE).or_default();
+        }
+        let (output, gas_used) = if precompile_addr == COLLATERAL_PRECOMPILE {
+            // The bridge moves EVM balances, so it runs against the cache
+            let bridge = self
+                .collateral_bridge
+                .as_mut()
+                .ok_or_else(|| anyhow!("Collateral bridge not attached"))?;
+            let mut cache = self.cache.write().unwrap();
+            bridge
+                .call(&mut cache, &tx.data, tx.gas_limit, tx.from, self.block_number)
+                .map_err(|e| anyhow!("Precompile execution failed: {}", e))?
+        } else if precompile_addr == MARGIN_PRECOMPILE {
+            // Collateral moves through the bridge's escrow
+            let bridge = self
+                .collateral_bridge
+                .as_mut()
+                .ok_or_else(|| anyhow!("Collateral bridge not attached"))?;
+            let mut cache = self.cache.write().unwrap();
+            margin::call(bridge, &mut cache, &tx.data, tx.gas_limit, tx.from, self.block_number)
+                .map_err(|e| anyhow!("Precompile execution failed: {}", e))?
+        } else if precompile_addr == ORACLE_PRECOMPILE {
+            let oracle = self
+                .price_oracle
+                .as_mut()
+                .ok_or_else(|| anyhow!("Price oracle not attached"))?;
+            let result = oracle
+                .call(&tx.data, tx.gas_limit, tx.from, self.block_timestamp)
+                .map_err(|e| anyhow!("Precompile execution failed: {}", e))?;
+            logs = oracle.take_logs();
+            result
+        } else if precompile_addr == VAULT_PRECOMPILE {
+            let mut vaults = self
+                .vaults
+                .as_ref()
+                .ok_or_else(|| anyhow!("Vaults not attached"))?
+                .lock()
+                .unwrap();
+            let result = vaults
+                .call(&tx.data, tx.gas_limit, tx.from, self.block_timestamp)
+                .map_err(|e| anyhow!("Precompile execution failed: {}", e))?;
+            logs = vaults.take_logs();
+            result
+        } else if precompile_addr == RANDOMNESS_PRECOMPILE {
+            self.randomness
+                .call(&tx.data, tx.gas_limit, self.block_number)
+                .map_err(|e| anyhow!("Precompile execution failed: {}", e))?
+        } else {
+            // Get or create the precompile instance
+            if !self.precompiles.contains_key(&precompile_addr) {
+                let precompile = get_precompile(&precompile_addr)
+                    .ok_or_else(|| anyhow!("Precompile not found"))?;
+                self.precompiles.insert(precompile_addr, precompile);
+            }
+
+            let precompile = self.precompiles.get_mut(&precompile_addr).unwrap();
+
+            // Execute the precompile
+            let result = precompile
+                .call(&tx.data, tx.gas_limit, tx.from)
+                .map_err(|e| anyhow!("Precompile execution failed: {}", e))?;
+            logs = precompile.take_logs();
+            result
+        };
+        Ok((output, gas_used, logs))
+    }
+
     /// Execute a transaction and commit state changes
     pub fn execute_and_commit(&mut self, tx: &Transaction) -> Result<Receipt> {
-        let receipt = self.execute_transaction(tx)?;
+        if let Some(to) = tx.to.filter(is_precompile) {
+            let receipt = self.execute_precompile(tx, to)?;
+            if self.tracing.is_some() {
+                self.record_trace(receipt.transaction_hash, Self::precompile_trace(tx, &receipt));
+            }
+            return Ok(receipt);
+        }
 
-        // Commit changes if successful
-        if receipt.success {
-            self.commit_transaction()?;
+        // Contracts' vault calls change core directly, so they are undone
+        // here if the transaction fails
+        let checkpoint = self.vaults.as_ref().map(|vaults| vaults.lock().unwrap().snapshot());
+        let executed = self.run_committed(tx);
+        if let (Some(vaults), Some(checkpoint)) = (&self.vaults, checkpoint) {
+            let mut vaults = vaults.lock().unwrap();
+            match &executed {
+                Ok((result, _)) if result.result.is_success() => vaults.discard_snapshot(checkpoint),
+                _ => vaults.revert_to(checkpoint)?,
+            }
         }
 
+        // Reverted transactions still consume their nonce and pay for gas
+        let (ResultAndState { result, state }, trace) = executed?;
+        self.mark_dirty(&state);
+        let mut cache = self.cache.write().unwrap();
+        cache.commit(state);
+
+        drop(cache);
+
+        // The EVM pays the tip to the block producer and burns the base fee
+        if let Some((fund, amount)) = self.fee_market.collect(result.gas_used()) {
+            self.credit(fund, amount)?;
+        }
+
+        let receipt = self.build_receipt(tx, result)?;
+        if let Some(trace) = trace {
+            self.record_trace(receipt.transaction_hash, trace);
+        }
         Ok(receipt)
     }
 
+    /// Run a transaction whose changes will be committed, tracing it if
+    /// tracing is enabled
+    fn run_committed(&self, tx: &Transaction) -> Result<(ResultAndState, Option<TransactionTrace>)> {
+        match self.tracing {
+            Some(config) => {
+                self.check_nonce(tx)?;
+                let (result, tracer) = self.inspect(self.build_env(tx), config, true)?;
+                let trace = tracer.into_trace(tx.gas_limit, result.result.gas_used());
+                Ok((result, Some(trace)))
+            }
+            None => Ok((self.transact(tx, true)?, None)),
+        }
+    }
+
+    /// Load the accounts a batch of transactions touches into the storage
+    /// cache with one batched read
+    pub fn prefetch(&self, transactions: &[Transaction]) -> Result<()> {
+        let accounts: Vec<Address> = transactions
+            .iter()
+            .flat_map(|tx| std::iter::once(tx.from).chain(tx.to))
+            .filter(|address| !is_precompile(address))
+            .collect();
+        self.cache.read().unwrap().db.prefetch(&accounts, &[])
+    }
+
     /// Execute multiple transactions in a batch
     pub fn execute_batch(&mut self, transactions: &[Transaction]) -> Result<Vec<Receipt>> {
+        self.prefetch(transactions)?;
         let mut receipts = Vec::new();
 
         for tx in transactions {
             match self.execute_and_commit(tx) {
                 Ok(receipt) => receipts.push(receipt),
-                Err(e) => {
-                    // On error, include a failed receipt
-                    receipts.push(Receipt {
-                        transaction_hash: self.compute_tx_hash(tx),
-                        from: tx.from,
-                        to: tx.to,
-                        contract_address: None,
-                        gas_used: tx.gas_limit,
-                        success: false,
-                        output: Bytes::from(format!("Error: {}", e)),
-                        logs: Vec::new(),
-                    });
-                }
+                // On error, include a failed receipt
+                Err(e) => receipts.push(self.failed_receipt(tx, &e)),
             }
         }
 
         Ok(receipts)
     }
 
+    /// Simulate an ordered bundle of transactions against the current state
+    ///
+    /// Each transaction sees the effects of those before it, as in a block,
+    /// but the bundle runs on a copy of the cached state and of the orderbook
+    /// precompiles, so nothing is committed. A transaction that cannot be
+    /// executed gets a failed receipt and the bundle continues. Collateral
+    /// bridge, margin, oracle and vault calls touch core state, which cannot
+    /// be copied, so they are refused rather than simulated.
+    pub fn simulate_bundle(&self, transactions: &[Transaction]) -> Result<BundleSimulation> {
+        self.prefetch(transactions)?;
+        let mut fork = self.fork();
+
+        let results: Vec<SimulatedTransaction> = transactions
+            .iter()
+            .map(|tx| match fork.simulate_transaction(tx) {
+                Ok((receipt, state_diff)) => SimulatedTransaction {
+                    receipt,
+                    error: None,
+                    state_diff,
+                },
+                Err(e) => SimulatedTransaction {
+                    receipt: self.failed_receipt(tx, &e),
+                    error: Some(e.to_string()),
+                    state_diff: StateDiff::new(),
+                },
+            })
+            .collect();
+
+        Ok(BundleSimulation {
+            block_number: self.block_number,
+            gas_used: results.iter().map(|r| r.receipt.gas_used).sum(),
+            results,
+        })
+    }
+
+    /// Copy of this executor whose changes are never written back
+    fn fork(&self) -> Self {
+        Self {
+            cache: Arc::new(RwLock::new(self.cache.read().unwrap().clone())),
+            block_number: self.block_number,
+            block_timestamp: self.block_timestamp,
+            precompiles: self
+                .precompiles
+                .iter()
+                .map(|(address, precompile)| (*address, precompile.fork()))
+                .collect(),
+            collateral_bridge: None,
+            price_oracle: None,
+            vaults: None,
+            randomness: self.randomness.clone(),
+            fee_market: self.fee_market.clone(),
+            commitment: self.commitment.clone(),
+            dirty: BTreeMap::new(),
+            snapshots: Vec::new(),
+            tracing: None,
+            traces: HashMap::new(),
+            trace_order: VecDeque::new(),
+        }
+    }
+
+    /// Core state machines behind the attached precompiles, each once
+    fn cores(&self) -> Vec<Arc<RwLock<CoreStateMachine>>> {
+        let mut cores: Vec<Arc<RwLock<CoreStateMachine>>> = Vec::new();
+        let attached = [
+            self.collateral_bridge.as_ref().map(|bridge| bridge.core().clone()),
+            self.price_oracle.as_ref().map(|oracle| oracle.core().clone()),
+            self.vaults.as_ref().map(|vaults| vaults.lock().unwrap().core().clone()),
+        ];
+        for core in attached.into_iter().flatten() {
+            if !cores.iter().any(|known| Arc::ptr_eq(known, &core)) {
+                cores.push(core);
+            }
+        }
+        cores
+    }
+
+    /// Open a snapshot of the EVM state that `revert_to` can roll back to,
+    /// e.g. before executing a block; snapshots nest, and the id is the
+    /// nesting depth
+    ///
+    /// Covers the cached accounts, the orderbook precompiles, block context,
+    /// randomness and fee market, writes persisted through the storage, the
+    /// collateral bridge's and vaults' books, and the core state behind the
+    /// bridge, margin, oracle and vault precompiles.
+    pub fn snapshot(&mut self) -> usize {
+        let cores = self
+            .cores()
+            .into_iter()
+            .map(|core| {
+                let checkpoint = core.read().unwrap().checkpoint();
+                (core, checkpoint)
+            })
+            .collect();
+        let cache = self.cache.read().unwrap();
+        self.snapshots.push(ExecutorSnapshot {
+            cache: cache.clone(),
+            storage: cache.db.snapshot(),
+            block_number: self.block_number,
+            block_timestamp: self.block_timestamp,
+            precompiles: self
+                .precompiles
+                .iter()
+                .map(|(address, precompile)| (*address, precompile.fork()))
+                .collect(),
+            randomness: self.randomness.clone(),
+            fee_market: self.fee_market.clone(),
+            bridge: self.collateral_bridge.as_ref().map(CollateralBridge::snapshot),
+            vaults: self.vaults.as_ref().map(|vaults| vaults.lock().unwrap().snapshot()),
+            cores,
+            commitment: self.commitment.clone(),
+            dirty: self.dirty.clone(),
+        });
+        self.snapshots.len() - 1
+    }
+
+    /// Restore the state captured by snapshot `id`, closing it and the
+    /// snapshots opened after it
+    ///
+    /// Every backend is restored even if another fails, so the EVM and core
+    /// never stay part-way through the reverted changes; a failure is still
+    /// reported, and the caller must treat its block as aborted.
+    pub fn revert_to(&mut self, id: usize) -> Result<()> {
+        if id >= self.snapshots.len() {
+            return Err(anyhow!("Unknown snapshot {}", id));
+        }
+        let snapshot = self.snapshots.drain(id..).next().unwrap();
+        let mut failures = Vec::new();
+        if let Err(e) = snapshot.cache.db.revert_to(snapshot.storage) {
+            failures.push(format!("storage: {}", e));
+        }
+        for (core, checkpoint) in snapshot.cores {
+            if let Err(e) = core.write().unwrap().restore_checkpoint(checkpoint) {
+                failures.push(format!("core: {}", e));
+            }
+        }
+        // Their core changes were restored with the core checkpoints
+        if let (Some(bridge), Some(saved)) = (&mut self.collateral_bridge, &snapshot.bridge) {
+            bridge.restore(saved);
+        }
+        if let (Some(vaults), Some(checkpoint)) = (&self.vaults, snapshot.vaults) {
+            vaults.lock().unwrap().reset_to(checkpoint);
+        }
+        *self.cache.write().unwrap() = snapshot.cache;
+        self.block_number = snapshot.block_number;
+        self.block_timestamp = snapshot.block_timestamp;
+        self.precompiles = snapshot.precompiles;
+        self.randomness = snapshot.randomness;
+        self.fee_market = snapshot.fee_market;
+        self.commitment = snapshot.commitment;
+        self.dirty = snapshot.dirty;
+        if !failures.is_empty() {
+            return Err(anyhow!("Snapshot {} restored with failures: {}", id, failures.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// Keep the changes made since snapshot `id`, closing it and the
+    /// snapshots opened after it
+    pub fn discard_snapshot(&mut self, id: usize) {
+        if let Some(snapshot) = self.snapshots.drain(id..).next() {
+            snapshot.cache.db.discard_snapshot(snapshot.storage);
+            if let (Some(vaults), Some(checkpoint)) = (&self.vaults, snapshot.vaults) {
+                vaults.lock().unwrap().discard_snapshot(checkpoint);
+            }
+        }
+    }
+
+    /// Execute a transaction, apply its changes to the cache and return
+    /// them as a diff
+    fn simulate_transaction(&mut self, tx: &Transaction) -> Result<(Receipt, StateDiff)> {
+        if let Some(to) = tx.to.filter(is_precompile) {
+            if to == COLLATERAL_PRECOMPILE || to == MARGIN_PRECOMPILE {
+                return Err(anyhow!("Collateral bridge calls cannot be simulated"));
+            }
+            if to == ORACLE_PRECOMPILE || to == VAULT_PRECOMPILE {
+                return Err(anyhow!("Oracle and vault calls cannot be simulated"));
+            }
+            // The remaining precompiles keep no EVM state
+            return Ok((self.execute_precompile(tx, to)?, StateDiff::new()));
+        }
+
+        let env = self.build_env(tx);
+        let mut cache = self.cache.write().unwrap();
+        let ResultAndState { result, state } = Evm::builder()
+            .with_db(&mut *cache)
+            .with_env(Box::new(env))
+            .build()
+            .transact()
+            .map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+
+        // The cache still holds the pre-transaction values
+        let mut diff = StateDiff::new();
+        for (address, account) in state.iter().filter(|(_, account)| account.is_touched()) {
+            let before = cache.basic(*address)?.unwrap_or_default();
+            let mut account_diff = AccountDiff::default();
+            if before.balance != account.info.balance {
+                account_diff.balance = Some((before.balance, account.info.balance));
+            }
+            if before.nonce != account.info.nonce {
+                account_diff.nonce = Some((before.nonce, account.info.nonce));
+            }
+            if before.code_hash != account.info.code_hash {
+                account_diff.code = account.info.code.as_ref().map(|code| code.original_bytes());
+            }
+            for (slot, value) in account.changed_storage_slots() {
+                account_diff.storage.insert(*slot, (value.original_value, value.present_value));
+            }
+            if !account_diff.is_empty() {
+                diff.insert(*address, account_diff);
+            }
+        }
+        cache.commit(state);
+        drop(cache);
+
+        Ok((self.build_receipt(tx, result)?, diff))
+    }
+
+    /// Execute a transaction against the current state without committing
+    /// anything, as `eth_call` does
+    ///
+    /// The nonce is not checked, and a zero gas price skips the base fee so
+    /// unfunded senders can call views. The EVM's changes are discarded
+    /// rather than applied to the cache; precompile calls run on a fork, and
+    /// those touching core state are refused as in `simulate_bundle`.
+    pub fn call(&self, tx: &Transaction) -> Result<Receipt> {
+        if tx.to.is_some_and(|to| is_precompile(&to)) {
+            return self.fork().simulate_transaction(tx).map(|(receipt, _)| receipt);
+        }
+
+        let env = self.call_env(tx);
+        let mut cache = self.cache.write().unwrap();
+        let ResultAndState { result, .. } = Evm::builder()
+            .with_db(&mut *cache)
+            .with_env(Box::new(env))
+            .append_handler_register_box(self.vault_register(false))
+            .build()
+            .transact()
+            .map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        drop(cache);
+
+        self.build_receipt(tx, result)
+    }
+
+    /// Trace a transaction as `call` would execute it, for debugging
+    pub fn trace_call(&self, tx: &Transaction, config: TraceConfig) -> Result<TransactionTrace> {
+        if tx.to.is_some_and(|to| is_precompile(&to)) {
+            return Ok(Self::precompile_trace(tx, &self.call(tx)?));
+        }
+        let (result, tracer) = self.inspect(self.call_env(tx), config, false)?;
+        Ok(tracer.into_trace(tx.gas_limit, result.result.gas_used()))
+    }
+
+    /// Environment of a `call`: any nonce, and no base fee at zero gas price
+    fn call_env(&self, tx: &Transaction) -> Env {
+        let mut env = self.build_env(tx);
+        env.tx.nonce = None;
+        if tx.max_fee().is_zero() {
+            env.block.basefee = U256::ZERO;
+        }
+        env
+    }
+
+    /// Smallest gas limit, up to the transaction's own (or the block's),
+    /// at which `call` succeeds
+    ///
+    /// Gas used is a lower bound but not always enough (refunds, the 63/64
+    /// rule, SSTORE's stipend check), so the limit is binary-searched between
+    /// it and the cap.
+    pub fn estimate_gas(&self, tx: &Transaction) -> Result<u64> {
+        let cap = match tx.gas_limit {
+            0 => BLOCK_GAS_LIMIT,
+            limit => limit.min(BLOCK_GAS_LIMIT),
+        };
+        let mut tx = tx.clone();
+        tx.gas_limit = cap;
+        let receipt = self.call(&tx)?;
+        if !receipt.success {
+            return Err(anyhow!("Execution reverted at gas limit {}: {}", cap, receipt.output));
+        }
+        // Precompiles charge fixed costs
+        if tx.to.is_some_and(|to| is_precompile(&to)) {
+            return Ok(receipt.gas_used);
+        }
+
+        // `low` always fails (or is below intrinsic gas), `high` succeeds
+        let mut low = receipt.gas_used.saturating_sub(1);
+        let mut high = cap;
+        while low + 1 < high {
+            let mid = low + (high - low) / 2;
+            tx.gas_limit = mid;
+            match self.call(&tx) {
+                Ok(receipt) if receipt.success => high = mid,
+                _ => low = mid,
+            }
+        }
+        Ok(high)
+    }
+
     /// Commit the current cached state to storage
     pub fn commit_transaction(&mut self) -> Result<()> {
         // The CacheDB automatically manages state changes
@@ -206,6 +1025,8 @@ impl EvmExecutor {
             code_hash: revm::primitives::KECCAK_EMPTY,
             code: None,
         });
+        drop(cache);
+        self.dirty.entry(address).or_default();
         
         Ok(())
     }
@@ -246,8 +1067,12 @@ impl EvmExecutor {
         // Set block context
         env.block.number = U256::from(self.block_number);
         env.block.timestamp = U256::from(self.block_timestamp);
-        env.block.gas_limit = U256::from(30_000_000u64); // 30M gas per block
-        env.block.basefee = tx.gas_price;
+        env.block.gas_limit = U256::from(BLOCK_GAS_LIMIT);
+        env.block.basefee = self.fee_market.base_fee();
+        env.block.coinbase = self.coinbase;
+        if let Some(randomness) = self.randomness.get(self.block_number) {
+            env.block.prevrandao = Some(randomness);
+        }
 
         // Set transaction context
         env.tx.caller = tx.from;
@@ -258,7 +1083,8 @@ impl EvmExecutor {
         env.tx.value = tx.value;
         env.tx.data = tx.data.clone();
         env.tx.gas_limit = tx.gas_limit;
-        env.tx.gas_price = tx.gas_price;
+        env.tx.gas_price = tx.max_fee();
+        env.tx.gas_priority_fee = tx.max_priority_fee_per_gas;
         env.tx.nonce = Some(tx.nonce);
         env.tx.chain_id = Some(tx.chain_id);
 
@@ -266,48 +1092,36 @@ impl EvmExecutor {
     }
 
     /// Build a receipt from execution result
-    fn build_receipt(&self, tx: &Transaction, result: ResultAndState) -> Result<Receipt> {
+    fn build_receipt(&self, tx: &Transaction, result: ExecutionResult) -> Result<Receipt> {
         let tx_hash = self.compute_tx_hash(tx);
 
-        let (success, output, gas_used, contract_address) = match result.result {
+        let (success, output, gas_used, contract_address, logs) = match result {
             ExecutionResult::Success {
                 output,
                 gas_used,
                 logs,
                 ..
             } => {
-                let output_bytes = match output {
-                    Output::Create(bytes, addr) => {
-                        // Contract creation
-                        return Ok(Receipt {
-                            transaction_hash: tx_hash,
-                            from: tx.from,
-                            to: tx.to,
-                            contract_address: addr,
-                            gas_used,
-                            success: true,
-                            output: bytes,
-                            logs: logs
-                                .into_iter()
-                                .map(|log| crate::types::Log {
-                                    address: log.address,
-                                    topics: log.data.topics().to_vec(),
-                                    data: Bytes::from(log.data.data.to_vec()),
-                                })
-                                .collect(),
-                        });
-                    }
-                    Output::Call(bytes) => bytes,
-                };
-
-                (true, output_bytes, gas_used, None)
+                let logs = logs
+                    .into_iter()
+                    .map(|log| crate::types::Log {
+                        address: log.address,
+                        topics: log.data.topics().to_vec(),
+                        data: Bytes::from(log.data.data.to_vec()),
+                    })
+                    .collect();
+                match output {
+                    // Contract creation
+                    Output::Create(bytes, addr) => (true, bytes, gas_used, addr, logs),
+                    Output::Call(bytes) => (true, bytes, gas_used, None, logs),
+                }
             }
             ExecutionResult::Revert { output, gas_used } => {
-                (false, output, gas_used, None)
+                (false, output, gas_used, None, Vec::new())
             }
             ExecutionResult::Halt { reason, gas_used } => {
                 let error_msg = format!("Halt: {:?}", reason);
-                (false, Bytes::from(error_msg), gas_used, None)
+                (false, Bytes::from(error_msg), gas_used, None, Vec::new())
             }
         };
 
@@ -319,24 +1133,27 @@ impl EvmExecutor {
             gas_used,
             success,
             output,
-            logs: Vec::new(),
+            logs,
         })
     }
 
-    /// Compute transaction hash (simplified)
-    fn compute_tx_hash(&self, tx: &Transaction) -> B256 {
-        use alloy_primitives::keccak256;
-
-        let mut data = Vec::new();
-        data.extend_from_slice(tx.from.as_slice());
-        if let Some(to) = tx.to {
-            data.extend_from_slice(to.as_slice());
+    /// Receipt for a transaction that could not be executed
+    fn failed_receipt(&self, tx: &Transaction, error: &anyhow::Error) -> Receipt {
+        Receipt {
+            transaction_hash: self.compute_tx_hash(tx),
+            from: tx.from,
+            to: tx.to,
+            contract_address: None,
+            gas_used: tx.gas_limit,
+            success: false,
+            output: Bytes::from(format!("Error: {}", error)),
+            logs: Vec::new(),
         }
-        data.extend_from_slice(&tx.nonce.to_le_bytes());
-        data.extend_from_slice(&tx.value.to_be_bytes::<32>());
-        data.extend_from_slice(&tx.data);
+    }
 
-        keccak256(&data)
+    /// Compute transaction hash
+    fn compute_tx_hash(&self, tx: &Transaction) -> B256 {
+        tx.hash()
     }
 }
 
@@ -354,6 +1171,13 @@ mod tests {
         (executor, temp_dir)
     }
 
+    /// Precompile call with the sender's next nonce and no gas price
+    fn precompile_tx(executor: &EvmExecutor, from: Address, to: Address, data: impl Into<Bytes>) -> Transaction {
+        let mut tx = Transaction::call(from, to, data.into(), executor.get_nonce(&from).unwrap());
+        tx.gas_price = U256::ZERO;
+        tx
+    }
+
     #[test]
     fn test_executor_creation() {
         let (executor, _temp) = create_test_executor();
@@ -423,20 +1247,115 @@ mod tests {
         // Fund sender with enough for multiple transfers + gas
         executor.create_account(sender, U256::from(10_000_000)).unwrap();
 
-        // Create multiple transactions with same nonce 0 for simplicity
-        // In reality, nonces would be managed by the state
         let tx1 = Transaction::transfer(sender, receiver1, U256::from(100), 0);
-        let tx2 = Transaction::transfer(sender, receiver2, U256::from(200), 0);
+        let tx2 = Transaction::transfer(sender, receiver2, U256::from(200), 1);
+        // Replays and skipped nonces are refused
+        let replay = Transaction::transfer(sender, receiver2, U256::from(200), 1);
+        let skipped = Transaction::transfer(sender, receiver2, U256::from(200), 5);
 
-        // Execute batch - note that both use nonce 0 since nonce isn't auto-incremented
-        // in our simple implementation yet
-        let receipts = executor.execute_batch(&[tx1, tx2]).unwrap();
+        let receipts = executor.execute_batch(&[tx1, tx2, replay, skipped]).unwrap();
 
-        assert_eq!(receipts.len(), 2);
-        // First should succeed
+        assert_eq!(receipts.len(), 4);
         assert!(receipts[0].success, "First transaction should succeed");
-        // Second might fail due to nonce issues in this simple implementation
-        // This is expected behavior for now
+        assert!(receipts[1].success, "Second transaction should succeed");
+        assert!(!receipts[2].success && !receipts[3].success);
+        assert_eq!(executor.get_nonce(&sender).unwrap(), 2);
+        assert_eq!(executor.get_balance(&receiver2).unwrap(), U256::from(200));
+    }
+
+    #[test]
+    fn test_fee_market_charges_base_fee_and_tip() {
+        let (mut executor, _temp) = create_test_executor();
+        let fund = Address::repeat_byte(0xaa);
+        executor.set_fee_market_config(FeeMarketConfig {
+            initial_base_fee: U256::from(10),
+            destination: crate::fee_market::FeeDestination::InsuranceFund(fund),
+            ..Default::default()
+        });
+
+        let sender = Address::repeat_byte(0x01);
+        let receiver = Address::repeat_byte(0x02);
+        let proposer = Address::repeat_byte(0xbb);
+        executor.create_account(sender, U256::from(10_000_000)).unwrap();
+        executor.set_block_coinbase(proposer);
+
+        // Pays the base fee of 10 plus a tip of 2 to the block's proposer
+        let tx = Transaction::transfer(sender, receiver, U256::from(100), 0)
+            .with_fees(U256::from(50), U256::from(2));
+        let receipt = executor.execute_and_commit(&tx).unwrap();
+        assert!(receipt.success);
+        assert_eq!(executor.get_balance(&sender).unwrap(), U256::from(10_000_000 - 100 - 21_000 * 12));
+        assert_eq!(executor.get_balance(&fund).unwrap(), U256::from(21_000 * 10));
+        assert_eq!(executor.get_balance(&proposer).unwrap(), U256::from(21_000 * 2));
+        assert_eq!(executor.get_balance(&Address::ZERO).unwrap(), U256::ZERO);
+
+        // Max fee below the base fee
+        let cheap = Transaction::transfer(sender, receiver, U256::from(100), 1)
+            .with_fees(U256::from(9), U256::from(9));
+        assert!(executor.execute_and_commit(&cheap).is_err());
+
+        // An empty block lowers the base fee
+        assert!(executor.end_block(0) < U256::from(10));
+    }
+
+    #[test]
+    fn test_precompile_calls_use_nonces_and_pay_for_gas() {
+        use crate::precompiles::spot::ISpot;
+        use crate::SPOT_PRECOMPILE;
+        use alloy_sol_types::{SolCall, SolValue};
+
+        let (mut executor, _temp) = create_test_executor();
+        let fund = Address::repeat_byte(0xaa);
+        executor.set_fee_market_config(FeeMarketConfig {
+            initial_base_fee: U256::from(10),
+            destination: crate::fee_market::FeeDestination::InsuranceFund(fund),
+            ..Default::default()
+        });
+        let trader = Address::repeat_byte(0x01);
+        let proposer = Address::repeat_byte(0xbb);
+        executor.create_account(trader, U256::from(100_000_000)).unwrap();
+        executor.set_block_coinbase(proposer);
+
+        let place = ISpot::placeOrderCall {
+            asset: Address::repeat_byte(0x02),
+            amount: U256::from(1000),
+            price: U256::from(100),
+            isBuy: true,
+        };
+        let tx = Transaction::call(trader, SPOT_PRECOMPILE, place.abi_encode().into(), 0)
+            .with_fees(U256::from(50), U256::from(2));
+        let receipt = executor.execute_and_commit(&tx).unwrap();
+        let gas_used = U256::from(receipt.gas_used);
+        let order_id = U256::abi_decode(&receipt.output, true).unwrap();
+        assert_eq!(executor.get_nonce(&trader).unwrap(), 1);
+        assert_eq!(executor.get_balance(&trader).unwrap(), U256::from(100_000_000) - gas_used * U256::from(12));
+        assert_eq!(executor.get_balance(&fund).unwrap(), gas_used * U256::from(10));
+        assert_eq!(executor.get_balance(&proposer).unwrap(), gas_used * U256::from(2));
+
+        // Replays are refused before the precompile runs
+        assert!(executor.execute_and_commit(&tx).is_err());
+        assert_eq!(executor.get_nonce(&trader).unwrap(), 1);
+
+        // A failing call still uses its nonce and pays for its whole gas limit
+        let balance = executor.get_balance(&trader).unwrap();
+        let bad = Transaction::call(trader, SPOT_PRECOMPILE, Bytes::from(vec![0u8; 4]), 1)
+            .with_fees(U256::from(50), U256::from(2));
+        assert!(executor.execute_and_commit(&bad).is_err());
+        assert_eq!(executor.get_nonce(&trader).unwrap(), 2);
+        assert_eq!(executor.get_balance(&trader).unwrap(), balance - U256::from(bad.gas_limit * 12));
+
+        // Unfunded senders cannot pay, but cancels may be gas-free
+        let outsider = Address::repeat_byte(0x03);
+        let tx = Transaction::call(outsider, SPOT_PRECOMPILE, place.abi_encode().into(), 0)
+            .with_fees(U256::from(50), U256::from(2));
+        assert!(executor.execute_and_commit(&tx).is_err());
+        assert_eq!(executor.get_nonce(&outsider).unwrap(), 0);
+        let cancel = ISpot::cancelOrderCall { orderId: order_id }.abi_encode();
+        let cancel = precompile_tx(&executor, trader, SPOT_PRECOMPILE, cancel);
+        let balance = executor.get_balance(&trader).unwrap();
+        assert!(executor.execute_and_commit(&cancel).unwrap().success);
+        assert_eq!(executor.get_balance(&trader).unwrap(), balance);
+        assert_eq!(executor.get_nonce(&trader).unwrap(), 3);
     }
 
     #[test]
@@ -457,6 +1376,212 @@ mod tests {
         assert_eq!(receipt.from, deployer);
     }
 
+    #[test]
+    fn test_simulate_bundle_without_committing() {
+        let (mut executor, _temp) = create_test_executor();
+        let sender = Address::repeat_byte(0x01);
+        executor.create_account(sender, U256::from(100_000_000)).unwrap();
+
+        // Runtime code emits an empty LOG0; the init code returns it
+        let init_code = Bytes::from(vec![
+            0x65, 0x60, 0x00, 0x60, 0x00, 0xa0, 0x00, 0x60, 0x00, 0x52, 0x60, 0x06, 0x60, 0x1a, 0xf3,
+        ]);
+        let contract = sender.create(0);
+        let bundle = [
+            Transaction::deploy(sender, init_code, 0),
+            // Depends on the deployment and nonce bump before it
+            Transaction::call(sender, contract, Bytes::new(), 1),
+            Transaction::call(sender, COLLATERAL_PRECOMPILE, Bytes::new(), 2),
+        ];
+
+        let simulation = executor.simulate_bundle(&bundle).unwrap();
+        let [deploy, call, bridge] = &simulation.results[..] else { panic!("expected 3 results") };
+        assert!(deploy.error.is_none() && deploy.receipt.success);
+        assert_eq!(deploy.state_diff[&sender].nonce, Some((0, 1)));
+        assert!(deploy.state_diff[&contract].code.is_some());
+        assert!(call.receipt.success);
+        assert_eq!(call.receipt.logs.len(), 1);
+        assert_eq!(call.state_diff[&sender].nonce, Some((1, 2)));
+        assert!(bridge.error.is_some() && !bridge.receipt.success);
+        assert_eq!(
+            simulation.gas_used,
+            simulation.results.iter().map(|r| r.receipt.gas_used).sum::<u64>()
+        );
+
+        // Nothing was committed
+        assert_eq!(executor.get_nonce(&sender).unwrap(), 0);
+        assert_eq!(executor.get_balance(&sender).unwrap(), U256::from(100_000_000));
+        assert_eq!(executor.get_code(&contract).unwrap(), None);
+    }
+
+    #[test]
+    fn test_call_and_estimate_gas_leave_state_untouched() {
+        let (mut executor, _temp) = create_test_executor();
+        let sender = Address::repeat_byte(0x01);
+        executor.create_account(sender, U256::from(100_000_000)).unwrap();
+
+        // Init code returning `runtime`
+        let deploy = |executor: &mut EvmExecutor, runtime: &[u8], nonce: u64| {
+            let len = runtime.len() as u8;
+            let mut init_code = vec![0x60, len, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, len, 0x60, 0x00, 0xf3];
+            init_code.extend_from_slice(runtime);
+            executor.deploy_contract(sender, Bytes::from(init_code), nonce).unwrap().0
+        };
+        // SSTORE(0, 1); STOP
+        let store = deploy(&mut executor, &[0x60, 0x01, 0x60, 0x00, 0x55, 0x00], 0);
+        // REVERT(0, 0)
+        let revert = deploy(&mut executor, &[0x60, 0x00, 0x60, 0x00, 0xfd], 1);
+
+        // Any nonce and no gas price: calls need not be in sequence or funded
+        let mut tx = Transaction::call(Address::repeat_byte(0x09), store, Bytes::new(), 7);
+        tx.gas_price = U256::ZERO;
+        let receipt = executor.call(&tx).unwrap();
+        assert!(receipt.success);
+        assert_eq!(executor.get_storage(&store, &U256::ZERO).unwrap(), U256::ZERO);
+
+        let estimate = executor.estimate_gas(&tx).unwrap();
+        assert!(estimate >= receipt.gas_used);
+        tx.gas_limit = estimate;
+        assert!(executor.call(&tx).unwrap().success);
+        tx.gas_limit = estimate - 1;
+        assert!(!executor.call(&tx).is_ok_and(|receipt| receipt.success));
+
+        let transfer = Transaction::transfer(sender, Address::repeat_byte(0x02), U256::from(5), 2);
+        assert_eq!(executor.estimate_gas(&transfer).unwrap(), 21_000);
+        let reverting = Transaction::call(sender, revert, Bytes::new(), 2);
+        assert!(!executor.call(&reverting).unwrap().success);
+        assert!(executor.estimate_gas(&reverting).is_err());
+
+        // Nothing was committed
+        assert_eq!(executor.get_nonce(&sender).unwrap(), 2);
+        assert_eq!(executor.get_balance(&Address::repeat_byte(0x02)).unwrap(), U256::ZERO);
+    }
+
+    #[test]
+    fn test_snapshots_revert_executed_transactions() {
+        let (mut executor, _temp) = create_test_executor();
+        let sender = Address::repeat_byte(0x01);
+        let receiver = Address::repeat_byte(0x02);
+        executor.create_account(sender, U256::from(1_000_000)).unwrap();
+
+        let block = executor.snapshot();
+        executor.set_block_context(1, 100);
+        executor.execute_and_commit(&Transaction::transfer(sender, receiver, U256::from(10), 0)).unwrap();
+        let inner = executor.snapshot();
+        executor.execute_and_commit(&Transaction::transfer(sender, receiver, U256::from(20), 1)).unwrap();
+        executor.end_block(42_000);
+
+        executor.revert_to(inner).unwrap();
+        assert_eq!(executor.get_balance(&receiver).unwrap(), U256::from(10));
+        assert_eq!(executor.get_nonce(&sender).unwrap(), 1);
+        assert!(executor.revert_to(inner).is_err());
+
+        let base_fee = executor.base_fee();
+        executor.revert_to(block).unwrap();
+        assert_eq!(executor.block_number(), 0);
+        assert_eq!(executor.base_fee(), base_fee);
+        assert_eq!(executor.get_balance(&receiver).unwrap(), U256::ZERO);
+        assert_eq!(executor.get_balance(&sender).unwrap(), U256::from(1_000_000));
+
+        // Discarded snapshots keep their changes
+        let kept = executor.snapshot();
+        executor.execute_and_commit(&Transaction::transfer(sender, receiver, U256::from(30), 0)).unwrap();
+        executor.discard_snapshot(kept);
+        assert!(executor.revert_to(kept).is_err());
+        assert_eq!(executor.get_balance(&receiver).unwrap(), U256::from(30));
+    }
+
+    #[test]
+    fn test_state_root_does_not_depend_on_cache_contents() {
+        let sender = Address::repeat_byte(0x01);
+        let receiver = Address::repeat_byte(0x02);
+        let stranger = Address::repeat_byte(0x03);
+        // Init code storing 42 in slot 1, with no runtime code
+        let init_code = Bytes::from(vec![0x60, 0x2a, 0x60, 0x01, 0x55, 0x00]);
+        let run_genesis = |executor: &mut EvmExecutor| {
+            executor.create_account(sender, U256::from(10_000_000)).unwrap();
+            executor.create_account(stranger, U256::from(5)).unwrap();
+            executor.execute_and_commit(&Transaction::deploy(sender, init_code.clone(), 0)).unwrap();
+            executor.commit_state().unwrap()
+        };
+
+        let (mut warm, _warm_dir) = create_test_executor();
+        let temp_dir = tempdir().unwrap();
+        let db = Arc::new(DB::open_default(temp_dir.path()).unwrap());
+        let mut restarted = EvmExecutor::new(EvmStorage::new(db.clone()));
+        let root = run_genesis(&mut warm);
+        assert_eq!(run_genesis(&mut restarted), root);
+        assert_ne!(root, StateCommitment::default().root());
+
+        // A cold cache rebuilds the same root from storage, and reads do not
+        // change it
+        let mut restarted = EvmExecutor::new(EvmStorage::new(db));
+        assert_eq!(restarted.state_root(), root);
+        assert_eq!(restarted.get_balance(&stranger).unwrap(), U256::from(5));
+        assert_eq!(restarted.get_storage(&sender.create(0), &U256::from(1)).unwrap(), U256::from(42));
+        warm.get_balance(&Address::repeat_byte(0x04)).unwrap();
+        assert_eq!(restarted.state_root(), warm.state_root());
+
+        // Blocks update both roots alike
+        let tx = Transaction::transfer(sender, receiver, U256::from(100), 1);
+        warm.execute_and_commit(&tx).unwrap();
+        restarted.execute_and_commit(&tx).unwrap();
+        let root = warm.commit_state().unwrap();
+        assert_eq!(restarted.commit_state().unwrap(), root);
+        let proof = warm.prove_account(&receiver);
+        assert!(!proof.verify_exclusion(&consensus::crypto::Hash::new(root.0), receiver.as_slice()));
+    }
+
+    #[test]
+    fn test_tracing_records_call_tree_and_opcodes() {
+        let (mut executor, _temp) = create_test_executor();
+        let sender = Address::repeat_byte(0x01);
+        executor.create_account(sender, U256::from(100_000_000)).unwrap();
+        executor.set_tracing(Some(TraceConfig { opcodes: true, stack: true }));
+
+        let deploy = |executor: &mut EvmExecutor, runtime: &[u8], nonce: u64| {
+            let len = runtime.len() as u8;
+            let mut init_code = vec![0x60, len, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, len, 0x60, 0x00, 0xf3];
+            init_code.extend_from_slice(runtime);
+            let (address, receipt) = executor.deploy_contract(sender, Bytes::from(init_code), nonce).unwrap();
+            (address, receipt.transaction_hash)
+        };
+        // REVERT(0, 0)
+        let (inner, deploy_hash) = deploy(&mut executor, &[0x60, 0x00, 0x60, 0x00, 0xfd], 0);
+        // CALL(gas, inner, 0, 0, 0, 0, 0); POP; STOP
+        let mut runtime = vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73];
+        runtime.extend_from_slice(inner.as_slice());
+        runtime.extend_from_slice(&[0x5a, 0xf1, 0x50, 0x00]);
+        let (outer, _) = deploy(&mut executor, &runtime, 1);
+
+        let creation = executor.trace(&deploy_hash).unwrap();
+        assert_eq!(creation.call.kind, "CREATE");
+        assert_eq!(creation.call.to, Some(inner));
+
+        let receipt = executor.call_contract(sender, outer, Bytes::new(), 2).unwrap();
+        let trace = executor.trace(&receipt.transaction_hash).unwrap().clone();
+        assert!(!trace.failed);
+        assert_eq!(trace.gas, receipt.gas_used);
+        assert_eq!(trace.call.to, Some(outer));
+        let [call] = &trace.call.calls[..] else { panic!("expected one inner call") };
+        assert_eq!((call.kind.as_str(), call.from, call.to), ("CALL", outer, Some(inner)));
+        assert_eq!(call.error.as_deref(), Some("execution reverted"));
+        let revert = trace.struct_logs.iter().find(|log| log.op == "REVERT").unwrap();
+        assert_eq!(revert.depth, 2);
+        assert_eq!(revert.stack.as_deref(), Some(&[U256::ZERO, U256::ZERO][..]));
+        assert!(trace.struct_logs.iter().any(|log| log.op == "CALL" && log.gas_cost > 0));
+
+        // Traced calls run on demand, without committing
+        let dry_run = Transaction::call(sender, outer, Bytes::new(), 3);
+        let traced = executor.trace_call(&dry_run, TraceConfig::default()).unwrap();
+        assert_eq!(traced.call.calls.len(), 1);
+        assert!(traced.struct_logs.is_empty());
+        assert_eq!(executor.get_nonce(&sender).unwrap(), 3);
+
+        executor.set_tracing(None);
+        assert!(executor.trace(&receipt.transaction_hash).is_none());
+    }
+
     #[test]
     fn test_insufficient_balance() {
         let (mut executor, _temp) = create_test_executor();
@@ -528,7 +1653,7 @@ mod tests {
         let data = Bytes::from(call.abi_encode());
 
         // Create transaction
-        let tx = Transaction::call(trader, SPOT_PRECOMPILE, data, 0);
+        let tx = precompile_tx(&executor, trader, SPOT_PRECOMPILE, data);
 
         // Execute
         let receipt = executor.execute_and_commit(&tx).unwrap();
@@ -560,7 +1685,7 @@ mod tests {
             isBuy: true,
         };
         let place_data = Bytes::from(place_call.abi_encode());
-        let place_tx = Transaction::call(trader, SPOT_PRECOMPILE, place_data, 0);
+        let place_tx = precompile_tx(&executor, trader, SPOT_PRECOMPILE, place_data);
         let place_receipt = executor.execute_and_commit(&place_tx).unwrap();
 
         assert!(place_receipt.success);
@@ -569,7 +1694,7 @@ mod tests {
         // Get order
         let get_call = ISpot::getOrderCall { orderId: order_id };
         let get_data = Bytes::from(get_call.abi_encode());
-        let get_tx = Transaction::call(trader, SPOT_PRECOMPILE, get_data, 0);
+        let get_tx = precompile_tx(&executor, trader, SPOT_PRECOMPILE, get_data);
         let get_receipt = executor.execute_and_commit(&get_tx).unwrap();
 
         assert!(get_receipt.success);
@@ -590,7 +1715,7 @@ mod tests {
         // Cancel order
         let cancel_call = ISpot::cancelOrderCall { orderId: order_id };
         let cancel_data = Bytes::from(cancel_call.abi_encode());
-        let cancel_tx = Transaction::call(trader, SPOT_PRECOMPILE, cancel_data, 0);
+        let cancel_tx = precompile_tx(&executor, trader, SPOT_PRECOMPILE, cancel_data);
         let cancel_receipt = executor.execute_and_commit(&cancel_tx).unwrap();
 
         assert!(cancel_receipt.success);
@@ -621,7 +1746,7 @@ mod tests {
         };
         let data = Bytes::from(call.abi_encode());
 
-        let tx = Transaction::call(trader, PERP_PRECOMPILE, data, 0);
+        let tx = precompile_tx(&executor, trader, PERP_PRECOMPILE, data);
 
         // This will fail because no mark price is set, but we can test the integration
         let result = executor.execute_and_commit(&tx);
@@ -650,7 +1775,7 @@ mod tests {
             isBuy: true,
         };
         let buy_data = Bytes::from(buy_call.abi_encode());
-        let buy_tx = Transaction::call(buyer, SPOT_PRECOMPILE, buy_data, 0);
+        let buy_tx = precompile_tx(&executor, buyer, SPOT_PRECOMPILE, buy_data);
         let buy_receipt = executor.execute_and_commit(&buy_tx).unwrap();
 
         assert!(buy_receipt.success);
@@ -663,7 +1788,7 @@ mod tests {
             isBuy: false,
         };
         let sell_data = Bytes::from(sell_call.abi_encode());
-        let sell_tx = Transaction::call(seller, SPOT_PRECOMPILE, sell_data, 0);
+        let sell_tx = precompile_tx(&executor, seller, SPOT_PRECOMPILE, sell_data);
         let sell_receipt = executor.execute_and_commit(&sell_tx).unwrap();
 
         assert!(sell_receipt.success);
@@ -691,7 +1816,7 @@ mod tests {
             isBuy: true,
         };
         let buy_data = Bytes::from(buy_call.abi_encode());
-        let buy_tx = Transaction::call(trader, SPOT_PRECOMPILE, buy_data, 0);
+        let buy_tx = precompile_tx(&executor, trader, SPOT_PRECOMPILE, buy_data);
         executor.execute_and_commit(&buy_tx).unwrap();
 
         // Place sell order
@@ -702,13 +1827,13 @@ mod tests {
             isBuy: false,
         };
         let sell_data = Bytes::from(sell_call.abi_encode());
-        let sell_tx = Transaction::call(trader, SPOT_PRECOMPILE, sell_data, 0);
+        let sell_tx = precompile_tx(&executor, trader, SPOT_PRECOMPILE, sell_data);
         executor.execute_and_commit(&sell_tx).unwrap();
 
         // Get best prices
         let prices_call = ISpot::getBestPricesCall { asset };
         let prices_data = Bytes::from(prices_call.abi_encode());
-        let prices_tx = Transaction::call(trader, SPOT_PRECOMPILE, prices_data, 0);
+        let prices_tx = precompile_tx(&executor, trader, SPOT_PRECOMPILE, prices_data);
         let prices_receipt = executor.execute_and_commit(&prices_tx).unwrap();
 
         assert!(prices_receipt.success);
@@ -718,6 +1843,410 @@ mod tests {
         assert_eq!(ask, U256::from(105));
     }
 
+    #[test]
+    fn test_collateral_bridge_conserves_balances() {
+        use crate::precompiles::collateral::{ICollateral, LedgerSide};
+        use alloy_sol_types::{SolCall, SolValue};
+        use openliquid_core::{AssetId, CoreStateMachine};
+
+        let (mut executor, _temp) = create_test_executor();
+        let user = Address::repeat_byte(0x01);
+        let asset = AssetId(0);
+        executor.create_account(user, U256::from(1000)).unwrap();
+
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        executor.set_collateral_bridge(CollateralBridge::new(core.clone(), asset));
+        executor.set_block_context(7, 0);
+
+        let call = |executor: &mut EvmExecutor, data: Vec<u8>| {
+            let tx = precompile_tx(&executor, user, COLLATERAL_PRECOMPILE, Bytes::from(data));
+            executor.execute_and_commit(&tx)
+        };
+
+        // Deposit 600: EVM 1000 -> 400, core 0 -> 600, escrow holds 600
+        let receipt = call(&mut executor, ICollateral::depositCall { amount: U256::from(600) }.abi_encode()).unwrap();
+        assert!(receipt.success);
+        assert_eq!(executor.get_balance(&user).unwrap(), U256::from(400));
+        assert_eq!(core.read().unwrap().get_collateral(&user, asset), U256::from(600));
+        assert_eq!(executor.get_balance(&COLLATERAL_PRECOMPILE).unwrap(), U256::from(600));
+
+        // Cannot deposit more than the EVM balance or withdraw more than core holds
+        assert!(call(&mut executor, ICollateral::depositCall { amount: U256::from(401) }.abi_encode()).is_err());
+        assert!(call(&mut executor, ICollateral::withdrawCall { amount: U256::from(601) }.abi_encode()).is_err());
+
+        let receipt = call(&mut executor, ICollateral::withdrawCall { amount: U256::from(250) }.abi_encode()).unwrap();
+        assert!(receipt.success);
+        assert_eq!(executor.get_balance(&user).unwrap(), U256::from(650));
+
+        let receipt = call(&mut executor, ICollateral::collateralOfCall { user }.abi_encode()).unwrap();
+        assert_eq!(U256::abi_decode(&receipt.output, true).unwrap(), U256::from(350));
+
+        // Matching legs on both sides
+        let bridge = executor.collateral_bridge().unwrap();
+        let ledger = bridge.ledger();
+        assert_eq!(ledger.len(), 4);
+        assert_eq!(ledger[0].side, LedgerSide::Evm);
+        assert_eq!(ledger[0].block_number, 7);
+        assert_eq!(ledger[0].delta, -ledger[1].delta);
+        assert_eq!(ledger[2].seq, ledger[3].seq);
+        assert_eq!(bridge.bridged_total(), U256::from(350));
+        executor.check_collateral_invariants().unwrap();
+
+        // Escrow tampering is detected
+        executor.create_account(COLLATERAL_PRECOMPILE, U256::from(1)).unwrap();
+        assert!(executor.check_collateral_invariants().is_err());
+    }
+
+    #[test]
+    fn test_margin_precompile_exposes_core_accounts() {
+        use crate::precompiles::margin::IMargin;
+        use alloy_sol_types::{SolCall, SolValue};
+        use openliquid_core::{AssetId, CoreStateMachine, Price, Side, Size};
+
+        let (mut executor, _temp) = create_test_executor();
+        let user = Address::repeat_byte(0x01);
+        let maker = Address::repeat_byte(0x02);
+        let asset = AssetId(1);
+        executor.create_account(user, U256::from(20_000)).unwrap();
+
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        executor.set_collateral_bridge(CollateralBridge::new(core.clone(), asset));
+
+        let call = |executor: &mut EvmExecutor, data: Vec<u8>| {
+            let tx = precompile_tx(&executor, user, MARGIN_PRECOMPILE, Bytes::from(data));
+            executor.execute_and_commit(&tx)
+        };
+
+        // Deposit moves EVM balance into core collateral through the bridge escrow
+        let receipt = call(&mut executor, IMargin::depositCall { amount: U256::from(10_000) }.abi_encode()).unwrap();
+        assert!(receipt.success);
+        assert_eq!(executor.get_balance(&user).unwrap(), U256::from(10_000));
+        assert_eq!(executor.get_balance(&COLLATERAL_PRECOMPILE).unwrap(), U256::from(10_000));
+        let receipt = call(&mut executor, IMargin::equityOfCall { user }.abi_encode()).unwrap();
+        assert_eq!(U256::abi_decode(&receipt.output, true).unwrap(), U256::from(10_000));
+
+        call(&mut executor, IMargin::setMarginModeCall { isolated: true }.abi_encode()).unwrap();
+        let receipt = call(&mut executor, IMargin::marginModeOfCall { user }.abi_encode()).unwrap();
+        assert!(bool::abi_decode(&receipt.output, true).unwrap());
+
+        // Open a long in core, then read it back through the precompile
+        {
+            let mut core = core.write().unwrap();
+            core.deposit_collateral(maker, asset, U256::from(10_000)).unwrap();
+            core.place_limit_order(maker, asset, Side::Ask, Price::from_float(1.0), Size(U256::from(100)), 0)
+                .unwrap();
+            core.place_limit_order_with_margin(user, asset, Side::Bid, Price::from_float(1.0), Size(U256::from(50)), 1)
+                .unwrap();
+        }
+        let receipt = call(&mut executor, IMargin::positionOfCall { user, asset: 1 }.abi_encode()).unwrap();
+        let position = IMargin::positionOfCall::abi_decode_returns(&receipt.output, true).unwrap();
+        assert_eq!(position.size, 50);
+        assert_eq!(position.entryPrice, Price::from_float(1.0).0);
+        let receipt = call(&mut executor, IMargin::positionsOfCall { user }.abi_encode()).unwrap();
+        let positions = IMargin::positionsOfCall::abi_decode_returns(&receipt.output, true).unwrap();
+        assert_eq!(positions.assets, vec![1]);
+        assert_eq!(positions.sizes, vec![50]);
+
+        // Mode is locked while a position is open
+        assert!(call(&mut executor, IMargin::setMarginModeCall { isolated: false }.abi_encode()).is_err());
+
+        let receipt = call(&mut executor, IMargin::withdrawCall { amount: U256::from(1_000) }.abi_encode()).unwrap();
+        assert!(receipt.success);
+        assert_eq!(executor.get_balance(&user).unwrap(), U256::from(11_000));
+        executor.check_collateral_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_oracle_precompile_reporters_and_staleness() {
+        use crate::precompiles::oracle::IOracle;
+        use alloy_sol_types::{SolCall, SolEvent, SolValue};
+        use openliquid_core::{AssetId, CoreStateMachine, Governance, Price};
+
+        let (mut executor, _temp) = create_test_executor();
+        let reporter = Address::repeat_byte(0x0a);
+        let outsider = Address::repeat_byte(0x0b);
+        let governor = Address::repeat_byte(0x0c);
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        core.write().unwrap().set_governance(Governance::new([governor]));
+        assert!(core.write().unwrap().authorize_oracle_reporter(&reporter, AssetId(1), reporter).is_err());
+        core.write().unwrap().authorize_oracle_reporter(&governor, AssetId(1), reporter).unwrap();
+        executor.set_price_oracle(PriceOracle::new(core.clone()));
+
+        let call = |executor: &mut EvmExecutor, from: Address, data: Vec<u8>| {
+            let tx = precompile_tx(&executor, from, ORACLE_PRECOMPILE, Bytes::from(data));
+            executor.execute_and_commit(&tx)
+        };
+        let read = |executor: &mut EvmExecutor, data: Vec<u8>| {
+            call(executor, outsider, data).map(|receipt| u64::abi_decode(&receipt.output, true).unwrap())
+        };
+
+        // Only whitelisted reporters post, and only for their assets
+        executor.set_block_context(1, 1_000);
+        let post = |asset, price| IOracle::postPriceCall { asset, price }.abi_encode();
+        assert!(call(&mut executor, outsider, post(1, 100_000_000)).is_err());
+        assert!(call(&mut executor, reporter, post(2, 100_000_000)).is_err());
+        assert!(call(&mut executor, reporter, post(1, 0)).is_err());
+        let receipt = call(&mut executor, reporter, post(1, 100_000_000)).unwrap();
+        let event = IOracle::PricePosted::decode_raw_log(
+            receipt.logs[0].topics.iter().copied(),
+            &receipt.logs[0].data,
+            true,
+        )
+        .unwrap();
+        assert_eq!((event.asset, event.reporter, event.timestamp), (1, reporter, 1_000));
+        assert_eq!(core.read().unwrap().get_oracle_price(AssetId(1), 1_000), Some(Price(100_000_000)));
+
+        executor.set_block_context(2, 1_030);
+        call(&mut executor, reporter, post(1, 120_000_000)).unwrap();
+        assert_eq!(read(&mut executor, IOracle::latestPriceCall { asset: 1 }.abi_encode()).unwrap(), 120_000_000);
+        let twap = IOracle::twapCall { asset: 1, window: 60 }.abi_encode();
+        executor.set_block_context(3, 1_060);
+        assert_eq!(read(&mut executor, twap).unwrap(), 110_000_000);
+
+        call(&mut executor, reporter, IOracle::postIndexPriceCall { asset: 1, price: 99_000_000 }.abi_encode()).unwrap();
+        assert_eq!(read(&mut executor, IOracle::indexPriceCall { asset: 1 }.abi_encode()).unwrap(), 99_000_000);
+        assert!(read(&mut executor, IOracle::indexPriceCall { asset: 2 }.abi_encode()).is_err());
+
+        // Past the core oracle's maximum age the price is stale
+        executor.set_block_context(4, 1_100);
+        assert!(read(&mut executor, IOracle::latestPriceCall { asset: 1 }.abi_encode()).is_err());
+        let receipt = call(&mut executor, outsider, IOracle::isStaleCall { asset: 1 }.abi_encode()).unwrap();
+        assert!(bool::abi_decode(&receipt.output, true).unwrap());
+
+        core.write().unwrap().revoke_oracle_reporter(&governor, AssetId(1), &reporter).unwrap();
+        let receipt =
+            call(&mut executor, outsider, IOracle::isReporterCall { asset: 1, reporter }.abi_encode()).unwrap();
+        assert!(!bool::abi_decode(&receipt.output, true).unwrap());
+        assert!(call(&mut executor, reporter, post(1, 130_000_000)).is_err());
+    }
+
+    #[test]
+    fn test_vault_precompile_shares_track_equity() {
+        use crate::precompiles::vault::{IVault, SHARE_PRICE_SCALE};
+        use alloy_sol_types::{SolCall, SolValue};
+        use openliquid_core::{AssetId, CoreStateMachine};
+
+        let (mut executor, _temp) = create_test_executor();
+        let owner = Address::repeat_byte(0x01);
+        let user = Address::repeat_byte(0x02);
+        let manager = Address::repeat_byte(0x03);
+        let asset = AssetId(1);
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        core.write().unwrap().deposit_collateral(owner, asset, U256::from(10_000)).unwrap();
+        core.write().unwrap().deposit_collateral(user, asset, U256::from(10_000)).unwrap();
+        executor.set_vaults(VaultPrecompile::new(core.clone(), asset));
+
+        let call = |executor: &mut EvmExecutor, from: Address, data: Vec<u8>| {
+            let tx = precompile_tx(&executor, from, VAULT_PRECOMPILE, Bytes::from(data));
+            executor.execute_and_commit(&tx)
+        };
+        let collateral = |user: &Address| core.read().unwrap().get_collateral(user, asset);
+
+        // Creation funds the vault from the owner's core collateral
+        let create = IVault::createVaultCall { manager, profitShareBps: 2_000, collateral: U256::from(4_000) };
+        let too_generous = IVault::createVaultCall { profitShareBps: 10_001, ..create.clone() };
+        assert!(call(&mut executor, owner, too_generous.abi_encode()).is_err());
+        assert_eq!(collateral(&owner), U256::from(10_000));
+        let receipt = call(&mut executor, owner, create.abi_encode()).unwrap();
+        let vault_id = u64::abi_decode(&receipt.output, true).unwrap();
+        assert_eq!(receipt.logs.len(), 1);
+        assert_eq!(collateral(&owner), U256::from(6_000));
+
+        // Equity doubles: new deposits buy half as many shares
+        core.write().unwrap().update_vault_equity(vault_id, U256::from(8_000), 0).unwrap();
+        let receipt = call(&mut executor, user, IVault::sharePriceCall { vaultId: vault_id }.abi_encode()).unwrap();
+        assert_eq!(U256::abi_decode(&receipt.output, true).unwrap(), U256::from(2 * SHARE_PRICE_SCALE));
+        let deposit = IVault::depositCall { vaultId: vault_id, amount: U256::from(2_000) }.abi_encode();
+        let receipt = call(&mut executor, user, deposit).unwrap();
+        assert_eq!(U256::abi_decode(&receipt.output, true).unwrap(), U256::from(1_000));
+        assert_eq!(collateral(&user), U256::from(8_000));
+
+        let receipt = call(&mut executor, user, IVault::vaultInfoCall { vaultId: vault_id }.abi_encode()).unwrap();
+        let info = IVault::vaultInfoCall::abi_decode_returns(&receipt.output, true).unwrap();
+        assert_eq!((info.owner, info.manager), (owner, manager));
+        assert_eq!((info.collateral, info.equity), (U256::from(6_000), U256::from(10_000)));
+        assert_eq!(info.totalShares, U256::from(5_000));
+        assert_eq!(info.pnl, 4_000);
+
+        // Redeeming returns the shares' equity to core collateral
+        let withdraw =
+            |shares: u64| IVault::withdrawCall { vaultId: vault_id, shares: U256::from(shares) }.abi_encode();
+        assert!(call(&mut executor, user, withdraw(1_001)).is_err());
+        let receipt = call(&mut executor, user, withdraw(1_000)).unwrap();
+        assert_eq!(U256::abi_decode(&receipt.output, true).unwrap(), U256::from(2_000));
+        assert_eq!(collateral(&user), U256::from(10_000));
+        let receipt = call(&mut executor, user, IVault::sharesOfCall { vaultId: vault_id, user }.abi_encode()).unwrap();
+        assert_eq!(U256::abi_decode(&receipt.output, true).unwrap(), U256::ZERO);
+    }
+
+    #[test]
+    fn test_contracts_call_vaults_and_failures_roll_back() {
+        use crate::precompiles::vault::IVault;
+        use alloy_sol_types::{SolCall, SolValue};
+        use openliquid_core::{AssetId, CoreStateMachine};
+
+        let (mut executor, _temp) = create_test_executor();
+        let sender = Address::repeat_byte(0x01);
+        let asset = AssetId(1);
+        executor.create_account(sender, U256::from(100_000_000)).unwrap();
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        core.write().unwrap().deposit_collateral(sender, asset, U256::from(10_000)).unwrap();
+        executor.set_vaults(VaultPrecompile::new(core.clone(), asset));
+        let create = IVault::createVaultCall { manager: sender, profitShareBps: 0, collateral: U256::from(1_000) };
+        let tx = precompile_tx(&executor, sender, VAULT_PRECOMPILE, create.abi_encode());
+        let vault_id = u64::abi_decode(&executor.execute_and_commit(&tx).unwrap().output, true).unwrap();
+
+        let deploy = |executor: &mut EvmExecutor, runtime: &[u8], nonce: u64| {
+            let len = runtime.len() as u8;
+            let mut init_code = vec![0x60, len, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, len, 0x60, 0x00, 0xf3];
+            init_code.extend_from_slice(runtime);
+            executor.deploy_contract(sender, Bytes::from(init_code), nonce).unwrap().0
+        };
+        // CALLDATACOPY(0, 0, CALLDATASIZE); CALL(gas, vaults, 0, 0, CALLDATASIZE, 0, 32)
+        let mut forward = vec![0x36, 0x60, 0x00, 0x60, 0x00, 0x37];
+        forward.extend_from_slice(&[0x60, 0x20, 0x60, 0x00, 0x36, 0x60, 0x00, 0x60, 0x00, 0x73]);
+        forward.extend_from_slice(VAULT_PRECOMPILE.as_slice());
+        forward.extend_from_slice(&[0x5a, 0xf1]);
+        // Return the call's output, or revert if it failed
+        let mut relay = forward.clone();
+        relay.extend_from_slice(&[0x15, 0x60, 0x2f, 0x57, 0x60, 0x20, 0x60, 0x00, 0xf3, 0x5b, 0x60, 0x00, 0x60, 0x00, 0xfd]);
+        // Revert whatever the call did
+        let mut regret = forward;
+        regret.extend_from_slice(&[0x50, 0x60, 0x00, 0x60, 0x00, 0xfd]);
+        let relay = deploy(&mut executor, &relay, 1);
+        let regret = deploy(&mut executor, &regret, 2);
+        for contract in [relay, regret] {
+            core.write().unwrap().deposit_collateral(contract, asset, U256::from(1_000)).unwrap();
+        }
+        let shares = |user: Address| core.read().unwrap().vaults().shares_of(vault_id, &user);
+        let collateral = |user: Address| core.read().unwrap().get_collateral(&user, asset);
+        let deposit = Bytes::from(IVault::depositCall { vaultId: vault_id, amount: U256::from(400) }.abi_encode());
+
+        // The contract deposits its own collateral, and the event is logged
+        let receipt = executor.call_contract(sender, relay, deposit.clone(), 3).unwrap();
+        assert!(receipt.success);
+        assert_eq!(shares(relay), U256::from(400));
+        assert_eq!(collateral(relay), U256::from(600));
+        assert_eq!(receipt.logs.len(), 1);
+
+        // A transaction that reverts after the call leaves no vault change
+        let receipt = executor.call_contract(sender, regret, deposit.clone(), 4).unwrap();
+        assert!(!receipt.success);
+        assert!(shares(regret).is_zero());
+        assert_eq!(collateral(regret), U256::from(1_000));
+
+        // `call` serves views but refuses changes
+        let shares_of = IVault::sharesOfCall { vaultId: vault_id, user: relay }.abi_encode();
+        let mut view = Transaction::call(sender, relay, Bytes::from(shares_of), 0);
+        view.gas_price = U256::ZERO;
+        let receipt = executor.call(&view).unwrap();
+        assert_eq!(U256::abi_decode(&receipt.output, true).unwrap(), U256::from(400));
+        view.data = deposit.clone();
+        assert!(!executor.call(&view).unwrap().success);
+        assert_eq!(shares(relay), U256::from(400));
+
+        // Reverting a failed block undoes its vault calls
+        let snapshot = executor.snapshot();
+        assert!(executor.call_contract(sender, relay, deposit, 5).unwrap().success);
+        assert_eq!(shares(relay), U256::from(800));
+        executor.revert_to(snapshot).unwrap();
+        assert_eq!(shares(relay), U256::from(400));
+        assert_eq!(collateral(relay), U256::from(600));
+        assert_eq!(core.read().unwrap().vaults().total_shares(vault_id), U256::from(1_400));
+    }
+
+    #[test]
+    fn test_collateral_withdrawals_wait_for_finalized_delay() {
+        use crate::precompiles::collateral::ICollateral;
+        use alloy_sol_types::{SolCall, SolValue};
+        use openliquid_core::{AssetId, CoreStateMachine};
+
+        let (mut executor, _temp) = create_test_executor();
+        let user = Address::repeat_byte(0x01);
+        let asset = AssetId(0);
+        executor.create_account(user, U256::from(1000)).unwrap();
+
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        let mut bridge = CollateralBridge::new(core.clone(), asset);
+        bridge.set_withdrawal_delay(2);
+        executor.set_collateral_bridge(bridge);
+        executor.set_block_context(1, 0);
+
+        let call = |executor: &mut EvmExecutor, from: Address, data: Vec<u8>| {
+            let tx = precompile_tx(&executor, from, COLLATERAL_PRECOMPILE, Bytes::from(data));
+            executor.execute_and_commit(&tx)
+        };
+        call(&mut executor, user, ICollateral::depositCall { amount: U256::from(600) }.abi_encode()).unwrap();
+
+        // Withdrawals are queued, not paid, and cannot exceed the collateral
+        call(&mut executor, user, ICollateral::withdrawCall { amount: U256::from(100) }.abi_encode()).unwrap();
+        call(&mut executor, user, ICollateral::withdrawCall { amount: U256::from(200) }.abi_encode()).unwrap();
+        assert!(call(&mut executor, user, ICollateral::withdrawCall { amount: U256::from(301) }.abi_encode()).is_err());
+        assert_eq!(executor.get_balance(&user).unwrap(), U256::from(400));
+        let receipt = call(&mut executor, user, ICollateral::withdrawalIdsCall { user }.abi_encode()).unwrap();
+        assert_eq!(Vec::<U256>::abi_decode(&receipt.output, true).unwrap(), vec![U256::from(1), U256::from(2)]);
+
+        // Only the requester can cancel
+        let cancel = ICollateral::cancelWithdrawalCall { id: U256::from(2) }.abi_encode();
+        assert!(call(&mut executor, Address::repeat_byte(0x02), cancel.clone()).is_err());
+        call(&mut executor, user, cancel).unwrap();
+
+        // Settles once block 1 + 2 is finalized
+        assert!(executor.settle_withdrawals(2).unwrap().unwrap().settled.is_empty());
+        let batch = executor.settle_withdrawals(3).unwrap().unwrap();
+        assert_eq!(batch.settled.iter().map(|w| w.id).collect::<Vec<_>>(), vec![1]);
+        assert!(batch.failed.is_empty());
+        assert_eq!(executor.get_balance(&user).unwrap(), U256::from(500));
+        assert_eq!(core.read().unwrap().get_collateral(&user, asset), U256::from(500));
+        assert!(executor.collateral_bridge().unwrap().withdrawal_queue().is_empty());
+        executor.check_collateral_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_collateral_bridge_requires_attachment() {
+        use crate::precompiles::collateral::ICollateral;
+        use alloy_sol_types::SolCall;
+
+        let (mut executor, _temp) = create_test_executor();
+        let data = ICollateral::depositCall { amount: U256::from(1) }.abi_encode();
+        let tx = precompile_tx(&executor, Address::repeat_byte(0x01), COLLATERAL_PRECOMPILE, Bytes::from(data));
+        assert!(executor.execute_and_commit(&tx).is_err());
+    }
+
+    #[test]
+    fn test_randomness_precompile_serves_block_beacon() {
+        use crate::precompiles::randomness::IRandomness;
+        use alloy_sol_types::{SolCall, SolValue};
+
+        let (mut executor, _temp) = create_test_executor();
+        let caller = Address::repeat_byte(0x01);
+        let call = |executor: &mut EvmExecutor, data: Vec<u8>| {
+            let tx = precompile_tx(&executor, caller, RANDOMNESS_PRECOMPILE, Bytes::from(data));
+            executor.execute_and_commit(&tx)
+        };
+
+        // No beacon value recorded for the block yet
+        executor.set_block_context(1, 0);
+        assert!(call(&mut executor, IRandomness::getRandomnessCall {}.abi_encode()).is_err());
+
+        executor.set_block_randomness(B256::repeat_byte(0xaa));
+        executor.set_block_context(2, 0);
+        executor.set_block_randomness(B256::repeat_byte(0xbb));
+
+        let receipt = call(&mut executor, IRandomness::getRandomnessCall {}.abi_encode()).unwrap();
+        assert_eq!(B256::abi_decode(&receipt.output, true).unwrap(), B256::repeat_byte(0xbb));
+
+        let receipt = call(&mut executor, IRandomness::getRandomnessAtCall { height: 1 }.abi_encode()).unwrap();
+        assert_eq!(B256::abi_decode(&receipt.output, true).unwrap(), B256::repeat_byte(0xaa));
+
+        // Future blocks cannot be read
+        assert!(call(&mut executor, IRandomness::getRandomnessAtCall { height: 3 }.abi_encode()).is_err());
+
+        // Also visible to contracts through PREVRANDAO
+        let env = executor.build_env(&Transaction::call(caller, caller, Bytes::new(), 0));
+        assert_eq!(env.block.prevrandao, Some(B256::repeat_byte(0xbb)));
+    }
+
     #[test]
     fn test_precompile_is_detected() {
         use crate::{is_precompile, SPOT_PRECOMPILE, PERP_PRECOMPILE};
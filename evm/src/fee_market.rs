@@ -0,0 +1,171 @@
+// EIP-1559 style fee market
+//
+// Every block has a base fee each unit of gas pays, plus a priority fee
+// (tip) to the block producer, together capped by the transaction's max
+// fee. The base fee moves after each block towards keeping blocks at the
+// gas target: up to 1/8 up when the block was full, down when it was empty.
+// The base fee part of what a transaction pays leaves circulation, either
+// burned or paid into an insurance fund account.
+
+use alloy_primitives::{Address, U256};
+
+/// Where the base fee part of transaction fees goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeDestination {
+    /// Taken out of circulation
+    Burn,
+    /// Credited to the insurance fund account
+    InsuranceFund(Address),
+}
+
+/// Fee market parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeMarketConfig {
+    /// Base fee of the first block
+    pub initial_base_fee: U256,
+    /// Lowest the base fee may fall to
+    pub min_base_fee: U256,
+    /// Gas per block the base fee steers towards
+    pub gas_target: u64,
+    /// Inverse of the largest base fee change per block
+    pub max_change_denominator: u64,
+    pub destination: FeeDestination,
+}
+
+impl Default for FeeMarketConfig {
+    fn default() -> Self {
+        Self {
+            initial_base_fee: U256::ZERO,
+            min_base_fee: U256::ZERO,
+            // Half the 30M block gas limit
+            gas_target: 15_000_000,
+            max_change_denominator: 8,
+            destination: FeeDestination::Burn,
+        }
+    }
+}
+
+/// Base fee of the block after one with `base_fee` that used `gas_used`
+pub fn next_base_fee(config: &FeeMarketConfig, base_fee: U256, gas_used: u64) -> U256 {
+    let target = config.gas_target.max(1);
+    let denominator = U256::from(config.max_change_denominator.max(1));
+    let next = if gas_used > target {
+        let delta = base_fee * U256::from(gas_used - target) / U256::from(target) / denominator;
+        base_fee + delta.max(U256::from(1))
+    } else {
+        let delta = base_fee * U256::from(target - gas_used) / U256::from(target) / denominator;
+        base_fee - delta
+    };
+    next.max(config.min_base_fee)
+}
+
+/// Current base fee and the base fees collected so far
+#[derive(Debug, Clone)]
+pub struct FeeMarket {
+    config: FeeMarketConfig,
+    base_fee: U256,
+    burned: U256,
+    to_insurance: U256,
+}
+
+impl FeeMarket {
+    pub fn new(config: FeeMarketConfig) -> Self {
+        Self {
+            base_fee: config.initial_base_fee.max(config.min_base_fee),
+            config,
+            burned: U256::ZERO,
+            to_insurance: U256::ZERO,
+        }
+    }
+
+    pub fn config(&self) -> &FeeMarketConfig {
+        &self.config
+    }
+
+    /// Base fee of the current block
+    pub fn base_fee(&self) -> U256 {
+        self.base_fee
+    }
+
+    /// Base fees burned so far
+    pub fn burned(&self) -> U256 {
+        self.burned
+    }
+
+    /// Base fees paid into the insurance fund so far
+    pub fn to_insurance(&self) -> U256 {
+        self.to_insurance
+    }
+
+    /// Account the base fee of `gas_used` gas, returning the insurance fund
+    /// to credit and the amount if fees are redirected
+    pub fn collect(&mut self, gas_used: u64) -> Option<(Address, U256)> {
+        let amount = self.base_fee * U256::from(gas_used);
+        match self.config.destination {
+            FeeDestination::Burn => {
+                self.burned += amount;
+                None
+            }
+            FeeDestination::InsuranceFund(fund) => {
+                self.to_insurance += amount;
+                Some((fund, amount)).filter(|(_, amount)| !amount.is_zero())
+            }
+        }
+    }
+
+    /// Move to the next block's base fee after a block used `gas_used`
+    pub fn end_block(&mut self, gas_used: u64) -> U256 {
+        self.base_fee = next_base_fee(&self.config, self.base_fee, gas_used);
+        self.base_fee
+    }
+}
+
+impl Default for FeeMarket {
+    fn default() -> Self {
+        Self::new(FeeMarketConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_fee_tracks_gas_target() {
+        let config = FeeMarketConfig {
+            initial_base_fee: U256::from(1_000),
+            min_base_fee: U256::from(900),
+            gas_target: 1_000,
+            ..Default::default()
+        };
+        let mut market = FeeMarket::new(config);
+
+        // Full block (twice the target): +1/8
+        assert_eq!(market.end_block(2_000), U256::from(1_125));
+        // At target: unchanged
+        assert_eq!(market.end_block(1_000), U256::from(1_125));
+        // Empty block: -1/8, then held at the floor
+        assert_eq!(market.end_block(0), U256::from(985));
+        assert_eq!(market.end_block(0), U256::from(900));
+        // Slightly over target still moves it up
+        assert_eq!(next_base_fee(&config, U256::from(900), 1_001), U256::from(901));
+        assert_eq!(next_base_fee(&FeeMarketConfig::default(), U256::ZERO, 30_000_000), U256::from(1));
+    }
+
+    #[test]
+    fn test_base_fees_burned_or_redirected() {
+        let fund = Address::repeat_byte(0xaa);
+        let mut burn = FeeMarket::new(FeeMarketConfig { initial_base_fee: U256::from(10), ..Default::default() });
+        assert_eq!(burn.collect(21_000), None);
+        assert_eq!(burn.burned(), U256::from(210_000));
+
+        let mut redirect = FeeMarket::new(FeeMarketConfig {
+            initial_base_fee: U256::from(10),
+            destination: FeeDestination::InsuranceFund(fund),
+            ..Default::default()
+        });
+        assert_eq!(redirect.collect(21_000), Some((fund, U256::from(210_000))));
+        assert_eq!(redirect.to_insurance(), U256::from(210_000));
+        assert_eq!(redirect.burned(), U256::ZERO);
+    }
+}
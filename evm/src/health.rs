@@ -0,0 +1,272 @@
+// Node Health
+//
+// Aggregates component states into liveness and readiness probes
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// State of a single component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum HealthStatus {
+    Healthy,
+    /// Working, but close to a limit
+    Degraded,
+    Unhealthy,
+}
+
+/// Health of one node component
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentHealth {
+    pub name: &'static str,
+    pub status: HealthStatus,
+    /// Human-readable detail for operators
+    pub detail: String,
+    /// Whether an unhealthy state means the node must be restarted
+    /// (rather than just taken out of rotation)
+    pub critical: bool,
+}
+
+/// Aggregated node health
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// Liveness: no critical component is unhealthy
+    pub live: bool,
+    /// Readiness: no component is unhealthy
+    pub ready: bool,
+    pub components: Vec<ComponentHealth>,
+}
+
+impl HealthReport {
+    /// Aggregate component states
+    pub fn new(components: Vec<ComponentHealth>) -> Self {
+        let unhealthy = |c: &ComponentHealth| c.status == HealthStatus::Unhealthy;
+        Self {
+            live: !components.iter().any(|c| c.critical && unhealthy(c)),
+            ready: !components.iter().any(unhealthy),
+            components,
+        }
+    }
+
+    /// Worst component status
+    pub fn status(&self) -> HealthStatus {
+        self.components
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(HealthStatus::Healthy)
+    }
+
+    /// Get a component by name
+    pub fn component(&self, name: &str) -> Option<&ComponentHealth> {
+        self.components.iter().find(|c| c.name == name)
+    }
+}
+
+/// Health thresholds
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// Time without a new block before consensus is considered stalled
+    pub max_stall: Duration,
+    /// Uncommitted blocks behind the chain tip before the node is not ready
+    pub max_executor_lag: u64,
+    /// Pending transactions before the node is not ready
+    pub max_mempool_depth: usize,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_stall: Duration::from_secs(30),
+            max_executor_lag: 10,
+            max_mempool_depth: 8_000,
+        }
+    }
+}
+
+/// Component state sampled from the node
+#[derive(Debug, Clone, Default)]
+pub struct HealthInputs {
+    /// Height of the chain tip
+    pub height: u64,
+    /// Height of the last committed block
+    pub committed_height: u64,
+    /// Connected validator peers, None without a network
+    pub validator_peers: Option<usize>,
+    /// Votes needed for a quorum (including our own)
+    pub quorum_size: usize,
+    /// Error from the storage write probe, if any
+    pub storage_error: Option<String>,
+    /// Pending mempool transactions
+    pub mempool_depth: usize,
+}
+
+/// Tracks consensus progress between probes and evaluates component health
+#[derive(Debug)]
+pub struct HealthMonitor {
+    config: HealthConfig,
+    last_height: u64,
+    last_advance: Instant,
+}
+
+impl HealthMonitor {
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            last_height: 0,
+            last_advance: Instant::now(),
+        }
+    }
+
+    pub fn config(&self) -> &HealthConfig {
+        &self.config
+    }
+
+    /// Evaluate all components
+    pub fn evaluate(&mut self, inputs: &HealthInputs, now: Instant) -> HealthReport {
+        HealthReport::new(vec![
+            self.check_consensus(inputs.height, now),
+            check_peers(inputs.validator_peers, inputs.quorum_size),
+            check_storage(inputs.storage_error.as_deref()),
+            self.check_executor(inputs.height, inputs.committed_height),
+            self.check_mempool(inputs.mempool_depth),
+        ])
+    }
+
+    /// Consensus is stalled if the height has not moved within `max_stall`
+    fn check_consensus(&mut self, height: u64, now: Instant) -> ComponentHealth {
+        if height > self.last_height {
+            self.last_height = height;
+            self.last_advance = now;
+        }
+        let idle = now.saturating_duration_since(self.last_advance);
+        let status = if idle > self.config.max_stall {
+            HealthStatus::Unhealthy
+        } else if idle > self.config.max_stall / 2 {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+        ComponentHealth {
+            name: "consensus",
+            status,
+            detail: format!("height {}, last advanced {}s ago", height, idle.as_secs()),
+            critical: true,
+        }
+    }
+
+    fn check_executor(&self, height: u64, committed_height: u64) -> ComponentHealth {
+        let lag = height.saturating_sub(committed_height);
+        ComponentHealth {
+            name: "executor",
+            status: threshold(lag as usize, self.config.max_executor_lag as usize),
+            detail: format!("{} blocks behind tip", lag),
+            critical: false,
+        }
+    }
+
+    fn check_mempool(&self, depth: usize) -> ComponentHealth {
+        ComponentHealth {
+            name: "mempool",
+            status: threshold(depth, self.config.max_mempool_depth),
+            detail: format!("{} pending transactions", depth),
+            critical: false,
+        }
+    }
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self::new(HealthConfig::default())
+    }
+}
+
+/// A quorum needs `quorum_size - 1` peers besides ourselves
+fn check_peers(validator_peers: Option<usize>, quorum_size: usize) -> ComponentHealth {
+    let needed = quorum_size.saturating_sub(1);
+    let (status, detail) = match validator_peers {
+        None => (HealthStatus::Unhealthy, "no network attached".to_string()),
+        Some(peers) if peers < needed => (
+            HealthStatus::Unhealthy,
+            format!("{} validator peers, {} needed for quorum", peers, needed),
+        ),
+        Some(peers) => (HealthStatus::Healthy, format!("{} validator peers", peers)),
+    };
+    ComponentHealth { name: "peers", status, detail, critical: false }
+}
+
+fn check_storage(error: Option<&str>) -> ComponentHealth {
+    ComponentHealth {
+        name: "storage",
+        status: if error.is_some() { HealthStatus::Unhealthy } else { HealthStatus::Healthy },
+        detail: error.map_or_else(|| "writable".to_string(), |e| format!("write failed: {}", e)),
+        critical: true,
+    }
+}
+
+/// Degraded past 80% of a limit, unhealthy past it
+fn threshold(value: usize, limit: usize) -> HealthStatus {
+    if value > limit {
+        HealthStatus::Unhealthy
+    } else if value * 5 > limit * 4 {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Healthy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> HealthInputs {
+        HealthInputs {
+            height: 1,
+            committed_height: 1,
+            validator_peers: Some(3),
+            quorum_size: 3,
+            storage_error: None,
+            mempool_depth: 0,
+        }
+    }
+
+    #[test]
+    fn test_readiness_and_liveness() {
+        let mut monitor = HealthMonitor::default();
+        let now = Instant::now();
+
+        let report = monitor.evaluate(&inputs(), now);
+        assert!(report.live && report.ready);
+        assert_eq!(report.status(), HealthStatus::Healthy);
+
+        // Losing peers or falling behind takes the node out of rotation
+        let report = monitor.evaluate(&HealthInputs { validator_peers: Some(1), ..inputs() }, now);
+        assert!(report.live && !report.ready);
+        assert_eq!(report.component("peers").unwrap().status, HealthStatus::Unhealthy);
+
+        let report = monitor.evaluate(&HealthInputs { height: 20, mempool_depth: 7_000, ..inputs() }, now);
+        assert!(report.live && !report.ready);
+        assert_eq!(report.component("mempool").unwrap().status, HealthStatus::Degraded);
+
+        // Storage failures fail liveness
+        let report = monitor.evaluate(&HealthInputs { storage_error: Some("disk full".into()), ..inputs() }, now);
+        assert!(!report.live && !report.ready);
+    }
+
+    #[test]
+    fn test_consensus_stall() {
+        let mut monitor = HealthMonitor::default();
+        let start = Instant::now();
+        assert_eq!(monitor.evaluate(&inputs(), start).status(), HealthStatus::Healthy);
+
+        let report = monitor.evaluate(&inputs(), start + Duration::from_secs(20));
+        assert_eq!(report.component("consensus").unwrap().status, HealthStatus::Degraded);
+        assert!(report.ready);
+
+        let report = monitor.evaluate(&inputs(), start + Duration::from_secs(31));
+        assert!(!report.live);
+
+        // A new block resets the stall timer
+        let report = monitor.evaluate(&HealthInputs { height: 2, committed_height: 2, ..inputs() }, start + Duration::from_secs(32));
+        assert!(report.live && report.ready);
+    }
+}
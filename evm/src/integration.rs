@@ -3,14 +3,16 @@
 // Full node implementation combining HotStuff consensus with EVM execution
 
 use crate::bridge::{ConsensusEvmBridge, MempoolStats};
-use crate::{EvmStateMachine, Mempool, Transaction};
+use crate::health::{HealthConfig, HealthInputs, HealthMonitor, HealthReport};
+use crate::{EvmStateMachine, EvmTxValidator, Mempool, Transaction};
 use anyhow::{anyhow, Result};
 use consensus::crypto::BLSKeyPair;
 use consensus::hotstuff::engine::ConsensusEngine;
 use consensus::hotstuff::types::{Block, Vote};
 use consensus::network::types::{ConsensusMessage, GossipMessage, NetworkMessage};
-use consensus::network::{NetworkConfig, NetworkEvent, NetworkManager};
+use consensus::network::{NetworkEvent, NetworkHandle};
 use consensus::storage::Storage;
+use consensus::watchdog::{Heartbeat, Watchdog, WatchdogConfig};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -30,12 +32,20 @@ pub struct IntegratedNode {
     node_id: usize,
     /// EVM bridge (consensus + mempool)
     bridge: Arc<ConsensusEvmBridge>,
-    /// Network manager
-    network: Option<Arc<RwLock<NetworkManager>>>,
+    /// Handle to the network event loop
+    network: Option<NetworkHandle>,
     /// Block proposal interval
     proposal_interval: Duration,
     /// Whether the node is running
     running: Arc<RwLock<bool>>,
+    /// Consensus progress tracking for health probes
+    health: Arc<RwLock<HealthMonitor>>,
+    /// Stall detection for consensus, execution and the network
+    watchdog: Watchdog,
+    /// Watchdog check loop, while the node is running
+    watchdog_task: Option<tokio::task::JoinHandle<()>>,
+    /// Progress of consensus message handling
+    consensus_heartbeat: Heartbeat,
 }
 
 impl IntegratedNode {
@@ -43,13 +53,18 @@ impl IntegratedNode {
     pub fn new(
         node_id: usize,
         storage: Arc<Storage>,
-        evm_state_machine: Box<EvmStateMachine>,
+        mut evm_state_machine: Box<EvmStateMachine>,
         keypair: BLSKeyPair,
         total_validators: usize,
         proposal_interval: Duration,
     ) -> Result<Self> {
+        let watchdog = Watchdog::new(WatchdogConfig::default());
+        evm_state_machine.set_heartbeat(watchdog.register("executor"));
+        let mut mempool = Mempool::new();
+        mempool.set_state(evm_state_machine.executor().storage());
+
         // Create consensus engine
-        let consensus = ConsensusEngine::new(
+        let mut consensus = ConsensusEngine::new(
             storage,
             evm_state_machine,
             keypair,
@@ -57,22 +72,45 @@ impl IntegratedNode {
             total_validators,
         )
         .map_err(|e| anyhow!("Failed to create consensus engine: {}", e))?;
+        consensus.set_tx_validator(Arc::new(EvmTxValidator::default()));
 
         let consensus = Arc::new(RwLock::new(consensus));
-        let mempool = Arc::new(RwLock::new(Mempool::new()));
+        let mempool = Arc::new(RwLock::new(mempool));
         let bridge = Arc::new(ConsensusEvmBridge::new(consensus, mempool));
 
+        // A stalled engine is restarted by reloading its state from storage
+        let consensus_heartbeat = watchdog.register_restartable("consensus", {
+            let bridge = bridge.clone();
+            move || {
+                let bridge = bridge.clone();
+                async move {
+                    let mut consensus = bridge.consensus.write().await;
+                    consensus.recover().await.map_err(|e| e.to_string())
+                }
+            }
+        });
+
         Ok(Self {
             node_id,
             bridge,
             network: None,
             proposal_interval,
             running: Arc::new(RwLock::new(false)),
+            health: Arc::new(RwLock::new(HealthMonitor::default())),
+            watchdog,
+            watchdog_task: None,
+            consensus_heartbeat,
         })
     }
 
-    /// Attach network manager to the node
-    pub fn with_network(mut self, network: Arc<RwLock<NetworkManager>>) -> Self {
+    /// Set the health probe thresholds
+    pub fn with_health_config(mut self, config: HealthConfig) -> Self {
+        self.health = Arc::new(RwLock::new(HealthMonitor::new(config)));
+        self
+    }
+
+    /// Attach a running network (see `NetworkManager::spawn`) to the node
+    pub fn with_network(mut self, network: NetworkHandle) -> Self {
         self.network = Some(network);
         self
     }
@@ -82,6 +120,12 @@ impl IntegratedNode {
         self.node_id
     }
 
+    /// Watchdog supervising the node. Register the network with it and pass
+    /// the heartbeat to `NetworkManager::set_heartbeat` before spawning it.
+    pub fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+
     /// Get bridge reference
     pub fn bridge(&self) -> &Arc<ConsensusEvmBridge> {
         &self.bridge
@@ -108,8 +152,7 @@ impl IntegratedNode {
                     .as_secs(),
             });
 
-            let mut net = network.write().await;
-            net.broadcast(msg).await
+            network.broadcast(msg).await
                 .map_err(|e| anyhow!("Failed to broadcast transaction: {}", e))?;
         }
 
@@ -132,7 +175,11 @@ impl IntegratedNode {
             *running = true;
         }
 
-        // Note: Proposal loop would be spawned here, but requires Send bounds on NetworkManager
+        if self.watchdog_task.is_none() {
+            self.watchdog_task = Some(self.watchdog.spawn());
+        }
+
+        // Note: Proposal loop would be spawned here (see `proposal_loop`)
         // For production, this would run in a separate task
         // Users can manually call propose_block when they're the leader
 
@@ -145,6 +192,10 @@ impl IntegratedNode {
         info!("Stopping integrated node {}", self.node_id);
         let mut running = self.running.write().await;
         *running = false;
+
+        if let Some(task) = self.watchdog_task.take() {
+            task.abort();
+        }
     }
 
     /// Proposal loop (runs in leader)
@@ -152,7 +203,7 @@ impl IntegratedNode {
         bridge: Arc<ConsensusEvmBridge>,
         interval: Duration,
         running: Arc<RwLock<bool>>,
-        network: Option<Arc<RwLock<NetworkManager>>>,
+        network: Option<NetworkHandle>,
     ) {
         let mut ticker = time::interval(interval);
         
@@ -194,8 +245,7 @@ impl IntegratedNode {
                                 .as_secs(),
                         });
                         
-                        let mut network_manager = net.write().await;
-                        if let Err(e) = network_manager.broadcast(msg).await {
+                        if let Err(e) = net.broadcast(msg).await {
                             error!("Failed to broadcast block: {}", e);
                         }
                     }
@@ -209,6 +259,13 @@ impl IntegratedNode {
 
     /// Handle incoming network event
     pub async fn handle_network_event(&self, event: NetworkEvent) -> Result<()> {
+        self.consensus_heartbeat.busy("network event");
+        let result = self.dispatch_network_event(event).await;
+        self.consensus_heartbeat.idle();
+        result
+    }
+
+    async fn dispatch_network_event(&self, event: NetworkEvent) -> Result<()> {
         match event {
             NetworkEvent::GossipReceived { message, .. } => {
                 self.handle_gossip_message(message).await?;
@@ -256,6 +313,13 @@ impl IntegratedNode {
                 debug!("Received QC gossip for view {}", qc.view);
                 // QCs are handled as part of block processing
             }
+            GossipMessage::ValidatorAnnouncement { .. } => {
+                // Consumed by the network layer
+            }
+            GossipMessage::MarketData { asset, .. } => {
+                debug!("Received market data for asset {}", asset);
+                // For market data consumers, not consensus
+            }
         }
         
         Ok(())
@@ -328,6 +392,40 @@ impl IntegratedNode {
         }
     }
 
+    /// Evaluate every component of the node
+    pub async fn health(&self) -> HealthReport {
+        let mut inputs = HealthInputs {
+            mempool_depth: self.bridge.mempool_stats().await.pending_count,
+            ..HealthInputs::default()
+        };
+        {
+            let consensus = self.bridge.consensus.read().await;
+            inputs.height = consensus.current_height();
+            inputs.committed_height = consensus
+                .committed_blocks()
+                .last()
+                .map_or(0, |block| block.height);
+            inputs.quorum_size = consensus.validator().quorum_size;
+            inputs.storage_error = consensus.storage().check_writable().err().map(|e| e.to_string());
+        }
+        if let Some(network) = &self.network {
+            // A stopped network loop reports as no network
+            inputs.validator_peers = network.health().await.ok().map(|health| health.validator_peers);
+        }
+
+        self.health.write().await.evaluate(&inputs, std::time::Instant::now())
+    }
+
+    /// Liveness probe: false if the node must be restarted
+    pub async fn liveness(&self) -> bool {
+        self.health().await.live
+    }
+
+    /// Readiness probe: false if the node should not serve traffic
+    pub async fn readiness(&self) -> bool {
+        self.health().await.ready
+    }
+
     /// Check if node is running
     pub async fn is_running(&self) -> bool {
         *self.running.read().await
@@ -348,6 +446,7 @@ pub struct NodeStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{test_key, test_sender};
     use alloy_primitives::{Address, U256};
     use consensus::storage::Storage;
     use rocksdb::DB;
@@ -373,9 +472,46 @@ mod tests {
     }
 
     fn create_test_tx(from_byte: u8, to_byte: u8, nonce: u64) -> Transaction {
-        let from = Address::repeat_byte(from_byte);
         let to = Address::repeat_byte(to_byte);
-        Transaction::transfer(from, to, U256::from(1000), nonce)
+        Transaction::transfer(test_sender(from_byte), to, U256::from(1000), nonce)
+            .sign(&test_key(from_byte))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_node_health_probes() {
+        let node = create_test_node(0, 4);
+        let report = node.health().await;
+        assert_eq!(report.component("storage").unwrap().detail, "writable");
+        assert_eq!(report.component("mempool").unwrap().status, crate::HealthStatus::Healthy);
+
+        // Without a network the node cannot reach quorum: alive but not ready
+        assert!(node.liveness().await);
+        assert!(!node.readiness().await);
+
+        // Mempool backlog over the limit
+        let node = create_test_node(0, 4).with_health_config(HealthConfig {
+            max_mempool_depth: 1,
+            ..HealthConfig::default()
+        });
+        node.submit_transaction(create_test_tx(1, 2, 0)).await.unwrap();
+        node.submit_transaction(create_test_tx(1, 2, 1)).await.unwrap();
+        let report = node.health().await;
+        assert!(report.live);
+        assert_eq!(report.component("mempool").unwrap().status, crate::HealthStatus::Unhealthy);
+    }
+
+    #[tokio::test]
+    async fn test_node_watchdog_tracks_subsystems() {
+        let node = create_test_node(0, 4);
+        let names: Vec<String> = node.watchdog().snapshot().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["executor".to_string(), "consensus".to_string()]);
+
+        node.handle_network_event(NetworkEvent::PartitionRecovered).await.unwrap();
+        let consensus = node.watchdog().snapshot().into_iter().find(|s| s.name == "consensus").unwrap();
+        assert!(consensus.idle);
+        assert!(consensus.beats > 0);
+        assert!(node.watchdog().check().await.is_empty());
     }
 
     #[tokio::test]
@@ -6,6 +6,7 @@
 mod tests {
     use crate::bridge::ConsensusEvmBridge;
     use crate::integration::IntegratedNode;
+    use crate::types::{test_key, test_sender};
     use crate::{EvmStateMachine, Mempool, Transaction};
     use alloy_primitives::{Address, U256};
     use consensus::crypto::bls::BLSKeyPair;
@@ -61,9 +62,10 @@ mod tests {
     }
 
     fn create_test_tx(from_byte: u8, to_byte: u8, nonce: u64) -> Transaction {
-        let from = Address::repeat_byte(from_byte);
         let to = Address::repeat_byte(to_byte);
-        Transaction::transfer(from, to, U256::from(1000), nonce)
+        Transaction::transfer(test_sender(from_byte), to, U256::from(1000), nonce)
+            .sign(&test_key(from_byte))
+            .unwrap()
     }
 
     #[tokio::test]
@@ -72,12 +74,8 @@ mod tests {
         let mut leader = create_test_node(1, 4);
         leader.start().await.unwrap();
 
-        // Create and fund sender account
-        let sender = Address::repeat_byte(0x01);
-        let receiver = Address::repeat_byte(0x02);
-        
         // Submit transaction
-        let tx = Transaction::transfer(sender, receiver, U256::from(1000), 0);
+        let tx = create_test_tx(0x01, 0x02, 0);
         leader.submit_transaction(tx).await.unwrap();
 
         // Verify transaction is in mempool
@@ -366,7 +364,7 @@ mod tests {
         // Each transaction should be deserializable
         for tx_bytes in &block.transactions {
             let tx: Transaction = serde_json::from_slice(tx_bytes).unwrap();
-            assert!(tx.from == Address::repeat_byte(0x01) || tx.from == Address::repeat_byte(0x03));
+            assert!(tx.from == test_sender(0x01) || tx.from == test_sender(0x03));
         }
     }
 
@@ -9,13 +9,21 @@
 // - Complete EVM state management
 
 pub mod bridge;
+pub mod cache;
 pub mod checkpoint;
+pub mod commitments;
 pub mod executor;
+pub mod fee_market;
+pub mod health;
 pub mod integration;
 pub mod mempool;
 pub mod precompiles;
+pub mod proposal;
+pub mod replica;
+pub mod rpc;
 pub mod storage;
 pub mod state_machine;
+pub mod trace;
 pub mod types;
 
 #[cfg(test)]
@@ -23,12 +31,29 @@ mod integration_tests;
 
 // Re-exports for convenience
 pub use bridge::{ConsensusEvmBridge, MempoolStats};
+pub use cache::{StateCacheConfig, StateCacheStats};
 pub use checkpoint::CheckpointManager;
 pub use executor::EvmExecutor;
+pub use fee_market::{FeeDestination, FeeMarket, FeeMarketConfig};
+pub use health::{ComponentHealth, HealthConfig, HealthMonitor, HealthReport, HealthStatus};
 pub use integration::{IntegratedNode, NodeStats};
-pub use mempool::Mempool;
-pub use precompiles::{get_precompile, is_precompile, PERP_PRECOMPILE, SPOT_PRECOMPILE};
+pub use mempool::{is_cancel_only, CancelLaneLimits, Mempool, Selection};
+pub use precompiles::collateral::{CollateralBridge, QueuedWithdrawal, WithdrawalBatch};
+pub use precompiles::oracle::PriceOracle;
+pub use precompiles::randomness::RandomnessBeacon;
+pub use precompiles::vault::VaultPrecompile;
+pub use precompiles::{
+    get_precompile, is_precompile, COLLATERAL_PRECOMPILE, MARGIN_PRECOMPILE, ORACLE_PRECOMPILE,
+    PERP_PRECOMPILE, RANDOMNESS_PRECOMPILE, SPOT_PRECOMPILE, VAULT_PRECOMPILE,
+};
+pub use proposal::{EvmTxValidator, ProposalBuilder, ProposalLimits};
+pub use replica::ReplicaNode;
+pub use rpc::{EthApi, RpcConfig, RpcServer};
 pub use state_machine::EvmStateMachine;
 pub use storage::EvmStorage;
-pub use types::{Account, Block, Receipt, StateSnapshot, StateTransition, Transaction};
+pub use trace::{CallFrame, StructLog, TraceConfig, TransactionTrace};
+pub use types::{
+    Account, AccountDiff, Block, BundleSimulation, Receipt, SimulatedTransaction, StateDiff,
+    StateSnapshot, StateTransition, Transaction,
+};
 
@@ -2,24 +2,106 @@
 //
 // Manages pending transactions awaiting block inclusion
 
+use crate::precompiles::spot::ISpot;
+use crate::precompiles::SPOT_PRECOMPILE;
+use crate::storage::EvmStorage;
 use crate::types::Transaction;
-use alloy_primitives::Address;
-use std::collections::{HashMap, VecDeque};
+use alloy_primitives::{keccak256, Address, B256, U256};
+use alloy_sol_types::SolCall;
+use k256::ecdsa::SigningKey;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+
+/// Gas price increase (percent) a same-nonce transaction needs to replace
+/// a pending one
+pub const REPLACEMENT_BUMP_PERCENT: u64 = 10;
+
+/// Rate limits of the gas-free cancellation lane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CancelLaneLimits {
+    /// Gas-free cancels admitted per account per block
+    pub per_account: usize,
+    /// Gas-free cancels admitted in total per block
+    pub per_block: usize,
+}
+
+impl Default for CancelLaneLimits {
+    fn default() -> Self {
+        Self {
+            per_account: 16,
+            per_block: 2_000,
+        }
+    }
+}
+
+/// Block builder decision on a candidate transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Include in the block
+    Include,
+    /// Drop from the mempool without including (e.g. already in a pending block)
+    Discard,
+    /// Leave in the mempool; the sender's later transactions are skipped
+    Defer,
+}
+
+/// Check if a transaction only cancels a resting order and may use the
+/// gas-free cancellation lane
+pub fn is_cancel_only(tx: &Transaction) -> bool {
+    tx.to == Some(SPOT_PRECOMPILE)
+        && tx.value.is_zero()
+        && tx.data.len() == 4 + 32
+        && tx.data.starts_with(&ISpot::cancelOrderCall::SELECTOR)
+}
 
 /// Simple transaction mempool
 /// 
 /// Stores pending transactions organized by sender address.
 /// Uses a simple round-robin selection strategy for fair transaction ordering.
+///
+/// Each sender's transactions are kept by nonce. Only those continuing the
+/// sender's next nonce without a gap are ready for a block; later ones stay
+/// queued until the gap is filled. The next nonce is the account nonce once
+/// known (see `set_account_nonce` and `set_state`), advanced as transactions are taken for
+/// blocks; for senders whose account nonce is unknown the lowest pending
+/// nonce is trusted. A transaction with the nonce of a pending one replaces
+/// it if it pays `REPLACEMENT_BUMP_PERCENT` more gas.
+///
+/// Order cancellations are admitted to a separate lane that ignores the
+/// minimum gas price and sender queue limits, so traders can always pull
+/// resting quotes during fee spikes. The lane is drained first when a block
+/// is built and is rate limited per account and per block.
 #[derive(Debug)]
 pub struct Mempool {
-    /// Pending transactions by sender address
-    pending: HashMap<Address, VecDeque<Transaction>>,
+    /// Pending transactions by sender address, then nonce
+    pending: HashMap<Address, BTreeMap<u64, Transaction>>,
+    /// Account nonces from committed blocks
+    account_nonces: HashMap<Address, u64>,
+    /// Committed EVM state account nonces are read from, if attached
+    state: Option<EvmStorage>,
+    /// Next nonce after the transactions taken for blocks since the last
+    /// commit
+    in_flight: HashMap<Address, u64>,
+    /// Base fee the next block is expected to charge
+    base_fee: U256,
     /// Maximum transactions per sender
     max_per_sender: usize,
     /// Maximum total transactions
     max_total: usize,
     /// Total transaction count
     total_count: usize,
+    /// Minimum gas price for admission outside the cancellation lane
+    min_gas_price: U256,
+    /// Pending gas-free cancellations, in arrival order
+    cancel_lane: VecDeque<Transaction>,
+    /// Gas-free cancellation limits
+    cancel_limits: CancelLaneLimits,
+    /// Gas-free cancellations admitted per account since the last block
+    cancels_admitted: HashMap<Address, usize>,
+    /// Gas-free cancellations admitted in total since the last block
+    cancels_admitted_total: usize,
+    /// Chain ID raw transactions must be signed for
+    chain_id: u64,
 }
 
 impl Mempool {
@@ -27,9 +109,19 @@ impl Mempool {
     pub fn new() -> Self {
         Self {
             pending: HashMap::new(),
+            account_nonces: HashMap::new(),
+            state: None,
+            in_flight: HashMap::new(),
+            base_fee: U256::ZERO,
             max_per_sender: 100,
             max_total: 10_000,
             total_count: 0,
+            min_gas_price: U256::ZERO,
+            cancel_lane: VecDeque::new(),
+            cancel_limits: CancelLaneLimits::default(),
+            cancels_admitted: HashMap::new(),
+            cancels_admitted_total: 0,
+            chain_id: 1,
         }
     }
 
@@ -39,12 +131,93 @@ impl Mempool {
             pending: HashMap::new(),
             max_per_sender,
             max_total,
-            total_count: 0,
+            ..Self::new()
         }
     }
 
-    /// Add a transaction to the mempool
+    /// Set the minimum gas price for admission (raised during fee spikes)
+    pub fn set_min_gas_price(&mut self, min_gas_price: U256) {
+        self.min_gas_price = min_gas_price;
+    }
+
+    /// Get the minimum gas price for admission
+    pub fn min_gas_price(&self) -> U256 {
+        self.min_gas_price
+    }
+
+    /// Read account nonces from committed EVM state, so transactions with a
+    /// nonce the account already used are refused
+    pub fn set_state(&mut self, state: EvmStorage) {
+        self.state = Some(state);
+    }
+
+    /// Set the chain ID raw transactions must be signed for
+    pub fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = chain_id;
+    }
+
+    /// Chain ID raw transactions must be signed for
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Set the base fee the next block charges, which `select` orders tips
+    /// against
+    pub fn set_base_fee(&mut self, base_fee: U256) {
+        self.base_fee = base_fee;
+    }
+
+    /// Get the base fee selection orders tips against
+    pub fn base_fee(&self) -> U256 {
+        self.base_fee
+    }
+
+    /// Set the gas-free cancellation lane limits
+    pub fn set_cancel_lane_limits(&mut self, limits: CancelLaneLimits) {
+        self.cancel_limits = limits;
+    }
+
+    /// Get the gas-free cancellation lane limits
+    pub fn cancel_lane_limits(&self) -> CancelLaneLimits {
+        self.cancel_limits
+    }
+
+    /// Add a transaction to the mempool.
+    ///
+    /// Cancellations within the lane quota are admitted gas-free; beyond it
+    /// they queue as regular transactions and must pay the minimum gas price.
+    /// Transactions must be signed by their sender.
     pub fn add(&mut self, tx: Transaction) -> Result<(), String> {
+        tx.verify_sender().map_err(|e| e.to_string())?;
+
+        if is_cancel_only(&tx) && self.cancel_quota_available(&tx.from) {
+            *self.cancels_admitted.entry(tx.from).or_default() += 1;
+            self.cancels_admitted_total += 1;
+            self.cancel_lane.push_back(tx);
+            return Ok(());
+        }
+
+        if tx.gas_price < self.min_gas_price {
+            return Err("Gas price below minimum".into());
+        }
+
+        if let Some(nonce) = self.account_nonce(&tx.from) {
+            if tx.nonce < nonce {
+                return Err(format!("Nonce too low: account is at {}", nonce));
+            }
+        }
+
+        // Same nonce: replace if paying enough more
+        if let Some(existing) = self.pending.get_mut(&tx.from).and_then(|queue| queue.get_mut(&tx.nonce)) {
+            let min_price = existing.gas_price
+                + existing.gas_price * U256::from(REPLACEMENT_BUMP_PERCENT) / U256::from(100);
+            if tx.gas_price <= existing.gas_price || tx.gas_price < min_price {
+                return Err("Replacement transaction underpriced".into());
+            }
+            *existing = tx;
+            return Ok(());
+        }
+
         // Check total limit
         if self.total_count >= self.max_total {
             return Err("Mempool full".into());
@@ -59,15 +232,146 @@ impl Mempool {
         }
 
         // Add transaction
-        queue.push_back(tx);
+        queue.insert(tx.nonce, tx);
         self.total_count += 1;
         
         Ok(())
     }
 
-    /// Get transactions for next block (round-robin across senders)
+    /// Add a signed raw transaction (see `Transaction::decode_raw`) for this
+    /// chain, returning its hash
+    pub fn add_raw(&mut self, raw: &[u8]) -> Result<B256, String> {
+        let tx = Transaction::decode_raw(raw).map_err(|e| e.to_string())?;
+        if tx.chain_id != self.chain_id {
+            return Err(format!("Chain ID {} instead of {}", tx.chain_id, self.chain_id));
+        }
+        self.add(tx)?;
+        Ok(keccak256(raw))
+    }
+
+    /// Sign a transaction with the sender's next nonce and add it,
+    /// returning the nonce
+    pub fn add_with_next_nonce(&mut self, mut tx: Transaction, key: &SigningKey) -> Result<u64, String> {
+        tx.nonce = self.next_nonce(&Address::from_private_key(key));
+        let nonce = tx.nonce;
+        self.add(tx.sign(key).map_err(|e| e.to_string())?)?;
+        Ok(nonce)
+    }
+
+    /// Nonce the sender's next transaction should use: the first one not
+    /// pending after its account nonce
+    pub fn next_nonce(&self, sender: &Address) -> u64 {
+        let mut next = self.base_nonce(sender).unwrap_or(0);
+        if let Some(queue) = self.pending.get(sender) {
+            for nonce in queue.range(next..).map(|(nonce, _)| *nonce) {
+                if nonce != next {
+                    break;
+                }
+                next += 1;
+            }
+        }
+        next
+    }
+
+    /// Record a sender's account nonce from committed state, dropping its
+    /// transactions below it
+    pub fn set_account_nonce(&mut self, sender: Address, nonce: u64) {
+        self.account_nonces.insert(sender, nonce);
+        if self.in_flight.get(&sender).is_some_and(|next| *next <= nonce) {
+            self.in_flight.remove(&sender);
+        }
+        if let Some(queue) = self.pending.get_mut(&sender) {
+            let kept = queue.split_off(&nonce);
+            self.total_count -= queue.len();
+            *queue = kept;
+        }
+        self.pending.retain(|_, q| !q.is_empty());
+    }
+
+    /// Advance account nonces past the transactions of a committed block.
+    /// Senders' transactions still in uncommitted blocks become ready once
+    /// those commit too.
+    pub fn on_committed(&mut self, transactions: &[Transaction]) {
+        let mut committed: HashMap<Address, u64> = HashMap::new();
+        for tx in transactions {
+            let next = committed.entry(tx.from).or_default();
+            *next = (*next).max(tx.nonce + 1);
+        }
+        self.in_flight.clear();
+        for (sender, next) in committed {
+            let nonce = self.account_nonces.get(&sender).map_or(next, |known| (*known).max(next));
+            self.set_account_nonce(sender, nonce);
+        }
+        // Senders with nothing pending are read from state again when they
+        // return; without state their nonces are kept
+        if self.state.is_some() {
+            let pending = &self.pending;
+            self.account_nonces.retain(|sender, _| pending.contains_key(sender));
+        }
+    }
+
+    /// Transactions queued behind a nonce gap
+    pub fn queued_count(&self) -> usize {
+        self.pending
+            .iter()
+            .map(|(sender, queue)| queue.len() - self.ready_len(sender, queue))
+            .sum()
+    }
+
+    /// Sender's account nonce, if known: the later of the one committed
+    /// blocks advanced it to and the one in the attached state
+    fn account_nonce(&self, sender: &Address) -> Option<u64> {
+        let committed = self.state.as_ref().and_then(|state| match state.get_account(sender) {
+            Ok(account) => Some(account.map_or(0, |account| account.nonce)),
+            Err(e) => {
+                log::warn!("Failed to read the nonce of {}: {}", sender, e);
+                None
+            }
+        });
+        match (self.account_nonces.get(sender).copied(), committed) {
+            (Some(known), Some(committed)) => Some(known.max(committed)),
+            (known, committed) => known.or(committed),
+        }
+    }
+
+    /// Nonce the sender's next ready transaction must have, if known
+    fn base_nonce(&self, sender: &Address) -> Option<u64> {
+        self.in_flight
+            .get(sender)
+            .copied()
+            .or_else(|| self.account_nonce(sender))
+            .or_else(|| self.pending.get(sender)?.keys().next().copied())
+    }
+
+    /// Sender's transaction that may go into a block next
+    fn ready_head(&self, sender: &Address) -> Option<&Transaction> {
+        let base = self.base_nonce(sender)?;
+        self.pending.get(sender)?.get(&base)
+    }
+
+    fn ready_len(&self, sender: &Address, queue: &BTreeMap<u64, Transaction>) -> usize {
+        let Some(base) = self.base_nonce(sender) else {
+            return 0;
+        };
+        queue.keys().skip_while(|nonce| **nonce < base).zip(base..).take_while(|(nonce, next)| **nonce == *next).count()
+    }
+
+    /// Remove the sender's ready transaction, advancing its next nonce
+    fn take_ready(&mut self, sender: &Address) -> Option<Transaction> {
+        let base = self.base_nonce(sender)?;
+        let tx = self.pending.get_mut(sender)?.remove(&base)?;
+        self.in_flight.insert(*sender, base + 1);
+        self.total_count -= 1;
+        Some(tx)
+    }
+
+    /// Get transactions for next block: gas-free cancellations first, then
+    /// round-robin across senders. Starts a new cancellation quota period.
     pub fn get_transactions(&mut self, max_count: usize) -> Vec<Transaction> {
-        let mut txs = Vec::new();
+        let lane_count = self.cancel_lane.len().min(max_count);
+        let mut txs: Vec<Transaction> = self.cancel_lane.drain(..lane_count).collect();
+        self.cancels_admitted.clear();
+        self.cancels_admitted_total = 0;
         
         // Round-robin across senders for fairness
         while txs.len() < max_count && self.total_count > 0 {
@@ -77,15 +381,12 @@ impl Mempool {
             let senders: Vec<Address> = self.pending.keys().copied().collect();
             
             for sender in senders {
-                if let Some(queue) = self.pending.get_mut(&sender) {
-                    if let Some(tx) = queue.pop_front() {
-                        txs.push(tx);
-                        self.total_count -= 1;
-                        found = true;
-                        
-                        if txs.len() >= max_count {
-                            break;
-                        }
+                if let Some(tx) = self.take_ready(&sender) {
+                    txs.push(tx);
+                    found = true;
+                    
+                    if txs.len() >= max_count {
+                        break;
                     }
                 }
             }
@@ -101,45 +402,115 @@ impl Mempool {
         txs
     }
 
+    /// Take transactions for a block as `decide` accepts them: gas-free
+    /// cancellations first in arrival order, then by effective tip under the
+    /// base fee across senders and in nonce order within each sender (ties
+    /// go to the lower sender address). Transactions behind a nonce gap or
+    /// whose max fee is below the base fee are left queued.
+    /// Starts a new cancellation quota period.
+    pub fn select(&mut self, mut decide: impl FnMut(&Transaction) -> Selection) -> Vec<Transaction> {
+        let mut txs = Vec::new();
+        let mut deferred = VecDeque::new();
+        while let Some(tx) = self.cancel_lane.pop_front() {
+            match decide(&tx) {
+                Selection::Include => txs.push(tx),
+                Selection::Discard => {}
+                Selection::Defer => deferred.push_back(tx),
+            }
+        }
+        self.cancel_lane = deferred;
+        self.cancels_admitted.clear();
+        self.cancels_admitted_total = 0;
+
+        let mut heads = BinaryHeap::new();
+        for sender in self.pending.keys() {
+            if let Some(tip) = self.ready_head(sender).and_then(|tx| tx.effective_tip(self.base_fee)) {
+                heads.push((tip, Reverse(*sender)));
+            }
+        }
+
+        while let Some((_, Reverse(sender))) = heads.pop() {
+            let Some(tx) = self.ready_head(&sender) else {
+                continue;
+            };
+            let selection = decide(tx);
+            if selection == Selection::Defer {
+                continue;
+            }
+            // Discarded transactions are already in a pending block
+            let tx = self.take_ready(&sender);
+            if selection == Selection::Include {
+                txs.extend(tx);
+            }
+            if let Some(tip) = self.ready_head(&sender).and_then(|tx| tx.effective_tip(self.base_fee)) {
+                heads.push((tip, Reverse(sender)));
+            }
+        }
+
+        self.pending.retain(|_, q| !q.is_empty());
+        txs
+    }
+
     /// Get pending transaction count
     pub fn len(&self) -> usize {
-        self.total_count
+        self.total_count + self.cancel_lane.len()
     }
 
     /// Check if mempool is empty
     pub fn is_empty(&self) -> bool {
-        self.total_count == 0
+        self.len() == 0
+    }
+
+    /// Get pending gas-free cancellation count
+    pub fn cancel_lane_len(&self) -> usize {
+        self.cancel_lane.len()
     }
 
     /// Get pending count for a specific sender
     pub fn sender_count(&self, address: &Address) -> usize {
         self.pending.get(address).map_or(0, |q| q.len())
+            + self.cancel_lane.iter().filter(|tx| tx.from == *address).count()
     }
 
     /// Clear all pending transactions
     pub fn clear(&mut self) {
         self.pending.clear();
+        self.account_nonces.clear();
+        self.in_flight.clear();
         self.total_count = 0;
+        self.cancel_lane.clear();
     }
 
     /// Remove all transactions from a specific sender
     pub fn remove_sender(&mut self, address: &Address) -> usize {
+        let lane_before = self.cancel_lane.len();
+        self.cancel_lane.retain(|tx| tx.from != *address);
+        let lane_removed = lane_before - self.cancel_lane.len();
+
         if let Some(queue) = self.pending.remove(address) {
             let count = queue.len();
             self.total_count -= count;
-            count
+            count + lane_removed
         } else {
-            0
+            lane_removed
         }
     }
 
     /// Get a snapshot of all pending transactions (for inspection)
     pub fn all_transactions(&self) -> Vec<Transaction> {
-        self.pending
-            .values()
-            .flat_map(|queue| queue.iter().cloned())
+        self.cancel_lane
+            .iter()
+            .cloned()
+            .chain(self.pending.values().flat_map(|queue| queue.values().cloned()))
             .collect()
     }
+
+    /// Check if a sender may submit another gas-free cancellation this block
+    fn cancel_quota_available(&self, sender: &Address) -> bool {
+        self.cancels_admitted_total < self.cancel_limits.per_block
+            && self.cancel_lane.len() < self.cancel_limits.per_block
+            && self.cancels_admitted.get(sender).copied().unwrap_or(0) < self.cancel_limits.per_account
+    }
 }
 
 impl Default for Mempool {
@@ -151,12 +522,16 @@ impl Default for Mempool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use alloy_primitives::U256;
+    use crate::types::{test_key, test_sender};
+    use alloy_primitives::Bytes;
 
-    fn create_test_tx(from_byte: u8, nonce: u64) -> Transaction {
-        let from = Address::repeat_byte(from_byte);
+    fn unsigned_tx(from_byte: u8, nonce: u64) -> Transaction {
         let to = Address::repeat_byte(0xff);
-        Transaction::transfer(from, to, U256::from(1000), nonce)
+        Transaction::transfer(test_sender(from_byte), to, U256::from(1000), nonce)
+    }
+
+    fn create_test_tx(from_byte: u8, nonce: u64) -> Transaction {
+        unsigned_tx(from_byte, nonce).sign(&test_key(from_byte)).unwrap()
     }
 
     #[test]
@@ -276,7 +651,7 @@ mod tests {
     #[test]
     fn test_sender_count() {
         let mut mempool = Mempool::new();
-        let addr = Address::repeat_byte(0x01);
+        let addr = test_sender(0x01);
         
         assert_eq!(mempool.sender_count(&addr), 0);
         
@@ -314,7 +689,7 @@ mod tests {
         
         assert_eq!(mempool.len(), 10);
         
-        let addr = Address::repeat_byte(0x01);
+        let addr = test_sender(0x01);
         let removed = mempool.remove_sender(&addr);
         
         assert_eq!(removed, 5);
@@ -346,7 +721,7 @@ mod tests {
         assert_eq!(txs.len(), 3);
         
         // Empty queues should be removed
-        let addr = Address::repeat_byte(0x01);
+        let addr = test_sender(0x01);
         assert_eq!(mempool.sender_count(&addr), 0);
     }
 
@@ -371,13 +746,223 @@ mod tests {
         
         assert_eq!(mempool.len(), 10);
         
-        let addr1 = Address::repeat_byte(0x01);
-        let addr2 = Address::repeat_byte(0x02);
-        let addr3 = Address::repeat_byte(0x03);
+        let addr1 = test_sender(0x01);
+        let addr2 = test_sender(0x02);
+        let addr3 = test_sender(0x03);
         
         assert_eq!(mempool.sender_count(&addr1), 3);
         assert_eq!(mempool.sender_count(&addr2), 5);
         assert_eq!(mempool.sender_count(&addr3), 2);
     }
-}
 
+    fn create_cancel_tx(from_byte: u8, order_id: u64) -> Transaction {
+        let call = ISpot::cancelOrderCall { orderId: U256::from(order_id) };
+        let mut tx = Transaction::call(
+            test_sender(from_byte),
+            SPOT_PRECOMPILE,
+            call.abi_encode().into(),
+            order_id,
+        );
+        tx.gas_price = U256::ZERO;
+        tx.sign(&test_key(from_byte)).unwrap()
+    }
+
+    #[test]
+    fn test_nonce_gaps_are_queued_until_filled() {
+        let mut mempool = Mempool::new();
+        let sender = test_sender(0x01);
+        mempool.set_account_nonce(sender, 5);
+
+        assert!(mempool.add(create_test_tx(0x01, 4)).is_err());
+        mempool.add(create_test_tx(0x01, 5)).unwrap();
+        mempool.add(create_test_tx(0x01, 7)).unwrap();
+        assert_eq!(mempool.next_nonce(&sender), 6);
+        assert_eq!(mempool.queued_count(), 1);
+
+        // Nonce 7 waits behind the gap
+        let txs = mempool.get_transactions(10);
+        assert_eq!(txs.iter().map(|tx| tx.nonce).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(mempool.len(), 1);
+
+        // Filling it releases both
+        assert_eq!(mempool.add_with_next_nonce(unsigned_tx(0x01, 0), &test_key(0x01)).unwrap(), 6);
+        let txs = mempool.select(|_| Selection::Include);
+        assert_eq!(txs.iter().map(|tx| tx.nonce).collect::<Vec<_>>(), vec![6, 7]);
+        assert!(mempool.is_empty());
+        assert_eq!(mempool.next_nonce(&sender), 8);
+    }
+
+    #[test]
+    fn test_replacement_by_fee_and_commit_pruning() {
+        let mut mempool = Mempool::new();
+        let sender = test_sender(0x01);
+        let priced = |nonce, price: u64| {
+            let mut tx = unsigned_tx(0x01, nonce);
+            tx.gas_price = U256::from(price);
+            tx.sign(&test_key(0x01)).unwrap()
+        };
+
+        mempool.add(priced(0, 100)).unwrap();
+        mempool.add(priced(1, 100)).unwrap();
+        mempool.add(priced(2, 100)).unwrap();
+        assert!(mempool.add(priced(1, 109)).is_err());
+        mempool.add(priced(1, 110)).unwrap();
+        assert_eq!(mempool.len(), 3);
+        assert!(mempool.all_transactions().iter().any(|tx| tx.nonce == 1 && tx.gas_price == U256::from(110)));
+
+        // Another node's block commits nonces 0 and 1
+        mempool.on_committed(&[priced(0, 1), priced(1, 1)]);
+        assert_eq!(mempool.len(), 1);
+        assert_eq!(mempool.next_nonce(&sender), 3);
+        assert!(mempool.add(priced(1, 500)).is_err());
+    }
+
+    #[test]
+    fn test_committed_nonces_outlive_empty_queues_and_follow_state() {
+        let mut mempool = Mempool::new();
+        mempool.add(create_test_tx(0x01, 0)).unwrap();
+        let txs = mempool.get_transactions(1);
+        mempool.on_committed(&txs);
+
+        // Nothing is pending, yet the committed nonce is still refused
+        assert!(mempool.add(create_test_tx(0x01, 0)).is_err());
+        assert_eq!(mempool.next_nonce(&test_sender(0x01)), 1);
+
+        // With state attached, account nonces are read from it
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = rocksdb::DB::open_default(temp_dir.path()).unwrap();
+        let state = EvmStorage::new(std::sync::Arc::new(db));
+        let account = crate::types::Account { nonce: 3, ..Default::default() };
+        state.set_account(&test_sender(0x02), &account).unwrap();
+        mempool.set_state(state);
+        assert!(mempool.add(create_test_tx(0x02, 2)).is_err());
+        mempool.add(create_test_tx(0x02, 3)).unwrap();
+        assert_eq!(mempool.next_nonce(&test_sender(0x02)), 4);
+    }
+
+    #[test]
+    fn test_select_orders_by_effective_tip() {
+        let mut mempool = Mempool::new();
+        mempool.set_base_fee(U256::from(100));
+        let fees = |from_byte, max_fee: u64, tip: u64| {
+            unsigned_tx(from_byte, 0)
+                .with_fees(U256::from(max_fee), U256::from(tip))
+                .sign(&test_key(from_byte))
+                .unwrap()
+        };
+
+        // Highest max fee, but its tip is capped at 5 above the base fee
+        mempool.add(fees(0x01, 105, 50)).unwrap();
+        mempool.add(fees(0x02, 200, 20)).unwrap();
+        // Legacy: the whole gas price above the base fee is tip
+        let mut legacy = unsigned_tx(0x03, 0);
+        legacy.gas_price = U256::from(110);
+        mempool.add(legacy.sign(&test_key(0x03)).unwrap()).unwrap();
+        // Cannot pay the base fee
+        mempool.add(fees(0x04, 99, 99)).unwrap();
+
+        let txs = mempool.select(|_| Selection::Include);
+        let senders: Vec<Address> = txs.iter().map(|tx| tx.from).collect();
+        assert_eq!(senders, vec![test_sender(0x02), test_sender(0x03), test_sender(0x01)]);
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_raw_transactions_are_authenticated() {
+        let key = k256::ecdsa::SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let mut mempool = Mempool::new();
+        mempool.set_chain_id(7);
+
+        let mut tx = unsigned_tx(0x01, 0);
+        tx.chain_id = 7;
+        let signed = tx.sign(&key).unwrap();
+        let raw = signed.encode_raw().unwrap();
+        assert_eq!(mempool.add_raw(&raw).unwrap(), keccak256(&raw));
+        assert_eq!(mempool.all_transactions()[0].from, Address::from_private_key(&key));
+
+        // Signed for another chain
+        let mut other_chain = unsigned_tx(0x01, 1);
+        other_chain.chain_id = 8;
+        let raw = other_chain.sign(&key).unwrap().encode_raw().unwrap();
+        assert!(mempool.add_raw(&raw).is_err());
+
+        // Claims a sender that did not sign it
+        let mut forged = signed.clone();
+        forged.nonce = 1;
+        forged.from = Address::repeat_byte(0x01);
+        assert!(mempool.add(forged).is_err());
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_cancel_lane_bypasses_fee_spike_and_full_queue() {
+        let mut mempool = Mempool::with_limits(2, 100);
+        mempool.set_min_gas_price(U256::from(100));
+
+        // Regular transactions are priced out and the sender queue fills up
+        assert!(mempool.add(create_test_tx(0x01, 0)).is_err());
+        mempool.set_min_gas_price(U256::ZERO);
+        mempool.add(create_test_tx(0x01, 0)).unwrap();
+        mempool.add(create_test_tx(0x01, 1)).unwrap();
+        mempool.set_min_gas_price(U256::from(100));
+
+        // Cancels still get in, and are included first
+        mempool.add(create_cancel_tx(0x01, 7)).unwrap();
+        assert_eq!(mempool.len(), 3);
+        assert_eq!(mempool.cancel_lane_len(), 1);
+
+        let txs = mempool.get_transactions(1);
+        assert!(is_cancel_only(&txs[0]));
+        assert_eq!(mempool.cancel_lane_len(), 0);
+    }
+
+    #[test]
+    fn test_cancel_lane_rate_limits() {
+        let mut mempool = Mempool::new();
+        mempool.set_cancel_lane_limits(CancelLaneLimits { per_account: 2, per_block: 3 });
+        mempool.set_min_gas_price(U256::from(100));
+
+        mempool.add(create_cancel_tx(0x01, 1)).unwrap();
+        mempool.add(create_cancel_tx(0x01, 2)).unwrap();
+        // Over the account quota the cancel must pay for gas
+        assert!(mempool.add(create_cancel_tx(0x01, 3)).is_err());
+        let mut paid = create_cancel_tx(0x01, 3);
+        paid.gas_price = U256::from(100);
+        mempool.add(paid.sign(&test_key(0x01)).unwrap()).unwrap();
+
+        mempool.add(create_cancel_tx(0x02, 1)).unwrap();
+        // Block quota exhausted
+        assert!(mempool.add(create_cancel_tx(0x03, 1)).is_err());
+        assert_eq!(mempool.cancel_lane_len(), 3);
+
+        // Quotas reset with the next block
+        assert_eq!(mempool.get_transactions(10).len(), 4);
+        mempool.add(create_cancel_tx(0x03, 1)).unwrap();
+
+        // Only cancellation calls qualify
+        let mut place = create_cancel_tx(0x04, 1);
+        place.data = Bytes::from(vec![0u8; 36]);
+        assert!(!is_cancel_only(&place));
+        assert!(mempool.add(place.sign(&test_key(0x04)).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_unsigned_and_spoofed_transactions_are_rejected() {
+        let mut mempool = Mempool::new();
+
+        let unsigned = unsigned_tx(0x01, 0);
+        assert!(mempool.add(unsigned).unwrap_err().contains("not signed"));
+
+        // Signed by one key but claiming another sender
+        let mut spoofed = create_test_tx(0x01, 0);
+        spoofed.from = test_sender(0x02);
+        assert!(mempool.add(spoofed).is_err());
+
+        // Cancels take the gas-free lane only once authenticated
+        let mut cancel = create_cancel_tx(0x01, 1);
+        cancel.signature = None;
+        assert!(mempool.add(cancel).is_err());
+        assert!(mempool.is_empty());
+        assert_eq!(mempool.cancel_lane_len(), 0);
+    }
+}
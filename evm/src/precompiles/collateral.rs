@@ -0,0 +1,497 @@
+use super::COLLATERAL_PRECOMPILE;
+use crate::storage::EvmStorage;
+use alloy_primitives::{Address, Bytes, I256, U256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use anyhow::{anyhow, Result};
+use openliquid_core::{AssetId, CoreStateMachine};
+use revm::{
+    db::{AccountState, CacheDB},
+    Database,
+};
+use std::sync::{Arc, RwLock};
+
+// Define Solidity interface using alloy
+sol! {
+    /// Collateral bridge between EVM balances and core margin accounts
+    interface ICollateral {
+        /// Move native balance from the caller's EVM account into core collateral
+        /// @param amount Amount to move
+        /// @return success True if the deposit was applied
+        function deposit(uint256 amount) external returns (bool success);
+
+        /// Move core collateral back to the caller's EVM account, queued
+        /// until the withdrawal delay has passed in finalized blocks
+        /// @param amount Amount to move
+        /// @return success True if the withdrawal was applied or queued
+        function withdraw(uint256 amount) external returns (bool success);
+
+        /// Cancel one of the caller's queued withdrawals
+        /// @param id Withdrawal ID
+        /// @return success True if the withdrawal was cancelled
+        function cancelWithdrawal(uint256 id) external returns (bool success);
+
+        /// Get the IDs of a user's queued withdrawals
+        /// @param user The account
+        /// @return ids Queued withdrawal IDs, oldest first
+        function withdrawalIds(address user) external view returns (uint256[] memory ids);
+
+        /// Get core collateral of a user
+        /// @param user The account
+        /// @return amount Deposited collateral in core
+        function collateralOf(address user) external view returns (uint256 amount);
+    }
+}
+
+/// Gas costs for operations
+const DEPOSIT_GAS: u64 = 40_000;
+const WITHDRAW_GAS: u64 = 40_000;
+const COLLATERAL_OF_GAS: u64 = 3_000;
+const CANCEL_WITHDRAWAL_GAS: u64 = 10_000;
+const WITHDRAWAL_IDS_GAS: u64 = 3_000;
+
+/// System a ledger entry applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerSide {
+    /// User's native EVM balance
+    Evm,
+    /// User's core collateral account
+    Core,
+}
+
+/// One leg of a bridge transfer.
+///
+/// Every transfer records one EVM and one core entry with the same sequence
+/// number and opposite deltas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerEntry {
+    pub seq: u64,
+    pub block_number: u64,
+    pub user: Address,
+    pub side: LedgerSide,
+    /// Signed balance change on `side`
+    pub delta: I256,
+}
+
+/// Withdrawal waiting for its delay to pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedWithdrawal {
+    pub id: u64,
+    pub user: Address,
+    pub amount: U256,
+    /// Block the withdrawal was requested in
+    pub requested_at: u64,
+}
+
+/// Withdrawals settled together when a block is finalized
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WithdrawalBatch {
+    pub block_number: u64,
+    /// Withdrawals paid out to EVM balances
+    pub settled: Vec<QueuedWithdrawal>,
+    /// Withdrawals core rejected at settlement, with the reason
+    pub failed: Vec<(QueuedWithdrawal, String)>,
+}
+
+/// Bridge state captured by `CollateralBridge::snapshot`
+#[derive(Debug, Clone)]
+pub struct BridgeSnapshot {
+    ledger_len: usize,
+    bridged_total: U256,
+    next_seq: u64,
+    withdrawal_queue: Vec<QueuedWithdrawal>,
+    next_withdrawal_id: u64,
+}
+
+/// Moves balances between EVM accounts and core collateral.
+///
+/// Deposited EVM balance is held in escrow at `COLLATERAL_PRECOMPILE`, so
+/// the escrow balance always equals the net collateral bridged into core.
+/// Called by the executor (not through `get_precompile`) since it needs
+/// access to EVM account state.
+///
+/// With a withdrawal delay set, withdrawals are queued and settled once
+/// the delay has passed in finalized blocks, giving the risk engine a
+/// window to review them and users a window to cancel.
+pub struct CollateralBridge {
+    /// Core state machine holding collateral accounts
+    core: Arc<RwLock<CoreStateMachine>>,
+    /// Core asset credited for bridged native balance
+    collateral_asset: AssetId,
+    /// Both legs of every transfer, in execution order
+    ledger: Vec<LedgerEntry>,
+    /// Net amount bridged into core (deposits - withdrawals)
+    bridged_total: U256,
+    /// Next transfer sequence number
+    next_seq: u64,
+    /// Finalized blocks a withdrawal waits before settling (0 = immediate)
+    withdrawal_delay: u64,
+    /// Queued withdrawals in request order
+    withdrawal_queue: Vec<QueuedWithdrawal>,
+    /// Next withdrawal ID
+    next_withdrawal_id: u64,
+}
+
+impl CollateralBridge {
+    pub fn new(core: Arc<RwLock<CoreStateMachine>>, collateral_asset: AssetId) -> Self {
+        Self {
+            core,
+            collateral_asset,
+            ledger: Vec::new(),
+            bridged_total: U256::ZERO,
+            next_seq: 1,
+            withdrawal_delay: 0,
+            withdrawal_queue: Vec::new(),
+            next_withdrawal_id: 1,
+        }
+    }
+
+    /// Set the number of finalized blocks a withdrawal waits before settling
+    pub fn set_withdrawal_delay(&mut self, blocks: u64) {
+        self.withdrawal_delay = blocks;
+    }
+
+    /// Finalized blocks a withdrawal waits before settling
+    pub fn withdrawal_delay(&self) -> u64 {
+        self.withdrawal_delay
+    }
+
+    /// Queued withdrawals in request order
+    pub fn withdrawal_queue(&self) -> &[QueuedWithdrawal] {
+        &self.withdrawal_queue
+    }
+
+    /// Total amount a user has queued for withdrawal
+    pub fn queued_amount(&self, user: &Address) -> U256 {
+        self.withdrawal_queue
+            .iter()
+            .filter(|w| w.user == *user)
+            .fold(U256::ZERO, |acc, w| acc + w.amount)
+    }
+
+    /// Cancel a queued withdrawal of `user`
+    pub fn cancel_withdrawal(&mut self, user: Address, id: u64) -> Result<QueuedWithdrawal> {
+        let index = self
+            .withdrawal_queue
+            .iter()
+            .position(|w| w.id == id && w.user == user)
+            .ok_or_else(|| anyhow!("No queued withdrawal {} for caller", id))?;
+        Ok(self.withdrawal_queue.remove(index))
+    }
+
+    /// Settle every queued withdrawal whose delay has passed by
+    /// `finalized_height`, in request order
+    ///
+    /// Core re-checks balance and margin at settlement; a withdrawal it
+    /// rejects is dropped from the queue and reported as failed.
+    pub fn settle_withdrawals(
+        &mut self,
+        db: &mut CacheDB<EvmStorage>,
+        finalized_height: u64,
+    ) -> Result<WithdrawalBatch> {
+        let delay = self.withdrawal_delay;
+        let (due, waiting) = std::mem::take(&mut self.withdrawal_queue)
+            .into_iter()
+            .partition(|w| w.requested_at.saturating_add(delay) <= finalized_height);
+        self.withdrawal_queue = waiting;
+
+        let mut batch = WithdrawalBatch { block_number: finalized_height, ..Default::default() };
+        for withdrawal in due {
+            match self.withdraw_impl(db, withdrawal.user, withdrawal.amount, finalized_height) {
+                Ok(()) => batch.settled.push(withdrawal),
+                Err(e) => batch.failed.push((withdrawal, e.to_string())),
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Capture the bridge's books so `restore` can roll them back
+    pub fn snapshot(&self) -> BridgeSnapshot {
+        BridgeSnapshot {
+            ledger_len: self.ledger.len(),
+            bridged_total: self.bridged_total,
+            next_seq: self.next_seq,
+            withdrawal_queue: self.withdrawal_queue.clone(),
+            next_withdrawal_id: self.next_withdrawal_id,
+        }
+    }
+
+    /// Roll the bridge's books back to `snapshot`
+    ///
+    /// EVM balances and the escrow are restored with the executor's cache,
+    /// and the core collateral transfers moved with a core checkpoint.
+    pub fn restore(&mut self, snapshot: &BridgeSnapshot) {
+        self.ledger.truncate(snapshot.ledger_len);
+        self.bridged_total = snapshot.bridged_total;
+        self.next_seq = snapshot.next_seq;
+        self.withdrawal_queue = snapshot.withdrawal_queue.clone();
+        self.next_withdrawal_id = snapshot.next_withdrawal_id;
+    }
+
+    /// Get all ledger entries
+    pub fn ledger(&self) -> &[LedgerEntry] {
+        &self.ledger
+    }
+
+    /// Net amount bridged into core
+    pub fn bridged_total(&self) -> U256 {
+        self.bridged_total
+    }
+
+    /// Core asset used as collateral
+    pub fn collateral_asset(&self) -> AssetId {
+        self.collateral_asset
+    }
+
+    /// Core state machine holding collateral accounts
+    pub fn core(&self) -> &Arc<RwLock<CoreStateMachine>> {
+        &self.core
+    }
+
+    /// Execute a bridge call against the executor's EVM state
+    pub fn call(
+        &mut self,
+        db: &mut CacheDB<EvmStorage>,
+        input: &Bytes,
+        gas_limit: u64,
+        caller: Address,
+        block_number: u64,
+    ) -> Result<(Bytes, u64)> {
+        if input.len() < 4 {
+            return Err(anyhow!("Input too short"));
+        }
+
+        // Route based on selector
+        match &input[..4] {
+            // deposit(uint256)
+            sel if sel == ICollateral::depositCall::SELECTOR => {
+                if DEPOSIT_GAS > gas_limit {
+                    return Err(anyhow!("Out of gas"));
+                }
+                let call = ICollateral::depositCall::abi_decode(input, false)?;
+                self.deposit_impl(db, caller, call.amount, block_number)?;
+                Ok((Bytes::from(true.abi_encode()), DEPOSIT_GAS))
+            }
+
+            // withdraw(uint256)
+            sel if sel == ICollateral::withdrawCall::SELECTOR => {
+                if WITHDRAW_GAS > gas_limit {
+                    return Err(anyhow!("Out of gas"));
+                }
+                let call = ICollateral::withdrawCall::abi_decode(input, false)?;
+                self.request_withdrawal(db, caller, call.amount, block_number)?;
+                Ok((Bytes::from(true.abi_encode()), WITHDRAW_GAS))
+            }
+
+            // cancelWithdrawal(uint256)
+            sel if sel == ICollateral::cancelWithdrawalCall::SELECTOR => {
+                if CANCEL_WITHDRAWAL_GAS > gas_limit {
+                    return Err(anyhow!("Out of gas"));
+                }
+                let call = ICollateral::cancelWithdrawalCall::abi_decode(input, false)?;
+                let id = u64::try_from(call.id).map_err(|_| anyhow!("Invalid withdrawal ID"))?;
+                self.cancel_withdrawal(caller, id)?;
+                Ok((Bytes::from(true.abi_encode()), CANCEL_WITHDRAWAL_GAS))
+            }
+
+            // withdrawalIds(address)
+            sel if sel == ICollateral::withdrawalIdsCall::SELECTOR => {
+                if WITHDRAWAL_IDS_GAS > gas_limit {
+                    return Err(anyhow!("Out of gas"));
+                }
+                let call = ICollateral::withdrawalIdsCall::abi_decode(input, false)?;
+                let ids: Vec<U256> = self
+                    .withdrawal_queue
+                    .iter()
+                    .filter(|w| w.user == call.user)
+                    .map(|w| U256::from(w.id))
+                    .collect();
+                Ok((Bytes::from(ids.abi_encode()), WITHDRAWAL_IDS_GAS))
+            }
+
+            // collateralOf(address)
+            sel if sel == ICollateral::collateralOfCall::SELECTOR => {
+                if COLLATERAL_OF_GAS > gas_limit {
+                    return Err(anyhow!("Out of gas"));
+                }
+                let call = ICollateral::collateralOfCall::abi_decode(input, false)?;
+                let amount = self.core_balance(&call.user);
+                Ok((Bytes::from(amount.abi_encode()), COLLATERAL_OF_GAS))
+            }
+
+            _ => Err(anyhow!("Unknown function selector")),
+        }
+    }
+
+    /// Withdraw now, or queue the withdrawal when a delay is set
+    pub(crate) fn request_withdrawal(
+        &mut self,
+        db: &mut CacheDB<EvmStorage>,
+        user: Address,
+        amount: U256,
+        block_number: u64,
+    ) -> Result<()> {
+        if self.withdrawal_delay == 0 {
+            self.withdraw_impl(db, user, amount, block_number)
+        } else {
+            self.queue_withdrawal(user, amount, block_number).map(|_| ())
+        }
+    }
+
+    pub(crate) fn deposit_impl(
+        &mut self,
+        db: &mut CacheDB<EvmStorage>,
+        user: Address,
+        amount: U256,
+        block_number: u64,
+    ) -> Result<()> {
+        let delta = Self::validate_amount(amount)?;
+        let evm_before = evm_balance(db, user)?;
+        if evm_before < amount {
+            return Err(anyhow!("Insufficient EVM balance"));
+        }
+        let core_before = self.core_balance(&user);
+
+        self.core
+            .write()
+            .unwrap()
+            .deposit_collateral(user, self.collateral_asset, amount)?;
+        set_evm_balance(db, user, evm_before - amount)?;
+        let escrow = evm_balance(db, COLLATERAL_PRECOMPILE)?;
+        set_evm_balance(db, COLLATERAL_PRECOMPILE, escrow + amount)?;
+        self.bridged_total += amount;
+
+        self.record(block_number, user, -delta, delta);
+        self.check_user_conserved(db, user, evm_before + core_before)
+    }
+
+    fn withdraw_impl(
+        &mut self,
+        db: &mut CacheDB<EvmStorage>,
+        user: Address,
+        amount: U256,
+        block_number: u64,
+    ) -> Result<()> {
+        let delta = Self::validate_amount(amount)?;
+        // Only bridged balance is held in escrow
+        if amount > self.bridged_total {
+            return Err(anyhow!("Withdrawal exceeds bridged collateral"));
+        }
+        let evm_before = evm_balance(db, user)?;
+        let core_before = self.core_balance(&user);
+
+        // Core enforces balance and margin requirements
+        self.core
+            .write()
+            .unwrap()
+            .withdraw_collateral(user, self.collateral_asset, amount)?;
+        let escrow = evm_balance(db, COLLATERAL_PRECOMPILE)?;
+        set_evm_balance(db, COLLATERAL_PRECOMPILE, escrow - amount)?;
+        set_evm_balance(db, user, evm_before + amount)?;
+        self.bridged_total -= amount;
+
+        self.record(block_number, user, delta, -delta);
+        self.check_user_conserved(db, user, evm_before + core_before)
+    }
+
+    /// Queue a withdrawal if the user's collateral and the escrow cover it
+    /// on top of what is already queued
+    fn queue_withdrawal(&mut self, user: Address, amount: U256, block_number: u64) -> Result<u64> {
+        Self::validate_amount(amount)?;
+        let queued_total = self.withdrawal_queue.iter().fold(U256::ZERO, |acc, w| acc + w.amount);
+        if queued_total + amount > self.bridged_total {
+            return Err(anyhow!("Withdrawal exceeds bridged collateral"));
+        }
+        if self.queued_amount(&user) + amount > self.core_balance(&user) {
+            return Err(anyhow!("Insufficient collateral"));
+        }
+
+        let id = self.next_withdrawal_id;
+        self.next_withdrawal_id += 1;
+        self.withdrawal_queue.push(QueuedWithdrawal { id, user, amount, requested_at: block_number });
+        Ok(id)
+    }
+
+    /// Check that the escrow matches the ledger and every transfer balances
+    pub fn check_invariants(&self, db: &mut CacheDB<EvmStorage>) -> Result<()> {
+        let mut evm_total = I256::ZERO;
+        let mut core_total = I256::ZERO;
+
+        for legs in self.ledger.chunks(2) {
+            match legs {
+                [evm, core]
+                    if evm.seq == core.seq
+                        && evm.side == LedgerSide::Evm
+                        && core.side == LedgerSide::Core
+                        && evm.delta + core.delta == I256::ZERO =>
+                {
+                    evm_total += evm.delta;
+                    core_total += core.delta;
+                }
+                _ => return Err(anyhow!("Unbalanced ledger entry")),
+            }
+        }
+
+        if core_total != I256::try_from(self.bridged_total)? || evm_total != -core_total {
+            return Err(anyhow!("Ledger does not match bridged total"));
+        }
+        if evm_balance(db, COLLATERAL_PRECOMPILE)? != self.bridged_total {
+            return Err(anyhow!("Escrow balance does not match bridged total"));
+        }
+
+        Ok(())
+    }
+
+    fn core_balance(&self, user: &Address) -> U256 {
+        self.core.read().unwrap().get_collateral(user, self.collateral_asset)
+    }
+
+    fn validate_amount(amount: U256) -> Result<I256> {
+        if amount == U256::ZERO {
+            return Err(anyhow!("Amount must be greater than zero"));
+        }
+        I256::try_from(amount).map_err(|_| anyhow!("Amount too large"))
+    }
+
+    fn record(&mut self, block_number: u64, user: Address, evm_delta: I256, core_delta: I256) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        for (side, delta) in [(LedgerSide::Evm, evm_delta), (LedgerSide::Core, core_delta)] {
+            self.ledger.push(LedgerEntry {
+                seq,
+                block_number,
+                user,
+                side,
+                delta,
+            });
+        }
+    }
+
+    /// A transfer must not change the user's combined EVM + core balance
+    fn check_user_conserved(
+        &self,
+        db: &mut CacheDB<EvmStorage>,
+        user: Address,
+        expected: U256,
+    ) -> Result<()> {
+        let total = evm_balance(db, user)? + self.core_balance(&user);
+        if total != expected {
+            return Err(anyhow!("Bridge transfer did not conserve balance"));
+        }
+        Ok(())
+    }
+}
+
+fn evm_balance(db: &mut CacheDB<EvmStorage>, address: Address) -> Result<U256> {
+    Ok(db.basic(address)?.map(|info| info.balance).unwrap_or_default())
+}
+
+fn set_evm_balance(db: &mut CacheDB<EvmStorage>, address: Address, balance: U256) -> Result<()> {
+    let account = db.load_account(address)?;
+    // An account cached as missing would otherwise keep reading as empty
+    if account.account_state == AccountState::NotExisting {
+        account.account_state = AccountState::Touched;
+    }
+    account.info.balance = balance;
+    Ok(())
+}
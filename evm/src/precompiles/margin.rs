@@ -0,0 +1,176 @@
+use super::collateral::CollateralBridge;
+use crate::storage::EvmStorage;
+use alloy_primitives::{Address, Bytes};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use anyhow::{anyhow, Result};
+use openliquid_core::{AssetId, MarginMode};
+use revm::db::CacheDB;
+
+// Define Solidity interface using alloy
+sol! {
+    /// Core margin accounts: collateral, equity, margin mode and positions
+    interface IMargin {
+        /// Move native balance from the caller's EVM account into core collateral
+        /// @param amount Amount to move
+        /// @return success True if the deposit was applied
+        function deposit(uint256 amount) external returns (bool success);
+
+        /// Move core collateral back to the caller's EVM account, queued
+        /// while the collateral bridge has a withdrawal delay
+        /// @param amount Amount to move
+        /// @return success True if the withdrawal was applied or queued
+        function withdraw(uint256 amount) external returns (bool success);
+
+        /// Get the total collateral value of a user's margin account
+        /// @param user The account
+        /// @return equity Account equity (0 without an account)
+        function equityOf(address user) external view returns (uint256 equity);
+
+        /// Switch the caller between cross and isolated margin; only
+        /// possible without open positions
+        /// @param isolated True for isolated, false for cross margin
+        /// @return success True if the mode was set
+        function setMarginMode(bool isolated) external returns (bool success);
+
+        /// Get a user's margin mode
+        /// @param user The account
+        /// @return isolated True for isolated, false for cross margin
+        function marginModeOf(address user) external view returns (bool isolated);
+
+        /// Get a user's position in one asset
+        /// @param user The account
+        /// @param asset Core asset ID
+        /// @return size Signed size (positive long, negative short)
+        /// @return entryPrice Entry price (6 decimals)
+        /// @return realizedPnl Realized PnL
+        /// @return unrealizedPnl Unrealized PnL at the last mark
+        function positionOf(address user, uint32 asset) external view returns (
+            int64 size,
+            uint64 entryPrice,
+            int64 realizedPnl,
+            int64 unrealizedPnl
+        );
+
+        /// Get a user's open positions
+        /// @param user The account
+        /// @return assets Core asset IDs, ascending
+        /// @return sizes Signed sizes, matching `assets`
+        function positionsOf(address user) external view returns (
+            uint32[] memory assets,
+            int64[] memory sizes
+        );
+    }
+}
+
+/// Gas costs for operations
+const DEPOSIT_GAS: u64 = 40_000;
+const WITHDRAW_GAS: u64 = 40_000;
+const SET_MARGIN_MODE_GAS: u64 = 20_000;
+const EQUITY_OF_GAS: u64 = 3_000;
+const MARGIN_MODE_OF_GAS: u64 = 2_000;
+const POSITION_OF_GAS: u64 = 5_000;
+const POSITIONS_OF_GAS: u64 = 10_000;
+
+/// Exposes core's `MarginEngine` to contracts.
+///
+/// Collateral moves through the collateral bridge, sharing its escrow,
+/// ledger and withdrawal delay, so the bridge must be attached. Called by
+/// the executor (not through `get_precompile`) since it needs the bridge
+/// and EVM account state.
+pub fn call(
+    bridge: &mut CollateralBridge,
+    db: &mut CacheDB<EvmStorage>,
+    input: &Bytes,
+    gas_limit: u64,
+    caller: Address,
+    block_number: u64,
+) -> Result<(Bytes, u64)> {
+    if input.len() < 4 {
+        return Err(anyhow!("Input too short"));
+    }
+
+    let charge = |gas: u64| {
+        if gas > gas_limit {
+            return Err(anyhow!("Out of gas"));
+        }
+        Ok(gas)
+    };
+
+    // Route based on selector
+    match &input[..4] {
+        // deposit(uint256)
+        sel if sel == IMargin::depositCall::SELECTOR => {
+            let gas = charge(DEPOSIT_GAS)?;
+            let call = IMargin::depositCall::abi_decode(input, false)?;
+            bridge.deposit_impl(db, caller, call.amount, block_number)?;
+            Ok((Bytes::from(true.abi_encode()), gas))
+        }
+
+        // withdraw(uint256)
+        sel if sel == IMargin::withdrawCall::SELECTOR => {
+            let gas = charge(WITHDRAW_GAS)?;
+            let call = IMargin::withdrawCall::abi_decode(input, false)?;
+            bridge.request_withdrawal(db, caller, call.amount, block_number)?;
+            Ok((Bytes::from(true.abi_encode()), gas))
+        }
+
+        // equityOf(address)
+        sel if sel == IMargin::equityOfCall::SELECTOR => {
+            let gas = charge(EQUITY_OF_GAS)?;
+            let call = IMargin::equityOfCall::abi_decode(input, false)?;
+            let equity = bridge
+                .core()
+                .read()
+                .unwrap()
+                .get_account_equity(&call.user)
+                .unwrap_or_default();
+            Ok((Bytes::from(equity.abi_encode()), gas))
+        }
+
+        // setMarginMode(bool)
+        sel if sel == IMargin::setMarginModeCall::SELECTOR => {
+            let gas = charge(SET_MARGIN_MODE_GAS)?;
+            let call = IMargin::setMarginModeCall::abi_decode(input, false)?;
+            let mode = if call.isolated { MarginMode::Isolated } else { MarginMode::Cross };
+            bridge.core().write().unwrap().set_margin_mode(caller, mode)?;
+            Ok((Bytes::from(true.abi_encode()), gas))
+        }
+
+        // marginModeOf(address)
+        sel if sel == IMargin::marginModeOfCall::SELECTOR => {
+            let gas = charge(MARGIN_MODE_OF_GAS)?;
+            let call = IMargin::marginModeOfCall::abi_decode(input, false)?;
+            let mode = bridge.core().read().unwrap().get_margin_mode(&call.user);
+            Ok((Bytes::from((mode == MarginMode::Isolated).abi_encode()), gas))
+        }
+
+        // positionOf(address,uint32)
+        sel if sel == IMargin::positionOfCall::SELECTOR => {
+            let gas = charge(POSITION_OF_GAS)?;
+            let call = IMargin::positionOfCall::abi_decode(input, false)?;
+            let core = bridge.core().read().unwrap();
+            let result = match core.get_position(&call.user, AssetId(call.asset)) {
+                Some(position) => (
+                    position.size,
+                    position.entry_price.0,
+                    position.realized_pnl,
+                    position.unrealized_pnl,
+                ),
+                None => (0, 0, 0, 0),
+            };
+            Ok((Bytes::from(result.abi_encode_params()), gas))
+        }
+
+        // positionsOf(address)
+        sel if sel == IMargin::positionsOfCall::SELECTOR => {
+            let gas = charge(POSITIONS_OF_GAS)?;
+            let call = IMargin::positionsOfCall::abi_decode(input, false)?;
+            let positions = bridge.core().read().unwrap().get_user_positions(&call.user);
+            let assets: Vec<u32> = positions.iter().map(|position| position.asset.0).collect();
+            let sizes: Vec<i64> = positions.iter().map(|position| position.size).collect();
+            Ok((Bytes::from((assets, sizes).abi_encode_params()), gas))
+        }
+
+        _ => Err(anyhow!("Unknown function selector")),
+    }
+}
@@ -1,10 +1,17 @@
+use crate::types::Log;
 use alloy_primitives::{Address, Bytes};
+use alloy_sol_types::SolEvent;
 use anyhow::Result;
 use std::sync::Arc;
 
+pub mod collateral;
+pub mod margin;
+pub mod oracle;
 pub mod orderbook;
 pub mod perp;
+pub mod randomness;
 pub mod spot;
+pub mod vault;
 #[cfg(test)]
 mod tests;
 
@@ -15,15 +22,56 @@ pub const SPOT_PRECOMPILE: Address = Address::new([
 pub const PERP_PRECOMPILE: Address = Address::new([
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
 ]);
+/// Collateral bridge; also the escrow account for bridged balances
+pub const COLLATERAL_PRECOMPILE: Address = Address::new([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
+]);
+/// Per-block randomness beacon
+pub const RANDOMNESS_PRECOMPILE: Address = Address::new([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4,
+]);
+/// Core margin accounts (collateral moves through the collateral bridge)
+pub const MARGIN_PRECOMPILE: Address = Address::new([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5,
+]);
+/// Core price oracle
+pub const ORACLE_PRECOMPILE: Address = Address::new([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 6,
+]);
+/// Market-making vaults
+pub const VAULT_PRECOMPILE: Address = Address::new([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7,
+]);
 
 /// Trait for custom precompiles
 pub trait Precompile: Send + Sync {
     /// Execute the precompile with the given input and gas limit
     /// Returns (output, gas_used)
     fn call(&mut self, input: &Bytes, gas_limit: u64, caller: Address) -> Result<(Bytes, u64)>;
+
+    /// Take the logs the last successful `call` emitted, oldest first
+    fn take_logs(&mut self) -> Vec<Log> {
+        Vec::new()
+    }
+
+    /// Copy of the in-memory state for simulation; the copy never persists
+    fn fork(&self) -> Box<dyn Precompile>;
+}
+
+/// ABI-encode an event as a log emitted by the precompile at `address`
+pub(crate) fn event_log<E: SolEvent>(address: Address, event: &E) -> Log {
+    let data = event.encode_log_data();
+    Log {
+        address,
+        topics: data.topics().to_vec(),
+        data: data.data,
+    }
 }
 
-/// Get a precompile instance by address
+/// Get a precompile instance by address.
+///
+/// The collateral bridge, margin, oracle, vault and randomness precompiles are not returned
+/// here: they need executor state and are held by the executor instead.
 pub fn get_precompile(address: &Address) -> Option<Box<dyn Precompile>> {
     match *address {
         SPOT_PRECOMPILE => Some(Box::new(spot::SpotPrecompile::new())),
@@ -62,6 +110,15 @@ pub fn get_precompile_with_storage(
 
 /// Check if an address is a precompile
 pub fn is_precompile(address: &Address) -> bool {
-    matches!(*address, SPOT_PRECOMPILE | PERP_PRECOMPILE)
+    matches!(
+        *address,
+        SPOT_PRECOMPILE
+            | PERP_PRECOMPILE
+            | COLLATERAL_PRECOMPILE
+            | RANDOMNESS_PRECOMPILE
+            | MARGIN_PRECOMPILE
+            | ORACLE_PRECOMPILE
+            | VAULT_PRECOMPILE
+    )
 }
 
@@ -0,0 +1,241 @@
+use super::event_log;
+use super::ORACLE_PRECOMPILE;
+use crate::types::Log;
+use alloy_primitives::{Address, Bytes};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use anyhow::{anyhow, Result};
+use openliquid_core::{AssetId, CoreStateMachine, Price};
+use std::sync::{Arc, RwLock};
+
+// Define Solidity interface using alloy
+sol! {
+    /// Core price oracle: whitelisted reporters post, anyone reads
+    interface IOracle {
+        /// Emitted when a reporter posts an external price
+        event PricePosted(uint32 indexed asset, address indexed reporter, uint64 price, uint64 timestamp);
+
+        /// Emitted when a reporter posts an index price
+        event IndexPricePosted(uint32 indexed asset, address indexed reporter, uint64 price);
+
+        /// Post an external price at the current block time (reporters only)
+        /// @param asset Core asset ID
+        /// @param price Price (6 decimals)
+        /// @return success True if the price was recorded
+        function postPrice(uint32 asset, uint64 price) external returns (bool success);
+
+        /// Post an index (spot reference) price (reporters only)
+        /// @param asset Core asset ID
+        /// @param price Price (6 decimals)
+        /// @return success True if the price was recorded
+        function postIndexPrice(uint32 asset, uint64 price) external returns (bool success);
+
+        /// Get the latest external price (reverts if missing or stale)
+        /// @param asset Core asset ID
+        /// @return price Price (6 decimals)
+        function latestPrice(uint32 asset) external view returns (uint64 price);
+
+        /// Check whether the external price is missing or stale
+        /// @param asset Core asset ID
+        /// @return stale True if `latestPrice` would revert
+        function isStale(uint32 asset) external view returns (bool stale);
+
+        /// Get the mark price used for margin (reverts if unavailable)
+        /// @param asset Core asset ID
+        /// @return price Price (6 decimals)
+        function markPrice(uint32 asset) external view returns (uint64 price);
+
+        /// Get the index price used for funding (reverts if unset)
+        /// @param asset Core asset ID
+        /// @return price Price (6 decimals)
+        function indexPrice(uint32 asset) external view returns (uint64 price);
+
+        /// Get the time-weighted average external price
+        /// @param asset Core asset ID
+        /// @param window Seconds before the current block time
+        /// @return price Price (6 decimals)
+        function twap(uint32 asset, uint64 window) external view returns (uint64 price);
+
+        /// Check whether an address may post prices for an asset
+        /// @param asset Core asset ID
+        /// @param reporter The address
+        /// @return allowed True if whitelisted
+        function isReporter(uint32 asset, address reporter) external view returns (bool allowed);
+    }
+}
+
+/// Gas costs for operations
+const POST_PRICE_GAS: u64 = 30_000;
+const POST_INDEX_PRICE_GAS: u64 = 25_000;
+const LATEST_PRICE_GAS: u64 = 2_000;
+const IS_STALE_GAS: u64 = 2_000;
+const MARK_PRICE_GAS: u64 = 5_000;
+const INDEX_PRICE_GAS: u64 = 2_000;
+const TWAP_GAS: u64 = 10_000;
+const IS_REPORTER_GAS: u64 = 1_000;
+
+/// Posts prices into and reads them from core's `OracleEngine`.
+///
+/// Reporters are whitelisted per asset in core state, by governance (see
+/// `CoreStateMachine::authorize_oracle_reporter`), not by contracts.
+/// Called by the executor (not through `get_precompile`) since
+/// it needs core state and the block timestamp.
+pub struct PriceOracle {
+    /// Core state machine holding the oracle
+    core: Arc<RwLock<CoreStateMachine>>,
+    /// Events emitted by the last call
+    logs: Vec<Log>,
+}
+
+impl PriceOracle {
+    pub fn new(core: Arc<RwLock<CoreStateMachine>>) -> Self {
+        Self {
+            core,
+            logs: Vec::new(),
+        }
+    }
+
+    /// Core state machine holding the oracle
+    pub fn core(&self) -> &Arc<RwLock<CoreStateMachine>> {
+        &self.core
+    }
+
+    /// Check whether `reporter` may post prices for `asset`
+    pub fn is_reporter(&self, asset: AssetId, reporter: &Address) -> bool {
+        self.core.read().unwrap().is_oracle_reporter(asset, reporter)
+    }
+
+    /// Take the logs the last successful `call` emitted, oldest first
+    pub fn take_logs(&mut self) -> Vec<Log> {
+        std::mem::take(&mut self.logs)
+    }
+
+    fn ensure_reporter(&self, asset: AssetId, caller: &Address) -> Result<()> {
+        if !self.is_reporter(asset, caller) {
+            return Err(anyhow!("{} is not a reporter for asset {}", caller, asset.0));
+        }
+        Ok(())
+    }
+
+    /// Execute an oracle call at block time `timestamp`
+    pub fn call(&mut self, input: &Bytes, gas_limit: u64, caller: Address, timestamp: u64) -> Result<(Bytes, u64)> {
+        if input.len() < 4 {
+            return Err(anyhow!("Input too short"));
+        }
+        self.logs.clear();
+
+        let charge = |gas: u64| {
+            if gas > gas_limit {
+                return Err(anyhow!("Out of gas"));
+            }
+            Ok(gas)
+        };
+        let price_output = |price: Option<Price>, what: &str, asset: u32| {
+            price
+                .map(|p| Bytes::from(p.0.abi_encode()))
+                .ok_or_else(|| anyhow!("No {} price for asset {}", what, asset))
+        };
+
+        // Route based on selector
+        match &input[..4] {
+            // postPrice(uint32,uint64)
+            sel if sel == IOracle::postPriceCall::SELECTOR => {
+                let gas = charge(POST_PRICE_GAS)?;
+                let call = IOracle::postPriceCall::abi_decode(input, false)?;
+                let asset = AssetId(call.asset);
+                self.ensure_reporter(asset, &caller)?;
+                if call.price == 0 {
+                    return Err(anyhow!("Price must be positive"));
+                }
+                self.core
+                    .write()
+                    .unwrap()
+                    .update_oracle_price(asset, Price(call.price), timestamp)?;
+                self.logs.push(event_log(
+                    ORACLE_PRECOMPILE,
+                    &IOracle::PricePosted {
+                        asset: call.asset,
+                        reporter: caller,
+                        price: call.price,
+                        timestamp,
+                    },
+                ));
+                Ok((Bytes::from(true.abi_encode()), gas))
+            }
+
+            // postIndexPrice(uint32,uint64)
+            sel if sel == IOracle::postIndexPriceCall::SELECTOR => {
+                let gas = charge(POST_INDEX_PRICE_GAS)?;
+                let call = IOracle::postIndexPriceCall::abi_decode(input, false)?;
+                let asset = AssetId(call.asset);
+                self.ensure_reporter(asset, &caller)?;
+                if call.price == 0 {
+                    return Err(anyhow!("Price must be positive"));
+                }
+                self.core.write().unwrap().set_index_price(asset, Price(call.price));
+                self.logs.push(event_log(
+                    ORACLE_PRECOMPILE,
+                    &IOracle::IndexPricePosted {
+                        asset: call.asset,
+                        reporter: caller,
+                        price: call.price,
+                    },
+                ));
+                Ok((Bytes::from(true.abi_encode()), gas))
+            }
+
+            // latestPrice(uint32)
+            sel if sel == IOracle::latestPriceCall::SELECTOR => {
+                let gas = charge(LATEST_PRICE_GAS)?;
+                let call = IOracle::latestPriceCall::abi_decode(input, false)?;
+                let price = self.core.read().unwrap().get_oracle_price(AssetId(call.asset), timestamp);
+                Ok((price_output(price, "fresh", call.asset)?, gas))
+            }
+
+            // isStale(uint32)
+            sel if sel == IOracle::isStaleCall::SELECTOR => {
+                let gas = charge(IS_STALE_GAS)?;
+                let call = IOracle::isStaleCall::abi_decode(input, false)?;
+                let price = self.core.read().unwrap().get_oracle_price(AssetId(call.asset), timestamp);
+                Ok((Bytes::from(price.is_none().abi_encode()), gas))
+            }
+
+            // markPrice(uint32)
+            sel if sel == IOracle::markPriceCall::SELECTOR => {
+                let gas = charge(MARK_PRICE_GAS)?;
+                let call = IOracle::markPriceCall::abi_decode(input, false)?;
+                let price = self.core.read().unwrap().get_mark_price(AssetId(call.asset), timestamp);
+                Ok((price_output(price, "mark", call.asset)?, gas))
+            }
+
+            // indexPrice(uint32)
+            sel if sel == IOracle::indexPriceCall::SELECTOR => {
+                let gas = charge(INDEX_PRICE_GAS)?;
+                let call = IOracle::indexPriceCall::abi_decode(input, false)?;
+                let price = self.core.read().unwrap().get_index_price(AssetId(call.asset));
+                Ok((price_output(price, "index", call.asset)?, gas))
+            }
+
+            // twap(uint32,uint64)
+            sel if sel == IOracle::twapCall::SELECTOR => {
+                let gas = charge(TWAP_GAS)?;
+                let call = IOracle::twapCall::abi_decode(input, false)?;
+                let price = self
+                    .core
+                    .read()
+                    .unwrap()
+                    .get_oracle_twap(AssetId(call.asset), call.window, timestamp)?;
+                Ok((Bytes::from(price.0.abi_encode()), gas))
+            }
+
+            // isReporter(uint32,address)
+            sel if sel == IOracle::isReporterCall::SELECTOR => {
+                let gas = charge(IS_REPORTER_GAS)?;
+                let call = IOracle::isReporterCall::abi_decode(input, false)?;
+                let allowed = self.is_reporter(AssetId(call.asset), &call.reporter);
+                Ok((Bytes::from(allowed.abi_encode()), gas))
+            }
+
+            _ => Err(anyhow!("Unknown function selector")),
+        }
+    }
+}
@@ -58,7 +58,7 @@ pub struct Trade {
 }
 
 /// Order book for a single asset
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OrderBook {
     /// Buy orders sorted by price (descending)
     pub(crate) bids: BTreeMap<U256, Vec<Order>>,
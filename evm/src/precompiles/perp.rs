@@ -1,5 +1,6 @@
-use super::Precompile;
+use super::{event_log, Precompile, PERP_PRECOMPILE};
 use crate::storage::EvmStorage;
+use crate::types::Log;
 use alloy_primitives::{Address, Bytes, I256, U256};
 use alloy_sol_types::{sol, SolCall, SolValue};
 use anyhow::{anyhow, Result};
@@ -11,6 +12,25 @@ use std::sync::Arc;
 sol! {
     /// Perpetual trading interface
     interface IPerp {
+        /// A position was opened at the mark price
+        event PositionOpened(
+            uint256 indexed positionId,
+            address indexed trader,
+            address indexed market,
+            uint256 size,
+            uint256 entryPrice,
+            uint256 leverage,
+            bool isLong
+        );
+
+        /// A position was liquidated at `price`
+        event Liquidated(
+            uint256 indexed positionId,
+            address indexed trader,
+            address indexed liquidator,
+            uint256 price
+        );
+
         /// Open a perpetual position
         /// @param market The market address
         /// @param size Position size (in base units)
@@ -174,6 +194,7 @@ impl Position {
 }
 
 /// Perpetual trading precompile
+#[derive(Clone)]
 pub struct PerpPrecompile {
     /// All positions
     positions: HashMap<u64, Position>,
@@ -185,6 +206,8 @@ pub struct PerpPrecompile {
     timestamp: u64,
     /// Storage backend (optional for persistence)
     storage: Option<Arc<EvmStorage>>,
+    /// Logs emitted by the current call
+    logs: Vec<Log>,
 }
 
 impl PerpPrecompile {
@@ -195,6 +218,7 @@ impl PerpPrecompile {
             mark_prices: HashMap::new(),
             timestamp: 0,
             storage: None,
+            logs: Vec::new(),
         }
     }
 
@@ -206,6 +230,7 @@ impl PerpPrecompile {
             mark_prices: HashMap::new(),
             timestamp: 0,
             storage: Some(storage),
+            logs: Vec::new(),
         }
     }
 
@@ -290,6 +315,19 @@ impl PerpPrecompile {
             storage.store_position(position_id, &position)?;
         }
 
+        self.logs.push(event_log(
+            PERP_PRECOMPILE,
+            &IPerp::PositionOpened {
+                positionId: U256::from(position_id),
+                trader,
+                market,
+                size,
+                entryPrice: entry_price,
+                leverage,
+                isLong: is_long,
+            },
+        ));
+
         Ok((U256::from(position_id), OPEN_POSITION_GAS))
     }
 
@@ -337,7 +375,7 @@ impl PerpPrecompile {
 
     fn liquidate_impl(
         &mut self,
-        _liquidator: Address,
+        liquidator: Address,
         position_id: U256,
     ) -> Result<(U256, u64)> {
         let position_id_u64 = position_id.to::<u64>();
@@ -373,6 +411,16 @@ impl PerpPrecompile {
             if let Some(storage) = &self.storage {
                 storage.store_position(position_id_u64, position)?;
             }
+
+            self.logs.push(event_log(
+                PERP_PRECOMPILE,
+                &IPerp::Liquidated {
+                    positionId: position_id,
+                    trader: position.trader,
+                    liquidator,
+                    price: current_price,
+                },
+            ));
         }
 
         Ok((current_price, LIQUIDATE_GAS))
@@ -432,6 +480,7 @@ impl Precompile for PerpPrecompile {
 
         // Increment timestamp
         self.timestamp += 1;
+        self.logs.clear();
 
         let selector = &input[..4];
 
@@ -520,6 +569,17 @@ impl Precompile for PerpPrecompile {
             _ => Err(anyhow!("Unknown function selector: {:?}", selector)),
         }
     }
+
+    fn take_logs(&mut self) -> Vec<Log> {
+        std::mem::take(&mut self.logs)
+    }
+
+    fn fork(&self) -> Box<dyn Precompile> {
+        Box::new(Self {
+            storage: None,
+            ..self.clone()
+        })
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,90 @@
+use alloy_primitives::{Bytes, B256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+// Define Solidity interface using alloy
+sol! {
+    /// Per-block randomness beacon derived from consensus QC signatures
+    interface IRandomness {
+        /// Get the randomness of the current block
+        /// @return value Beacon output (reverts if the block has none)
+        function getRandomness() external view returns (bytes32 value);
+
+        /// Get the randomness of a recent block
+        /// @param height Block height (within the last BEACON_HISTORY blocks)
+        /// @return value Beacon output
+        function getRandomnessAt(uint64 height) external view returns (bytes32 value);
+    }
+}
+
+/// Gas costs for operations
+const GET_RANDOMNESS_GAS: u64 = 2_000;
+const GET_RANDOMNESS_AT_GAS: u64 = 2_600;
+
+/// Number of recent beacon values kept for `getRandomnessAt`
+pub const BEACON_HISTORY: usize = 256;
+
+/// Serves beacon values recorded by the executor for each block.
+///
+/// Called by the executor (not through `get_precompile`) since values are
+/// fed in from consensus as blocks are applied.
+#[derive(Debug, Clone, Default)]
+pub struct RandomnessBeacon {
+    /// Recent beacon values by block height
+    history: BTreeMap<u64, B256>,
+}
+
+impl RandomnessBeacon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the beacon value for a block, pruning old entries
+    pub fn record(&mut self, height: u64, value: B256) {
+        self.history.insert(height, value);
+        while self.history.len() > BEACON_HISTORY {
+            self.history.pop_first();
+        }
+    }
+
+    /// Beacon value for a block, if still retained
+    pub fn get(&self, height: u64) -> Option<B256> {
+        self.history.get(&height).copied()
+    }
+
+    /// Execute a beacon call in the context of `block_number`
+    pub fn call(&self, input: &Bytes, gas_limit: u64, block_number: u64) -> Result<(Bytes, u64)> {
+        if input.len() < 4 {
+            return Err(anyhow!("Input too short"));
+        }
+
+        // Route based on selector
+        let (height, gas) = match &input[..4] {
+            // getRandomness()
+            sel if sel == IRandomness::getRandomnessCall::SELECTOR => {
+                (block_number, GET_RANDOMNESS_GAS)
+            }
+
+            // getRandomnessAt(uint64)
+            sel if sel == IRandomness::getRandomnessAtCall::SELECTOR => {
+                let call = IRandomness::getRandomnessAtCall::abi_decode(input, false)?;
+                // Future blocks are never readable, even if recorded early
+                if call.height > block_number {
+                    return Err(anyhow!("Randomness not yet available"));
+                }
+                (call.height, GET_RANDOMNESS_AT_GAS)
+            }
+
+            _ => return Err(anyhow!("Unknown function selector")),
+        };
+
+        if gas > gas_limit {
+            return Err(anyhow!("Out of gas"));
+        }
+        let value = self
+            .get(height)
+            .ok_or_else(|| anyhow!("No randomness for block {}", height))?;
+        Ok((Bytes::from(value.abi_encode()), gas))
+    }
+}
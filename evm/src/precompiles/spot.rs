@@ -1,5 +1,6 @@
-use super::{orderbook::OrderBook, Precompile};
+use super::{event_log, orderbook::OrderBook, Precompile, SPOT_PRECOMPILE};
 use crate::storage::EvmStorage;
+use crate::types::Log;
 use alloy_primitives::{Address, Bytes, U256};
 use alloy_sol_types::{sol, SolCall, SolValue};
 use anyhow::{anyhow, Result};
@@ -10,6 +11,30 @@ use std::sync::Arc;
 sol! {
     /// Spot trading interface
     interface ISpot {
+        /// An order was placed (before matching)
+        event OrderPlaced(
+            uint256 indexed orderId,
+            address indexed user,
+            address indexed asset,
+            uint256 amount,
+            uint256 price,
+            bool isBuy
+        );
+
+        /// Two orders traded `amount` at `price`
+        event OrderFilled(
+            uint256 indexed buyOrderId,
+            uint256 indexed sellOrderId,
+            address indexed asset,
+            address buyer,
+            address seller,
+            uint256 price,
+            uint256 amount
+        );
+
+        /// An order was cancelled by its owner
+        event OrderCancelled(uint256 indexed orderId, address indexed user);
+
         /// Place a limit order
         /// @param asset The asset to trade
         /// @param amount Amount of asset
@@ -75,11 +100,16 @@ const GET_BEST_PRICES_GAS: u64 = 3_000;
 const GET_DEPTH_GAS: u64 = 10_000;
 
 /// Spot trading precompile
+#[derive(Clone)]
 pub struct SpotPrecompile {
     /// Order books per asset
     order_books: HashMap<Address, OrderBook>,
     /// Global order ID to (asset, local_id) mapping
     order_map: HashMap<u64, (Address, u64)>,
+    /// (asset, local_id) to global order ID mapping
+    global_ids: HashMap<(Address, u64), u64>,
+    /// Logs emitted by the current call
+    logs: Vec<Log>,
     /// Next global order ID
     next_global_id: u64,
     /// Current timestamp
@@ -93,6 +123,8 @@ impl SpotPrecompile {
         Self {
             order_books: HashMap::new(),
             order_map: HashMap::new(),
+            global_ids: HashMap::new(),
+            logs: Vec::new(),
             next_global_id: 1,
             timestamp: 0,
             storage: None,
@@ -104,6 +136,8 @@ impl SpotPrecompile {
         Self {
             order_books: HashMap::new(),
             order_map: HashMap::new(),
+            global_ids: HashMap::new(),
+            logs: Vec::new(),
             next_global_id: 1,
             timestamp: 0,
             storage: Some(storage),
@@ -123,6 +157,7 @@ impl SpotPrecompile {
         for (order_id, order) in orders {
             // Update order map
             self.order_map.insert(order_id, (order.asset, order.id));
+            self.global_ids.insert((order.asset, order.id), order_id);
 
             // Get or create book for this asset
             let book = self.get_or_create_book(order.asset);
@@ -184,6 +219,32 @@ impl SpotPrecompile {
 
         // Now we can add to order_map (after book is done being used)
         self.order_map.insert(global_id, (asset, local_id));
+        self.global_ids.insert((asset, local_id), global_id);
+
+        self.logs.push(event_log(
+            SPOT_PRECOMPILE,
+            &ISpot::OrderPlaced {
+                orderId: U256::from(global_id),
+                user: caller,
+                asset,
+                amount,
+                price,
+                isBuy: is_buy,
+            },
+        ));
+        for trade in &trades {
+            let global = |local_id| U256::from(self.global_ids.get(&(asset, local_id)).copied().unwrap_or_default());
+            let event = ISpot::OrderFilled {
+                buyOrderId: global(trade.buy_order_id),
+                sellOrderId: global(trade.sell_order_id),
+                asset,
+                buyer: trade.buyer,
+                seller: trade.seller,
+                price: trade.price,
+                amount: trade.amount,
+            };
+            self.logs.push(event_log(SPOT_PRECOMPILE, &event));
+        }
 
         // Persist order if storage is available and order wasn't fully filled
         if let Some(storage) = storage {
@@ -216,6 +277,11 @@ impl SpotPrecompile {
 
         if cancelled.is_some() {
             self.order_map.remove(&order_id_u64);
+            self.global_ids.remove(&(asset, local_id));
+            self.logs.push(event_log(
+                SPOT_PRECOMPILE,
+                &ISpot::OrderCancelled { orderId: order_id, user: caller },
+            ));
             
             // Delete from storage if available
             if let Some(storage) = &self.storage {
@@ -317,6 +383,7 @@ impl Precompile for SpotPrecompile {
 
         // Increment timestamp for each call
         self.timestamp += 1;
+        self.logs.clear();
 
         // Extract function selector (first 4 bytes)
         let selector = &input[..4];
@@ -392,6 +459,17 @@ impl Precompile for SpotPrecompile {
             _ => Err(anyhow!("Unknown function selector: {:?}", selector)),
         }
     }
+
+    fn take_logs(&mut self) -> Vec<Log> {
+        std::mem::take(&mut self.logs)
+    }
+
+    fn fork(&self) -> Box<dyn Precompile> {
+        Box::new(Self {
+            storage: None,
+            ..self.clone()
+        })
+    }
 }
 
 #[cfg(test)]
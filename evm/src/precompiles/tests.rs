@@ -512,3 +512,71 @@ fn test_perp_liquidation_price_calculation() {
     assert!(liq_price < U256::from(56_000));
 }
 
+
+// Event logs
+
+fn decode_event<E: alloy_sol_types::SolEvent>(log: &crate::types::Log) -> E {
+    E::decode_raw_log(log.topics.iter().copied(), &log.data, true).unwrap()
+}
+
+#[test]
+fn test_spot_emits_order_events() {
+    let mut precompile = spot::SpotPrecompile::new();
+    let maker = Address::repeat_byte(0x01);
+    let taker = Address::repeat_byte(0x02);
+    let asset = Address::repeat_byte(0x03);
+    let place = |amount: u64, is_buy| {
+        Bytes::from(
+            ISpot::placeOrderCall { asset, amount: U256::from(amount), price: U256::from(100), isBuy: is_buy }
+                .abi_encode(),
+        )
+    };
+
+    precompile.call(&place(1000, false), 1_000_000, maker).unwrap();
+    let logs = precompile.take_logs();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].address, SPOT_PRECOMPILE);
+    assert_eq!(logs[0].topics[0], <ISpot::OrderPlaced as alloy_sol_types::SolEvent>::SIGNATURE_HASH);
+    let placed: ISpot::OrderPlaced = decode_event(&logs[0]);
+    assert_eq!((placed.orderId, placed.user, placed.isBuy), (U256::from(1), maker, false));
+
+    // Crossing buy: placed, then filled against order 1
+    precompile.call(&place(400, true), 1_000_000, taker).unwrap();
+    let logs = precompile.take_logs();
+    assert_eq!(logs.len(), 2);
+    let filled: ISpot::OrderFilled = decode_event(&logs[1]);
+    assert_eq!((filled.buyOrderId, filled.sellOrderId), (U256::from(2), U256::from(1)));
+    assert_eq!((filled.buyer, filled.seller, filled.amount), (taker, maker, U256::from(400)));
+
+    let cancel = Bytes::from(ISpot::cancelOrderCall { orderId: U256::from(1) }.abi_encode());
+    precompile.call(&cancel, 1_000_000, maker).unwrap();
+    let cancelled: ISpot::OrderCancelled = decode_event(&precompile.take_logs()[0]);
+    assert_eq!((cancelled.orderId, cancelled.user), (U256::from(1), maker));
+
+    // Failed calls emit nothing
+    assert!(precompile.call(&cancel, 1_000_000, maker).is_err());
+    assert!(precompile.take_logs().is_empty());
+}
+
+#[test]
+fn test_perp_emits_position_events() {
+    let mut precompile = perp::PerpPrecompile::new();
+    let trader = Address::repeat_byte(0x01);
+    let liquidator = Address::repeat_byte(0x02);
+    let market = Address::repeat_byte(0x03);
+    precompile.set_mark_price(market, U256::from(100_000));
+
+    let open = IPerp::openPositionCall { market, size: U256::from(1_000), leverage: U256::from(20), isLong: true };
+    precompile.call(&Bytes::from(open.abi_encode()), 1_000_000, trader).unwrap();
+    let opened: IPerp::PositionOpened = decode_event(&precompile.take_logs()[0]);
+    assert_eq!((opened.trader, opened.market, opened.entryPrice), (trader, market, U256::from(100_000)));
+
+    precompile.set_mark_price(market, U256::from(95_000));
+    let liquidate = IPerp::liquidateCall { positionId: opened.positionId };
+    precompile.call(&Bytes::from(liquidate.abi_encode()), 1_000_000, liquidator).unwrap();
+    let logs = precompile.take_logs();
+    assert_eq!(logs[0].address, PERP_PRECOMPILE);
+    let liquidated: IPerp::Liquidated = decode_event(&logs[0]);
+    assert_eq!((liquidated.trader, liquidated.liquidator), (trader, liquidator));
+    assert_eq!(liquidated.price, U256::from(95_000));
+}
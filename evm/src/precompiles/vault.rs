@@ -0,0 +1,442 @@
+use super::event_log;
+use super::VAULT_PRECOMPILE;
+use crate::types::Log;
+use alloy_primitives::{Address, Bytes, I256, U256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use anyhow::{anyhow, Result};
+use openliquid_core::{AssetId, CoreStateMachine, VaultId, VaultState};
+use revm::handler::register::HandleRegisterBox;
+use revm::interpreter::{CallInputs, Gas, InstructionResult, InterpreterResult};
+use revm::{Context, Database, FrameOrResult};
+use std::sync::{Arc, Mutex, RwLock};
+
+// Define Solidity interface using alloy
+sol! {
+    /// Market-making vaults funded from core collateral
+    interface IVault {
+        /// Emitted when a vault is created
+        event VaultCreated(uint64 indexed vaultId, address indexed owner, address manager, uint256 collateral);
+
+        /// Emitted when collateral is deposited for shares
+        event Deposit(uint64 indexed vaultId, address indexed user, uint256 amount, uint256 shares);
+
+        /// Emitted when shares are redeemed for collateral
+        event Withdraw(uint64 indexed vaultId, address indexed user, uint256 amount, uint256 shares);
+
+        /// Create a vault owned by the caller, funded from their core collateral
+        /// @param manager Address running the vault's strategy
+        /// @param profitShareBps Manager profit share (basis points)
+        /// @param collateral Initial collateral
+        /// @return vaultId The new vault's ID
+        function createVault(address manager, uint64 profitShareBps, uint256 collateral)
+            external
+            returns (uint64 vaultId);
+
+        /// Move core collateral into a vault for shares
+        /// @param vaultId Vault to deposit into
+        /// @param amount Collateral to deposit
+        /// @return shares Shares minted
+        function deposit(uint64 vaultId, uint256 amount) external returns (uint256 shares);
+
+        /// Redeem shares for core collateral
+        /// @param vaultId Vault to withdraw from
+        /// @param shares Shares to redeem
+        /// @return amount Collateral returned
+        function withdraw(uint64 vaultId, uint256 shares) external returns (uint256 amount);
+
+        /// Get the equity backing one share
+        /// @param vaultId Vault ID
+        /// @return price Equity per share (18 decimals)
+        function sharePrice(uint64 vaultId) external view returns (uint256 price);
+
+        /// Get a user's shares in a vault
+        /// @param vaultId Vault ID
+        /// @param user The account
+        /// @return shares Shares held
+        function sharesOf(uint64 vaultId, address user) external view returns (uint256 shares);
+
+        /// Get a vault's accounts
+        /// @param vaultId Vault ID
+        /// @return owner Vault owner
+        /// @return manager Strategy manager
+        /// @return collateral Deposited collateral
+        /// @return equity Marked equity
+        /// @return totalShares Shares outstanding
+        /// @return pnl Equity minus collateral
+        function vaultInfo(uint64 vaultId) external view returns (
+            address owner,
+            address manager,
+            uint256 collateral,
+            uint256 equity,
+            uint256 totalShares,
+            int64 pnl
+        );
+    }
+}
+
+/// Gas costs for operations
+const CREATE_VAULT_GAS: u64 = 100_000;
+const DEPOSIT_GAS: u64 = 50_000;
+const WITHDRAW_GAS: u64 = 50_000;
+const SHARE_PRICE_GAS: u64 = 3_000;
+const SHARES_OF_GAS: u64 = 2_000;
+const VAULT_INFO_GAS: u64 = 5_000;
+
+/// Fixed-point scale of `sharePrice`
+pub const SHARE_PRICE_SCALE: u64 = 1_000_000_000_000_000_000;
+
+/// Rollback point opened by `snapshot`
+struct Checkpoint {
+    /// Moves recorded when the checkpoint was opened
+    moves_len: usize,
+    /// Vault state before the first call that changed it, if any did
+    state: Option<VaultState>,
+}
+
+/// Exposes core's vaults to transactions and contracts.
+///
+/// Vaults and their shares live in core, which persists them. Deposits
+/// and withdrawals move the caller's core collateral in `asset` into and
+/// out of vaults. Called by the executor (not through `get_precompile`)
+/// since it needs core state and the block timestamp.
+pub struct VaultPrecompile {
+    /// Core state machine holding the vaults and depositors' collateral
+    core: Arc<RwLock<CoreStateMachine>>,
+    /// Collateral asset vaults are funded in
+    asset: AssetId,
+    /// Core collateral moved by calls since the oldest open checkpoint
+    moves: Vec<(Address, I256)>,
+    /// Open checkpoints, oldest first
+    checkpoints: Vec<Checkpoint>,
+    /// Events emitted by the last call
+    logs: Vec<Log>,
+}
+
+impl VaultPrecompile {
+    pub fn new(core: Arc<RwLock<CoreStateMachine>>, asset: AssetId) -> Self {
+        Self {
+            core,
+            asset,
+            moves: Vec::new(),
+            checkpoints: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    /// Core state machine holding the vaults
+    pub fn core(&self) -> &Arc<RwLock<CoreStateMachine>> {
+        &self.core
+    }
+
+    /// Shares `user` holds in a vault
+    pub fn shares_of(&self, vault_id: VaultId, user: &Address) -> U256 {
+        self.core.read().unwrap().vaults().shares_of(vault_id, user)
+    }
+
+    /// Shares outstanding in a vault
+    pub fn total_shares(&self, vault_id: VaultId) -> U256 {
+        self.core.read().unwrap().vaults().total_shares(vault_id)
+    }
+
+    /// Equity per share, scaled by `SHARE_PRICE_SCALE`
+    pub fn share_price(&self, vault_id: VaultId) -> Result<U256> {
+        self.core.read().unwrap().vaults().share_price(vault_id, U256::from(SHARE_PRICE_SCALE))
+    }
+
+    /// Take the logs the last successful `call` emitted, oldest first
+    pub fn take_logs(&mut self) -> Vec<Log> {
+        std::mem::take(&mut self.logs)
+    }
+
+    /// Open a checkpoint that `revert_to` can roll vault calls back to;
+    /// checkpoints nest, and the id is the nesting depth
+    pub fn snapshot(&mut self) -> usize {
+        self.checkpoints.push(Checkpoint { moves_len: self.moves.len(), state: None });
+        self.checkpoints.len() - 1
+    }
+
+    /// Undo the vault calls made since checkpoint `id`, closing it and the
+    /// checkpoints opened after it
+    ///
+    /// Restores the vaults in core and moves the collateral back. Fails
+    /// without changing anything if a user no longer holds the collateral
+    /// their reverted withdrawals paid out.
+    pub fn revert_to(&mut self, id: usize) -> Result<()> {
+        let checkpoint = self.checkpoints.get(id).ok_or_else(|| anyhow!("Unknown vault checkpoint {}", id))?;
+        let Some(state) = checkpoint.state.clone() else {
+            self.checkpoints.truncate(id);
+            return Ok(());
+        };
+        let mut net: Vec<(Address, I256)> = Vec::new();
+        for (user, delta) in &self.moves[checkpoint.moves_len..] {
+            match net.iter_mut().find(|(u, _)| u == user) {
+                Some((_, total)) => *total += *delta,
+                None => net.push((*user, *delta)),
+            }
+        }
+
+        let mut core = self.core.write().unwrap();
+        for (user, delta) in &net {
+            if delta.is_positive() && core.get_collateral(user, self.asset) < delta.unsigned_abs() {
+                return Err(anyhow!("Cannot revert vault withdrawals of {}: collateral already spent", user));
+            }
+        }
+        for (user, delta) in net {
+            if delta.is_positive() {
+                core.revert_collateral_deposit(user, self.asset, delta.unsigned_abs())?;
+            } else if delta.is_negative() {
+                core.deposit_collateral(user, self.asset, delta.unsigned_abs())?;
+            }
+        }
+        core.restore_vaults(state)?;
+        drop(core);
+
+        self.moves.truncate(self.checkpoints[id].moves_len);
+        self.checkpoints.truncate(id);
+        Ok(())
+    }
+
+    /// Close checkpoint `id` and those opened after it without undoing
+    /// the calls made since, as core was restored as a whole
+    pub fn reset_to(&mut self, id: usize) {
+        if let Some(checkpoint) = self.checkpoints.get(id) {
+            self.moves.truncate(checkpoint.moves_len);
+            self.checkpoints.truncate(id);
+        }
+    }
+
+    /// Keep the vault calls made since checkpoint `id`, closing it and the
+    /// checkpoints opened after it
+    pub fn discard_snapshot(&mut self, id: usize) {
+        self.checkpoints.truncate(id);
+        if self.checkpoints.is_empty() {
+            self.moves.clear();
+        }
+    }
+
+    /// Save the vault state into checkpoints opened since the last change
+    fn checkpoint_state(&mut self) {
+        if self.checkpoints.iter().all(|checkpoint| checkpoint.state.is_some()) {
+            return;
+        }
+        let state = self.core.read().unwrap().vaults().state();
+        for checkpoint in self.checkpoints.iter_mut().filter(|checkpoint| checkpoint.state.is_none()) {
+            checkpoint.state = Some(state.clone());
+        }
+    }
+
+    /// Create a vault funded with `collateral` from the owner's core account
+    fn create_vault(
+        &mut self,
+        owner: Address,
+        manager: Address,
+        profit_share_bps: u64,
+        collateral: U256,
+        timestamp: u64,
+    ) -> Result<VaultId> {
+        self.checkpoint_state();
+        let vault_id = self.core.write().unwrap().create_vault(
+            owner,
+            manager,
+            self.asset,
+            profit_share_bps,
+            collateral,
+            timestamp,
+        )?;
+        self.moves.push((owner, -I256::from_raw(collateral)));
+        Ok(vault_id)
+    }
+
+    /// Deposit `amount` of the user's core collateral, returning the shares minted
+    fn deposit(&mut self, vault_id: VaultId, user: Address, amount: U256, timestamp: u64) -> Result<U256> {
+        self.checkpoint_state();
+        let shares = self.core.write().unwrap().deposit_to_vault(vault_id, user, self.asset, amount, timestamp)?;
+        self.moves.push((user, -I256::from_raw(amount)));
+        Ok(shares)
+    }
+
+    /// Redeem `shares` for core collateral, returning the amount paid out
+    fn withdraw(&mut self, vault_id: VaultId, user: Address, shares: U256, timestamp: u64) -> Result<U256> {
+        self.checkpoint_state();
+        let amount = self.core.write().unwrap().withdraw_from_vault(vault_id, user, self.asset, shares, timestamp)?;
+        self.moves.push((user, I256::from_raw(amount)));
+        Ok(amount)
+    }
+
+    /// Whether `input` calls a function that changes vaults
+    fn is_mutating(input: &[u8]) -> bool {
+        input.get(..4).is_some_and(|selector| {
+            selector == IVault::createVaultCall::SELECTOR
+                || selector == IVault::depositCall::SELECTOR
+                || selector == IVault::withdrawCall::SELECTOR
+        })
+    }
+
+    /// Execute a vault call made by a contract during EVM execution
+    ///
+    /// Only direct, value-free calls are served. Calls that change vaults
+    /// must come from the transaction's target contract, outside a static
+    /// context, in a run whose changes are committed: core is not rolled
+    /// back with reverted EVM frames, only with the whole transaction.
+    fn contract_call(&mut self, inputs: &CallInputs, depth: u64, writable: bool, timestamp: u64) -> Result<(Bytes, u64)> {
+        if inputs.target_address != VAULT_PRECOMPILE {
+            return Err(anyhow!("Vaults cannot be delegate-called"));
+        }
+        if inputs.transfers_value() {
+            return Err(anyhow!("Vault calls cannot transfer value"));
+        }
+        if Self::is_mutating(&inputs.input) && (!writable || inputs.is_static || depth > 1) {
+            return Err(anyhow!("Vault changes must be called by the transaction's target contract"));
+        }
+        self.call(&inputs.input, inputs.gas_limit, inputs.caller, timestamp)
+    }
+
+    /// Execute a vault call from `caller` at block time `timestamp`
+    pub fn call(&mut self, input: &Bytes, gas_limit: u64, caller: Address, timestamp: u64) -> Result<(Bytes, u64)> {
+        if input.len() < 4 {
+            return Err(anyhow!("Input too short"));
+        }
+        self.logs.clear();
+
+        let charge = |gas: u64| {
+            if gas > gas_limit {
+                return Err(anyhow!("Out of gas"));
+            }
+            Ok(gas)
+        };
+
+        // Route based on selector
+        match &input[..4] {
+            // createVault(address,uint64,uint256)
+            sel if sel == IVault::createVaultCall::SELECTOR => {
+                let gas = charge(CREATE_VAULT_GAS)?;
+                let call = IVault::createVaultCall::abi_decode(input, false)?;
+                let vault_id =
+                    self.create_vault(caller, call.manager, call.profitShareBps, call.collateral, timestamp)?;
+                self.logs.push(event_log(
+                    VAULT_PRECOMPILE,
+                    &IVault::VaultCreated {
+                        vaultId: vault_id,
+                        owner: caller,
+                        manager: call.manager,
+                        collateral: call.collateral,
+                    },
+                ));
+                Ok((Bytes::from(vault_id.abi_encode()), gas))
+            }
+
+            // deposit(uint64,uint256)
+            sel if sel == IVault::depositCall::SELECTOR => {
+                let gas = charge(DEPOSIT_GAS)?;
+                let call = IVault::depositCall::abi_decode(input, false)?;
+                let shares = self.deposit(call.vaultId, caller, call.amount, timestamp)?;
+                self.logs.push(event_log(
+                    VAULT_PRECOMPILE,
+                    &IVault::Deposit {
+                        vaultId: call.vaultId,
+                        user: caller,
+                        amount: call.amount,
+                        shares,
+                    },
+                ));
+                Ok((Bytes::from(shares.abi_encode()), gas))
+            }
+
+            // withdraw(uint64,uint256)
+            sel if sel == IVault::withdrawCall::SELECTOR => {
+                let gas = charge(WITHDRAW_GAS)?;
+                let call = IVault::withdrawCall::abi_decode(input, false)?;
+                let amount = self.withdraw(call.vaultId, caller, call.shares, timestamp)?;
+                self.logs.push(event_log(
+                    VAULT_PRECOMPILE,
+                    &IVault::Withdraw {
+                        vaultId: call.vaultId,
+                        user: caller,
+                        amount,
+                        shares: call.shares,
+                    },
+                ));
+                Ok((Bytes::from(amount.abi_encode()), gas))
+            }
+
+            // sharePrice(uint64)
+            sel if sel == IVault::sharePriceCall::SELECTOR => {
+                let gas = charge(SHARE_PRICE_GAS)?;
+                let call = IVault::sharePriceCall::abi_decode(input, false)?;
+                let price = self.share_price(call.vaultId)?;
+                Ok((Bytes::from(price.abi_encode()), gas))
+            }
+
+            // sharesOf(uint64,address)
+            sel if sel == IVault::sharesOfCall::SELECTOR => {
+                let gas = charge(SHARES_OF_GAS)?;
+                let call = IVault::sharesOfCall::abi_decode(input, false)?;
+                let shares = self.shares_of(call.vaultId, &call.user);
+                Ok((Bytes::from(shares.abi_encode()), gas))
+            }
+
+            // vaultInfo(uint64)
+            sel if sel == IVault::vaultInfoCall::SELECTOR => {
+                let gas = charge(VAULT_INFO_GAS)?;
+                let call = IVault::vaultInfoCall::abi_decode(input, false)?;
+                let core = self.core.read().unwrap();
+                let vaults = core.vaults();
+                let vault = vaults.get_vault(call.vaultId).ok_or_else(|| anyhow!("Vault not found"))?;
+                let info = (
+                    vault.owner,
+                    vault.manager,
+                    vault.collateral,
+                    vault.equity,
+                    vaults.total_shares(call.vaultId),
+                    vaults.get_vault_pnl(call.vaultId)?,
+                );
+                Ok((Bytes::from(info.abi_encode_params()), gas))
+            }
+
+            _ => Err(anyhow!("Unknown function selector")),
+        }
+    }
+}
+
+/// Handler register routing contract calls to `VAULT_PRECOMPILE` into
+/// `vaults` at block time `timestamp`, with the calling contract as caller
+///
+/// Changing calls are refused unless `writable`; see
+/// `VaultPrecompile::contract_call`. A refused or failed call fails like a
+/// precompile error, consuming its gas.
+pub(crate) fn handle_register<EXT, DB: Database>(
+    vaults: Arc<Mutex<VaultPrecompile>>,
+    timestamp: u64,
+    writable: bool,
+) -> HandleRegisterBox<'static, EXT, DB> {
+    Box::new(move |handler| {
+        let vaults = vaults.clone();
+        let call = handler.execution.call.clone();
+        handler.execution.call = Arc::new(move |ctx: &mut Context<EXT, DB>, inputs: Box<CallInputs>| {
+            if inputs.bytecode_address != VAULT_PRECOMPILE {
+                return call(ctx, inputs);
+            }
+            let depth = ctx.evm.journaled_state.depth();
+            let mut vaults = vaults.lock().unwrap();
+            let mut gas = Gas::new(inputs.gas_limit);
+            let (result, output) = match vaults.contract_call(&inputs, depth, writable, timestamp) {
+                Ok((output, gas_used)) if gas.record_cost(gas_used) => {
+                    for log in vaults.take_logs() {
+                        ctx.evm
+                            .journaled_state
+                            .log(revm::primitives::Log::new_unchecked(log.address, log.topics, log.data));
+                    }
+                    (InstructionResult::Return, output)
+                }
+                _ => {
+                    gas.spend_all();
+                    (InstructionResult::PrecompileError, Bytes::new())
+                }
+            };
+            Ok(FrameOrResult::new_call_result(
+                InterpreterResult { result, gas, output },
+                inputs.return_memory_offset.clone(),
+            ))
+        });
+    })
+}
@@ -0,0 +1,271 @@
+// Block Proposal Builder
+//
+// Selects mempool transactions for a leader's block proposal
+
+use crate::mempool::Selection;
+use crate::{Mempool, Transaction};
+use alloy_primitives::Address;
+use consensus::hotstuff::engine::ConsensusEngine;
+use consensus::hotstuff::tx_validation::TxValidator;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Per-block limits applied when building a proposal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProposalLimits {
+    /// Total gas limit of included transactions
+    pub max_gas: u64,
+    /// Total encoded size of included transactions
+    pub max_bytes: usize,
+    /// Maximum number of transactions
+    pub max_txs: usize,
+}
+
+impl Default for ProposalLimits {
+    fn default() -> Self {
+        Self {
+            max_gas: 30_000_000,
+            max_bytes: 1024 * 1024,
+            max_txs: 10_000,
+        }
+    }
+}
+
+/// Stateless checks of encoded EVM transactions
+///
+/// Transactions must decode, fit in `max_tx_bytes`, target `chain_id`,
+/// carry their sender's signature, and have a gas limit between
+/// the intrinsic cost of a transfer and the block gas limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvmTxValidator {
+    pub chain_id: u64,
+    pub max_tx_bytes: usize,
+    pub max_gas: u64,
+}
+
+/// Gas every transaction pays before execution
+const INTRINSIC_GAS: u64 = 21_000;
+
+impl Default for EvmTxValidator {
+    fn default() -> Self {
+        Self {
+            chain_id: 1,
+            max_tx_bytes: 128 * 1024,
+            max_gas: ProposalLimits::default().max_gas,
+        }
+    }
+}
+
+impl TxValidator for EvmTxValidator {
+    fn validate(&self, tx: &[u8]) -> Result<(), String> {
+        if tx.len() > self.max_tx_bytes {
+            return Err(format!("{} bytes exceeds the {} byte limit", tx.len(), self.max_tx_bytes));
+        }
+        let tx: Transaction = serde_json::from_slice(tx).map_err(|e| format!("undecodable: {}", e))?;
+        if tx.chain_id != self.chain_id {
+            return Err(format!("chain ID {} instead of {}", tx.chain_id, self.chain_id));
+        }
+        tx.verify_sender().map_err(|e| format!("bad signature: {}", e))?;
+        if tx.gas_limit < INTRINSIC_GAS || tx.gas_limit > self.max_gas {
+            return Err(format!("gas limit {} out of range", tx.gas_limit));
+        }
+        Ok(())
+    }
+}
+
+/// Builds block payloads from the mempool
+///
+/// Transactions are taken by gas price across senders and in nonce order
+/// within each sender until the gas, byte or count limit is reached.
+/// Transactions already carried by uncommitted blocks in the consensus tree
+/// are dropped so a proposal never repeats a pending block's payload, and
+/// so are transactions the validator rejects.
+#[derive(Clone, Default)]
+pub struct ProposalBuilder {
+    limits: ProposalLimits,
+    validator: Option<Arc<dyn TxValidator>>,
+}
+
+impl std::fmt::Debug for ProposalBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProposalBuilder")
+            .field("limits", &self.limits)
+            .field("validator", &self.validator.is_some())
+            .finish()
+    }
+}
+
+impl ProposalBuilder {
+    /// Create a builder with the given limits
+    pub fn new(limits: ProposalLimits) -> Self {
+        Self { limits, validator: None }
+    }
+
+    /// Drop transactions failing the same checks validators apply at vote time
+    pub fn with_validator(mut self, validator: Arc<dyn TxValidator>) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Get the configured limits
+    pub fn limits(&self) -> &ProposalLimits {
+        &self.limits
+    }
+
+    /// Sender and nonce of every transaction in blocks above the last
+    /// committed height
+    pub fn pending_transactions(consensus: &ConsensusEngine) -> HashSet<(Address, u64)> {
+        let committed_height = consensus
+            .committed_blocks()
+            .last()
+            .map(|b| b.height)
+            .unwrap_or(0);
+
+        consensus
+            .validator()
+            .blocks
+            .values()
+            .filter(|block| block.height > committed_height)
+            .flat_map(|block| block.transactions.iter())
+            .filter_map(|bytes| serde_json::from_slice::<Transaction>(bytes).ok())
+            .map(|tx| (tx.from, tx.nonce))
+            .collect()
+    }
+
+    /// Take transactions from the mempool and encode them for a block
+    ///
+    /// Transactions in `pending` are discarded; a transaction that does not
+    /// fit the remaining budget stays in the mempool for a later block.
+    pub fn select(&self, mempool: &mut Mempool, pending: &HashSet<(Address, u64)>) -> Vec<Vec<u8>> {
+        let mut gas_used = 0u64;
+        let mut bytes_used = 0usize;
+        let mut payload = Vec::new();
+
+        mempool.select(|tx| {
+            if pending.contains(&(tx.from, tx.nonce)) {
+                return Selection::Discard;
+            }
+            let Ok(bytes) = serde_json::to_vec(tx) else {
+                return Selection::Discard;
+            };
+            if self.validator.as_ref().is_some_and(|v| v.validate(&bytes).is_err()) {
+                return Selection::Discard;
+            }
+            if payload.len() >= self.limits.max_txs
+                || gas_used.saturating_add(tx.gas_limit) > self.limits.max_gas
+                || bytes_used + bytes.len() > self.limits.max_bytes
+            {
+                return Selection::Defer;
+            }
+            gas_used += tx.gas_limit;
+            bytes_used += bytes.len();
+            payload.push(bytes);
+            Selection::Include
+        });
+
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{test_key, test_sender};
+    use alloy_primitives::U256;
+
+    fn unsigned_tx(from_byte: u8, nonce: u64, gas_price: u64) -> Transaction {
+        let mut tx = Transaction::transfer(
+            test_sender(from_byte),
+            Address::repeat_byte(0xff),
+            U256::from(1000),
+            nonce,
+        );
+        tx.gas_price = U256::from(gas_price);
+        tx
+    }
+
+    fn priced_tx(from_byte: u8, nonce: u64, gas_price: u64) -> Transaction {
+        unsigned_tx(from_byte, nonce, gas_price).sign(&test_key(from_byte)).unwrap()
+    }
+
+    fn decode(payload: &[Vec<u8>]) -> Vec<(Address, u64)> {
+        payload
+            .iter()
+            .map(|b| serde_json::from_slice::<Transaction>(b).unwrap())
+            .map(|tx| (tx.from, tx.nonce))
+            .collect()
+    }
+
+    #[test]
+    fn test_select_orders_by_gas_price_and_nonce_within_limits() {
+        let mut mempool = Mempool::new();
+        // Sender 1 submits out of nonce order; its second nonce pays more
+        mempool.add(priced_tx(0x01, 1, 50)).unwrap();
+        mempool.add(priced_tx(0x01, 0, 10)).unwrap();
+        mempool.add(priced_tx(0x02, 0, 30)).unwrap();
+        mempool.add(priced_tx(0x03, 0, 20)).unwrap();
+
+        let gas = priced_tx(0x01, 0, 0).gas_limit;
+        let builder = ProposalBuilder::new(ProposalLimits {
+            max_gas: gas * 3,
+            ..Default::default()
+        });
+        let payload = builder.select(&mut mempool, &HashSet::new());
+
+        // Sender 1's head only pays 10, so it waits behind 30 and 20
+        assert_eq!(decode(&payload), vec![(test_sender(0x02), 0), (test_sender(0x03), 0), (test_sender(0x01), 0)]);
+        assert_eq!(mempool.len(), 1);
+        let payload = builder.select(&mut mempool, &HashSet::new());
+        assert_eq!(decode(&payload), vec![(test_sender(0x01), 1)]);
+    }
+
+    #[test]
+    fn test_select_discards_invalid_transactions() {
+        let mut mempool = Mempool::new();
+        let mut wrong_chain = unsigned_tx(0x01, 0, 10);
+        wrong_chain.chain_id = 7;
+        let mut no_gas = unsigned_tx(0x02, 0, 10);
+        no_gas.gas_limit = 100;
+        mempool.add(wrong_chain.sign(&test_key(0x01)).unwrap()).unwrap();
+        mempool.add(no_gas.sign(&test_key(0x02)).unwrap()).unwrap();
+        mempool.add(priced_tx(0x03, 0, 10)).unwrap();
+
+        let validator = Arc::new(EvmTxValidator::default());
+        let payload = ProposalBuilder::default()
+            .with_validator(validator.clone())
+            .select(&mut mempool, &HashSet::new());
+
+        assert_eq!(decode(&payload), vec![(test_sender(0x03), 0)]);
+        assert!(mempool.is_empty());
+        assert!(validator.validate(b"junk").is_err());
+        assert!(validator.validate(&vec![b' '; 200 * 1024]).unwrap_err().contains("byte limit"));
+    }
+
+    #[test]
+    fn test_select_drops_transactions_in_pending_blocks() {
+        let mut mempool = Mempool::new();
+        mempool.add(priced_tx(0x01, 0, 10)).unwrap();
+        mempool.add(priced_tx(0x02, 0, 10)).unwrap();
+
+        let pending = HashSet::from([(test_sender(0x01), 0)]);
+        let payload = ProposalBuilder::default().select(&mut mempool, &pending);
+
+        assert_eq!(decode(&payload), vec![(test_sender(0x02), 0)]);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_validator_rejects_unsigned_and_spoofed_transactions() {
+        let validator = EvmTxValidator::default();
+        let encode = |tx: &Transaction| serde_json::to_vec(tx).unwrap();
+        assert!(validator.validate(&encode(&priced_tx(0x01, 0, 10))).is_ok());
+
+        let unsigned = unsigned_tx(0x01, 0, 10);
+        assert!(validator.validate(&encode(&unsigned)).unwrap_err().contains("not signed"));
+
+        // Signed by one key but claiming another sender
+        let mut spoofed = priced_tx(0x01, 0, 10);
+        spoofed.from = test_sender(0x02);
+        assert!(validator.validate(&encode(&spoofed)).unwrap_err().contains("bad signature"));
+    }
+}
@@ -0,0 +1,230 @@
+// Read Replica
+//
+// Non-validating node that follows committed blocks to serve queries
+
+use anyhow::{anyhow, Result};
+use consensus::crypto::{BLSPublicKey, Hash};
+use consensus::hotstuff::types::Block;
+use consensus::network::types::{ConsensusMessage, GossipMessage, NetworkMessage};
+use consensus::network::NetworkEvent;
+use consensus::storage::state_machine::{Query, QueryResponse, StateMachine};
+use consensus::storage::Storage;
+use consensus::sync::COMMIT_CHAIN_LENGTH;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+/// Non-validating node that applies committed blocks to its state
+///
+/// Blocks gossiped by validators are accepted only with a QC from a quorum
+/// of the validator set certifying their parent. A block is applied once
+/// `COMMIT_CHAIN_LENGTH` certified descendants extend it, the same
+/// three-chain rule validators commit by. The replica never votes or
+/// proposes, so any number of them can serve RPC, WebSocket and market data
+/// queries without changing the validator set.
+///
+/// A replica resumes from the latest block in its storage, e.g. one
+/// installed by snapshot sync; with empty storage it follows the first
+/// certified chain from height 1.
+pub struct ReplicaNode {
+    storage: Arc<Storage>,
+    state_machine: Arc<RwLock<Box<dyn StateMachine>>>,
+    validator_keys: HashMap<u64, BLSPublicKey>,
+    quorum_size: usize,
+    /// Height and hash of the last applied block
+    applied: Arc<RwLock<(u64, Option<Hash>)>>,
+    /// Certified blocks above the applied height, by hash
+    pending: Arc<RwLock<HashMap<Hash, Block>>>,
+}
+
+impl ReplicaNode {
+    /// Create a replica following the validator set `validator_keys`
+    pub fn new(
+        storage: Arc<Storage>,
+        state_machine: Box<dyn StateMachine>,
+        validator_keys: HashMap<u64, BLSPublicKey>,
+        quorum_size: usize,
+    ) -> Result<Self> {
+        let applied = storage
+            .get_latest_block()
+            .map_err(|e| anyhow!("Failed to load latest block: {}", e))?
+            .map_or((0, None), |block| (block.height, Some(block.hash())));
+
+        Ok(Self {
+            storage,
+            state_machine: Arc::new(RwLock::new(state_machine)),
+            validator_keys,
+            quorum_size,
+            applied: Arc::new(RwLock::new(applied)),
+            pending: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    /// Height of the last applied block
+    pub async fn committed_height(&self) -> u64 {
+        self.applied.read().await.0
+    }
+
+    /// Certified blocks waiting for their commit chain
+    pub async fn pending_blocks(&self) -> usize {
+        self.pending.read().await.len()
+    }
+
+    /// Query the applied state
+    pub async fn query(&self, query: &Query) -> Result<QueryResponse> {
+        self.state_machine
+            .read()
+            .await
+            .query(query)
+            .map_err(|e| anyhow!("Query failed: {}", e))
+    }
+
+    /// Accept a block from the network and apply every block it commits
+    ///
+    /// Returns the heights applied.
+    pub async fn on_block(&self, block: Block) -> Result<Vec<u64>> {
+        let qc = block
+            .justify
+            .as_ref()
+            .ok_or_else(|| anyhow!("Block at height {} has no QC", block.height))?;
+        if qc.block_hash != block.parent {
+            return Err(anyhow!("QC does not certify the parent of block {}", block.height));
+        }
+        qc.verify_quorum(&self.validator_keys, self.quorum_size)
+            .map_err(|e| anyhow!("Invalid QC on block {}: {}", block.height, e))?;
+
+        if block.height <= self.committed_height().await {
+            return Ok(Vec::new());
+        }
+        self.pending.write().await.insert(block.hash(), block);
+        self.apply_committed().await
+    }
+
+    /// Follow blocks gossiped or proposed by validators; everything else
+    /// is ignored
+    pub async fn handle_network_event(&self, event: NetworkEvent) -> Result<()> {
+        let block = match event {
+            NetworkEvent::GossipReceived { message: GossipMessage::Block { block, .. }, .. } => block,
+            NetworkEvent::MessageReceived {
+                message: NetworkMessage::Consensus(ConsensusMessage::Proposal { block, .. }),
+                ..
+            } => block,
+            _ => return Ok(()),
+        };
+
+        match self.on_block(block).await {
+            Ok(heights) if !heights.is_empty() => debug!("Replica applied blocks {:?}", heights),
+            Ok(_) => {}
+            Err(e) => warn!("Replica rejected block: {}", e),
+        }
+        Ok(())
+    }
+
+    /// Apply blocks in height order while each has a full commit chain
+    async fn apply_committed(&self) -> Result<Vec<u64>> {
+        let mut applied_heights = Vec::new();
+        loop {
+            let (height, hash) = *self.applied.read().await;
+            let next = {
+                let pending = self.pending.read().await;
+                pending
+                    .values()
+                    .filter(|b| b.height == height + 1 && hash.is_none_or(|h| b.parent == h))
+                    .find(|b| Self::has_commit_chain(&pending, b.hash(), COMMIT_CHAIN_LENGTH))
+                    .cloned()
+            };
+            let Some(block) = next else {
+                break;
+            };
+
+            {
+                let mut sm = self.state_machine.write().await;
+                sm.apply_block(&block)
+                    .map_err(|e| anyhow!("Failed to apply block {}: {}", block.height, e))?;
+                sm.commit()
+                    .map_err(|e| anyhow!("Failed to commit block {}: {}", block.height, e))?;
+            }
+            self.storage
+                .store_block(&block)
+                .map_err(|e| anyhow!("Failed to store block {}: {}", block.height, e))?;
+
+            *self.applied.write().await = (block.height, Some(block.hash()));
+            self.pending.write().await.retain(|_, b| b.height > block.height);
+            info!("Replica committed block {}", block.height);
+            applied_heights.push(block.height);
+        }
+        Ok(applied_heights)
+    }
+
+    /// Whether `depth` certified blocks extend the block with `hash`
+    fn has_commit_chain(pending: &HashMap<Hash, Block>, hash: Hash, depth: usize) -> bool {
+        depth == 0
+            || pending
+                .values()
+                .filter(|child| child.parent == hash)
+                .any(|child| Self::has_commit_chain(pending, child.hash(), depth - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::crypto::{threshold_combine, threshold_sign, BLSKeyPair};
+    use consensus::hotstuff::types::{vote_message, MessageType, QuorumCertificate};
+    use consensus::storage::state_machine::SimpleStateMachine;
+
+    /// Blocks at heights 1..=tip, each justified by a QC from validators 0-2
+    fn certified_chain(keys: &[BLSKeyPair], tip: u64) -> Vec<Block> {
+        let genesis = Block::genesis(keys[0].public_key.clone());
+        let mut blocks = vec![genesis];
+        for height in 1..=tip {
+            let parent = blocks.last().unwrap();
+            let data = vote_message(&MessageType::Prepare, &parent.hash(), parent.view);
+            let partials: Vec<_> = keys[..3].iter().map(|k| threshold_sign(&k.secret_key, &data)).collect();
+            let qc = QuorumCertificate::new(
+                MessageType::Prepare,
+                parent.hash(),
+                parent.view,
+                threshold_combine(&data, &partials, 3).unwrap(),
+            )
+            .with_signers(vec![0, 1, 2]);
+            let block = Block::new(parent.hash(), height, height, Some(qc), vec![], keys[0].public_key.clone());
+            blocks.push(block);
+        }
+        blocks.split_off(1)
+    }
+
+    #[tokio::test]
+    async fn test_replica_applies_blocks_once_committed() {
+        let keys: Vec<_> = (0..4).map(BLSKeyPair::with_id).collect();
+        let validator_keys: HashMap<u64, BLSPublicKey> =
+            keys.iter().enumerate().map(|(id, k)| (id as u64, k.public_key.clone())).collect();
+        let blocks = certified_chain(&keys, 5);
+
+        let storage = Arc::new(Storage::new_temp().unwrap());
+        let replica = ReplicaNode::new(storage.clone(), Box::new(SimpleStateMachine::new()), validator_keys, 3).unwrap();
+
+        // A block whose QC signer set does not match the signature is rejected
+        let mut forged = blocks[0].clone();
+        forged.justify.as_mut().unwrap().signers = vec![0, 1, 3];
+        assert!(replica.on_block(forged).await.is_err());
+
+        // Blocks arrive out of order; height 1 commits once 2-4 extend it
+        for block in [&blocks[2], &blocks[0], &blocks[1]] {
+            assert!(replica.on_block(block.clone()).await.unwrap().is_empty());
+        }
+        assert_eq!(replica.on_block(blocks[3].clone()).await.unwrap(), vec![1]);
+        assert_eq!(replica.on_block(blocks[4].clone()).await.unwrap(), vec![2]);
+        assert_eq!(replica.committed_height().await, 2);
+        assert_eq!(replica.pending_blocks().await, 3);
+        assert_eq!(storage.get_latest_block_height().unwrap(), Some(2));
+
+        let response = replica.query(&Query::GetStateHash { height: 2 }).await.unwrap();
+        assert!(matches!(response, QueryResponse::Hash(_)));
+
+        // A restarted replica resumes from storage
+        let restarted = ReplicaNode::new(storage, Box::new(SimpleStateMachine::new()), HashMap::new(), 3).unwrap();
+        assert_eq!(restarted.committed_height().await, 2);
+    }
+}
@@ -0,0 +1,904 @@
+// Ethereum JSON-RPC
+//
+// Serves the core `eth_*` methods over HTTP so standard wallets and tooling
+// can connect: chain and account reads against the executor, raw
+// transaction submission to the mempool, and receipts and logs of executed
+// blocks, which the node records into the API as blocks are applied. Only
+// the latest state is kept for EVM accounts, so state reads at other
+// heights are not found.
+//
+// The `debug` namespace serves transaction traces: those the executor
+// recorded while tracing was enabled, and traces of calls on demand.
+
+use crate::executor::EvmExecutor;
+use crate::mempool::Mempool;
+use crate::trace::{TraceConfig, TransactionTrace};
+use crate::types::{Block, Log, Receipt, Transaction};
+use alloy_primitives::{Address, Bloom, Bytes, B256, U256, U64};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{RwLock, Semaphore};
+use tokio::time::timeout;
+use tracing::{debug, info};
+
+/// Executed blocks whose receipts and logs stay queryable
+pub const INDEXED_BLOCKS: usize = 8192;
+
+/// Most blocks one `eth_getLogs` request may span
+const MAX_LOG_BLOCK_RANGE: u64 = 1024;
+
+/// Gas limit of `eth_call` and `eth_estimateGas` when the request sets none
+const CALL_GAS_LIMIT: u64 = 30_000_000;
+
+/// Largest request body accepted
+const MAX_REQUEST_BYTES: usize = 5 * 1024 * 1024;
+
+/// Most requests one batch may carry
+const MAX_BATCH_SIZE: usize = 100;
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+/// Execution reverted (as geth reports it)
+const EXECUTION_REVERTED: i64 = 3;
+
+/// JSON-RPC error object
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    fn invalid_params(message: impl Display) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    fn server(message: impl Display) -> Self {
+        Self::new(SERVER_ERROR, message)
+    }
+
+    fn to_json(&self) -> Value {
+        match &self.data {
+            Some(data) => json!({ "code": self.code, "message": self.message, "data": data }),
+            None => json!({ "code": self.code, "message": self.message }),
+        }
+    }
+}
+
+type RpcResult = Result<Value, RpcError>;
+
+/// JSON-RPC endpoint configuration
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    /// Address the HTTP endpoint listens on
+    pub listen_addr: SocketAddr,
+    /// Time a client has to send its whole request
+    pub read_timeout: Duration,
+    /// Time a client has to take the whole response
+    pub write_timeout: Duration,
+    /// Connections served at once; further clients wait to be accepted
+    pub max_connections: usize,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: ([127, 0, 0, 1], 8545).into(),
+            read_timeout: Duration::from_secs(10),
+            write_timeout: Duration::from_secs(10),
+            max_connections: 256,
+        }
+    }
+}
+
+/// Call object of `eth_call` and `eth_estimateGas`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CallRequest {
+    from: Option<Address>,
+    to: Option<Address>,
+    gas: Option<U64>,
+    gas_price: Option<U256>,
+    max_fee_per_gas: Option<U256>,
+    max_priority_fee_per_gas: Option<U256>,
+    value: Option<U256>,
+    /// Preferred over `data` when both are set
+    input: Option<Bytes>,
+    data: Option<Bytes>,
+}
+
+/// Options of `debug_traceTransaction` and `debug_traceCall`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceOptions {
+    /// `callTracer` for the call tree; the opcode log when unset
+    tracer: Option<String>,
+    #[serde(default)]
+    disable_stack: bool,
+}
+
+impl TraceOptions {
+    fn from_params(params: &[Value], index: usize) -> Result<Self, RpcError> {
+        match params.get(index) {
+            None | Some(Value::Null) => Ok(Self::default()),
+            Some(_) => param(params, index),
+        }
+    }
+
+    fn call_tracer(&self) -> Result<bool, RpcError> {
+        match self.tracer.as_deref() {
+            None => Ok(false),
+            Some("callTracer") => Ok(true),
+            Some(other) => Err(RpcError::invalid_params(format!("Unsupported tracer {}", other))),
+        }
+    }
+
+    /// A trace as the chosen tracer reports it
+    fn to_json(&self, trace: &TransactionTrace) -> RpcResult {
+        let value = if self.call_tracer()? {
+            serde_json::to_value(&trace.call)
+        } else {
+            serde_json::to_value(trace)
+        };
+        value.map_err(RpcError::server)
+    }
+}
+
+/// One value or any of several
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T: PartialEq> OneOrMany<T> {
+    fn matches(&self, value: &T) -> bool {
+        match self {
+            Self::One(one) => one == value,
+            Self::Many(many) => many.contains(value),
+        }
+    }
+}
+
+/// Filter object of `eth_getLogs`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogFilter {
+    from_block: Option<Value>,
+    to_block: Option<Value>,
+    block_hash: Option<B256>,
+    address: Option<OneOrMany<Address>>,
+    /// Topics by position; a null position matches any topic
+    #[serde(default)]
+    topics: Vec<Option<OneOrMany<B256>>>,
+}
+
+impl LogFilter {
+    fn matches(&self, log: &Log) -> bool {
+        if self.address.as_ref().is_some_and(|address| !address.matches(&log.address)) {
+            return false;
+        }
+        self.topics.iter().enumerate().all(|(position, topic)| match topic {
+            None => true,
+            Some(topic) => log.topics.get(position).is_some_and(|t| topic.matches(t)),
+        })
+    }
+}
+
+/// A receipt and where it sits in the chain
+#[derive(Debug, Clone)]
+struct IndexedReceipt {
+    block_number: u64,
+    block_hash: B256,
+    /// Position in the block
+    index: usize,
+    transaction: Transaction,
+    receipt: Receipt,
+    cumulative_gas_used: u64,
+    /// Position in the block of the receipt's first log
+    first_log_index: usize,
+}
+
+impl IndexedReceipt {
+    fn log_json(&self, offset: usize, log: &Log) -> Value {
+        json!({
+            "address": log.address,
+            "topics": log.topics,
+            "data": log.data,
+            "blockNumber": U64::from(self.block_number),
+            "blockHash": self.block_hash,
+            "transactionHash": self.receipt.transaction_hash,
+            "transactionIndex": U64::from(self.index),
+            "logIndex": U64::from(self.first_log_index + offset),
+            "removed": false,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let receipt = &self.receipt;
+        let mut bloom = Bloom::default();
+        for log in &receipt.logs {
+            bloom.accrue_raw_log(log.address, &log.topics);
+        }
+        let logs: Vec<Value> = receipt
+            .logs
+            .iter()
+            .enumerate()
+            .map(|(offset, log)| self.log_json(offset, log))
+            .collect();
+        let tx_type = if self.transaction.max_fee_per_gas.is_some() { 2u64 } else { 0 };
+        json!({
+            "transactionHash": receipt.transaction_hash,
+            "transactionIndex": U64::from(self.index),
+            "blockHash": self.block_hash,
+            "blockNumber": U64::from(self.block_number),
+            "from": receipt.from,
+            "to": receipt.to,
+            "contractAddress": receipt.contract_address,
+            "gasUsed": U64::from(receipt.gas_used),
+            "cumulativeGasUsed": U64::from(self.cumulative_gas_used),
+            "status": U64::from(receipt.success as u64),
+            "type": U64::from(tx_type),
+            "logs": logs,
+            "logsBloom": bloom,
+        })
+    }
+}
+
+/// Receipts of recently executed blocks, by transaction hash
+#[derive(Debug, Default)]
+struct ReceiptIndex {
+    receipts: HashMap<B256, IndexedReceipt>,
+    /// Block hash and transaction hashes (in block order) by height
+    blocks: BTreeMap<u64, (B256, Vec<B256>)>,
+}
+
+impl ReceiptIndex {
+    fn forget(&mut self, height: u64) {
+        if let Some((_, hashes)) = self.blocks.remove(&height) {
+            for hash in hashes {
+                self.receipts.remove(&hash);
+            }
+        }
+    }
+
+    /// Record an executed block; a height recorded again (after a
+    /// rollback) replaces what was there
+    fn record(&mut self, block: &Block, receipts: &[Receipt]) {
+        self.forget(block.number);
+
+        let mut cumulative_gas_used = 0;
+        let mut first_log_index = 0;
+        let mut hashes = Vec::with_capacity(receipts.len());
+        for (index, (transaction, receipt)) in block.transactions.iter().zip(receipts).enumerate() {
+            cumulative_gas_used += receipt.gas_used;
+            hashes.push(receipt.transaction_hash);
+            self.receipts.insert(
+                receipt.transaction_hash,
+                IndexedReceipt {
+                    block_number: block.number,
+                    block_hash: block.hash,
+                    index,
+                    transaction: transaction.clone(),
+                    receipt: receipt.clone(),
+                    cumulative_gas_used,
+                    first_log_index,
+                },
+            );
+            first_log_index += receipt.logs.len();
+        }
+        self.blocks.insert(block.number, (block.hash, hashes));
+
+        while self.blocks.len() > INDEXED_BLOCKS {
+            let oldest = *self.blocks.keys().next().unwrap();
+            self.forget(oldest);
+        }
+    }
+
+    /// Logs matching `filter` in blocks `from..=to`, oldest first
+    fn logs(&self, filter: &LogFilter, from: u64, to: u64) -> Vec<Value> {
+        let mut logs = Vec::new();
+        if from > to {
+            return logs;
+        }
+        for (_, hashes) in self.blocks.range(from..=to).map(|(_, block)| block) {
+            for entry in hashes.iter().filter_map(|hash| self.receipts.get(hash)) {
+                for (offset, log) in entry.receipt.logs.iter().enumerate() {
+                    if filter.matches(log) {
+                        logs.push(entry.log_json(offset, log));
+                    }
+                }
+            }
+        }
+        logs
+    }
+
+    fn height_of(&self, block_hash: &B256) -> Option<u64> {
+        self.blocks
+            .iter()
+            .find(|(_, (hash, _))| hash == block_hash)
+            .map(|(height, _)| *height)
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, RpcError> {
+    let value = params
+        .get(index)
+        .cloned()
+        .ok_or_else(|| RpcError::invalid_params(format!("Missing parameter {}", index)))?;
+    serde_json::from_value(value).map_err(|e| RpcError::invalid_params(format!("Parameter {}: {}", index, e)))
+}
+
+/// Height a block number or tag refers to; no tag means the latest block
+fn block_number(tag: Option<&Value>, latest: u64) -> Result<u64, RpcError> {
+    match tag {
+        None | Some(Value::Null) => Ok(latest),
+        Some(Value::String(tag)) => match tag.as_str() {
+            "latest" | "pending" | "safe" | "finalized" => Ok(latest),
+            "earliest" => Ok(0),
+            number => number
+                .parse::<U64>()
+                .map(|number| number.to::<u64>())
+                .map_err(|e| RpcError::invalid_params(format!("Invalid block number {}: {}", number, e))),
+        },
+        Some(other) => Err(RpcError::invalid_params(format!("Invalid block tag {}", other))),
+    }
+}
+
+/// `eth_*` methods over the executor, mempool and recorded receipts
+pub struct EthApi {
+    executor: Arc<RwLock<EvmExecutor>>,
+    mempool: Arc<RwLock<Mempool>>,
+    receipts: RwLock<ReceiptIndex>,
+}
+
+impl EthApi {
+    pub fn new(executor: Arc<RwLock<EvmExecutor>>, mempool: Arc<RwLock<Mempool>>) -> Self {
+        Self {
+            executor,
+            mempool,
+            receipts: RwLock::new(ReceiptIndex::default()),
+        }
+    }
+
+    /// Make an executed block's receipts and logs queryable, e.g. from
+    /// `EvmStateMachine::last_block` and `last_receipts`
+    pub async fn record_block(&self, block: &Block, receipts: &[Receipt]) {
+        self.receipts.write().await.record(block, receipts);
+    }
+
+    /// Answer a JSON-RPC request or batch of requests
+    pub async fn handle(&self, request: Value) -> Value {
+        match request {
+            Value::Array(batch) if batch.is_empty() => {
+                Self::response(Value::Null, Err(RpcError::new(INVALID_REQUEST, "Empty batch")))
+            }
+            Value::Array(batch) if batch.len() > MAX_BATCH_SIZE => {
+                let message = format!("Batch of {} requests exceeds the limit of {}", batch.len(), MAX_BATCH_SIZE);
+                Self::response(Value::Null, Err(RpcError::new(INVALID_REQUEST, message)))
+            }
+            Value::Array(batch) => {
+                let mut responses = Vec::with_capacity(batch.len());
+                for request in batch {
+                    responses.push(self.handle_single(request).await);
+                }
+                Value::Array(responses)
+            }
+            request => self.handle_single(request).await,
+        }
+    }
+
+    fn response(id: Value, result: RpcResult) -> Value {
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error.to_json() }),
+        }
+    }
+
+    async fn handle_single(&self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Self::response(id, Err(RpcError::new(INVALID_REQUEST, "Missing method")));
+        };
+        let params = match request.get("params") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(params)) => params.clone(),
+            Some(_) => {
+                return Self::response(id, Err(RpcError::invalid_params("Parameters must be an array")));
+            }
+        };
+        let result = self.dispatch(method, &params).await;
+        Self::response(id, result)
+    }
+
+    async fn dispatch(&self, method: &str, params: &[Value]) -> RpcResult {
+        match method {
+            "eth_chainId" => Ok(json!(U64::from(self.mempool.read().await.chain_id()))),
+            "eth_blockNumber" => Ok(json!(U64::from(self.executor.read().await.block_number()))),
+            "eth_getBalance" => {
+                let address: Address = param(params, 0)?;
+                let executor = self.executor.read().await;
+                self.ensure_latest(&executor, params.get(1))?;
+                let balance = executor.get_balance(&address).map_err(RpcError::server)?;
+                Ok(json!(balance))
+            }
+            "eth_getTransactionCount" => {
+                let address: Address = param(params, 0)?;
+                let executor = self.executor.read().await;
+                let mut nonce = executor.get_nonce(&address).map_err(RpcError::server)?;
+                if params.get(1).and_then(Value::as_str) == Some("pending") {
+                    nonce = nonce.max(self.mempool.read().await.next_nonce(&address));
+                } else {
+                    self.ensure_latest(&executor, params.get(1))?;
+                }
+                Ok(json!(U64::from(nonce)))
+            }
+            "eth_sendRawTransaction" => {
+                let raw: Bytes = param(params, 0)?;
+                let hash = self.mempool.write().await.add_raw(&raw).map_err(RpcError::server)?;
+                Ok(json!(hash))
+            }
+            "eth_call" => {
+                let executor = self.executor.read().await;
+                let tx = self.call_transaction(&executor, params)?;
+                let receipt = executor.call(&tx).map_err(RpcError::server)?;
+                Ok(json!(Self::successful(receipt)?.output))
+            }
+            "eth_estimateGas" => {
+                let executor = self.executor.read().await;
+                let tx = self.call_transaction(&executor, params)?;
+                // A revert at the cap is reported with its data, as for calls
+                let receipt = executor.call(&tx).map_err(RpcError::server)?;
+                Self::successful(receipt)?;
+                let gas = executor.estimate_gas(&tx).map_err(RpcError::server)?;
+                Ok(json!(U64::from(gas)))
+            }
+            "eth_getTransactionReceipt" => {
+                let hash: B256 = param(params, 0)?;
+                let receipts = self.receipts.read().await;
+                Ok(receipts.receipts.get(&hash).map_or(Value::Null, IndexedReceipt::to_json))
+            }
+            "eth_getLogs" => {
+                let filter: LogFilter = param(params, 0)?;
+                let receipts = self.receipts.read().await;
+                let (from, to) = match filter.block_hash {
+                    Some(hash) => {
+                        let height = receipts
+                            .height_of(&hash)
+                            .ok_or_else(|| RpcError::server(format!("Block {} not found", hash)))?;
+                        (height, height)
+                    }
+                    None => {
+                        let latest = self.executor.read().await.block_number();
+                        (
+                            block_number(filter.from_block.as_ref(), latest)?,
+                            block_number(filter.to_block.as_ref(), latest)?,
+                        )
+                    }
+                };
+                if from > to {
+                    return Err(RpcError::invalid_params(format!("fromBlock {} is after toBlock {}", from, to)));
+                }
+                if to - from >= MAX_LOG_BLOCK_RANGE {
+                    return Err(RpcError::invalid_params(format!(
+                        "Block range exceeds {} blocks",
+                        MAX_LOG_BLOCK_RANGE
+                    )));
+                }
+                Ok(Value::Array(receipts.logs(&filter, from, to)))
+            }
+            "debug_traceTransaction" => {
+                let hash: B256 = param(params, 0)?;
+                let options = TraceOptions::from_params(params, 1)?;
+                let executor = self.executor.read().await;
+                let trace = executor.trace(&hash).ok_or_else(|| {
+                    RpcError::server(format!("No trace of transaction {} (tracing disabled or pruned)", hash))
+                })?;
+                options.to_json(trace)
+            }
+            "debug_traceCall" => {
+                let options = TraceOptions::from_params(params, 2)?;
+                let config = TraceConfig {
+                    opcodes: !options.call_tracer()?,
+                    stack: !options.disable_stack,
+                };
+                let executor = self.executor.read().await;
+                let tx = self.call_transaction(&executor, params)?;
+                let trace = executor.trace_call(&tx, config).map_err(RpcError::server)?;
+                options.to_json(&trace)
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method {} not found", method))),
+        }
+    }
+
+    /// Fail unless `tag` refers to the latest block, the only state kept
+    fn ensure_latest(&self, executor: &EvmExecutor, tag: Option<&Value>) -> Result<(), RpcError> {
+        let latest = executor.block_number();
+        let height = block_number(tag, latest)?;
+        if height != latest {
+            return Err(RpcError::server(format!("State at block {} is not available", height)));
+        }
+        Ok(())
+    }
+
+    /// Transaction a call object describes, run against the latest state
+    fn call_transaction(&self, executor: &EvmExecutor, params: &[Value]) -> Result<Transaction, RpcError> {
+        let request: CallRequest = param(params, 0)?;
+        self.ensure_latest(executor, params.get(1))?;
+
+        let from = request.from.unwrap_or_default();
+        let nonce = executor.get_nonce(&from).map_err(RpcError::server)?;
+        let data = request.input.or(request.data).unwrap_or_default();
+        let mut tx = match request.to {
+            Some(to) => Transaction::call(from, to, data, nonce),
+            None => Transaction::deploy(from, data, nonce),
+        };
+        tx.value = request.value.unwrap_or_default();
+        tx.gas_limit = request.gas.map_or(CALL_GAS_LIMIT, |gas| gas.to());
+        tx.gas_price = request.gas_price.unwrap_or_default();
+        if let Some(max_fee) = request.max_fee_per_gas {
+            tx = tx.with_fees(max_fee, request.max_priority_fee_per_gas.unwrap_or_default());
+        }
+        Ok(tx)
+    }
+
+    /// The receipt, or an execution reverted error carrying its output
+    fn successful(receipt: Receipt) -> Result<Receipt, RpcError> {
+        if !receipt.success {
+            let mut error = RpcError::new(EXECUTION_REVERTED, "execution reverted");
+            error.data = Some(json!(receipt.output));
+            return Err(error);
+        }
+        Ok(receipt)
+    }
+}
+
+/// HTTP endpoint serving an `EthApi`
+pub struct RpcServer {
+    listener: TcpListener,
+    api: Arc<EthApi>,
+    config: RpcConfig,
+    connections: Arc<Semaphore>,
+}
+
+impl RpcServer {
+    /// Bind the endpoint
+    pub async fn bind(config: RpcConfig, api: Arc<EthApi>) -> io::Result<Self> {
+        let listener = TcpListener::bind(config.listen_addr).await?;
+        info!("Serving JSON-RPC on http://{}", listener.local_addr()?);
+        let connections = Arc::new(Semaphore::new(config.max_connections));
+        Ok(Self { listener, api, config, connections })
+    }
+
+    /// Bound address
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve requests until the task is dropped
+    pub async fn run(self) -> io::Result<()> {
+        loop {
+            // Never closed, so acquiring only waits
+            let permit = self.connections.clone().acquire_owned().await.expect("semaphore closed");
+            let (stream, peer) = self.listener.accept().await?;
+            let api = self.api.clone();
+            let config = self.config.clone();
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream, &api, &config).await {
+                    debug!("JSON-RPC request from {} failed: {}", peer, e);
+                }
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Read one HTTP request, returning its method and body
+async fn read_request(stream: &mut TcpStream) -> io::Result<(String, Vec<u8>)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if buffer.len() > MAX_REQUEST_BYTES {
+            return Err(invalid("Request headers too large"));
+        }
+        let len = stream.read(&mut chunk).await?;
+        if len == 0 {
+            return Err(invalid("Connection closed mid-request"));
+        }
+        buffer.extend_from_slice(&chunk[..len]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let method = head.split_whitespace().next().unwrap_or_default().to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.trim().parse::<usize>())
+        .transpose()
+        .map_err(|_| invalid("Invalid Content-Length"))?
+        .unwrap_or(0);
+    if content_length > MAX_REQUEST_BYTES {
+        return Err(invalid("Request body too large"));
+    }
+
+    let mut body = buffer.split_off(header_end);
+    while body.len() < content_length {
+        let len = stream.read(&mut chunk).await?;
+        if len == 0 {
+            return Err(invalid("Connection closed mid-body"));
+        }
+        body.extend_from_slice(&chunk[..len]);
+    }
+    body.truncate(content_length);
+    Ok((method, body))
+}
+
+/// Answer one HTTP request; only `POST` with a JSON-RPC body is served.
+/// Clients too slow to send the request or take the response are dropped.
+async fn respond(mut stream: TcpStream, api: &EthApi, config: &RpcConfig) -> io::Result<()> {
+    let timed_out = |_| io::Error::new(io::ErrorKind::TimedOut, "Client too slow");
+    let (method, body) = timeout(config.read_timeout, read_request(&mut stream)).await.map_err(timed_out)??;
+    let response = if method == "POST" {
+        let reply = match serde_json::from_slice::<Value>(&body) {
+            Ok(request) => api.handle(request).await,
+            Err(e) => EthApi::response(Value::Null, Err(RpcError::new(PARSE_ERROR, e))),
+        };
+        let body = reply.to_string();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 405 Method Not Allowed\r\nAllow: POST\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    timeout(config.write_timeout, async {
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    })
+    .await
+    .map_err(timed_out)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::EvmStorage;
+    use rocksdb::DB;
+    use tempfile::tempdir;
+
+    fn create_test_api() -> (EthApi, Arc<RwLock<EvmExecutor>>, tempfile::TempDir) {
+        let temp_dir = tempdir().unwrap();
+        let db = DB::open_default(temp_dir.path()).unwrap();
+        let executor = Arc::new(RwLock::new(EvmExecutor::new(EvmStorage::new(Arc::new(db)))));
+        let api = EthApi::new(executor.clone(), Arc::new(RwLock::new(Mempool::new())));
+        (api, executor, temp_dir)
+    }
+
+    async fn call(api: &EthApi, method: &str, params: Value) -> Value {
+        api.handle(json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_eth_methods_read_state_and_submit() {
+        let (api, executor, _temp) = create_test_api();
+        let key = k256::ecdsa::SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let sender = Address::from_private_key(&key);
+        let receiver = Address::repeat_byte(0x02);
+        executor.write().await.create_account(sender, U256::from(1_000_000)).unwrap();
+        executor.write().await.set_block_context(5, 0);
+
+        assert_eq!(call(&api, "eth_chainId", json!([])).await["result"], "0x1");
+        assert_eq!(call(&api, "eth_blockNumber", json!([])).await["result"], "0x5");
+        let balance = call(&api, "eth_getBalance", json!([sender, "latest"])).await;
+        assert_eq!(balance["result"], "0xf4240");
+        let historic = call(&api, "eth_getBalance", json!([sender, "0x4"])).await;
+        assert_eq!(historic["error"]["code"], SERVER_ERROR);
+
+        // Calls run against the state without changing it
+        let transfer = json!({ "from": sender, "to": receiver, "value": "0x64" });
+        assert_eq!(call(&api, "eth_estimateGas", json!([transfer])).await["result"], "0x5208");
+        assert_eq!(call(&api, "eth_call", json!([transfer, "latest"])).await["result"], "0x");
+        assert_eq!(call(&api, "eth_getBalance", json!([receiver])).await["result"], "0x0");
+
+        let tx = Transaction::transfer(sender, receiver, U256::from(100), 0).sign(&key).unwrap();
+        let raw = tx.encode_raw().unwrap();
+        let sent = call(&api, "eth_sendRawTransaction", json!([raw])).await;
+        assert_eq!(sent["result"], json!(tx.hash()));
+        let pending = call(&api, "eth_getTransactionCount", json!([sender, "pending"])).await;
+        assert_eq!(pending["result"], "0x1");
+        assert_eq!(call(&api, "eth_getTransactionCount", json!([sender])).await["result"], "0x0");
+
+        assert_eq!(call(&api, "eth_foo", json!([])).await["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(call(&api, "eth_getBalance", json!(["nope"])).await["error"]["code"], INVALID_PARAMS);
+        let batch = api.handle(json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "eth_chainId" },
+            { "jsonrpc": "2.0", "id": 2, "method": "eth_blockNumber" },
+        ]))
+        .await;
+        assert_eq!(batch[1]["id"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_receipts_and_logs_of_recorded_blocks() {
+        let (api, _executor, _temp) = create_test_api();
+        let emitter = Address::repeat_byte(0xee);
+        let topic = B256::repeat_byte(0x01);
+        let receipt = |nonce: u64, logs: Vec<Log>| {
+            let tx = Transaction::call(Address::repeat_byte(0x01), emitter, Bytes::new(), nonce);
+            let receipt = Receipt {
+                transaction_hash: tx.hash(),
+                from: tx.from,
+                to: tx.to,
+                contract_address: None,
+                gas_used: 30_000,
+                success: true,
+                output: Bytes::new(),
+                logs,
+            };
+            (tx, receipt)
+        };
+        let log = |topics: Vec<B256>| Log { address: emitter, topics, data: Bytes::from(vec![0x2a]) };
+        let (tx0, receipt0) = receipt(0, vec![log(vec![topic])]);
+        let (tx1, receipt1) = receipt(1, vec![log(vec![B256::repeat_byte(0x02)]), log(vec![topic])]);
+        let block = Block {
+            number: 1,
+            hash: B256::repeat_byte(0xbb),
+            parent_hash: B256::ZERO,
+            timestamp: 0,
+            transactions: vec![tx0, tx1.clone()],
+            state_root: B256::ZERO,
+            receipts_root: B256::ZERO,
+        };
+        api.record_block(&block, &[receipt0, receipt1]).await;
+
+        let found = call(&api, "eth_getTransactionReceipt", json!([tx1.hash()])).await["result"].clone();
+        assert_eq!(found["transactionIndex"], "0x1");
+        assert_eq!(found["cumulativeGasUsed"], "0xea60");
+        assert_eq!(found["status"], "0x1");
+        assert_eq!(found["logs"][1]["logIndex"], "0x2");
+        let missing = call(&api, "eth_getTransactionReceipt", json!([B256::ZERO])).await;
+        assert_eq!(missing["result"], Value::Null);
+
+        let filter = json!({ "fromBlock": "0x1", "toBlock": "0x1", "address": [emitter], "topics": [topic] });
+        let logs = call(&api, "eth_getLogs", json!([filter])).await["result"].clone();
+        assert_eq!(logs.as_array().unwrap().len(), 2);
+        assert_eq!(logs[1]["logIndex"], "0x2");
+        let by_hash = json!({ "blockHash": block.hash, "topics": [null, topic] });
+        assert_eq!(call(&api, "eth_getLogs", json!([by_hash])).await["result"], json!([]));
+        let elsewhere = json!({ "fromBlock": "0x2", "toBlock": "0x3" });
+        assert_eq!(call(&api, "eth_getLogs", json!([elsewhere])).await["result"], json!([]));
+        let reversed = json!({ "fromBlock": "0x3", "toBlock": "0x1" });
+        assert_eq!(call(&api, "eth_getLogs", json!([reversed])).await["error"]["code"], INVALID_PARAMS);
+        let too_wide = json!({ "fromBlock": "0x0", "toBlock": format!("{:#x}", MAX_LOG_BLOCK_RANGE) });
+        assert_eq!(call(&api, "eth_getLogs", json!([too_wide])).await["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_debug_namespace_serves_traces() {
+        let (api, executor, _temp) = create_test_api();
+        let sender = Address::repeat_byte(0x01);
+        let receiver = Address::repeat_byte(0x02);
+        let mut executor = executor.write().await;
+        executor.create_account(sender, U256::from(1_000_000)).unwrap();
+        executor.set_tracing(Some(TraceConfig::default()));
+        let tx = Transaction::transfer(sender, receiver, U256::from(100), 0);
+        let hash = executor.execute_and_commit(&tx).unwrap().transaction_hash;
+        drop(executor);
+
+        let trace = call(&api, "debug_traceTransaction", json!([hash])).await["result"].clone();
+        assert_eq!(trace["gas"], 21_000);
+        assert_eq!(trace["failed"], false);
+        let options = json!({ "tracer": "callTracer" });
+        let frame = call(&api, "debug_traceTransaction", json!([hash, options])).await["result"].clone();
+        assert_eq!(frame["type"], "CALL");
+        assert_eq!(frame["to"], json!(receiver));
+        assert_eq!(frame["gasUsed"], "0x5208");
+        let missing = call(&api, "debug_traceTransaction", json!([B256::ZERO])).await;
+        assert_eq!(missing["error"]["code"], SERVER_ERROR);
+        let unsupported = call(&api, "debug_traceTransaction", json!([hash, { "tracer": "prestateTracer" }])).await;
+        assert_eq!(unsupported["error"]["code"], INVALID_PARAMS);
+
+        let transfer = json!({ "from": sender, "to": receiver, "value": "0x1" });
+        let traced = call(&api, "debug_traceCall", json!([transfer, "latest"])).await["result"].clone();
+        assert_eq!(traced["structLogs"], json!([]));
+        assert_eq!(traced["call"]["value"], "0x1");
+    }
+
+    #[tokio::test]
+    async fn test_http_endpoint() {
+        let (api, _executor, _temp) = create_test_api();
+        let config = RpcConfig { listen_addr: ([127, 0, 0, 1], 0).into(), ..Default::default() };
+        let server = RpcServer::bind(config, Arc::new(api))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let send = |request: String| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let body = r#"{"jsonrpc":"2.0","id":7,"method":"eth_chainId","params":[]}"#;
+        let response = send(format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        ))
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(r#"{"id":7,"jsonrpc":"2.0","result":"0x1"}"#));
+
+        let response = send("POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\n{x}".to_string()).await;
+        assert!(response.contains(&PARSE_ERROR.to_string()));
+        assert!(send("GET / HTTP/1.1\r\n\r\n".to_string()).await.starts_with("HTTP/1.1 405"));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_limits() {
+        let (api, _executor, _temp) = create_test_api();
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_chainId" });
+        let reply = api.handle(Value::Array(vec![request.clone(); MAX_BATCH_SIZE + 1])).await;
+        assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+        assert_eq!(api.handle(Value::Array(vec![request; MAX_BATCH_SIZE])).await.as_array().unwrap().len(), MAX_BATCH_SIZE);
+
+        let config = RpcConfig {
+            listen_addr: ([127, 0, 0, 1], 0).into(),
+            read_timeout: Duration::from_millis(200),
+            max_connections: 1,
+            ..Default::default()
+        };
+        let server = RpcServer::bind(config, Arc::new(api)).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        // An idle client holds the only connection until its read times out
+        let started = std::time::Instant::now();
+        let mut idle = TcpStream::connect(addr).await.unwrap();
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let body = r#"{"jsonrpc":"2.0","id":7,"method":"eth_chainId"}"#;
+        let request = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        // Dropped without an answer
+        let mut unanswered = String::new();
+        idle.read_to_string(&mut unanswered).await.unwrap();
+        assert!(unanswered.is_empty());
+    }
+}
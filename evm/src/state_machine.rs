@@ -2,19 +2,21 @@
 //
 // Bridges consensus StateMachine trait with EVM executor
 
-use alloy_primitives::{Address, B256};
+use alloy_primitives::{Address, B256, U256};
 use anyhow::Result;
 use consensus::storage::state_machine::{
     Query, QueryResponse, State, StateError, StateMachine, StateTransition as ConsensusStateTransition,
 };
+use consensus::watchdog::Heartbeat;
 use consensus::{crypto::Hash, hotstuff::types::Block};
 use rocksdb::DB;
 use std::sync::Arc;
 
 use crate::checkpoint::CheckpointManager;
+use crate::commitments;
 use crate::executor::EvmExecutor;
 use crate::storage::EvmStorage;
-use crate::types::{Receipt, Transaction};
+use crate::types::{Block as EvmBlock, BundleSimulation, Receipt, Transaction};
 
 /// EVM State Machine
 /// 
@@ -26,7 +28,14 @@ pub struct EvmStateMachine {
     current_state: State,
     pending_state: Option<State>,
     pending_receipts: Vec<Receipt>,
+    /// Executor snapshot taken before the pending block, reverted to on
+    /// rollback
+    pending_snapshot: Option<usize>,
+    /// Last executed block with its state and receipts roots
+    last_block: Option<EvmBlock>,
     history: Vec<State>,
+    /// Block execution progress, for the watchdog
+    heartbeat: Option<Heartbeat>,
 }
 
 impl EvmStateMachine {
@@ -49,7 +58,10 @@ impl EvmStateMachine {
             current_state: genesis.clone(),
             pending_state: None,
             pending_receipts: Vec::new(),
+            pending_snapshot: None,
+            last_block: None,
             history: vec![genesis],
+            heartbeat: None,
         }
     }
 
@@ -91,6 +103,36 @@ impl EvmStateMachine {
         &self.pending_receipts
     }
 
+    /// Get the last executed block, with the state and receipts roots it
+    /// committed to
+    pub fn last_block(&self) -> Option<&EvmBlock> {
+        self.last_block.as_ref()
+    }
+
+    /// State key under which a block's EVM state root and receipts root are
+    /// recorded, so they can be proven against the consensus state root
+    pub fn block_roots_key(height: u64) -> Vec<u8> {
+        format!("block_roots_{}", height).into_bytes()
+    }
+
+    /// Simulate an ordered bundle of transactions against the state at
+    /// `height` (the latest when `None`) without committing anything
+    ///
+    /// Only the latest state is kept for EVM accounts, so other heights are
+    /// not found.
+    pub fn simulate_bundle(
+        &self,
+        transactions: &[Transaction],
+        height: Option<u64>,
+    ) -> Result<BundleSimulation, StateError> {
+        if height.is_some_and(|height| height != self.current_state.height) {
+            return Err(StateError::StateNotFound);
+        }
+        self.executor
+            .simulate_bundle(transactions)
+            .map_err(|e| StateError::InvalidTransition(format!("Bundle simulation failed: {}", e)))
+    }
+
     /// Decode transactions from block data
     fn decode_transactions(&self, block: &Block) -> Result<Vec<Transaction>, StateError> {
         let mut transactions = Vec::new();
@@ -125,38 +167,63 @@ impl EvmStateMachine {
         })
     }
 
-    /// Compute state root from current EVM state
-    fn compute_state_root(&self) -> Result<Hash, StateError> {
-        // For now, use a simple hash of state data
-        // In production, this would be a Merkle Patricia Trie root
-        let hash = self.current_state.compute_hash();
-        Ok(hash)
-    }
-
     /// Convert B256 to consensus Hash
     #[allow(dead_code)]
     fn b256_to_hash(b256: B256) -> Hash {
         Hash::new(b256.0)
     }
 
-    /// Convert consensus Hash to B256
-    #[allow(dead_code)]
-    fn hash_to_b256(hash: Hash) -> B256 {
-        B256::from_slice(hash.as_bytes())
+    /// Report block execution progress to a watchdog
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = Some(heartbeat);
     }
-}
 
-impl StateMachine for EvmStateMachine {
-    fn apply_block(&mut self, block: &Block) -> Result<ConsensusStateTransition, StateError> {
+    /// Execute a block atomically: if it fails mid-way, its partial EVM
+    /// changes are reverted
+    fn execute_block(&mut self, block: &Block) -> Result<ConsensusStateTransition, StateError> {
+        let snapshot = self.executor.snapshot();
+        match self.execute_block_transactions(block) {
+            Ok(transition) => {
+                // Blocks applied again before commit revert to the first
+                self.pending_snapshot.get_or_insert(snapshot);
+                Ok(transition)
+            }
+            Err(e) => {
+                self.executor
+                    .revert_to(snapshot)
+                    .map_err(|revert| StateError::InvalidTransition(format!("{}; revert failed: {}", e, revert)))?;
+                Err(e)
+            }
+        }
+    }
+
+    fn execute_block_transactions(&mut self, block: &Block) -> Result<ConsensusStateTransition, StateError> {
+        // The proposer must charge the base fee the previous blocks set
+        let base_fee = self.executor.base_fee();
+        if U256::from(block.base_fee) != base_fee {
+            return Err(StateError::InvalidTransition(format!(
+                "Block base fee {} instead of {}",
+                block.base_fee, base_fee
+            )));
+        }
+
         // Set block context for EVM execution
         self.executor
             .set_block_context(block.height, std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs());
+        self.executor.set_block_coinbase(Address::from(block.fee_recipient));
+        if let Some(randomness) = block.randomness() {
+            self.executor
+                .set_block_randomness(B256::from(*randomness.as_bytes()));
+        }
 
-        // Decode transactions
+        // Decode transactions, then read the accounts they touch in one batch
         let transactions = self.decode_transactions(block)?;
+        self.executor
+            .prefetch(&transactions)
+            .map_err(|e| StateError::InvalidTransition(format!("State prefetch failed: {}", e)))?;
 
         // Execute all transactions
         let mut receipts = Vec::new();
@@ -172,6 +239,14 @@ impl StateMachine for EvmStateMachine {
             }
         }
 
+        // Next block's base fee follows this one's gas usage
+        self.executor.end_block(receipts.iter().map(|receipt| receipt.gas_used).sum());
+
+        // Blocks are applied once committed, so their height is final
+        self.executor
+            .settle_withdrawals(block.height)
+            .map_err(|e| StateError::InvalidTransition(format!("Withdrawal settlement failed: {}", e)))?;
+
         // Create new state
         let mut new_state = self.current_state.clone();
         new_state.height = block.height;
@@ -184,8 +259,19 @@ impl StateMachine for EvmStateMachine {
             new_state.set(key, value);
         }
 
+        // Commit to the EVM state and receipts, recorded in consensus state
+        let state_root = self
+            .executor
+            .commit_state()
+            .map_err(|e| StateError::InvalidTransition(format!("State commit failed: {}", e)))?;
+        let receipts_root = commitments::receipts_root(&receipts);
+        new_state.set(
+            Self::block_roots_key(block.height),
+            [state_root.as_slice(), receipts_root.as_slice()].concat(),
+        );
+
         // Compute new state root
-        new_state.root_hash = self.compute_state_root()?;
+        new_state.root_hash = new_state.compute_hash();
 
         // Create transition
         let transition = ConsensusStateTransition {
@@ -198,9 +284,33 @@ impl StateMachine for EvmStateMachine {
         // Store as pending
         self.pending_state = Some(new_state);
         self.pending_receipts = receipts;
+        self.last_block = Some(EvmBlock {
+            number: block.height,
+            hash: commitments::hash_to_b256(block.hash()),
+            parent_hash: commitments::hash_to_b256(block.parent),
+            timestamp: block.timestamp,
+            transactions,
+            state_root,
+            receipts_root,
+        });
 
         Ok(transition)
     }
+}
+
+impl StateMachine for EvmStateMachine {
+    fn apply_block(&mut self, block: &Block) -> Result<ConsensusStateTransition, StateError> {
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.busy(format!("applying block {}", block.height));
+        }
+        let transition = self.execute_block(block);
+        // Committed (or dropped) before the next block
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.set_queue_depth("pending_receipts", self.pending_receipts.len());
+            heartbeat.idle();
+        }
+        transition
+    }
 
     fn query(&self, query: &Query) -> Result<QueryResponse, StateError> {
         match query {
@@ -239,9 +349,14 @@ impl StateMachine for EvmStateMachine {
                 let exists = self.current_state.get(key).is_some();
                 Ok(QueryResponse::Exists(exists))
             }
+            Query::GetProof { key } => Ok(self.current_state.proof_response(key)),
         }
     }
 
+    fn next_base_fee(&self) -> u128 {
+        self.executor.base_fee().saturating_to()
+    }
+
     fn commit(&mut self) -> Result<Hash, StateError> {
         if let Some(pending) = self.pending_state.take() {
             let hash = pending.root_hash;
@@ -250,6 +365,9 @@ impl StateMachine for EvmStateMachine {
             self.history.push(pending.clone());
             self.current_state = pending;
             self.pending_receipts.clear();
+            if let Some(snapshot) = self.pending_snapshot.take() {
+                self.executor.discard_snapshot(snapshot);
+            }
             
             // Check if should create checkpoint
             if self.checkpoint_manager.should_checkpoint(height) {
@@ -271,6 +389,13 @@ impl StateMachine for EvmStateMachine {
         if self.pending_state.is_some() {
             self.pending_state = None;
             self.pending_receipts.clear();
+            self.last_block = None;
+            // Undo the pending block's EVM changes
+            if let Some(snapshot) = self.pending_snapshot.take() {
+                self.executor
+                    .revert_to(snapshot)
+                    .map_err(|e| StateError::InvalidTransition(format!("EVM state revert failed: {}", e)))?;
+            }
             Ok(())
         } else {
             Err(StateError::InvalidTransition(
@@ -370,6 +495,115 @@ mod tests {
         assert_eq!(sm.current_state().height, 0);
     }
 
+    #[test]
+    fn test_failed_and_rolled_back_blocks_leave_no_evm_changes() {
+        let (mut sm, _temp) = create_test_state_machine();
+        let sender = Address::repeat_byte(0x01);
+        let receiver = Address::repeat_byte(0x02);
+        sm.executor_mut().create_account(sender, U256::from(10_000_000)).unwrap();
+        let transfer = |nonce| serde_json::to_vec(&Transaction::transfer(sender, receiver, U256::from(1000), nonce)).unwrap();
+
+        // The second transaction's nonce is wrong, after the first executed
+        let block = create_test_block(1, vec![transfer(0), transfer(5)]);
+        assert!(sm.apply_block(&block).is_err());
+        assert_eq!(sm.executor().get_balance(&receiver).unwrap(), U256::ZERO);
+        assert_eq!(sm.executor().get_nonce(&sender).unwrap(), 0);
+
+        let block = create_test_block(1, vec![transfer(0)]);
+        sm.apply_block(&block).unwrap();
+        assert_eq!(sm.executor().get_balance(&receiver).unwrap(), U256::from(1000));
+        sm.rollback().unwrap();
+        assert_eq!(sm.executor().get_balance(&receiver).unwrap(), U256::ZERO);
+
+        sm.apply_block(&block).unwrap();
+        sm.commit().unwrap();
+        assert_eq!(sm.executor().get_balance(&receiver).unwrap(), U256::from(1000));
+    }
+
+    #[test]
+    fn test_failed_block_reverts_bridge_deposit() {
+        use crate::precompiles::collateral::{CollateralBridge, ICollateral};
+        use crate::precompiles::COLLATERAL_PRECOMPILE;
+        use alloy_primitives::Bytes;
+        use alloy_sol_types::SolCall;
+        use openliquid_core::{AssetId, CoreStateMachine};
+        use std::sync::RwLock;
+
+        let (mut sm, _temp) = create_test_state_machine();
+        let user = Address::repeat_byte(0x01);
+        let asset = AssetId(0);
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        sm.executor_mut().create_account(user, U256::from(10_000_000)).unwrap();
+        sm.executor_mut().set_collateral_bridge(CollateralBridge::new(core.clone(), asset));
+
+        // The deposit executes, then the transfer's nonce is wrong
+        let deposit = Transaction::call(
+            user,
+            COLLATERAL_PRECOMPILE,
+            Bytes::from(ICollateral::depositCall { amount: U256::from(600) }.abi_encode()),
+            0,
+        );
+        let transfer = Transaction::transfer(user, Address::repeat_byte(0x02), U256::from(1000), 5);
+        let block = create_test_block(1, vec![
+            serde_json::to_vec(&deposit).unwrap(),
+            serde_json::to_vec(&transfer).unwrap(),
+        ]);
+        assert!(sm.apply_block(&block).is_err());
+
+        assert_eq!(sm.executor().get_balance(&user).unwrap(), U256::from(10_000_000));
+        assert_eq!(core.read().unwrap().get_collateral(&user, asset), U256::ZERO);
+        let bridge = sm.executor().collateral_bridge().unwrap();
+        assert_eq!(bridge.bridged_total(), U256::ZERO);
+        assert!(bridge.ledger().is_empty());
+        sm.executor().check_collateral_invariants().unwrap();
+
+        // The same deposit applies cleanly afterwards
+        let block = create_test_block(1, vec![serde_json::to_vec(&deposit).unwrap()]);
+        sm.apply_block(&block).unwrap();
+        assert_eq!(core.read().unwrap().get_collateral(&user, asset), U256::from(600));
+        sm.executor().check_collateral_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_failed_block_reverts_every_core_backend() {
+        use crate::precompiles::oracle::{IOracle, PriceOracle};
+        use crate::precompiles::vault::{IVault, VaultPrecompile};
+        use crate::precompiles::{ORACLE_PRECOMPILE, VAULT_PRECOMPILE};
+        use alloy_primitives::Bytes;
+        use alloy_sol_types::SolCall;
+        use openliquid_core::{AssetId, CoreStateMachine, Governance};
+        use std::sync::RwLock;
+
+        let (mut sm, _temp) = create_test_state_machine();
+        let user = Address::repeat_byte(0x01);
+        let asset = AssetId(0);
+        let core = Arc::new(RwLock::new(CoreStateMachine::new()));
+        core.write().unwrap().deposit_collateral(user, asset, U256::from(1_000)).unwrap();
+        let vault_id = core.write().unwrap().create_vault(user, user, asset, 0, U256::from(500), 0).unwrap();
+        core.write().unwrap().set_governance(Governance::new([user]));
+        core.write().unwrap().authorize_oracle_reporter(&user, AssetId(1), user).unwrap();
+        sm.executor_mut().create_account(user, U256::from(10_000_000)).unwrap();
+        sm.executor_mut().set_price_oracle(PriceOracle::new(core.clone()));
+        sm.executor_mut().set_vaults(VaultPrecompile::new(core.clone(), asset));
+
+        // The price post and vault deposit execute, then the transfer's
+        // nonce is wrong
+        let post = IOracle::postPriceCall { asset: 1, price: 100_000_000 }.abi_encode();
+        let deposit = IVault::depositCall { vaultId: vault_id, amount: U256::from(200) }.abi_encode();
+        let block = create_test_block(1, vec![
+            serde_json::to_vec(&Transaction::call(user, ORACLE_PRECOMPILE, Bytes::from(post), 0)).unwrap(),
+            serde_json::to_vec(&Transaction::call(user, VAULT_PRECOMPILE, Bytes::from(deposit), 1)).unwrap(),
+            serde_json::to_vec(&Transaction::transfer(user, Address::repeat_byte(0x02), U256::from(1), 7)).unwrap(),
+        ]);
+        assert!(sm.apply_block(&block).is_err());
+
+        let core = core.read().unwrap();
+        assert_eq!(core.get_oracle_price(AssetId(1), 0), None);
+        assert_eq!(core.vaults().shares_of(vault_id, &user), U256::from(500));
+        assert_eq!(core.get_collateral(&user, asset), U256::from(500));
+        assert_eq!(sm.executor().get_nonce(&user).unwrap(), 0);
+    }
+
     #[test]
     fn test_query_state() {
         let (mut sm, _temp) = create_test_state_machine();
@@ -435,6 +669,38 @@ mod tests {
         assert_eq!(sm.history.len(), 4); // genesis + 3 blocks
     }
 
+    #[test]
+    fn test_blocks_carry_base_fee_and_pay_tips_to_fee_recipient() {
+        let (mut sm, _temp) = create_test_state_machine();
+        sm.executor_mut().set_fee_market_config(crate::FeeMarketConfig {
+            initial_base_fee: U256::from(10),
+            ..Default::default()
+        });
+        let sender = Address::repeat_byte(0x01);
+        let proposer = Address::repeat_byte(0xbb);
+        sm.executor_mut()
+            .create_account(sender, U256::from(10_000_000))
+            .unwrap();
+        let tx = Transaction::transfer(sender, Address::repeat_byte(0x02), U256::from(1000), 0)
+            .with_fees(U256::from(50), U256::from(2));
+        let transactions = vec![serde_json::to_vec(&tx).unwrap()];
+
+        // A proposal must charge the base fee the chain is at
+        assert_eq!(sm.next_base_fee(), 10);
+        let wrong = create_test_block(1, transactions.clone()).with_fees(9, proposer.into());
+        assert!(sm.apply_block(&wrong).unwrap_err().to_string().contains("base fee"));
+        assert_eq!(sm.executor().get_nonce(&sender).unwrap(), 0);
+
+        let block = create_test_block(1, transactions).with_fees(10, proposer.into());
+        sm.apply_block(&block).unwrap();
+        sm.commit().unwrap();
+        assert_eq!(sm.executor().get_balance(&proposer).unwrap(), U256::from(21_000 * 2));
+
+        // A mostly empty block lowers the next one's base fee
+        assert!(sm.next_base_fee() < 10);
+        assert_eq!(U256::from(sm.next_base_fee()), sm.executor().base_fee());
+    }
+
     #[test]
     fn test_receipts_stored_in_state() {
         let (mut sm, _temp) = create_test_state_machine();
@@ -466,5 +732,41 @@ mod tests {
             _ => panic!("Expected receipt in state"),
         }
     }
+
+    #[test]
+    fn test_block_commits_to_state_and_receipts_roots() {
+        let (mut sm, _temp) = create_test_state_machine();
+        let sender = Address::repeat_byte(0x01);
+        let receiver = Address::repeat_byte(0x02);
+        sm.executor_mut()
+            .create_account(sender, U256::from(10_000_000))
+            .unwrap();
+        let before = sm.executor().state_root();
+
+        let tx = Transaction::transfer(sender, receiver, U256::from(1000), 0);
+        let block = create_test_block(1, vec![serde_json::to_vec(&tx).unwrap()]);
+        let transition = sm.apply_block(&block).unwrap();
+        let evm_block = sm.last_block().unwrap().clone();
+        assert_eq!(evm_block.number, 1);
+        assert_eq!(evm_block.state_root, sm.executor().state_root());
+        assert_ne!(evm_block.state_root, before);
+        assert_eq!(evm_block.receipts_root, commitments::receipts_root(sm.last_receipts()));
+
+        // Both roots are recorded in, and provable against, consensus state
+        assert_eq!(transition.new_state.root_hash, transition.new_state.compute_hash());
+        sm.commit().unwrap();
+        let key = EvmStateMachine::block_roots_key(1);
+        let QueryResponse::Proof { value, state_root, proof } = sm.query(&Query::GetProof { key: key.clone() }).unwrap()
+        else {
+            panic!("Expected a proof");
+        };
+        let value = value.unwrap();
+        assert_eq!(value, [evm_block.state_root.as_slice(), evm_block.receipts_root.as_slice()].concat());
+        assert!(proof.verify_inclusion(&state_root, &key, &value));
+
+        // The receiver's account is provable against the EVM state root
+        let proof = sm.executor().prove_account(&receiver);
+        assert!(!proof.verify_exclusion(&Hash::new(evm_block.state_root.0), receiver.as_slice()));
+    }
 }
 
@@ -1,6 +1,7 @@
 // EVM Storage Adapter
 //
-// Bridges RocksDB storage to revm's Database trait
+// Bridges RocksDB storage to revm's Database trait, with an LRU cache of
+// accounts and storage slots shared by every clone of the storage
 
 use alloy_primitives::{Address, Bytes, B256, U256};
 use anyhow::{anyhow, Result};
@@ -9,21 +10,25 @@ use revm::{
     Database, DatabaseRef,
 };
 use rocksdb::DB;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use crate::cache::{StateCache, StateCacheConfig, StateCacheStats};
 use crate::types::{Account, KECCAK_EMPTY};
 use crate::precompiles::orderbook::Order;
 use crate::precompiles::perp::Position;
 
 /// Storage keys for EVM data in RocksDB
+const ACCOUNT_PREFIX: &[u8] = b"evm_account_";
+const STORAGE_PREFIX: &[u8] = b"evm_storage_";
+
 fn account_key(address: &Address) -> Vec<u8> {
-    let mut key = b"evm_account_".to_vec();
+    let mut key = ACCOUNT_PREFIX.to_vec();
     key.extend_from_slice(address.as_slice());
     key
 }
 
 fn storage_key(address: &Address, slot: &U256) -> Vec<u8> {
-    let mut key = b"evm_storage_".to_vec();
+    let mut key = STORAGE_PREFIX.to_vec();
     key.extend_from_slice(address.as_slice());
     key.push(b'_');
     let mut slot_bytes = [0u8; 32];
@@ -78,59 +83,204 @@ fn snapshot_key(snapshot_id: u64) -> Vec<u8> {
     key
 }
 
+fn decode_account(bytes: Option<Vec<u8>>) -> Result<Option<Account>> {
+    match bytes {
+        Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+fn decode_slot(bytes: Option<Vec<u8>>) -> U256 {
+    match bytes {
+        Some(bytes) if bytes.len() == 32 => {
+            let mut data = [0u8; 32];
+            data.copy_from_slice(&bytes);
+            U256::from_be_bytes(data)
+        }
+        _ => U256::ZERO,
+    }
+}
+
+/// Value a write replaced, restored when reverting to a snapshot
+enum JournalEntry {
+    Account(Address, Option<Account>),
+    Storage(Address, U256, U256),
+    Code(Address, Option<Bytes>),
+}
+
+/// Writes made since the oldest open snapshot
+#[derive(Default)]
+struct Journal {
+    entries: Vec<JournalEntry>,
+    /// Journal length at each open snapshot, oldest first
+    snapshots: Vec<usize>,
+}
+
 /// EVM Storage backed by RocksDB
 #[derive(Clone)]
 pub struct EvmStorage {
     db: Arc<DB>,
+    /// Accounts and storage slots, shared between clones
+    cache: Arc<Mutex<StateCache>>,
+    /// Account, storage and code writes under open snapshots, shared
+    /// between clones
+    journal: Arc<Mutex<Journal>>,
+}
+
+impl std::fmt::Debug for EvmStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvmStorage").finish_non_exhaustive()
+    }
 }
 
 impl EvmStorage {
-    /// Create a new EVM storage instance
+    /// Create a new EVM storage instance with the default cache sizes
     pub fn new(db: Arc<DB>) -> Self {
-        Self { db }
+        Self::with_cache(db, StateCacheConfig::default())
+    }
+
+    /// Create a new EVM storage instance with custom cache sizes
+    pub fn with_cache(db: Arc<DB>, config: StateCacheConfig) -> Self {
+        Self {
+            db,
+            cache: Arc::new(Mutex::new(StateCache::new(config))),
+            journal: Arc::new(Mutex::new(Journal::default())),
+        }
+    }
+
+    /// Open a snapshot that account, storage and code writes can be
+    /// reverted to; snapshots nest, and the id is the nesting depth
+    pub fn snapshot(&self) -> usize {
+        let mut journal = self.journal.lock().unwrap();
+        let len = journal.entries.len();
+        journal.snapshots.push(len);
+        journal.snapshots.len() - 1
+    }
+
+    /// Undo the writes made since snapshot `id`, in the database and the
+    /// cache, closing it and the snapshots opened after it
+    pub fn revert_to(&self, id: usize) -> Result<()> {
+        let mut journal = self.journal.lock().unwrap();
+        let start = *journal
+            .snapshots
+            .get(id)
+            .ok_or_else(|| anyhow!("Unknown storage snapshot {}", id))?;
+        journal.snapshots.truncate(id);
+        let entries = journal.entries.split_off(start);
+        drop(journal);
+
+        for entry in entries.into_iter().rev() {
+            match entry {
+                JournalEntry::Account(address, account) => self.put_account(&address, account.as_ref())?,
+                JournalEntry::Storage(address, slot, value) => self.put_storage(&address, &slot, &value)?,
+                JournalEntry::Code(address, code) => self.put_code(&address, code.as_ref())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the writes made since snapshot `id`, closing it and the
+    /// snapshots opened after it
+    pub fn discard_snapshot(&self, id: usize) {
+        let mut journal = self.journal.lock().unwrap();
+        journal.snapshots.truncate(id);
+        if journal.snapshots.is_empty() {
+            journal.entries.clear();
+        }
+    }
+
+    /// Record the value a write replaces, if a snapshot is open
+    fn record(&self, entry: impl FnOnce() -> Result<JournalEntry>) -> Result<()> {
+        if self.journal.lock().unwrap().snapshots.is_empty() {
+            return Ok(());
+        }
+        let entry = entry()?;
+        self.journal.lock().unwrap().entries.push(entry);
+        Ok(())
+    }
+
+    /// Cache hit and miss counters
+    pub fn cache_stats(&self) -> StateCacheStats {
+        self.cache.lock().unwrap().stats()
+    }
+
+    /// Load accounts and storage slots into the cache with one batched
+    /// read, ahead of executing a block that touches them
+    pub fn prefetch(&self, accounts: &[Address], slots: &[(Address, U256)]) -> Result<()> {
+        let (accounts, slots): (Vec<_>, Vec<_>) = {
+            let cache = self.cache.lock().unwrap();
+            (
+                accounts.iter().filter(|a| !cache.contains_account(a)).copied().collect(),
+                slots.iter().filter(|(a, s)| !cache.contains_storage(a, s)).copied().collect(),
+            )
+        };
+        if accounts.is_empty() && slots.is_empty() {
+            return Ok(());
+        }
+
+        let keys = accounts
+            .iter()
+            .map(account_key)
+            .chain(slots.iter().map(|(address, slot)| storage_key(address, slot)));
+        let mut values = self.db.multi_get(keys).into_iter();
+
+        let mut cache = self.cache.lock().unwrap();
+        for (address, value) in accounts.into_iter().zip(values.by_ref()) {
+            cache.put_account(address, decode_account(value?)?);
+        }
+        for ((address, slot), value) in slots.into_iter().zip(values) {
+            cache.put_storage(address, slot, decode_slot(value?));
+        }
+        Ok(())
     }
 
     /// Get account information
     pub fn get_account(&self, address: &Address) -> Result<Option<Account>> {
-        let key = account_key(address);
-        match self.db.get(&key)? {
-            Some(bytes) => {
-                let account: Account = bincode::deserialize(&bytes)?;
-                Ok(Some(account))
-            }
-            None => Ok(None),
+        if let Some(account) = self.cache.lock().unwrap().get_account(address) {
+            return Ok(account);
         }
+        let account = decode_account(self.db.get(account_key(address))?)?;
+        self.cache.lock().unwrap().put_account(*address, account.clone());
+        Ok(account)
     }
 
     /// Store account information
     pub fn set_account(&self, address: &Address, account: &Account) -> Result<()> {
+        self.record(|| Ok(JournalEntry::Account(*address, self.get_account(address)?)))?;
+        self.put_account(address, Some(account))
+    }
+
+    fn put_account(&self, address: &Address, account: Option<&Account>) -> Result<()> {
         let key = account_key(address);
-        let bytes = bincode::serialize(account)?;
-        self.db.put(&key, &bytes)?;
+        match account {
+            Some(account) => self.db.put(&key, bincode::serialize(account)?)?,
+            None => self.db.delete(&key)?,
+        }
+        self.cache.lock().unwrap().put_account(*address, account.cloned());
         Ok(())
     }
 
     /// Get storage slot value
     pub fn get_storage(&self, address: &Address, slot: &U256) -> Result<U256> {
-        let key = storage_key(address, slot);
-        match self.db.get(&key)? {
-            Some(bytes) => {
-                if bytes.len() != 32 {
-                    return Ok(U256::ZERO);
-                }
-                let mut data = [0u8; 32];
-                data.copy_from_slice(&bytes);
-                Ok(U256::from_be_bytes(data))
-            }
-            None => Ok(U256::ZERO),
+        if let Some(value) = self.cache.lock().unwrap().get_storage(address, slot) {
+            return Ok(value);
         }
+        let value = decode_slot(self.db.get(storage_key(address, slot))?);
+        self.cache.lock().unwrap().put_storage(*address, *slot, value);
+        Ok(value)
     }
 
     /// Set storage slot value
     pub fn set_storage(&self, address: &Address, slot: &U256, value: &U256) -> Result<()> {
+        self.record(|| Ok(JournalEntry::Storage(*address, *slot, self.get_storage(address, slot)?)))?;
+        self.put_storage(address, slot, value)
+    }
+
+    fn put_storage(&self, address: &Address, slot: &U256, value: &U256) -> Result<()> {
         let key = storage_key(address, slot);
         let value_bytes = value.to_be_bytes::<32>();
         self.db.put(&key, &value_bytes)?;
+        self.cache.lock().unwrap().put_storage(*address, *slot, *value);
         Ok(())
     }
 
@@ -145,8 +295,16 @@ impl EvmStorage {
 
     /// Store contract code
     pub fn set_code(&self, address: &Address, code: &Bytes) -> Result<()> {
+        self.record(|| Ok(JournalEntry::Code(*address, self.get_code(address)?)))?;
+        self.put_code(address, Some(code))
+    }
+
+    fn put_code(&self, address: &Address, code: Option<&Bytes>) -> Result<()> {
         let key = code_key(address);
-        self.db.put(&key, code.as_ref())?;
+        match code {
+            Some(code) => self.db.put(&key, code.as_ref())?,
+            None => self.db.delete(&key)?,
+        }
         Ok(())
     }
 
@@ -173,14 +331,49 @@ impl EvmStorage {
         Ok(())
     }
 
+    /// Every persisted account, e.g. to rebuild the state commitment
+    pub fn accounts(&self) -> Result<Vec<(Address, Account)>> {
+        let mut accounts = Vec::new();
+        for item in self.db.prefix_iterator(ACCOUNT_PREFIX) {
+            let (key, value) = item?;
+            if !key.starts_with(ACCOUNT_PREFIX) {
+                break;
+            }
+            if key.len() == ACCOUNT_PREFIX.len() + 20 {
+                let address = Address::from_slice(&key[ACCOUNT_PREFIX.len()..]);
+                accounts.push((address, bincode::deserialize(&value)?));
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Every persisted non-zero storage slot, as (address, slot, value)
+    pub fn storage_slots(&self) -> Result<Vec<(Address, U256, U256)>> {
+        let mut slots = Vec::new();
+        for item in self.db.prefix_iterator(STORAGE_PREFIX) {
+            let (key, value) = item?;
+            if !key.starts_with(STORAGE_PREFIX) {
+                break;
+            }
+            let rest = &key[STORAGE_PREFIX.len()..];
+            if rest.len() != 20 + 1 + 32 {
+                continue;
+            }
+            let value = decode_slot(Some(value.to_vec()));
+            if !value.is_zero() {
+                slots.push((Address::from_slice(&rest[..20]), U256::from_be_slice(&rest[21..]), value));
+            }
+        }
+        Ok(slots)
+    }
+
     /// Delete account and all associated data
     pub fn delete_account(&self, address: &Address) -> Result<()> {
-        let account_k = account_key(address);
-        let code_k = code_key(address);
-        
-        self.db.delete(&account_k)?;
-        self.db.delete(&code_k)?;
-        
+        self.record(|| Ok(JournalEntry::Account(*address, self.get_account(address)?)))?;
+        self.record(|| Ok(JournalEntry::Code(*address, self.get_code(address)?)))?;
+        self.put_account(address, None)?;
+        self.put_code(address, None)?;
+
         // Note: Storage slots are not deleted here for efficiency
         // They would need to be tracked separately for full cleanup
         
@@ -367,11 +560,18 @@ impl Database for EvmStorage {
                     account.code_hash
                 };
 
+                // Code is stored by address, not by hash
+                let code = if code_hash == revm::primitives::KECCAK_EMPTY {
+                    None
+                } else {
+                    self.get_code(&address)?.map(Bytecode::new_raw)
+                };
+
                 Ok(Some(AccountInfo {
                     balance: account.balance,
                     nonce: account.nonce,
                     code_hash,
-                    code: None, // Code will be loaded separately if needed
+                    code,
                 }))
             }
             None => Ok(None),
@@ -462,6 +662,71 @@ mod tests {
         assert_eq!(retrieved.unwrap().balance, U256::from(1000));
     }
 
+    #[test]
+    fn test_state_cache_serves_repeat_and_prefetched_reads() {
+        let (storage, _temp) = create_test_storage();
+        let (alice, bob) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02));
+        storage.set_account(&alice, &Account::with_balance(U256::from(7))).unwrap();
+        storage.set_storage(&alice, &U256::from(1), &U256::from(9)).unwrap();
+
+        // Writes go through the cache, so a fresh instance over the same
+        // database starts cold
+        let cold = EvmStorage::new(storage.db.clone());
+        cold.prefetch(&[alice, bob], &[(alice, U256::from(1))]).unwrap();
+        assert_eq!(cold.get_account(&alice).unwrap().unwrap().balance, U256::from(7));
+        assert_eq!(cold.get_account(&bob).unwrap(), None);
+        assert_eq!(cold.get_storage(&alice, &U256::from(1)).unwrap(), U256::from(9));
+        assert_eq!(cold.get_storage(&alice, &U256::from(2)).unwrap(), U256::ZERO);
+        let stats = cold.cache_stats();
+        assert_eq!((stats.account_hits, stats.account_misses), (2, 0));
+        assert_eq!((stats.storage_hits, stats.storage_misses), (1, 1));
+
+        // Clones share the cache and see each other's writes
+        let clone = cold.clone();
+        clone.set_storage(&alice, &U256::from(1), &U256::from(10)).unwrap();
+        assert_eq!(cold.get_storage(&alice, &U256::from(1)).unwrap(), U256::from(10));
+
+        // A disabled cache always reads through
+        let uncached = EvmStorage::with_cache(storage.db.clone(), StateCacheConfig { account_capacity: 0, storage_capacity: 0 });
+        uncached.get_account(&alice).unwrap();
+        uncached.get_account(&alice).unwrap();
+        assert_eq!(uncached.cache_stats().account_misses, 2);
+    }
+
+    #[test]
+    fn test_snapshots_revert_writes_in_database_and_cache() {
+        let (storage, _temp) = create_test_storage();
+        let (alice, bob) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02));
+        storage.set_account(&alice, &Account::with_balance(U256::from(7))).unwrap();
+        storage.set_storage(&alice, &U256::from(1), &U256::from(9)).unwrap();
+
+        let outer = storage.snapshot();
+        storage.set_account(&alice, &Account::with_balance(U256::from(8))).unwrap();
+        storage.set_code(&bob, &Bytes::from(vec![0x60, 0x80])).unwrap();
+        let inner = storage.clone().snapshot();
+        storage.set_storage(&alice, &U256::from(1), &U256::from(10)).unwrap();
+        storage.delete_account(&alice).unwrap();
+
+        storage.revert_to(inner).unwrap();
+        assert_eq!(storage.get_account(&alice).unwrap().unwrap().balance, U256::from(8));
+        assert_eq!(storage.get_storage(&alice, &U256::from(1)).unwrap(), U256::from(9));
+        assert!(storage.revert_to(inner).is_err());
+
+        storage.revert_to(outer).unwrap();
+        assert_eq!(storage.get_account(&alice).unwrap().unwrap().balance, U256::from(7));
+        assert_eq!(storage.get_code(&bob).unwrap(), None);
+        // The database was restored too, not only the cache
+        let cold = EvmStorage::new(storage.db.clone());
+        assert_eq!(cold.get_account(&alice).unwrap().unwrap().balance, U256::from(7));
+
+        // Discarded snapshots keep their writes
+        let kept = storage.snapshot();
+        storage.set_storage(&alice, &U256::from(1), &U256::from(11)).unwrap();
+        storage.discard_snapshot(kept);
+        assert!(storage.revert_to(kept).is_err());
+        assert_eq!(storage.get_storage(&alice, &U256::from(1)).unwrap(), U256::from(11));
+    }
+
     #[test]
     fn test_storage_slot() {
         let (storage, _temp) = create_test_storage();
@@ -537,6 +802,23 @@ mod tests {
         assert_eq!(retrieved, value);
     }
 
+    #[test]
+    fn test_list_accounts_and_slots() {
+        let (storage, _temp) = create_test_storage();
+        let address = Address::repeat_byte(0x01);
+        storage.set_account(&address, &Account::with_balance(U256::from(7))).unwrap();
+        storage.set_storage(&address, &U256::from(1), &U256::from(2)).unwrap();
+        storage.set_storage(&address, &U256::from(3), &U256::ZERO).unwrap();
+        storage.set_block_hash(1, &B256::repeat_byte(0xaa)).unwrap();
+
+        let accounts = storage.accounts().unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].0, address);
+        assert_eq!(accounts[0].1.balance, U256::from(7));
+        // Zero slots are left out
+        assert_eq!(storage.storage_slots().unwrap(), vec![(address, U256::from(1), U256::from(2))]);
+    }
+
     #[test]
     fn test_nonexistent_account() {
         let (mut storage, _temp) = create_test_storage();
@@ -0,0 +1,220 @@
+// Transaction tracing
+//
+// A revm inspector recording what a transaction did, for debugging contract
+// and precompile interactions: the tree of calls and creations it made, in
+// the shape of geth's `callTracer`, and optionally every opcode it executed,
+// in the shape of geth's struct logger. Served by `debug_traceTransaction`
+// and `debug_traceCall`.
+
+use alloy_primitives::{Address, Bytes, U256, U64};
+use revm::interpreter::{
+    CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, CreateScheme, InstructionResult,
+    Interpreter, OpCode,
+};
+use revm::{Database, EvmContext, Inspector};
+use serde::{Deserialize, Serialize};
+
+/// What to record besides the call tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceConfig {
+    /// Record every executed opcode
+    #[serde(default)]
+    pub opcodes: bool,
+    /// Include the stack in opcode records
+    #[serde(default)]
+    pub stack: bool,
+}
+
+/// A call or creation and the calls it made
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    /// `CALL`, `STATICCALL`, `DELEGATECALL`, `CALLCODE`, `CREATE` or `CREATE2`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub from: Address,
+    /// Callee, or the created contract
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+    pub value: U256,
+    pub gas: U64,
+    pub gas_used: U64,
+    pub input: Bytes,
+    pub output: Bytes,
+    /// Why the frame failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+}
+
+/// One executed opcode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    pub pc: u64,
+    pub op: String,
+    /// Gas remaining before the opcode
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// Call depth, starting at 1
+    pub depth: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<Vec<U256>>,
+}
+
+/// Everything recorded about one transaction
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionTrace {
+    pub gas: u64,
+    pub failed: bool,
+    pub return_value: Bytes,
+    /// Top-level call or creation
+    pub call: CallFrame,
+    /// Executed opcodes, if requested
+    #[serde(default)]
+    pub struct_logs: Vec<StructLog>,
+}
+
+/// Inspector building a `TransactionTrace`
+#[derive(Debug, Default)]
+pub struct Tracer {
+    config: TraceConfig,
+    /// Frames entered but not yet returned from, outermost first
+    stack: Vec<CallFrame>,
+    /// The outermost frame, once it returned
+    root: Option<CallFrame>,
+    struct_logs: Vec<StructLog>,
+    /// Gas remaining before the opcode being executed
+    step_gas: u64,
+}
+
+impl Tracer {
+    pub fn new(config: TraceConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// The trace of a transaction given `gas_limit` that used `gas_used`;
+    /// the top-level frame reports those rather than its gas net of the
+    /// intrinsic cost
+    pub fn into_trace(self, gas_limit: u64, gas_used: u64) -> TransactionTrace {
+        let mut call = self.root.unwrap_or_default();
+        call.gas = U64::from(gas_limit);
+        call.gas_used = U64::from(gas_used);
+        TransactionTrace {
+            gas: gas_used,
+            failed: call.error.is_some(),
+            return_value: call.output.clone(),
+            call,
+            struct_logs: self.struct_logs,
+        }
+    }
+
+    fn enter(&mut self, frame: CallFrame) {
+        self.stack.push(frame);
+    }
+
+    fn exit(&mut self, result: InstructionResult, output: &Bytes, gas_used: u64, created: Option<Address>) {
+        let Some(mut frame) = self.stack.pop() else { return };
+        frame.output = output.clone();
+        frame.gas_used = U64::from(gas_used);
+        if created.is_some() {
+            frame.to = created;
+        }
+        if !result.is_ok() {
+            frame.error = Some(if result.is_revert() {
+                "execution reverted".to_string()
+            } else {
+                format!("{:?}", result)
+            });
+        }
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+fn call_kind(scheme: CallScheme) -> &'static str {
+    match scheme {
+        CallScheme::Call | CallScheme::ExtCall => "CALL",
+        CallScheme::CallCode => "CALLCODE",
+        CallScheme::DelegateCall | CallScheme::ExtDelegateCall => "DELEGATECALL",
+        CallScheme::StaticCall | CallScheme::ExtStaticCall => "STATICCALL",
+    }
+}
+
+impl<DB: Database> Inspector<DB> for Tracer {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if !self.config.opcodes {
+            return;
+        }
+        self.step_gas = interp.gas.remaining();
+        let opcode = interp.current_opcode();
+        self.struct_logs.push(StructLog {
+            pc: interp.program_counter() as u64,
+            op: OpCode::name_by_op(opcode).to_string(),
+            gas: self.step_gas,
+            gas_cost: 0,
+            depth: context.journaled_state.depth(),
+            stack: self.config.stack.then(|| interp.stack.data().clone()),
+        });
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        if let Some(log) = self.struct_logs.last_mut().filter(|_| self.config.opcodes) {
+            log.gas_cost = self.step_gas.saturating_sub(interp.gas.remaining());
+        }
+    }
+
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.enter(CallFrame {
+            kind: call_kind(inputs.scheme).to_string(),
+            from: inputs.caller,
+            to: Some(inputs.target_address),
+            value: inputs.value.get(),
+            gas: U64::from(inputs.gas_limit),
+            input: inputs.input.clone(),
+            ..Default::default()
+        });
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        let result = &outcome.result;
+        self.exit(result.result, &result.output, result.gas.spent(), None);
+        outcome
+    }
+
+    fn create(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let kind = match inputs.scheme {
+            CreateScheme::Create => "CREATE",
+            CreateScheme::Create2 { .. } => "CREATE2",
+        };
+        self.enter(CallFrame {
+            kind: kind.to_string(),
+            from: inputs.caller,
+            value: inputs.value,
+            gas: U64::from(inputs.gas_limit),
+            input: inputs.init_code.clone(),
+            ..Default::default()
+        });
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &CreateInputs,
+        outcome: CreateOutcome,
+    ) -> CreateOutcome {
+        let result = &outcome.result;
+        self.exit(result.result, &result.output, result.gas.spent(), outcome.address);
+        outcome
+    }
+}
@@ -2,8 +2,15 @@
 // 
 // Defines core types for EVM transaction execution
 
-use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_primitives::{keccak256, Address, Bytes, PrimitiveSignature, B256, U256};
+use alloy_rlp::{Decodable, Encodable, Header};
+use anyhow::{anyhow, Result};
+use k256::ecdsa::SigningKey;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// EIP-2718 type byte of fee market (EIP-1559) transactions
+pub const EIP1559_TX_TYPE: u8 = 0x02;
 
 /// EVM Transaction
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -18,12 +25,24 @@ pub struct Transaction {
     pub data: Bytes,
     /// Gas limit
     pub gas_limit: u64,
-    /// Gas price
+    /// Gas price; the max fee per gas of fee market transactions
     pub gas_price: U256,
     /// Transaction nonce
     pub nonce: u64,
     /// Chain ID for replay protection
     pub chain_id: u64,
+    /// Most the sender pays per gas, base fee included (fee market
+    /// transactions only)
+    #[serde(default)]
+    pub max_fee_per_gas: Option<U256>,
+    /// Most the sender tips the block producer per gas (fee market
+    /// transactions only)
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<U256>,
+    /// Sender's secp256k1 signature over `signature_hash`; unsigned
+    /// transactions are trusted to come from `from`
+    #[serde(default)]
+    pub signature: Option<PrimitiveSignature>,
 }
 
 impl Transaction {
@@ -38,6 +57,9 @@ impl Transaction {
             gas_price: U256::from(1u64), // 1 wei for testing
             nonce,
             chain_id: 1,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            signature: None,
         }
     }
 
@@ -52,6 +74,9 @@ impl Transaction {
             gas_price: U256::from(1u64), // 1 wei for testing
             nonce,
             chain_id: 1,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            signature: None,
         }
     }
 
@@ -66,8 +91,251 @@ impl Transaction {
             gas_price: U256::from(1u64), // 1 wei for testing
             nonce,
             chain_id: 1,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            signature: None,
+        }
+    }
+
+    /// Turn into a fee market transaction paying at most `max_fee` per gas,
+    /// of which at most `max_priority_fee` is tip
+    pub fn with_fees(mut self, max_fee: U256, max_priority_fee: U256) -> Self {
+        self.gas_price = max_fee;
+        self.max_fee_per_gas = Some(max_fee);
+        self.max_priority_fee_per_gas = Some(max_priority_fee.min(max_fee));
+        self
+    }
+
+    /// Most the sender pays per gas
+    pub fn max_fee(&self) -> U256 {
+        self.max_fee_per_gas.unwrap_or(self.gas_price)
+    }
+
+    /// Price per gas paid under `base_fee`, or None if the max fee does not
+    /// cover it. Legacy transactions pay their whole gas price.
+    pub fn effective_gas_price(&self, base_fee: U256) -> Option<U256> {
+        let max_fee = self.max_fee();
+        if max_fee < base_fee {
+            return None;
+        }
+        Some(match self.max_priority_fee_per_gas {
+            Some(tip) => max_fee.min(base_fee + tip),
+            None => max_fee,
+        })
+    }
+
+    /// Tip per gas the block producer gets under `base_fee`
+    pub fn effective_tip(&self, base_fee: U256) -> Option<U256> {
+        self.effective_gas_price(base_fee).map(|price| price - base_fee)
+    }
+
+    /// Hash identifying the transaction: that of its raw encoding when
+    /// signed, as Ethereum tooling computes it, else a hash of its sender,
+    /// recipient, nonce, value and data
+    pub fn hash(&self) -> B256 {
+        if let Ok(raw) = self.encode_raw() {
+            return keccak256(raw);
+        }
+        let mut data = Vec::new();
+        data.extend_from_slice(self.from.as_slice());
+        if let Some(to) = self.to {
+            data.extend_from_slice(to.as_slice());
+        }
+        data.extend_from_slice(&self.nonce.to_le_bytes());
+        data.extend_from_slice(&self.value.to_be_bytes::<32>());
+        data.extend_from_slice(&self.data);
+        keccak256(&data)
+    }
+
+    /// Hash the sender signs: the EIP-1559 payload for fee market
+    /// transactions, the EIP-155 one (committing to the chain ID) otherwise
+    pub fn signature_hash(&self) -> B256 {
+        let mut out = Vec::new();
+        match (self.max_fee_per_gas, self.max_priority_fee_per_gas) {
+            (Some(max_fee), Some(max_priority_fee)) => {
+                out.push(EIP1559_TX_TYPE);
+                self.encode_eip1559_fields(max_fee, max_priority_fee, None, &mut out);
+            }
+            _ => self.encode_legacy_fields(None, &mut out),
+        }
+        keccak256(out)
+    }
+
+    /// Sign with `key`, setting the sender to the key's address
+    pub fn sign(mut self, key: &SigningKey) -> Result<Self> {
+        self.from = Address::from_private_key(key);
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(self.signature_hash().as_slice())
+            .map_err(|e| anyhow!("Signing failed: {}", e))?;
+        self.signature = Some(PrimitiveSignature::from_signature_and_parity(signature, recovery_id.is_y_odd()));
+        Ok(self)
+    }
+
+    /// Address that signed the transaction
+    pub fn recover_sender(&self) -> Result<Address> {
+        let signature = self.signature.ok_or_else(|| anyhow!("Transaction is not signed"))?;
+        // High-s signatures are malleable copies of low-s ones
+        if signature.normalize_s().is_some() {
+            return Err(anyhow!("Signature s value is not normalized"));
+        }
+        signature
+            .recover_address_from_prehash(&self.signature_hash())
+            .map_err(|e| anyhow!("Signature recovery failed: {}", e))
+    }
+
+    /// Check that a signed transaction was signed by `from`
+    pub fn verify_sender(&self) -> Result<()> {
+        let signer = self.recover_sender()?;
+        if signer != self.from {
+            return Err(anyhow!("Signed by {} but sent from {}", signer, self.from));
+        }
+        Ok(())
+    }
+
+    /// Encode a signed transaction as raw EIP-2718 bytes: a legacy EIP-155
+    /// RLP list, or the fee market type byte followed by its RLP list
+    pub fn encode_raw(&self) -> Result<Bytes> {
+        let signature = self.signature.ok_or_else(|| anyhow!("Transaction is not signed"))?;
+        let mut out = Vec::new();
+        match (self.max_fee_per_gas, self.max_priority_fee_per_gas) {
+            (Some(max_fee), Some(max_priority_fee)) => {
+                out.push(EIP1559_TX_TYPE);
+                self.encode_eip1559_fields(max_fee, max_priority_fee, Some(&signature), &mut out);
+            }
+            _ => self.encode_legacy_fields(Some(&signature), &mut out),
+        }
+        Ok(out.into())
+    }
+
+    /// Decode raw transaction bytes and recover the sender from the
+    /// signature. Legacy transactions must be EIP-155 protected; typed ones
+    /// other than EIP-1559 are not supported.
+    pub fn decode_raw(raw: &[u8]) -> Result<Self> {
+        let rlp_err = |e: alloy_rlp::Error| anyhow!("Invalid transaction RLP: {}", e);
+        let (mut buf, typed) = match raw.first() {
+            Some(&EIP1559_TX_TYPE) => (&raw[1..], true),
+            Some(byte) if *byte >= 0xc0 => (raw, false),
+            Some(byte) => return Err(anyhow!("Unsupported transaction type {:#04x}", byte)),
+            None => return Err(anyhow!("Empty transaction")),
+        };
+        let header = Header::decode(&mut buf).map_err(rlp_err)?;
+        if !header.list || header.payload_length != buf.len() {
+            return Err(anyhow!("Invalid transaction RLP: not a single list"));
+        }
+
+        let mut tx = Self::transfer(Address::ZERO, Address::ZERO, U256::ZERO, 0);
+        if typed {
+            tx.chain_id = u64::decode(&mut buf).map_err(rlp_err)?;
+            tx.nonce = u64::decode(&mut buf).map_err(rlp_err)?;
+            let max_priority_fee = U256::decode(&mut buf).map_err(rlp_err)?;
+            let max_fee = U256::decode(&mut buf).map_err(rlp_err)?;
+            tx = tx.with_fees(max_fee, max_priority_fee);
+            tx.max_priority_fee_per_gas = Some(max_priority_fee);
+            decode_call_fields(&mut tx, &mut buf).map_err(rlp_err)?;
+            let access_list = Header::decode(&mut buf).map_err(rlp_err)?;
+            if !access_list.list || access_list.payload_length != 0 {
+                return Err(anyhow!("Access lists are not supported"));
+            }
+            let y_parity = bool::decode(&mut buf).map_err(rlp_err)?;
+            let r = U256::decode(&mut buf).map_err(rlp_err)?;
+            let s = U256::decode(&mut buf).map_err(rlp_err)?;
+            tx.signature = Some(PrimitiveSignature::new(r, s, y_parity));
+        } else {
+            tx.nonce = u64::decode(&mut buf).map_err(rlp_err)?;
+            tx.gas_price = U256::decode(&mut buf).map_err(rlp_err)?;
+            decode_call_fields(&mut tx, &mut buf).map_err(rlp_err)?;
+            let v = u64::decode(&mut buf).map_err(rlp_err)?;
+            let r = U256::decode(&mut buf).map_err(rlp_err)?;
+            let s = U256::decode(&mut buf).map_err(rlp_err)?;
+            if v < 35 {
+                return Err(anyhow!("Transaction has no EIP-155 chain ID"));
+            }
+            tx.chain_id = (v - 35) / 2;
+            tx.signature = Some(PrimitiveSignature::new(r, s, (v - 35) % 2 == 1));
+        }
+        if !buf.is_empty() {
+            return Err(anyhow!("Invalid transaction RLP: trailing fields"));
+        }
+
+        tx.from = tx.recover_sender()?;
+        Ok(tx)
+    }
+
+    /// Legacy RLP list; with `signature` the EIP-155 v, r, s, without it the
+    /// chain ID and two zeros the signature commits to
+    fn encode_legacy_fields(&self, signature: Option<&PrimitiveSignature>, out: &mut Vec<u8>) {
+        let mut payload = Vec::new();
+        self.nonce.encode(&mut payload);
+        self.gas_price.encode(&mut payload);
+        self.encode_call_fields(&mut payload);
+        match signature {
+            Some(signature) => {
+                (self.chain_id * 2 + 35 + signature.v() as u64).encode(&mut payload);
+                signature.r().encode(&mut payload);
+                signature.s().encode(&mut payload);
+            }
+            None => {
+                self.chain_id.encode(&mut payload);
+                0u8.encode(&mut payload);
+                0u8.encode(&mut payload);
+            }
+        }
+        encode_list(&payload, out);
+    }
+
+    /// EIP-1559 RLP list with an empty access list, signed fields included
+    /// only with `signature`
+    fn encode_eip1559_fields(
+        &self,
+        max_fee: U256,
+        max_priority_fee: U256,
+        signature: Option<&PrimitiveSignature>,
+        out: &mut Vec<u8>,
+    ) {
+        let mut payload = Vec::new();
+        self.chain_id.encode(&mut payload);
+        self.nonce.encode(&mut payload);
+        max_priority_fee.encode(&mut payload);
+        max_fee.encode(&mut payload);
+        self.encode_call_fields(&mut payload);
+        encode_list(&[], &mut payload);
+        if let Some(signature) = signature {
+            signature.v().encode(&mut payload);
+            signature.r().encode(&mut payload);
+            signature.s().encode(&mut payload);
         }
+        encode_list(&payload, out);
     }
+
+    /// Gas limit, recipient, value and data, common to all transaction types
+    fn encode_call_fields(&self, out: &mut Vec<u8>) {
+        self.gas_limit.encode(out);
+        match &self.to {
+            Some(to) => to.encode(out),
+            // Contract creation has an empty recipient
+            None => Bytes::new().encode(out),
+        }
+        self.value.encode(out);
+        self.data.encode(out);
+    }
+}
+
+/// Wrap an encoded payload in an RLP list header
+fn encode_list(payload: &[u8], out: &mut Vec<u8>) {
+    Header { list: true, payload_length: payload.len() }.encode(out);
+    out.extend_from_slice(payload);
+}
+
+fn decode_call_fields(tx: &mut Transaction, buf: &mut &[u8]) -> alloy_rlp::Result<()> {
+    tx.gas_limit = u64::decode(buf)?;
+    tx.to = match Bytes::decode(buf)? {
+        to if to.is_empty() => None,
+        to if to.len() == 20 => Some(Address::from_slice(&to)),
+        _ => return Err(alloy_rlp::Error::UnexpectedLength),
+    };
+    tx.value = U256::decode(buf)?;
+    tx.data = Bytes::decode(buf)?;
+    Ok(())
 }
 
 /// Account information
@@ -145,11 +413,54 @@ pub struct Log {
     pub data: Bytes,
 }
 
+/// Changes a transaction made to one account, as (before, after) pairs
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub balance: Option<(U256, U256)>,
+    pub nonce: Option<(u64, u64)>,
+    /// Code deployed to the account
+    pub code: Option<Bytes>,
+    /// Changed storage slots
+    pub storage: BTreeMap<U256, (U256, U256)>,
+}
+
+impl AccountDiff {
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none() && self.nonce.is_none() && self.code.is_none() && self.storage.is_empty()
+    }
+}
+
+/// Changed accounts by address
+pub type StateDiff = BTreeMap<Address, AccountDiff>;
+
+/// Outcome of one transaction in a simulated bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedTransaction {
+    pub receipt: Receipt,
+    /// Why the transaction could not be executed, if it could not
+    pub error: Option<String>,
+    pub state_diff: StateDiff,
+}
+
+/// Outcome of simulating an ordered bundle of transactions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSimulation {
+    /// Block whose state and context the bundle ran against
+    pub block_number: u64,
+    /// Per-transaction outcomes, in bundle order
+    pub results: Vec<SimulatedTransaction>,
+    /// Total gas used by the bundle
+    pub gas_used: u64,
+}
+
 /// State transition result
 #[derive(Debug, Clone)]
 pub struct StateTransition {
     /// New state root after applying block
     pub state_root: B256,
+    /// Root of the block's receipts
+    pub receipts_root: B256,
     /// Transaction receipts
     pub receipts: Vec<Receipt>,
     /// Total gas used in block
@@ -169,6 +480,12 @@ pub struct Block {
     pub timestamp: u64,
     /// Transactions in this block
     pub transactions: Vec<Transaction>,
+    /// EVM state root after executing the block
+    #[serde(default)]
+    pub state_root: B256,
+    /// Root of the block's receipts
+    #[serde(default)]
+    pub receipts_root: B256,
 }
 
 /// State snapshot for checkpointing
@@ -231,6 +548,18 @@ pub const KECCAK_EMPTY: B256 = B256::new([
     0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
 ]);
 
+/// Deterministic signing key for tests
+#[cfg(test)]
+pub(crate) fn test_key(byte: u8) -> SigningKey {
+    SigningKey::from_slice(&[byte; 32]).expect("non-zero test key")
+}
+
+/// Address of `test_key(byte)`
+#[cfg(test)]
+pub(crate) fn test_sender(byte: u8) -> Address {
+    Address::from_private_key(&test_key(byte))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,6 +590,57 @@ mod tests {
         assert_eq!(tx.data, bytecode);
     }
 
+    #[test]
+    fn test_raw_transaction_roundtrip_recovers_sender() {
+        let key = SigningKey::from_slice(&[0x22; 32]).unwrap();
+        let to = Address::repeat_byte(0x02);
+
+        let legacy = Transaction::transfer(Address::ZERO, to, U256::from(1000), 3).sign(&key).unwrap();
+        let fee_market = Transaction::deploy(Address::ZERO, Bytes::from(vec![0x60, 0x00]), 4)
+            .with_fees(U256::from(100), U256::from(2))
+            .sign(&key)
+            .unwrap();
+        for tx in [legacy, fee_market] {
+            let raw = tx.encode_raw().unwrap();
+            let decoded = Transaction::decode_raw(&raw).unwrap();
+            assert_eq!(decoded, tx);
+            assert_eq!(decoded.from, Address::from_private_key(&key));
+        }
+
+        // A changed field recovers a different sender
+        let mut tampered = Transaction::transfer(Address::ZERO, to, U256::from(1000), 0).sign(&key).unwrap();
+        tampered.value = U256::from(2000);
+        assert!(tampered.verify_sender().is_err());
+        let decoded = Transaction::decode_raw(&tampered.encode_raw().unwrap()).unwrap();
+        assert_ne!(decoded.from, tampered.from);
+    }
+
+    #[test]
+    fn test_decode_raw_rejects_unprotected_and_malformed() {
+        let key = SigningKey::from_slice(&[0x22; 32]).unwrap();
+        let tx = Transaction::transfer(Address::ZERO, Address::repeat_byte(0x02), U256::from(1), 0)
+            .sign(&key)
+            .unwrap();
+        let signature = tx.signature.unwrap();
+
+        // Pre-EIP-155 v of 27 carries no chain ID
+        let mut payload = Vec::new();
+        tx.nonce.encode(&mut payload);
+        tx.gas_price.encode(&mut payload);
+        tx.encode_call_fields(&mut payload);
+        (27 + signature.v() as u64).encode(&mut payload);
+        signature.r().encode(&mut payload);
+        signature.s().encode(&mut payload);
+        let mut raw = Vec::new();
+        encode_list(&payload, &mut raw);
+        assert!(Transaction::decode_raw(&raw).unwrap_err().to_string().contains("EIP-155"));
+
+        let raw = tx.encode_raw().unwrap();
+        assert!(Transaction::decode_raw(&raw[..raw.len() - 1]).is_err());
+        assert!(Transaction::decode_raw(&[0x01, 0xc0]).is_err());
+        assert!(Transaction::decode_raw(&[]).is_err());
+    }
+
     #[test]
     fn test_account_default() {
         let account = Account::default();
@@ -45,7 +45,7 @@ fn test_full_block_execution_flow() {
 
     // Create transactions
     let tx1 = Transaction::transfer(sender, receiver, U256::from(1000), 0);
-    let tx2 = Transaction::transfer(sender, receiver, U256::from(2000), 0);
+    let tx2 = Transaction::transfer(sender, receiver, U256::from(2000), 1);
 
     let tx1_bytes = serde_json::to_vec(&tx1).unwrap();
     let tx2_bytes = serde_json::to_vec(&tx2).unwrap();
@@ -77,7 +77,7 @@ fn test_state_persistence_across_blocks() {
     // Apply multiple blocks
     for i in 1..=5 {
         let receiver = Address::repeat_byte((i + 1) as u8);
-        let tx = Transaction::transfer(sender, receiver, U256::from(1000), 0);
+        let tx = Transaction::transfer(sender, receiver, U256::from(1000), i - 1);
         let tx_bytes = serde_json::to_vec(&tx).unwrap();
 
         let block = create_test_block(i, vec![tx_bytes]);
@@ -0,0 +1,157 @@
+// Key-value storage backends
+//
+// Consensus and core storage are written against `StorageBackend`, an
+// ordered key-value store with named column families and atomic batches,
+// rather than against RocksDB directly. RocksDB backs production nodes;
+// the in-memory backend lets tests run without temporary directories, and
+// embedded deployments can pick the lighter sled backend (`sled` feature).
+
+pub mod memory;
+pub mod rocks;
+#[cfg(feature = "sled")]
+pub mod sled;
+
+pub use memory::MemoryBackend;
+pub use rocks::RocksDbBackend;
+#[cfg(feature = "sled")]
+pub use self::sled::SledBackend;
+
+use thiserror::Error;
+
+/// Column family of keys written without one
+pub const DEFAULT_CF: &str = "default";
+
+/// Backend errors
+#[derive(Error, Debug)]
+pub enum BackendError {
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Column family not found: {0}")]
+    UnknownColumnFamily(String),
+}
+
+pub type Result<T> = std::result::Result<T, BackendError>;
+
+/// Key-value pair yielded by iteration
+pub type KeyValue = (Vec<u8>, Vec<u8>);
+
+/// Iterator over a column family, in key order
+pub type KvIterator<'a> = Box<dyn Iterator<Item = Result<KeyValue>> + 'a>;
+
+/// Iteration direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+/// Single write in a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    Put { cf: String, key: Vec<u8>, value: Vec<u8> },
+    Delete { cf: String, key: Vec<u8> },
+}
+
+/// Writes applied atomically by `StorageBackend::write`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, cf: &str, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) {
+        self.ops.push(BatchOp::Put {
+            cf: cf.to_string(),
+            key: key.as_ref().to_vec(),
+            value: value.as_ref().to_vec(),
+        });
+    }
+
+    pub fn delete(&mut self, cf: &str, key: impl AsRef<[u8]>) {
+        self.ops.push(BatchOp::Delete {
+            cf: cf.to_string(),
+            key: key.as_ref().to_vec(),
+        });
+    }
+
+    /// Writes in the order they were added
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+
+    pub fn into_ops(self) -> Vec<BatchOp> {
+        self.ops
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// Ordered key-value store with column families
+pub trait StorageBackend: Send + Sync {
+    /// Value of `key` in `cf`
+    fn get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Apply every write in `batch` atomically; with `sync`, durably
+    /// before returning
+    fn write(&self, batch: WriteBatch, sync: bool) -> Result<()>;
+
+    /// Iterate `cf` in `direction`, starting at `from` (inclusive) or at
+    /// the first key in that direction
+    fn iter<'a>(&'a self, cf: &str, from: Option<&[u8]>, direction: Direction) -> Result<KvIterator<'a>>;
+
+    fn put(&self, cf: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut batch = WriteBatch::new();
+        batch.put(cf, key, value);
+        self.write(batch, false)
+    }
+
+    fn delete(&self, cf: &str, key: &[u8]) -> Result<()> {
+        let mut batch = WriteBatch::new();
+        batch.delete(cf, key);
+        self.write(batch, false)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Behaviour every backend must share
+    pub(crate) fn check_backend(backend: &dyn StorageBackend) {
+        let cf = "blocks";
+        for key in [3u8, 1, 2] {
+            backend.put(cf, &[key], &[key * 10]).unwrap();
+        }
+        assert_eq!(backend.get(cf, &[2]).unwrap(), Some(vec![20]));
+        assert_eq!(backend.get("states", &[2]).unwrap(), None);
+
+        let keys = |from: Option<&[u8]>, direction| -> Vec<Vec<u8>> {
+            backend.iter(cf, from, direction).unwrap().map(|kv| kv.unwrap().0).collect()
+        };
+        assert_eq!(keys(None, Direction::Forward), vec![vec![1], vec![2], vec![3]]);
+        assert_eq!(keys(Some(&[2]), Direction::Forward), vec![vec![2], vec![3]]);
+        assert_eq!(keys(None, Direction::Reverse), vec![vec![3], vec![2], vec![1]]);
+        assert_eq!(keys(Some(&[2]), Direction::Reverse), vec![vec![2], vec![1]]);
+
+        // Batches apply in order across column families
+        let mut batch = WriteBatch::new();
+        batch.delete(cf, [1]);
+        batch.put("states", [1], [7]);
+        batch.put(cf, [4], [40]);
+        batch.delete(cf, [4]);
+        backend.write(batch, true).unwrap();
+        assert_eq!(keys(None, Direction::Forward), vec![vec![2], vec![3]]);
+        assert_eq!(backend.get("states", &[1]).unwrap(), Some(vec![7]));
+    }
+}
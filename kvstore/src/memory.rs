@@ -0,0 +1,75 @@
+// In-memory backend
+//
+// Column families are sorted maps behind one lock, so batches are atomic
+// and iteration sees a consistent snapshot. Column families are created on
+// first write. Nothing survives the process; meant for tests.
+
+use crate::{BatchOp, Direction, KvIterator, Result, StorageBackend, WriteBatch};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+type ColumnFamily = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Backend keeping every column family in memory
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    cfs: RwLock<HashMap<String, ColumnFamily>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let cfs = self.cfs.read().unwrap();
+        Ok(cfs.get(cf).and_then(|entries| entries.get(key).cloned()))
+    }
+
+    fn write(&self, batch: WriteBatch, _sync: bool) -> Result<()> {
+        let mut cfs = self.cfs.write().unwrap();
+        for op in batch.into_ops() {
+            match op {
+                BatchOp::Put { cf, key, value } => {
+                    cfs.entry(cf).or_default().insert(key, value);
+                }
+                BatchOp::Delete { cf, key } => {
+                    if let Some(entries) = cfs.get_mut(&cf) {
+                        entries.remove(&key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn iter<'a>(&'a self, cf: &str, from: Option<&[u8]>, direction: Direction) -> Result<KvIterator<'a>> {
+        let cfs = self.cfs.read().unwrap();
+        let Some(entries) = cfs.get(cf) else {
+            return Ok(Box::new(std::iter::empty()));
+        };
+        let snapshot: Vec<(Vec<u8>, Vec<u8>)> = match (direction, from) {
+            (Direction::Forward, None) => entries.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            (Direction::Forward, Some(from)) => {
+                entries.range(from.to_vec()..).map(|(k, v)| (k.clone(), v.clone())).collect()
+            }
+            (Direction::Reverse, None) => entries.iter().rev().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            (Direction::Reverse, Some(from)) => {
+                entries.range(..=from.to_vec()).rev().map(|(k, v)| (k.clone(), v.clone())).collect()
+            }
+        };
+        Ok(Box::new(snapshot.into_iter().map(Ok)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_backend() {
+        crate::tests::check_backend(&MemoryBackend::new());
+    }
+}
@@ -0,0 +1,89 @@
+// RocksDB backend
+//
+// Column families are fixed when the database is opened; writes to any
+// other column family fail with `UnknownColumnFamily`. The default column
+// family is always available.
+
+use crate::{BackendError, BatchOp, Direction, KvIterator, Result, StorageBackend, WriteBatch};
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, WriteOptions, DB};
+use std::path::Path;
+
+impl From<rocksdb::Error> for BackendError {
+    fn from(e: rocksdb::Error) -> Self {
+        Self::Database(e.to_string())
+    }
+}
+
+/// Backend storing column families in a RocksDB database
+pub struct RocksDbBackend {
+    db: DB,
+}
+
+impl RocksDbBackend {
+    /// Open (creating if missing) the database at `path` with `cfs`
+    pub fn open(path: &Path, cfs: &[&str]) -> Result<Self> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        let descriptors = cfs.iter().map(|name| ColumnFamilyDescriptor::new(*name, Options::default()));
+        let db = DB::open_cf_descriptors(&opts, path, descriptors)?;
+        Ok(Self { db })
+    }
+
+    fn cf(&self, name: &str) -> Result<&ColumnFamily> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| BackendError::UnknownColumnFamily(name.to_string()))
+    }
+}
+
+impl StorageBackend for RocksDbBackend {
+    fn get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.get_cf(self.cf(cf)?, key)?)
+    }
+
+    fn write(&self, batch: WriteBatch, sync: bool) -> Result<()> {
+        let mut rocks_batch = rocksdb::WriteBatch::default();
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put { cf, key, value } => rocks_batch.put_cf(self.cf(cf)?, key, value),
+                BatchOp::Delete { cf, key } => rocks_batch.delete_cf(self.cf(cf)?, key),
+            }
+        }
+        let mut opts = WriteOptions::default();
+        opts.set_sync(sync);
+        self.db.write_opt(rocks_batch, &opts)?;
+        Ok(())
+    }
+
+    fn iter<'a>(&'a self, cf: &str, from: Option<&[u8]>, direction: Direction) -> Result<KvIterator<'a>> {
+        let direction = match direction {
+            Direction::Forward => rocksdb::Direction::Forward,
+            Direction::Reverse => rocksdb::Direction::Reverse,
+        };
+        let mode = match (from, direction) {
+            (Some(from), direction) => IteratorMode::From(from, direction),
+            (None, rocksdb::Direction::Forward) => IteratorMode::Start,
+            (None, rocksdb::Direction::Reverse) => IteratorMode::End,
+        };
+        let iter = self.db.iterator_cf(self.cf(cf)?, mode);
+        Ok(Box::new(iter.map(|item| {
+            let (key, value) = item?;
+            Ok((key.into_vec(), value.into_vec()))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rocksdb_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = RocksDbBackend::open(dir.path(), &["blocks", "states"]).unwrap();
+        crate::tests::check_backend(&backend);
+        assert!(matches!(backend.put("missing", b"k", b"v"), Err(BackendError::UnknownColumnFamily(_))));
+    }
+}
@@ -0,0 +1,109 @@
+// sled backend
+//
+// Each column family is a sled tree, opened on first use; the default
+// column family is the database's default tree. Batches spanning several
+// trees run as one multi-tree transaction.
+
+use crate::{BackendError, BatchOp, Direction, KvIterator, Result, StorageBackend, WriteBatch, DEFAULT_CF};
+use sled::transaction::TransactionError;
+use sled::{Db, Transactional, Tree};
+use std::collections::HashMap;
+use std::path::Path;
+
+impl From<sled::Error> for BackendError {
+    fn from(e: sled::Error) -> Self {
+        Self::Database(e.to_string())
+    }
+}
+
+/// Backend storing column families as trees of a sled database
+pub struct SledBackend {
+    db: Db,
+}
+
+impl SledBackend {
+    /// Open (creating if missing) the database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self { db: sled::open(path)? })
+    }
+
+    fn tree(&self, cf: &str) -> Result<Tree> {
+        if cf == DEFAULT_CF {
+            return Ok((*self.db).clone());
+        }
+        Ok(self.db.open_tree(cf)?)
+    }
+}
+
+impl StorageBackend for SledBackend {
+    fn get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.tree(cf)?.get(key)?.map(|value| value.to_vec()))
+    }
+
+    fn write(&self, batch: WriteBatch, sync: bool) -> Result<()> {
+        // Trees in the transaction, and each op's index into them
+        let mut trees: Vec<Tree> = Vec::new();
+        let mut indices: HashMap<String, usize> = HashMap::new();
+        let mut ops = Vec::with_capacity(batch.len());
+        for op in batch.into_ops() {
+            let cf = match &op {
+                BatchOp::Put { cf, .. } | BatchOp::Delete { cf, .. } => cf.clone(),
+            };
+            let index = match indices.get(&cf) {
+                Some(index) => *index,
+                None => {
+                    trees.push(self.tree(&cf)?);
+                    indices.insert(cf, trees.len() - 1);
+                    trees.len() - 1
+                }
+            };
+            ops.push((index, op));
+        }
+
+        trees
+            .as_slice()
+            .transaction(|views| {
+                for (index, op) in &ops {
+                    match op {
+                        BatchOp::Put { key, value, .. } => {
+                            views[*index].insert(key.as_slice(), value.as_slice())?;
+                        }
+                        BatchOp::Delete { key, .. } => {
+                            views[*index].remove(key.as_slice())?;
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .map_err(|e: TransactionError| BackendError::Database(e.to_string()))?;
+        if sync {
+            self.db.flush()?;
+        }
+        Ok(())
+    }
+
+    fn iter<'a>(&'a self, cf: &str, from: Option<&[u8]>, direction: Direction) -> Result<KvIterator<'a>> {
+        let tree = self.tree(cf)?;
+        let iter: Box<dyn Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>> = match (from, direction) {
+            (None, Direction::Forward) => Box::new(tree.iter()),
+            (Some(from), Direction::Forward) => Box::new(tree.range(from.to_vec()..)),
+            (None, Direction::Reverse) => Box::new(tree.iter().rev()),
+            (Some(from), Direction::Reverse) => Box::new(tree.range(..=from.to_vec()).rev()),
+        };
+        Ok(Box::new(iter.map(|item| {
+            let (key, value) = item?;
+            Ok((key.to_vec(), value.to_vec()))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sled_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::tests::check_backend(&SledBackend::open(dir.path()).unwrap());
+    }
+}
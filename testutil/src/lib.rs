@@ -3,10 +3,12 @@
 /// Provides:
 /// - Test data generators
 /// - Fixtures for testing
+/// - Deterministic multi-node network simulation
 /// - Byzantine fault injection utilities
 
 pub mod generators;
 pub mod fixtures;
+pub mod simulation;
 
 pub use generators::*;
 
@@ -0,0 +1,357 @@
+// Deterministic network simulation
+//
+// Runs nodes in-process over a simulated network with per-message
+// latency, random drops, partitions and per-node clock skew. Every random
+// choice comes from one seeded RNG and events are processed in
+// (time, insertion) order, so a seed always replays the same execution.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+/// Index of a node in the simulation
+pub type NodeId = usize;
+
+/// Simulated time in milliseconds
+pub type Time = u64;
+
+/// Simulation parameters
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Seed of every random choice in the run
+    pub seed: u64,
+    /// Minimum one-way message latency
+    pub min_latency: Time,
+    /// Maximum one-way message latency
+    pub max_latency: Time,
+    /// Probability that a message between two nodes is lost
+    pub drop_rate: f64,
+    /// Largest clock offset of a node from simulated time, either way
+    pub max_clock_skew: Time,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            min_latency: 10,
+            max_latency: 50,
+            drop_rate: 0.0,
+            max_clock_skew: 0,
+        }
+    }
+}
+
+/// Protocol participant driven by the simulation
+pub trait Node {
+    type Message: Clone + Debug;
+
+    /// Called once at time zero
+    fn start(&mut self, ctx: &mut Context<Self::Message>);
+
+    /// Handle a message from `from`
+    fn on_message(&mut self, from: NodeId, message: Self::Message, ctx: &mut Context<Self::Message>);
+
+    /// Handle a timer set with `Context::set_timer`
+    fn on_timer(&mut self, tag: u64, ctx: &mut Context<Self::Message>);
+}
+
+enum Effect<M> {
+    Send { to: NodeId, message: M },
+    Timer { delay: Time, tag: u64 },
+}
+
+/// A node's view of the simulation while it handles an event
+pub struct Context<M> {
+    node: NodeId,
+    node_count: usize,
+    local_time: Time,
+    effects: Vec<Effect<M>>,
+}
+
+impl<M: Clone> Context<M> {
+    /// This node's ID
+    pub fn id(&self) -> NodeId {
+        self.node
+    }
+
+    /// Number of nodes in the simulation
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// This node's clock, including its skew
+    pub fn now(&self) -> Time {
+        self.local_time
+    }
+
+    /// Send a message; messages to self arrive immediately and are never lost
+    pub fn send(&mut self, to: NodeId, message: M) {
+        self.effects.push(Effect::Send { to, message });
+    }
+
+    /// Send a message to every node, including this one
+    pub fn broadcast(&mut self, message: M) {
+        for to in 0..self.node_count {
+            self.send(to, message.clone());
+        }
+    }
+
+    /// Call `on_timer(tag)` after `delay`; timers cannot be cancelled, so
+    /// nodes ignore tags they no longer expect
+    pub fn set_timer(&mut self, delay: Time, tag: u64) {
+        self.effects.push(Effect::Timer { delay, tag });
+    }
+}
+
+enum Event<M> {
+    Deliver { from: NodeId, to: NodeId, message: M },
+    Timer { node: NodeId, tag: u64 },
+}
+
+/// Message counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimStats {
+    pub delivered: u64,
+    /// Lost to the random drop rate
+    pub dropped: u64,
+    /// Lost to a partition
+    pub partitioned: u64,
+    pub timers_fired: u64,
+}
+
+/// Discrete-event simulation of `N` nodes
+pub struct Simulation<N: Node> {
+    nodes: Vec<N>,
+    config: SimConfig,
+    rng: StdRng,
+    now: Time,
+    /// Pending events by (time, insertion sequence)
+    queue: BTreeMap<(Time, u64), Event<N::Message>>,
+    next_seq: u64,
+    /// Clock offset of each node
+    skew: Vec<i64>,
+    /// Partition group of each node; nodes talk only within a group
+    groups: Vec<usize>,
+    stats: SimStats,
+}
+
+impl<N: Node> Simulation<N> {
+    /// Create a simulation and start every node at time zero
+    pub fn new(config: SimConfig, nodes: Vec<N>) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let max_skew = config.max_clock_skew as i64;
+        let skew = (0..nodes.len()).map(|_| rng.gen_range(-max_skew..=max_skew)).collect();
+        let mut sim = Self {
+            groups: vec![0; nodes.len()],
+            nodes,
+            config,
+            rng,
+            now: 0,
+            queue: BTreeMap::new(),
+            next_seq: 0,
+            skew,
+            stats: SimStats::default(),
+        };
+        for node in 0..sim.nodes.len() {
+            let mut ctx = sim.context(node);
+            sim.nodes[node].start(&mut ctx);
+            sim.apply(node, ctx);
+        }
+        sim
+    }
+
+    /// Current simulated time
+    pub fn now(&self) -> Time {
+        self.now
+    }
+
+    /// All nodes, by ID
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+
+    /// Get a node
+    pub fn node(&self, id: NodeId) -> &N {
+        &self.nodes[id]
+    }
+
+    /// Message counters so far
+    pub fn stats(&self) -> SimStats {
+        self.stats
+    }
+
+    /// Change the latency range of messages sent from now on
+    pub fn set_latency(&mut self, min: Time, max: Time) {
+        self.config.min_latency = min;
+        self.config.max_latency = max.max(min);
+    }
+
+    /// Change the drop rate of messages sent from now on
+    pub fn set_drop_rate(&mut self, drop_rate: f64) {
+        self.config.drop_rate = drop_rate;
+    }
+
+    /// Split the network: nodes reach only nodes in the same group, and
+    /// nodes in no group are isolated. Messages already in flight across
+    /// the split are lost on arrival.
+    pub fn partition(&mut self, groups: &[&[NodeId]]) {
+        for (node, group) in self.groups.iter_mut().enumerate() {
+            *group = groups.len() + node;
+        }
+        for (index, group) in groups.iter().enumerate() {
+            for &node in *group {
+                self.groups[node] = index;
+            }
+        }
+    }
+
+    /// Reconnect every node
+    pub fn heal(&mut self) {
+        self.groups.fill(0);
+    }
+
+    /// Process the next event; returns false once no events remain
+    pub fn step(&mut self) -> bool {
+        let Some(((time, _), event)) = self.queue.pop_first() else {
+            return false;
+        };
+        self.now = time;
+
+        match event {
+            Event::Deliver { from, to, message } => {
+                if self.groups[from] != self.groups[to] {
+                    self.stats.partitioned += 1;
+                    return true;
+                }
+                self.stats.delivered += 1;
+                let mut ctx = self.context(to);
+                self.nodes[to].on_message(from, message, &mut ctx);
+                self.apply(to, ctx);
+            }
+            Event::Timer { node, tag } => {
+                self.stats.timers_fired += 1;
+                let mut ctx = self.context(node);
+                self.nodes[node].on_timer(tag, &mut ctx);
+                self.apply(node, ctx);
+            }
+        }
+        true
+    }
+
+    /// Process every event up to and including `time`
+    pub fn run_until(&mut self, time: Time) {
+        while self.queue.first_key_value().is_some_and(|(&(t, _), _)| t <= time) {
+            self.step();
+        }
+        self.now = self.now.max(time);
+    }
+
+    /// Process events until `done` holds or `deadline` passes; returns
+    /// whether `done` held
+    pub fn run_until_condition(&mut self, deadline: Time, mut done: impl FnMut(&[N]) -> bool) -> bool {
+        while !done(&self.nodes) {
+            if self.queue.first_key_value().is_none_or(|(&(t, _), _)| t > deadline) {
+                self.now = self.now.max(deadline);
+                return false;
+            }
+            self.step();
+        }
+        true
+    }
+
+    fn context(&self, node: NodeId) -> Context<N::Message> {
+        Context {
+            node,
+            node_count: self.nodes.len(),
+            local_time: self.now.saturating_add_signed(self.skew[node]),
+            effects: Vec::new(),
+        }
+    }
+
+    fn apply(&mut self, node: NodeId, ctx: Context<N::Message>) {
+        for effect in ctx.effects {
+            match effect {
+                Effect::Send { to, message } if to == node => {
+                    self.schedule(self.now, Event::Deliver { from: node, to, message });
+                }
+                Effect::Send { to, message } => {
+                    if self.rng.gen_bool(self.config.drop_rate.clamp(0.0, 1.0)) {
+                        self.stats.dropped += 1;
+                        continue;
+                    }
+                    let latency = self.rng.gen_range(self.config.min_latency..=self.config.max_latency);
+                    self.schedule(self.now + latency, Event::Deliver { from: node, to, message });
+                }
+                Effect::Timer { delay, tag } => {
+                    self.schedule(self.now + delay, Event::Timer { node, tag });
+                }
+            }
+        }
+    }
+
+    fn schedule(&mut self, time: Time, event: Event<N::Message>) {
+        self.queue.insert((time, self.next_seq), event);
+        self.next_seq += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Floods a counter: each node forwards every message it receives to
+    /// a random peer until the hop budget runs out
+    struct Relay {
+        received: Vec<(Time, NodeId, u32)>,
+    }
+
+    impl Node for Relay {
+        type Message = u32;
+
+        fn start(&mut self, ctx: &mut Context<u32>) {
+            if ctx.id() == 0 {
+                ctx.broadcast(20);
+            }
+            ctx.set_timer(5, 0);
+        }
+
+        fn on_message(&mut self, from: NodeId, hops: u32, ctx: &mut Context<u32>) {
+            self.received.push((ctx.now(), from, hops));
+            if hops > 0 {
+                ctx.send((ctx.id() + hops as usize) % ctx.node_count(), hops - 1);
+            }
+        }
+
+        fn on_timer(&mut self, _tag: u64, _ctx: &mut Context<u32>) {}
+    }
+
+    /// Messages each node received, as (local time, sender, hops)
+    type Trace = Vec<Vec<(Time, NodeId, u32)>>;
+
+    fn run(seed: u64) -> (Trace, SimStats) {
+        let config = SimConfig { seed, drop_rate: 0.1, max_clock_skew: 20, ..Default::default() };
+        let nodes = (0..4).map(|_| Relay { received: vec![] }).collect();
+        let mut sim = Simulation::new(config, nodes);
+        sim.run_until(10_000);
+        (sim.nodes().iter().map(|n| n.received.clone()).collect(), sim.stats())
+    }
+
+    #[test]
+    fn test_seed_replays_execution() {
+        let (trace, stats) = run(7);
+        assert_eq!(run(7), (trace.clone(), stats));
+        assert_ne!(run(8).0, trace);
+        assert_eq!(stats.timers_fired, 4);
+        assert!(stats.dropped > 0);
+
+        // An isolated node hears nothing from the others
+        let nodes = (0..4).map(|_| Relay { received: vec![] }).collect();
+        let mut sim = Simulation::new(SimConfig::default(), nodes);
+        sim.partition(&[&[0, 1, 2]]);
+        sim.run_until(10_000);
+        assert!(sim.node(3).received.is_empty());
+        assert!(sim.stats().partitioned > 0);
+    }
+}